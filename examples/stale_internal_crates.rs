@@ -0,0 +1,45 @@
+//! An example `report --plugin` program: internal crates must track their
+//! newest release
+//!
+//! Build it and point `report` at it:
+//!
+//! ```text
+//! cargo build --example stale_internal_crates
+//! INTERNAL_PREFIX=acme- cargo sane report --plugin target/debug/examples/stale_internal_crates
+//! ```
+//!
+//! The program reads the project analysis from stdin, flags each
+//! dependency named with the internal prefix that has an update waiting,
+//! and prints the findings on stdout. Anything it writes to stderr shows up
+//! in the report if it exits unsuccessfully.
+
+use cargo_sane::analyzer::lint::LintSeverity;
+use cargo_sane::analyzer::plugin::{Finding, PluginOutput, ProjectAnalysis};
+use std::io::Read;
+
+fn main() -> anyhow::Result<()> {
+    let prefix = std::env::var("INTERNAL_PREFIX").unwrap_or_else(|_| "acme-".to_string());
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let analysis: ProjectAnalysis = serde_json::from_str(&input)?;
+
+    let findings = analysis
+        .check
+        .dependencies
+        .iter()
+        .filter(|dep| dep.name.starts_with(&prefix) && dep.has_update())
+        .map(|dep| Finding {
+            code: "stale-internal-crate".to_string(),
+            severity: LintSeverity::Error,
+            message: format!(
+                "{} {} is behind the internal release {}",
+                dep.name,
+                dep.current_version,
+                dep.latest_version.as_ref().unwrap()
+            ),
+            dependency: Some(dep.name.clone()),
+        })
+        .collect();
+    println!("{}", serde_json::to_string(&PluginOutput { findings })?);
+    Ok(())
+}
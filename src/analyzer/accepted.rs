@@ -0,0 +1,393 @@
+//! Findings the team has reviewed and accepted
+//!
+//! Acceptances live in `.cargo-sane/accepted.toml`, one `[[accepted]]` table
+//! per decision:
+//!
+//! ```toml
+//! [[accepted]]
+//! type = "advisory"
+//! id = "RUSTSEC-2020-0071"
+//! reason = "time is only used for formatting; no local offsets"
+//! expires = "2025-01-01"
+//! accepted_by = "alice"
+//!
+//! [[accepted]]
+//! type = "conflict"
+//! package = "syn"
+//!
+//! [[accepted]]
+//! type = "build_script"
+//! package = "ring"
+//! ```
+//!
+//! Accepted findings are reported separately instead of as problems. An
+//! acceptance stops applying at the start of its `expires` date, after which
+//! the finding is reported as before, with a warning.
+
+use crate::analyzer::health::AffectedPackage;
+use crate::core::advisory::Advisory;
+use crate::utils::cache::STATE_DIR;
+use crate::utils::formatting::parse_date;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ACCEPTED_FILE: &str = "accepted.toml";
+
+/// Every acceptance recorded for a project
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AcceptedRisks {
+    #[serde(default)]
+    pub accepted: Vec<AcceptedRisk>,
+}
+
+/// One decision to live with a finding
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AcceptedRisk {
+    #[serde(flatten)]
+    pub subject: RiskSubject,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Date (YYYY-MM-DD) from which the acceptance no longer applies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accepted_by: Option<String>,
+}
+
+/// What an acceptance covers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RiskSubject {
+    /// An advisory, by its id or one of its aliases, wherever it applies
+    Advisory { id: String },
+    /// A crate present at several versions
+    Conflict { package: String },
+    /// A dependency running a build script or proc-macro, reviewed so
+    /// `health --fail-on-new-build-scripts` lets it in
+    BuildScript { package: String },
+}
+
+/// An advisory finding moved aside by an acceptance
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AcceptedFinding {
+    pub name: String,
+    pub version: Version,
+    pub advisory_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accepted_by: Option<String>,
+}
+
+impl AcceptedRisk {
+    /// The acceptance no longer applies at the Unix time `now`. An
+    /// unparsable date counts as expired, so a typo can't accept a risk
+    /// forever.
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires
+            .as_deref()
+            .is_some_and(|date| parse_date(date).is_none_or(|expires| now >= expires))
+    }
+}
+
+impl AcceptedFinding {
+    /// One report line, e.g. "time 0.1.40: RUSTSEC-2020-0071 (no local
+    /// offsets; until 2025-01-01, by alice)"
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {}: {}{}",
+            self.name,
+            self.version,
+            self.advisory_id,
+            details(&self.reason, &self.expires, &self.accepted_by)
+        )
+    }
+}
+
+impl AcceptedRisk {
+    /// One listing line, e.g. "conflict syn (pinned upstream; by alice)"
+    pub fn summary(&self) -> String {
+        format!(
+            "{}{}",
+            self.subject,
+            details(&self.reason, &self.expires, &self.accepted_by)
+        )
+    }
+}
+
+fn details(reason: &Option<String>, expires: &Option<String>, by: &Option<String>) -> String {
+    let mut parts = Vec::new();
+    if let Some(reason) = reason {
+        parts.push(reason.clone());
+    }
+    let mut terms = Vec::new();
+    if let Some(expires) = expires {
+        terms.push(format!("until {}", expires));
+    }
+    if let Some(by) = by {
+        terms.push(format!("by {}", by));
+    }
+    if !terms.is_empty() {
+        parts.push(terms.join(", "));
+    }
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", parts.join("; "))
+    }
+}
+
+impl AcceptedRisks {
+    /// The acceptances of the project in `root`; none when there's no file
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = Self::path(root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content =
+            fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).context(format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, root: &Path) -> Result<PathBuf> {
+        let path = Self::path(root);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&path, toml::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn path(root: &Path) -> PathBuf {
+        root.join(STATE_DIR).join(ACCEPTED_FILE)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accepted.is_empty()
+    }
+
+    /// Record `risk`, replacing an earlier acceptance of the same subject
+    pub fn accept(&mut self, risk: AcceptedRisk) {
+        self.accepted.retain(|r| r.subject != risk.subject);
+        self.accepted.push(risk);
+    }
+
+    /// Drop the acceptance of `subject`; false when there was none
+    pub fn remove(&mut self, subject: &RiskSubject) -> bool {
+        let before = self.accepted.len();
+        self.accepted.retain(|r| &r.subject != subject);
+        self.accepted.len() != before
+    }
+
+    /// Acceptances past their expiry date
+    pub fn expired(&self, now: u64) -> Vec<&AcceptedRisk> {
+        self.accepted.iter().filter(|r| r.is_expired(now)).collect()
+    }
+
+    /// The acceptance in force for `subject`, if any
+    pub fn find(&self, subject: &RiskSubject, now: u64) -> Option<&AcceptedRisk> {
+        self.accepted
+            .iter()
+            .find(|r| &r.subject == subject && !r.is_expired(now))
+    }
+
+    /// The acceptance in force for `advisory`, under its id or an alias
+    fn find_advisory(&self, advisory: &Advisory, now: u64) -> Option<&AcceptedRisk> {
+        std::iter::once(&advisory.id)
+            .chain(&advisory.aliases)
+            .find_map(|id| self.find(&RiskSubject::Advisory { id: id.clone() }, now))
+    }
+
+    /// Packages of duplicated crates accepted as they are
+    pub fn conflicts(&self, now: u64) -> Vec<String> {
+        self.accepted
+            .iter()
+            .filter(|r| !r.is_expired(now))
+            .filter_map(|r| match &r.subject {
+                RiskSubject::Conflict { package } => Some(package.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Packages whose build-time code was reviewed and accepted
+    pub fn build_scripts(&self, now: u64) -> Vec<String> {
+        self.accepted
+            .iter()
+            .filter(|r| !r.is_expired(now))
+            .filter_map(|r| match &r.subject {
+                RiskSubject::BuildScript { package } => Some(package.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Move accepted advisories out of `vulnerable`, dropping packages left
+    /// without any, and return them as findings
+    pub fn take_accepted(
+        &self,
+        vulnerable: &mut Vec<AffectedPackage>,
+        now: u64,
+    ) -> Vec<AcceptedFinding> {
+        let mut accepted = Vec::new();
+        for package in vulnerable.iter_mut() {
+            package.advisories.retain(|advisory| {
+                let Some(risk) = self.find_advisory(advisory, now) else {
+                    return true;
+                };
+                accepted.push(AcceptedFinding {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    advisory_id: advisory.id.clone(),
+                    reason: risk.reason.clone(),
+                    expires: risk.expires.clone(),
+                    accepted_by: risk.accepted_by.clone(),
+                });
+                false
+            });
+        }
+        vulnerable.retain(|package| !package.advisories.is_empty());
+        accepted
+    }
+}
+
+impl fmt::Display for RiskSubject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskSubject::Advisory { id } => write!(f, "advisory {}", id),
+            RiskSubject::Conflict { package } => write!(f, "conflict {}", package),
+            RiskSubject::BuildScript { package } => write!(f, "build-time code of {}", package),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::attribution::Attribution;
+    use crate::core::dependency::DependencySource;
+
+    const NOW: u64 = 1_700_000_000; // 2023-11-14
+
+    fn risk(subject: RiskSubject, expires: Option<&str>) -> AcceptedRisk {
+        AcceptedRisk {
+            subject,
+            reason: Some("reviewed".to_string()),
+            expires: expires.map(str::to_string),
+            accepted_by: None,
+        }
+    }
+
+    fn advisory(id: &str) -> RiskSubject {
+        RiskSubject::Advisory { id: id.to_string() }
+    }
+
+    #[test]
+    fn test_expiry() {
+        assert!(!risk(advisory("A"), None).is_expired(NOW));
+        assert!(!risk(advisory("A"), Some("2023-11-15")).is_expired(NOW));
+        assert!(risk(advisory("A"), Some("2023-11-14")).is_expired(NOW));
+        assert!(risk(advisory("A"), Some("next year")).is_expired(NOW));
+
+        let mut risks = AcceptedRisks::default();
+        risks.accept(risk(advisory("A"), Some("2023-01-01")));
+        risks.accept(risk(
+            RiskSubject::Conflict {
+                package: "syn".to_string(),
+            },
+            None,
+        ));
+        assert_eq!(risks.expired(NOW).len(), 1);
+        assert!(risks.find(&advisory("A"), NOW).is_none());
+        assert_eq!(risks.conflicts(NOW), vec!["syn"]);
+    }
+
+    #[test]
+    fn test_take_accepted_advisories() {
+        let package = |name: &str, ids: &[&str]| AffectedPackage {
+            name: name.to_string(),
+            version: Version::new(0, 1, 0),
+            source: DependencySource::Registry,
+            attribution: Attribution::direct(name),
+            also_via: Vec::new(),
+            owner: None,
+            advisories: ids
+                .iter()
+                .map(|id| Advisory {
+                    id: id.to_string(),
+                    package: name.to_string(),
+                    title: String::new(),
+                    severity: None,
+                    cvss: None,
+                    aliases: vec![format!("CVE-{}", id)],
+                    patched_versions: Vec::new(),
+                    informational: None,
+                    url: String::new(),
+                })
+                .collect(),
+        };
+        let mut vulnerable = vec![package("time", &["A"]), package("chrono", &["A", "B"])];
+        let mut risks = AcceptedRisks::default();
+        risks.accept(risk(advisory("CVE-A"), Some("2024-01-01")));
+        risks.accept(risk(advisory("B"), Some("2023-01-01")));
+
+        let accepted = risks.take_accepted(&mut vulnerable, NOW);
+        let accepted: Vec<(&str, &str)> = accepted
+            .iter()
+            .map(|a| (a.name.as_str(), a.advisory_id.as_str()))
+            .collect();
+        assert_eq!(accepted, vec![("time", "A"), ("chrono", "A")]);
+        assert_eq!(vulnerable.len(), 1);
+        assert_eq!(vulnerable[0].advisories[0].id, "B");
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut risks = AcceptedRisks::default();
+        risks.accept(risk(advisory("RUSTSEC-2020-0071"), Some("2025-01-01")));
+        risks.accept(risk(advisory("RUSTSEC-2020-0071"), None));
+        risks.save(dir.path()).unwrap();
+
+        let content = fs::read_to_string(AcceptedRisks::path(dir.path())).unwrap();
+        assert!(content.contains("type = \"advisory\""), "{}", content);
+        let loaded = AcceptedRisks::load(dir.path()).unwrap();
+        assert_eq!(loaded, risks);
+        assert_eq!(loaded.accepted.len(), 1);
+        assert!(loaded.accepted[0].expires.is_none());
+    }
+
+    #[test]
+    fn test_summaries() {
+        let finding = AcceptedFinding {
+            name: "time".to_string(),
+            version: Version::new(0, 1, 40),
+            advisory_id: "RUSTSEC-2020-0071".to_string(),
+            reason: Some("no local offsets".to_string()),
+            expires: Some("2025-01-01".to_string()),
+            accepted_by: Some("alice".to_string()),
+        };
+        assert_eq!(
+            finding.summary(),
+            "time 0.1.40: RUSTSEC-2020-0071 (no local offsets; until 2025-01-01, by alice)"
+        );
+
+        let mut conflict = risk(
+            RiskSubject::Conflict {
+                package: "syn".to_string(),
+            },
+            None,
+        );
+        assert_eq!(conflict.summary(), "conflict syn (reviewed)");
+        conflict.reason = None;
+        assert_eq!(conflict.summary(), "conflict syn");
+    }
+}
@@ -0,0 +1,277 @@
+//! Which direct dependency brings a package in
+//!
+//! A finding deep in the resolve graph belongs to whoever owns the direct
+//! dependency whose subtree introduces it. Paths come from Cargo.lock and are
+//! the shortest ones, one per direct dependency that reaches the package.
+
+use crate::core::lockfile::{LockedPackage, Lockfile};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Attribution {
+    /// The direct dependency whose subtree introduces the package, or the
+    /// package itself when it is direct
+    pub direct_parent: String,
+    /// Steps from the project: 1 for a direct dependency
+    pub depth: usize,
+    /// Package names from the direct parent down to the package
+    pub path: Vec<String>,
+}
+
+impl Attribution {
+    /// A direct dependency, which is its own parent
+    pub fn direct(name: &str) -> Self {
+        Self {
+            direct_parent: name.to_string(),
+            depth: 1,
+            path: vec![name.to_string()],
+        }
+    }
+}
+
+/// The resolve graph of a lockfile, with packages as nodes by index
+pub struct ResolveGraph<'a> {
+    packages: Vec<&'a LockedPackage>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl<'a> ResolveGraph<'a> {
+    pub fn new(lockfile: &'a Lockfile) -> Self {
+        let packages: Vec<&LockedPackage> = lockfile.packages.iter().collect();
+        let edges = packages
+            .iter()
+            .map(|package| {
+                lockfile
+                    .dependencies_of(package)
+                    .into_iter()
+                    .filter_map(|dep| packages.iter().position(|p| std::ptr::eq(*p, dep)))
+                    .collect()
+            })
+            .collect();
+        Self { packages, edges }
+    }
+
+    pub fn package(&self, node: usize) -> &'a LockedPackage {
+        self.packages[node]
+    }
+
+    /// The node of a locked package version
+    pub fn node(&self, name: &str, version: &Version) -> Option<usize> {
+        self.packages
+            .iter()
+            .position(|p| p.name == name && &p.version == version)
+    }
+
+    /// The direct dependencies of a local package, from its lockfile entry
+    pub fn dependencies_of_local(&self, name: &str) -> Option<&[usize]> {
+        let node = self
+            .packages
+            .iter()
+            .position(|p| p.name == name && p.source.is_none())?;
+        Some(&self.edges[node])
+    }
+
+    /// Every package reachable from `roots`, roots included, nearest first
+    pub fn reachable(&self, roots: &[usize]) -> Vec<usize> {
+        let mut seen = vec![false; self.packages.len()];
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        for &root in roots {
+            if !std::mem::replace(&mut seen[root], true) {
+                queue.push_back(root);
+            }
+        }
+
+        let mut order = Vec::new();
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+            for &next in &self.edges[node] {
+                if !std::mem::replace(&mut seen[next], true) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        order
+    }
+
+    /// How `target` is reached through each root that reaches it, nearest
+    /// first and then by name. A root target is attributed to itself alone.
+    pub fn attribute(&self, roots: &[usize], target: usize) -> Vec<Attribution> {
+        if roots.contains(&target) {
+            return vec![Attribution::direct(&self.packages[target].name)];
+        }
+
+        let mut roots = roots.to_vec();
+        roots.sort_unstable();
+        roots.dedup();
+        let mut found: Vec<Attribution> = roots
+            .into_iter()
+            .filter_map(|root| self.shortest_path(root, target))
+            .map(|nodes| {
+                let path: Vec<String> = nodes
+                    .iter()
+                    .map(|&node| self.packages[node].name.clone())
+                    .collect();
+                Attribution {
+                    direct_parent: path[0].clone(),
+                    depth: path.len(),
+                    path,
+                }
+            })
+            .collect();
+        found.sort_by(|a, b| {
+            (a.depth, &a.direct_parent, &a.path).cmp(&(b.depth, &b.direct_parent, &b.path))
+        });
+        found
+    }
+
+    /// The nodes along a shortest path from `from` to `to`, both included
+    fn shortest_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut previous: Vec<Option<usize>> = vec![None; self.packages.len()];
+        let mut seen = vec![false; self.packages.len()];
+        let mut queue = VecDeque::from([from]);
+        seen[from] = true;
+
+        while let Some(node) = queue.pop_front() {
+            if node == to {
+                let mut path = vec![to];
+                let mut current = to;
+                while let Some(before) = previous[current] {
+                    path.push(before);
+                    current = before;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &next in &self.edges[node] {
+                if !std::mem::replace(&mut seen[next], true) {
+                    previous[next] = Some(node);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A lockfile with one registry package per `(name, dependencies)` and a
+    /// local `app` depending on `direct`
+    fn lockfile(direct: &[&str], packages: &[(&str, &[&str])]) -> Lockfile {
+        let list = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| format!("\"{}\"", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut content = format!(
+            "[[package]]\nname = \"app\"\nversion = \"0.1.0\"\ndependencies = [{}]\n",
+            list(direct)
+        );
+        for (name, dependencies) in packages {
+            content.push_str(&format!(
+                "\n[[package]]\nname = \"{}\"\nversion = \"1.0.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\ndependencies = [{}]\n",
+                name,
+                list(dependencies)
+            ));
+        }
+        Lockfile::parse(PathBuf::from("Cargo.lock"), &content).unwrap()
+    }
+
+    fn node(graph: &ResolveGraph, name: &str) -> usize {
+        graph.node(name, &Version::new(1, 0, 0)).unwrap()
+    }
+
+    fn roots(graph: &ResolveGraph) -> Vec<usize> {
+        graph.dependencies_of_local("app").unwrap().to_vec()
+    }
+
+    fn parents(attributions: &[Attribution]) -> Vec<(&str, usize)> {
+        attributions
+            .iter()
+            .map(|a| (a.direct_parent.as_str(), a.depth))
+            .collect()
+    }
+
+    #[test]
+    fn test_diamond_lists_every_direct_parent() {
+        // app -> web -> http -> vuln, app -> grpc -> http
+        let lockfile = lockfile(
+            &["web", "grpc"],
+            &[
+                ("web", &["http"]),
+                ("grpc", &["http"]),
+                ("http", &["vuln"]),
+                ("vuln", &[]),
+            ],
+        );
+        let graph = ResolveGraph::new(&lockfile);
+        let found = graph.attribute(&roots(&graph), node(&graph, "vuln"));
+
+        assert_eq!(parents(&found), [("grpc", 3), ("web", 3)]);
+        assert_eq!(found[1].path, ["web", "http", "vuln"]);
+    }
+
+    #[test]
+    fn test_nearest_parent_comes_first() {
+        // app -> b -> c -> vuln, app -> a -> vuln, and c and a also meet
+        let lockfile = lockfile(
+            &["b", "a"],
+            &[
+                ("a", &["vuln", "c"]),
+                ("b", &["c"]),
+                ("c", &["vuln"]),
+                ("vuln", &[]),
+            ],
+        );
+        let graph = ResolveGraph::new(&lockfile);
+        let found = graph.attribute(&roots(&graph), node(&graph, "vuln"));
+
+        assert_eq!(parents(&found), [("a", 2), ("b", 3)]);
+        assert_eq!(found[0].path, ["a", "vuln"]);
+        assert_eq!(found[1].path, ["b", "c", "vuln"]);
+    }
+
+    #[test]
+    fn test_direct_package_is_its_own_parent() {
+        // vuln is direct and also under web
+        let lockfile = lockfile(&["web", "vuln"], &[("web", &["vuln"]), ("vuln", &[])]);
+        let graph = ResolveGraph::new(&lockfile);
+        let found = graph.attribute(&roots(&graph), node(&graph, "vuln"));
+
+        assert_eq!(found, [Attribution::direct("vuln")]);
+    }
+
+    #[test]
+    fn test_reachable_visits_diamonds_once() {
+        let lockfile = lockfile(
+            &["web", "grpc"],
+            &[
+                ("web", &["http"]),
+                ("grpc", &["http"]),
+                ("http", &["vuln"]),
+                ("vuln", &[]),
+                ("unused", &["vuln"]),
+            ],
+        );
+        let graph = ResolveGraph::new(&lockfile);
+        let names: Vec<&str> = graph
+            .reachable(&roots(&graph))
+            .into_iter()
+            .map(|node| graph.package(node).name.as_str())
+            .collect();
+
+        assert_eq!(names, ["web", "grpc", "http", "vuln"]);
+        assert!(graph
+            .attribute(&roots(&graph), node(&graph, "unused"))
+            .is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+//! Find the dependencies that run code at build time
+//!
+//! A build script runs on the machine doing the build, and a proc-macro runs
+//! inside the compiler; either can read the environment, write files and
+//! reach the network before any test runs. `cargo metadata` marks both: a
+//! `custom-build` target, or a `proc-macro` one. Every package the project
+//! depends on, however deeply, is listed with the direct dependencies that
+//! bring it in, so a review can start from the parent that added it.
+
+use crate::utils::cargo::{Metadata, MetadataPackage};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::path::Path;
+
+/// A way a package runs code at build time
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildTimeCode {
+    /// A `build.rs`, run before the package compiles
+    BuildScript,
+    /// A procedural macro, run by the compiler wherever it's used
+    ProcMacro,
+}
+
+impl BuildTimeCode {
+    pub fn label(self) -> &'static str {
+        match self {
+            BuildTimeCode::BuildScript => "build script",
+            BuildTimeCode::ProcMacro => "proc-macro",
+        }
+    }
+}
+
+/// A package in the dependency tree that runs code at build time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BuildTimePackage {
+    pub name: String,
+    pub version: Version,
+    pub code: Vec<BuildTimeCode>,
+    /// The direct dependencies it is, or is reached through
+    pub via: Vec<String>,
+}
+
+/// Every package of the dependency tree that runs code at build time
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BuildTimeSurface {
+    /// How many packages the tree holds, the project's own left out
+    pub total: usize,
+    pub packages: Vec<BuildTimePackage>,
+    /// Names of the packages a baseline didn't run build-time code from.
+    /// Filled in by `health --fail-on-new-build-scripts`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_since_baseline: Option<Vec<String>>,
+}
+
+impl BuildTimeSurface {
+    /// The packages under each direct dependency, which lists a package of
+    /// its own first when it runs build-time code itself
+    pub fn by_parent(&self) -> BTreeMap<&str, Vec<&BuildTimePackage>> {
+        let mut groups: BTreeMap<&str, Vec<&BuildTimePackage>> = BTreeMap::new();
+        for package in &self.packages {
+            for parent in &package.via {
+                groups.entry(parent).or_default().push(package);
+            }
+        }
+        for (parent, packages) in &mut groups {
+            packages.sort_by_key(|p| (p.name != *parent, p.name.as_str()));
+        }
+        groups
+    }
+
+    /// Packages running build-time code that `baseline` had none from, by
+    /// name: a new version of a crate already there isn't new
+    pub fn new_since(&self, baseline: &BuildTimeSurface) -> Vec<&BuildTimePackage> {
+        let known: HashSet<&str> = baseline.packages.iter().map(|p| p.name.as_str()).collect();
+        let mut seen = HashSet::new();
+        self.packages
+            .iter()
+            .filter(|p| !known.contains(p.name.as_str()) && seen.insert(p.name.as_str()))
+            .collect()
+    }
+}
+
+/// The build-time code in the dependency tree of the package whose manifest
+/// is `manifest_path`, or of every workspace member when the metadata
+/// doesn't cover that manifest (as with a saved metadata file)
+pub fn build_time_surface(metadata: &Metadata, manifest_path: &Path) -> BuildTimeSurface {
+    let members: HashSet<&str> = metadata
+        .workspace_members
+        .iter()
+        .map(String::as_str)
+        .collect();
+    let roots: Vec<&str> = match metadata.package_for_manifest(manifest_path) {
+        Some(package) => vec![package.id.as_str()],
+        None => members.iter().copied().collect(),
+    };
+
+    // Members a root depends on by path are passed through, so their
+    // dependencies count as direct ones too
+    let mut direct: BTreeSet<&str> = BTreeSet::new();
+    let mut visited: HashSet<&str> = roots.iter().copied().collect();
+    let mut queue: VecDeque<&str> = roots.into_iter().collect();
+    while let Some(id) = queue.pop_front() {
+        for dep in metadata
+            .node(id)
+            .map(|n| n.deps.as_slice())
+            .unwrap_or_default()
+        {
+            if !members.contains(dep.pkg.as_str()) {
+                direct.insert(&dep.pkg);
+            } else if visited.insert(&dep.pkg) {
+                queue.push_back(&dep.pkg);
+            }
+        }
+    }
+
+    let mut via: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for parent in &direct {
+        let Some(name) = metadata.package(parent).map(|p| p.name.as_str()) else {
+            continue;
+        };
+        for id in reachable(metadata, parent, &members) {
+            via.entry(id).or_default().insert(name);
+        }
+    }
+
+    let mut packages: Vec<BuildTimePackage> = via
+        .iter()
+        .filter_map(|(id, parents)| {
+            let package = metadata.package(id)?;
+            let code = build_time_code(package);
+            (!code.is_empty()).then(|| BuildTimePackage {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                code,
+                via: parents.iter().map(|p| p.to_string()).collect(),
+            })
+        })
+        .collect();
+    packages.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+    BuildTimeSurface {
+        total: via.len(),
+        packages,
+        new_since_baseline: None,
+    }
+}
+
+fn build_time_code(package: &MetadataPackage) -> Vec<BuildTimeCode> {
+    let mut code = Vec::new();
+    if package.has_build_script() {
+        code.push(BuildTimeCode::BuildScript);
+    }
+    if package.is_proc_macro() {
+        code.push(BuildTimeCode::ProcMacro);
+    }
+    code
+}
+
+/// `start` and everything below it, leaving workspace members out
+fn reachable<'a>(metadata: &'a Metadata, start: &'a str, members: &HashSet<&str>) -> Vec<&'a str> {
+    let mut visited: HashSet<&str> = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    let mut found = Vec::new();
+    while let Some(id) = queue.pop_front() {
+        found.push(id);
+        for dep in metadata
+            .node(id)
+            .map(|n| n.deps.as_slice())
+            .unwrap_or_default()
+        {
+            if !members.contains(dep.pkg.as_str()) && visited.insert(&dep.pkg) {
+                queue.push_back(&dep.pkg);
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(name, target kinds, dependencies)` packages, `app` the one member
+    fn metadata(packages: &[(&str, &[&str], &[&str])]) -> Metadata {
+        let json = serde_json::json!({
+            "packages": packages.iter().map(|(name, kinds, _)| serde_json::json!({
+                "id": name,
+                "name": name,
+                "version": "1.0.0",
+                "source": null,
+                "manifest_path": format!("/work/{}/Cargo.toml", name),
+                "targets": [{"name": name, "kind": kinds}],
+            })).collect::<Vec<_>>(),
+            "resolve": {
+                "nodes": packages.iter().map(|(name, _, deps)| serde_json::json!({
+                    "id": name,
+                    "deps": deps.iter().map(|d| serde_json::json!({"name": d, "pkg": d})).collect::<Vec<_>>(),
+                })).collect::<Vec<_>>(),
+                "root": "app"
+            },
+            "workspace_members": ["app"],
+            "workspace_root": "/work",
+            "version": 1
+        });
+        Metadata::parse(&json.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_lists_build_time_code_by_direct_parent() {
+        let metadata = metadata(&[
+            ("app", &["bin", "custom-build"], &["serde", "tokio", "ring"]),
+            ("serde", &["lib", "custom-build"], &["serde_derive"]),
+            ("serde_derive", &["proc-macro"], &["proc-macro2"]),
+            ("proc-macro2", &["lib", "custom-build"], &[]),
+            ("tokio", &["lib"], &["tokio-macros", "bytes"]),
+            ("tokio-macros", &["proc-macro"], &["proc-macro2"]),
+            ("bytes", &["lib"], &[]),
+            ("ring", &["lib", "custom-build"], &["cc"]),
+            ("cc", &["lib"], &[]),
+        ]);
+        let surface = build_time_surface(&metadata, Path::new("/nowhere/Cargo.toml"));
+
+        // The project's own build script isn't a dependency's
+        assert_eq!(surface.total, 8);
+        let listed: Vec<(&str, &[BuildTimeCode], Vec<&str>)> = surface
+            .packages
+            .iter()
+            .map(|p| {
+                (
+                    p.name.as_str(),
+                    p.code.as_slice(),
+                    p.via.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                (
+                    "proc-macro2",
+                    &[BuildTimeCode::BuildScript][..],
+                    vec!["serde", "tokio"]
+                ),
+                ("ring", &[BuildTimeCode::BuildScript][..], vec!["ring"]),
+                ("serde", &[BuildTimeCode::BuildScript][..], vec!["serde"]),
+                (
+                    "serde_derive",
+                    &[BuildTimeCode::ProcMacro][..],
+                    vec!["serde"]
+                ),
+                (
+                    "tokio-macros",
+                    &[BuildTimeCode::ProcMacro][..],
+                    vec!["tokio"]
+                ),
+            ]
+        );
+
+        let groups = surface.by_parent();
+        let serde: Vec<&str> = groups["serde"].iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(serde, ["serde", "proc-macro2", "serde_derive"]);
+    }
+
+    #[test]
+    fn test_new_since_baseline_goes_by_name() {
+        let package = |name: &str, version: &str| BuildTimePackage {
+            name: name.to_string(),
+            version: Version::parse(version).unwrap(),
+            code: vec![BuildTimeCode::BuildScript],
+            via: vec![name.to_string()],
+        };
+        let baseline = BuildTimeSurface {
+            total: 10,
+            packages: vec![package("ring", "0.16.20")],
+            new_since_baseline: None,
+        };
+        let current = BuildTimeSurface {
+            total: 12,
+            packages: vec![package("openssl-sys", "0.9.102"), package("ring", "0.17.8")],
+            new_since_baseline: None,
+        };
+        let new: Vec<&str> = current
+            .new_since(&baseline)
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(new, ["openssl-sys"]);
+    }
+}
@@ -0,0 +1,436 @@
+//! Find crates compiled more than once at the same version
+//!
+//! Duplicated versions show in Cargo.lock; these don't. Cargo compiles a
+//! package again for the host when a build script or proc-macro needs it
+//! with other settings than the rest of the build: build scripts use the
+//! `build-override` profile, and the v2 resolver gives build-dependencies
+//! features of their own. Each extra compilation costs build time nobody
+//! sees.
+//!
+//! `cargo build --unit-graph` lists every compilation, but only on nightly.
+//! Without it the resolved graph of `cargo metadata` shows the packages used
+//! both at build time and in the built code, which is where the second
+//! compilation comes from; those findings are marked unconfirmed.
+
+use crate::core::manifest::Manifest;
+use crate::utils::cargo::{self, CargoOptions, Metadata, UnitGraph};
+use crate::Result;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+
+/// Why a package is compiled more than once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildUnitCause {
+    /// Build-time and run-time uses enable different features
+    FeatureSplit,
+    /// Compiled again for the host because a proc-macro uses it
+    ProcMacroHost,
+    /// Compiled again for the host because a build script uses it
+    BuildScriptHost,
+}
+
+/// A package version cargo compiles more than once
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DuplicateBuildUnit {
+    pub name: String,
+    pub version: Version,
+    pub cause: BuildUnitCause,
+    /// How many times it is compiled, when the unit graph tells
+    pub builds: Option<usize>,
+    /// The packages and build scripts using it at build time, e.g.
+    /// "serde_derive" or "build script of ring"
+    pub host_parents: Vec<String>,
+    /// The features of each compilation, when they differ
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feature_sets: Vec<Vec<String>>,
+    /// Seen in the unit graph rather than inferred from the resolve graph
+    pub confirmed: bool,
+}
+
+impl DuplicateBuildUnit {
+    /// What would let cargo compile it once
+    pub fn suggestion(&self) -> String {
+        match self.cause {
+            BuildUnitCause::FeatureSplit => format!(
+                "enable the same features of {} wherever it is a build-dependency",
+                self.name
+            ),
+            BuildUnitCause::ProcMacroHost | BuildUnitCause::BuildScriptHost => {
+                "give [profile.*.build-override] the settings of its profile so the host and \
+                 target builds can be shared"
+                    .to_string()
+            }
+        }
+    }
+
+    /// e.g. "compiled 2× (proc-macro host build for serde_derive)"
+    pub fn describe(&self) -> String {
+        let times = match self.builds {
+            Some(builds) => format!("compiled {}×", builds),
+            None => "likely compiled twice".to_string(),
+        };
+        let parents = self.host_parents.join(", ");
+        let cause = match self.cause {
+            BuildUnitCause::FeatureSplit => {
+                let sets: Vec<String> = self
+                    .feature_sets
+                    .iter()
+                    .map(|set| format!("[{}]", set.join(", ")))
+                    .collect();
+                format!("features differ: {}", sets.join(" vs "))
+            }
+            BuildUnitCause::ProcMacroHost => format!("proc-macro host build for {}", parents),
+            BuildUnitCause::BuildScriptHost => format!("host build for {}", parents),
+        };
+        format!("{} ({})", times, cause)
+    }
+}
+
+/// The packages of `manifest`'s project compiled more than once, from the
+/// unit graph when the toolchain gives one, else from `cargo metadata`
+pub fn find_duplicate_units(
+    manifest: &Manifest,
+    options: &CargoOptions,
+) -> Result<Vec<DuplicateBuildUnit>> {
+    let metadata = cargo::metadata(&manifest.path, options)?;
+    // With a metadata file, cargo may not be run at all
+    if Metadata::file().is_none() {
+        if let Ok(graph) = cargo::unit_graph(&manifest.path, options) {
+            return Ok(from_unit_graph(&graph, &metadata));
+        }
+    }
+    Ok(from_metadata(&metadata))
+}
+
+/// Library compilations of the same package that differ in features,
+/// profile or platform
+pub fn from_unit_graph(graph: &UnitGraph, metadata: &Metadata) -> Vec<DuplicateBuildUnit> {
+    let units = &graph.units;
+    let library = |i: usize| !units[i].is_build_script() && units[i].mode != "run-custom-build";
+    // Proc-macros and build scripts run on the host, and so does everything
+    // they depend on
+    let reach = |seed: &dyn Fn(usize) -> bool| {
+        let mut reached = HashSet::new();
+        let mut queue: VecDeque<usize> = (0..units.len()).filter(|&i| seed(i)).collect();
+        while let Some(i) = queue.pop_front() {
+            if !reached.insert(i) {
+                continue;
+            }
+            queue.extend(units[i].dependencies.iter().map(|d| d.index));
+        }
+        reached
+    };
+    let macro_host = reach(&|i| units[i].is_proc_macro());
+    let script_host = reach(&|i| units[i].is_build_script());
+    let label = |i: usize| {
+        let name = metadata
+            .package(&units[i].pkg_id)
+            .map_or(units[i].pkg_id.as_str(), |p| p.name.as_str());
+        if units[i].is_build_script() {
+            format!("build script of {}", name)
+        } else {
+            name.to_string()
+        }
+    };
+
+    let mut copies: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for i in (0..units.len()).filter(|&i| library(i)) {
+        copies.entry(units[i].pkg_id.as_str()).or_default().push(i);
+    }
+    let mut found = Vec::new();
+    for (pkg_id, indices) in copies {
+        let distinct: BTreeSet<(Vec<String>, String, Option<&str>)> = indices
+            .iter()
+            .map(|&i| {
+                let mut features = units[i].features.clone();
+                features.sort();
+                (
+                    features,
+                    units[i].profile.to_string(),
+                    units[i].platform.as_deref(),
+                )
+            })
+            .collect();
+        if distinct.len() < 2 {
+            continue;
+        }
+        let Some(package) = metadata.package(pkg_id) else {
+            continue;
+        };
+
+        let feature_sets: BTreeSet<Vec<String>> = distinct.into_iter().map(|(f, _, _)| f).collect();
+        let host: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|i| macro_host.contains(i) || script_host.contains(i))
+            .collect();
+        let cause = if feature_sets.len() > 1 {
+            BuildUnitCause::FeatureSplit
+        } else if host.iter().any(|i| macro_host.contains(i)) {
+            BuildUnitCause::ProcMacroHost
+        } else {
+            BuildUnitCause::BuildScriptHost
+        };
+        let host_parents: BTreeSet<String> = (0..units.len())
+            .filter(|&j| {
+                units[j]
+                    .dependencies
+                    .iter()
+                    .any(|d| host.contains(&d.index))
+            })
+            .filter(|&j| units[j].mode != "run-custom-build")
+            .map(label)
+            .collect();
+        found.push(DuplicateBuildUnit {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            cause,
+            builds: Some(indices.len()),
+            host_parents: host_parents.into_iter().collect(),
+            feature_sets: if feature_sets.len() > 1 {
+                feature_sets.into_iter().collect()
+            } else {
+                Vec::new()
+            },
+            confirmed: true,
+        });
+    }
+    found
+}
+
+/// Packages the workspace's code uses that build scripts or proc-macros
+/// also use. Whether cargo shares the compilation can't be told from here.
+pub fn from_metadata(metadata: &Metadata) -> Vec<DuplicateBuildUnit> {
+    let Some(resolve) = &metadata.resolve else {
+        return Vec::new();
+    };
+    let is_proc_macro = |id: &str| metadata.package(id).is_some_and(|p| p.is_proc_macro());
+    let name = |id: &str| {
+        metadata
+            .package(id)
+            .map_or(id.to_string(), |p| p.name.clone())
+    };
+    let node = |id: &str| resolve.nodes.iter().find(|n| n.id == id);
+
+    // The packages compiled into the workspace's own code
+    let mut target: HashSet<String> = HashSet::new();
+    // Build-time packages, each with who needs it and whether a proc-macro
+    // is behind it
+    let mut host_seeds: Vec<(String, String, bool)> = Vec::new();
+    let mut queue: VecDeque<String> = metadata.workspace_members.iter().cloned().collect();
+    while let Some(id) = queue.pop_front() {
+        if !target.insert(id.clone()) {
+            continue;
+        }
+        let member = metadata.workspace_members.contains(&id);
+        for dep in node(&id).map(|n| n.deps.as_slice()).unwrap_or_default() {
+            let kinds: Vec<Option<&str>> = if dep.dep_kinds.is_empty() {
+                vec![None]
+            } else {
+                dep.dep_kinds.iter().map(|k| k.kind.as_deref()).collect()
+            };
+            if kinds.contains(&Some("build")) {
+                host_seeds.push((
+                    dep.pkg.clone(),
+                    format!("build script of {}", name(&id)),
+                    false,
+                ));
+            }
+            let runtime = kinds
+                .iter()
+                .any(|k| k.is_none() || (member && *k == Some("dev")));
+            if !runtime {
+                continue;
+            }
+            if is_proc_macro(&dep.pkg) {
+                for inner in node(&dep.pkg)
+                    .map(|n| n.deps.as_slice())
+                    .unwrap_or_default()
+                {
+                    host_seeds.push((inner.pkg.clone(), name(&dep.pkg), true));
+                }
+            } else {
+                queue.push_back(dep.pkg.clone());
+            }
+        }
+    }
+
+    // Everything build-time packages use is built for the host too
+    let mut host: BTreeMap<String, (BTreeSet<String>, bool)> = BTreeMap::new();
+    let mut queue: VecDeque<(String, String, bool)> = host_seeds.into();
+    while let Some((id, parent, via_macro)) = queue.pop_front() {
+        let entry = host.entry(id.clone()).or_default();
+        let seen = !entry.0.is_empty();
+        let grew = entry.0.insert(parent) | (via_macro && !entry.1);
+        entry.1 |= via_macro;
+        if seen && !grew {
+            continue;
+        }
+        for dep in node(&id).map(|n| n.deps.as_slice()).unwrap_or_default() {
+            if dep.is_dev_only() {
+                continue;
+            }
+            let build = dep
+                .dep_kinds
+                .iter()
+                .any(|k| k.kind.as_deref() == Some("build"));
+            let parent = if build {
+                format!("build script of {}", name(&id))
+            } else {
+                name(&id)
+            };
+            queue.push_back((dep.pkg.clone(), parent, via_macro));
+        }
+    }
+
+    host.into_iter()
+        .filter(|(id, _)| target.contains(id) && !metadata.workspace_members.contains(id))
+        .filter_map(|(id, (parents, via_macro))| {
+            let package = metadata.package(&id)?;
+            Some(DuplicateBuildUnit {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                cause: if via_macro {
+                    BuildUnitCause::ProcMacroHost
+                } else {
+                    BuildUnitCause::BuildScriptHost
+                },
+                builds: None,
+                host_parents: parents.into_iter().collect(),
+                feature_sets: Vec::new(),
+                confirmed: false,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, kind: &str) -> String {
+        format!(
+            r#"{{"id": "{name} 1.0.0", "name": "{name}", "version": "1.0.0", "source": null,
+                "manifest_path": "/{name}/Cargo.toml", "targets": [{{"name": "{name}", "kind": ["{kind}"]}}]}}"#
+        )
+    }
+
+    /// app uses serde, derives with serde_derive and has a build script
+    /// using cc; serde and cc both use `shared`
+    fn metadata() -> Metadata {
+        let packages = [
+            package("app", "bin"),
+            package("serde", "lib"),
+            package("serde_derive", "proc-macro"),
+            package("cc", "lib"),
+            package("shared", "lib"),
+            package("only_host", "lib"),
+        ];
+        Metadata::parse(&format!(
+            r#"{{
+                "packages": [{}],
+                "resolve": {{
+                    "nodes": [
+                        {{"id": "app 1.0.0", "deps": [
+                            {{"name": "serde", "pkg": "serde 1.0.0", "dep_kinds": [{{"kind": null}}]}},
+                            {{"name": "serde_derive", "pkg": "serde_derive 1.0.0", "dep_kinds": [{{"kind": null}}]}},
+                            {{"name": "cc", "pkg": "cc 1.0.0", "dep_kinds": [{{"kind": "build"}}]}}
+                        ]}},
+                        {{"id": "serde 1.0.0", "deps": [
+                            {{"name": "shared", "pkg": "shared 1.0.0", "dep_kinds": [{{"kind": null}}]}}
+                        ]}},
+                        {{"id": "serde_derive 1.0.0", "deps": [
+                            {{"name": "serde", "pkg": "serde 1.0.0", "dep_kinds": [{{"kind": null}}]}},
+                            {{"name": "only_host", "pkg": "only_host 1.0.0", "dep_kinds": [{{"kind": null}}]}}
+                        ]}},
+                        {{"id": "cc 1.0.0", "deps": [
+                            {{"name": "shared", "pkg": "shared 1.0.0", "dep_kinds": [{{"kind": null}}]}}
+                        ]}},
+                        {{"id": "shared 1.0.0", "deps": []}},
+                        {{"id": "only_host 1.0.0", "deps": []}}
+                    ],
+                    "root": "app 1.0.0"
+                }},
+                "workspace_members": ["app 1.0.0"],
+                "workspace_root": "/app",
+                "version": 1
+            }}"#,
+            packages.join(",")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_from_metadata() {
+        let found = from_metadata(&metadata());
+        let names: Vec<&str> = found.iter().map(|u| u.name.as_str()).collect();
+        // only_host is only built for the host, and cc isn't in the app
+        assert_eq!(names, vec!["serde", "shared"]);
+
+        assert_eq!(found[0].cause, BuildUnitCause::ProcMacroHost);
+        assert_eq!(found[0].host_parents, vec!["serde_derive"]);
+        assert!(!found[0].confirmed);
+        assert_eq!(
+            found[0].describe(),
+            "likely compiled twice (proc-macro host build for serde_derive)"
+        );
+
+        // Reached through both the proc-macro and the build script
+        assert_eq!(found[1].cause, BuildUnitCause::ProcMacroHost);
+        assert_eq!(found[1].host_parents, vec!["cc", "serde"]);
+    }
+
+    fn unit(pkg: &str, kind: &str, mode: &str, features: &[&str], deps: &[usize]) -> String {
+        let features: Vec<String> = features.iter().map(|f| format!("\"{}\"", f)).collect();
+        let deps: Vec<String> = deps
+            .iter()
+            .map(|d| format!("{{\"index\": {}}}", d))
+            .collect();
+        format!(
+            r#"{{"pkg_id": "{pkg} 1.0.0", "target": {{"name": "{pkg}", "kind": ["{kind}"]}},
+                "profile": {{"opt_level": "0"}}, "platform": null, "mode": "{mode}",
+                "features": [{}], "dependencies": [{}]}}"#,
+            features.join(", "),
+            deps.join(", ")
+        )
+    }
+
+    #[test]
+    fn test_from_unit_graph() {
+        let units = [
+            // 0: the app, using serde with derive and its build script output
+            unit("app", "bin", "build", &[], &[1, 3, 5]),
+            // 1: serde for the app
+            unit("serde", "lib", "build", &["derive", "std"], &[2]),
+            // 2: shared, the same for the app and the build script
+            unit("shared", "lib", "build", &[], &[]),
+            // 3: serde_derive, using a host serde without derive
+            unit("serde_derive", "proc-macro", "build", &[], &[4]),
+            unit("serde", "lib", "build", &["std"], &[]),
+            // 5: running the build script, 6: compiling it
+            unit("app", "custom-build", "run-custom-build", &[], &[6]),
+            unit("app", "custom-build", "build", &[], &[2]),
+        ];
+        let graph = UnitGraph::parse(&format!(
+            r#"{{"version": 1, "units": [{}], "roots": [0]}}"#,
+            units.join(",")
+        ))
+        .unwrap();
+
+        let found = from_unit_graph(&graph, &metadata());
+        assert_eq!(found.len(), 1);
+        let serde = &found[0];
+        assert_eq!(serde.name, "serde");
+        assert_eq!(serde.builds, Some(2));
+        assert_eq!(serde.cause, BuildUnitCause::FeatureSplit);
+        assert_eq!(serde.host_parents, vec!["serde_derive"]);
+        assert!(serde.confirmed);
+        assert_eq!(
+            serde.describe(),
+            "compiled 2× (features differ: [derive, std] vs [std])"
+        );
+    }
+}
@@ -0,0 +1,675 @@
+//! Dependencies referenced only behind `#[cfg]`s a default build leaves out
+//!
+//! The source scan behind `clean` counts a reference wherever it is, so
+//! `#[cfg(feature = "metrics")] use prometheus::Registry;` keeps prometheus
+//! "used" although most builds never compile that line. Here each file is
+//! parsed and every reference is tied to the `#[cfg(...)]`s of the items
+//! around it, the `cfg_attr`s it sits in and the `mod` declarations leading
+//! to its file. A dependency referenced only under conditions no feature of
+//! the package can make true, or that its default features leave false, is
+//! reported with those conditions. Conditions on anything but features,
+//! like `unix` or `test`, may well hold, so they never count against a
+//! dependency; nor does a `#[cfg]` inside a function body, which is read as
+//! part of the function. Nothing is removed on this account: enabling the
+//! feature is a build like any other.
+
+use crate::analyzer::feature_table::Reference;
+use crate::analyzer::usage::{crate_ident, extract_crate_idents, FileUsage};
+use crate::analyzer::usage_cache::UsageCache;
+use crate::core::dependency::DependencyKind;
+use crate::core::manifest::{DependencySection, Manifest};
+use anyhow::Result;
+use quote::ToTokens;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, ImplItem, Item, Meta, Token, TraitItem};
+
+/// A `cfg(...)` predicate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cfg {
+    /// `feature = "name"`
+    Feature(String),
+    /// Any other option, like `unix` or `target_os = "linux"`, as written
+    Other(String),
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+}
+
+impl Cfg {
+    /// Parse a predicate as written inside `cfg(...)`
+    pub fn parse(predicate: &str) -> Option<Self> {
+        Self::from_meta(&syn::parse_str::<Meta>(predicate).ok()?)
+    }
+
+    fn from_meta(meta: &Meta) -> Option<Self> {
+        match meta {
+            Meta::Path(path) => Some(Cfg::Other(path.to_token_stream().to_string())),
+            Meta::NameValue(option) => match &option.value {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(name),
+                    ..
+                }) if option.path.is_ident("feature") => Some(Cfg::Feature(name.value())),
+                _ => Some(Cfg::Other(option.to_token_stream().to_string())),
+            },
+            Meta::List(list) => {
+                let nested = list
+                    .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                    .ok()?
+                    .iter()
+                    .map(Cfg::from_meta)
+                    .collect::<Option<Vec<_>>>()?;
+                if list.path.is_ident("all") {
+                    Some(Cfg::All(nested))
+                } else if list.path.is_ident("any") {
+                    Some(Cfg::Any(nested))
+                } else if list.path.is_ident("not") && nested.len() == 1 {
+                    nested.into_iter().next().map(|cfg| Cfg::Not(Box::new(cfg)))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// The predicate of a `#[cfg(...)]` attribute; one that can't be read
+    /// is kept as written
+    fn of_attribute(attr: &Attribute) -> Option<Self> {
+        if !attr.path().is_ident("cfg") {
+            return None;
+        }
+        Some(
+            attr.parse_args::<Meta>()
+                .ok()
+                .and_then(|meta| Cfg::from_meta(&meta))
+                .unwrap_or_else(|| Cfg::Other(attr.meta.to_token_stream().to_string())),
+        )
+    }
+
+    /// A predicate as stored by the scan, kept as written if unreadable
+    fn stored(predicate: &str) -> Self {
+        Cfg::parse(predicate).unwrap_or_else(|| Cfg::Other(predicate.to_string()))
+    }
+
+    /// Every one of `cfgs`: the one predicate, or `all(...)` of them
+    fn all(mut cfgs: Vec<Cfg>) -> Self {
+        if cfgs.len() == 1 {
+            cfgs.remove(0)
+        } else {
+            Cfg::All(cfgs)
+        }
+    }
+
+    /// Whether the predicate holds given whether each feature is on, or
+    /// `None` when that turns on more than features
+    pub fn eval(&self, feature: &impl Fn(&str) -> Option<bool>) -> Option<bool> {
+        // all() is false as soon as one is, any() true as soon as one is
+        let fold = |cfgs: &[Cfg], decisive: bool| {
+            let mut result = Some(!decisive);
+            for cfg in cfgs {
+                match cfg.eval(feature) {
+                    Some(value) if value == decisive => return Some(decisive),
+                    Some(_) => {}
+                    None => result = None,
+                }
+            }
+            result
+        };
+        match self {
+            Cfg::Feature(name) => feature(name),
+            Cfg::Other(_) => None,
+            Cfg::All(cfgs) => fold(cfgs, false),
+            Cfg::Any(cfgs) => fold(cfgs, true),
+            Cfg::Not(cfg) => cfg.eval(feature).map(|value| !value),
+        }
+    }
+}
+
+impl fmt::Display for Cfg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |cfgs: &[Cfg]| {
+            cfgs.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Cfg::Feature(name) => write!(f, "feature = \"{}\"", name),
+            Cfg::Other(option) => write!(f, "{}", option),
+            Cfg::All(cfgs) => write!(f, "all({})", list(cfgs)),
+            Cfg::Any(cfgs) => write!(f, "any({})", list(cfgs)),
+            Cfg::Not(cfg) => write!(f, "not({})", cfg),
+        }
+    }
+}
+
+/// What in one file sits behind `#[cfg]`s, with each condition as written
+/// in `cfg(...)`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CfgScan {
+    /// Identifiers referenced only under conditions, with each of them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub gated: BTreeMap<String, BTreeSet<String>>,
+    /// `mod name;` declarations made only under conditions, with each of
+    /// them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modules: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Find what `source` references only under `#[cfg]`s. Source that doesn't
+/// parse has nothing gated: all of it counts.
+pub fn scan_cfgs(source: &str) -> CfgScan {
+    let Ok(file) = syn::parse_file(source) else {
+        return CfgScan::default();
+    };
+    let mut scanner = Scanner::default();
+    let conditions: Vec<Cfg> = file.attrs.iter().filter_map(Cfg::of_attribute).collect();
+    scanner.items(&file.items, &conditions);
+
+    let Scanner {
+        mut scan,
+        unconditional,
+        ungated_modules,
+    } = scanner;
+    scan.gated.retain(|ident, _| !unconditional.contains(ident));
+    scan.modules
+        .retain(|module, _| !ungated_modules.contains(module));
+    scan
+}
+
+#[derive(Default)]
+struct Scanner {
+    scan: CfgScan,
+    unconditional: BTreeSet<String>,
+    ungated_modules: BTreeSet<String>,
+}
+
+impl Scanner {
+    fn items(&mut self, items: &[Item], conditions: &[Cfg]) {
+        for item in items {
+            let mut item = item.clone();
+            let attrs = attrs_mut(&mut item).map(std::mem::take).unwrap_or_default();
+            let mut conditions = conditions.to_vec();
+            conditions.extend(attrs.iter().filter_map(Cfg::of_attribute));
+            self.attributes(&attrs, &conditions);
+
+            match &item {
+                Item::Mod(module) => match &module.content {
+                    Some((_, items)) => self.items(items, &conditions),
+                    None if conditions.is_empty() => {
+                        self.ungated_modules.insert(module.ident.to_string());
+                    }
+                    None => {
+                        self.scan
+                            .modules
+                            .entry(module.ident.to_string())
+                            .or_default()
+                            .insert(Cfg::all(conditions).to_string());
+                    }
+                },
+                Item::Impl(block) => {
+                    let mut header = block.clone();
+                    header.items.clear();
+                    self.record(&header, &conditions);
+                    for member in &block.items {
+                        self.member(member, impl_item_attrs(member), &conditions);
+                    }
+                }
+                Item::Trait(block) => {
+                    let mut header = block.clone();
+                    header.items.clear();
+                    self.record(&header, &conditions);
+                    for member in &block.items {
+                        self.member(member, trait_item_attrs(member), &conditions);
+                    }
+                }
+                item => self.record(item, &conditions),
+            }
+        }
+    }
+
+    /// An item of an impl or trait block, under its own `#[cfg]`s too
+    fn member(&mut self, member: &impl ToTokens, attrs: &[Attribute], conditions: &[Cfg]) {
+        let mut conditions = conditions.to_vec();
+        conditions.extend(attrs.iter().filter_map(Cfg::of_attribute));
+        self.record(member, &conditions);
+    }
+
+    /// Attributes other than `cfg`; what a `cfg_attr` applies only counts
+    /// under its predicate
+    fn attributes(&mut self, attrs: &[Attribute], conditions: &[Cfg]) {
+        for attr in attrs {
+            if attr.path().is_ident("cfg") {
+                continue;
+            }
+            let applied = attr
+                .path()
+                .is_ident("cfg_attr")
+                .then(|| {
+                    attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                        .ok()
+                })
+                .flatten();
+            match applied {
+                Some(metas) if metas.len() > 1 => {
+                    let mut metas = metas.into_iter();
+                    let mut conditions = conditions.to_vec();
+                    conditions.extend(metas.next().and_then(|meta| Cfg::from_meta(&meta)));
+                    for meta in metas {
+                        self.record(&meta, &conditions);
+                    }
+                }
+                _ => self.record(attr, conditions),
+            }
+        }
+    }
+
+    fn record(&mut self, tokens: &impl ToTokens, conditions: &[Cfg]) {
+        let idents = extract_crate_idents(&tokens.to_token_stream().to_string());
+        if conditions.is_empty() {
+            self.unconditional.extend(idents);
+            return;
+        }
+        let condition = Cfg::all(conditions.to_vec()).to_string();
+        for ident in idents {
+            self.scan
+                .gated
+                .entry(ident)
+                .or_default()
+                .insert(condition.clone());
+        }
+    }
+}
+
+fn attrs_mut(item: &mut Item) -> Option<&mut Vec<Attribute>> {
+    Some(match item {
+        Item::Const(item) => &mut item.attrs,
+        Item::Enum(item) => &mut item.attrs,
+        Item::ExternCrate(item) => &mut item.attrs,
+        Item::Fn(item) => &mut item.attrs,
+        Item::ForeignMod(item) => &mut item.attrs,
+        Item::Impl(item) => &mut item.attrs,
+        Item::Macro(item) => &mut item.attrs,
+        Item::Mod(item) => &mut item.attrs,
+        Item::Static(item) => &mut item.attrs,
+        Item::Struct(item) => &mut item.attrs,
+        Item::Trait(item) => &mut item.attrs,
+        Item::TraitAlias(item) => &mut item.attrs,
+        Item::Type(item) => &mut item.attrs,
+        Item::Union(item) => &mut item.attrs,
+        Item::Use(item) => &mut item.attrs,
+        _ => return None,
+    })
+}
+
+fn impl_item_attrs(item: &ImplItem) -> &[Attribute] {
+    match item {
+        ImplItem::Const(item) => &item.attrs,
+        ImplItem::Fn(item) => &item.attrs,
+        ImplItem::Type(item) => &item.attrs,
+        ImplItem::Macro(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+fn trait_item_attrs(item: &TraitItem) -> &[Attribute] {
+    match item {
+        TraitItem::Const(item) => &item.attrs,
+        TraitItem::Fn(item) => &item.attrs,
+        TraitItem::Type(item) => &item.attrs,
+        TraitItem::Macro(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+/// Why a dependency referenced only under conditions is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Gating {
+    /// No feature of the package can make any of the conditions true
+    Unreachable,
+    /// The default features leave every condition false
+    NonDefault,
+}
+
+/// A dependency the default build doesn't use: every reference to it sits
+/// behind a `#[cfg]` that is false there
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ConditionalUsage {
+    pub name: String,
+    pub section: DependencySection,
+    pub line: Option<usize>,
+    pub gating: Gating,
+    /// Each condition the dependency is referenced under, as written in
+    /// `cfg(...)`
+    pub conditions: Vec<String>,
+}
+
+/// The features a package defines, and those its default set turns on
+#[derive(Debug, Clone, Default)]
+pub struct FeatureSet {
+    defined: BTreeSet<String>,
+    default: BTreeSet<String>,
+}
+
+impl FeatureSet {
+    /// The `[features]` of `manifest`, plus the implicit feature of each
+    /// optional dependency no `dep:` reference hides
+    pub fn of(manifest: &Manifest) -> Self {
+        let features = &manifest.content.features;
+        let hidden: BTreeSet<&str> = features
+            .values()
+            .flatten()
+            .filter_map(|reference| match Reference::parse(reference) {
+                Reference::Dependency(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+        let implicit: BTreeSet<String> = manifest
+            .declarations()
+            .into_iter()
+            .filter(|(section, name, spec)| {
+                section.kind != DependencyKind::Dev
+                    && spec.is_optional()
+                    && !hidden.contains(name.as_str())
+            })
+            .map(|(_, name, _)| name)
+            .collect();
+
+        let mut defined: BTreeSet<String> = features.keys().cloned().collect();
+        defined.extend(implicit.iter().cloned());
+
+        let mut default = BTreeSet::new();
+        let mut pending = vec!["default".to_string()];
+        while let Some(feature) = pending.pop() {
+            if !defined.contains(&feature) || !default.insert(feature.clone()) {
+                continue;
+            }
+            for reference in features.get(&feature).into_iter().flatten() {
+                match Reference::parse(reference) {
+                    Reference::Feature(name) => pending.push(name.to_string()),
+                    // `name/feature` turns an optional dependency on
+                    Reference::DependencyFeature { name, weak: false } => {
+                        pending.push(name.to_string())
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Self { defined, default }
+    }
+
+    /// Whether `condition` can hold with some features on; `None` when
+    /// that turns on more than features
+    fn reachable(&self, condition: &Cfg) -> Option<bool> {
+        condition.eval(&|name: &str| (!self.defined.contains(name)).then_some(false))
+    }
+
+    /// Whether `condition` holds with the default features
+    fn by_default(&self, condition: &Cfg) -> Option<bool> {
+        condition.eval(&|name: &str| Some(self.default.contains(name)))
+    }
+}
+
+/// Find the dependencies of `manifest` whose every reference in `files`
+/// sits behind a condition the default build leaves false. An optional
+/// dependency is only reported when no feature can reach its code, since
+/// the default build leaves it out as well. Dependencies with no reference
+/// at all are left to [`find_unused_dependencies`].
+///
+/// [`find_unused_dependencies`]: crate::analyzer::usage::find_unused_dependencies
+pub fn find_conditionally_used(
+    manifest: &Manifest,
+    files: &[PathBuf],
+    cache: &UsageCache,
+) -> Result<Vec<ConditionalUsage>> {
+    let scans: HashMap<&Path, FileUsage> = files
+        .iter()
+        .map(|file| Ok((file.as_path(), cache.scan(file)?)))
+        .collect::<Result<_>>()?;
+
+    let mut unconditional = BTreeSet::new();
+    let mut gated: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for (file, usage) in &scans {
+        let outer = file_conditions(file, &scans);
+        let condition = |inner: Option<&str>| {
+            let mut all = outer.clone();
+            all.extend(inner.map(Cfg::stored));
+            Cfg::all(all).to_string()
+        };
+        for ident in &usage.idents {
+            if usage.cfg.gated.contains_key(ident) {
+                continue;
+            }
+            if outer.is_empty() {
+                unconditional.insert(ident.clone());
+            } else {
+                gated
+                    .entry(ident.clone())
+                    .or_default()
+                    .insert(condition(None));
+            }
+        }
+        for (ident, conditions) in &usage.cfg.gated {
+            let entry = gated.entry(ident.clone()).or_default();
+            entry.extend(conditions.iter().map(|c| condition(Some(c))));
+        }
+    }
+
+    let features = FeatureSet::of(manifest);
+    let mut found = Vec::new();
+    for (section, name, spec) in manifest.declarations() {
+        let ident = crate_ident(&name);
+        if spec.is_artifact_only() || unconditional.contains(&ident) {
+            continue;
+        }
+        let Some(conditions) = gated.get(&ident) else {
+            continue;
+        };
+        let parsed: Vec<Cfg> = conditions.iter().map(|c| Cfg::stored(c)).collect();
+        let gating = if parsed.iter().all(|c| features.reachable(c) == Some(false)) {
+            Gating::Unreachable
+        } else if !spec.is_optional()
+            && parsed.iter().all(|c| features.by_default(c) == Some(false))
+        {
+            Gating::NonDefault
+        } else {
+            continue;
+        };
+        found.push(ConditionalUsage {
+            line: manifest.location_in(&name, &section).map(|(line, _)| line),
+            name,
+            section,
+            gating,
+            conditions: conditions.iter().cloned().collect(),
+        });
+    }
+    Ok(found)
+}
+
+/// The conditions `file` is compiled under, from the `mod` declarations
+/// leading to it: `a/b.rs` and `a/b/mod.rs` are declared in `a/mod.rs`,
+/// `a.rs` or a `lib.rs`/`main.rs` beside them. Crate roots and files no
+/// scanned file declares have none.
+fn file_conditions(file: &Path, scans: &HashMap<&Path, FileUsage>) -> Vec<Cfg> {
+    let (Some(stem), Some(dir)) = (file.file_stem().and_then(|s| s.to_str()), file.parent()) else {
+        return Vec::new();
+    };
+    let (module, parent_dir) = match stem {
+        "lib" | "main" => return Vec::new(),
+        "mod" => match (dir.file_name().and_then(|s| s.to_str()), dir.parent()) {
+            (Some(module), Some(parent_dir)) => (module, parent_dir),
+            _ => return Vec::new(),
+        },
+        module => (module, dir),
+    };
+
+    let parent = ["mod.rs", "lib.rs", "main.rs"]
+        .iter()
+        .map(|name| parent_dir.join(name))
+        .chain(std::iter::once(parent_dir.with_extension("rs")))
+        .find(|candidate| candidate != file && scans.contains_key(candidate.as_path()));
+    let Some(parent) = parent else {
+        return Vec::new();
+    };
+
+    let mut conditions = file_conditions(&parent, scans);
+    if let Some(declared) = scans[parent.as_path()].cfg.modules.get(module) {
+        let mut alternatives: Vec<Cfg> = declared.iter().map(|c| Cfg::stored(c)).collect();
+        conditions.push(if alternatives.len() == 1 {
+            alternatives.remove(0)
+        } else {
+            Cfg::Any(alternatives)
+        });
+    }
+    conditions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gated(scan: &CfgScan, ident: &str) -> Vec<String> {
+        scan.gated
+            .get(ident)
+            .map(|c| c.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_cfg_parse_and_eval() {
+        let cfg =
+            Cfg::parse(r#"all(feature = "metrics", not(any(unix, feature = "std")))"#).unwrap();
+        assert_eq!(
+            cfg.to_string(),
+            r#"all(feature = "metrics", not(any(unix, feature = "std")))"#
+        );
+        assert_eq!(Cfg::parse(&cfg.to_string()), Some(cfg.clone()));
+
+        let on = |on: &'static [&'static str]| move |name: &str| Some(on.contains(&name));
+        assert_eq!(cfg.eval(&on(&[])), Some(false));
+        assert_eq!(cfg.eval(&on(&["metrics", "std"])), Some(false));
+        // unix decides
+        assert_eq!(cfg.eval(&on(&["metrics"])), None);
+        assert_eq!(
+            Cfg::parse(r#"target_os = "linux""#),
+            Some(Cfg::Other(r#"target_os = "linux""#.to_string()))
+        );
+        assert_eq!(Cfg::parse("madeup(x)"), None);
+    }
+
+    #[test]
+    fn test_scan_ties_references_to_their_cfgs() {
+        let scan = scan_cfgs(
+            r#"
+use serde::Serialize;
+
+#[cfg(feature = "metrics")]
+use prometheus::Registry;
+
+#[cfg(feature = "metrics")]
+mod stats;
+#[cfg(unix)]
+mod sys;
+#[cfg(windows)]
+mod sys;
+mod model;
+
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Serialize)]
+pub struct Point;
+
+#[cfg(feature = "metrics")]
+mod inner {
+    #[cfg(unix)]
+    fn pid() -> u32 { libc::getpid() as u32 }
+}
+
+impl Point {
+    #[cfg(feature = "trace")]
+    fn trace(&self) { tracing::info!("point"); }
+
+    fn both(&self) { serde_json::json!({}); }
+}
+
+#[cfg(feature = "trace")]
+fn more() { serde::de::IgnoredAny; }
+"#,
+        );
+        assert!(gated(&scan, "serde").is_empty());
+        assert!(gated(&scan, "serde_json").is_empty());
+        assert_eq!(gated(&scan, "prometheus"), [r#"feature = "metrics""#]);
+        assert_eq!(gated(&scan, "schemars"), [r#"feature = "schema""#]);
+        assert_eq!(gated(&scan, "libc"), [r#"all(feature = "metrics", unix)"#]);
+        assert_eq!(gated(&scan, "tracing"), [r#"feature = "trace""#]);
+
+        let modules: Vec<(&str, Vec<&str>)> = scan
+            .modules
+            .iter()
+            .map(|(m, c)| (m.as_str(), c.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(
+            modules,
+            [
+                ("stats", vec![r#"feature = "metrics""#]),
+                ("sys", vec!["unix", "windows"]),
+            ]
+        );
+
+        // A whole file under #![cfg], and one that doesn't parse
+        let scan = scan_cfgs("#![cfg(feature = \"metrics\")]\nuse prometheus::Registry;\n");
+        assert_eq!(gated(&scan, "prometheus"), [r#"feature = "metrics""#]);
+        assert_eq!(scan_cfgs("fn broken( {"), CfgScan::default());
+    }
+
+    #[test]
+    fn test_feature_set() {
+        let manifest = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            r#"[package]
+name = "demo"
+
+[features]
+default = ["std"]
+std = ["serde/std", "fast"]
+fast = []
+metrics = ["dep:prometheus"]
+
+[dependencies]
+serde = { version = "1", optional = true }
+prometheus = { version = "0.13", optional = true }
+json = { version = "1", optional = true }
+"#,
+        )
+        .unwrap();
+        let features = FeatureSet::of(&manifest);
+        let names = |set: &BTreeSet<String>| set.iter().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            names(&features.defined),
+            ["default", "fast", "json", "metrics", "serde", "std"]
+        );
+        assert_eq!(
+            names(&features.default),
+            ["default", "fast", "serde", "std"]
+        );
+
+        let cfg = |predicate: &str| Cfg::parse(predicate).unwrap();
+        assert_eq!(
+            features.reachable(&cfg(r#"feature = "prometheus""#)),
+            Some(false)
+        );
+        assert_eq!(features.reachable(&cfg(r#"feature = "metrics""#)), None);
+        assert_eq!(
+            features.by_default(&cfg(r#"feature = "metrics""#)),
+            Some(false)
+        );
+        assert_eq!(
+            features.by_default(&cfg(r#"feature = "serde""#)),
+            Some(true)
+        );
+    }
+}
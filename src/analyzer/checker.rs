@@ -1,103 +1,774 @@
 //! Check for dependency updates
 
-use crate::core::dependency::Dependency;
-use crate::core::manifest::Manifest;
+use crate::analyzer::declarations::{find_declaration_conflicts, DeclarationConflict};
+use crate::analyzer::features::{feature_usage, FeatureUsage};
+use crate::analyzer::freshness::BudgetViolation;
+use crate::analyzer::internal::InternalCrates;
+use crate::analyzer::lock_mismatch::LockMismatch;
+use crate::analyzer::ownership::OwnershipChange;
+use crate::analyzer::redundancy::Redundancy;
+use crate::analyzer::stats::DependencyStats;
+use crate::analyzer::workflows::WorkflowPin;
+use crate::analyzer::workspace::WorkspaceReport;
+use crate::core::deny::DenyPolicy;
+use crate::core::dependency::{
+    Dependency, DependencyKind, DependencySource, ForkedDependency, GitDependency, Location,
+    PathDependency, SkipCause, SkippedDependency,
+};
+use crate::core::license::{license_change, LicensePolicy};
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::{DependencySection, Manifest};
+use crate::core::policy::VersionPolicy;
+use crate::core::version::{
+    is_newer, same_release, select_target_version, Prereleases, PublishedVersion,
+};
+use crate::core::workspace::Workspace;
+use crate::utils::advisory_db::AdvisoryIndex;
+use crate::utils::cancel::is_cancelled;
+use crate::utils::cargo::Metadata;
 use crate::utils::crates_io::CratesIoClient;
+use crate::utils::progress::{HiddenProgress, Progress};
+use crate::utils::registry::{NotFound, RegistryProvider, DEFAULT_CONCURRENCY};
+use crate::utils::timings;
 use crate::Result;
-use indicatif::{ProgressBar, ProgressStyle};
+use futures::stream::{self, StreamExt};
+use schemars::JsonSchema;
 use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
 
-pub struct DependencyChecker {
-    client: CratesIoClient,
+pub struct DependencyChecker<P = CratesIoClient> {
+    provider: P,
+    concurrency: usize,
+    metadata: Option<Metadata>,
+    advisories: AdvisoryIndex,
+    policy: VersionPolicy,
+    licenses: LicensePolicy,
+    deny: DenyPolicy,
+    prereleases: bool,
+    internal: InternalCrates,
+    ignored: Vec<String>,
+    intentional_forks: Vec<String>,
+    progress: Arc<dyn Progress>,
 }
 
-impl DependencyChecker {
-    pub fn new() -> Result<Self> {
-        Ok(Self {
-            client: CratesIoClient::new()?,
+/// Everything `cargo sane check` found, ready for rendering or serialization
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CheckReport {
+    pub package: Option<String>,
+    pub manifest: PathBuf,
+    pub dependencies: Vec<Dependency>,
+    #[serde(default)]
+    pub git_dependencies: Vec<GitDependency>,
+    /// Git dependencies that look like forks of a crates.io package and
+    /// trail its newest release
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forks: Vec<ForkedDependency>,
+    /// Path dependencies that are also published, with the registry version
+    #[serde(default)]
+    pub path_dependencies: Vec<PathDependency>,
+    pub declaration_conflicts: Vec<DeclarationConflict>,
+    #[serde(default)]
+    pub features: Vec<FeatureUsage>,
+    /// Filled in by `check --redundancy`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redundancies: Vec<Redundancy>,
+    /// Filled in by `check --stats`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<DependencyStats>,
+    /// Filled in by `check --owners`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ownership_changes: Vec<OwnershipChange>,
+    /// Dependencies behind their category's freshness budget, when the
+    /// config sets budgets
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub freshness: Vec<BudgetViolation>,
+    /// Declared crates that are the project's own, and so weren't looked up
+    /// or held to any budget
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub internal: Vec<String>,
+    /// `[dependencies]` entries without an update check of their own, or
+    /// whose update a policy holds back, with the reason
+    #[serde(default)]
+    pub skipped: Vec<SkippedDependency>,
+    /// Requirements Cargo.lock no longer satisfies, filled in on every
+    /// `check` since Cargo.lock changes without Cargo.toml
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lock_mismatches: Vec<LockMismatch>,
+    /// Tools CI workflows install at a pinned version. Filled in by
+    /// `check --workflows`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workflow_pins: Vec<WorkflowPin>,
+    /// Whether `--timeout` stopped the run before every lookup was made
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool,
+    /// Dependencies not looked up before the timeout
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub unchecked: usize,
+}
+
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+impl<P: RegistryProvider> DependencyChecker<P> {
+    /// Create a checker that looks versions up through `provider`. This
+    /// can't fail: an HTTP setup that doesn't work, such as a malformed
+    /// proxy, is reported by the provider's own constructor.
+    pub fn with_provider(provider: P) -> Self {
+        Self {
+            provider,
+            concurrency: DEFAULT_CONCURRENCY,
+            metadata: None,
+            advisories: AdvisoryIndex::default(),
+            policy: VersionPolicy::default(),
+            licenses: LicensePolicy::default(),
+            deny: DenyPolicy::default(),
+            prereleases: false,
+            internal: InternalCrates::default(),
+            ignored: Vec::new(),
+            intentional_forks: Vec::new(),
+            progress: Arc::new(HiddenProgress),
+        }
+    }
+
+    /// Limit the number of registry requests in flight at once (0 keeps the default)
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        if concurrency > 0 {
+            self.concurrency = concurrency;
+        }
+        self
+    }
+
+    /// Report lookup progress through `progress` (hidden by default)
+    pub fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Skip update targets with an open advisory in `advisories`
+    pub fn with_advisories(mut self, advisories: AdvisoryIndex) -> Self {
+        self.advisories = advisories;
+        self
+    }
+
+    /// Target the blessed versions in `policy` for the crates it lists
+    pub fn with_policy(mut self, policy: VersionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Hold the new license of an update target that relicenses against
+    /// `licenses`
+    pub fn with_licenses(mut self, licenses: LicensePolicy) -> Self {
+        self.licenses = licenses;
+        self
+    }
+
+    /// Flag update targets the project's `deny.toml` bans, or whose license
+    /// it doesn't accept
+    pub fn with_deny(mut self, deny: DenyPolicy) -> Self {
+        self.deny = deny;
+        self
+    }
+
+    /// Suggest prereleases as update targets, as `--pre` does. Without it,
+    /// only a dependency already on a prerelease moves on to a newer
+    /// prerelease of the same version.
+    pub fn with_prereleases(mut self, prereleases: bool) -> Self {
+        self.prereleases = prereleases;
+        self
+    }
+
+    /// Set the crates in `internal` aside instead of looking them up (none
+    /// by default)
+    pub fn with_internal(mut self, internal: InternalCrates) -> Self {
+        self.internal = internal;
+        self
+    }
+
+    /// Leave the crates in `ignored` unchecked, as the config's
+    /// `ignore_crates` asks
+    pub fn with_ignored(mut self, ignored: Vec<String>) -> Self {
+        self.ignored = ignored;
+        self
+    }
+
+    /// Don't compare the git dependencies in `forks` with crates.io, as the
+    /// config's `intentional_forks` asks
+    pub fn with_intentional_forks(mut self, forks: Vec<String>) -> Self {
+        self.intentional_forks = forks;
+        self
+    }
+
+    /// Use `cargo metadata` output to report resolved feature sets
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Run the full check of a manifest
+    pub async fn check(&self, manifest: &Manifest) -> Result<CheckReport> {
+        // The lockfile only adds detail, so a broken one shouldn't fail the check
+        let lockfile = Lockfile::for_manifest(manifest).unwrap_or_else(|e| {
+            eprintln!("Warning: Ignoring Cargo.lock: {:#}", e);
+            None
+        });
+
+        let checked = self.check_registry_dependencies(manifest).await;
+        let git = git_dependencies(manifest, lockfile.as_ref());
+        let unchecked = checked
+            .skipped
+            .iter()
+            .filter(|s| s.reason == SkipCause::TimedOut)
+            .count();
+
+        Ok(CheckReport {
+            package: manifest.package_name().map(str::to_string),
+            manifest: manifest.path.clone(),
+            dependencies: checked.dependencies,
+            forks: self.check_forks(&git).await,
+            git_dependencies: git,
+            path_dependencies: self.check_path_dependencies(manifest).await,
+            declaration_conflicts: find_declaration_conflicts(manifest),
+            features: feature_usage(manifest, self.metadata.as_ref()),
+            redundancies: Vec::new(),
+            stats: None,
+            ownership_changes: Vec::new(),
+            freshness: Vec::new(),
+            internal: checked.internal,
+            skipped: checked.skipped,
+            lock_mismatches: Vec::new(),
+            workflow_pins: Vec::new(),
+            partial: unchecked > 0,
+            unchecked,
         })
     }
 
     /// Analyze all dependencies in a manifest
-    pub fn check_dependencies(&self, manifest: &Manifest) -> Result<Vec<Dependency>> {
-        let deps = manifest.get_dependencies();
-        let mut results = Vec::new();
+    pub async fn check_dependencies(&self, manifest: &Manifest) -> Result<Vec<Dependency>> {
+        Ok(self
+            .check_registry_dependencies(manifest)
+            .await
+            .dependencies)
+    }
 
-        if deps.is_empty() {
-            return Ok(results);
+    /// Check the registry dependencies of `manifest`, recording every one
+    /// that gets no update check, and why
+    async fn check_registry_dependencies(&self, manifest: &Manifest) -> Checked {
+        let (candidates, mut skipped) = version_candidates(manifest);
+        let mut internal = Vec::new();
+        let mut lookups = Vec::new();
+        for candidate in candidates {
+            if self.ignored.contains(&candidate.name) {
+                skipped.push(SkippedDependency::new(&candidate.name, SkipCause::Ignored));
+            } else if self.internal.contains(&candidate.name) {
+                skipped.push(SkippedDependency::new(&candidate.name, SkipCause::Internal));
+                internal.push(candidate.name);
+            } else {
+                lookups.push(candidate);
+            }
         }
 
-        // Create progress bar
-        let pb = ProgressBar::new(deps.len() as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template(
-                    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}",
-                )
-                .expect("Failed to set progress style")
-                .progress_chars("#>-"),
-        );
+        let published = if lookups.is_empty() {
+            Vec::new()
+        } else {
+            let names: Vec<&str> = lookups.iter().map(|c| c.name.as_str()).collect();
+            self.fetch_versions(&names, "Checking crates.io").await
+        };
+
+        let mut dependencies = Vec::new();
+        for (candidate, lookup) in lookups.into_iter().zip(published) {
+            match lookup {
+                Lookup::Found(versions) => {
+                    let dep = candidate.into_dependency(manifest, Some(&versions), self);
+                    skipped.extend(self.policy_hold(&dep, &versions));
+                    dependencies.push(dep);
+                }
+                Lookup::Internal => {
+                    skipped.push(SkippedDependency::new(&candidate.name, SkipCause::Internal));
+                    internal.push(candidate.name);
+                }
+                // Still a row, with nothing newer known; the skip says why
+                Lookup::Failed(error) => {
+                    skipped.push(
+                        SkippedDependency::new(&candidate.name, SkipCause::RegistryError)
+                            .with_detail(error),
+                    );
+                    dependencies.push(candidate.into_dependency(manifest, None, self));
+                }
+                Lookup::Unchecked => {
+                    skipped.push(SkippedDependency::new(&candidate.name, SkipCause::TimedOut));
+                    dependencies.push(candidate.into_dependency(manifest, None, self));
+                }
+            }
+        }
+        internal.sort();
+        internal.dedup();
+        skipped.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Checked {
+            dependencies,
+            internal,
+            skipped,
+        }
+    }
+
+    /// The versions file holding `dep` below the release it would otherwise
+    /// move to, if it does
+    fn policy_hold(
+        &self,
+        dep: &Dependency,
+        published: &[PublishedVersion],
+    ) -> Option<SkippedDependency> {
+        dep.policy.as_ref()?;
+        let prereleases = Prereleases::for_current(&dep.current_version, self.prereleases);
+        let unconstrained = select_target_version(published, &prereleases, |version| {
+            self.advisories.affecting(&dep.name, version)
+        })
+        .target?;
+        let held = dep.latest_version.as_ref().unwrap_or(&dep.current_version);
+        let within = match self.policy.track(&dep.name) {
+            Some(series) => format!("tracking {}", series),
+            None => format!("held at {}", held),
+        };
+        is_newer(&unconstrained, held).then(|| {
+            SkippedDependency::new(&dep.name, SkipCause::PolicyHeld).with_detail(format!(
+                "{}; {} is the newest release",
+                within, unconstrained
+            ))
+        })
+    }
+
+    /// Compare each path dependency's local version with the registry. Path
+    /// crates that were never published are skipped without a warning.
+    pub async fn check_path_dependencies(&self, manifest: &Manifest) -> Vec<PathDependency> {
+        let root = manifest.path.parent().unwrap_or(Path::new("."));
+        let local: Vec<(String, Manifest)> = manifest.path_dependencies();
+        let lookups = local.iter().filter_map(|(name, nested)| {
+            let package = nested.package_name()?;
+            // Whatever the registry has under that name is someone else's
+            if !nested.is_publishable() && self.internal.contains(package) {
+                return None;
+            }
+            let version = nested.package_version()?;
+            Some(async move {
+                let published = self.provider.get_published_versions(package).await.ok()?;
+                let latest =
+                    select_target_version(&published, &Prereleases::Stable, |_| Vec::new())
+                        .target?;
+                let location = manifest
+                    .location_of(name, DependencyKind::Normal)
+                    .map(|(line, column)| Location { line, column });
+                Some(PathDependency {
+                    name: name.clone(),
+                    package: package.to_string(),
+                    path: nested
+                        .path
+                        .parent()
+                        .map(|dir| dir.strip_prefix(root).unwrap_or(dir))
+                        .unwrap_or(&nested.path)
+                        .to_path_buf(),
+                    local_version: version,
+                    published_version: latest,
+                    location,
+                })
+            })
+        });
 
-        for (name, spec) in deps {
-            pb.set_message(format!("Checking {}", name));
+        stream::iter(lookups)
+            .buffered(self.concurrency)
+            .filter_map(|found| async move { found })
+            .collect()
+            .await
+    }
 
-            // Skip git and path dependencies
-            if !spec.is_crates_io() {
-                pb.inc(1);
-                continue;
+    /// Compare each locked git dependency with the crates.io package of the
+    /// same name, keeping those the registry has moved past. Whether the git
+    /// repository really forks that package is a guess.
+    pub async fn check_forks(&self, git: &[GitDependency]) -> Vec<ForkedDependency> {
+        let lookups = git.iter().filter_map(|dep| {
+            let package = dep.package.as_deref().unwrap_or(&dep.name);
+            if self
+                .intentional_forks
+                .iter()
+                .any(|f| f == &dep.name || f == package)
+                || self.internal.contains(package)
+            {
+                return None;
             }
+            let based_on = dep.locked_version.clone()?;
+            Some(async move {
+                let published = self.provider.get_published_versions(package).await.ok()?;
+                let upstream =
+                    select_target_version(&published, &Prereleases::Stable, |_| Vec::new())
+                        .target
+                        .filter(|upstream| upstream > &based_on)?;
+                let fixed = self.advisories.affecting(package, &upstream);
+                let fixed_upstream = self
+                    .advisories
+                    .affecting(package, &based_on)
+                    .into_iter()
+                    .filter(|id| !fixed.contains(id))
+                    .collect();
+                Some(ForkedDependency {
+                    name: dep.name.clone(),
+                    package: package.to_string(),
+                    url: dep.url.clone(),
+                    based_on,
+                    upstream,
+                    fixed_upstream,
+                    location: dep.location,
+                })
+            })
+        });
 
-            // Get current version
-            let version_str = match spec.version() {
-                Some(v) => v,
-                None => {
-                    pb.inc(1);
-                    continue;
-                }
-            };
+        stream::iter(lookups)
+            .buffered(self.concurrency)
+            .filter_map(|found| async move { found })
+            .collect()
+            .await
+    }
 
-            // Parse version requirement (remove ^, ~, etc)
-            let current_version = match parse_version_req(version_str) {
-                Some(v) => v,
-                None => {
-                    eprintln!(
-                        "Warning: Could not parse version '{}' for {}",
-                        version_str, name
-                    );
-                    pb.inc(1);
-                    continue;
+    /// Check every member of a workspace, looking each crate up only once no
+    /// matter how many members declare it
+    pub async fn check_workspace(&self, workspace: &Workspace) -> Result<WorkspaceReport> {
+        let mut internal_names = BTreeSet::new();
+        let member_candidates: Vec<(&Manifest, Vec<Candidate>)> = workspace
+            .members
+            .iter()
+            .map(|member| {
+                let (internal, candidates): (Vec<Candidate>, Vec<Candidate>) =
+                    version_candidates(member)
+                        .0
+                        .into_iter()
+                        .partition(|c| self.internal.contains(&c.name));
+                internal_names.extend(internal.into_iter().map(|c| c.name));
+                (member, candidates)
+            })
+            .collect();
+
+        let names: BTreeSet<&str> = member_candidates
+            .iter()
+            .flat_map(|(_, candidates)| candidates.iter().map(|c| c.name.as_str()))
+            .collect();
+        let names: Vec<&str> = names.into_iter().collect();
+        let declared: usize = member_candidates.iter().map(|(_, c)| c.len()).sum();
+        let message = format!(
+            "Fetching {} unique crates for {} members ({} declarations)",
+            names.len(),
+            member_candidates.len(),
+            declared
+        );
+        let published: HashMap<&str, Lookup> = names
+            .iter()
+            .copied()
+            .zip(self.fetch_versions(&names, &message).await)
+            .collect();
+
+        internal_names.extend(
+            published
+                .iter()
+                .filter(|(_, lookup)| matches!(lookup, Lookup::Internal))
+                .map(|(name, _)| name.to_string()),
+        );
+
+        // The lookups are shared, so what's left is per member
+        self.progress.start(
+            member_candidates.len() as u64,
+            &format!(
+                "Fetched {} unique crates for {} members",
+                names.len(),
+                member_candidates.len()
+            ),
+        );
+        let members = member_candidates
+            .iter()
+            .map(|(member, candidates)| {
+                let started = Instant::now();
+                let dependencies = candidates
+                    .iter()
+                    .filter(|candidate| !internal_names.contains(&candidate.name))
+                    .map(|candidate| {
+                        let versions = published
+                            .get(candidate.name.as_str())
+                            .and_then(Lookup::versions);
+                        candidate.clone().into_dependency(member, versions, self)
+                    })
+                    .collect();
+                let name = Workspace::member_name(member);
+                self.progress.item_done(&name, started.elapsed());
+                (name, member.path.clone(), dependencies)
+            })
+            .collect();
+        self.progress.finish();
+
+        let mut report = WorkspaceReport::rollup(workspace.root.path.clone(), members);
+        report.internal = internal_names.into_iter().collect();
+        // One `cargo metadata` of the root covers every member
+        if let Some(metadata) = &self.metadata {
+            for member in &mut report.members {
+                if let Some(manifest) = workspace.members.iter().find(|m| m.path == member.manifest)
+                {
+                    member.features = feature_usage(manifest, Some(metadata));
                 }
-            };
+            }
+        }
+        Ok(report)
+    }
+
+    /// Look up the published versions of each crate, a bounded number at a
+    /// time. Results line up with `names`; failed lookups are warned about,
+    /// apart from internal crates the registry doesn't know and lookups
+    /// `--timeout` refused.
+    async fn fetch_versions(&self, names: &[&str], message: &str) -> Vec<Lookup> {
+        let _span = timings::span("registry");
+        self.progress.start(names.len() as u64, message);
 
-            // Fetch latest version from crates.io
-            let latest_version = match self.client.get_latest_version(&name) {
-                Ok(v) => Some(v),
+        // Completion order is arbitrary, so results are slotted back by index
+        let mut fetched: Vec<Lookup> = vec![Lookup::Failed(String::new()); names.len()];
+        let mut lookups = stream::iter(names.iter().enumerate())
+            .map(|(index, name)| async move {
+                let started = Instant::now();
+                let versions = self.provider.get_published_versions(name).await;
+                (index, name, versions, started.elapsed())
+            })
+            .buffer_unordered(self.concurrency);
+
+        while let Some((index, name, versions, elapsed)) = lookups.next().await {
+            match versions {
+                Ok(v) => fetched[index] = Lookup::Found(v),
+                Err(e) if e.is::<NotFound>() && self.internal.is_unpublished_own(name) => {
+                    fetched[index] = Lookup::Internal
+                }
+                Err(e) if is_cancelled(&e) => fetched[index] = Lookup::Unchecked,
                 Err(e) => {
-                    eprintln!("Warning: Failed to fetch info for {}: {}", name, e);
-                    None
+                    self.progress
+                        .warn(&format!("Failed to fetch info for {}: {}", name, e));
+                    fetched[index] = Lookup::Failed(e.to_string());
                 }
-            };
-
-            let mut dep = Dependency::new(name.clone(), current_version, true);
-            if let Some(latest) = latest_version {
-                dep = dep.with_latest(latest);
             }
+            self.progress.item_done(name, elapsed);
+        }
+
+        self.progress.finish();
+
+        fetched
+    }
+}
+
+/// How looking a crate up went
+#[derive(Clone)]
+enum Lookup {
+    Found(Vec<PublishedVersion>),
+    /// Not on the registry, and one of the project's own crates
+    Internal,
+    /// With the error
+    Failed(String),
+    /// Refused, because the run reached its timeout
+    Unchecked,
+}
 
-            results.push(dep);
-            pb.inc(1);
+impl Lookup {
+    fn versions(&self) -> Option<&[PublishedVersion]> {
+        match self {
+            Lookup::Found(versions) => Some(versions),
+            _ => None,
         }
+    }
+}
+
+/// What checking the registry dependencies of one manifest came to
+struct Checked {
+    dependencies: Vec<Dependency>,
+    internal: Vec<String>,
+    skipped: Vec<SkippedDependency>,
+}
 
-        pb.finish_with_message("Done");
-        println!();
+/// A registry dependency that can be version-checked
+#[derive(Clone)]
+struct Candidate {
+    name: String,
+    requirement: String,
+    current_version: Version,
+    artifact: Vec<String>,
+    inherited_from: Option<PathBuf>,
+    section: DependencySection,
+}
 
-        Ok(results)
+impl Candidate {
+    /// Build the dependency, targeting the newest published version that is
+    /// neither yanked nor affected by a known advisory, and within the
+    /// blessed requirement if the versions file lists the crate
+    fn into_dependency<P>(
+        self,
+        manifest: &Manifest,
+        published: Option<&[PublishedVersion]>,
+        checker: &DependencyChecker<P>,
+    ) -> Dependency {
+        let mut dep = Dependency::new(self.name, self.current_version, true)
+            .with_kind(self.section.kind)
+            .with_requirement(&self.requirement);
+        dep.artifact = self.artifact;
+        dep.inherited_from = self.inherited_from;
+        if let Some((line, column)) = manifest.location_in(&dep.name, &self.section) {
+            dep = dep.with_location(Location { line, column });
+        }
+        dep.target = self.section.target;
+        if let Some(published) = published {
+            let advisories = |version: &Version| checker.advisories.affecting(&dep.name, version);
+            let affecting_current = advisories(&dep.current_version);
+            let prereleases = Prereleases::for_current(&dep.current_version, checker.prereleases);
+            let mut selection = match checker.policy.evaluate(
+                &dep.name,
+                &dep.current_version,
+                published,
+                &prereleases,
+                advisories,
+            ) {
+                Some((selection, policy)) => {
+                    dep.policy = Some(policy);
+                    selection
+                }
+                None => select_target_version(published, &prereleases, advisories),
+            };
+            let current = published
+                .iter()
+                .find(|p| same_release(&p.version, &dep.current_version));
+            if let Some(current) = current {
+                dep.yanked = current.yanked;
+                dep.released_at = current.created_at;
+                dep.current_size = current.crate_size;
+            }
+            // Releases the project is already past aren't worth mentioning
+            selection
+                .skipped
+                .retain(|skipped| is_newer(&skipped.version, &dep.current_version));
+            dep = dep.with_selection(selection);
+            // With no clean release to move to, the current version stays
+            // vulnerable rather than up to date
+            if !dep.has_update() {
+                dep.unpatched_advisories = affecting_current;
+            }
+            let target = published
+                .iter()
+                .find(|p| Some(&p.version) == dep.latest_version.as_ref());
+            dep.latest_published_by = target.and_then(|p| p.published_by.clone());
+            dep.latest_size = target.and_then(|p| p.crate_size);
+            // Releases without a recorded license can't be compared
+            dep.license_change = match (
+                current.and_then(|p| p.license.as_deref()),
+                target.and_then(|p| p.license.as_deref()),
+            ) {
+                (Some(from), Some(to)) => license_change(from, to, &checker.licenses),
+                _ => None,
+            };
+            dep.deny = match &dep.latest_version {
+                Some(latest) if dep.has_update() => checker.deny.violation(
+                    &dep.name,
+                    latest,
+                    target.and_then(|p| p.license.as_deref()),
+                ),
+                _ => None,
+            };
+        }
+        dep
     }
 }
 
-impl Default for DependencyChecker {
-    fn default() -> Self {
-        Self::new().expect("Failed to create DependencyChecker")
+/// The registry dependencies of a manifest that can be version-checked,
+/// with the version their requirement resolves to, and the ones that can't
+fn version_candidates(manifest: &Manifest) -> (Vec<Candidate>, Vec<SkippedDependency>) {
+    let mut candidates = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (name, spec, section) in manifest.get_dependencies() {
+        let inherited_from = spec
+            .inherits_workspace()
+            .then(|| manifest.workspace_root().map(Path::to_path_buf));
+        let spec = match inherited_from {
+            Some(_) => match manifest.resolve_inherited(&name, &spec) {
+                Some(resolved) => resolved,
+                None => {
+                    skipped.push(
+                        SkippedDependency::new(&name, SkipCause::ParseFailure).with_detail(
+                            "declared with workspace = true, but no [workspace.dependencies] entry was found",
+                        ),
+                    );
+                    continue;
+                }
+            },
+            None => spec,
+        };
+        if spec.is_git() {
+            skipped.push(SkippedDependency::new(&name, SkipCause::Git));
+            continue;
+        }
+        if spec.is_path() {
+            skipped.push(SkippedDependency::new(&name, SkipCause::Path));
+            continue;
+        }
+
+        let Some(version_str) = spec.version() else {
+            skipped.push(
+                SkippedDependency::new(&name, SkipCause::ParseFailure)
+                    .with_detail("no version requirement"),
+            );
+            continue;
+        };
+
+        // Parse version requirement (remove ^, ~, etc)
+        match parse_version_req(version_str) {
+            Some(current_version) => candidates.push(Candidate {
+                requirement: version_str.to_string(),
+                artifact: spec.artifacts().to_vec(),
+                inherited_from: inherited_from.flatten(),
+                name,
+                current_version,
+                section,
+            }),
+            None => skipped.push(
+                SkippedDependency::new(&name, SkipCause::ParseFailure)
+                    .with_detail(format!("can't read a version from '{}'", version_str)),
+            ),
+        }
     }
+
+    (candidates, skipped)
+}
+
+/// Collect the git dependencies of a manifest, with the version and commit
+/// they are locked to when a lockfile is available
+pub fn git_dependencies(manifest: &Manifest, lockfile: Option<&Lockfile>) -> Vec<GitDependency> {
+    manifest
+        .get_dependencies()
+        .into_iter()
+        .filter_map(|(name, spec, section)| {
+            let spec = manifest.resolve_inherited(&name, &spec).unwrap_or(spec);
+            let (url, reference) = spec.git()?;
+            let package = spec.package().map(str::to_string);
+            let locked = lockfile.and_then(|l| l.git_package(package.as_deref().unwrap_or(&name)));
+            let location = manifest
+                .location_in(&name, &section)
+                .map(|(line, column)| Location { line, column });
+
+            Some(GitDependency {
+                kind: section.kind,
+                source: DependencySource::Git,
+                url: url.to_string(),
+                package,
+                reference,
+                locked_version: locked.map(|p| p.version.clone()),
+                locked_commit: locked.and_then(|p| p.git_source()?.commit),
+                location,
+                target: section.target,
+                name,
+            })
+        })
+        .collect()
 }
 
 /// Parse a version requirement string and extract a concrete version
@@ -108,15 +779,18 @@ impl Default for DependencyChecker {
 ///   "^1.0.5" -> Some(1.0.5)
 ///   "~1.0.5" -> Some(1.0.5)
 ///   ">=1.0.5" -> Some(1.0.5)
-fn parse_version_req(req: &str) -> Option<Version> {
+///   ">=1.0.5, <2" -> Some(1.0.5)
+///   "=0.12.0-beta.1" -> Some(0.12.0-beta.1)
+///   "1.2.3+build.5" -> Some(1.2.3), as cargo ignores build metadata here
+pub(crate) fn parse_version_req(req: &str) -> Option<Version> {
+    // The first comparator, without the build metadata cargo ignores
+    let first = req.split(',').next().unwrap_or(req);
+    let first = first.split('+').next().unwrap_or(first);
+
     // Remove common version requirement prefixes
-    let cleaned = req
+    let cleaned = first
         .trim()
-        .trim_start_matches('^')
-        .trim_start_matches('~')
-        .trim_start_matches('=')
-        .trim_start_matches('>')
-        .trim_start_matches('<')
+        .trim_start_matches(['^', '~', '=', '>', '<'])
         .trim();
 
     // Try to parse the cleaned version directly
@@ -146,9 +820,82 @@ fn normalize_version(version: &str) -> String {
     }
 }
 
+/// Fixtures shared by the unit tests of modules that consume check reports
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::CheckReport;
+    use crate::core::dependency::Dependency;
+    use std::path::PathBuf;
+
+    /// A report for the package `demo` listing only `dependencies`
+    pub(crate) fn check_report(dependencies: Vec<Dependency>) -> CheckReport {
+        CheckReport {
+            package: Some("demo".to_string()),
+            manifest: PathBuf::from("Cargo.toml"),
+            dependencies,
+            git_dependencies: Vec::new(),
+            forks: Vec::new(),
+            path_dependencies: Vec::new(),
+            declaration_conflicts: Vec::new(),
+            features: Vec::new(),
+            redundancies: Vec::new(),
+            stats: None,
+            ownership_changes: Vec::new(),
+            freshness: Vec::new(),
+            internal: Vec::new(),
+            skipped: Vec::new(),
+            lock_mismatches: Vec::new(),
+            workflow_pins: Vec::new(),
+            partial: false,
+            unchecked: 0,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::dependency::GitReference;
+
+    #[test]
+    fn test_git_dependencies_use_lockfile() {
+        let manifest = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            r#"[dependencies]
+serde = "1.0"
+forked = { git = "https://github.com/ourorg/forked", branch = "main" }
+pinned = { git = "https://github.com/ourorg/pinned", rev = "abc123" }
+"#,
+        )
+        .unwrap();
+        let lockfile = Lockfile::parse(
+            PathBuf::from("Cargo.lock"),
+            r#"
+[[package]]
+name = "forked"
+version = "1.2.0"
+source = "git+https://github.com/ourorg/forked?branch=main#0123abcd"
+"#,
+        )
+        .unwrap();
+
+        let git = git_dependencies(&manifest, Some(&lockfile));
+        assert_eq!(git.len(), 2);
+
+        assert_eq!(git[0].name, "forked");
+        assert_eq!(git[0].reference, GitReference::Branch("main".to_string()));
+        assert_eq!(git[0].locked_version, Some(Version::new(1, 2, 0)));
+        assert_eq!(git[0].locked_commit.as_deref(), Some("0123abcd"));
+        assert_eq!(git[0].location.map(|l| l.line), Some(3));
+
+        assert_eq!(git[1].name, "pinned");
+        assert_eq!(git[1].reference, GitReference::Rev("abc123".to_string()));
+        assert_eq!(git[1].locked_version, None);
+
+        let json = serde_json::to_value(&git[0]).unwrap();
+        assert_eq!(json["source"], "git");
+        assert_eq!(json["reference"]["branch"], "main");
+    }
 
     #[test]
     fn test_normalize_version() {
@@ -166,5 +913,18 @@ mod tests {
         assert_eq!(parse_version_req("^1.0.5"), Some(Version::new(1, 0, 5)));
         assert_eq!(parse_version_req("~1.0.5"), Some(Version::new(1, 0, 5)));
         assert_eq!(parse_version_req("1.35"), Some(Version::new(1, 35, 0)));
+        assert_eq!(
+            parse_version_req(">=1.0.5, <2"),
+            Some(Version::new(1, 0, 5))
+        );
+        assert_eq!(
+            parse_version_req("=0.12.0-beta.1"),
+            Some(Version::parse("0.12.0-beta.1").unwrap())
+        );
+        assert_eq!(
+            parse_version_req("1.2.3+build.5"),
+            Some(Version::new(1, 2, 3))
+        );
+        assert_eq!(parse_version_req("0.16+1.7"), Some(Version::new(0, 16, 0)));
     }
 }
@@ -0,0 +1,218 @@
+//! Compare the checksums Cargo.lock records with the ones crates.io publishes
+//!
+//! Cargo.lock pins each registry package to the SHA-256 of its .crate file.
+//! The sparse index publishes the same checksum for every release, so the
+//! two always agree unless something between them changed: a tampered
+//! registry or mirror, a corrupted or hand-edited lockfile, or source
+//! replacement serving different code under the same version.
+
+use crate::core::lockfile::Lockfile;
+use crate::utils::checksums::PublishedChecksums;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+/// How the lockfile's crates.io packages compare with the index
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ChecksumReport {
+    /// Packages whose checksum matches the published one
+    pub verified: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mismatches: Vec<ChecksumMismatch>,
+    /// Packages from git or another registry, which the crates.io index
+    /// doesn't cover
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedChecksum>,
+    /// crates.io packages that couldn't be compared, with the reason
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unverified: Vec<SkippedChecksum>,
+}
+
+/// A package whose locked checksum isn't the one crates.io publishes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ChecksumMismatch {
+    pub name: String,
+    pub version: Version,
+    /// As recorded in Cargo.lock
+    pub locked: String,
+    /// As published in the index
+    pub published: String,
+}
+
+/// A package left out of the comparison
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SkippedChecksum {
+    pub name: String,
+    pub version: Version,
+    /// Where the package comes from, or why it couldn't be compared
+    pub reason: String,
+}
+
+impl ChecksumReport {
+    pub fn is_empty(&self) -> bool {
+        self.verified == 0
+            && self.mismatches.is_empty()
+            && self.skipped.is_empty()
+            && self.unverified.is_empty()
+    }
+}
+
+/// The `(crate, version)` of every crates.io package in `lockfile`, the
+/// ones to look up
+pub fn crates_io_packages(lockfile: &Lockfile) -> Vec<(String, Version)> {
+    lockfile
+        .packages
+        .iter()
+        .filter(|p| p.is_crates_io() && p.checksum.is_some())
+        .map(|p| (p.name.clone(), p.version.clone()))
+        .collect()
+}
+
+/// Compare every downloaded package in `lockfile` with `published`.
+/// Packages without a source are the project's own or path dependencies,
+/// with nothing downloaded to check.
+pub fn verify_checksums(lockfile: &Lockfile, published: &PublishedChecksums) -> ChecksumReport {
+    let mut report = ChecksumReport::default();
+    for package in &lockfile.packages {
+        if package.source.is_none() {
+            continue;
+        }
+        let skipped = |reason: &str| SkippedChecksum {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            reason: reason.to_string(),
+        };
+        if !package.is_crates_io() {
+            let kind = if package.git_source().is_some() {
+                "git"
+            } else {
+                "another registry"
+            };
+            report.skipped.push(skipped(kind));
+            continue;
+        }
+        let Some(locked) = &package.checksum else {
+            report
+                .unverified
+                .push(skipped("Cargo.lock records no checksum"));
+            continue;
+        };
+        let key = (package.name.clone(), package.version.clone());
+        match published.checksums.get(&key) {
+            Some(cksum) if cksum.eq_ignore_ascii_case(locked) => report.verified += 1,
+            Some(cksum) => report.mismatches.push(ChecksumMismatch {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                locked: locked.clone(),
+                published: cksum.clone(),
+            }),
+            None => {
+                let reason = match published.failed.get(&package.name) {
+                    Some(error) => format!("index lookup failed: {}", error),
+                    None => "the index lists no checksum for this version".to_string(),
+                };
+                report.unverified.push(skipped(&reason));
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const LOCK: &str = r#"version = 3
+
+[[package]]
+name = "demo"
+version = "0.1.0"
+dependencies = ["serde", "rand", "fork", "private"]
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "aaaa"
+
+[[package]]
+name = "rand"
+version = "0.8.5"
+source = "sparse+https://index.crates.io/"
+checksum = "bbbb"
+
+[[package]]
+name = "fork"
+version = "1.0.0"
+source = "git+https://github.com/me/fork#0123456789abcdef"
+
+[[package]]
+name = "private"
+version = "2.0.0"
+source = "sparse+https://registry.example.com/index/"
+checksum = "cccc"
+"#;
+
+    #[test]
+    fn test_verify_checksums() {
+        let lockfile = Lockfile::parse(PathBuf::from("Cargo.lock"), LOCK).unwrap();
+        assert_eq!(
+            crates_io_packages(&lockfile),
+            vec![
+                ("serde".to_string(), Version::new(1, 0, 200)),
+                ("rand".to_string(), Version::new(0, 8, 5)),
+            ]
+        );
+
+        let mut published = PublishedChecksums::default();
+        published.checksums.insert(
+            ("serde".to_string(), Version::new(1, 0, 200)),
+            "AAAA".to_string(),
+        );
+        published.checksums.insert(
+            ("rand".to_string(), Version::new(0, 8, 5)),
+            "ffff".to_string(),
+        );
+        let report = verify_checksums(&lockfile, &published);
+
+        assert_eq!(report.verified, 1);
+        assert_eq!(
+            report.mismatches,
+            vec![ChecksumMismatch {
+                name: "rand".to_string(),
+                version: Version::new(0, 8, 5),
+                locked: "bbbb".to_string(),
+                published: "ffff".to_string(),
+            }]
+        );
+        let skipped: Vec<(&str, &str)> = report
+            .skipped
+            .iter()
+            .map(|s| (s.name.as_str(), s.reason.as_str()))
+            .collect();
+        assert_eq!(
+            skipped,
+            vec![("fork", "git"), ("private", "another registry")]
+        );
+        assert!(report.unverified.is_empty());
+
+        published.checksums.clear();
+        published
+            .failed
+            .insert("serde".to_string(), "timed out".to_string());
+        let report = verify_checksums(&lockfile, &published);
+        let reasons: Vec<&str> = report
+            .unverified
+            .iter()
+            .map(|s| s.reason.as_str())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "index lookup failed: timed out",
+                "the index lists no checksum for this version"
+            ]
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! Where two projects' dependencies diverge, for `cargo sane compare`
+//!
+//! Related services drift apart one update at a time. Both sides' crates.io
+//! dependencies are aligned by package name, whatever they're renamed to,
+//! and each shared crate is compared by version (Cargo.lock's where there
+//! is one, else the requirement's lower bound) and by the features it's
+//! built with. A divergent version gets a release both sides could move
+//! to: the newest one that both requirements accept, else the newer of the
+//! two versions in use.
+
+use crate::analyzer::checker::parse_version_req;
+use crate::analyzer::intake::locked_version;
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::core::version::{select_target_version, Prereleases, PublishedVersion};
+use crate::utils::registry::RegistryProvider;
+use futures::stream::{self, StreamExt};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// How one project declares a crate, its sections taken together
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Declared {
+    /// The requirement of its first declaration
+    pub requirement: String,
+    /// The version Cargo.lock resolves, else the requirement's lower bound
+    pub version: Option<Version>,
+    /// The features it's built with, `default` included unless every
+    /// declaration turns default features off
+    pub features: BTreeSet<String>,
+}
+
+/// A shared crate the two projects use at different versions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionDivergence {
+    pub name: String,
+    pub ours: Declared,
+    pub theirs: Declared,
+    /// The release to converge on, once suggested
+    pub target: Option<Convergence>,
+}
+
+/// A release both projects could move to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Convergence {
+    pub version: Version,
+    pub basis: ConvergenceBasis,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConvergenceBasis {
+    /// The newest release both requirements accept, so neither manifest
+    /// has to change
+    BothRequirements,
+    /// The newer of the two versions in use, for when no release fits both
+    /// requirements or the releases weren't looked up
+    Newest,
+}
+
+/// A shared crate the two projects build with different features
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureDivergence {
+    pub name: String,
+    pub only_ours: Vec<String>,
+    pub only_theirs: Vec<String>,
+}
+
+/// Everything `cargo sane compare` found
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comparison {
+    /// Package name of each side, else its directory
+    pub ours: String,
+    pub theirs: String,
+    /// How many crates both sides depend on
+    pub shared: usize,
+    pub versions: Vec<VersionDivergence>,
+    pub features: Vec<FeatureDivergence>,
+    pub only_ours: Vec<String>,
+    pub only_theirs: Vec<String>,
+}
+
+impl Comparison {
+    /// Whether the two projects agree on everything compared
+    pub fn is_converged(&self) -> bool {
+        self.versions.is_empty()
+            && self.features.is_empty()
+            && self.only_ours.is_empty()
+            && self.only_theirs.is_empty()
+    }
+}
+
+/// The crates.io dependencies of `manifest` by package name
+pub fn declared(manifest: &Manifest, lockfile: Option<&Lockfile>) -> BTreeMap<String, Declared> {
+    let mut declared: BTreeMap<String, Declared> = BTreeMap::new();
+    let mut defaults: BTreeSet<String> = BTreeSet::new();
+    for (_, key, spec) in manifest.declarations() {
+        if !spec.is_crates_io() {
+            continue;
+        }
+        let name = spec.package().map_or(key, str::to_string);
+        if spec.default_features() {
+            defaults.insert(name.clone());
+        }
+        let entry = declared.entry(name.clone()).or_insert_with(|| {
+            let requirement = spec.version().unwrap_or("*").to_string();
+            Declared {
+                version: locked_version(lockfile, &name, &requirement)
+                    .or_else(|| parse_version_req(&requirement)),
+                requirement,
+                features: BTreeSet::new(),
+            }
+        });
+        entry.features.extend(spec.features().iter().cloned());
+    }
+    for name in defaults {
+        if let Some(entry) = declared.get_mut(&name) {
+            entry.features.insert("default".to_string());
+        }
+    }
+    declared
+}
+
+/// Align the dependencies of `ours` and `theirs`. No convergence targets
+/// are set; [`suggest_targets`] or [`newest_targets`] fill them in.
+pub fn compare(
+    ours: (&Manifest, Option<&Lockfile>),
+    theirs: (&Manifest, Option<&Lockfile>),
+) -> Comparison {
+    let our_crates = declared(ours.0, ours.1);
+    let their_crates = declared(theirs.0, theirs.1);
+
+    let mut comparison = Comparison {
+        ours: label(ours.0),
+        theirs: label(theirs.0),
+        shared: 0,
+        versions: Vec::new(),
+        features: Vec::new(),
+        only_ours: our_crates
+            .keys()
+            .filter(|name| !their_crates.contains_key(*name))
+            .cloned()
+            .collect(),
+        only_theirs: their_crates
+            .keys()
+            .filter(|name| !our_crates.contains_key(*name))
+            .cloned()
+            .collect(),
+    };
+    for (name, ours) in &our_crates {
+        let Some(theirs) = their_crates.get(name) else {
+            continue;
+        };
+        comparison.shared += 1;
+
+        // Without a version on one side, only the requirements can differ
+        let diverges = match (&ours.version, &theirs.version) {
+            (Some(a), Some(b)) => a != b,
+            _ => ours.requirement != theirs.requirement,
+        };
+        if diverges {
+            comparison.versions.push(VersionDivergence {
+                name: name.clone(),
+                ours: ours.clone(),
+                theirs: theirs.clone(),
+                target: None,
+            });
+        }
+        if ours.features != theirs.features {
+            comparison.features.push(FeatureDivergence {
+                name: name.clone(),
+                only_ours: ours
+                    .features
+                    .difference(&theirs.features)
+                    .cloned()
+                    .collect(),
+                only_theirs: theirs
+                    .features
+                    .difference(&ours.features)
+                    .cloned()
+                    .collect(),
+            });
+        }
+    }
+    comparison
+}
+
+/// The release to converge on from `published`: the newest clean one both
+/// requirements accept, else the newer version in use
+pub fn convergence_target(
+    ours: &Declared,
+    theirs: &Declared,
+    published: &[PublishedVersion],
+) -> Option<Convergence> {
+    let requirements: Option<Vec<VersionReq>> = [&ours.requirement, &theirs.requirement]
+        .into_iter()
+        .map(|requirement| VersionReq::parse(requirement).ok())
+        .collect();
+    if let Some(requirements) = requirements {
+        let within: Vec<PublishedVersion> = published
+            .iter()
+            .filter(|p| requirements.iter().all(|req| req.matches(&p.version)))
+            .cloned()
+            .collect();
+        if let Some(version) =
+            select_target_version(&within, &Prereleases::Stable, |_| Vec::new()).target
+        {
+            return Some(Convergence {
+                version,
+                basis: ConvergenceBasis::BothRequirements,
+            });
+        }
+    }
+    newest(ours, theirs)
+}
+
+fn newest(ours: &Declared, theirs: &Declared) -> Option<Convergence> {
+    let version = ours.version.iter().chain(&theirs.version).max()?.clone();
+    Some(Convergence {
+        version,
+        basis: ConvergenceBasis::Newest,
+    })
+}
+
+/// Set every divergent crate's target from its releases on `provider`. A
+/// crate whose lookup fails gets the newer version in use.
+pub async fn suggest_targets<P: RegistryProvider>(
+    comparison: &mut Comparison,
+    provider: &P,
+    concurrency: usize,
+) {
+    let published: Vec<Vec<PublishedVersion>> = stream::iter(&comparison.versions)
+        .map(|divergence| async move {
+            provider
+                .get_published_versions(&divergence.name)
+                .await
+                .unwrap_or_default()
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+    for (divergence, published) in comparison.versions.iter_mut().zip(published) {
+        divergence.target = convergence_target(&divergence.ours, &divergence.theirs, &published);
+    }
+}
+
+/// Set every divergent crate's target to the newer version in use, without
+/// looking releases up
+pub fn newest_targets(comparison: &mut Comparison) {
+    for divergence in &mut comparison.versions {
+        divergence.target = newest(&divergence.ours, &divergence.theirs);
+    }
+}
+
+fn label(manifest: &Manifest) -> String {
+    match manifest.package_name() {
+        Some(name) => name.to_string(),
+        None => manifest
+            .path
+            .parent()
+            .and_then(Path::file_name)
+            .map_or_else(
+                || manifest.path.display().to_string(),
+                |dir| dir.to_string_lossy().into_owned(),
+            ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn manifest(name: &str, dependencies: &str) -> Manifest {
+        Manifest::parse(
+            PathBuf::from(format!("{}/Cargo.toml", name)),
+            &format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+                name, dependencies
+            ),
+        )
+        .unwrap()
+    }
+
+    fn published(versions: &[&str]) -> Vec<PublishedVersion> {
+        versions
+            .iter()
+            .map(|v| PublishedVersion {
+                version: Version::parse(v).unwrap(),
+                yanked: false,
+                created_at: None,
+                published_by: None,
+                license: None,
+                crate_size: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_aligns_by_package_name() {
+        let ours = manifest(
+            "api",
+            "serde = { version = \"1.0.190\", features = [\"derive\"] }\n\
+             tokio = { version = \"1\", default-features = false }\nanyhow = \"1\"\n",
+        );
+        let theirs = manifest(
+            "worker",
+            "serde = { version = \"1.0.200\", features = [\"derive\"] }\n\
+             rt = { package = \"tokio\", version = \"1\" }\nthiserror = \"1\"\n",
+        );
+
+        let comparison = compare((&ours, None), (&theirs, None));
+        assert_eq!(
+            (comparison.ours.as_str(), comparison.theirs.as_str()),
+            ("api", "worker")
+        );
+        assert_eq!(comparison.shared, 2);
+        let divergent: Vec<&str> = comparison
+            .versions
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect();
+        assert_eq!(divergent, ["serde"]);
+        assert_eq!(
+            comparison.features,
+            [FeatureDivergence {
+                name: "tokio".to_string(),
+                only_ours: Vec::new(),
+                only_theirs: vec!["default".to_string()],
+            }]
+        );
+        assert_eq!(comparison.only_ours, ["anyhow"]);
+        assert_eq!(comparison.only_theirs, ["thiserror"]);
+    }
+
+    #[test]
+    fn test_convergence_target() {
+        let side = |requirement: &str, version: &str| Declared {
+            requirement: requirement.to_string(),
+            version: Version::parse(version).ok(),
+            features: BTreeSet::new(),
+        };
+        let releases = published(&["1.0.190", "1.0.200", "1.0.210", "2.0.0"]);
+
+        // Both requirements take 1.0.210
+        let target = convergence_target(
+            &side("1.0.190", "1.0.190"),
+            &side("1.0.200", "1.0.200"),
+            &releases,
+        );
+        assert_eq!(
+            target,
+            Some(Convergence {
+                version: Version::new(1, 0, 210),
+                basis: ConvergenceBasis::BothRequirements,
+            })
+        );
+
+        // No release fits both, so the newer version in use is suggested
+        let target = convergence_target(&side("1", "1.0.210"), &side("2", "2.0.0"), &releases);
+        assert_eq!(
+            target,
+            Some(Convergence {
+                version: Version::new(2, 0, 0),
+                basis: ConvergenceBasis::Newest,
+            })
+        );
+    }
+}
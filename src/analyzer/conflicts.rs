@@ -1 +1,710 @@
 //! Detect and resolve version conflicts
+//!
+//! A conflict is a crate that ends up in the dependency graph at more than one
+//! version. They are found with `cargo tree --duplicates`, which lists every
+//! duplicated package followed by the packages that depend on it, or in the
+//! resolved graph of a `--metadata-file` when cargo may not be run.
+//!
+//! The duplicated versions can be run through the advisory scan. A conflict
+//! where one version is vulnerable and another isn't is security-relevant:
+//! converging on the clean version removes the vulnerability.
+//!
+//! Packages are identified by name and version whatever registry they came
+//! from, so a mirror set up with `[source.crates-io] replace-with` doesn't
+//! split one crate in two. The same version coming from two sources is
+//! reported apart, as a [`SourceSplit`]: it usually means a migration left
+//! some dependents on the old registry, and two copies of one release can
+//! differ.
+
+use crate::analyzer::health::AffectedPackage;
+use crate::core::manifest::Manifest;
+use crate::core::version::is_compatible;
+use crate::utils::cargo::{self, CargoOptions, Metadata};
+use crate::Result;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How crates.io is shown among sources
+pub const CRATES_IO: &str = "crates.io";
+
+/// Longest `cargo tree` line looked at. Package entries are a name, a
+/// version and a source path or URL; anything longer is skipped unread.
+const MAX_TREE_LINE: usize = 8 * 1024;
+
+/// Index URLs of crates.io, as they appear in source ids
+const CRATES_IO_INDEXES: &[&str] = &[
+    "https://github.com/rust-lang/crates.io-index",
+    "https://index.crates.io/",
+];
+
+/// A crate present at several versions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Conflict {
+    pub name: String,
+    /// Every version in the graph, lowest first
+    pub versions: Vec<ConflictVersion>,
+    /// Some versions have advisories against them and others don't
+    #[serde(default)]
+    pub security_relevant: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ConflictVersion {
+    pub version: Version,
+    /// Packages depending directly on this version, e.g. "serde_derive v1.0.100"
+    pub dependents: Vec<String>,
+    /// Ids of the vulnerability advisories affecting this version
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub advisories: Vec<String>,
+    /// Where this version came from: "crates.io", a registry index URL, a
+    /// git repository or "path"
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+/// One version of a crate resolved from more than one source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SourceSplit {
+    pub name: String,
+    pub version: Version,
+    pub sources: Vec<String>,
+    /// Packages depending on any of the copies
+    pub dependents: Vec<String>,
+}
+
+/// All version conflicts of a project
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ConflictReport {
+    pub conflicts: Vec<Conflict>,
+    #[serde(default)]
+    pub source_splits: Vec<SourceSplit>,
+}
+
+/// How far a conflict can be resolved without touching other crates' code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolvability {
+    /// Every other version is semver-compatible with the target, so
+    /// `cargo update --precise` converges them all
+    Lockfile,
+    /// Some versions can move in the lockfile; the rest are held by
+    /// requirements that exclude the target
+    Partial,
+    /// No version can move in the lockfile: the dependents need upgrading
+    NeedsUpgrade,
+    /// There's no version to converge on, e.g. every one has advisories
+    Blocked,
+}
+
+impl Conflict {
+    pub fn newest(&self) -> Option<&Version> {
+        self.versions.last().map(|v| &v.version)
+    }
+
+    /// Versions other than the target, each with whether a lockfile update
+    /// can move it to the target
+    pub fn movable(&self) -> Vec<(&ConflictVersion, bool)> {
+        let Some(target) = self.target() else {
+            return Vec::new();
+        };
+        self.versions
+            .iter()
+            .filter(|v| &v.version != target)
+            .map(|v| (v, is_compatible(&v.version, target)))
+            .collect()
+    }
+
+    pub fn resolvability(&self) -> Resolvability {
+        if self.target().is_none() {
+            return Resolvability::Blocked;
+        }
+        let movable = self.movable();
+        match movable.iter().filter(|(_, compatible)| *compatible).count() {
+            n if n == movable.len() => Resolvability::Lockfile,
+            0 => Resolvability::NeedsUpgrade,
+            _ => Resolvability::Partial,
+        }
+    }
+
+    /// Packages depending on versions other than the target, by name
+    pub fn dependents_off_target(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .movable()
+            .into_iter()
+            .flat_map(|(version, _)| &version.dependents)
+            .map(|dependent| dependent_name(dependent))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The version to converge on: the newest one without advisories when
+    /// the conflict is security-relevant, otherwise simply the newest
+    pub fn target(&self) -> Option<&Version> {
+        if self.security_relevant {
+            self.versions
+                .iter()
+                .rev()
+                .find(|v| v.advisories.is_empty())
+                .map(|v| &v.version)
+        } else {
+            self.newest()
+        }
+    }
+}
+
+impl ConflictReport {
+    /// Order conflicts by impact: security-relevant ones first, then those
+    /// with the most versions and dependents
+    pub fn sort_by_impact(&mut self) {
+        self.conflicts.sort_by_key(|c| {
+            let dependents: usize = c.versions.iter().map(|v| v.dependents.len()).sum();
+            (
+                std::cmp::Reverse(c.security_relevant),
+                std::cmp::Reverse(c.versions.len()),
+                std::cmp::Reverse(dependents),
+                c.name.clone(),
+            )
+        });
+    }
+
+    /// Drop conflicts of the crates in `ignored`
+    pub fn without(mut self, ignored: &[String]) -> Self {
+        self.conflicts.retain(|c| !ignored.contains(&c.name));
+        self.source_splits.retain(|s| !ignored.contains(&s.name));
+        self
+    }
+
+    /// Every duplicated package version, for scanning
+    pub fn packages(&self) -> Vec<(String, Version)> {
+        self.conflicts
+            .iter()
+            .flat_map(|c| {
+                c.versions
+                    .iter()
+                    .map(|v| (c.name.clone(), v.version.clone()))
+            })
+            .collect()
+    }
+
+    /// Attach the advisory findings for the duplicated versions. Informational
+    /// notices (unmaintained and the like) don't make a version vulnerable.
+    pub fn annotate(&mut self, affected: &[AffectedPackage]) {
+        for conflict in &mut self.conflicts {
+            for version in &mut conflict.versions {
+                version.advisories = affected
+                    .iter()
+                    .filter(|p| p.name == conflict.name && p.version == version.version)
+                    .flat_map(|p| &p.advisories)
+                    .filter(|a| a.informational.is_none())
+                    .map(|a| a.id.clone())
+                    .collect();
+            }
+            let vulnerable = conflict
+                .versions
+                .iter()
+                .filter(|v| !v.advisories.is_empty())
+                .count();
+            conflict.security_relevant = vulnerable > 0 && vulnerable < conflict.versions.len();
+        }
+    }
+}
+
+/// Find the version conflicts of the project owning `manifest`
+pub fn find_conflicts(manifest: &Manifest, cargo: &CargoOptions) -> Result<ConflictReport> {
+    if Metadata::file().is_some() {
+        let metadata = cargo::metadata(&manifest.path, cargo)?;
+        return Ok(ConflictReport::from_metadata(&metadata));
+    }
+    let output = cargo::tree_duplicates(&manifest.path, cargo)?;
+    Ok(ConflictReport::from_tree(&output))
+}
+
+/// Parse `cargo tree --duplicates --prefix depth` output into conflicts
+pub fn parse_duplicates(output: &str) -> Vec<Conflict> {
+    ConflictReport::from_tree(output).conflicts
+}
+
+impl ConflictReport {
+    /// Parse `cargo tree --duplicates --prefix depth` output. Depth 0 lines
+    /// are the duplicated packages, depth 1 lines their direct dependents;
+    /// anything that isn't a package entry is ignored, and a depth 0 line
+    /// that isn't one ends the dependents of the package before it.
+    pub fn from_tree(output: &str) -> Self {
+        let mut packages = Packages::default();
+        let mut current: Option<(String, Version)> = None;
+
+        for line in output.lines() {
+            if line.len() > MAX_TREE_LINE {
+                continue;
+            }
+            let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+            let Ok(depth) = line[..digits].parse::<usize>() else {
+                continue;
+            };
+            let Some((name, version, source)) = parse_package(&line[digits..]) else {
+                if depth == 0 {
+                    current = None;
+                }
+                continue;
+            };
+
+            match depth {
+                0 => {
+                    packages.add(&name, &version, source);
+                    current = Some((name, version));
+                }
+                1 => {
+                    if let Some((dup_name, dup_version)) = &current {
+                        packages.depends(dup_name, dup_version, &name, &version);
+                    }
+                }
+                _ => {}
+            }
+        }
+        packages.into_report()
+    }
+
+    /// Group the packages of `cargo metadata` output. The resolved graph
+    /// covers every target platform, so this may list duplicates `cargo
+    /// tree`, which only looks at the host, leaves out.
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        let mut packages = Packages::default();
+        for package in &metadata.packages {
+            let source = match &package.source {
+                Some(source) => normalize_source(source),
+                None => "path".to_string(),
+            };
+            packages.add(&package.name, &package.version, source);
+        }
+        for node in metadata.resolve.iter().flat_map(|r| &r.nodes) {
+            let Some(parent) = metadata.package(&node.id) else {
+                continue;
+            };
+            for dep in &node.deps {
+                if let Some(child) = metadata.package(&dep.pkg) {
+                    packages.depends(&child.name, &child.version, &parent.name, &parent.version);
+                }
+            }
+        }
+        packages.into_report()
+    }
+}
+
+/// Packages by name and version, with every source each came from
+#[derive(Default)]
+struct Packages {
+    found: BTreeMap<String, BTreeMap<Version, Found>>,
+}
+
+#[derive(Default)]
+struct Found {
+    sources: BTreeSet<String>,
+    dependents: BTreeSet<String>,
+}
+
+impl Packages {
+    fn add(&mut self, name: &str, version: &Version, source: String) {
+        self.entry(name, version).sources.insert(source);
+    }
+
+    fn depends(&mut self, name: &str, version: &Version, dependent: &str, at: &Version) {
+        self.entry(name, version)
+            .dependents
+            .insert(format!("{} v{}", dependent, at));
+    }
+
+    fn entry(&mut self, name: &str, version: &Version) -> &mut Found {
+        self.found
+            .entry(name.to_string())
+            .or_default()
+            .entry(version.clone())
+            .or_default()
+    }
+
+    /// Crates at several versions, and versions from several sources
+    fn into_report(self) -> ConflictReport {
+        let mut report = ConflictReport::default();
+        for (name, versions) in self.found {
+            for (version, found) in &versions {
+                if found.sources.len() > 1 {
+                    report.source_splits.push(SourceSplit {
+                        name: name.clone(),
+                        version: version.clone(),
+                        sources: found.sources.iter().cloned().collect(),
+                        dependents: found.dependents.iter().cloned().collect(),
+                    });
+                }
+            }
+            if versions.len() > 1 {
+                report.conflicts.push(Conflict {
+                    name,
+                    versions: versions
+                        .into_iter()
+                        .map(|(version, found)| ConflictVersion {
+                            version,
+                            dependents: found.dependents.into_iter().collect(),
+                            advisories: Vec::new(),
+                            sources: found.sources.into_iter().collect(),
+                        })
+                        .collect(),
+                    security_relevant: false,
+                });
+            }
+        }
+        report
+    }
+}
+
+/// A source id as shown in reports: crates.io and its mirrors' original
+/// id become "crates.io", registry indexes their URL, git sources their
+/// URL without the resolved commit
+pub fn normalize_source(source: &str) -> String {
+    let source = source
+        .strip_prefix("registry+")
+        .or_else(|| source.strip_prefix("sparse+"))
+        .or_else(|| source.strip_prefix("git+"))
+        .unwrap_or(source);
+    let source = source.split('#').next().unwrap_or(source);
+    if CRATES_IO_INDEXES
+        .iter()
+        .any(|index| index.trim_end_matches('/') == source.trim_end_matches('/'))
+    {
+        return CRATES_IO.to_string();
+    }
+    source.to_string()
+}
+
+/// The package name of a dependent entry such as "serde_derive v1.0.100"
+pub fn dependent_name(dependent: &str) -> &str {
+    dependent.split_whitespace().next().unwrap_or(dependent)
+}
+
+/// Parse `name v1.2.3 (source) (proc-macro) (*)` into its name, version
+/// and source. cargo only shows sources other than crates.io: a path, a git
+/// URL, or "registry `<name or index>`".
+fn parse_package(entry: &str) -> Option<(String, Version, String)> {
+    let (name, rest) = entry.split_once(char::is_whitespace)?;
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let (version, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let version = Version::parse(version.strip_prefix('v')?).ok()?;
+
+    let source = rest
+        .split(" (")
+        .filter_map(|part| part.trim().strip_suffix(')'))
+        .map(|part| part.trim_start_matches('('))
+        .find(|part| !matches!(*part, "proc-macro" | "*"));
+    let source = match source {
+        None => CRATES_IO.to_string(),
+        Some(source) => match source.strip_prefix("registry `") {
+            Some(registry) => normalize_source(registry.trim_end_matches('`')),
+            None if source.contains("://") => normalize_source(source),
+            None => "path".to_string(),
+        },
+    };
+    Some((name.to_string(), version, source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duplicates_skips_non_package_lines() {
+        let output = "\
+0v1 v0.1.0
+1web v1.0.0
+0[build-dependencies]
+1grpc v1.0.0
+0v1\tv0.2.0 (proc-macro)
+1v2 v1.0.0 (*)
+1serde feature \"derive\"
+0warning: v1 v0.3.0 is unused
+";
+        let conflicts = parse_duplicates(output);
+        assert_eq!(conflicts.len(), 1);
+
+        let v1 = &conflicts[0];
+        assert_eq!(v1.name, "v1");
+        assert_eq!(v1.versions.len(), 2);
+        assert_eq!(v1.versions[0].dependents, vec!["web v1.0.0"]);
+        assert_eq!(v1.versions[1].dependents, vec!["v2 v1.0.0"]);
+    }
+
+    #[test]
+    fn test_parse_duplicates() {
+        let output = "\
+0syn v1.0.109
+1serde_derive v1.0.100 (proc-macro)
+2serde v1.0.100
+3demo v0.1.0 (/work/demo)
+
+0syn v2.0.50
+1thiserror-impl v1.0.57 (proc-macro)
+2thiserror v1.0.57
+1tokio-macros v2.2.0 (proc-macro)
+1thiserror-impl v1.0.57 (proc-macro) (*)
+";
+        let conflicts = parse_duplicates(output);
+        assert_eq!(conflicts.len(), 1);
+
+        let syn = &conflicts[0];
+        assert_eq!(syn.name, "syn");
+        assert_eq!(syn.versions.len(), 2);
+        assert_eq!(syn.versions[0].version, Version::new(1, 0, 109));
+        assert_eq!(syn.versions[0].dependents, vec!["serde_derive v1.0.100"]);
+        assert_eq!(
+            syn.versions[1].dependents,
+            vec!["thiserror-impl v1.0.57", "tokio-macros v2.2.0"]
+        );
+        assert_eq!(syn.newest(), Some(&Version::new(2, 0, 50)));
+    }
+
+    #[test]
+    fn test_tree_sources() {
+        let output = "\
+0syn v1.0.109
+1serde_derive v1.0.100 (proc-macro)
+
+0syn v2.0.50
+1thiserror-impl v1.0.57 (proc-macro)
+
+0syn v2.0.50 (registry `corp`)
+1tokio-macros v2.2.0 (proc-macro) (registry `corp`)
+
+0tokio v1.36.0 (https://github.com/tokio-rs/tokio?branch=master#5a2b1c3d)
+1demo v0.1.0 (/work/demo)
+
+0tokio v1.36.0
+1hyper v1.2.0
+";
+        let report = ConflictReport::from_tree(output);
+        let syn = &report.conflicts[0];
+        assert_eq!(syn.versions.len(), 2);
+        assert_eq!(syn.versions[0].sources, vec![CRATES_IO]);
+        assert_eq!(syn.versions[1].sources, vec!["corp", CRATES_IO]);
+        assert_eq!(
+            syn.versions[1].dependents,
+            vec!["thiserror-impl v1.0.57", "tokio-macros v2.2.0"]
+        );
+
+        let splits: Vec<(&str, &[String])> = report
+            .source_splits
+            .iter()
+            .map(|s| (s.name.as_str(), s.sources.as_slice()))
+            .collect();
+        assert_eq!(
+            splits,
+            vec![
+                ("syn", &["corp".to_string(), CRATES_IO.to_string()][..]),
+                (
+                    "tokio",
+                    &[
+                        CRATES_IO.to_string(),
+                        "https://github.com/tokio-rs/tokio?branch=master".to_string()
+                    ][..]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_metadata_with_mirrored_sources() {
+        let metadata: Metadata =
+            serde_json::from_str(include_str!("../../tests/fixtures/mirrored/metadata.json"))
+                .unwrap();
+        let report = ConflictReport::from_metadata(&metadata);
+
+        // Both crates.io index ids are the same source: serde isn't split
+        assert_eq!(report.conflicts.len(), 1);
+        let syn = &report.conflicts[0];
+        assert_eq!(syn.name, "syn");
+        assert_eq!(syn.versions[0].dependents, vec!["serde v1.0.200"]);
+        assert_eq!(
+            syn.versions[1].sources,
+            vec![CRATES_IO, "https://mirror.corp.example/index/"]
+        );
+        assert_eq!(
+            syn.versions[1].dependents,
+            vec!["demo v0.1.0", "thiserror-impl v1.0.57"]
+        );
+
+        assert_eq!(report.source_splits.len(), 1);
+        assert_eq!(report.source_splits[0].name, "syn");
+        assert_eq!(report.source_splits[0].version, Version::new(2, 0, 50));
+    }
+
+    #[test]
+    fn test_normalize_source() {
+        assert_eq!(
+            normalize_source("registry+https://github.com/rust-lang/crates.io-index"),
+            CRATES_IO
+        );
+        assert_eq!(
+            normalize_source("sparse+https://index.crates.io/"),
+            CRATES_IO
+        );
+        assert_eq!(
+            normalize_source("git+https://github.com/serde-rs/serde#0123abcd"),
+            "https://github.com/serde-rs/serde"
+        );
+    }
+
+    #[test]
+    fn test_ignores_noise() {
+        let output = "\
+warning: something odd
+0lonely v1.0.0
+
+1orphan v0.1.0
+";
+        assert!(parse_duplicates(output).is_empty());
+    }
+
+    fn affected(name: &str, version: Version, id: &str, informational: bool) -> AffectedPackage {
+        AffectedPackage {
+            name: name.to_string(),
+            version,
+            source: crate::core::dependency::DependencySource::Registry,
+            attribution: crate::analyzer::attribution::Attribution::direct(name),
+            also_via: Vec::new(),
+            owner: None,
+            advisories: vec![crate::core::advisory::Advisory {
+                id: id.to_string(),
+                package: name.to_string(),
+                title: "bad".to_string(),
+                severity: None,
+                cvss: None,
+                aliases: Vec::new(),
+                patched_versions: Vec::new(),
+                informational: informational.then(|| "unmaintained".to_string()),
+                url: String::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_annotate_security_relevance() {
+        let output = "0time v0.1.45
+1chrono v0.4.19
+0time v0.3.30
+1demo v0.1.0 (/work/demo)
+0time v0.3.36
+1tracing-subscriber v0.3.18
+0ansi_term v0.11.0
+1clap v2.34.0
+0ansi_term v0.12.1
+1demo v0.1.0 (/work/demo)
+";
+        let mut report = ConflictReport::from_tree(output);
+        assert_eq!(report.packages().len(), 5);
+
+        report.annotate(&[
+            affected("time", Version::new(0, 1, 45), "RUSTSEC-2020-0071", false),
+            affected("time", Version::new(0, 3, 36), "RUSTSEC-2099-0001", false),
+            affected(
+                "ansi_term",
+                Version::new(0, 11, 0),
+                "RUSTSEC-2021-0139",
+                true,
+            ),
+            affected(
+                "ansi_term",
+                Version::new(0, 12, 1),
+                "RUSTSEC-2021-0139",
+                true,
+            ),
+        ]);
+
+        let ansi_term = &report.conflicts[0];
+        assert!(!ansi_term.security_relevant);
+        assert!(ansi_term.versions.iter().all(|v| v.advisories.is_empty()));
+        assert_eq!(ansi_term.target(), Some(&Version::new(0, 12, 1)));
+
+        // The newest duplicate is the vulnerable one here, so converge down
+        let time = &report.conflicts[1];
+        assert!(time.security_relevant);
+        assert_eq!(time.versions[0].advisories, vec!["RUSTSEC-2020-0071"]);
+        assert!(time.versions[1].advisories.is_empty());
+        assert_eq!(time.target(), Some(&Version::new(0, 3, 30)));
+        assert_eq!(time.newest(), Some(&Version::new(0, 3, 36)));
+    }
+
+    fn conflict(versions: &[(&str, &[&str], &[&str])]) -> Conflict {
+        let mut conflict = Conflict {
+            name: "demo".to_string(),
+            versions: versions
+                .iter()
+                .map(|(version, dependents, advisories)| ConflictVersion {
+                    version: Version::parse(version).unwrap(),
+                    dependents: dependents.iter().map(|d| d.to_string()).collect(),
+                    advisories: advisories.iter().map(|a| a.to_string()).collect(),
+                    sources: vec![CRATES_IO.to_string()],
+                })
+                .collect(),
+            security_relevant: false,
+        };
+        conflict.security_relevant = conflict.versions.iter().any(|v| !v.advisories.is_empty());
+        conflict
+    }
+
+    #[test]
+    fn test_resolvability() {
+        let lockfile = conflict(&[("1.0.1", &["a v0.1.0"], &[]), ("1.0.5", &["b v0.2.0"], &[])]);
+        assert_eq!(lockfile.resolvability(), Resolvability::Lockfile);
+
+        let partial = conflict(&[
+            ("0.9.0", &["old v1.0.0"], &[]),
+            ("1.0.1", &["a v0.1.0"], &[]),
+            ("1.0.5", &["b v0.2.0"], &[]),
+        ]);
+        assert_eq!(partial.resolvability(), Resolvability::Partial);
+        assert_eq!(partial.dependents_off_target(), vec!["a", "old"]);
+
+        let upgrade = conflict(&[
+            ("1.0.0", &["old v1.0.0"], &[]),
+            ("2.0.0", &["new v1.0.0"], &[]),
+        ]);
+        assert_eq!(upgrade.resolvability(), Resolvability::NeedsUpgrade);
+
+        let blocked = conflict(&[
+            ("1.0.0", &[], &["RUSTSEC-1"]),
+            ("2.0.0", &[], &["RUSTSEC-2"]),
+        ]);
+        assert_eq!(blocked.resolvability(), Resolvability::Blocked);
+    }
+
+    #[test]
+    fn test_sort_by_impact() {
+        let mut wide = conflict(&[
+            ("1.0.0", &["a v1.0.0", "b v1.0.0"], &[]),
+            ("2.0.0", &[], &[]),
+        ]);
+        wide.name = "wide".to_string();
+        let mut narrow = conflict(&[("1.0.0", &["a v1.0.0"], &[]), ("2.0.0", &[], &[])]);
+        narrow.name = "narrow".to_string();
+        let mut vulnerable = conflict(&[("1.0.0", &[], &["RUSTSEC-1"]), ("2.0.0", &[], &[])]);
+        vulnerable.name = "vulnerable".to_string();
+
+        let mut report = ConflictReport {
+            conflicts: vec![narrow, wide, vulnerable],
+            source_splits: Vec::new(),
+        };
+        report.sort_by_impact();
+        let names: Vec<&str> = report.conflicts.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["vulnerable", "wide", "narrow"]);
+
+        let report = report.without(&["wide".to_string()]);
+        assert_eq!(report.conflicts.len(), 2);
+    }
+}
@@ -0,0 +1,235 @@
+//! Detect a crate declared in overlapping manifest sections
+//!
+//! Cargo rejects a key repeated inside one table, but happily accepts the same
+//! crate in `[dependencies]` and in `[target.'cfg(..)'.dependencies]`. When the
+//! target predicate is active the two declarations are unified, so differing
+//! version requirements, feature lists or `default-features` flags silently
+//! merge into something nobody wrote down.
+
+use crate::analyzer::checker::parse_version_req;
+use crate::core::dependency::DependencyKind;
+use crate::core::manifest::{DependencySection, Manifest};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// One declaration of a crate in a single manifest section
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Declaration {
+    pub section: DependencySection,
+    pub requirement: Option<String>,
+    pub features: Vec<String>,
+    pub default_features: bool,
+    pub line: Option<usize>,
+}
+
+/// A crate declared in more than one overlapping section with disagreeing values
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DeclarationConflict {
+    pub name: String,
+    pub kind: DependencyKind,
+    pub declarations: Vec<Declaration>,
+    pub requirement_mismatch: bool,
+    /// The declarations enable different features
+    #[serde(default)]
+    pub features_mismatch: bool,
+    pub default_features_mismatch: bool,
+}
+
+impl DeclarationConflict {
+    /// The requirement every declaration should agree on: the unconditional
+    /// declaration's if there is one, otherwise the highest requirement
+    pub fn consolidation_target(&self) -> Option<&str> {
+        if let Some(requirement) = self
+            .declarations
+            .iter()
+            .find(|d| d.section.target.is_none())
+            .and_then(|d| d.requirement.as_deref())
+        {
+            return Some(requirement);
+        }
+
+        self.declarations
+            .iter()
+            .filter_map(|d| d.requirement.as_deref())
+            .max_by_key(|req| parse_version_req(req))
+    }
+
+    /// Declarations whose requirement differs from the consolidation target
+    pub fn divergent_declarations(&self) -> Vec<&Declaration> {
+        let target = self.consolidation_target();
+        self.declarations
+            .iter()
+            .filter(|d| d.requirement.is_some() && d.requirement.as_deref() != target)
+            .collect()
+    }
+}
+
+/// Find crates declared in several sections of the same kind whose version
+/// requirements, features or `default-features` settings disagree
+pub fn find_declaration_conflicts(manifest: &Manifest) -> Vec<DeclarationConflict> {
+    let mut groups: BTreeMap<(String, DependencyKind), Vec<Declaration>> = BTreeMap::new();
+
+    for (section, name, spec) in manifest.declarations() {
+        let line = manifest.location_in(&name, &section).map(|(line, _)| line);
+        let declaration = Declaration {
+            requirement: spec.version().map(str::to_string),
+            features: spec.features().to_vec(),
+            default_features: spec.default_features(),
+            line,
+            section: section.clone(),
+        };
+        groups
+            .entry((name, section.kind))
+            .or_default()
+            .push(declaration);
+    }
+
+    groups
+        .into_iter()
+        .filter(|(_, declarations)| declarations.len() > 1)
+        .filter_map(|((name, kind), declarations)| {
+            let requirements: BTreeSet<&str> = declarations
+                .iter()
+                .filter_map(|d| d.requirement.as_deref())
+                .collect();
+            let requirement_mismatch = requirements.len() > 1;
+            // Order and repeats don't change what a feature list enables
+            let feature_sets: BTreeSet<BTreeSet<&str>> = declarations
+                .iter()
+                .map(|d| d.features.iter().map(String::as_str).collect())
+                .collect();
+            let features_mismatch = feature_sets.len() > 1;
+            let default_features_mismatch = declarations
+                .iter()
+                .any(|d| d.default_features != declarations[0].default_features);
+
+            if !requirement_mismatch && !features_mismatch && !default_features_mismatch {
+                return None;
+            }
+
+            Some(DeclarationConflict {
+                name,
+                kind,
+                declarations,
+                requirement_mismatch,
+                features_mismatch,
+                default_features_mismatch,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn conflicts(text: &str) -> Vec<DeclarationConflict> {
+        let manifest = Manifest::parse(PathBuf::from("Cargo.toml"), text).unwrap();
+        find_declaration_conflicts(&manifest)
+    }
+
+    #[test]
+    fn test_target_and_normal_requirement_mismatch() {
+        let found = conflicts(
+            r#"[dependencies]
+tokio = "1.35"
+serde = "1.0"
+
+[target.'cfg(all())'.dependencies]
+tokio = { version = "1.20", features = ["net"] }
+"#,
+        );
+
+        assert_eq!(found.len(), 1);
+        let conflict = &found[0];
+        assert_eq!(conflict.name, "tokio");
+        assert!(conflict.requirement_mismatch);
+        assert!(conflict.features_mismatch);
+        assert!(!conflict.default_features_mismatch);
+        assert_eq!(conflict.consolidation_target(), Some("1.35"));
+
+        let divergent = conflict.divergent_declarations();
+        assert_eq!(divergent.len(), 1);
+        assert_eq!(divergent[0].section.target.as_deref(), Some("cfg(all())"));
+        assert_eq!(divergent[0].line, Some(6));
+    }
+
+    #[test]
+    fn test_default_features_mismatch() {
+        let found = conflicts(
+            r#"[dependencies]
+reqwest = "0.12"
+
+[target.'cfg(unix)'.dependencies]
+reqwest = { version = "0.12", default-features = false }
+"#,
+        );
+
+        assert_eq!(found.len(), 1);
+        assert!(!found[0].requirement_mismatch);
+        assert!(!found[0].features_mismatch);
+        assert!(found[0].default_features_mismatch);
+    }
+
+    #[test]
+    fn test_features_mismatch() {
+        let found = conflicts(
+            r#"[dependencies]
+libc = "0.2"
+serde = { version = "1.0", features = ["derive", "rc"] }
+
+[target.'cfg(unix)'.dependencies]
+libc = { version = "0.2", features = ["extra_traits"] }
+serde = { version = "1.0", features = ["rc", "derive", "rc"] }
+"#,
+        );
+
+        assert_eq!(found.len(), 1);
+        let conflict = &found[0];
+        assert_eq!(conflict.name, "libc");
+        assert!(!conflict.requirement_mismatch);
+        assert!(conflict.features_mismatch);
+        assert!(!conflict.default_features_mismatch);
+        let features: Vec<&[String]> = conflict
+            .declarations
+            .iter()
+            .map(|d| d.features.as_slice())
+            .collect();
+        assert_eq!(features, [&[][..], &["extra_traits".to_string()][..]]);
+    }
+
+    #[test]
+    fn test_agreeing_declarations_and_other_kinds_are_not_conflicts() {
+        let found = conflicts(
+            r#"[dependencies]
+libc = "0.2"
+serde = "1.0"
+
+[dev-dependencies]
+serde = "1.0.100"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+"#,
+        );
+
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn test_target_only_declarations_pick_highest() {
+        let found = conflicts(
+            r#"[target.'cfg(unix)'.dependencies]
+nix = "0.27"
+
+[target.'cfg(target_os = "linux")'.dependencies]
+nix = "0.29"
+"#,
+        );
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].consolidation_target(), Some("0.29"));
+    }
+}
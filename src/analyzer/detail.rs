@@ -0,0 +1,385 @@
+//! Everything known about one dependency, for `cargo sane check <crate>`
+//!
+//! The full check looks every dependency up; this looks up a single crate
+//! and goes deeper: each declaration, what the lockfile holds, how far
+//! behind that is, and the commands that would move it.
+
+use crate::analyzer::history::DependencyOrigin;
+use crate::core::dependency::{DependencyKind, DependencySource};
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::{DependencySection, DependencySpec, Manifest};
+use crate::core::version::{
+    is_newer, same_release, select_target_version, without_build_metadata, Prereleases,
+    PublishedVersion,
+};
+use crate::utils::advisory_db::AdvisoryIndex;
+use anyhow::Result;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+/// How many declared names are suggested when the crate isn't found
+const MAX_SUGGESTIONS: usize = 3;
+
+/// One declaration of the crate in Cargo.toml
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeclaredRequirement {
+    pub section: DependencySection,
+    /// The requirement as written; None for git and path dependencies
+    pub requirement: Option<String>,
+    pub source: DependencySource,
+}
+
+/// The expanded view of one dependency
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrateDetail {
+    pub name: String,
+    pub declarations: Vec<DeclaredRequirement>,
+    /// The version Cargo.lock resolves the requirements to
+    pub locked: Option<Version>,
+    pub locked_released_at: Option<u64>,
+    /// Whether the locked version has been yanked
+    pub yanked: bool,
+    /// The newest release every declared requirement accepts
+    pub latest_compatible: Option<Version>,
+    /// The newest release, whatever the requirements say
+    pub latest: Option<Version>,
+    pub latest_released_at: Option<u64>,
+    /// Releases newer than the locked version
+    pub releases_behind: usize,
+    /// Open advisories against the locked version, by id
+    pub advisories: Vec<String>,
+    /// Commands that move the dependency forward, the least disruptive first
+    pub commands: Vec<String>,
+    /// The commit that added each committed declaration, with `--history`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<DependencyOrigin>,
+}
+
+impl CrateDetail {
+    /// Seconds between the locked release and the latest one
+    pub fn release_gap(&self) -> Option<u64> {
+        Some(
+            self.latest_released_at?
+                .saturating_sub(self.locked_released_at?),
+        )
+    }
+}
+
+/// The declarations of `name` in `manifest`, or an error suggesting the
+/// closest declared names when there are none
+pub fn declarations_of(manifest: &Manifest, name: &str) -> Result<Vec<DeclaredRequirement>> {
+    let declarations = manifest.declarations();
+    let found: Vec<DeclaredRequirement> = declarations
+        .iter()
+        .filter(|(_, declared, _)| declared == name)
+        .map(|(section, _, spec)| DeclaredRequirement {
+            section: section.clone(),
+            requirement: spec.version().map(str::to_string),
+            source: source_of(spec),
+        })
+        .collect();
+    if !found.is_empty() {
+        return Ok(found);
+    }
+
+    let mut names: Vec<&str> = declarations.iter().map(|(_, n, _)| n.as_str()).collect();
+    names.dedup();
+    let suggestions = close_matches(name, &names);
+    let hint = match suggestions.as_slice() {
+        [] => String::new(),
+        [only] => format!("; did you mean {}?", only),
+        _ => format!("; did you mean one of {}?", suggestions.join(", ")),
+    };
+    anyhow::bail!(
+        "{} is not a dependency in {}{}",
+        name,
+        manifest.path.display(),
+        hint
+    )
+}
+
+/// Put together the detail of `name` from its declarations, the lockfile
+/// and its published releases
+pub fn crate_detail(
+    name: &str,
+    declarations: Vec<DeclaredRequirement>,
+    lockfile: Option<&Lockfile>,
+    published: &[PublishedVersion],
+    advisories: &AdvisoryIndex,
+) -> CrateDetail {
+    let requirements: Vec<VersionReq> = declarations
+        .iter()
+        .filter(|d| d.source == DependencySource::Registry)
+        .filter_map(|d| VersionReq::parse(d.requirement.as_deref()?).ok())
+        .collect();
+    let accepts = |version: &Version| requirements.iter().all(|r| r.matches(version));
+
+    // With several versions locked, the one the requirements accept is ours
+    let locked_versions: Vec<&Version> = lockfile
+        .map(|l| l.packages_named(name))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| &p.version)
+        .collect();
+    let locked = locked_versions
+        .iter()
+        .rev()
+        .find(|v| accepts(v))
+        .or(locked_versions.last())
+        .map(|v| (*v).clone());
+
+    let release = |version: &Version| published.iter().find(|p| same_release(&p.version, version));
+    let prereleases = match &locked {
+        Some(locked) => Prereleases::for_current(locked, false),
+        None => Prereleases::Stable,
+    };
+    let latest = select_target_version(published, &prereleases, |_| Vec::new()).target;
+    let compatible: Vec<PublishedVersion> = published
+        .iter()
+        .filter(|p| accepts(&p.version))
+        .cloned()
+        .collect();
+    let latest_compatible = select_target_version(&compatible, &prereleases, |_| Vec::new()).target;
+
+    let releases_behind = match &locked {
+        Some(locked) => published
+            .iter()
+            .filter(|p| !p.yanked && p.version.pre.is_empty() && is_newer(&p.version, locked))
+            .count(),
+        None => 0,
+    };
+
+    let mut commands = Vec::new();
+    let spec = if locked_versions.len() > 1 {
+        locked.as_ref().map(|v| format!("{}@{}", name, v))
+    } else {
+        Some(name.to_string())
+    };
+    if let (Some(locked), Some(target), Some(spec)) = (&locked, &latest_compatible, spec) {
+        if is_newer(target, locked) {
+            commands.push(format!("cargo update -p {} --precise {}", spec, target));
+        }
+    }
+    if let Some(latest) = &latest {
+        if latest_compatible.as_ref() != Some(latest) && !accepts(latest) {
+            for declaration in &declarations {
+                if declaration.source != DependencySource::Registry {
+                    continue;
+                }
+                let command = cargo_add(name, latest, &declaration.section);
+                if !commands.contains(&command) {
+                    commands.push(command);
+                }
+            }
+        }
+    }
+
+    CrateDetail {
+        name: name.to_string(),
+        declarations,
+        locked_released_at: locked.as_ref().and_then(|v| release(v)?.created_at),
+        yanked: locked.as_ref().and_then(release).is_some_and(|p| p.yanked),
+        advisories: locked
+            .as_ref()
+            .map(|v| advisories.affecting(name, v))
+            .unwrap_or_default(),
+        latest_released_at: latest.as_ref().and_then(|v| release(v)?.created_at),
+        locked,
+        latest_compatible,
+        latest,
+        releases_behind,
+        commands,
+        history: Vec::new(),
+    }
+}
+
+fn source_of(spec: &DependencySpec) -> DependencySource {
+    if spec.is_git() {
+        DependencySource::Git
+    } else if spec.is_path() {
+        DependencySource::Path
+    } else {
+        DependencySource::Registry
+    }
+}
+
+/// `cargo add` raising the requirement in `section` to `version`
+fn cargo_add(name: &str, version: &Version, section: &DependencySection) -> String {
+    let mut command = "cargo add".to_string();
+    match section.kind {
+        DependencyKind::Normal => {}
+        DependencyKind::Dev => command.push_str(" --dev"),
+        DependencyKind::Build => command.push_str(" --build"),
+    }
+    if let Some(target) = &section.target {
+        command.push_str(&format!(" --target '{}'", target));
+    }
+    format!(
+        "{} {}@{}",
+        command,
+        name,
+        without_build_metadata(&version.to_string())
+    )
+}
+
+/// Names in `candidates` a typo away from `name`, closest first
+fn close_matches<'a>(name: &str, candidates: &[&'a str]) -> Vec<&'a str> {
+    let normalize = |s: &str| s.to_lowercase().replace('-', "_");
+    let wanted = normalize(name);
+    let limit = (wanted.chars().count() / 3).max(2);
+
+    let mut matches: Vec<(usize, &str)> = candidates
+        .iter()
+        .filter_map(|candidate| {
+            let normalized = normalize(candidate);
+            let prefix = wanted.len().min(normalized.len()) >= 3
+                && (normalized.starts_with(&wanted) || wanted.starts_with(&normalized));
+            let distance = if prefix {
+                0
+            } else {
+                edit_distance(&wanted, &normalized)
+            };
+            (distance <= limit).then_some((distance, *candidate))
+        })
+        .collect();
+    matches.sort();
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn manifest(dependencies: &str) -> Manifest {
+        Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            &format!(
+                "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n\n{}",
+                dependencies
+            ),
+        )
+        .unwrap()
+    }
+
+    fn published(list: &[(&str, bool, u64)]) -> Vec<PublishedVersion> {
+        list.iter()
+            .map(|(version, yanked, created_at)| PublishedVersion {
+                version: Version::parse(version).unwrap(),
+                yanked: *yanked,
+                created_at: Some(*created_at),
+                published_by: None,
+                license: None,
+                crate_size: None,
+            })
+            .collect()
+    }
+
+    fn lockfile(versions: &[&str]) -> Lockfile {
+        let packages: String = versions
+            .iter()
+            .map(|v| format!("[[package]]\nname = \"serde\"\nversion = \"{}\"\n\n", v))
+            .collect();
+        Lockfile::parse(PathBuf::from("Cargo.lock"), &packages).unwrap()
+    }
+
+    #[test]
+    fn test_detail() {
+        let manifest = manifest(
+            "[dependencies]\nserde = \"1.0.100\"\n\n[dev-dependencies]\nserde = \"1.0\"\n",
+        );
+        let declarations = declarations_of(&manifest, "serde").unwrap();
+        assert_eq!(declarations.len(), 2);
+
+        let releases = published(&[
+            ("2.0.0", false, 4000),
+            ("1.0.210", true, 3000),
+            ("1.0.200", false, 2000),
+            ("1.0.150", true, 1000),
+        ]);
+        let detail = crate_detail(
+            "serde",
+            declarations,
+            Some(&lockfile(&["1.0.150"])),
+            &releases,
+            &AdvisoryIndex::default(),
+        );
+        assert_eq!(detail.locked, Some(Version::new(1, 0, 150)));
+        assert!(detail.yanked);
+        assert_eq!(detail.latest_compatible, Some(Version::new(1, 0, 200)));
+        assert_eq!(detail.latest, Some(Version::new(2, 0, 0)));
+        assert_eq!(detail.releases_behind, 2);
+        assert_eq!(detail.release_gap(), Some(3000));
+        assert_eq!(
+            detail.commands,
+            vec![
+                "cargo update -p serde --precise 1.0.200",
+                "cargo add serde@2.0.0",
+                "cargo add --dev serde@2.0.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_up_to_date() {
+        let manifest = manifest("[dependencies]\nserde = \"1\"\n");
+        let detail = crate_detail(
+            "serde",
+            declarations_of(&manifest, "serde").unwrap(),
+            Some(&lockfile(&["0.9.0", "1.0.200"])),
+            &published(&[("1.0.200", false, 2000)]),
+            &AdvisoryIndex::default(),
+        );
+        assert_eq!(detail.locked, Some(Version::new(1, 0, 200)));
+        assert!(!detail.yanked);
+        assert_eq!(detail.releases_behind, 0);
+        assert!(detail.commands.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_crate_suggests_close_names() {
+        let manifest =
+            manifest("[dependencies]\nserde = \"1\"\nserde_json = \"1\"\ntokio = \"1\"\n");
+        let error = declarations_of(&manifest, "serd").unwrap_err().to_string();
+        assert!(
+            error.ends_with("did you mean one of serde, serde_json?"),
+            "{}",
+            error
+        );
+        let error = declarations_of(&manifest, "toki").unwrap_err().to_string();
+        assert!(error.ends_with("did you mean tokio?"), "{}", error);
+        let error = declarations_of(&manifest, "rand").unwrap_err().to_string();
+        assert!(
+            error.ends_with("is not a dependency in Cargo.toml"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("tokio", "tokio"), 0);
+        assert_eq!(edit_distance("toiko", "tokio"), 2);
+        assert_eq!(edit_distance("reqwest", "request"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
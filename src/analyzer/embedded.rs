@@ -0,0 +1,475 @@
+//! Copies of crates pasted into the source tree
+//!
+//! Code copied out of a crate and committed with the project never shows
+//! up in Cargo.toml or Cargo.lock, so no manifest-based check sees it, nor
+//! the advisories against the version it was copied from. The scan looks
+//! for what such copies leave behind:
+//!
+//! - a `Cargo.toml` with a `[package]` that is neither the project, a
+//!   workspace member nor a path dependency, and doesn't depend on the
+//!   project the way fuzz targets and xtask helpers do
+//! - a `#![crate_name = "..."]` attribute in a file opening with a license
+//!   header, which a crate root rarely has inside another crate
+//! - a directory named after a known crate, holding a `lib.rs` or
+//!   `src/lib.rs` of its own
+//!
+//! Findings are guesses, so they inform and never fail a run. Copies that
+//! Cargo.lock locks at the same version, as `cargo vendor` leaves them, are
+//! already scanned and left out.
+
+use crate::core::advisory::Advisory;
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::core::workspace::Workspace;
+use regex::Regex;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Lines at the top of a file searched for a license header
+const HEADER_LINES: usize = 20;
+
+/// A likely copy of a crate inside the project
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EmbeddedCrate {
+    /// The directory holding the copy, relative to the project
+    pub path: PathBuf,
+    /// Best guess at the crate it was copied from
+    pub name: String,
+    /// Best guess at the version, from an embedded Cargo.toml or a version
+    /// constant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<Version>,
+    /// What gave the copy away, strongest first
+    pub markers: Vec<EmbeddedMarker>,
+    /// Advisories against the guessed version. Filled in by the health
+    /// command.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub advisories: Vec<Advisory>,
+}
+
+/// One sign of an embedded crate, with the file showing it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EmbeddedMarker {
+    pub kind: MarkerKind,
+    /// Relative to the project
+    pub file: PathBuf,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkerKind {
+    /// A package manifest outside the project's own crates
+    NestedManifest,
+    /// A `#![crate_name]` attribute under a license header
+    CrateNameAttribute,
+    /// A directory named after a known crate, with a crate root inside
+    KnownCrateLayout,
+}
+
+impl MarkerKind {
+    pub fn describe(self) -> &'static str {
+        match self {
+            MarkerKind::NestedManifest => "Cargo.toml outside the workspace",
+            MarkerKind::CrateNameAttribute => "#![crate_name] under a license header",
+            MarkerKind::KnownCrateLayout => "directory named after a known crate",
+        }
+    }
+}
+
+/// The manifests of the project owning `manifest`: its own, the workspace
+/// members' and those of path dependencies, canonicalized
+pub fn own_manifests(manifest: &Manifest) -> BTreeSet<PathBuf> {
+    let mut manifests = vec![manifest.clone()];
+    if let Ok(Some(workspace)) = Workspace::load(manifest.clone()) {
+        manifests.extend(workspace.members);
+    }
+    let mut own = BTreeSet::new();
+    for manifest in &manifests {
+        own.insert(canonical(&manifest.path));
+        for (_, nested) in manifest.path_dependencies() {
+            own.insert(canonical(&nested.path));
+        }
+    }
+    own
+}
+
+/// Likely embedded crates among `files`, the Cargo.toml and `.rs` files
+/// walked below `root`. `own` holds the project's manifests and `known` the
+/// crate names a directory may be named after.
+pub fn find_embedded_crates(
+    root: &Path,
+    files: &[PathBuf],
+    own: &BTreeSet<PathBuf>,
+    known: &BTreeSet<String>,
+    lockfile: Option<&Lockfile>,
+) -> Vec<EmbeddedCrate> {
+    let own_dirs: BTreeSet<PathBuf> = own
+        .iter()
+        .filter_map(|path| Some(path.parent()?.to_path_buf()))
+        .collect();
+    let mut found: Vec<EmbeddedCrate> = Vec::new();
+
+    for file in files {
+        let candidate = if file.file_name().is_some_and(|n| n == "Cargo.toml") {
+            nested_manifest(file, own)
+        } else {
+            crate_name_attribute(file).or_else(|| known_crate_layout(file, known))
+        };
+        let Some(Candidate {
+            kind,
+            name,
+            version,
+            dir,
+        }) = candidate
+        else {
+            continue;
+        };
+        if own_dirs.contains(&canonical(&dir)) {
+            continue;
+        }
+        let marker = EmbeddedMarker {
+            kind,
+            file: relative(root, file),
+        };
+        let path = relative(root, &dir);
+
+        // A copy's crate root and manifest are one finding
+        match found
+            .iter_mut()
+            .find(|c| path.starts_with(&c.path) || c.path.starts_with(&path))
+        {
+            Some(existing) => {
+                if path.as_os_str().len() < existing.path.as_os_str().len() {
+                    existing.path = path;
+                }
+                if kind < existing.markers[0].kind {
+                    existing.name = name;
+                }
+                existing.version = existing.version.take().or(version);
+                existing.markers.push(marker);
+                existing.markers.sort_by_key(|m| m.kind);
+            }
+            None => found.push(EmbeddedCrate {
+                path,
+                name,
+                version,
+                markers: vec![marker],
+                advisories: Vec::new(),
+            }),
+        }
+    }
+
+    for embedded in &mut found {
+        if embedded.version.is_none() {
+            embedded.version = version_constant(&root.join(&embedded.path));
+        }
+    }
+    // What Cargo.lock locks at the same version is scanned already
+    found.retain(|embedded| {
+        let Some(version) = &embedded.version else {
+            return true;
+        };
+        !lockfile.is_some_and(|lockfile| {
+            lockfile
+                .packages
+                .iter()
+                .any(|p| p.source.is_some() && p.name == embedded.name && &p.version == version)
+        })
+    });
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    found
+}
+
+/// What one file suggests: a copy of `name` in `dir`
+struct Candidate {
+    kind: MarkerKind,
+    name: String,
+    version: Option<Version>,
+    dir: PathBuf,
+}
+
+/// A package manifest that isn't the project's, and isn't a helper crate
+/// depending on it by path
+fn nested_manifest(file: &Path, own: &BTreeSet<PathBuf>) -> Option<Candidate> {
+    if own.contains(&canonical(file)) {
+        return None;
+    }
+    let manifest = Manifest::from_path(file).ok()?;
+    let name = manifest.package_name()?.to_string();
+    let dir = canonical(file.parent()?);
+    let reaches_out = manifest
+        .get_dependencies()
+        .iter()
+        .filter_map(|(_, spec, _)| spec.path())
+        .any(|path| !canonical(&dir.join(path)).starts_with(&dir));
+    if reaches_out {
+        return None;
+    }
+    Some(Candidate {
+        kind: MarkerKind::NestedManifest,
+        name,
+        version: manifest.package_version(),
+        dir: file.parent()?.to_path_buf(),
+    })
+}
+
+/// A `#![crate_name = "..."]` attribute in a file opening with a license
+/// header
+fn crate_name_attribute(file: &Path) -> Option<Candidate> {
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    let attribute = ATTRIBUTE.get_or_init(|| {
+        Regex::new(r#"(?m)^\s*#!\[crate_name\s*=\s*"([A-Za-z0-9_-]+)"\s*\]"#).unwrap()
+    });
+    let content = fs::read_to_string(file).ok()?;
+    let name = attribute.captures(&content)?.get(1)?.as_str().to_string();
+    if !has_license_header(&content) {
+        return None;
+    }
+    Some(Candidate {
+        kind: MarkerKind::CrateNameAttribute,
+        name,
+        version: None,
+        dir: crate_dir(file)?,
+    })
+}
+
+/// A `lib.rs` in a directory named after a known crate, or in the `src/`
+/// of one
+fn known_crate_layout(file: &Path, known: &BTreeSet<String>) -> Option<Candidate> {
+    if file.file_name()? != "lib.rs" {
+        return None;
+    }
+    let dir = crate_dir(file)?;
+    let dir_name = dir.file_name()?.to_str()?.replace('-', "_");
+    let name = known
+        .iter()
+        .find(|name| name.replace('-', "_") == dir_name)?;
+    Some(Candidate {
+        kind: MarkerKind::KnownCrateLayout,
+        name: name.clone(),
+        version: None,
+        dir,
+    })
+}
+
+/// The directory of the crate `file` is the root of: its own, or the one
+/// above a `src/`
+fn crate_dir(file: &Path) -> Option<PathBuf> {
+    let dir = file.parent()?;
+    if dir.file_name().is_some_and(|n| n == "src") {
+        return dir.parent().map(Path::to_path_buf);
+    }
+    Some(dir.to_path_buf())
+}
+
+fn has_license_header(content: &str) -> bool {
+    const MARKERS: [&str; 5] = [
+        "SPDX-License-Identifier",
+        "Copyright",
+        "Licensed under",
+        "MIT License",
+        "Apache License",
+    ];
+    content
+        .lines()
+        .take(HEADER_LINES)
+        .any(|line| MARKERS.iter().any(|marker| line.contains(marker)))
+}
+
+/// The version in a `const VERSION: &str = "1.2.3"` in the `.rs` files
+/// directly in `dir` or its `src/`
+fn version_constant(dir: &Path) -> Option<Version> {
+    static CONSTANT: OnceLock<Regex> = OnceLock::new();
+    let constant = CONSTANT.get_or_init(|| {
+        Regex::new(
+            r#"(?m)^\s*(?:pub(?:\([a-z]+\))?\s+)?const\s+[A-Z_]*VERSION[A-Z_]*\s*:\s*&(?:'static\s+)?str\s*=\s*"v?(\d+\.\d+\.\d+[^"]*)""#,
+        )
+        .unwrap()
+    });
+    let mut files: Vec<PathBuf> = [dir.to_path_buf(), dir.join("src")]
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+        .collect();
+    files.sort();
+    files.iter().find_map(|file| {
+        let content = fs::read_to_string(file).ok()?;
+        Version::parse(constant.captures(&content)?.get(1)?.as_str()).ok()
+    })
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn relative(root: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(root)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::files::{collect_files, WalkOptions};
+
+    const LICENSED_ROOT: &str = "// Copyright 2018 The Servo Project Developers.\n\
+                                 // Licensed under the Apache License, Version 2.0 or MIT.\n\n";
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    /// A project with a copy of smallvec planted under `src/vendored/`
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "Cargo.toml",
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n\n[dependencies]\n\
+             helper = { path = \"crates/helper\" }\nlibc = \"0.2\"\n",
+        );
+        // The project's own crate roots, however licensed
+        write(
+            root,
+            "src/lib.rs",
+            &format!(
+                "{}#![crate_name = \"demo\"]\nmod vendored;\n",
+                LICENSED_ROOT
+            ),
+        );
+        write(
+            root,
+            "crates/helper/Cargo.toml",
+            "[package]\nname = \"helper\"\nversion = \"0.1.0\"\n",
+        );
+        write(root, "crates/helper/src/lib.rs", "");
+        // A fuzz harness depends on the project, so it's no copy
+        write(
+            root,
+            "fuzz/Cargo.toml",
+            "[package]\nname = \"demo-fuzz\"\nversion = \"0.0.0\"\n\n[dependencies]\n\
+             demo = { path = \"..\" }\n",
+        );
+        // The planted copy: its manifest and its crate root
+        write(
+            root,
+            "src/vendored/smallvec/Cargo.toml",
+            "# THIS FILE IS AUTOMATICALLY GENERATED BY CARGO\n[package]\nname = \"smallvec\"\n\
+             version = \"0.6.9\"\n",
+        );
+        write(
+            root,
+            "src/vendored/smallvec/src/lib.rs",
+            &format!("{}#![crate_name = \"smallvec\"]\n", LICENSED_ROOT),
+        );
+        // Pasted without a manifest, but under its crate name
+        write(
+            root,
+            "src/vendored/base64/lib.rs",
+            "pub const VERSION: &str = \"0.10.1\";\n",
+        );
+        // A module named after a crate, but no crate root
+        write(root, "src/serde/mod.rs", "");
+        // `cargo vendor` output, locked at the same version
+        write(
+            root,
+            "vendor/libc/Cargo.toml",
+            "[package]\nname = \"libc\"\nversion = \"0.2.150\"\n",
+        );
+        dir
+    }
+
+    #[test]
+    fn test_find_embedded_crates() {
+        let dir = fixture();
+        let root = dir.path();
+        let manifest = Manifest::from_path(&root.join("Cargo.toml")).unwrap();
+        let files = collect_files(root, &WalkOptions::default(), |path| {
+            path.file_name().is_some_and(|name| name == "Cargo.toml")
+                || path.extension().is_some_and(|ext| ext == "rs")
+        })
+        .unwrap();
+        let lockfile = Lockfile::parse(
+            root.join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"libc\"\nversion = \"0.2.150\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        )
+        .unwrap();
+        let known: BTreeSet<String> = ["base64", "serde", "libc"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+
+        let found = find_embedded_crates(
+            root,
+            &files,
+            &own_manifests(&manifest),
+            &known,
+            Some(&lockfile),
+        );
+        let summary: Vec<(String, &str, Option<String>, Vec<MarkerKind>)> = found
+            .iter()
+            .map(|copy| {
+                (
+                    copy.path.display().to_string(),
+                    copy.name.as_str(),
+                    copy.version.as_ref().map(ToString::to_string),
+                    copy.markers.iter().map(|m| m.kind).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "src/vendored/base64".to_string(),
+                    "base64",
+                    Some("0.10.1".to_string()),
+                    vec![MarkerKind::KnownCrateLayout],
+                ),
+                (
+                    "src/vendored/smallvec".to_string(),
+                    "smallvec",
+                    Some("0.6.9".to_string()),
+                    vec![MarkerKind::NestedManifest, MarkerKind::CrateNameAttribute],
+                ),
+            ]
+        );
+        assert_eq!(
+            found[1].markers[1].file,
+            PathBuf::from("src/vendored/smallvec/src/lib.rs")
+        );
+
+        // Without Cargo.lock, the vendored libc is a copy like any other
+        let found = find_embedded_crates(root, &files, &own_manifests(&manifest), &known, None);
+        assert!(found.iter().any(|copy| copy.name == "libc"), "{:?}", found);
+    }
+
+    #[test]
+    fn test_license_header_is_required_for_crate_name() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "gen/lib.rs", "#![crate_name = \"generated\"]\n");
+        assert!(crate_name_attribute(&dir.path().join("gen/lib.rs")).is_none());
+        write(
+            dir.path(),
+            "gen/lib.rs",
+            "// SPDX-License-Identifier: MIT\n#![crate_name = \"generated\"]\n",
+        );
+        let candidate = crate_name_attribute(&dir.path().join("gen/lib.rs")).unwrap();
+        assert_eq!(candidate.name, "generated");
+        assert_eq!(candidate.dir, dir.path().join("gen"));
+    }
+}
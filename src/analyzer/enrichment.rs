@@ -0,0 +1,363 @@
+//! Registry details for the most exposed of the scanned packages
+//!
+//! Matching advisories is local, so `health` covers every scanned package.
+//! Looking a package up on crates.io costs two requests, so
+//! `health --enrich` spends them on a budget of the most exposed packages:
+//! direct dependencies first, then crates that parse untrusted input (a
+//! curated table in `untrusted_input.toml`), then the most downloaded. The
+//! packages past the budget are reported as advisory-only.
+
+use crate::core::config::Settings;
+use crate::core::version::PublishedVersion;
+use crate::utils::crates_io::CratesIoClient;
+use crate::utils::formatting::format_date;
+use crate::utils::registry::RegistryProvider;
+use anyhow::{Context, Result};
+use futures::stream::{self, StreamExt};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+const TABLE: &str = include_str!("untrusted_input.toml");
+
+/// How many packages `health --enrich` looks up when neither the config
+/// nor `--enrich-limit` says
+pub const DEFAULT_ENRICH_LIMIT: usize = Settings::DEFAULT.enrich_limit;
+
+/// Two years without a release suggests nobody maintains a crate
+const DORMANT_AFTER_SECS: u64 = 2 * 365 * 86_400;
+
+#[derive(Deserialize)]
+struct Table {
+    crates: Vec<String>,
+}
+
+/// The built-in table of crates that parse untrusted input
+pub fn untrusted_input_parsers() -> Result<Vec<String>> {
+    let table: Table =
+        toml::from_str(TABLE).context("Failed to parse the untrusted input table")?;
+    Ok(table.crates)
+}
+
+/// Why a package is worth a registry lookup, most exposed first
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Exposure {
+    /// The manifest depends on it
+    Direct,
+    /// A transitive package that parses untrusted input
+    UntrustedInput,
+    Transitive,
+}
+
+/// A scanned registry package competing for the enrichment budget
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub name: String,
+    pub version: Version,
+    pub exposure: Exposure,
+    /// All-time downloads, when they were looked up
+    pub downloads: Option<u64>,
+}
+
+impl Candidate {
+    pub fn new(name: String, version: Version, direct: bool, parsers: &[String]) -> Self {
+        let exposure = if direct {
+            Exposure::Direct
+        } else if parsers.contains(&name) {
+            Exposure::UntrustedInput
+        } else {
+            Exposure::Transitive
+        };
+        Self {
+            name,
+            version,
+            exposure,
+            downloads: None,
+        }
+    }
+
+    fn label(&self) -> String {
+        format!("{}@{}", self.name, self.version)
+    }
+}
+
+/// Order candidates most exposed first: by exposure, then by downloads
+/// (unknown counts last), then by name and version so the order is stable
+pub fn rank_by_exposure(candidates: &mut [Candidate]) {
+    candidates.sort_by(|a, b| {
+        (a.exposure, Reverse(a.downloads), &a.name, &a.version).cmp(&(
+            b.exposure,
+            Reverse(b.downloads),
+            &b.name,
+            &b.version,
+        ))
+    });
+}
+
+/// Split ranked candidates into the first `limit` (all of them for 0) and
+/// the rest
+pub fn split_budget(mut ranked: Vec<Candidate>, limit: usize) -> (Vec<Candidate>, Vec<Candidate>) {
+    if limit == 0 || limit >= ranked.len() {
+        return (ranked, Vec::new());
+    }
+    let rest = ranked.split_off(limit);
+    (ranked, rest)
+}
+
+/// Whether download counts decide which candidates fit in `limit`: only
+/// when the budget runs out among the plain transitive packages
+pub fn needs_downloads(candidates: &[Candidate], limit: usize) -> bool {
+    let ahead = candidates
+        .iter()
+        .filter(|c| c.exposure < Exposure::Transitive)
+        .count();
+    limit != 0 && candidates.len() > limit && ahead < limit
+}
+
+/// What crates.io says about a package version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EnrichedPackage {
+    pub name: String,
+    pub version: Version,
+    pub exposure: Exposure,
+    /// The license the locked version was published under
+    pub license: Option<String>,
+    /// The crate's repository link
+    pub repository: Option<String>,
+    /// When the newest non-yanked release came out, as Unix seconds
+    pub last_release: Option<u64>,
+    /// Whether the locked version was yanked
+    pub yanked: bool,
+}
+
+impl EnrichedPackage {
+    /// What a reviewer should look at, if anything
+    pub fn concerns(&self, now: u64) -> Vec<String> {
+        let mut concerns = Vec::new();
+        if self.yanked {
+            concerns.push(format!("{} is yanked", self.version));
+        }
+        if let Some(released) = self
+            .last_release
+            .filter(|released| now.saturating_sub(*released) > DORMANT_AFTER_SECS)
+        {
+            concerns.push(format!(
+                "no release since {}, may be unmaintained",
+                format_date(released)
+            ));
+        }
+        if self.repository.is_none() {
+            concerns.push("no repository link".to_string());
+        }
+        if self.license.is_none() {
+            concerns.push("no license on crates.io".to_string());
+        }
+        concerns
+    }
+}
+
+/// The packages `health --enrich` looked up, and those it left to advisory
+/// matching alone
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Enrichment {
+    /// The budget: how many packages could be looked up (0 for all)
+    pub limit: usize,
+    pub enriched: Vec<EnrichedPackage>,
+    /// `name@version` of the packages only matched against advisories
+    pub advisory_only: Vec<String>,
+    /// `name@version` of the packages whose lookup failed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed: Vec<String>,
+}
+
+/// Rank `candidates` by exposure and look the first `limit` up on crates.io
+pub async fn enrich(
+    client: &CratesIoClient,
+    mut candidates: Vec<Candidate>,
+    limit: usize,
+    concurrency: usize,
+) -> Enrichment {
+    if needs_downloads(&candidates, limit) {
+        let names: Vec<&str> = candidates
+            .iter()
+            .filter(|c| c.exposure == Exposure::Transitive)
+            .map(|c| c.name.as_str())
+            .collect();
+        // Without the counts, the rest is ranked by name
+        if let Ok(downloads) = client.get_downloads(&names).await {
+            for candidate in &mut candidates {
+                candidate.downloads = downloads.get(&candidate.name).copied();
+            }
+        }
+    }
+    rank_by_exposure(&mut candidates);
+    let (chosen, rest) = split_budget(candidates, limit);
+
+    let lookups = chosen.into_iter().map(|candidate| async move {
+        let (krate, versions) = futures::join!(
+            client.get_crate(&candidate.name),
+            client.get_published_versions(&candidate.name)
+        );
+        match (krate, versions) {
+            (Ok(krate), Ok(versions)) => Ok(enriched(candidate, krate.repository, &versions)),
+            _ => Err(candidate.label()),
+        }
+    });
+    let results: Vec<std::result::Result<EnrichedPackage, String>> = stream::iter(lookups)
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut enrichment = Enrichment {
+        limit,
+        enriched: Vec::new(),
+        advisory_only: rest.iter().map(Candidate::label).collect(),
+        failed: Vec::new(),
+    };
+    for result in results {
+        match result {
+            Ok(package) => enrichment.enriched.push(package),
+            Err(label) => enrichment.failed.push(label),
+        }
+    }
+    enrichment
+}
+
+fn enriched(
+    candidate: Candidate,
+    repository: Option<String>,
+    versions: &[PublishedVersion],
+) -> EnrichedPackage {
+    let locked = versions.iter().find(|v| v.version == candidate.version);
+    EnrichedPackage {
+        license: locked.and_then(|v| v.license.clone()),
+        repository: repository.filter(|r| !r.trim().is_empty()),
+        last_release: versions
+            .iter()
+            .filter(|v| !v.yanked)
+            .filter_map(|v| v.created_at)
+            .max(),
+        yanked: locked.is_some_and(|v| v.yanked),
+        name: candidate.name,
+        version: candidate.version,
+        exposure: candidate.exposure,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn candidate(name: &str, direct: bool, downloads: Option<u64>) -> Candidate {
+        let parsers = vec!["httparse".to_string(), "flate2".to_string()];
+        Candidate {
+            downloads,
+            ..Candidate::new(name.to_string(), Version::new(1, 0, 0), direct, &parsers)
+        }
+    }
+
+    fn names(candidates: &[Candidate]) -> Vec<&str> {
+        candidates.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_table_is_well_formed() {
+        let parsers = untrusted_input_parsers().unwrap();
+        assert!(!parsers.is_empty());
+        let mut seen = BTreeSet::new();
+        for name in &parsers {
+            assert!(seen.insert(name), "{} twice", name);
+        }
+    }
+
+    #[test]
+    fn test_ranking_puts_direct_then_parsers_then_downloads() {
+        let mut candidates = vec![
+            candidate("tiny", false, Some(10)),
+            candidate("flate2", false, Some(5)),
+            candidate("unknown", false, None),
+            candidate("serde", true, None),
+            candidate("popular", false, Some(1_000)),
+            candidate("httparse", false, None),
+            // A direct dependency that parses input is direct first
+            candidate("httparse", true, None),
+            candidate("anyhow", true, Some(1)),
+        ];
+        rank_by_exposure(&mut candidates);
+        assert_eq!(
+            names(&candidates),
+            ["anyhow", "httparse", "serde", "flate2", "httparse", "popular", "tiny", "unknown"]
+        );
+        assert_eq!(candidates[3].exposure, Exposure::UntrustedInput);
+        assert_eq!(candidates[5].exposure, Exposure::Transitive);
+    }
+
+    #[test]
+    fn test_budget_split() {
+        let ranked = vec![
+            candidate("a", true, None),
+            candidate("b", false, None),
+            candidate("c", false, None),
+        ];
+        let (enriched, rest) = split_budget(ranked.clone(), 2);
+        assert_eq!(
+            (names(&enriched), names(&rest)),
+            (vec!["a", "b"], vec!["c"])
+        );
+        let (enriched, rest) = split_budget(ranked.clone(), 0);
+        assert_eq!(enriched.len(), 3);
+        assert!(rest.is_empty());
+        let (enriched, _) = split_budget(ranked, 10);
+        assert_eq!(enriched.len(), 3);
+    }
+
+    #[test]
+    fn test_downloads_only_matter_past_the_exposed() {
+        let candidates = vec![
+            candidate("a", true, None),
+            candidate("httparse", false, None),
+            candidate("b", false, None),
+            candidate("c", false, None),
+        ];
+        // The budget ends among the plain transitive packages
+        assert!(needs_downloads(&candidates, 3));
+        // Everything fits, or everything left out is plainly transitive
+        assert!(!needs_downloads(&candidates, 4));
+        assert!(!needs_downloads(&candidates, 0));
+        assert!(!needs_downloads(&candidates, 2));
+    }
+
+    #[test]
+    fn test_concerns() {
+        const NOW: u64 = 1_709_296_245;
+        let mut package = EnrichedPackage {
+            name: "demo".to_string(),
+            version: Version::new(1, 0, 0),
+            exposure: Exposure::Direct,
+            license: Some("MIT".to_string()),
+            repository: Some("https://github.com/me/demo".to_string()),
+            last_release: Some(NOW - 86_400),
+            yanked: false,
+        };
+        assert!(package.concerns(NOW).is_empty());
+
+        package.last_release = Some(NOW - 3 * 365 * 86_400);
+        package.yanked = true;
+        package.repository = None;
+        package.license = None;
+        assert_eq!(
+            package.concerns(NOW),
+            [
+                "1.0.0 is yanked",
+                "no release since 2021-03-02, may be unmaintained",
+                "no repository link",
+                "no license on crates.io",
+            ]
+        );
+    }
+}
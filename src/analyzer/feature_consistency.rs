@@ -0,0 +1,405 @@
+//! Find shared dependencies whose workspace members declare them differently
+//!
+//! Cargo builds a workspace with the union of the features its members ask
+//! for, and with default features on as soon as one member leaves them on.
+//! A member built on its own gets only what it declared, so feature sets
+//! that differ between members, legacy feature names (a curated table in
+//! `feature_aliases.toml`) and disagreeing `default-features` flags make
+//! builds differ in ways nobody wrote down. Each finding carries the unified
+//! declaration to promote to `[workspace.dependencies]`.
+
+use crate::analyzer::checker::parse_version_req;
+use crate::core::manifest::{DependencySection, DependencySpec, Manifest};
+use crate::core::workspace::Workspace;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+const TABLE: &str = include_str!("feature_aliases.toml");
+
+/// A feature name that enables the same thing as the documented one
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeatureAlias {
+    #[serde(rename = "crate")]
+    pub name: String,
+    pub legacy: String,
+    /// The documented name to use instead
+    pub feature: String,
+}
+
+#[derive(Deserialize)]
+struct Table {
+    alias: Vec<FeatureAlias>,
+}
+
+/// The built-in alias table
+pub fn feature_aliases() -> Result<Vec<FeatureAlias>> {
+    let table: Table = toml::from_str(TABLE).context("Failed to parse the feature alias table")?;
+    Ok(table.alias)
+}
+
+/// How one member declares a shared dependency in one section. Inherited
+/// declarations show what they inherit, with the member's own features added.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MemberDeclaration {
+    pub member: String,
+    pub manifest: std::path::PathBuf,
+    pub section: DependencySection,
+    pub requirement: Option<String>,
+    /// Features as written
+    pub features: Vec<String>,
+    pub default_features: bool,
+    /// `{ workspace = true }`
+    pub inherited: bool,
+    pub line: Option<usize>,
+}
+
+/// A feature of the shared dependency and the members enabling it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FeatureRow {
+    /// The documented name, whichever way members spell it
+    pub feature: String,
+    pub members: Vec<String>,
+}
+
+/// A legacy feature name one member uses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LegacyFeature {
+    pub member: String,
+    pub legacy: String,
+    pub feature: String,
+}
+
+/// The declaration every member can share: what cargo builds the whole
+/// workspace with today
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct UnifiedDeclaration {
+    /// The highest requirement any member asks for
+    pub requirement: Option<String>,
+    pub features: Vec<String>,
+    pub default_features: bool,
+}
+
+/// A dependency the members of a workspace declare inconsistently
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FeatureInconsistency {
+    pub name: String,
+    pub declarations: Vec<MemberDeclaration>,
+    /// Which member enables which feature
+    pub matrix: Vec<FeatureRow>,
+    pub legacy_names: Vec<LegacyFeature>,
+    pub features_differ: bool,
+    pub default_features_differ: bool,
+    pub unified: UnifiedDeclaration,
+}
+
+impl UnifiedDeclaration {
+    /// The declaration as an inline TOML table
+    pub fn to_toml(&self) -> String {
+        let mut keys = Vec::new();
+        if let Some(requirement) = &self.requirement {
+            keys.push(format!("version = {}", quote(requirement)));
+        }
+        if !self.default_features {
+            keys.push("default-features = false".to_string());
+        }
+        if !self.features.is_empty() {
+            let features: Vec<String> = self.features.iter().map(|f| quote(f)).collect();
+            keys.push(format!("features = [{}]", features.join(", ")));
+        }
+        format!("{{ {} }}", keys.join(", "))
+    }
+}
+
+impl FeatureInconsistency {
+    /// The TOML to promote the dependency to `[workspace.dependencies]`,
+    /// and what members declare instead
+    pub fn snippet(&self) -> String {
+        format!(
+            "[workspace.dependencies]\n{} = {}\n\n# In each member, instead of its own declaration:\n{} = {{ workspace = true }}\n",
+            self.name,
+            self.unified.to_toml(),
+            self.name
+        )
+    }
+}
+
+fn quote(text: &str) -> String {
+    toml::Value::String(text.to_string()).to_string()
+}
+
+/// Find registry dependencies declared by two or more members of
+/// `workspace` with different features, legacy feature names or different
+/// `default-features` flags. Renamed declarations are left out: only
+/// declarations under the package's own name can share one entry.
+pub fn find_feature_inconsistencies(
+    workspace: &Workspace,
+    aliases: &[FeatureAlias],
+) -> Vec<FeatureInconsistency> {
+    let inherited = workspace
+        .root
+        .content
+        .workspace
+        .as_ref()
+        .map(|table| &table.dependencies);
+
+    let mut groups: BTreeMap<String, Vec<MemberDeclaration>> = BTreeMap::new();
+    for manifest in &workspace.members {
+        for (section, name, spec) in manifest.declarations() {
+            if spec.package().is_some_and(|package| package != name) {
+                continue;
+            }
+            let base = match spec.inherits_workspace() {
+                true => match inherited.and_then(|deps| deps.get(&name)) {
+                    Some(base) => base,
+                    None => continue,
+                },
+                false => &spec,
+            };
+            if !base.is_crates_io() {
+                continue;
+            }
+            let declaration = declaration(manifest, section, &name, &spec, base);
+            groups.entry(name).or_default().push(declaration);
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter_map(|(name, declarations)| inconsistency(name, declarations, aliases))
+        .collect()
+}
+
+fn declaration(
+    manifest: &Manifest,
+    section: DependencySection,
+    name: &str,
+    spec: &DependencySpec,
+    base: &DependencySpec,
+) -> MemberDeclaration {
+    let mut features = base.features().to_vec();
+    if spec.inherits_workspace() {
+        features.extend(spec.features().iter().cloned());
+    }
+    MemberDeclaration {
+        member: Workspace::member_name(manifest),
+        manifest: manifest.path.clone(),
+        line: manifest.location_in(name, &section).map(|(line, _)| line),
+        section,
+        requirement: base.version().map(str::to_string),
+        features,
+        default_features: base.default_features(),
+        inherited: spec.inherits_workspace(),
+    }
+}
+
+fn inconsistency(
+    name: String,
+    declarations: Vec<MemberDeclaration>,
+    aliases: &[FeatureAlias],
+) -> Option<FeatureInconsistency> {
+    let members: BTreeSet<&str> = declarations.iter().map(|d| d.member.as_str()).collect();
+    if members.len() < 2 {
+        return None;
+    }
+
+    let documented = |feature: &str| {
+        aliases
+            .iter()
+            .find(|a| a.name == name && a.legacy == feature)
+            .map_or(feature.to_string(), |a| a.feature.clone())
+    };
+    let feature_sets: Vec<BTreeSet<String>> = declarations
+        .iter()
+        .map(|d| d.features.iter().map(|f| documented(f)).collect())
+        .collect();
+    let features_differ = feature_sets.iter().any(|set| set != &feature_sets[0]);
+    let default_features_differ = declarations
+        .iter()
+        .any(|d| d.default_features != declarations[0].default_features);
+
+    let mut legacy_names = Vec::new();
+    for declaration in &declarations {
+        for feature in &declaration.features {
+            let documented = documented(feature);
+            let legacy = LegacyFeature {
+                member: declaration.member.clone(),
+                legacy: feature.clone(),
+                feature: documented.clone(),
+            };
+            if &documented != feature && !legacy_names.contains(&legacy) {
+                legacy_names.push(legacy);
+            }
+        }
+    }
+
+    if !features_differ && !default_features_differ && legacy_names.is_empty() {
+        return None;
+    }
+
+    let union: BTreeSet<&String> = feature_sets.iter().flatten().collect();
+    let matrix = union
+        .iter()
+        .map(|feature| {
+            let mut members: Vec<String> = Vec::new();
+            for (declaration, set) in declarations.iter().zip(&feature_sets) {
+                if set.contains(*feature) && !members.contains(&declaration.member) {
+                    members.push(declaration.member.clone());
+                }
+            }
+            FeatureRow {
+                feature: feature.to_string(),
+                members,
+            }
+        })
+        .collect();
+    let unified = UnifiedDeclaration {
+        requirement: declarations
+            .iter()
+            .filter_map(|d| d.requirement.as_deref())
+            .max_by_key(|requirement| parse_version_req(requirement))
+            .map(str::to_string),
+        features: union.into_iter().cloned().collect(),
+        default_features: declarations.iter().any(|d| d.default_features),
+    };
+
+    Some(FeatureInconsistency {
+        name,
+        declarations,
+        matrix,
+        legacy_names,
+        features_differ,
+        default_features_differ,
+        unified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn workspace(root: &str, members: &[(&str, &str)]) -> Workspace {
+        let root = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            &format!("[workspace]\nmembers = [\"crates/*\"]\n{}", root),
+        )
+        .unwrap();
+        let members = members
+            .iter()
+            .map(|(name, dependencies)| {
+                Manifest::parse(
+                    PathBuf::from(format!("crates/{}/Cargo.toml", name)),
+                    &format!(
+                        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n{}",
+                        name, dependencies
+                    ),
+                )
+                .unwrap()
+            })
+            .collect();
+        Workspace { root, members }
+    }
+
+    fn find(workspace: &Workspace) -> Vec<FeatureInconsistency> {
+        find_feature_inconsistencies(workspace, &feature_aliases().unwrap())
+    }
+
+    #[test]
+    fn test_table_is_well_formed() {
+        let aliases = feature_aliases().unwrap();
+        assert!(!aliases.is_empty());
+        let mut seen = BTreeSet::new();
+        for alias in &aliases {
+            assert!(
+                seen.insert((&alias.name, &alias.legacy)),
+                "{} twice",
+                alias.legacy
+            );
+            assert_ne!(alias.legacy, alias.feature, "{}", alias.name);
+        }
+    }
+
+    #[test]
+    fn test_legacy_spelling_and_default_features() {
+        let workspace = workspace(
+            "",
+            &[
+                (
+                    "api",
+                    "[dependencies]\nserde = { version = \"1.0.190\", features = [\"derive\"] }\ntokio = \"1\"\n",
+                ),
+                (
+                    "cli",
+                    "[dependencies]\nserde = { version = \"1.0.200\", features = [\"serde_derive\"] }\n\
+                     tokio = { version = \"1\", default-features = false, features = [\"rt\"] }\n",
+                ),
+            ],
+        );
+        let found = find(&workspace);
+        assert_eq!(found.len(), 2);
+
+        // The same features, spelled two ways
+        let serde = &found[0];
+        assert_eq!(serde.name, "serde");
+        assert!(!serde.features_differ && !serde.default_features_differ);
+        assert_eq!(
+            serde.legacy_names,
+            [LegacyFeature {
+                member: "cli".to_string(),
+                legacy: "serde_derive".to_string(),
+                feature: "derive".to_string(),
+            }]
+        );
+        assert_eq!(serde.matrix[0].members, ["api", "cli"]);
+        assert_eq!(
+            serde.unified.to_toml(),
+            r#"{ version = "1.0.200", features = ["derive"] }"#
+        );
+
+        // Defaults stay on: one member asks for them
+        let tokio = &found[1];
+        assert!(tokio.features_differ && tokio.default_features_differ);
+        assert!(tokio.unified.default_features);
+        assert_eq!(tokio.matrix[0].feature, "rt");
+        assert_eq!(tokio.matrix[0].members, ["cli"]);
+        assert_eq!(
+            tokio.snippet(),
+            "[workspace.dependencies]\ntokio = { version = \"1\", features = [\"rt\"] }\n\n\
+             # In each member, instead of its own declaration:\ntokio = { workspace = true }\n"
+        );
+    }
+
+    #[test]
+    fn test_inherited_declarations_and_agreement() {
+        let workspace = workspace(
+            "\n[workspace.dependencies]\nanyhow = \"1\"\nclap = { version = \"4\", default-features = false }\n",
+            &[
+                (
+                    "api",
+                    "[dependencies]\nanyhow = { workspace = true }\nclap = { workspace = true, features = [\"std\"] }\n\
+                     log = \"0.4\"\nrenamed = { package = \"serde\", version = \"1\" }\n",
+                ),
+                (
+                    "cli",
+                    "[dependencies]\nanyhow = \"1\"\nclap = { workspace = true }\nlog = \"0.4\"\n\
+                     serde = { version = \"1\", features = [\"rc\"] }\n",
+                ),
+            ],
+        );
+        let found = find(&workspace);
+        // anyhow and log agree; the renamed serde isn't matched by name
+        let names: Vec<&str> = found.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["clap"]);
+
+        let clap = &found[0];
+        assert!(clap.declarations.iter().all(|d| d.inherited));
+        assert_eq!(clap.declarations[0].features, ["std"]);
+        assert!(!clap.unified.default_features);
+        assert_eq!(
+            clap.unified.to_toml(),
+            r#"{ version = "4", default-features = false, features = ["std"] }"#
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Check the `[features]` table against the dependencies it refers to
+//!
+//! Removing a dependency leaves behind every feature that enables it, and
+//! cargo only complains to whoever turns that feature on. Each reference is
+//! checked against the manifest's own declarations: `dep:name` and a bare
+//! `name` need an optional dependency, `name/feature` and `name?/feature` a
+//! dependency at all. Optional dependencies no feature turns on are noted
+//! too; only their implicit feature enables them, which is easy to miss
+//! when the dependency itself is no longer needed.
+
+use crate::analyzer::lint::LintSeverity;
+use crate::core::dependency::DependencyKind;
+use crate::core::manifest::Manifest;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use toml_edit::Document;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FeatureIssue {
+    /// `dep:name` or `name/feature` names no dependency
+    MissingDependency,
+    /// `dep:name` or a bare `name` names a dependency that isn't optional
+    NotOptional,
+    /// A bare `name` that is neither a feature nor a dependency
+    UnknownFeature,
+    /// An optional dependency no feature enables
+    Orphaned,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureFinding {
+    /// The `[features]` entry the reference is in; none for an orphaned
+    /// dependency
+    pub feature: Option<String>,
+    /// The reference as written, e.g. `dep:native-tls`
+    pub reference: Option<String>,
+    /// The dependency or feature referred to
+    pub name: String,
+    pub kind: FeatureIssue,
+    pub severity: LintSeverity,
+    pub message: String,
+    pub suggestion: Option<String>,
+    pub line: Option<usize>,
+}
+
+impl FeatureFinding {
+    /// Whether `lint --fix` can remove the dead reference by itself
+    pub fn is_fixable(&self) -> bool {
+        matches!(
+            self.kind,
+            FeatureIssue::MissingDependency | FeatureIssue::UnknownFeature
+        ) && self.reference.is_some()
+    }
+}
+
+/// A `[features]` reference, split into its parts
+pub(crate) enum Reference<'a> {
+    /// `dep:name`
+    Dependency(&'a str),
+    /// `name/feature`, or `name?/feature` when `weak`
+    DependencyFeature { name: &'a str, weak: bool },
+    /// A bare `name`
+    Feature(&'a str),
+}
+
+impl<'a> Reference<'a> {
+    pub(crate) fn parse(reference: &'a str) -> Self {
+        if let Some(name) = reference.strip_prefix("dep:") {
+            return Reference::Dependency(name);
+        }
+        match reference.split_once('/') {
+            Some((name, _)) => match name.strip_suffix('?') {
+                Some(name) => Reference::DependencyFeature { name, weak: true },
+                None => Reference::DependencyFeature { name, weak: false },
+            },
+            None => Reference::Feature(reference),
+        }
+    }
+}
+
+/// Check the `[features]` table of `manifest`, whose text is `content`.
+/// Dev-dependencies can't be enabled by a feature, so only the other
+/// sections count.
+pub fn find_feature_issues(manifest: &Manifest, content: &str) -> Vec<FeatureFinding> {
+    let Ok(document) = Document::parse(content) else {
+        return Vec::new();
+    };
+    let line_of = |offset: usize| content[..offset].matches('\n').count() + 1;
+
+    // Whether each dependency that isn't a dev-dependency is optional
+    let mut dependencies: BTreeMap<String, bool> = BTreeMap::new();
+    for (section, name, spec) in manifest.declarations() {
+        if section.kind != DependencyKind::Dev {
+            *dependencies.entry(name).or_default() |= spec.is_optional();
+        }
+    }
+
+    let mut entries: Vec<(&str, &str, Option<usize>)> = Vec::new();
+    if let Some(features) = document.get("features").and_then(|f| f.as_table_like()) {
+        for (feature, item) in features.iter() {
+            let Some(array) = item.as_array() else {
+                continue;
+            };
+            for value in array.iter() {
+                if let Some(reference) = value.as_str() {
+                    entries.push((feature, reference, value.span().map(|s| line_of(s.start))));
+                }
+            }
+        }
+    }
+    let feature_names: BTreeSet<&str> = document
+        .get("features")
+        .and_then(|f| f.as_table_like())
+        .map(|features| features.iter().map(|(name, _)| name).collect())
+        .unwrap_or_default();
+
+    let mut findings = Vec::new();
+    let mut enabled: BTreeSet<&str> = BTreeSet::new();
+    for &(feature, reference, line) in &entries {
+        let finding =
+            |name: &str, kind, message: String, suggestion: Option<String>| FeatureFinding {
+                feature: Some(feature.to_string()),
+                reference: Some(reference.to_string()),
+                name: name.to_string(),
+                kind,
+                severity: LintSeverity::Error,
+                message,
+                suggestion,
+                line,
+            };
+        let remove = Some(format!("remove \"{}\" from {}", reference, feature));
+        match Reference::parse(reference) {
+            Reference::Dependency(name) => match dependencies.get(name) {
+                None => findings.push(finding(
+                    name,
+                    FeatureIssue::MissingDependency,
+                    format!("enables {}, which isn't a dependency", name),
+                    remove,
+                )),
+                Some(false) => findings.push(finding(
+                    name,
+                    FeatureIssue::NotOptional,
+                    format!("enables {}, which isn't an optional dependency", name),
+                    Some(format!("add `optional = true` to {}", name)),
+                )),
+                Some(true) => {
+                    enabled.insert(name);
+                }
+            },
+            Reference::DependencyFeature { name, weak } => {
+                if !dependencies.contains_key(name) {
+                    findings.push(finding(
+                        name,
+                        FeatureIssue::MissingDependency,
+                        format!("enables a feature of {}, which isn't a dependency", name),
+                        remove,
+                    ));
+                } else if !weak {
+                    enabled.insert(name);
+                }
+            }
+            Reference::Feature(name) => match dependencies.get(name) {
+                _ if feature_names.contains(name) => {}
+                None => findings.push(finding(
+                    name,
+                    FeatureIssue::UnknownFeature,
+                    format!("{} is neither a feature nor a dependency", name),
+                    remove,
+                )),
+                Some(false) => findings.push(finding(
+                    name,
+                    FeatureIssue::NotOptional,
+                    format!("enables {}, which isn't an optional dependency", name),
+                    Some(format!("add `optional = true` to {}", name)),
+                )),
+                Some(true) => {
+                    enabled.insert(name);
+                }
+            },
+        }
+    }
+
+    for (section, name, spec) in manifest.declarations() {
+        if !spec.is_optional() || enabled.contains(name.as_str()) {
+            continue;
+        }
+        if findings
+            .iter()
+            .any(|f| f.kind == FeatureIssue::Orphaned && f.name == name)
+        {
+            continue;
+        }
+        findings.push(FeatureFinding {
+            feature: None,
+            reference: None,
+            line: manifest.location_in(&name, &section).map(|(line, _)| line),
+            message: format!(
+                "is optional, but no feature enables it; only its implicit `{}` feature does",
+                name
+            ),
+            suggestion: Some(format!(
+                "{} = [\"dep:{}\"] under [features], or remove the dependency",
+                name, name
+            )),
+            name,
+            kind: FeatureIssue::Orphaned,
+            severity: LintSeverity::Info,
+        });
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn check(content: &str) -> Vec<FeatureFinding> {
+        let manifest = Manifest::parse(PathBuf::from("Cargo.toml"), content).unwrap();
+        find_feature_issues(&manifest, content)
+    }
+
+    #[test]
+    fn test_every_kind_of_dead_reference() {
+        let findings = check(
+            r#"[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+serde = { version = "1", optional = true }
+log = "0.4"
+rustls = { version = "0.23", optional = true }
+chrono = { version = "0.4", optional = true }
+
+[dev-dependencies]
+proptest = "1"
+
+[features]
+default = ["std"]
+std = ["serde?/std", "log/std"]
+tls = [
+    "dep:native-tls",
+    "dep:rustls",
+]
+json = ["dep:serde", "serde_json/std"]
+logging = ["dep:log"]
+legacy = ["openssl", "std"]
+fuzz = ["proptest/std"]
+"#,
+        );
+
+        let found: Vec<(Option<&str>, &str, FeatureIssue, Option<usize>)> = findings
+            .iter()
+            .map(|f| (f.feature.as_deref(), f.name.as_str(), f.kind, f.line))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    Some("tls"),
+                    "native-tls",
+                    FeatureIssue::MissingDependency,
+                    Some(18)
+                ),
+                (
+                    Some("json"),
+                    "serde_json",
+                    FeatureIssue::MissingDependency,
+                    Some(21)
+                ),
+                (Some("logging"), "log", FeatureIssue::NotOptional, Some(22)),
+                (
+                    Some("legacy"),
+                    "openssl",
+                    FeatureIssue::UnknownFeature,
+                    Some(23)
+                ),
+                // Features can't reach dev-dependencies
+                (
+                    Some("fuzz"),
+                    "proptest",
+                    FeatureIssue::MissingDependency,
+                    Some(24)
+                ),
+                (None, "chrono", FeatureIssue::Orphaned, Some(9)),
+            ]
+        );
+        assert!(findings[0].is_fixable());
+        assert!(!findings[2].is_fixable());
+        assert!(!findings[5].is_fixable());
+        assert_eq!(findings[5].severity, LintSeverity::Info);
+    }
+
+    #[test]
+    fn test_consistent_table_passes() {
+        let findings = check(
+            r#"[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+serde = { version = "1", optional = true }
+
+[target.'cfg(unix)'.dependencies]
+nix = { version = "0.29", optional = true }
+
+[features]
+serde = ["dep:serde"]
+unix = ["nix/fs"]
+"#,
+        );
+        assert!(findings.is_empty(), "{:?}", findings);
+    }
+}
@@ -0,0 +1,120 @@
+//! Summarize the optional features enabled on dependencies
+//!
+//! What a manifest asks for is only half the story: cargo unifies features
+//! across every dependent of a crate, so the set that ends up compiled can be
+//! larger than anything written in Cargo.toml. When `cargo metadata` is
+//! available the resolved set is recorded next to the requested one.
+
+use crate::core::manifest::{DependencySection, Manifest};
+use crate::utils::cargo::Metadata;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Requested and resolved features of one dependency declaration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FeatureUsage {
+    pub name: String,
+    pub section: DependencySection,
+    /// Features listed explicitly in the declaration
+    pub requested: Vec<String>,
+    pub default_features: bool,
+    /// The unified feature set cargo resolved, when metadata was available
+    pub resolved: Option<Vec<String>>,
+}
+
+impl FeatureUsage {
+    /// Resolved features nobody asked for in this manifest, pulled in by
+    /// default features or by unification with other dependents
+    pub fn unrequested(&self) -> Vec<&str> {
+        self.resolved
+            .iter()
+            .flatten()
+            .filter(|f| *f != "default" && !self.requested.contains(f))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Whether the declaration deviates from plain defaults in any way
+    pub fn is_notable(&self) -> bool {
+        !self.requested.is_empty() || !self.default_features || !self.unrequested().is_empty()
+    }
+}
+
+/// Collect the feature settings of every dependency declaration, resolving
+/// them against `metadata` when it is available
+pub fn feature_usage(manifest: &Manifest, metadata: Option<&Metadata>) -> Vec<FeatureUsage> {
+    let root = metadata.and_then(|m| m.package_for_manifest(&manifest.path));
+
+    manifest
+        .declarations()
+        .into_iter()
+        .map(|(section, name, spec)| {
+            let mut requested = spec.features().to_vec();
+            requested.sort();
+            let resolved = metadata
+                .zip(root)
+                .and_then(|(metadata, root)| metadata.resolved_features(&root.id, &name));
+
+            FeatureUsage {
+                requested,
+                default_features: spec.default_features(),
+                resolved,
+                section,
+                name,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_feature_usage_without_metadata() {
+        let manifest = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            r#"[dependencies]
+serde = { version = "1.0", features = ["derive", "alloc"] }
+regex = "1"
+
+[dev-dependencies]
+tokio = { version = "1", default-features = false, features = ["rt"] }
+"#,
+        )
+        .unwrap();
+
+        let usage = feature_usage(&manifest, None);
+        assert_eq!(usage.len(), 3);
+
+        let serde = usage.iter().find(|u| u.name == "serde").unwrap();
+        assert_eq!(serde.requested, vec!["alloc", "derive"]);
+        assert!(serde.default_features);
+        assert!(serde.resolved.is_none());
+        assert!(serde.is_notable());
+
+        let regex = usage.iter().find(|u| u.name == "regex").unwrap();
+        assert!(!regex.is_notable());
+
+        let tokio = usage.iter().find(|u| u.name == "tokio").unwrap();
+        assert!(!tokio.default_features);
+    }
+
+    #[test]
+    fn test_unrequested_features() {
+        let usage = FeatureUsage {
+            name: "tokio".to_string(),
+            section: DependencySection::new(crate::core::dependency::DependencyKind::Normal),
+            requested: vec!["rt".to_string()],
+            default_features: true,
+            resolved: Some(vec![
+                "default".to_string(),
+                "macros".to_string(),
+                "rt".to_string(),
+            ]),
+        };
+
+        assert_eq!(usage.unrequested(), vec!["macros"]);
+    }
+}
@@ -0,0 +1,253 @@
+//! Freshness budgets: per-category limits on how far dependencies may lag
+//!
+//! Not every dependency needs the same care. A TLS stack or a parser of
+//! untrusted input should track its latest release closely, while a
+//! benchmarking harness can lag. Each crate gets a category, from
+//! `[freshness.categories]` or the built-in list below, falling back to
+//! "default", and is checked against that category's budget.
+
+use crate::analyzer::stats::breaking_releases;
+use crate::core::config::{FreshnessBudget, FreshnessConfig};
+use crate::core::dependency::Dependency;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+/// Category of crates nothing else categorizes, and the budget of
+/// categories without one
+pub const DEFAULT_CATEGORY: &str = "default";
+
+/// Well-known crates by category: cryptography, TLS and parsers of
+/// untrusted input are "security", test and benchmark tooling "dev-tools"
+const BUILTIN_CATEGORIES: &[(&str, &[&str])] = &[
+    (
+        "security",
+        &[
+            "aes",
+            "aes-gcm",
+            "argon2",
+            "bcrypt",
+            "chacha20poly1305",
+            "curve25519-dalek",
+            "ed25519-dalek",
+            "h2",
+            "hmac",
+            "httparse",
+            "hyper",
+            "hyper-rustls",
+            "jsonwebtoken",
+            "native-tls",
+            "openssl",
+            "p256",
+            "quick-xml",
+            "ring",
+            "rsa",
+            "rustls",
+            "rustls-webpki",
+            "serde_json",
+            "serde_yaml",
+            "sha1",
+            "sha2",
+            "tokio-rustls",
+            "url",
+            "webpki",
+            "x25519-dalek",
+            "xml-rs",
+        ],
+    ),
+    (
+        "dev-tools",
+        &[
+            "assert_cmd",
+            "criterion",
+            "insta",
+            "mockall",
+            "predicates",
+            "pretty_assertions",
+            "proptest",
+            "quickcheck",
+            "rstest",
+            "tempfile",
+            "trybuild",
+            "wiremock",
+        ],
+    ),
+];
+
+/// A dependency further behind than its category allows
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BudgetViolation {
+    pub name: String,
+    pub category: String,
+    pub current: Version,
+    pub latest: Version,
+    pub majors_behind: u64,
+    pub minors_behind: u64,
+    pub budget: FreshnessBudget,
+}
+
+impl BudgetViolation {
+    /// What the budget allows and how far the crate is behind, e.g.
+    /// "3 minor releases behind, budget 1"
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(max) = self.budget.max_majors_behind {
+            if self.majors_behind > max {
+                parts.push(format!(
+                    "{} breaking release(s) behind, budget {}",
+                    self.majors_behind, max
+                ));
+            }
+        }
+        if let Some(max) = self.budget.max_minors_behind {
+            if self.minors_behind > max {
+                parts.push(format!(
+                    "{} minor release(s) behind, budget {}",
+                    self.minors_behind, max
+                ));
+            }
+        }
+        parts.join("; ")
+    }
+}
+
+/// The category of `name`: configured, built in, or "default"
+pub fn category<'a>(name: &str, config: &'a FreshnessConfig) -> &'a str {
+    if let Some(category) = config.categories.get(name) {
+        return category;
+    }
+    BUILTIN_CATEGORIES
+        .iter()
+        .find(|(_, crates)| crates.contains(&name))
+        .map_or(DEFAULT_CATEGORY, |(category, _)| category)
+}
+
+/// Breaking and compatible minor releases between `current` and `latest`.
+/// Minors only count within the breaking release in use.
+pub fn releases_behind(current: &Version, latest: &Version) -> (u64, u64) {
+    let majors = breaking_releases(current, latest);
+    let minors = match (majors, current.major) {
+        (0, 0) if latest.minor == current.minor => latest.patch.saturating_sub(current.patch),
+        (0, major) if major > 0 && latest.major == major => {
+            latest.minor.saturating_sub(current.minor)
+        }
+        _ => 0,
+    };
+    (majors, minors)
+}
+
+/// Dependencies outside their category's budget, in name order. Empty
+/// when no budgets are configured.
+pub fn budget_violations(
+    dependencies: &[Dependency],
+    config: &FreshnessConfig,
+) -> Vec<BudgetViolation> {
+    let mut violations: Vec<BudgetViolation> = dependencies
+        .iter()
+        .filter_map(|dep| {
+            let latest = dep.latest_version.as_ref()?;
+            let category = category(&dep.name, config);
+            let budget = config
+                .budgets
+                .get(category)
+                .or_else(|| config.budgets.get(DEFAULT_CATEGORY))?;
+            let (majors_behind, minors_behind) = releases_behind(&dep.current_version, latest);
+            let exceeded = budget
+                .max_majors_behind
+                .is_some_and(|max| majors_behind > max)
+                || budget
+                    .max_minors_behind
+                    .is_some_and(|max| minors_behind > max);
+            exceeded.then(|| BudgetViolation {
+                name: dep.name.clone(),
+                category: category.to_string(),
+                current: dep.current_version.clone(),
+                latest: latest.clone(),
+                majors_behind,
+                minors_behind,
+                budget: *budget,
+            })
+        })
+        .collect();
+    violations.sort_by(|a, b| a.name.cmp(&b.name));
+    violations.dedup_by(|a, b| a.name == b.name && a.current == b.current);
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dependency::test_support::dep;
+
+    fn config() -> FreshnessConfig {
+        toml::from_str(
+            r#"
+            [budgets]
+            security = { max_majors_behind = 0, max_minors_behind = 1 }
+            default = { max_majors_behind = 1 }
+
+            [categories]
+            my-parser = "security"
+            criterion = "default"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_categories() {
+        let config = config();
+        assert_eq!(category("rustls", &config), "security");
+        assert_eq!(category("my-parser", &config), "security");
+        assert_eq!(category("proptest", &config), "dev-tools");
+        assert_eq!(category("criterion", &config), "default");
+        assert_eq!(category("anyhow", &config), DEFAULT_CATEGORY);
+    }
+
+    #[test]
+    fn test_releases_behind() {
+        let v = |s: &str| Version::parse(s).unwrap();
+        assert_eq!(releases_behind(&v("1.2.0"), &v("1.5.3")), (0, 3));
+        assert_eq!(releases_behind(&v("1.2.0"), &v("3.0.0")), (2, 0));
+        assert_eq!(releases_behind(&v("0.21.1"), &v("0.21.4")), (0, 3));
+        assert_eq!(releases_behind(&v("0.20.1"), &v("0.23.0")), (3, 0));
+    }
+
+    #[test]
+    fn test_budget_violations() {
+        let deps = vec![
+            dep("rustls", "0.23.1", "0.23.2"),
+            dep("my-parser", "1.2.0", "1.5.0"),
+            dep("hyper", "0.14.28", "1.3.1"),
+            dep("anyhow", "1.0.0", "2.0.0"),
+            dep("clap", "2.34.0", "4.5.4"),
+            dep("proptest", "0.9.0", "1.4.0"),
+        ];
+        let violations = budget_violations(&deps, &config());
+        let summary: Vec<(&str, &str, String)> = violations
+            .iter()
+            .map(|v| (v.name.as_str(), v.category.as_str(), v.describe()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "clap",
+                    "default",
+                    "2 breaking release(s) behind, budget 1".to_string()
+                ),
+                (
+                    "hyper",
+                    "security",
+                    "1 breaking release(s) behind, budget 0".to_string()
+                ),
+                (
+                    "my-parser",
+                    "security",
+                    "3 minor release(s) behind, budget 1".to_string()
+                ),
+            ]
+        );
+        assert!(budget_violations(&deps, &FreshnessConfig::default()).is_empty());
+    }
+}
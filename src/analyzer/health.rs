@@ -1 +1,772 @@
 //! Health check for dependencies
+
+use crate::analyzer::accepted::AcceptedFinding;
+use crate::analyzer::attribution::{Attribution, ResolveGraph};
+use crate::analyzer::build_time::BuildTimeSurface;
+use crate::analyzer::checker::{git_dependencies, parse_version_req};
+use crate::analyzer::checksums::ChecksumReport;
+use crate::analyzer::embedded::EmbeddedCrate;
+use crate::analyzer::enrichment::Enrichment;
+use crate::analyzer::internal::InternalCrates;
+use crate::analyzer::ownership::OwnershipChange;
+use crate::analyzer::system_libs::SystemLibrary;
+use crate::core::advisory::Advisory;
+use crate::core::dependency::{DependencySource, ForkedDependency};
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::core::version::Series;
+use crate::utils::advisories::{AdvisorySource, OsvClient};
+use crate::utils::advisory_db::{database_path, AdvisoryDb, DatabaseInfo, DbOptions};
+use crate::utils::cancel::is_cancelled;
+use crate::utils::progress::{HiddenProgress, Progress};
+use crate::utils::registry::DEFAULT_CONCURRENCY;
+use crate::utils::test_mode::Scenario;
+use crate::utils::timings;
+use crate::Result;
+use futures::stream::{self, StreamExt};
+use schemars::JsonSchema;
+use semver::{Op, Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+pub struct HealthChecker<A = OsvClient> {
+    source: A,
+    concurrency: usize,
+    internal: InternalCrates,
+    transitive: bool,
+    progress: Arc<dyn Progress>,
+}
+
+/// Everything `cargo sane health` found
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HealthReport {
+    pub package: Option<String>,
+    pub manifest: PathBuf,
+    /// Number of package versions looked up in the advisory database
+    pub scanned: usize,
+    pub vulnerable: Vec<AffectedPackage>,
+    /// The advisory data the scan used, when it came from a local database
+    #[serde(default)]
+    pub database: Option<DatabaseInfo>,
+    /// Native libraries linked through -sys crates, whose vulnerabilities
+    /// crate advisories don't cover. Filled in by the health command.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub system_libraries: Vec<SystemLibrary>,
+    /// Owner changes of direct dependencies since the last snapshot that
+    /// recorded owners. Filled in by `health --owners`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ownership_changes: Vec<OwnershipChange>,
+    /// Advisories moved out of `vulnerable` by an accepted-risk decision.
+    /// Filled in by the health command.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accepted: Vec<AcceptedFinding>,
+    /// Advisories matched by name against the project's own crates. They
+    /// are most likely about a published crate of the same name, so they're
+    /// listed apart from `vulnerable` and fail nothing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub internal: Vec<AffectedPackage>,
+    /// Git dependencies that look like forks of a crates.io package and
+    /// trail its newest release. Filled in by the health command.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forks: Vec<ForkedDependency>,
+    /// Registry details of the most exposed packages, and which packages
+    /// were only matched against advisories. Filled in by `health --enrich`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enrichment: Option<Enrichment>,
+    /// How Cargo.lock's checksums compare with the ones crates.io
+    /// publishes. Filled in by the health command unless it's offline.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksums: Option<ChecksumReport>,
+    /// Likely copies of crates pasted into the source tree. Filled in by
+    /// `health --scan-embedded`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub embedded: Vec<EmbeddedCrate>,
+    /// The dependencies with a build script or proc-macro. Filled in by the
+    /// health command when cargo metadata is available.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_time: Option<BuildTimeSurface>,
+    /// Whether `--timeout` stopped the scan before every lookup was made
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool,
+    /// Package versions of `scanned` not looked up before the timeout
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub unchecked: usize,
+}
+
+/// What `cargo sane health --workspace` found, per member
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WorkspaceHealth {
+    pub root: PathBuf,
+    /// Distinct package versions looked up, across all members
+    pub scanned: usize,
+    #[serde(default)]
+    pub database: Option<DatabaseInfo>,
+    /// Each member's findings; `scanned` counts the member's own packages
+    pub members: Vec<HealthReport>,
+    /// How the shared Cargo.lock's checksums compare with the ones crates.io
+    /// publishes. Filled in by the health command unless it's offline.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksums: Option<ChecksumReport>,
+    /// Whether `--timeout` stopped the scan before every lookup was made
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool,
+    /// Package versions of `scanned` not looked up before the timeout
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub unchecked: usize,
+}
+
+impl WorkspaceHealth {
+    /// Every member's findings
+    pub fn vulnerable(&self) -> impl Iterator<Item = &AffectedPackage> {
+        self.members.iter().flat_map(|member| &member.vulnerable)
+    }
+}
+
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+/// A dependency version with at least one advisory against it
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AffectedPackage {
+    pub name: String,
+    pub version: Version,
+    pub source: DependencySource,
+    pub advisories: Vec<Advisory>,
+    /// How the package is reached, through the nearest direct dependency
+    #[serde(flatten)]
+    pub attribution: Attribution,
+    /// The other direct dependencies whose subtrees also bring it in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub also_via: Vec<Attribution>,
+    /// The team owning the direct dependency that brings it in, from
+    /// `.cargo-sane/owners.toml`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+impl AffectedPackage {
+    /// The lowest release above the current one that every vulnerability
+    /// advisory lists as patched, judged from their patched ranges alone.
+    /// `None` when an advisory has no patched release or there are only
+    /// informational notices.
+    pub fn fix_version(&self) -> Option<Version> {
+        self.fix_version_where(|_| true)
+    }
+
+    /// [`Self::fix_version`] within a tracked release series, for a crate
+    /// whose fixes are backported to an older branch
+    pub fn fix_version_in(&self, series: &Series) -> Option<Version> {
+        self.fix_version_where(|version| series.contains(version))
+    }
+
+    fn fix_version_where(&self, accepts: impl Fn(&Version) -> bool) -> Option<Version> {
+        let vulnerabilities: Vec<&Advisory> = self
+            .advisories
+            .iter()
+            .filter(|a| a.informational.is_none())
+            .collect();
+        if vulnerabilities.is_empty() {
+            return None;
+        }
+
+        let mut candidates: Vec<Version> = vulnerabilities
+            .iter()
+            .flat_map(|a| &a.patched_versions)
+            .filter_map(|req| VersionReq::parse(req).ok())
+            .flat_map(|req| req.comparators)
+            .filter(|c| matches!(c.op, Op::GreaterEq | Op::Caret | Op::Tilde | Op::Exact))
+            .map(|c| Version {
+                pre: c.pre,
+                ..Version::new(c.major, c.minor.unwrap_or(0), c.patch.unwrap_or(0))
+            })
+            .filter(|v| *v > self.version && accepts(v))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        candidates
+            .into_iter()
+            .find(|v| vulnerabilities.iter().all(|a| !a.affects(v)))
+    }
+}
+
+impl HealthChecker<AdvisoryDb> {
+    /// Open the local advisory database of the project owning `manifest`,
+    /// returning the checker together with what was loaded
+    pub fn open(manifest: &Manifest, options: DbOptions) -> Result<(Self, DatabaseInfo)> {
+        let client = OsvClient::new()?;
+        // A snapshot of one scenario's answers is no use to another
+        let name = match Scenario::active() {
+            Some(scenario) => format!("test scenario {}", scenario.fingerprint()),
+            None => format!("OSV.dev ({})", client.base_url()),
+        };
+        let db = AdvisoryDb::open(client, &name, database_path(manifest), options)?;
+        let info = db.info();
+        Ok((Self::with_source(db), info))
+    }
+}
+
+impl<A: AdvisorySource> HealthChecker<A> {
+    /// Create a health checker that looks advisories up through `source`,
+    /// which can't fail any more than `DependencyChecker::with_provider`
+    pub fn with_source(source: A) -> Self {
+        Self {
+            source,
+            concurrency: DEFAULT_CONCURRENCY,
+            internal: InternalCrates::default(),
+            transitive: false,
+            progress: Arc::new(HiddenProgress),
+        }
+    }
+
+    /// Also scan every registry package Cargo.lock resolves below the
+    /// direct dependencies (direct dependencies only by default)
+    pub fn with_transitive(mut self, transitive: bool) -> Self {
+        self.transitive = transitive;
+        self
+    }
+
+    /// Limit the number of advisory lookups in flight at once (0 keeps the default)
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        if concurrency > 0 {
+            self.concurrency = concurrency;
+        }
+        self
+    }
+
+    /// Report lookup progress through `progress` (hidden by default)
+    pub fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Report advisories against the crates in `internal` apart from the
+    /// rest (none by default)
+    pub fn with_internal(mut self, internal: InternalCrates) -> Self {
+        self.internal = internal;
+        self
+    }
+
+    /// The advisory source lookups go through
+    pub fn source(&self) -> &A {
+        &self.source
+    }
+
+    /// Scan the direct dependencies of a manifest for known advisories, and
+    /// with `with_transitive` everything locked below them.
+    ///
+    /// Registry dependencies are looked up at their locked version when a
+    /// lockfile is available. Git dependencies are matched by crate name at
+    /// the version Cargo.lock recorded for them, and are skipped otherwise.
+    /// With a lockfile, each finding is attributed to the direct
+    /// dependencies that bring it in.
+    pub async fn check(
+        &self,
+        manifest: &Manifest,
+        lockfile: Option<&Lockfile>,
+    ) -> Result<HealthReport> {
+        let graph = lockfile.map(ResolveGraph::new);
+        let (targets, roots) = self.targets(manifest, lockfile, graph.as_ref());
+
+        let scanned = targets.len();
+        let (found, unchecked) = self.scan(targets).await?;
+        Ok(self.report(manifest, scanned, found, graph.as_ref(), &roots, unchecked))
+    }
+
+    /// [`Self::check`] every member of a workspace against the lockfile
+    /// they share. A package version several members use is looked up once.
+    pub async fn check_workspace(
+        &self,
+        root: &Manifest,
+        members: &[Manifest],
+        lockfile: Option<&Lockfile>,
+    ) -> Result<WorkspaceHealth> {
+        let graph = lockfile.map(ResolveGraph::new);
+        let member_targets: Vec<_> = members
+            .iter()
+            .map(|member| self.targets(member, lockfile, graph.as_ref()))
+            .collect();
+
+        let mut seen = BTreeSet::new();
+        let unique: Vec<(String, Version, DependencySource)> = member_targets
+            .iter()
+            .flat_map(|(targets, _)| targets)
+            .filter(|(name, version, _)| seen.insert((name.clone(), version.clone())))
+            .cloned()
+            .collect();
+        let scanned = unique.len();
+        let (found, unchecked) = self.scan(unique).await?;
+
+        let members = members
+            .iter()
+            .zip(member_targets)
+            .map(|(member, (targets, roots))| {
+                // In the member's own target order, direct dependencies first
+                let own = targets
+                    .iter()
+                    .filter_map(|(name, version, _)| {
+                        found
+                            .iter()
+                            .find(|p| &p.name == name && &p.version == version)
+                    })
+                    .cloned()
+                    .collect();
+                self.report(member, targets.len(), own, graph.as_ref(), &roots, 0)
+            })
+            .collect();
+
+        Ok(WorkspaceHealth {
+            root: root.path.clone(),
+            scanned,
+            database: self.source.database_info(),
+            members,
+            checksums: None,
+            partial: unchecked > 0,
+            unchecked,
+        })
+    }
+
+    /// The report on `manifest` from the packages `found` among the
+    /// `scanned` ones, each attributed to the direct dependencies at
+    /// `roots` that bring it in
+    fn report(
+        &self,
+        manifest: &Manifest,
+        scanned: usize,
+        mut found: Vec<AffectedPackage>,
+        graph: Option<&ResolveGraph>,
+        roots: &[usize],
+        unchecked: usize,
+    ) -> HealthReport {
+        if let Some(graph) = graph {
+            for package in &mut found {
+                let Some(node) = graph.node(&package.name, &package.version) else {
+                    continue;
+                };
+                let mut attributions = graph.attribute(roots, node).into_iter();
+                if let Some(nearest) = attributions.next() {
+                    package.attribution = nearest;
+                    package.also_via = attributions.collect();
+                }
+            }
+        }
+        let (internal, vulnerable) = found
+            .into_iter()
+            .partition(|package| self.internal.contains(&package.name));
+
+        HealthReport {
+            package: manifest.package_name().map(str::to_string),
+            manifest: manifest.path.clone(),
+            scanned,
+            vulnerable,
+            database: self.source.database_info(),
+            system_libraries: Vec::new(),
+            forks: Vec::new(),
+            enrichment: None,
+            checksums: None,
+            embedded: Vec::new(),
+            build_time: None,
+            ownership_changes: Vec::new(),
+            accepted: Vec::new(),
+            internal,
+            partial: unchecked > 0,
+            unchecked,
+        }
+    }
+
+    /// The registry packages `check` scans, each with whether the manifest
+    /// depends on it directly
+    pub fn scanned_packages(
+        &self,
+        manifest: &Manifest,
+        lockfile: Option<&Lockfile>,
+    ) -> Vec<(String, Version, bool)> {
+        let graph = lockfile.map(ResolveGraph::new);
+        let direct = scan_targets(manifest, lockfile).len();
+        let (targets, _) = self.targets(manifest, lockfile, graph.as_ref());
+        targets
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (_, _, source))| *source == DependencySource::Registry)
+            .map(|(index, (name, version, _))| (name, version, index < direct))
+            .collect()
+    }
+
+    /// The package versions to scan, direct dependencies first, and the
+    /// graph nodes of the direct dependencies
+    fn targets(
+        &self,
+        manifest: &Manifest,
+        lockfile: Option<&Lockfile>,
+        graph: Option<&ResolveGraph>,
+    ) -> (Vec<(String, Version, DependencySource)>, Vec<usize>) {
+        let mut targets = scan_targets(manifest, lockfile);
+        let roots = graph
+            .map(|graph| direct_nodes(graph, manifest, &targets))
+            .unwrap_or_default();
+        if let (true, Some(graph)) = (self.transitive, graph) {
+            for node in graph.reachable(&roots) {
+                let package = graph.package(node);
+                let known = targets
+                    .iter()
+                    .any(|(name, version, _)| name == &package.name && version == &package.version);
+                if package.is_registry() && !known {
+                    targets.push((
+                        package.name.clone(),
+                        package.version.clone(),
+                        DependencySource::Registry,
+                    ));
+                }
+            }
+        }
+        (targets, roots)
+    }
+
+    /// Scan already resolved registry packages, such as the duplicated
+    /// versions of a conflict report
+    pub async fn check_packages(
+        &self,
+        packages: &[(String, Version)],
+    ) -> Result<Vec<AffectedPackage>> {
+        let targets = packages
+            .iter()
+            .map(|(name, version)| (name.clone(), version.clone(), DependencySource::Registry))
+            .collect();
+        Ok(self.scan(targets).await?.0)
+    }
+
+    /// Look up every target, keeping those with advisories in target order,
+    /// and count the lookups `--timeout` refused
+    async fn scan(
+        &self,
+        targets: Vec<(String, Version, DependencySource)>,
+    ) -> Result<(Vec<AffectedPackage>, usize)> {
+        let _span = timings::span("advisories");
+        self.progress
+            .start(targets.len() as u64, "Checking advisories");
+
+        let mut found: Vec<Option<AffectedPackage>> = vec![None; targets.len()];
+        let mut unchecked = 0;
+        let mut lookups = stream::iter(targets.into_iter().enumerate())
+            .map(|(index, (name, version, source))| async move {
+                let started = Instant::now();
+                let advisories = self.source.advisories_for(&name, &version).await;
+                (index, name, version, source, advisories, started.elapsed())
+            })
+            .buffer_unordered(self.concurrency);
+
+        while let Some((index, name, version, source, advisories, elapsed)) = lookups.next().await {
+            self.progress.item_done(&name, elapsed);
+            let advisories = match advisories {
+                Ok(advisories) => advisories,
+                Err(e) if is_cancelled(&e) => {
+                    unchecked += 1;
+                    continue;
+                }
+                Err(e) => {
+                    self.progress.finish();
+                    return Err(e);
+                }
+            };
+            if !advisories.is_empty() {
+                found[index] = Some(AffectedPackage {
+                    attribution: Attribution::direct(&name),
+                    also_via: Vec::new(),
+                    owner: None,
+                    name,
+                    version,
+                    source,
+                    advisories,
+                });
+            }
+        }
+        self.progress.finish();
+
+        Ok((found.into_iter().flatten().collect(), unchecked))
+    }
+}
+
+/// The package versions to look up, in manifest order
+fn scan_targets(
+    manifest: &Manifest,
+    lockfile: Option<&Lockfile>,
+) -> Vec<(String, Version, DependencySource)> {
+    let mut targets = Vec::new();
+
+    for (name, spec, _) in manifest.get_dependencies() {
+        if !spec.is_crates_io() {
+            continue;
+        }
+        let Some(requirement) = spec.version() else {
+            continue;
+        };
+
+        // Prefer the highest locked registry version satisfying the requirement
+        let locked = VersionReq::parse(requirement).ok().and_then(|req| {
+            lockfile?
+                .packages_named(&name)
+                .into_iter()
+                .filter(|p| p.is_registry() && req.matches(&p.version))
+                .map(|p| p.version.clone())
+                .next_back()
+        });
+
+        if let Some(version) = locked.or_else(|| parse_version_req(requirement)) {
+            targets.push((name, version, DependencySource::Registry));
+        }
+    }
+    // A crate declared for several targets is still one package to look up
+    targets.dedup();
+
+    for dep in git_dependencies(manifest, lockfile) {
+        if let Some(version) = dep.locked_version {
+            targets.push((dep.name, version, DependencySource::Git));
+        }
+    }
+
+    targets
+}
+
+/// The lockfile nodes of the manifest's direct dependencies: those of its
+/// package's entry, or for a virtual manifest the scanned targets
+fn direct_nodes(
+    graph: &ResolveGraph,
+    manifest: &Manifest,
+    targets: &[(String, Version, DependencySource)],
+) -> Vec<usize> {
+    if let Some(nodes) = manifest
+        .package_name()
+        .and_then(|name| graph.dependencies_of_local(name))
+    {
+        return nodes.to_vec();
+    }
+    targets
+        .iter()
+        .filter_map(|(name, version, _)| graph.node(name, version))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::advisory::Severity;
+    use std::collections::HashMap;
+
+    struct MockAdvisories(HashMap<(String, Version), Vec<Advisory>>);
+
+    impl AdvisorySource for MockAdvisories {
+        async fn advisories_for(
+            &self,
+            crate_name: &str,
+            version: &Version,
+        ) -> Result<Vec<Advisory>> {
+            Ok(self
+                .0
+                .get(&(crate_name.to_string(), version.clone()))
+                .cloned()
+                .unwrap_or_default())
+        }
+    }
+
+    fn advisory(id: &str, package: &str) -> Advisory {
+        Advisory {
+            id: id.to_string(),
+            package: package.to_string(),
+            title: "Memory corruption".to_string(),
+            severity: Some(Severity::High),
+            cvss: None,
+            aliases: Vec::new(),
+            patched_versions: vec![">=1.3.0".to_string()],
+            informational: None,
+            url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_git_dependencies_matched_by_locked_version() {
+        let manifest = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            r#"[dependencies]
+serde = "1.0"
+forked = { git = "https://github.com/ourorg/forked", branch = "main" }
+unlocked = { git = "https://github.com/ourorg/unlocked" }
+"#,
+        )
+        .unwrap();
+        let lockfile = Lockfile::parse(
+            PathBuf::from("Cargo.lock"),
+            r#"
+[[package]]
+name = "forked"
+version = "1.2.0"
+source = "git+https://github.com/ourorg/forked?branch=main#0123456789abcdef"
+
+[[package]]
+name = "serde"
+version = "1.0.150"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+
+        let mut db = HashMap::new();
+        db.insert(
+            ("forked".to_string(), Version::new(1, 2, 0)),
+            vec![advisory("RUSTSEC-2024-0001", "forked")],
+        );
+        db.insert(
+            ("serde".to_string(), Version::new(1, 0, 0)),
+            vec![advisory("RUSTSEC-2024-0002", "serde")],
+        );
+
+        let checker = HealthChecker::with_source(MockAdvisories(db));
+        let report =
+            futures::executor::block_on(checker.check(&manifest, Some(&lockfile))).unwrap();
+
+        // serde is checked at its locked 1.0.150, not the requirement floor
+        assert_eq!(report.scanned, 2);
+        assert_eq!(report.vulnerable.len(), 1);
+        assert_eq!(report.vulnerable[0].name, "forked");
+        assert_eq!(report.vulnerable[0].source, DependencySource::Git);
+        assert_eq!(report.vulnerable[0].advisories[0].id, "RUSTSEC-2024-0001");
+    }
+
+    #[test]
+    fn test_transitive_findings_name_every_direct_parent() {
+        let manifest = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nweb = \"1\"\ngrpc = \"1\"\n",
+        )
+        .unwrap();
+        let registry = "source = \"registry+https://github.com/rust-lang/crates.io-index\"";
+        let lockfile = Lockfile::parse(
+            PathBuf::from("Cargo.lock"),
+            &format!(
+                r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["grpc", "web"]
+
+[[package]]
+name = "grpc"
+version = "1.0.0"
+{registry}
+dependencies = ["http"]
+
+[[package]]
+name = "http"
+version = "1.0.0"
+{registry}
+dependencies = ["vuln"]
+
+[[package]]
+name = "vuln"
+version = "1.0.0"
+{registry}
+
+[[package]]
+name = "web"
+version = "1.0.0"
+{registry}
+dependencies = ["http", "vuln"]
+"#
+            ),
+        )
+        .unwrap();
+        let mut db = HashMap::new();
+        db.insert(
+            ("vuln".to_string(), Version::new(1, 0, 0)),
+            vec![advisory("RUSTSEC-2024-0001", "vuln")],
+        );
+        db.insert(
+            ("web".to_string(), Version::new(1, 0, 0)),
+            vec![advisory("RUSTSEC-2024-0002", "web")],
+        );
+        let checker = HealthChecker::with_source(MockAdvisories(db));
+
+        // Direct only: vuln isn't scanned
+        let report =
+            futures::executor::block_on(checker.check(&manifest, Some(&lockfile))).unwrap();
+        assert_eq!(report.scanned, 2);
+        assert_eq!(report.vulnerable.len(), 1);
+        assert_eq!(report.vulnerable[0].attribution, Attribution::direct("web"));
+
+        let checker = checker.with_transitive(true);
+        let report =
+            futures::executor::block_on(checker.check(&manifest, Some(&lockfile))).unwrap();
+        assert_eq!(report.scanned, 4);
+        let vuln = &report.vulnerable[1];
+        assert_eq!(vuln.name, "vuln");
+        assert_eq!(vuln.attribution.direct_parent, "web");
+        assert_eq!(vuln.attribution.path, ["web", "vuln"]);
+        assert_eq!(vuln.also_via.len(), 1);
+        assert_eq!(vuln.also_via[0].path, ["grpc", "http", "vuln"]);
+        assert_eq!(vuln.also_via[0].depth, 3);
+
+        let json = serde_json::to_value(vuln).unwrap();
+        assert_eq!(json["direct_parent"], "web");
+        assert_eq!(json["depth"], 2);
+        assert_eq!(json["also_via"][0]["direct_parent"], "grpc");
+    }
+
+    #[test]
+    fn test_fix_version_satisfies_every_advisory() {
+        let mut first = advisory("RUSTSEC-2024-0001", "foo");
+        first.patched_versions = vec![">=1.2.3".to_string()];
+        let mut second = advisory("RUSTSEC-2024-0002", "foo");
+        second.patched_versions = vec!["^1.1.9".to_string(), ">=1.3.0".to_string()];
+        let mut package = AffectedPackage {
+            name: "foo".to_string(),
+            version: Version::new(1, 0, 0),
+            source: DependencySource::Registry,
+            advisories: vec![first],
+            attribution: Attribution::direct("foo"),
+            also_via: Vec::new(),
+            owner: None,
+        };
+        assert_eq!(package.fix_version(), Some(Version::new(1, 2, 3)));
+
+        // 1.1.9 fixes the second advisory but not the first
+        package.advisories.push(second);
+        assert_eq!(package.fix_version(), Some(Version::new(1, 2, 3)));
+
+        let mut unpatched = advisory("RUSTSEC-2024-0003", "foo");
+        unpatched.patched_versions.clear();
+        package.advisories.push(unpatched);
+        assert_eq!(package.fix_version(), None);
+
+        let mut notice = advisory("RUSTSEC-2024-0004", "foo");
+        notice.informational = Some("unmaintained".to_string());
+        package.advisories = vec![notice];
+        assert_eq!(package.fix_version(), None);
+    }
+
+    #[test]
+    fn test_fix_version_in_a_tracked_series() {
+        let mut backported = advisory("RUSTSEC-2024-0005", "hyper");
+        backported.patched_versions = vec![">=0.14.28, <0.15.0".to_string(), ">=1.0.3".to_string()];
+        let package = AffectedPackage {
+            name: "hyper".to_string(),
+            version: Version::new(0, 14, 20),
+            source: DependencySource::Registry,
+            advisories: vec![backported],
+            attribution: Attribution::direct("hyper"),
+            also_via: Vec::new(),
+            owner: None,
+        };
+        let series = |s: &str| Series::parse(s).unwrap();
+        assert_eq!(
+            package.fix_version_in(&series("0.14")),
+            Some(Version::new(0, 14, 28))
+        );
+        assert_eq!(
+            package.fix_version_in(&series("1")),
+            Some(Version::new(1, 0, 3))
+        );
+        assert_eq!(package.fix_version_in(&series("0.13")), None);
+    }
+}
@@ -0,0 +1,189 @@
+//! When each dependency was added to Cargo.toml, and by whom
+//!
+//! The declaration lines are traced back through git history to the commit
+//! that introduced them, for the governance question of who brought a
+//! dependency in. A trace runs git twice per dependency, so results are
+//! cached until HEAD or Cargo.toml changes. Outside a repository, without
+//! git, or in a shallow clone, whose oldest commit is only where the clone
+//! starts, there is no history to give.
+
+use crate::core::manifest::{DependencySection, Manifest};
+use crate::utils::cache::{fingerprint, ReportCache, STATE_DIR};
+use crate::utils::formatting::format_date;
+use crate::utils::git::GitRepo;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Traces are keyed on HEAD and the manifest, so they only expire to keep
+/// the cache file from outliving its repository state by much
+pub const CACHE_TTL_MINUTES: u64 = 7 * 24 * 60;
+
+/// The commit that added a dependency's declaration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DependencyOrigin {
+    pub name: String,
+    pub section: DependencySection,
+    pub commit: String,
+    pub author: String,
+    pub email: String,
+    /// Unix timestamp of the commit's authorship
+    pub added_at: u64,
+}
+
+impl DependencyOrigin {
+    /// `@login` for a GitHub noreply address, else the author's name
+    pub fn author_handle(&self) -> String {
+        let login = self
+            .email
+            .strip_suffix("@users.noreply.github.com")
+            .map(|local| local.split_once('+').map_or(local, |(_, login)| login));
+        match login {
+            Some(login) if !login.is_empty() => format!("@{}", login),
+            _ => self.author.clone(),
+        }
+    }
+
+    /// e.g. "added 2021-03 by @alice"
+    pub fn summary(&self) -> String {
+        format!(
+            "added {} by {}",
+            &format_date(self.added_at)[..7],
+            self.author_handle()
+        )
+    }
+}
+
+/// The origin of every declaration in `manifest` that is committed, in
+/// declaration order, from the cache while HEAD and the manifest are as
+/// they were
+pub fn dependency_history(manifest: &Manifest) -> Result<Vec<DependencyOrigin>> {
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let repo = GitRepo::discover(root).context("not in a git repository")?;
+    if repo.is_shallow() {
+        anyhow::bail!(
+            "the clone is shallow, so its oldest commits aren't where dependencies began"
+        );
+    }
+    let head = repo.head().context("the repository has no commits")?;
+    let text = fs::read_to_string(&manifest.path)
+        .with_context(|| format!("Failed to read {}", manifest.path.display()))?;
+
+    let cache = ReportCache::new(root.join(STATE_DIR).join("history.json"), CACHE_TTL_MINUTES);
+    let key = fingerprint(&format!("{}\n{}", head, text));
+    if let Some((origins, _)) = cache.load(&key) {
+        return Ok(origins);
+    }
+    let origins = trace(manifest, &repo)?;
+    // A cache that can't be written only costs the next run a trace
+    let _ = cache.store(&key, &origins);
+    Ok(origins)
+}
+
+fn trace(manifest: &Manifest, repo: &GitRepo) -> Result<Vec<DependencyOrigin>> {
+    let declarations: Vec<(DependencySection, String, usize)> = manifest
+        .declarations()
+        .into_iter()
+        .filter_map(|(section, name, _)| {
+            let (line, _) = manifest.location_in(&name, &section)?;
+            Some((section, name, line))
+        })
+        .collect();
+    let lines: Vec<(usize, &str)> = declarations
+        .iter()
+        .map(|(_, name, line)| (*line, name.as_str()))
+        .collect();
+    let mut commits = repo.line_origins(&manifest.path, &lines)?;
+
+    Ok(declarations
+        .into_iter()
+        .filter_map(|(section, name, line)| {
+            let commit = commits.remove(&line)?;
+            Some(DependencyOrigin {
+                name,
+                section,
+                commit: commit.id,
+                author: commit.author,
+                email: commit.email,
+                added_at: commit.time,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dependency::DependencyKind;
+    use std::process::Command;
+
+    fn origin(author: &str, email: &str) -> DependencyOrigin {
+        DependencyOrigin {
+            name: "serde".to_string(),
+            section: DependencySection::new(DependencyKind::Normal),
+            commit: "0".repeat(40),
+            author: author.to_string(),
+            email: email.to_string(),
+            // 2021-03-04
+            added_at: 1_614_859_200,
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let noreply = origin("Alice Liddell", "1234+alice@users.noreply.github.com");
+        assert_eq!(noreply.summary(), "added 2021-03 by @alice");
+        let old_noreply = origin("Alice Liddell", "alice@users.noreply.github.com");
+        assert_eq!(old_noreply.author_handle(), "@alice");
+        let elsewhere = origin("Alice Liddell", "alice@example.com");
+        assert_eq!(elsewhere.summary(), "added 2021-03 by Alice Liddell");
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "commit.gpgsign=false"])
+            .args(args)
+            .env("GIT_AUTHOR_DATE", "@1614859200 +0000")
+            .env("GIT_COMMITTER_DATE", "@1614859200 +0000")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_dependency_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Cargo.toml");
+        fs::write(
+            &path,
+            "[package]\nname = \"a\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1\"\n\n\
+             [dev-dependencies.tempfile]\nversion = \"3\"\n",
+        )
+        .unwrap();
+        // Outside a repository there is nothing to trace
+        let manifest = Manifest::from_path(&path).unwrap();
+        if GitRepo::discover(dir.path()).is_none() {
+            assert!(dependency_history(&manifest).is_err());
+        }
+
+        git(dir.path(), &["init", "-q"]);
+        git(dir.path(), &["config", "user.name", "Alice"]);
+        git(dir.path(), &["config", "user.email", "alice@example.com"]);
+        git(dir.path(), &["add", "Cargo.toml"]);
+        git(dir.path(), &["commit", "-q", "-m", "init"]);
+
+        let origins = dependency_history(&manifest).unwrap();
+        let names: Vec<&str> = origins.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, vec!["serde", "tempfile"]);
+        assert_eq!(origins[0].summary(), "added 2021-03 by Alice");
+        assert_eq!(origins[1].section.kind, DependencyKind::Dev);
+
+        // The second run is answered from the cache
+        assert!(dir.path().join(STATE_DIR).join("history.json").exists());
+        assert_eq!(dependency_history(&manifest).unwrap(), origins);
+    }
+}
@@ -0,0 +1,171 @@
+//! Estimate what an update drags into the dependency graph
+
+use crate::utils::cargo::Metadata;
+use crate::utils::sparse_index::{IndexEntry, SparseIndexClient};
+use crate::Result;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Direct dependency changes between two published versions of a crate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateImpact {
+    pub name: String,
+    pub from: Option<Version>,
+    pub to: Version,
+    /// Dependencies the new version adds, with their requirements
+    pub added: Vec<(String, String)>,
+    /// Dependencies the new version no longer has
+    pub removed: Vec<String>,
+    /// How many added crates are not in the current dependency graph at all
+    pub new_packages: Option<usize>,
+}
+
+impl UpdateImpact {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Fetch the index entries for `name` and diff the dependencies of `current`
+/// against those of `candidate`
+pub async fn update_impact(
+    index: &SparseIndexClient,
+    name: &str,
+    current: &Version,
+    candidate: &Version,
+    metadata: Option<&Metadata>,
+) -> Result<UpdateImpact> {
+    let entries = index.entries(name).await?;
+    diff_versions(name, &entries, current, candidate, metadata)
+        .ok_or_else(|| anyhow::anyhow!("{} {} is not in the index", name, candidate))
+}
+
+/// Diff two versions found in `entries`. `current` is usually a requirement's
+/// lower bound, so the newest release compatible with it stands in when that
+/// exact version was never published.
+pub fn diff_versions(
+    name: &str,
+    entries: &[IndexEntry],
+    current: &Version,
+    candidate: &Version,
+    metadata: Option<&Metadata>,
+) -> Option<UpdateImpact> {
+    let to = entries.iter().find(|e| &e.vers == candidate)?;
+    let from = entries.iter().find(|e| &e.vers == current).or_else(|| {
+        let req = VersionReq::parse(&format!("^{}", current)).ok()?;
+        entries
+            .iter()
+            .filter(|e| req.matches(&e.vers))
+            .max_by(|a, b| a.vers.cmp(&b.vers))
+    });
+
+    let before = from.map(built_dependencies).unwrap_or_default();
+    let after = built_dependencies(to);
+
+    let added: Vec<(String, String)> = after
+        .iter()
+        .filter(|(dep, _)| !before.contains_key(*dep))
+        .map(|(dep, req)| (dep.to_string(), req.to_string()))
+        .collect();
+    let removed = before
+        .keys()
+        .filter(|dep| !after.contains_key(*dep))
+        .map(|dep| dep.to_string())
+        .collect();
+
+    let new_packages = metadata.map(|metadata| {
+        added
+            .iter()
+            .filter(|(dep, _)| !metadata.packages.iter().any(|p| &p.name == dep))
+            .count()
+    });
+
+    Some(UpdateImpact {
+        name: name.to_string(),
+        from: from.map(|e| e.vers.clone()),
+        to: candidate.clone(),
+        added,
+        removed,
+        new_packages,
+    })
+}
+
+/// Non-optional dependencies a consumer of this version always builds.
+/// Optional ones depend on features and can't be judged from the index alone.
+fn built_dependencies(entry: &IndexEntry) -> BTreeMap<&str, &str> {
+    entry
+        .deps
+        .iter()
+        .filter(|d| d.is_built() && !d.optional)
+        .map(|d| (d.crate_name(), d.req.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sparse_index::parse_entries;
+
+    const INDEX: &str = r#"{"name":"demo","vers":"1.2.0","deps":[{"name":"log","req":"^0.4","optional":false,"kind":"normal","target":null},{"name":"baz","req":"^1","optional":false,"kind":"normal","target":null}],"yanked":false}
+{"name":"demo","vers":"2.0.0","deps":[{"name":"log","req":"^0.4","optional":false,"kind":"normal","target":null},{"name":"foo","req":"^1","optional":false,"kind":"normal","target":null},{"name":"bar","req":"^0.3","optional":false,"kind":"build","target":null},{"name":"quux","req":"^1","optional":true,"kind":"normal","target":null},{"name":"tester","req":"^1","optional":false,"kind":"dev","target":null}],"yanked":false}
+"#;
+
+    #[test]
+    fn test_diff_versions() {
+        let entries = parse_entries(INDEX).unwrap();
+        // 1.0.0 was never published, so the newest 1.x release stands in
+        let impact = diff_versions(
+            "demo",
+            &entries,
+            &Version::new(1, 0, 0),
+            &Version::new(2, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(impact.from, Some(Version::new(1, 2, 0)));
+        assert_eq!(
+            impact.added,
+            vec![
+                ("bar".to_string(), "^0.3".to_string()),
+                ("foo".to_string(), "^1".to_string())
+            ]
+        );
+        assert_eq!(impact.removed, vec!["baz"]);
+        assert_eq!(impact.new_packages, None);
+    }
+
+    #[test]
+    fn test_new_packages_against_metadata() {
+        let entries = parse_entries(INDEX).unwrap();
+        let metadata = Metadata::parse(
+            r#"{"packages": [{"id": "foo 1.0.0", "name": "foo", "version": "1.0.0", "source": null, "manifest_path": "/foo/Cargo.toml"}],
+                "resolve": null, "workspace_members": [], "workspace_root": "/", "version": 1}"#,
+        )
+        .unwrap();
+
+        let impact = diff_versions(
+            "demo",
+            &entries,
+            &Version::new(1, 2, 0),
+            &Version::new(2, 0, 0),
+            Some(&metadata),
+        )
+        .unwrap();
+        assert_eq!(impact.new_packages, Some(1));
+    }
+
+    #[test]
+    fn test_unknown_candidate() {
+        let entries = parse_entries(INDEX).unwrap();
+        assert!(diff_versions(
+            "demo",
+            &entries,
+            &Version::new(1, 2, 0),
+            &Version::new(3, 0, 0),
+            None
+        )
+        .is_none());
+    }
+}
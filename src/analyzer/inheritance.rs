@@ -0,0 +1,350 @@
+//! Find dependencies workspace members could inherit instead of declaring
+//!
+//! A crate several members declare on their own drifts one member at a time.
+//! When every declaration is a plain registry one whose requirements have a
+//! version in common, it can move to `[workspace.dependencies]`: the entry
+//! takes the strictest requirement and the features every member asks for,
+//! and each member keeps only the features it adds, as an override on
+//! `{ workspace = true }`. Cargo builds each member with what it built it
+//! with before.
+
+use crate::analyzer::checker::parse_version_req;
+use crate::analyzer::feature_consistency::UnifiedDeclaration;
+use crate::core::manifest::{DependencySection, DependencySpec, Manifest};
+use crate::core::workspace::Workspace;
+use schemars::JsonSchema;
+use semver::VersionReq;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+/// A member's declaration and what replaces it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct InheritedDeclaration {
+    pub member: String,
+    pub manifest: PathBuf,
+    pub section: DependencySection,
+    /// The requirement it declares now
+    pub requirement: String,
+    /// The features it asks for beyond the workspace entry's, kept as an
+    /// override
+    pub features: Vec<String>,
+    pub optional: bool,
+    pub line: Option<usize>,
+}
+
+impl InheritedDeclaration {
+    /// The inline table that replaces the declaration
+    pub fn to_toml(&self) -> String {
+        let mut keys = vec!["workspace = true".to_string()];
+        if !self.features.is_empty() {
+            let features: Vec<String> = self.features.iter().map(|f| quote(f)).collect();
+            keys.push(format!("features = [{}]", features.join(", ")));
+        }
+        if self.optional {
+            keys.push("optional = true".to_string());
+        }
+        format!("{{ {} }}", keys.join(", "))
+    }
+}
+
+/// A crate two or more members declare directly, and how they'd inherit it
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct InheritanceSuggestion {
+    pub name: String,
+    /// The `[workspace.dependencies]` entry to add at the root
+    pub entry: UnifiedDeclaration,
+    pub declarations: Vec<InheritedDeclaration>,
+}
+
+impl InheritanceSuggestion {
+    /// How many members declare the crate
+    pub fn members(&self) -> usize {
+        self.declarations
+            .iter()
+            .map(|d| d.member.as_str())
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+}
+
+/// The edits to one manifest, as TOML to paste into it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ManifestSnippet {
+    pub manifest: PathBuf,
+    pub toml: String,
+}
+
+fn quote(text: &str) -> String {
+    toml::Value::String(text.to_string()).to_string()
+}
+
+/// Find the crates two or more members of `workspace` declare directly with
+/// requirements that have a version in common and the same
+/// `default-features`. Renamed, git, path, artifact and alternate-registry
+/// declarations are left out, as are crates `[workspace.dependencies]`
+/// already has.
+pub fn find_inheritance_candidates(workspace: &Workspace) -> Vec<InheritanceSuggestion> {
+    let existing = workspace
+        .root
+        .content
+        .workspace
+        .as_ref()
+        .map(|table| &table.dependencies);
+
+    let mut groups: BTreeMap<String, Vec<(InheritedDeclaration, bool)>> = BTreeMap::new();
+    for manifest in &workspace.members {
+        for (section, name, spec) in manifest.declarations() {
+            if existing.is_some_and(|deps| deps.contains_key(&name)) {
+                continue;
+            }
+            let Some(requirement) = inheritable(&spec) else {
+                continue;
+            };
+            let declaration = InheritedDeclaration {
+                member: Workspace::member_name(manifest),
+                manifest: manifest.path.clone(),
+                line: manifest.location_in(&name, &section).map(|(line, _)| line),
+                section,
+                requirement: requirement.to_string(),
+                features: spec.features().to_vec(),
+                optional: spec.is_optional(),
+            };
+            groups
+                .entry(name)
+                .or_default()
+                .push((declaration, spec.default_features()));
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter_map(|(name, declarations)| suggestion(name, declarations))
+        .collect()
+}
+
+/// The requirement of a declaration that could become `workspace = true`:
+/// a crates.io one with nothing but a version, features, `optional` and
+/// `default-features`
+fn inheritable(spec: &DependencySpec) -> Option<&str> {
+    match spec {
+        DependencySpec::Simple(version) => Some(version),
+        DependencySpec::Detailed(d) => {
+            let plain = d.git.is_none()
+                && d.path.is_none()
+                && d.artifact.is_none()
+                && d.lib.is_none()
+                && d.target.is_none()
+                && d.other.as_ref().is_none_or(|other| other.is_empty());
+            plain.then_some(d.version.as_deref()?)
+        }
+    }
+}
+
+fn suggestion(
+    name: String,
+    declarations: Vec<(InheritedDeclaration, bool)>,
+) -> Option<InheritanceSuggestion> {
+    let members: BTreeSet<&str> = declarations
+        .iter()
+        .map(|(d, _)| d.member.as_str())
+        .collect();
+    if members.len() < 2 {
+        return None;
+    }
+    // A member can't turn default features back off under an entry that
+    // leaves them on, nor the other way round
+    let default_features = declarations[0].1;
+    if declarations.iter().any(|(_, d)| *d != default_features) {
+        return None;
+    }
+
+    // The strictest requirement, if every other one accepts its lowest version
+    let requirement = declarations
+        .iter()
+        .map(|(d, _)| d.requirement.as_str())
+        .max_by_key(|requirement| parse_version_req(requirement))?;
+    let lowest = parse_version_req(requirement)?;
+    let compatible = declarations
+        .iter()
+        .all(|(d, _)| VersionReq::parse(&d.requirement).is_ok_and(|req| req.matches(&lowest)));
+    if !compatible {
+        return None;
+    }
+
+    let mut shared: Option<BTreeSet<String>> = None;
+    for (declaration, _) in &declarations {
+        let features: BTreeSet<String> = declaration.features.iter().cloned().collect();
+        shared = Some(match shared {
+            Some(shared) => shared.intersection(&features).cloned().collect(),
+            None => features,
+        });
+    }
+    let shared = shared.unwrap_or_default();
+
+    Some(InheritanceSuggestion {
+        entry: UnifiedDeclaration {
+            requirement: Some(requirement.to_string()),
+            features: shared.iter().cloned().collect(),
+            default_features,
+        },
+        declarations: declarations
+            .into_iter()
+            .map(|(mut declaration, _)| {
+                declaration.features.retain(|f| !shared.contains(f));
+                declaration
+            })
+            .collect(),
+        name,
+    })
+}
+
+/// The TOML each manifest gets, the root's `[workspace.dependencies]`
+/// entries first, then each member's replacements under their section
+/// headers
+pub fn snippets(root: &Manifest, suggestions: &[InheritanceSuggestion]) -> Vec<ManifestSnippet> {
+    let mut snippets = Vec::new();
+    if suggestions.is_empty() {
+        return snippets;
+    }
+
+    let mut entries = String::from("[workspace.dependencies]\n");
+    for suggestion in suggestions {
+        entries.push_str(&format!(
+            "{} = {}\n",
+            suggestion.name,
+            suggestion.entry.to_toml()
+        ));
+    }
+    snippets.push(ManifestSnippet {
+        manifest: root.path.clone(),
+        toml: entries,
+    });
+
+    let mut members: BTreeMap<&PathBuf, BTreeMap<String, Vec<String>>> = BTreeMap::new();
+    for suggestion in suggestions {
+        for declaration in &suggestion.declarations {
+            members
+                .entry(&declaration.manifest)
+                .or_default()
+                .entry(declaration.section.to_string())
+                .or_default()
+                .push(format!("{} = {}", suggestion.name, declaration.to_toml()));
+        }
+    }
+    for (manifest, sections) in members {
+        let toml: Vec<String> = sections
+            .into_iter()
+            .map(|(section, lines)| format!("[{}]\n{}\n", section, lines.join("\n")))
+            .collect();
+        // The root is a member too when it has a [package]
+        match snippets.iter_mut().find(|s| &s.manifest == manifest) {
+            Some(snippet) => {
+                snippet.toml.push('\n');
+                snippet.toml.push_str(&toml.join("\n"));
+            }
+            None => snippets.push(ManifestSnippet {
+                manifest: manifest.clone(),
+                toml: toml.join("\n"),
+            }),
+        }
+    }
+    snippets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn workspace(members: &[(&str, &str)]) -> Workspace {
+        let root = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\n\n[workspace.dependencies]\nanyhow = \"1\"\n",
+        )
+        .unwrap();
+        let members = members
+            .iter()
+            .map(|(name, dependencies)| {
+                Manifest::parse(
+                    PathBuf::from(format!("crates/{}/Cargo.toml", name)),
+                    &format!(
+                        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n{}",
+                        name, dependencies
+                    ),
+                )
+                .unwrap()
+            })
+            .collect();
+        Workspace { root, members }
+    }
+
+    #[test]
+    fn test_compatible_declarations_are_suggested() {
+        let workspace = workspace(&[
+            (
+                "api",
+                "[dependencies]\nserde = { version = \"1.0.190\", features = [\"derive\", \"rc\"] }\n\
+                 tokio = { version = \"1\", default-features = false }\nanyhow = \"1\"\n\
+                 log = \"0.4\"\nrand = \"0.8\"\n",
+            ),
+            (
+                "cli",
+                "[dependencies]\nserde = { version = \"1.0.200\", features = [\"derive\"], optional = true }\n\
+                 tokio = \"1\"\nanyhow = \"1\"\nrand = \"0.7\"\n\n[dev-dependencies]\nlog = \"0.4.20\"\n",
+            ),
+        ]);
+
+        // tokio disagrees on default features, rand has no version in
+        // common and anyhow is inherited already
+        let found = find_inheritance_candidates(&workspace);
+        let names: Vec<&str> = found.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["log", "serde"]);
+
+        let serde = &found[1];
+        assert_eq!(
+            serde.entry.to_toml(),
+            r#"{ version = "1.0.200", features = ["derive"] }"#
+        );
+        assert_eq!(serde.members(), 2);
+        assert_eq!(
+            serde.declarations[0].to_toml(),
+            r#"{ workspace = true, features = ["rc"] }"#
+        );
+        assert_eq!(
+            serde.declarations[1].to_toml(),
+            "{ workspace = true, optional = true }"
+        );
+        assert_eq!(found[0].entry.to_toml(), r#"{ version = "0.4.20" }"#);
+    }
+
+    #[test]
+    fn test_snippets_are_toml_per_file() {
+        let workspace = workspace(&[
+            ("api", "[dependencies]\nlog = \"0.4\"\nserde = \"1\"\n"),
+            (
+                "cli",
+                "[dependencies]\nserde = \"1\"\n\n[dev-dependencies]\nlog = \"0.4\"\n",
+            ),
+        ]);
+        let found = find_inheritance_candidates(&workspace);
+        let snippets = snippets(&workspace.root, &found);
+
+        let files: Vec<&Path> = snippets.iter().map(|s| s.manifest.as_path()).collect();
+        assert_eq!(
+            files,
+            [
+                Path::new("Cargo.toml"),
+                Path::new("crates/api/Cargo.toml"),
+                Path::new("crates/cli/Cargo.toml"),
+            ]
+        );
+        for snippet in &snippets {
+            toml::from_str::<toml::Table>(&snippet.toml).unwrap();
+        }
+        assert_eq!(
+            snippets[2].toml,
+            "[dependencies]\nserde = { workspace = true }\n\n[dev-dependencies]\nlog = { workspace = true }\n"
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! Due diligence on the dependencies a change adds, for `cargo sane intake`
+//!
+//! A crate counts as added when the manifest declares it now and didn't at
+//! the base revision, whatever it's renamed to and in whichever section.
+//! Each gets the checks a reviewer would otherwise run by hand: advisories
+//! ever filed against it, its license against the `[licenses]` policy,
+//! maintenance signals from crates.io, the packages it alone brings into
+//! Cargo.lock, its owners, and the direct dependencies that already cover
+//! the same ground. A lookup that fails leaves its check open rather than
+//! failing the review.
+
+use crate::analyzer::attribution::ResolveGraph;
+use crate::analyzer::checker::parse_version_req;
+use crate::analyzer::enrichment::{enrich, Candidate, EnrichedPackage};
+use crate::analyzer::redundancy::RedundancyGroup;
+use crate::core::advisory::Advisory;
+use crate::core::license::LicensePolicy;
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::{DependencySection, Manifest};
+use crate::utils::advisories::OsvClient;
+use crate::utils::crates_io::CratesIoClient;
+use crate::utils::owners::crate_owners;
+use futures::stream::{self, StreamExt};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// A crates.io dependency the manifest declares now and didn't before
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddedDependency {
+    /// The package name, which a rename doesn't change
+    pub name: String,
+    /// The requirement of its first declaration
+    pub requirement: String,
+    pub sections: Vec<DependencySection>,
+}
+
+/// The checklist findings for one added crate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntakeReview {
+    pub name: String,
+    pub requirement: String,
+    pub sections: Vec<DependencySection>,
+    /// The version Cargo.lock resolves, else the requirement's lower bound
+    pub version: Option<Version>,
+    /// Every advisory on record against the crate, whichever versions
+    pub advisories: Vec<Advisory>,
+    /// Ids of the advisories `version` isn't patched for
+    pub affecting: Vec<String>,
+    /// What crates.io says about `version`
+    pub registry: Option<EnrichedPackage>,
+    /// Whether the `[licenses]` policy takes the license; unset without a
+    /// policy or a license
+    pub license_permitted: Option<bool>,
+    /// Maintenance signals worth a reviewer's look
+    pub concerns: Vec<String>,
+    /// `name version` of the packages only this crate brings into
+    /// Cargo.lock, itself left out; unset until Cargo.lock has it
+    pub new_packages: Option<Vec<String>>,
+    /// crates.io owner logins
+    pub owners: Option<Vec<String>>,
+    /// Direct dependencies already covering the same ground
+    pub overlaps: Vec<Overlap>,
+    /// The lookups that failed, leaving their checks open
+    pub failed: Vec<String>,
+}
+
+/// Existing direct dependencies in the same redundancy group as the new one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Overlap {
+    pub group: String,
+    pub crates: Vec<String>,
+    pub note: String,
+}
+
+/// The crates.io dependencies of `after` that `before` doesn't declare; all
+/// of them without a `before`
+pub fn added_dependencies(before: Option<&Manifest>, after: &Manifest) -> Vec<AddedDependency> {
+    let existing: BTreeSet<String> = before
+        .map(|manifest| {
+            manifest
+                .declarations()
+                .into_iter()
+                .map(|(_, key, spec)| spec.package().map_or(key, str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut added: BTreeMap<String, AddedDependency> = BTreeMap::new();
+    for (section, key, spec) in after.declarations() {
+        let name = spec.package().map_or(key, str::to_string);
+        if !spec.is_crates_io() || existing.contains(&name) {
+            continue;
+        }
+        let entry = added
+            .entry(name.clone())
+            .or_insert_with(|| AddedDependency {
+                name,
+                requirement: spec.version().unwrap_or("*").to_string(),
+                sections: Vec::new(),
+            });
+        if !entry.sections.contains(&section) {
+            entry.sections.push(section);
+        }
+    }
+    added.into_values().collect()
+}
+
+/// The highest locked registry version of `name` its requirement takes
+pub fn locked_version(
+    lockfile: Option<&Lockfile>,
+    name: &str,
+    requirement: &str,
+) -> Option<Version> {
+    let req = VersionReq::parse(requirement).ok()?;
+    lockfile?
+        .packages_named(name)
+        .into_iter()
+        .filter(|p| p.is_registry() && req.matches(&p.version))
+        .map(|p| p.version.clone())
+        .next_back()
+}
+
+/// `name version` of the packages reachable from `name` at `version` in
+/// Cargo.lock and from no other direct dependency of `manifest`, sorted.
+/// `None` when Cargo.lock doesn't have that version.
+pub fn introduced_packages(
+    lockfile: &Lockfile,
+    manifest: &Manifest,
+    name: &str,
+    version: &Version,
+) -> Option<Vec<String>> {
+    let graph = ResolveGraph::new(lockfile);
+    let node = graph.node(name, version)?;
+    let roots: Vec<usize> = match manifest
+        .package_name()
+        .and_then(|package| graph.dependencies_of_local(package))
+    {
+        Some(roots) => roots.to_vec(),
+        // A virtual manifest has no package of its own to start from
+        None => manifest
+            .declarations()
+            .into_iter()
+            .filter_map(|(_, key, spec)| {
+                let name = spec.package().map_or(key, str::to_string);
+                let version = locked_version(Some(lockfile), &name, spec.version()?)?;
+                graph.node(&name, &version)
+            })
+            .collect(),
+    };
+
+    let others: Vec<usize> = roots.into_iter().filter(|&root| root != node).collect();
+    let elsewhere: BTreeSet<usize> = graph.reachable(&others).into_iter().collect();
+    let mut introduced: Vec<String> = graph
+        .reachable(&[node])
+        .into_iter()
+        .filter(|other| *other != node && !elsewhere.contains(other))
+        .map(|other| {
+            let package = graph.package(other);
+            format!("{} {}", package.name, package.version)
+        })
+        .collect();
+    introduced.sort();
+    introduced.dedup();
+    Some(introduced)
+}
+
+/// The groups of `groups` with `name` and any of `direct`, naming only the
+/// members from `direct`
+pub fn overlaps(groups: &[RedundancyGroup], name: &str, direct: &BTreeSet<String>) -> Vec<Overlap> {
+    groups
+        .iter()
+        .filter(|group| group.crates.iter().any(|c| c == name))
+        .filter_map(|group| {
+            let crates: Vec<String> = group
+                .crates
+                .iter()
+                .filter(|c| *c != name && direct.contains(*c))
+                .cloned()
+                .collect();
+            (!crates.is_empty()).then(|| Overlap {
+                group: group.name.clone(),
+                crates,
+                note: group.note.clone(),
+            })
+        })
+        .collect()
+}
+
+/// What the reviews are checked against
+pub struct Intake<'a> {
+    pub manifest: &'a Manifest,
+    pub lockfile: Option<&'a Lockfile>,
+    pub policy: &'a LicensePolicy,
+    pub groups: &'a [RedundancyGroup],
+    pub concurrency: usize,
+    /// When the review runs, as Unix seconds, for maintenance signals
+    pub now: u64,
+}
+
+impl Intake<'_> {
+    /// Review every crate of `added`, in order
+    pub async fn review(
+        &self,
+        added: Vec<AddedDependency>,
+        crates_io: &CratesIoClient,
+        advisories: &OsvClient,
+    ) -> Vec<IntakeReview> {
+        let root = self.manifest.path.parent().unwrap_or(Path::new("."));
+        let direct: BTreeSet<String> = self
+            .manifest
+            .declarations()
+            .into_iter()
+            .map(|(_, key, spec)| spec.package().map_or(key, str::to_string))
+            .collect();
+
+        let mut reviews: Vec<IntakeReview> = added
+            .into_iter()
+            .map(|dependency| {
+                let version =
+                    locked_version(self.lockfile, &dependency.name, &dependency.requirement)
+                        .or_else(|| parse_version_req(&dependency.requirement));
+                let new_packages = match (self.lockfile, &version) {
+                    (Some(lockfile), Some(version)) => {
+                        introduced_packages(lockfile, self.manifest, &dependency.name, version)
+                    }
+                    _ => None,
+                };
+                IntakeReview {
+                    overlaps: overlaps(self.groups, &dependency.name, &direct),
+                    name: dependency.name,
+                    requirement: dependency.requirement,
+                    sections: dependency.sections,
+                    version,
+                    advisories: Vec::new(),
+                    affecting: Vec::new(),
+                    registry: None,
+                    license_permitted: None,
+                    concerns: Vec::new(),
+                    new_packages,
+                    owners: None,
+                    failed: Vec::new(),
+                }
+            })
+            .collect();
+
+        let names: Vec<String> = reviews.iter().map(|r| r.name.clone()).collect();
+        let candidates: Vec<Candidate> = reviews
+            .iter()
+            .filter_map(|r| {
+                Some(Candidate::new(
+                    r.name.clone(),
+                    r.version.clone()?,
+                    true,
+                    &[],
+                ))
+            })
+            .collect();
+        let (on_record, enrichment, owners) = futures::join!(
+            stream::iter(&names)
+                .map(|name| async move { advisories.advisories_of(name).await })
+                .buffered(self.concurrency.max(1))
+                .collect::<Vec<_>>(),
+            enrich(crates_io, candidates, 0, self.concurrency),
+            crate_owners(crates_io, &names, root, self.concurrency),
+        );
+        let owners = owners.ok();
+
+        for (review, on_record) in reviews.iter_mut().zip(on_record) {
+            match on_record {
+                Ok(on_record) => {
+                    review.affecting = on_record
+                        .iter()
+                        .filter(|a| a.informational.is_none())
+                        .filter(|a| review.version.as_ref().is_none_or(|v| a.affects(v)))
+                        .map(|a| a.id.clone())
+                        .collect();
+                    review.advisories = on_record;
+                }
+                Err(_) => review.failed.push("advisories".to_string()),
+            }
+
+            review.registry = enrichment
+                .enriched
+                .iter()
+                .find(|p| p.name == review.name)
+                .cloned();
+            match &review.registry {
+                Some(package) => {
+                    review.concerns = package.concerns(self.now);
+                    if !self.policy.is_empty() {
+                        review.license_permitted =
+                            package.license.as_deref().map(|l| self.policy.permits(l));
+                    }
+                }
+                None => review.failed.push("crates.io".to_string()),
+            }
+
+            review.owners = owners
+                .as_ref()
+                .and_then(|owners| owners.get(&review.name).cloned());
+            if review.owners.is_none() {
+                review.failed.push("owners".to_string());
+            }
+        }
+        reviews
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::redundancy::redundancy_groups;
+    use crate::core::dependency::DependencyKind;
+    use std::path::PathBuf;
+
+    fn manifest(dependencies: &str) -> Manifest {
+        Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            &format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n{}",
+                dependencies
+            ),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_added_dependencies() {
+        let before = manifest("[dependencies]\nserde = \"1\"\nfutures = \"0.3\"\n");
+        let after = manifest(
+            "[dependencies]\nserde = \"1\"\nfut = { package = \"futures\", version = \"0.3\" }\n\
+             chrono = \"0.4\"\nlocal = { path = \"../local\" }\n\n\
+             [dev-dependencies]\nchrono = \"0.4\"\ntempfile = \"3\"\n",
+        );
+
+        let added = added_dependencies(Some(&before), &after);
+        let names: Vec<&str> = added.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["chrono", "tempfile"]);
+        let kinds: Vec<DependencyKind> = added[0].sections.iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, [DependencyKind::Normal, DependencyKind::Dev]);
+        assert_eq!(added[0].requirement, "0.4");
+
+        // A manifest new at the base revision adds everything it declares
+        assert_eq!(added_dependencies(None, &before).len(), 2);
+    }
+
+    #[test]
+    fn test_introduced_packages() {
+        let manifest = manifest("[dependencies]\nreqwest = \"0.12\"\nserde = \"1\"\n");
+        let lockfile = Lockfile::parse(
+            PathBuf::from("Cargo.lock"),
+            r#"version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["reqwest", "serde"]
+
+[[package]]
+name = "reqwest"
+version = "0.12.4"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = ["hyper", "serde"]
+
+[[package]]
+name = "hyper"
+version = "1.3.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = ["bytes"]
+
+[[package]]
+name = "bytes"
+version = "1.6.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+
+        let version = locked_version(Some(&lockfile), "reqwest", "0.12").unwrap();
+        assert_eq!(version, Version::new(0, 12, 4));
+        // serde is a direct dependency of its own
+        assert_eq!(
+            introduced_packages(&lockfile, &manifest, "reqwest", &version).unwrap(),
+            ["bytes 1.6.0", "hyper 1.3.0"]
+        );
+        assert!(
+            introduced_packages(&lockfile, &manifest, "reqwest", &Version::new(0, 13, 0)).is_none()
+        );
+    }
+
+    #[test]
+    fn test_overlaps_name_existing_dependencies() {
+        let groups = redundancy_groups().unwrap();
+        let group = &groups[0];
+        let (new, existing) = (&group.crates[0], &group.crates[1]);
+        let direct = BTreeSet::from([new.clone(), existing.clone(), "serde".to_string()]);
+
+        let found = overlaps(&groups, new, &direct);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].crates, std::slice::from_ref(existing));
+        assert!(overlaps(&groups, "serde", &direct).is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+//! The project's own crates
+//!
+//! Workspace members, path dependencies and crates patched in from a path
+//! never come from a registry. Looking them up there earns a 404 at best,
+//! and at worst a different crate that happens to share the name, whose
+//! releases and advisories have nothing to do with the project. Such crates
+//! are set aside as internal: still listed, but kept out of the findings
+//! that fail a run.
+
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::core::workspace::Workspace;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InternalCrates {
+    /// Internal whatever the registry says
+    local: BTreeSet<String>,
+    /// Locked from the project's own git repository, so internal unless the
+    /// registry knows them
+    own_repository: BTreeSet<String>,
+}
+
+impl InternalCrates {
+    /// The internal crates of the project owning `manifest`: its package,
+    /// the workspace members when it's a workspace root, the path
+    /// dependencies of any of those, and whatever Cargo.lock records
+    /// without a source
+    pub fn discover(manifest: &Manifest, lockfile: Option<&Lockfile>) -> Self {
+        let mut manifests = vec![manifest.clone()];
+        if let Ok(Some(workspace)) = Workspace::load(manifest.clone()) {
+            manifests.extend(workspace.members);
+        }
+
+        let mut local = BTreeSet::new();
+        for manifest in &manifests {
+            local.extend(manifest.package_name().map(str::to_string));
+            for (name, nested) in manifest.path_dependencies() {
+                local.extend(nested.package_name().map(str::to_string));
+                local.insert(name);
+            }
+        }
+
+        let repository = manifest.repository().map(normalize_repository);
+        let mut own_repository = BTreeSet::new();
+        for package in lockfile.map_or(&[][..], |l| &l.packages) {
+            if package.source.is_none() {
+                local.insert(package.name.clone());
+            } else if let Some(git) = package.git_source() {
+                if repository.as_deref() == Some(normalize_repository(&git.url).as_str()) {
+                    own_repository.insert(package.name.clone());
+                }
+            }
+        }
+
+        Self {
+            local,
+            own_repository,
+        }
+    }
+
+    /// Whether `name` is internal whatever the registry says
+    pub fn contains(&self, name: &str) -> bool {
+        self.local.contains(name)
+    }
+
+    /// Whether `name`, which the registry doesn't know, is internal
+    pub fn is_unpublished_own(&self, name: &str) -> bool {
+        self.contains(name) || self.own_repository.contains(name)
+    }
+}
+
+/// `https://github.com/Org/Repo.git/` and `ssh://git@github.com/org/repo`
+/// name the same repository
+fn normalize_repository(url: &str) -> String {
+    let url = url.trim().to_lowercase();
+    let url = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    let url = url.strip_prefix("git@").unwrap_or(url);
+    url.trim_end_matches('/')
+        .trim_end_matches(".git")
+        .replacen(':', "/", 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_discover() {
+        let manifest = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            r#"[package]
+name = "app"
+version = "0.1.0"
+repository = "https://github.com/OurOrg/app"
+
+[dependencies]
+serde = "1.0"
+internal-util = "0.1"
+tools = { git = "https://github.com/ourorg/app" }
+forked = { git = "https://github.com/someone/forked" }
+"#,
+        )
+        .unwrap();
+        let lockfile = Lockfile::parse(
+            PathBuf::from("Cargo.lock"),
+            r#"
+[[package]]
+name = "internal-util"
+version = "0.1.0"
+
+[[package]]
+name = "tools"
+version = "0.2.0"
+source = "git+ssh://git@github.com/ourorg/app.git#0123456789abcdef"
+
+[[package]]
+name = "forked"
+version = "1.2.0"
+source = "git+https://github.com/someone/forked#0123456789abcdef"
+
+[[package]]
+name = "serde"
+version = "1.0.150"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+
+        let internal = InternalCrates::discover(&manifest, Some(&lockfile));
+        assert!(internal.contains("app"));
+        // Patched in from a path, so locked without a source
+        assert!(internal.contains("internal-util"));
+        assert!(!internal.contains("tools"));
+        assert!(internal.is_unpublished_own("tools"));
+        assert!(!internal.is_unpublished_own("forked"));
+        assert!(!internal.is_unpublished_own("serde"));
+    }
+
+    #[test]
+    fn test_normalize_repository() {
+        assert_eq!(
+            normalize_repository("https://github.com/OurOrg/App.git/"),
+            "github.com/ourorg/app"
+        );
+        assert_eq!(
+            normalize_repository("git@github.com:ourorg/app.git"),
+            "github.com/ourorg/app"
+        );
+    }
+}
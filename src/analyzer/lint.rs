@@ -0,0 +1,400 @@
+//! Lint dependency requirements that make builds drift
+//!
+//! A `*` requirement, an unbounded `>=` range or a git branch all resolve to
+//! something different depending on when the lockfile was last regenerated.
+//! Inherited declarations are checked as resolved, and a root's
+//! `[workspace.dependencies]` entries like any other declaration.
+//! Each finding carries a concrete replacement derived from Cargo.lock where
+//! the resolved version is known.
+
+use crate::core::dependency::GitReference;
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::{DependencySection, DependencySpec, Manifest, ResolvedDeclaration};
+use schemars::JsonSchema;
+use semver::{Op, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintKind {
+    /// `*` accepts any version at all
+    Wildcard,
+    /// A lower bound with no upper bound, e.g. `>=0`
+    Unbounded,
+    /// A git dependency following a branch instead of a fixed rev
+    GitBranch,
+    /// A path dependency without a version, which can't be published
+    PathWithoutVersion,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintFinding {
+    pub name: String,
+    pub section: DependencySection,
+    pub kind: LintKind,
+    pub severity: LintSeverity,
+    pub requirement: Option<String>,
+    pub message: String,
+    /// A replacement declaration, when the resolved version is known
+    pub suggestion: Option<String>,
+    /// The version from Cargo.lock a fix would pin to
+    pub resolved_version: Option<String>,
+    pub line: Option<usize>,
+    /// The requirement is a `[workspace.dependencies]` entry rather than a
+    /// declaration in `section`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub workspace: bool,
+    /// The workspace root whose `[workspace.dependencies]` the requirement
+    /// comes from, for `workspace = true` declarations of a member
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inherited_from: Option<PathBuf>,
+}
+
+impl fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            LintSeverity::Info => "info",
+            LintSeverity::Warning => "warning",
+            LintSeverity::Error => "error",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+impl LintFinding {
+    /// Whether `lint --fix` can rewrite this finding by itself: inherited
+    /// requirements are the workspace root's to change
+    pub fn is_fixable(&self) -> bool {
+        self.kind == LintKind::Wildcard
+            && self.resolved_version.is_some()
+            && self.inherited_from.is_none()
+    }
+
+    /// The table the requirement is in, e.g. `dependencies` or
+    /// `workspace.dependencies`
+    pub fn table(&self) -> String {
+        if self.workspace {
+            "workspace.dependencies".to_string()
+        } else {
+            self.section.to_string()
+        }
+    }
+}
+
+/// Check every dependency declaration of a manifest
+pub fn lint_manifest(manifest: &Manifest, lockfile: Option<&Lockfile>) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+
+    for declaration in manifest.resolved_declarations() {
+        let ResolvedDeclaration {
+            section,
+            name,
+            spec,
+            line,
+            workspace,
+            inherited_from,
+        } = declaration;
+        let finding = |kind, severity, message: String, suggestion, resolved| LintFinding {
+            name: name.clone(),
+            section: section.clone(),
+            kind,
+            severity,
+            requirement: spec.version().map(str::to_string),
+            message,
+            suggestion,
+            resolved_version: resolved,
+            line,
+            workspace,
+            inherited_from: inherited_from.clone(),
+        };
+
+        if let Some((_, reference)) = spec.git() {
+            if matches!(
+                reference,
+                GitReference::Branch(_) | GitReference::DefaultBranch
+            ) {
+                let commit = lockfile
+                    .and_then(|l| l.git_package(&name))
+                    .and_then(|p| p.git_source()?.commit);
+                findings.push(finding(
+                    LintKind::GitBranch,
+                    LintSeverity::Warning,
+                    format!("tracks the {} without a pinned rev", reference),
+                    commit.as_ref().map(|c| format!("rev = \"{}\"", c)),
+                    None,
+                ));
+            }
+            continue;
+        }
+
+        if spec.is_path() {
+            if spec.version().is_none() && manifest.is_publishable() {
+                let version = locked_version(lockfile, &name, false);
+                findings.push(finding(
+                    LintKind::PathWithoutVersion,
+                    LintSeverity::Warning,
+                    "path dependency has no version, so the package can't be published".to_string(),
+                    version.as_ref().map(|v| format!("version = \"{}\"", v)),
+                    version,
+                ));
+            }
+            continue;
+        }
+
+        let Some(requirement) = spec.version() else {
+            continue;
+        };
+        let kind = if requirement.trim() == "*" {
+            LintKind::Wildcard
+        } else if is_unbounded(requirement) {
+            LintKind::Unbounded
+        } else {
+            continue;
+        };
+
+        let resolved = locked_version(lockfile, &name, true);
+        let message = match kind {
+            LintKind::Wildcard => "wildcard requirement accepts any version".to_string(),
+            _ => format!("requirement \"{}\" has no upper bound", requirement),
+        };
+        let suggestion = resolved
+            .as_ref()
+            .map(|v| suggested_declaration(&name, &spec, v));
+        let severity = match kind {
+            LintKind::Wildcard => LintSeverity::Error,
+            _ => LintSeverity::Warning,
+        };
+        findings.push(finding(kind, severity, message, suggestion, resolved));
+    }
+
+    findings
+}
+
+/// Whether a requirement only bounds versions from below
+fn is_unbounded(requirement: &str) -> bool {
+    let Ok(req) = VersionReq::parse(requirement) else {
+        return false;
+    };
+    !req.comparators.is_empty()
+        && req
+            .comparators
+            .iter()
+            .all(|c| matches!(c.op, Op::Greater | Op::GreaterEq))
+}
+
+/// The highest version of `name` in Cargo.lock from a registry, or from a
+/// local path when `registry` is false
+fn locked_version(lockfile: Option<&Lockfile>, name: &str, registry: bool) -> Option<String> {
+    lockfile?
+        .packages_named(name)
+        .into_iter()
+        .filter(|p| p.is_registry() == registry && p.git_source().is_none())
+        .map(|p| p.version.to_string())
+        .next_back()
+}
+
+fn suggested_declaration(name: &str, spec: &DependencySpec, version: &str) -> String {
+    match spec {
+        DependencySpec::Simple(_) => format!("{} = \"{}\"", name, version),
+        DependencySpec::Detailed(_) => format!("version = \"{}\"", version),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const LOCKFILE: &str = r#"
+[[package]]
+name = "foo"
+version = "1.4.2"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "bar"
+version = "0.3.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "baz"
+version = "2.0.0"
+source = "git+https://github.com/ourorg/baz?branch=main#deadbeef"
+
+[[package]]
+name = "local"
+version = "0.2.0"
+"#;
+
+    fn lint(manifest: &str) -> Vec<LintFinding> {
+        let manifest = Manifest::parse(PathBuf::from("Cargo.toml"), manifest).unwrap();
+        let lockfile = Lockfile::parse(PathBuf::from("Cargo.lock"), LOCKFILE).unwrap();
+        lint_manifest(&manifest, Some(&lockfile))
+    }
+
+    #[test]
+    fn test_flags_drifting_requirements() {
+        let findings = lint(
+            r#"[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+foo = "*"
+bar = { version = ">=0", features = ["x"] }
+baz = { git = "https://github.com/ourorg/baz", branch = "main" }
+local = { path = "../local" }
+pinned = { git = "https://github.com/ourorg/pinned", rev = "abc" }
+fine = "1.2"
+"#,
+        );
+
+        let kinds: Vec<(&str, LintKind, LintSeverity)> = findings
+            .iter()
+            .map(|f| (f.name.as_str(), f.kind, f.severity))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("bar", LintKind::Unbounded, LintSeverity::Warning),
+                ("baz", LintKind::GitBranch, LintSeverity::Warning),
+                ("foo", LintKind::Wildcard, LintSeverity::Error),
+                ("local", LintKind::PathWithoutVersion, LintSeverity::Warning),
+            ]
+        );
+
+        assert_eq!(
+            findings[0].suggestion.as_deref(),
+            Some("version = \"0.3.1\"")
+        );
+        assert_eq!(
+            findings[1].suggestion.as_deref(),
+            Some("rev = \"deadbeef\"")
+        );
+        assert_eq!(findings[2].suggestion.as_deref(), Some("foo = \"1.4.2\""));
+        assert!(findings[2].is_fixable());
+        assert!(!findings[0].is_fixable());
+        assert_eq!(
+            findings[3].suggestion.as_deref(),
+            Some("version = \"0.2.0\"")
+        );
+        assert_eq!(findings[2].line, Some(6));
+    }
+
+    #[test]
+    fn test_unpublished_path_dependencies_are_fine() {
+        let findings = lint(
+            r#"[package]
+name = "demo"
+version = "0.1.0"
+publish = false
+
+[dependencies]
+local = { path = "../local" }
+"#,
+        );
+        assert!(findings.is_empty());
+    }
+
+    #[test]
+    fn test_workspace_dependencies_are_linted() {
+        let root = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            r#"[workspace]
+members = ["a"]
+
+[workspace.dependencies]
+zeta = "*"
+foo = { version = "*", features = ["x"] }
+bar = "0.3"
+"#,
+        )
+        .unwrap();
+        let lockfile = Lockfile::parse(PathBuf::from("Cargo.lock"), LOCKFILE).unwrap();
+        let findings = lint_manifest(&root, Some(&lockfile));
+        let found: Vec<(&str, LintKind, String, Option<usize>)> = findings
+            .iter()
+            .map(|f| (f.name.as_str(), f.kind, f.table(), f.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "foo",
+                    LintKind::Wildcard,
+                    "workspace.dependencies".to_string(),
+                    Some(6)
+                ),
+                (
+                    "zeta",
+                    LintKind::Wildcard,
+                    "workspace.dependencies".to_string(),
+                    Some(5)
+                ),
+            ]
+        );
+        assert_eq!(
+            findings[0].suggestion.as_deref(),
+            Some("version = \"1.4.2\"")
+        );
+        assert!(findings[0].is_fixable());
+        assert_eq!(findings[1].severity, LintSeverity::Error);
+
+        // A member sees the requirement it inherits, but leaves fixing it
+        // to the root
+        let member = Manifest::parse(
+            PathBuf::from("a/Cargo.toml"),
+            r#"[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+foo = { workspace = true }
+bar.workspace = true
+"#,
+        )
+        .unwrap()
+        .with_workspace_root(&root);
+        let findings = lint_manifest(&member, Some(&lockfile));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].name, "foo");
+        assert_eq!(findings[0].requirement.as_deref(), Some("*"));
+        assert_eq!(findings[0].line, Some(6));
+        assert_eq!(
+            findings[0].inherited_from.as_deref(),
+            Some(std::path::Path::new("Cargo.toml"))
+        );
+        assert!(!findings[0].is_fixable());
+    }
+
+    #[test]
+    fn test_is_unbounded() {
+        assert!(is_unbounded(">=0"));
+        assert!(is_unbounded("> 1.2"));
+        assert!(!is_unbounded(">=1, <2"));
+        assert!(!is_unbounded("1.2"));
+        assert!(!is_unbounded("~1"));
+    }
+}
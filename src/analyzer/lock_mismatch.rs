@@ -0,0 +1,250 @@
+//! Requirements Cargo.lock no longer satisfies
+//!
+//! Editing `tokio = "1"` to `tokio = "2"` doesn't touch Cargo.lock until
+//! something forces a resolve, so the lockfile can go on pinning 1.x, and
+//! `--locked` builds ship the old version or fail. Each direct dependency's
+//! requirement, inherited ones resolved, and each `[workspace.dependencies]`
+//! entry is compared with the registry versions locked for it.
+
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::{DependencySection, Manifest, ResolvedDeclaration};
+use crate::core::version::without_build_metadata;
+use schemars::JsonSchema;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LockMismatch {
+    /// The name the dependency is declared under
+    pub name: String,
+    /// The package locked for it, which differs from `name` when the
+    /// dependency is renamed
+    pub package: String,
+    pub section: DependencySection,
+    pub requirement: String,
+    /// The versions Cargo.lock holds, none of which satisfies the requirement
+    pub locked: Vec<Version>,
+    /// The `cargo update` invocation that brings Cargo.lock in line
+    pub command: String,
+    /// 1-based line of the declaration
+    pub line: Option<usize>,
+    /// The requirement is a `[workspace.dependencies]` entry rather than a
+    /// declaration in `section`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub workspace: bool,
+    /// The workspace root whose `[workspace.dependencies]` the requirement
+    /// comes from, for `workspace = true` declarations of a member
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inherited_from: Option<PathBuf>,
+}
+
+impl LockMismatch {
+    /// The table the requirement is in, e.g. `dependencies` or
+    /// `workspace.dependencies`
+    pub fn table(&self) -> String {
+        if self.workspace {
+            "workspace.dependencies".to_string()
+        } else {
+            self.section.to_string()
+        }
+    }
+
+    /// The package spec `cargo update -p` takes, `name@version` when more
+    /// than one version is locked
+    pub fn package_spec(&self) -> String {
+        match self.locked.as_slice() {
+            [version, _, ..] => format!("{}@{}", self.package, version),
+            _ => self.package.clone(),
+        }
+    }
+}
+
+/// Every registry dependency of `manifest` whose requirement matches none of
+/// the versions locked for it. Dependencies Cargo.lock doesn't know yet are
+/// left out: the next build resolves them anyway.
+pub fn find_lock_mismatches(manifest: &Manifest, lockfile: &Lockfile) -> Vec<LockMismatch> {
+    let mut mismatches = Vec::new();
+    for declaration in manifest.resolved_declarations() {
+        let ResolvedDeclaration {
+            section,
+            name,
+            spec,
+            line,
+            workspace,
+            inherited_from,
+        } = declaration;
+        if !spec.is_crates_io() {
+            continue;
+        }
+        let Some(requirement) = spec.version() else {
+            continue;
+        };
+        let Ok(req) = VersionReq::parse(&without_build_metadata(requirement)) else {
+            continue;
+        };
+
+        let package = spec.package().unwrap_or(&name);
+        let locked: Vec<Version> = lockfile
+            .packages_named(package)
+            .into_iter()
+            .filter(|p| p.is_registry())
+            .map(|p| p.version.clone())
+            .collect();
+        if locked.is_empty() || locked.iter().any(|v| req.matches(v)) {
+            continue;
+        }
+
+        let mut mismatch = LockMismatch {
+            line,
+            workspace,
+            inherited_from,
+            package: package.to_string(),
+            name: name.clone(),
+            section,
+            requirement: requirement.to_string(),
+            locked,
+            command: String::new(),
+        };
+        mismatch.command = format!("cargo update -p {}", mismatch.package_spec());
+        mismatches.push(mismatch);
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_find_lock_mismatches() {
+        let manifest = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            r#"[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+tokio = "2"
+serde = "1.0"
+rand = "0.9"
+http = { version = "1", package = "http-types" }
+fresh = "1"
+local = { path = "../local", version = "0.3" }
+"#,
+        )
+        .unwrap();
+        let lockfile = Lockfile::parse(
+            PathBuf::from("Cargo.lock"),
+            r#"
+[[package]]
+name = "tokio"
+version = "1.38.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "rand"
+version = "0.7.3"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "rand"
+version = "0.8.5"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "http-types"
+version = "2.12.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "local"
+version = "0.2.0"
+"#,
+        )
+        .unwrap();
+
+        let mismatches = find_lock_mismatches(&manifest, &lockfile);
+        let found: Vec<(&str, &str)> = mismatches
+            .iter()
+            .map(|m| (m.name.as_str(), m.command.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("http", "cargo update -p http-types"),
+                ("rand", "cargo update -p rand@0.7.3"),
+                ("tokio", "cargo update -p tokio"),
+            ]
+        );
+        assert_eq!(mismatches[2].locked, vec![Version::new(1, 38, 0)]);
+        assert_eq!(mismatches[2].line, Some(6));
+    }
+
+    #[test]
+    fn test_workspace_requirements_are_compared() {
+        let root = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            r#"[workspace]
+members = ["a"]
+
+[workspace.dependencies]
+tokio = "2"
+serde = "1.0"
+"#,
+        )
+        .unwrap();
+        let member = Manifest::parse(
+            PathBuf::from("a/Cargo.toml"),
+            r#"[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+tokio = { workspace = true, features = ["rt"] }
+serde = { workspace = true }
+"#,
+        )
+        .unwrap()
+        .with_workspace_root(&root);
+        let lockfile = Lockfile::parse(
+            PathBuf::from("Cargo.lock"),
+            r#"
+[[package]]
+name = "tokio"
+version = "1.38.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+
+        let at_root = find_lock_mismatches(&root, &lockfile);
+        assert_eq!(at_root.len(), 1);
+        assert_eq!(at_root[0].name, "tokio");
+        assert_eq!(at_root[0].requirement, "2");
+        assert_eq!(at_root[0].table(), "workspace.dependencies");
+        assert_eq!(at_root[0].line, Some(5));
+
+        let in_member = find_lock_mismatches(&member, &lockfile);
+        assert_eq!(in_member.len(), 1);
+        assert_eq!(in_member[0].requirement, "2");
+        assert_eq!(in_member[0].locked, vec![Version::new(1, 38, 0)]);
+        assert_eq!(in_member[0].table(), "dependencies");
+        assert_eq!(in_member[0].line, Some(6));
+        assert_eq!(
+            in_member[0].inherited_from.as_deref(),
+            Some(Path::new("Cargo.toml"))
+        );
+    }
+}
@@ -1,5 +1,43 @@
 //! Dependency analysis
 
+pub mod accepted;
+pub mod attribution;
+pub mod build_time;
+pub mod build_units;
+pub mod cfg_usage;
 pub mod checker;
+pub mod checksums;
+pub mod compare;
 pub mod conflicts;
+pub mod declarations;
+pub mod detail;
+pub mod embedded;
+pub mod enrichment;
+pub mod feature_consistency;
+pub mod feature_table;
+pub mod features;
+pub mod freshness;
 pub mod health;
+pub mod history;
+pub mod impact;
+pub mod inheritance;
+pub mod intake;
+pub mod internal;
+pub mod lint;
+pub mod lock_mismatch;
+pub mod msrv;
+pub mod ownership;
+pub mod plugin;
+pub mod priority;
+pub mod redundancy;
+pub mod size;
+pub mod snapshot;
+pub mod snoozed;
+pub mod stats;
+pub mod std_replacements;
+pub mod system_libs;
+pub mod teams;
+pub mod usage;
+pub mod usage_cache;
+pub mod workflows;
+pub mod workspace;
@@ -0,0 +1,139 @@
+//! Find workspace members an update would push past their `rust-version`
+//!
+//! A workspace has one Cargo.lock, so a crate resolves to the same release
+//! for every member that uses it, whether declared directly or inherited
+//! from `[workspace.dependencies]`. Raising it for one member raises it for
+//! all of them, including a member that promises to build on an older Rust
+//! than the new release supports.
+
+use crate::core::version::format_rust_version;
+use crate::core::workspace::Workspace;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A member whose declared `rust-version` is older than an update target needs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MsrvConflict {
+    /// The member's directory relative to the workspace root
+    pub member: String,
+    pub declared: Version,
+    pub name: String,
+    pub target: Version,
+    /// The `rust-version` the target release declares
+    pub required: Version,
+}
+
+impl fmt::Display for MsrvConflict {
+    /// e.g. "crates/embedded declares 1.65; tokio 1.38.0 needs 1.70"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} declares {}; {} {} needs {}",
+            self.member,
+            format_rust_version(&self.declared),
+            self.name,
+            self.target,
+            format_rust_version(&self.required)
+        )
+    }
+}
+
+/// The members that use `name` and declare a `rust-version` older than
+/// `required`, the one its `target` release declares
+pub fn msrv_conflicts(
+    workspace: &Workspace,
+    name: &str,
+    target: &Version,
+    required: &Version,
+) -> Vec<MsrvConflict> {
+    workspace
+        .members
+        .iter()
+        .filter(|member| {
+            member
+                .declarations()
+                .iter()
+                .any(|(_, key, spec)| spec.package().unwrap_or(key) == name)
+        })
+        .filter_map(|member| {
+            let declared = workspace.rust_version_of(member)?;
+            (declared < *required).then(|| MsrvConflict {
+                member: workspace.member_dir(member),
+                declared,
+                name: name.to_string(),
+                target: target.clone(),
+                required: required.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::manifest::Manifest;
+    use std::fs;
+    use std::path::Path;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_msrv_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\"]\n\n[workspace.package]\nrust-version = \"1.65\"\n\n\
+             [workspace.dependencies]\ntokio = \"1.30\"\n",
+        );
+        // Inherits both the dependency and the rust-version
+        write(
+            root,
+            "crates/embedded/Cargo.toml",
+            "[package]\nname = \"embedded\"\nrust-version.workspace = true\n\n\
+             [dependencies]\ntokio = { workspace = true }\n",
+        );
+        // New enough
+        write(
+            root,
+            "crates/server/Cargo.toml",
+            "[package]\nname = \"server\"\nrust-version = \"1.75\"\n\n\
+             [dependencies]\ntokio = \"1.30\"\n",
+        );
+        // Renamed, and no rust-version at all
+        write(
+            root,
+            "crates/tools/Cargo.toml",
+            "[package]\nname = \"tools\"\n\n[dependencies]\nrt = { package = \"tokio\", version = \"1\" }\n",
+        );
+        // Old, but doesn't use tokio
+        write(
+            root,
+            "crates/legacy/Cargo.toml",
+            "[package]\nname = \"legacy\"\nrust-version = \"1.56\"\n",
+        );
+
+        let manifest = Manifest::from_path(&root.join("Cargo.toml")).unwrap();
+        let workspace = Workspace::load(manifest).unwrap().unwrap();
+        let target = Version::new(1, 38, 0);
+
+        let conflicts = msrv_conflicts(&workspace, "tokio", &target, &Version::new(1, 70, 0));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].to_string(),
+            "crates/embedded declares 1.65; tokio 1.38.0 needs 1.70"
+        );
+
+        assert!(msrv_conflicts(&workspace, "tokio", &target, &Version::new(1, 63, 0)).is_empty());
+        let conflicts = msrv_conflicts(&workspace, "tokio", &target, &Version::new(1, 80, 0));
+        let members: Vec<&str> = conflicts.iter().map(|c| c.member.as_str()).collect();
+        assert_eq!(members, vec!["crates/embedded", "crates/server"]);
+    }
+}
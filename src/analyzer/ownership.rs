@@ -0,0 +1,226 @@
+//! Changes in who owns and publishes dependencies
+//!
+//! A crate changing hands is usually a routine handover, but it is also how
+//! several supply-chain attacks started. These findings are informational:
+//! they compare owner lists with the last snapshot that recorded them, and
+//! flag a latest release published by someone who was never an owner.
+
+use crate::core::dependency::Dependency;
+use crate::utils::owners::Owners;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Team logins look like `github:<org>:<team>`
+const TEAM_PREFIX: &str = "github:";
+
+/// An ownership signal worth a look before updating
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct OwnershipChange {
+    pub name: String,
+    /// Owners added since the last snapshot
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub added: Vec<String>,
+    /// Owners removed since the last snapshot
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+    /// The latest version's publisher, when they were never an owner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unfamiliar_publisher: Option<Publisher>,
+    /// Where to review the crate's owners
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Publisher {
+    pub login: String,
+    pub version: Version,
+}
+
+/// Compare `current` owners with `previous` ones (empty when no snapshot
+/// recorded any) and check who published each dependency's latest version.
+/// Crates absent from `previous` have no history and aren't compared.
+pub fn ownership_changes(
+    previous: &Owners,
+    current: &Owners,
+    dependencies: &[Dependency],
+) -> Vec<OwnershipChange> {
+    let mut changes: Vec<OwnershipChange> = current
+        .iter()
+        .filter_map(|(name, owners)| {
+            let before = previous.get(name)?;
+            let (added, removed) = difference(before, owners);
+            if added.is_empty() && removed.is_empty() {
+                return None;
+            }
+            Some(OwnershipChange {
+                added,
+                removed,
+                ..OwnershipChange::new(name)
+            })
+        })
+        .collect();
+
+    for dep in dependencies {
+        let Some(publisher) = unfamiliar_publisher(dep, previous, current) else {
+            continue;
+        };
+        match changes.iter_mut().find(|c| c.name == dep.name) {
+            Some(change) => change.unfamiliar_publisher = Some(publisher),
+            None => changes.push(OwnershipChange {
+                unfamiliar_publisher: Some(publisher),
+                ..OwnershipChange::new(&dep.name)
+            }),
+        }
+    }
+
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    changes
+}
+
+impl OwnershipChange {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            added: Vec::new(),
+            removed: Vec::new(),
+            unfamiliar_publisher: None,
+            url: owners_url(name),
+        }
+    }
+}
+
+/// The crate's crates.io page, which lists its owners
+pub fn owners_url(name: &str) -> String {
+    format!("https://crates.io/crates/{}", name)
+}
+
+/// The publisher of an update we'd move to, if no known owner list of the
+/// crate includes them. Crates owned by a team are skipped: any member may
+/// publish, and team membership isn't visible.
+fn unfamiliar_publisher(
+    dep: &Dependency,
+    previous: &Owners,
+    current: &Owners,
+) -> Option<Publisher> {
+    let login = dep.latest_published_by.as_ref()?;
+    let latest = dep.latest_version.as_ref().filter(|_| dep.has_update())?;
+    let known: BTreeSet<&String> = previous
+        .get(&dep.name)
+        .into_iter()
+        .chain(current.get(&dep.name))
+        .flatten()
+        .collect();
+    if known.is_empty()
+        || known.contains(login)
+        || known.iter().any(|owner| owner.starts_with(TEAM_PREFIX))
+    {
+        return None;
+    }
+    Some(Publisher {
+        login: login.clone(),
+        version: latest.clone(),
+    })
+}
+
+fn difference(before: &[String], after: &[String]) -> (Vec<String>, Vec<String>) {
+    let before: BTreeSet<&String> = before.iter().collect();
+    let after: BTreeSet<&String> = after.iter().collect();
+    (
+        after.difference(&before).map(|s| s.to_string()).collect(),
+        before.difference(&after).map(|s| s.to_string()).collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owners(entries: &[(&str, &[&str])]) -> Owners {
+        entries
+            .iter()
+            .map(|(name, logins)| {
+                (
+                    name.to_string(),
+                    logins.iter().map(|l| l.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn dep(name: &str, latest: &str, publisher: &str) -> Dependency {
+        let mut dep = Dependency::new(name.to_string(), Version::new(1, 0, 0), true)
+            .with_latest(Version::parse(latest).unwrap());
+        dep.latest_published_by = Some(publisher.to_string());
+        dep
+    }
+
+    #[test]
+    fn test_owner_set_changes_since_snapshot() {
+        let previous = owners(&[
+            ("left-pad", &["alice"]),
+            ("serde", &["dtolnay"]),
+            ("tokio", &["carllerche"]),
+        ]);
+        let current = owners(&[
+            ("left-pad", &["mallory"]),
+            ("serde", &["dtolnay"]),
+            ("tokio", &["carllerche", "darksonn"]),
+            ("rand", &["newcomer"]),
+        ]);
+
+        let changes = ownership_changes(&previous, &current, &[]);
+        let summary: Vec<(&str, Vec<String>, Vec<String>)> = changes
+            .iter()
+            .map(|c| (c.name.as_str(), c.added.clone(), c.removed.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "left-pad",
+                    vec!["mallory".to_string()],
+                    vec!["alice".to_string()]
+                ),
+                ("tokio", vec!["darksonn".to_string()], vec![]),
+            ]
+        );
+        assert_eq!(changes[0].url, "https://crates.io/crates/left-pad");
+    }
+
+    #[test]
+    fn test_unfamiliar_publisher() {
+        let current = owners(&[
+            ("foo", &["alice"]),
+            ("bar", &["alice"]),
+            ("baz", &["github:org:release"]),
+        ]);
+        let deps = vec![
+            dep("foo", "1.1.0", "mallory"),
+            dep("bar", "1.1.0", "alice"),
+            dep("baz", "1.1.0", "bob"),
+            dep("qux", "1.1.0", "bob"),
+            dep("foo-up-to-date", "1.0.0", "mallory"),
+        ];
+
+        let changes = ownership_changes(&Owners::new(), &current, &deps);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].unfamiliar_publisher,
+            Some(Publisher {
+                login: "mallory".to_string(),
+                version: Version::new(1, 1, 0),
+            })
+        );
+    }
+
+    #[test]
+    fn test_former_owner_is_familiar() {
+        let previous = owners(&[("foo", &["alice"])]);
+        let current = owners(&[("foo", &["bob"])]);
+        let changes = ownership_changes(&previous, &current, &[dep("foo", "2.0.0", "alice")]);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].unfamiliar_publisher, None);
+    }
+}
@@ -0,0 +1,335 @@
+//! Custom analyzers whose findings join `cargo sane report`
+//!
+//! An [`Analyzer`] looks at the [`ProjectAnalysis`] the report gathered and
+//! returns [`Finding`]s. Analyzers are compiled in through [`register`]
+//! (with the `plugins` feature), or run as external programs with
+//! `report --plugin`: the program gets the analysis as JSON on stdin and
+//! prints a [`PluginOutput`] on stdout. Output that doesn't match the
+//! schema, or a program exiting unsuccessfully, fails that analyzer as a
+//! whole, so a half-read answer never passes for a clean one. Findings stay
+//! grouped under the analyzer that produced them.
+
+use crate::analyzer::checker::CheckReport;
+use crate::analyzer::conflicts::ConflictReport;
+use crate::analyzer::health::HealthReport;
+use crate::analyzer::lint::LintSeverity;
+use crate::analyzer::snapshot::Snapshot;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// Version of the stdin/stdout protocol, bumped when a field changes
+/// meaning or goes away. Added fields don't bump it.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// What an analyzer gets to look at: the check, advisory and conflict
+/// results of the project being reported on
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectAnalysis {
+    /// [`PROTOCOL_VERSION`] of the sender
+    pub protocol: u32,
+    pub manifest: PathBuf,
+    pub package: Option<String>,
+    pub check: CheckReport,
+    /// Missing when the advisory lookup failed
+    pub health: Option<HealthReport>,
+    /// Missing when `cargo tree` could not be run
+    pub conflicts: Option<ConflictReport>,
+}
+
+impl ProjectAnalysis {
+    pub fn new(snapshot: &Snapshot) -> Self {
+        Self {
+            protocol: PROTOCOL_VERSION,
+            manifest: snapshot.check.manifest.clone(),
+            package: snapshot.check.package.clone(),
+            check: snapshot.check.clone(),
+            health: snapshot.health.clone(),
+            conflicts: snapshot.conflicts.clone(),
+        }
+    }
+}
+
+/// One problem an analyzer found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Finding {
+    /// A short, stable name for the check, such as `stale-internal-crate`
+    pub code: String,
+    pub severity: LintSeverity,
+    pub message: String,
+    /// The dependency the finding is about, if it's about one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependency: Option<String>,
+}
+
+/// What an external analyzer prints on stdout
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PluginOutput {
+    pub findings: Vec<Finding>,
+}
+
+/// A check to run over every report
+pub trait Analyzer: Send + Sync {
+    /// The name findings are attributed to
+    fn name(&self) -> &str;
+
+    fn run(&self, analysis: &ProjectAnalysis) -> Result<Vec<Finding>>;
+
+    /// The program behind the analyzer, for external ones
+    fn program(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// The findings of one analyzer, or why it produced none
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AnalyzerFindings {
+    pub analyzer: String,
+    /// The program run, for analyzers given with `--plugin`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program: Option<PathBuf>,
+    pub findings: Vec<Finding>,
+    /// Set when the analyzer failed; its findings are then empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AnalyzerFindings {
+    /// Whether a finding reaches `threshold`
+    pub fn reaches(&self, threshold: LintSeverity) -> bool {
+        self.findings.iter().any(|f| f.severity >= threshold)
+    }
+}
+
+#[cfg(feature = "plugins")]
+static REGISTERED: std::sync::Mutex<Vec<Arc<dyn Analyzer>>> = std::sync::Mutex::new(Vec::new());
+
+/// Add an analyzer to every report this process makes
+#[cfg(feature = "plugins")]
+pub fn register(analyzer: impl Analyzer + 'static) {
+    if let Ok(mut registered) = REGISTERED.lock() {
+        registered.push(Arc::new(analyzer));
+    }
+}
+
+/// The analyzers compiled in with [`register`]
+pub fn registered() -> Vec<Arc<dyn Analyzer>> {
+    #[cfg(feature = "plugins")]
+    if let Ok(registered) = REGISTERED.lock() {
+        return registered.clone();
+    }
+    Vec::new()
+}
+
+/// Run every analyzer over `analysis`, in order, keeping the failures
+/// next to the findings
+pub fn run_analyzers(
+    analyzers: &[Arc<dyn Analyzer>],
+    analysis: &ProjectAnalysis,
+) -> Vec<AnalyzerFindings> {
+    analyzers
+        .iter()
+        .map(|analyzer| {
+            let (findings, error) = match analyzer.run(analysis) {
+                Ok(findings) => (findings, None),
+                Err(e) => (Vec::new(), Some(format!("{:#}", e))),
+            };
+            AnalyzerFindings {
+                analyzer: analyzer.name().to_string(),
+                program: analyzer.program().map(Path::to_path_buf),
+                findings,
+                error,
+            }
+        })
+        .collect()
+}
+
+/// An analyzer run as a separate program, speaking JSON over stdin and
+/// stdout
+pub struct ExternalAnalyzer {
+    program: PathBuf,
+    name: String,
+}
+
+impl ExternalAnalyzer {
+    /// The program at `program`, named after its file stem
+    pub fn new(program: PathBuf) -> Self {
+        let name = program
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| program.display().to_string());
+        Self { program, name }
+    }
+}
+
+impl Analyzer for ExternalAnalyzer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn program(&self) -> Option<&Path> {
+        Some(&self.program)
+    }
+
+    fn run(&self, analysis: &ProjectAnalysis) -> Result<Vec<Finding>> {
+        let input = serde_json::to_vec(analysis)?;
+        let mut child = Command::new(&self.program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", self.program.display()))?;
+
+        // Written from another thread, so a plugin answering before it has
+        // read everything can't leave both sides waiting on a full pipe
+        let mut stdin = child
+            .stdin
+            .take()
+            .context("The plugin's stdin was not piped")?;
+        let writer = std::thread::spawn(move || {
+            // A plugin that doesn't read its input is free not to
+            let _ = stdin.write_all(&input);
+        });
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("Failed to run {}", self.program.display()))?;
+        let _ = writer.join();
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!(
+                "{} exited with {}{}",
+                self.program.display(),
+                output.status,
+                match stderr.trim() {
+                    "" => String::new(),
+                    stderr => format!(": {}", stderr),
+                }
+            );
+        }
+        parse_output(&output.stdout)
+    }
+}
+
+/// The findings in a plugin's stdout, which must be a [`PluginOutput`]
+/// with a code and a message on every finding
+pub fn parse_output(stdout: &[u8]) -> Result<Vec<Finding>> {
+    let output: PluginOutput = serde_json::from_slice(stdout).context(
+        "The plugin's output doesn't match the plugin output schema (see `cargo sane schema plugin-output`)",
+    )?;
+    for (i, finding) in output.findings.iter().enumerate() {
+        if finding.code.trim().is_empty() {
+            anyhow::bail!("Finding {} of the plugin's output has an empty code", i + 1);
+        }
+        if finding.message.trim().is_empty() {
+            anyhow::bail!(
+                "Finding {} ({}) of the plugin's output has an empty message",
+                i + 1,
+                finding.code
+            );
+        }
+    }
+    Ok(output.findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::checker::test_support::check_report;
+
+    fn empty_analysis() -> ProjectAnalysis {
+        serde_json::from_value(serde_json::json!({
+            "protocol": PROTOCOL_VERSION,
+            "manifest": "Cargo.toml",
+            "package": null,
+            "check": check_report(Vec::new()),
+            "health": null,
+            "conflicts": null,
+        }))
+        .unwrap()
+    }
+
+    struct Fixed(&'static str, Result<LintSeverity, &'static str>);
+
+    impl Analyzer for Fixed {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn run(&self, _: &ProjectAnalysis) -> Result<Vec<Finding>> {
+            let severity = self.1.map_err(|e| anyhow::anyhow!(e))?;
+            Ok(vec![Finding {
+                code: "fixed".to_string(),
+                severity,
+                message: format!("from {}", self.0),
+                dependency: None,
+            }])
+        }
+    }
+
+    #[test]
+    fn test_findings_are_attributed_to_their_analyzer() {
+        let analyzers: Vec<Arc<dyn Analyzer>> = vec![
+            Arc::new(Fixed("policy", Ok(LintSeverity::Warning))),
+            Arc::new(Fixed("broken", Err("registry unreachable"))),
+        ];
+        let results = run_analyzers(&analyzers, &empty_analysis());
+        assert_eq!(results[0].analyzer, "policy");
+        assert_eq!(results[0].findings[0].message, "from policy");
+        assert!(results[0].reaches(LintSeverity::Warning));
+        assert!(!results[0].reaches(LintSeverity::Error));
+        assert_eq!(results[1].analyzer, "broken");
+        assert!(results[1].findings.is_empty());
+        assert_eq!(results[1].error.as_deref(), Some("registry unreachable"));
+    }
+
+    #[cfg(feature = "plugins")]
+    #[test]
+    fn test_registered_analyzers_run_with_every_report() {
+        register(Fixed("compiled-in", Ok(LintSeverity::Info)));
+        let results = run_analyzers(&registered(), &empty_analysis());
+        assert!(results.iter().any(|r| r.analyzer == "compiled-in"));
+    }
+
+    #[test]
+    fn test_parse_output_accepts_the_schema() {
+        let findings = parse_output(
+            br#"{"findings": [
+                {"code": "stale-internal-crate", "severity": "error",
+                 "message": "acme-auth is 2 releases behind", "dependency": "acme-auth"},
+                {"code": "policy", "severity": "info", "message": "checked 3 crates"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].severity, LintSeverity::Error);
+        assert_eq!(findings[1].dependency, None);
+        assert!(parse_output(br#"{"findings": []}"#).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_output_rejects_malformed_output() {
+        for malformed in [
+            &b""[..],
+            b"not json",
+            b"[]",
+            br#"{"findings": [{"code": "x", "message": "m"}]}"#,
+            br#"{"findings": [{"code": "x", "severity": "fatal", "message": "m"}]}"#,
+            br#"{"findings": [{"code": "x", "severity": "info", "message": "m", "extra": 1}]}"#,
+            br#"{"findings": [{"code": " ", "severity": "info", "message": "m"}]}"#,
+            br#"{"findings": [{"code": "x", "severity": "info", "message": ""}]}"#,
+        ] {
+            assert!(
+                parse_output(malformed).is_err(),
+                "{}",
+                String::from_utf8_lossy(malformed)
+            );
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Rank findings by how much they deserve attention
+//!
+//! Every command that truncates its output orders entries with the same
+//! comparator: vulnerable, then major updates, then yanked versions in use,
+//! then minor and patch updates. Within a class, the further behind, the
+//! more significant.
+
+use crate::core::dependency::{Dependency, UpdateType};
+use semver::Version;
+use std::cmp::Reverse;
+
+/// The kind of finding, least significant first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Class {
+    UpToDate,
+    Patch,
+    Minor,
+    Yanked,
+    Major,
+    Vulnerable,
+}
+
+/// How significant a finding is; greater is more significant
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Significance {
+    pub class: Class,
+    /// Major, minor and patch releases between current and target
+    pub staleness: (u64, u64, u64),
+}
+
+impl Significance {
+    pub fn new(class: Class, current: &Version, target: Option<&Version>) -> Self {
+        Self {
+            class,
+            staleness: target.map_or((0, 0, 0), |target| staleness(current, target)),
+        }
+    }
+
+    /// Rank a checked dependency, given whether it has open advisories
+    pub fn of_dependency(dep: &Dependency, vulnerable: bool) -> Self {
+        let class = if vulnerable {
+            Class::Vulnerable
+        } else {
+            match dep.update_type() {
+                UpdateType::Major => Class::Major,
+                _ if dep.yanked => Class::Yanked,
+                UpdateType::Minor => Class::Minor,
+                UpdateType::Patch => Class::Patch,
+                UpdateType::UpToDate => Class::UpToDate,
+            }
+        };
+        Self::new(class, &dep.current_version, dep.latest_version.as_ref())
+    }
+}
+
+/// Sort `items` most significant first, keeping ties in their current order
+pub fn rank<T>(items: &mut [T], significance: impl Fn(&T) -> Significance) {
+    items.sort_by_cached_key(|item| Reverse(significance(item)));
+}
+
+/// The first `limit` items (all of them for 0) and how many were left out
+pub fn truncate<T>(items: &[T], limit: usize) -> (&[T], usize) {
+    if limit == 0 || items.len() <= limit {
+        (items, 0)
+    } else {
+        (&items[..limit], items.len() - limit)
+    }
+}
+
+fn staleness(current: &Version, target: &Version) -> (u64, u64, u64) {
+    if target <= current {
+        return (0, 0, 0);
+    }
+    if target.major != current.major {
+        (target.major.saturating_sub(current.major), target.minor, 0)
+    } else if target.minor != current.minor {
+        (0, target.minor - current.minor, target.patch)
+    } else {
+        (0, 0, target.patch - current.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dependency::test_support::dep;
+
+    #[test]
+    fn test_classes_rank_in_order() {
+        let mut yanked = dep("yanked", "1.2.0", "1.2.1");
+        yanked.yanked = true;
+        let deps = vec![
+            (dep("patch", "1.0.0", "1.0.9"), false),
+            (dep("minor", "1.0.0", "1.1.0"), false),
+            (dep("current", "1.0.0", "1.0.0"), false),
+            (dep("major", "1.0.0", "2.0.0"), false),
+            (yanked, false),
+            (dep("vulnerable", "1.0.0", "1.0.1"), true),
+        ];
+
+        let mut ranked = deps.clone();
+        rank(&mut ranked, |(d, vulnerable)| {
+            Significance::of_dependency(d, *vulnerable)
+        });
+        let names: Vec<&str> = ranked.iter().map(|(d, _)| d.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["vulnerable", "major", "yanked", "minor", "patch", "current"]
+        );
+    }
+
+    #[test]
+    fn test_staleness_breaks_ties() {
+        let mut deps = vec![
+            dep("one-minor", "1.0.0", "1.1.0"),
+            dep("five-minors", "1.0.0", "1.5.0"),
+            dep("one-minor-more-patches", "1.0.0", "1.1.7"),
+            dep("three-majors", "1.0.0", "4.0.0"),
+            dep("one-major", "1.9.0", "2.0.0"),
+        ];
+        rank(&mut deps, |d| Significance::of_dependency(d, false));
+        let names: Vec<&str> = deps.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "three-majors",
+                "one-major",
+                "five-minors",
+                "one-minor-more-patches",
+                "one-minor"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        let items = [1, 2, 3, 4, 5];
+        assert_eq!(truncate(&items, 0), (&items[..], 0));
+        assert_eq!(truncate(&items, 2), (&items[..2], 3));
+        assert_eq!(truncate(&items, 9), (&items[..], 0));
+    }
+}
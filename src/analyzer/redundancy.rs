@@ -0,0 +1,119 @@
+//! Detect direct dependencies that duplicate each other's functionality
+//!
+//! Backed by a curated table of equivalent crates in `redundancy.toml`. The
+//! analysis only informs; which crate to keep is the project's call.
+
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+const TABLE: &str = include_str!("redundancy.toml");
+
+/// Crates that cover the same ground
+#[derive(Debug, Clone, Deserialize)]
+pub struct RedundancyGroup {
+    pub name: String,
+    pub crates: Vec<String>,
+    /// Which way projects usually consolidate
+    pub note: String,
+}
+
+#[derive(Deserialize)]
+struct Table {
+    group: Vec<RedundancyGroup>,
+}
+
+/// Two or more members of one group that are all direct dependencies
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Redundancy {
+    pub group: String,
+    /// In the table's order
+    pub crates: Vec<String>,
+    pub note: String,
+}
+
+/// The built-in equivalence table
+pub fn redundancy_groups() -> Result<Vec<RedundancyGroup>> {
+    let table: Table = toml::from_str(TABLE).context("Failed to parse the redundancy table")?;
+    Ok(table.group)
+}
+
+/// Groups with more than one member among `dependencies`
+pub fn find_redundancies<'a>(
+    groups: &[RedundancyGroup],
+    dependencies: impl IntoIterator<Item = &'a str>,
+) -> Vec<Redundancy> {
+    let dependencies: BTreeSet<&str> = dependencies.into_iter().collect();
+    groups
+        .iter()
+        .filter_map(|group| {
+            let present: Vec<String> = group
+                .crates
+                .iter()
+                .filter(|name| dependencies.contains(name.as_str()))
+                .cloned()
+                .collect();
+            (present.len() > 1).then(|| Redundancy {
+                group: group.name.clone(),
+                crates: present,
+                note: group.note.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_is_well_formed() {
+        let groups = redundancy_groups().unwrap();
+        assert!(!groups.is_empty());
+
+        let mut seen = BTreeSet::new();
+        for group in &groups {
+            assert!(group.crates.len() > 1, "{} has one member", group.name);
+            assert!(!group.note.is_empty(), "{} has no note", group.name);
+            for name in &group.crates {
+                assert!(seen.insert(name.as_str()), "{} is in two groups", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_redundancies() {
+        let groups = redundancy_groups().unwrap();
+        let found = find_redundancies(
+            &groups,
+            [
+                "once_cell",
+                "eyre",
+                "serde",
+                "anyhow",
+                "thiserror",
+                "lazy_static",
+                "rustls",
+            ],
+        );
+
+        let summary: Vec<(&str, Vec<&str>)> = found
+            .iter()
+            .map(|r| {
+                (
+                    r.group.as_str(),
+                    r.crates.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("error handling", vec!["anyhow", "eyre"]),
+                ("lazy initialization", vec!["lazy_static", "once_cell"]),
+            ]
+        );
+        assert!(find_redundancies(&groups, ["anyhow", "thiserror"]).is_empty());
+    }
+}
@@ -0,0 +1,355 @@
+//! Estimate what the dependency set costs at build time
+//!
+//! Package counts come from the resolved graph in `cargo metadata`. Seconds
+//! come from a `cargo build --timings=json` log when one is supplied, and
+//! otherwise from a rough built-in table of notoriously slow crates.
+
+use crate::utils::cargo::Metadata;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Rough clean-build cost, in seconds, of crates known to be heavy
+const HEAVY_CRATES: &[(&str, f64)] = &[
+    ("aws-lc-sys", 60.0),
+    ("openssl-sys", 30.0),
+    ("librocksdb-sys", 120.0),
+    ("libsqlite3-sys", 20.0),
+    ("ring", 25.0),
+    ("syn", 8.0),
+    ("tokio", 10.0),
+    ("regex-automata", 8.0),
+    ("serde_derive", 6.0),
+    ("bindgen", 12.0),
+    ("zstd-sys", 15.0),
+    ("winapi", 6.0),
+    ("windows-sys", 4.0),
+    ("rustls", 8.0),
+    ("hyper", 8.0),
+    ("clap_builder", 7.0),
+    ("diesel", 20.0),
+    ("sqlx-macros", 12.0),
+];
+
+/// Assumed cost of a package that isn't in the table
+const DEFAULT_PACKAGE_SECONDS: f64 = 1.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizeReport {
+    pub total_packages: usize,
+    pub build_scripts: Vec<String>,
+    pub proc_macros: Vec<String>,
+    /// Packages in the graph with a known high build cost
+    pub heavy: Vec<PackageCost>,
+    /// Direct dependencies ranked by the subtree only they pull in
+    pub direct: Vec<DirectCost>,
+    /// Whether seconds come from a timings log rather than estimates
+    pub measured: bool,
+    pub estimated_seconds: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageCost {
+    pub name: String,
+    pub version: semver::Version,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectCost {
+    pub name: String,
+    /// Packages only reachable through this dependency, itself included
+    pub unique_packages: Vec<String>,
+    /// Build time removing this dependency would save
+    pub seconds: f64,
+}
+
+/// Per-crate build seconds read from `cargo build --timings=json` output
+#[derive(Debug, Clone, Default)]
+pub struct BuildTimings(HashMap<String, f64>);
+
+impl BuildTimings {
+    /// Parse the JSON lines cargo prints, summing the `timing-info` messages
+    /// per package name. Lines that aren't timing messages are skipped.
+    pub fn parse(text: &str) -> Self {
+        let mut seconds = HashMap::new();
+        for line in text.lines() {
+            let Ok(message) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+            if message["reason"] != "timing-info" {
+                continue;
+            }
+            let (Some(package_id), Some(duration)) =
+                (message["package_id"].as_str(), message["duration"].as_f64())
+            else {
+                continue;
+            };
+            *seconds.entry(package_name(package_id)).or_insert(0.0) += duration;
+        }
+        Self(seconds)
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.0.get(name).copied()
+    }
+}
+
+/// Extract the crate name from a package id in either the legacy
+/// (`name 1.0.0 (source)`) or the newer (`source#name@1.0.0`) format
+fn package_name(package_id: &str) -> String {
+    if let Some((_, spec)) = package_id.rsplit_once('#') {
+        let name = spec.split('@').next().unwrap_or(spec);
+        // `path+file:///x/foo#0.1.0` carries only the version after '#'
+        if name.chars().next().is_some_and(|c| c.is_ascii_digit()) {
+            let url = package_id.rsplit_once('#').map(|(u, _)| u).unwrap_or("");
+            return url.rsplit('/').next().unwrap_or(url).to_string();
+        }
+        return name.to_string();
+    }
+    package_id
+        .split_whitespace()
+        .next()
+        .unwrap_or(package_id)
+        .to_string()
+}
+
+/// Analyze the build graph of package `root`
+pub fn analyze_size(metadata: &Metadata, root: &str, timings: Option<&BuildTimings>) -> SizeReport {
+    let cost = |id: &str| -> f64 {
+        let Some(package) = metadata.package(id) else {
+            return 0.0;
+        };
+        if let Some(seconds) = timings.and_then(|t| t.get(&package.name)) {
+            return seconds;
+        }
+        if timings.is_some() {
+            // Not in the log means it wasn't rebuilt
+            return 0.0;
+        }
+        HEAVY_CRATES
+            .iter()
+            .find(|(name, _)| *name == package.name)
+            .map(|(_, seconds)| *seconds)
+            .unwrap_or(DEFAULT_PACKAGE_SECONDS)
+    };
+
+    let everything = reachable(metadata, root, None);
+    let names = |ids: &BTreeSet<String>| -> Vec<String> {
+        let mut names: Vec<String> = ids
+            .iter()
+            .filter_map(|id| metadata.package(id))
+            .map(|p| p.name.clone())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    };
+
+    let packages: Vec<&str> = everything
+        .iter()
+        .filter(|id| id.as_str() != root)
+        .map(String::as_str)
+        .collect();
+
+    let build_scripts = names(
+        &packages
+            .iter()
+            .filter(|id| metadata.package(id).is_some_and(|p| p.has_build_script()))
+            .map(|id| id.to_string())
+            .collect(),
+    );
+    let proc_macros = names(
+        &packages
+            .iter()
+            .filter(|id| metadata.package(id).is_some_and(|p| p.is_proc_macro()))
+            .map(|id| id.to_string())
+            .collect(),
+    );
+
+    let mut heavy: Vec<PackageCost> = packages
+        .iter()
+        .filter_map(|id| {
+            let package = metadata.package(id)?;
+            let known = HEAVY_CRATES.iter().any(|(name, _)| *name == package.name);
+            let measured_slow = timings.is_some_and(|_| cost(id) >= 5.0);
+            (known || measured_slow).then(|| PackageCost {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                seconds: cost(id),
+            })
+        })
+        .collect();
+    heavy.sort_by(|a, b| b.seconds.total_cmp(&a.seconds).then(a.name.cmp(&b.name)));
+
+    let mut direct: Vec<DirectCost> = direct_dependencies(metadata, root)
+        .into_iter()
+        .filter_map(|id| {
+            let name = metadata.package(&id)?.name.clone();
+            let without = reachable(metadata, root, Some(&id));
+            let unique: BTreeSet<String> = everything.difference(&without).cloned().collect();
+            Some(DirectCost {
+                name,
+                seconds: unique.iter().fold(0.0, |total, id| total + cost(id)),
+                unique_packages: names(&unique),
+            })
+        })
+        .collect();
+    direct.sort_by(|a, b| {
+        b.unique_packages
+            .len()
+            .cmp(&a.unique_packages.len())
+            .then(b.seconds.total_cmp(&a.seconds))
+            .then(a.name.cmp(&b.name))
+    });
+
+    SizeReport {
+        total_packages: packages.len(),
+        estimated_seconds: packages.iter().fold(0.0, |total, id| total + cost(id)),
+        build_scripts,
+        proc_macros,
+        heavy,
+        direct,
+        measured: timings.is_some(),
+    }
+}
+
+/// Non-dev direct dependencies of `root`
+fn direct_dependencies(metadata: &Metadata, root: &str) -> Vec<String> {
+    let mut ids: Vec<String> = metadata
+        .node(root)
+        .map(|node| {
+            node.deps
+                .iter()
+                .filter(|d| !d.is_dev_only())
+                .map(|d| d.pkg.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// Package ids reachable from `root` over non-dev edges, optionally pretending
+/// `removed` isn't a dependency of the root
+fn reachable(metadata: &Metadata, root: &str, removed: Option<&str>) -> BTreeSet<String> {
+    let mut seen = BTreeSet::new();
+    let mut stack = vec![root.to_string()];
+
+    while let Some(id) = stack.pop() {
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        let Some(node) = metadata.node(&id) else {
+            continue;
+        };
+        for dep in node.deps.iter().filter(|d| !d.is_dev_only()) {
+            if id == root && Some(dep.pkg.as_str()) == removed {
+                continue;
+            }
+            stack.push(dep.pkg.clone());
+        }
+    }
+
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// app -> {cli, net}; cli -> {syn}; net -> {syn, ring}; dev: app -> tester
+    fn metadata() -> Metadata {
+        let package = |name: &str, kinds: &[&str]| {
+            serde_json::json!({
+                "id": format!("{} 1.0.0", name),
+                "name": name,
+                "version": "1.0.0",
+                "source": null,
+                "manifest_path": format!("/{}/Cargo.toml", name),
+                "targets": [{"name": name, "kind": kinds}],
+            })
+        };
+        let dep = |name: &str, kind: Option<&str>| {
+            serde_json::json!({
+                "name": name,
+                "pkg": format!("{} 1.0.0", name),
+                "dep_kinds": [{"kind": kind, "target": null}],
+            })
+        };
+        let node = |name: &str, deps: Vec<serde_json::Value>| serde_json::json!({"id": format!("{} 1.0.0", name), "deps": deps, "features": []});
+
+        let json = serde_json::json!({
+            "packages": [
+                package("app", &["bin"]),
+                package("cli", &["lib"]),
+                package("net", &["lib", "custom-build"]),
+                package("syn", &["lib"]),
+                package("ring", &["lib", "custom-build"]),
+                package("derive", &["proc-macro"]),
+                package("tester", &["lib"]),
+            ],
+            "resolve": {
+                "nodes": [
+                    node("app", vec![dep("cli", None), dep("net", None), dep("tester", Some("dev"))]),
+                    node("cli", vec![dep("syn", None), dep("derive", None)]),
+                    node("net", vec![dep("syn", None), dep("ring", None)]),
+                    node("syn", vec![]),
+                    node("ring", vec![]),
+                    node("derive", vec![]),
+                    node("tester", vec![]),
+                ],
+                "root": "app 1.0.0"
+            },
+            "workspace_members": ["app 1.0.0"],
+            "workspace_root": "/app",
+            "version": 1
+        });
+        Metadata::parse(&json.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_unique_subtrees() {
+        let report = analyze_size(&metadata(), "app 1.0.0", None);
+
+        assert_eq!(report.total_packages, 5);
+        assert_eq!(report.build_scripts, vec!["net", "ring"]);
+        assert_eq!(report.proc_macros, vec!["derive"]);
+        assert_eq!(report.heavy[0].name, "ring");
+        assert!(!report.measured);
+
+        // syn is shared, so it is in neither unique subtree; equal package
+        // counts are ranked by seconds
+        assert_eq!(report.direct[0].name, "net");
+        assert_eq!(report.direct[0].unique_packages, vec!["net", "ring"]);
+        assert_eq!(report.direct[0].seconds, 26.0);
+        assert_eq!(report.direct[1].name, "cli");
+        assert_eq!(report.direct[1].unique_packages, vec!["cli", "derive"]);
+        assert_eq!(report.direct.len(), 2);
+    }
+
+    #[test]
+    fn test_measured_timings() {
+        let timings = BuildTimings::parse(
+            r#"{"reason":"timing-info","package_id":"registry+https://github.com/rust-lang/crates.io-index#ring@1.0.0","target":{"name":"ring"},"mode":"build","duration":12.5,"rmeta_time":3.0}
+{"reason":"timing-info","package_id":"ring 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)","target":{"name":"build-script-build"},"mode":"run-custom-build","duration":4.0}
+{"reason":"compiler-artifact","package_id":"net 1.0.0"}
+not json
+"#,
+        );
+        assert_eq!(timings.get("ring"), Some(16.5));
+        assert_eq!(timings.get("net"), None);
+
+        let report = analyze_size(&metadata(), "app 1.0.0", Some(&timings));
+        assert!(report.measured);
+        let net = report.direct.iter().find(|d| d.name == "net").unwrap();
+        assert_eq!(net.seconds, 16.5);
+    }
+
+    #[test]
+    fn test_package_name() {
+        assert_eq!(package_name("serde 1.0.0 (registry+https://x)"), "serde");
+        assert_eq!(package_name("registry+https://x#serde@1.0.0"), "serde");
+        assert_eq!(package_name("path+file:///work/app#0.1.0"), "app");
+    }
+}
@@ -0,0 +1,487 @@
+//! Point-in-time records of dependency state, and what changed between two
+//!
+//! A snapshot bundles the check, health and conflict reports of one run.
+//! Everything inside is sorted on creation so that two snapshots of the same
+//! state serialize identically and diffs only show real changes.
+
+use crate::analyzer::checker::CheckReport;
+use crate::analyzer::conflicts::ConflictReport;
+use crate::analyzer::freshness::BudgetViolation;
+use crate::analyzer::health::HealthReport;
+use crate::analyzer::history::DependencyOrigin;
+use crate::analyzer::plugin::AnalyzerFindings;
+use crate::analyzer::stats::DependencyStats;
+use crate::core::advisory::Severity;
+use crate::utils::owners::Owners;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Snapshot {
+    pub tag: Option<String>,
+    /// Unix timestamp of when the snapshot was taken
+    pub created_at: u64,
+    pub tool_version: String,
+    pub check: CheckReport,
+    /// Missing when the advisory lookup failed
+    pub health: Option<HealthReport>,
+    /// Missing when `cargo tree` could not be run
+    pub conflicts: Option<ConflictReport>,
+    /// Owner logins of each dependency, recorded by `snapshot save --owners`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub owners: Owners,
+}
+
+/// Changes from an older snapshot to a newer one
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SnapshotDiff {
+    pub dependencies_before: usize,
+    pub dependencies_after: usize,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Dependencies with an update now that had none (or didn't exist) before
+    pub newly_outdated: Vec<OutdatedDependency>,
+    /// Dependencies that had an update before and are up to date now
+    #[serde(default)]
+    pub no_longer_outdated: Vec<String>,
+    /// `None` when either snapshot lacks a health report
+    pub new_advisories: Option<Vec<NewAdvisory>>,
+    /// Advisories that applied before and no longer do, with the version
+    /// they applied to; `None` when either snapshot lacks a health report
+    #[serde(default)]
+    pub resolved_advisories: Option<Vec<NewAdvisory>>,
+    /// `None` when either snapshot lacks a conflict report
+    pub resolved_conflicts: Option<Vec<String>>,
+    pub new_conflicts: Option<Vec<String>>,
+    /// Dependencies running build-time code that didn't before; `None`
+    /// when either snapshot lacks the build-time code list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_build_time_code: Option<Vec<String>>,
+}
+
+/// Everything `cargo sane report` found: the current state, and what
+/// changed since `--since`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectReport {
+    pub check: CheckReport,
+    pub health: Option<HealthReport>,
+    pub conflicts: Option<ConflictReport>,
+    pub stats: DependencyStats,
+    pub freshness: Vec<BudgetViolation>,
+    /// `None` without `--since`
+    pub since: Option<SnapshotDiff>,
+    /// The commit that added each declaration; `None` without `--history`
+    /// or when git history isn't available
+    #[serde(default)]
+    pub history: Option<Vec<DependencyOrigin>>,
+    /// What the `--plugin` programs and compiled-in analyzers found
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub analyzers: Vec<AnalyzerFindings>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OutdatedDependency {
+    pub name: String,
+    pub current_version: Version,
+    pub latest_version: Version,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NewAdvisory {
+    pub id: String,
+    pub package: String,
+    pub version: Version,
+    pub title: String,
+    pub severity: Option<Severity>,
+}
+
+impl Snapshot {
+    pub fn new(
+        created_at: u64,
+        check: CheckReport,
+        health: Option<HealthReport>,
+        conflicts: Option<ConflictReport>,
+    ) -> Self {
+        let mut snapshot = Self {
+            tag: None,
+            created_at,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            check,
+            health,
+            conflicts,
+            owners: Owners::new(),
+        };
+        snapshot.normalize();
+        snapshot
+    }
+
+    pub fn with_tag(mut self, tag: Option<String>) -> Self {
+        self.tag = tag;
+        self
+    }
+
+    pub fn with_owners(mut self, mut owners: Owners) -> Self {
+        for logins in owners.values_mut() {
+            logins.sort();
+        }
+        self.owners = owners;
+        self
+    }
+
+    /// Sort every list so equal states serialize to equal bytes
+    fn normalize(&mut self) {
+        let check = &mut self.check;
+        check
+            .dependencies
+            .sort_by(|a, b| (&a.name, a.kind.section()).cmp(&(&b.name, b.kind.section())));
+        check.git_dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+        check
+            .declaration_conflicts
+            .sort_by(|a, b| a.name.cmp(&b.name));
+        check
+            .features
+            .sort_by_key(|f| (f.name.clone(), f.section.to_string()));
+
+        if let Some(health) = &mut self.health {
+            health
+                .vulnerable
+                .sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+            for package in &mut health.vulnerable {
+                package.advisories.sort_by(|a, b| a.id.cmp(&b.id));
+            }
+        }
+
+        if let Some(conflicts) = &mut self.conflicts {
+            conflicts.conflicts.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+    }
+
+    /// What changed from this snapshot to `current`
+    pub fn diff(&self, current: &Snapshot) -> SnapshotDiff {
+        let before: BTreeSet<&str> = dependency_names(self);
+        let after: BTreeSet<&str> = dependency_names(current);
+
+        let outdated_before: BTreeSet<&str> = self
+            .check
+            .dependencies
+            .iter()
+            .filter(|d| d.has_update())
+            .map(|d| d.name.as_str())
+            .collect();
+        let mut newly_outdated: Vec<OutdatedDependency> = current
+            .check
+            .dependencies
+            .iter()
+            .filter(|d| d.has_update() && !outdated_before.contains(d.name.as_str()))
+            .filter_map(|d| {
+                Some(OutdatedDependency {
+                    name: d.name.clone(),
+                    current_version: d.current_version.clone(),
+                    latest_version: d.latest_version.clone()?,
+                })
+            })
+            .collect();
+        newly_outdated.dedup_by(|a, b| a.name == b.name);
+
+        let outdated_after: BTreeSet<&str> = current
+            .check
+            .dependencies
+            .iter()
+            .filter(|d| d.has_update())
+            .map(|d| d.name.as_str())
+            .collect();
+        let no_longer_outdated = outdated_before
+            .iter()
+            .filter(|name| after.contains(*name) && !outdated_after.contains(*name))
+            .map(|name| name.to_string())
+            .collect();
+
+        let (new_advisories, resolved_advisories) = match (&self.health, &current.health) {
+            (Some(before), Some(after)) => (
+                Some(advisories_missing_from(before, after)),
+                Some(advisories_missing_from(after, before)),
+            ),
+            _ => (None, None),
+        };
+
+        let (resolved_conflicts, new_conflicts) = match (&self.conflicts, &current.conflicts) {
+            (Some(before), Some(after)) => {
+                let names = |report: &ConflictReport| -> BTreeSet<String> {
+                    report.conflicts.iter().map(|c| c.name.clone()).collect()
+                };
+                let (before, after) = (names(before), names(after));
+                (
+                    Some(before.difference(&after).cloned().collect()),
+                    Some(after.difference(&before).cloned().collect()),
+                )
+            }
+            _ => (None, None),
+        };
+
+        let build_time = |snapshot: &Snapshot| {
+            snapshot
+                .health
+                .as_ref()
+                .and_then(|health| health.build_time.clone())
+        };
+        let new_build_time_code = match (build_time(self), build_time(current)) {
+            (Some(before), Some(after)) => Some(
+                after
+                    .new_since(&before)
+                    .into_iter()
+                    .map(|p| p.name.clone())
+                    .collect(),
+            ),
+            _ => None,
+        };
+
+        SnapshotDiff {
+            dependencies_before: before.len(),
+            dependencies_after: after.len(),
+            added: after.difference(&before).map(|n| n.to_string()).collect(),
+            removed: before.difference(&after).map(|n| n.to_string()).collect(),
+            newly_outdated,
+            no_longer_outdated,
+            new_advisories,
+            resolved_advisories,
+            resolved_conflicts,
+            new_conflicts,
+            new_build_time_code,
+        }
+    }
+}
+
+impl SnapshotDiff {
+    /// Change in the number of dependencies
+    pub fn dependency_delta(&self) -> i64 {
+        self.dependencies_after as i64 - self.dependencies_before as i64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let empty = |list: &Option<Vec<String>>| list.as_ref().is_none_or(Vec::is_empty);
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.newly_outdated.is_empty()
+            && self.no_longer_outdated.is_empty()
+            && self.new_advisories.as_ref().is_none_or(Vec::is_empty)
+            && self.resolved_advisories.as_ref().is_none_or(Vec::is_empty)
+            && empty(&self.resolved_conflicts)
+            && empty(&self.new_conflicts)
+            && empty(&self.new_build_time_code)
+    }
+}
+
+/// The advisories of `report` that `known` doesn't list for the same package
+fn advisories_missing_from(known: &HealthReport, report: &HealthReport) -> Vec<NewAdvisory> {
+    let known: BTreeSet<(&str, &str)> = known
+        .vulnerable
+        .iter()
+        .flat_map(|p| {
+            p.advisories
+                .iter()
+                .map(|a| (p.name.as_str(), a.id.as_str()))
+        })
+        .collect();
+    report
+        .vulnerable
+        .iter()
+        .flat_map(|p| p.advisories.iter().map(move |a| (p, a)))
+        .filter(|(p, a)| !known.contains(&(p.name.as_str(), a.id.as_str())))
+        .map(|(p, a)| NewAdvisory {
+            id: a.id.clone(),
+            package: p.name.clone(),
+            version: p.version.clone(),
+            title: a.title.clone(),
+            severity: a.severity,
+        })
+        .collect()
+}
+
+fn dependency_names(snapshot: &Snapshot) -> BTreeSet<&str> {
+    snapshot
+        .check
+        .dependencies
+        .iter()
+        .map(|d| d.name.as_str())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::attribution::Attribution;
+    use crate::analyzer::checker::test_support::check_report;
+    use crate::analyzer::conflicts::{Conflict, ConflictVersion};
+    use crate::analyzer::health::AffectedPackage;
+    use crate::core::advisory::Advisory;
+    use crate::core::dependency::test_support::dep;
+    use crate::core::dependency::DependencySource;
+    use std::path::PathBuf;
+
+    fn health(advisories: &[(&str, &str)]) -> HealthReport {
+        HealthReport {
+            package: Some("demo".to_string()),
+            manifest: PathBuf::from("Cargo.toml"),
+            scanned: 3,
+            vulnerable: advisories
+                .iter()
+                .map(|(package, id)| AffectedPackage {
+                    name: package.to_string(),
+                    version: Version::new(1, 0, 0),
+                    source: DependencySource::Registry,
+                    attribution: Attribution::direct(package),
+                    also_via: Vec::new(),
+                    owner: None,
+                    advisories: vec![Advisory {
+                        id: id.to_string(),
+                        package: package.to_string(),
+                        title: "bad".to_string(),
+                        severity: Some(Severity::High),
+                        cvss: None,
+                        aliases: Vec::new(),
+                        patched_versions: Vec::new(),
+                        informational: None,
+                        url: String::new(),
+                    }],
+                })
+                .collect(),
+            database: None,
+            system_libraries: Vec::new(),
+            forks: Vec::new(),
+            enrichment: None,
+            checksums: None,
+            embedded: Vec::new(),
+            build_time: None,
+            partial: false,
+            unchecked: 0,
+            ownership_changes: Vec::new(),
+            accepted: Vec::new(),
+            internal: Vec::new(),
+        }
+    }
+
+    fn conflicts(names: &[&str]) -> ConflictReport {
+        ConflictReport {
+            conflicts: names
+                .iter()
+                .map(|name| Conflict {
+                    name: name.to_string(),
+                    versions: vec![
+                        ConflictVersion {
+                            version: Version::new(1, 0, 0),
+                            dependents: Vec::new(),
+                            advisories: Vec::new(),
+                            sources: Vec::new(),
+                        },
+                        ConflictVersion {
+                            version: Version::new(2, 0, 0),
+                            dependents: Vec::new(),
+                            advisories: Vec::new(),
+                            sources: Vec::new(),
+                        },
+                    ],
+                    security_relevant: false,
+                })
+                .collect(),
+            source_splits: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_snapshots_are_stable_ordered() {
+        let a = Snapshot::new(
+            0,
+            check_report(vec![dep("b", "1.0.0", "1.0.0"), dep("a", "1.0.0", "1.0.0")]),
+            Some(health(&[("y", "RUSTSEC-2"), ("x", "RUSTSEC-1")])),
+            None,
+        );
+        let b = Snapshot::new(
+            0,
+            check_report(vec![dep("a", "1.0.0", "1.0.0"), dep("b", "1.0.0", "1.0.0")]),
+            Some(health(&[("x", "RUSTSEC-1"), ("y", "RUSTSEC-2")])),
+            None,
+        );
+        assert_eq!(
+            serde_json::to_string(&a).unwrap(),
+            serde_json::to_string(&b).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = Snapshot::new(
+            0,
+            check_report(vec![
+                dep("serde", "1.0.0", "1.0.0"),
+                dep("log", "0.4.0", "0.4.20"),
+                dep("old", "1.0.0", "1.0.0"),
+            ]),
+            Some(health(&[("log", "RUSTSEC-1")])),
+            Some(conflicts(&["syn", "bitflags"])),
+        );
+        let after = Snapshot::new(
+            100,
+            check_report(vec![
+                dep("serde", "1.0.0", "1.0.200"),
+                dep("log", "0.4.0", "0.4.21"),
+                dep("new", "0.1.0", "0.2.0"),
+                dep("tokio", "1.0.0", "1.0.0"),
+            ]),
+            Some(health(&[("log", "RUSTSEC-1"), ("new", "RUSTSEC-2")])),
+            Some(conflicts(&["syn"])),
+        );
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.dependencies_before, 3);
+        assert_eq!(diff.dependencies_after, 4);
+        assert_eq!(diff.dependency_delta(), 1);
+        assert_eq!(diff.added, vec!["new", "tokio"]);
+        assert_eq!(diff.removed, vec!["old"]);
+
+        let outdated: Vec<&str> = diff
+            .newly_outdated
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect();
+        assert_eq!(outdated, vec!["new", "serde"]);
+
+        let advisories = diff.new_advisories.as_ref().unwrap();
+        assert_eq!(advisories.len(), 1);
+        assert_eq!(advisories[0].id, "RUSTSEC-2");
+        assert_eq!(diff.resolved_advisories.as_ref().unwrap().len(), 0);
+        assert!(diff.no_longer_outdated.is_empty());
+
+        // Going back: serde is up to date again, and new's advisory left with it
+        let back = after.diff(&before);
+        assert_eq!(back.no_longer_outdated, vec!["serde"]);
+        let resolved = back.resolved_advisories.as_ref().unwrap();
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(
+            (resolved[0].id.as_str(), resolved[0].package.as_str()),
+            ("RUSTSEC-2", "new")
+        );
+
+        assert_eq!(diff.resolved_conflicts, Some(vec!["bitflags".to_string()]));
+        assert_eq!(diff.new_conflicts, Some(Vec::new()));
+        assert!(!diff.is_empty());
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn test_missing_reports_are_not_compared() {
+        let before = Snapshot::new(0, check_report(Vec::new()), None, None);
+        let after = Snapshot::new(
+            1,
+            check_report(Vec::new()),
+            Some(health(&[("x", "RUSTSEC-1")])),
+            Some(conflicts(&["syn"])),
+        );
+        let diff = before.diff(&after);
+        assert!(diff.new_advisories.is_none());
+        assert!(diff.resolved_advisories.is_none());
+        assert!(diff.new_conflicts.is_none());
+        assert!(diff.is_empty());
+    }
+}
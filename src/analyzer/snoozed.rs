@@ -0,0 +1,241 @@
+//! Update suggestions put off for now
+//!
+//! Snoozes live in `.cargo-sane/snoozed.toml`, one `[[snoozed]]` table per
+//! crate:
+//!
+//! ```toml
+//! [[snoozed]]
+//! name = "clap"
+//! version = "4.6.0"
+//! until = "2025-03-01"
+//! snoozed_by = "alice"
+//! ```
+//!
+//! A snoozed crate's update stays out of `check` and `update` while its
+//! target is no newer than `version`. The snooze lapses once a newer
+//! release appears or at the start of its `until` date, whichever is first.
+
+use crate::core::dependency::Dependency;
+use crate::utils::cache::STATE_DIR;
+use crate::utils::formatting::parse_date;
+use anyhow::{Context, Result};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SNOOZED_FILE: &str = "snoozed.toml";
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Every snooze recorded for a project
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snoozes {
+    #[serde(default)]
+    pub snoozed: Vec<Snooze>,
+}
+
+/// One crate's update, put off
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snooze {
+    pub name: String,
+    /// The newest update target the snooze covers
+    pub version: Version,
+    /// Date (YYYY-MM-DD) from which the snooze no longer applies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snoozed_by: Option<String>,
+}
+
+impl Snooze {
+    /// The snooze no longer applies at the Unix time `now`. An unparsable
+    /// date counts as expired, like an acceptance's.
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.until
+            .as_deref()
+            .is_some_and(|date| parse_date(date).is_none_or(|until| now >= until))
+    }
+
+    /// Whether it hides an update of `dep` at the Unix time `now`
+    pub fn covers(&self, dep: &Dependency, now: u64) -> bool {
+        dep.name == self.name
+            && !self.is_expired(now)
+            && dep
+                .latest_version
+                .as_ref()
+                .is_some_and(|target| target <= &self.version)
+    }
+
+    /// One listing line, e.g. "clap up to 4.6.0 (until 2025-03-01, by alice)"
+    pub fn summary(&self) -> String {
+        let mut terms = Vec::new();
+        if let Some(until) = &self.until {
+            terms.push(format!("until {}", until));
+        }
+        if let Some(by) = &self.snoozed_by {
+            terms.push(format!("by {}", by));
+        }
+        let terms = if terms.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", terms.join(", "))
+        };
+        format!("{} up to {}{}", self.name, self.version, terms)
+    }
+}
+
+impl Snoozes {
+    /// The snoozes of the project in `root`; none when there's no file
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = Self::path(root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content =
+            fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).context(format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, root: &Path) -> Result<PathBuf> {
+        let path = Self::path(root);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&path, toml::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn path(root: &Path) -> PathBuf {
+        root.join(STATE_DIR).join(SNOOZED_FILE)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snoozed.is_empty()
+    }
+
+    /// Record `snooze`, replacing an earlier one of the same crate
+    pub fn snooze(&mut self, snooze: Snooze) {
+        self.snoozed.retain(|s| s.name != snooze.name);
+        self.snoozed.push(snooze);
+    }
+
+    /// Drop the snooze of `name`; false when there was none
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.snoozed.len();
+        self.snoozed.retain(|s| s.name != name);
+        self.snoozed.len() != before
+    }
+
+    /// Mark the dependencies whose update a snooze hides at the Unix time
+    /// `now`, returning how many
+    pub fn apply(&self, dependencies: &mut [Dependency], now: u64) -> usize {
+        let mut count = 0;
+        for dep in dependencies.iter_mut() {
+            dep.snoozed =
+                dep.has_update() && self.snoozed.iter().any(|snooze| snooze.covers(dep, now));
+            count += usize::from(dep.snoozed);
+        }
+        count
+    }
+}
+
+/// Parse a snooze length like "30d" or "2w" into seconds
+pub fn parse_length(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let (count, unit) = text.split_at(text.len().checked_sub(1)?);
+    let days = match unit {
+        "d" => 1,
+        "w" => 7,
+        _ => return None,
+    };
+    let count: u64 = count.parse().ok().filter(|&count| count > 0)?;
+    count.checked_mul(days * SECONDS_PER_DAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000; // 2023-11-14
+
+    fn snooze(version: &str, until: Option<&str>) -> Snooze {
+        Snooze {
+            name: "clap".to_string(),
+            version: Version::parse(version).unwrap(),
+            until: until.map(str::to_string),
+            snoozed_by: None,
+        }
+    }
+
+    fn clap(current: &str, latest: &str) -> Dependency {
+        Dependency::new("clap".to_string(), Version::parse(current).unwrap(), true)
+            .with_latest(Version::parse(latest).unwrap())
+    }
+
+    #[test]
+    fn test_snooze_lapses_on_a_newer_release_or_its_date() {
+        let dep = clap("4.5.0", "4.6.0");
+        assert!(snooze("4.6.0", None).covers(&dep, NOW));
+        assert!(snooze("4.6.0", Some("2023-11-15")).covers(&dep, NOW));
+        assert!(!snooze("4.6.0", Some("2023-11-14")).covers(&dep, NOW));
+        assert!(!snooze("4.6.0", Some("soon")).covers(&dep, NOW));
+
+        assert!(!snooze("4.6.0", None).covers(&clap("4.5.0", "4.6.1"), NOW));
+        assert!(snooze("4.7.0", None).covers(&clap("4.5.0", "4.6.1"), NOW));
+
+        let mut other = dep.clone();
+        other.name = "serde".to_string();
+        assert!(!snooze("4.6.0", None).covers(&other, NOW));
+    }
+
+    #[test]
+    fn test_apply_marks_snoozed_updates() {
+        let mut snoozes = Snoozes::default();
+        snoozes.snooze(snooze("4.6.0", Some("2023-01-01")));
+        snoozes.snooze(snooze("4.6.0", None));
+        assert_eq!(snoozes.snoozed.len(), 1);
+
+        let mut dependencies = vec![
+            clap("4.5.0", "4.6.0"),
+            Dependency::new("serde".to_string(), Version::new(1, 0, 0), true)
+                .with_latest(Version::new(1, 0, 1)),
+        ];
+        assert_eq!(snoozes.apply(&mut dependencies, NOW), 1);
+        assert!(dependencies[0].snoozed);
+        assert!(!dependencies[1].snoozed);
+
+        // Up to date after all: nothing left to hide
+        dependencies[0].latest_version = Some(Version::new(4, 5, 0));
+        assert_eq!(snoozes.apply(&mut dependencies, NOW), 0);
+        assert!(!dependencies[0].snoozed);
+    }
+
+    #[test]
+    fn test_parse_length() {
+        assert_eq!(parse_length("30d"), Some(30 * SECONDS_PER_DAY));
+        assert_eq!(parse_length("2w"), Some(14 * SECONDS_PER_DAY));
+        assert_eq!(parse_length("0d"), None);
+        assert_eq!(parse_length("30"), None);
+        assert_eq!(parse_length("d"), None);
+        assert_eq!(parse_length(""), None);
+        assert_eq!(parse_length("1m"), None);
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut snoozes = Snoozes::default();
+        snoozes.snooze(snooze("4.6.0", Some("2025-01-01")));
+        snoozes.save(dir.path()).unwrap();
+
+        let loaded = Snoozes::load(dir.path()).unwrap();
+        assert_eq!(loaded, snoozes);
+        assert_eq!(
+            loaded.snoozed[0].summary(),
+            "clap up to 4.6.0 (until 2025-01-01)"
+        );
+        assert!(loaded.clone().remove("clap"));
+        assert!(!Snoozes::default().remove("clap"));
+    }
+}
@@ -0,0 +1,217 @@
+//! Aggregate statistics over a checked dependency set
+//!
+//! Where `check` lists crates one by one, these numbers answer "how old is
+//! our dependency set overall?": the spread of release ages of the versions
+//! in use, how many crates are far behind, and optionally how many distinct
+//! maintainer groups the project relies on.
+
+use crate::core::dependency::Dependency;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Upper bounds of the age buckets, in days
+const THREE_MONTHS: u64 = 91;
+const ONE_YEAR: u64 = 365;
+const TWO_YEARS: u64 = 730;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DependencyStats {
+    pub total: usize,
+    /// Dependencies whose current version has a known release date; the
+    /// ages below cover only these
+    pub dated: usize,
+    pub median_age_days: Option<u64>,
+    pub mean_age_days: Option<u64>,
+    pub histogram: AgeHistogram,
+    /// Crates more than one breaking release behind the latest
+    pub majors_behind: usize,
+    /// Groups of crates sharing at least one owner, when owners were fetched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintainer_groups: Option<usize>,
+}
+
+/// Dependencies by the age of the version in use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct AgeHistogram {
+    pub under_3_months: usize,
+    pub months_3_to_12: usize,
+    pub years_1_to_2: usize,
+    pub over_2_years: usize,
+}
+
+impl AgeHistogram {
+    /// Buckets with their labels, youngest first
+    pub fn buckets(&self) -> [(&'static str, usize); 4] {
+        [
+            ("< 3 months", self.under_3_months),
+            ("3–12 months", self.months_3_to_12),
+            ("1–2 years", self.years_1_to_2),
+            ("> 2 years", self.over_2_years),
+        ]
+    }
+
+    fn add(&mut self, age_days: u64) {
+        match age_days {
+            age if age < THREE_MONTHS => self.under_3_months += 1,
+            age if age < ONE_YEAR => self.months_3_to_12 += 1,
+            age if age < TWO_YEARS => self.years_1_to_2 += 1,
+            _ => self.over_2_years += 1,
+        }
+    }
+}
+
+/// Summarize `dependencies` as of the Unix time `now`
+pub fn dependency_stats(dependencies: &[Dependency], now: u64) -> DependencyStats {
+    let mut ages: Vec<u64> = dependencies
+        .iter()
+        .filter_map(|dep| dep.released_at)
+        .map(|released| now.saturating_sub(released) / SECONDS_PER_DAY)
+        .collect();
+    ages.sort_unstable();
+
+    let mut histogram = AgeHistogram::default();
+    for &age in &ages {
+        histogram.add(age);
+    }
+
+    DependencyStats {
+        total: dependencies.len(),
+        dated: ages.len(),
+        median_age_days: median(&ages),
+        mean_age_days: (!ages.is_empty()).then(|| ages.iter().sum::<u64>() / ages.len() as u64),
+        histogram,
+        majors_behind: dependencies
+            .iter()
+            .filter(|dep| {
+                dep.latest_version
+                    .as_ref()
+                    .is_some_and(|latest| breaking_releases(&dep.current_version, latest) > 1)
+            })
+            .count(),
+        maintainer_groups: None,
+    }
+}
+
+/// Number of groups of crates linked by shared owners: crates owned by the
+/// same person or team, directly or through a chain, form one group
+pub fn maintainer_groups(owners: &[Vec<String>]) -> usize {
+    let mut parent: Vec<usize> = (0..owners.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    let mut first_crate: HashMap<&str, usize> = HashMap::new();
+    for (index, logins) in owners.iter().enumerate() {
+        for login in logins {
+            match first_crate.get(login.as_str()) {
+                Some(&other) => {
+                    let (a, b) = (root(&mut parent, index), root(&mut parent, other));
+                    parent[a] = b;
+                }
+                None => {
+                    first_crate.insert(login, index);
+                }
+            }
+        }
+    }
+    (0..owners.len())
+        .filter(|&i| root(&mut parent, i) == i)
+        .count()
+}
+
+/// Semver-breaking releases between two versions: majors, or minors below 1.0
+pub(crate) fn breaking_releases(current: &Version, latest: &Version) -> u64 {
+    if latest.major > current.major {
+        latest.major - current.major
+    } else if latest.major == 0 && current.major == 0 && latest.minor > current.minor {
+        latest.minor - current.minor
+    } else {
+        0
+    }
+}
+
+fn median(sorted: &[u64]) -> Option<u64> {
+    match sorted.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(sorted[n / 2]),
+        n => Some((sorted[n / 2 - 1] + sorted[n / 2]) / 2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn dep(current: &str, latest: &str, age_days: Option<u64>) -> Dependency {
+        let mut dep = Dependency::new("demo".to_string(), Version::parse(current).unwrap(), true)
+            .with_latest(Version::parse(latest).unwrap());
+        dep.released_at = age_days.map(|days| NOW - days * SECONDS_PER_DAY);
+        dep
+    }
+
+    #[test]
+    fn test_ages_and_histogram() {
+        let deps = vec![
+            dep("1.0.0", "1.0.0", Some(10)),
+            dep("1.0.0", "1.2.0", Some(100)),
+            dep("1.0.0", "3.0.0", Some(400)),
+            dep("0.1.0", "0.4.0", Some(1000)),
+            dep("0.1.0", "0.2.0", Some(1200)),
+            dep("1.0.0", "2.0.0", None),
+        ];
+        let stats = dependency_stats(&deps, NOW);
+
+        assert_eq!(stats.total, 6);
+        assert_eq!(stats.dated, 5);
+        assert_eq!(stats.median_age_days, Some(400));
+        assert_eq!(stats.mean_age_days, Some(542));
+        assert_eq!(
+            stats.histogram,
+            AgeHistogram {
+                under_3_months: 1,
+                months_3_to_12: 1,
+                years_1_to_2: 1,
+                over_2_years: 2,
+            }
+        );
+        assert_eq!(stats.majors_behind, 2);
+    }
+
+    #[test]
+    fn test_empty_set() {
+        let stats = dependency_stats(&[], NOW);
+        assert_eq!(stats.median_age_days, None);
+        assert_eq!(stats.mean_age_days, None);
+        assert_eq!(stats.histogram, AgeHistogram::default());
+    }
+
+    #[test]
+    fn test_median_of_even_count() {
+        assert_eq!(median(&[10, 20, 30, 50]), Some(25));
+    }
+
+    #[test]
+    fn test_maintainer_groups() {
+        let owners = |logins: &[&str]| logins.iter().map(|l| l.to_string()).collect();
+        let groups = vec![
+            owners(&["dtolnay"]),
+            owners(&["dtolnay", "github:serde-rs:publish"]),
+            owners(&["github:serde-rs:publish"]),
+            owners(&["carllerche", "github:tokio-rs:core"]),
+            owners(&["github:tokio-rs:core"]),
+            owners(&["burntsushi"]),
+            owners(&[]),
+        ];
+        assert_eq!(maintainer_groups(&groups), 4);
+    }
+}
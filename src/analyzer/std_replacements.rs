@@ -0,0 +1,178 @@
+//! Direct dependencies the standard library can replace
+//!
+//! Backed by a curated table in `std_replacements.toml`. A crate is only
+//! suggested for removal when the package's `rust-version` is at least the
+//! release that made it unnecessary; without a `rust-version` there is no
+//! floor to hold it back. The analysis only informs, nothing is removed.
+
+use crate::core::manifest::Manifest;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+const TABLE: &str = include_str!("std_replacements.toml");
+
+/// A crate and the std API that took over its job
+#[derive(Debug, Clone, Deserialize)]
+pub struct StdEquivalent {
+    #[serde(rename = "crate")]
+    pub name: String,
+    /// The API to migrate to
+    pub std: String,
+    /// The Rust release that covers the crate's common uses, e.g. "1.80"
+    pub since: String,
+    /// What doesn't carry over, or how the migration goes
+    pub note: String,
+}
+
+#[derive(Deserialize)]
+struct Table {
+    replacement: Vec<StdEquivalent>,
+}
+
+/// A direct dependency the package's Rust version can do without
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StdReplacement {
+    /// The dependency name, as declared
+    pub name: String,
+    pub std: String,
+    pub since: String,
+    pub note: String,
+}
+
+impl StdEquivalent {
+    /// `since` as a version
+    fn since(&self) -> Option<Version> {
+        Version::parse(&format!("{}.0", self.since)).ok()
+    }
+}
+
+/// The built-in replacement table
+pub fn std_equivalents() -> Result<Vec<StdEquivalent>> {
+    let table: Table =
+        toml::from_str(TABLE).context("Failed to parse the std replacement table")?;
+    Ok(table.replacement)
+}
+
+/// Direct dependencies of `manifest` that std replaces at its
+/// `rust-version`, in declaration order. Renamed dependencies are matched
+/// by their package name.
+pub fn find_std_replacements(
+    equivalents: &[StdEquivalent],
+    manifest: &Manifest,
+) -> Vec<StdReplacement> {
+    let msrv = manifest.rust_version();
+    let mut found: Vec<StdReplacement> = Vec::new();
+    for (_, name, spec) in manifest.declarations() {
+        if spec.is_path() || spec.is_git() || found.iter().any(|r| r.name == name) {
+            continue;
+        }
+        let package = spec.package().unwrap_or(&name);
+        let Some(equivalent) = equivalents.iter().find(|e| e.name == package) else {
+            continue;
+        };
+        let allowed = match (&msrv, equivalent.since()) {
+            (Some(msrv), Some(since)) => msrv >= &since,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+        if allowed {
+            found.push(StdReplacement {
+                name: name.clone(),
+                std: equivalent.std.clone(),
+                since: equivalent.since.clone(),
+                note: equivalent.note.clone(),
+            });
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+
+    fn manifest(rust_version: Option<&str>, dependencies: &str) -> Manifest {
+        let rust_version = rust_version
+            .map(|v| format!("rust-version = \"{}\"\n", v))
+            .unwrap_or_default();
+        Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            &format!(
+                "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n{}\n[dependencies]\n{}",
+                rust_version, dependencies
+            ),
+        )
+        .unwrap()
+    }
+
+    fn names(equivalents: &[StdEquivalent], manifest: &Manifest) -> Vec<String> {
+        find_std_replacements(equivalents, manifest)
+            .into_iter()
+            .map(|r| r.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_table_is_well_formed() {
+        let equivalents = std_equivalents().unwrap();
+        assert!(!equivalents.is_empty());
+
+        let mut seen = BTreeSet::new();
+        for equivalent in &equivalents {
+            assert!(seen.insert(&equivalent.name), "{} twice", equivalent.name);
+            assert!(equivalent.since().is_some(), "{}", equivalent.since);
+            assert!(!equivalent.std.is_empty(), "{}", equivalent.name);
+            assert!(!equivalent.note.is_empty(), "{}", equivalent.name);
+        }
+    }
+
+    #[test]
+    fn test_rust_version_gates_suggestions() {
+        let equivalents = std_equivalents().unwrap();
+        let dependencies = "once_cell = \"1\"\natty = \"0.2\"\nnum_cpus = \"1\"\nserde = \"1\"\n";
+
+        assert_eq!(
+            names(&equivalents, &manifest(Some("1.58"), dependencies)),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            names(&equivalents, &manifest(Some("1.70"), dependencies)),
+            ["atty", "num_cpus"]
+        );
+        assert_eq!(
+            names(&equivalents, &manifest(Some("1.79.9"), dependencies)),
+            ["atty", "num_cpus"]
+        );
+        assert_eq!(
+            names(&equivalents, &manifest(Some("1.80"), dependencies)),
+            ["atty", "num_cpus", "once_cell"]
+        );
+        // No rust-version, no floor
+        assert_eq!(
+            names(&equivalents, &manifest(None, dependencies)),
+            ["atty", "num_cpus", "once_cell"]
+        );
+    }
+
+    #[test]
+    fn test_only_registry_dependencies_by_package_name() {
+        let equivalents = std_equivalents().unwrap();
+        let manifest = manifest(
+            Some("1.80"),
+            "lazy = { version = \"1\", package = \"lazy_static\" }\n\
+             atty = { path = \"vendor/atty\" }\n\
+             [dev-dependencies]\n\
+             lazy = { version = \"1\", package = \"lazy_static\" }\n",
+        );
+
+        let found = find_std_replacements(&equivalents, &manifest);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "lazy");
+        assert_eq!(found[0].std, "std::sync::LazyLock");
+        assert_eq!(found[0].since, "1.80");
+    }
+}
@@ -0,0 +1,259 @@
+//! Find the system libraries a project links against
+//!
+//! Crates such as openssl-sys link a library the operating system provides.
+//! Its vulnerabilities never show up in crate advisories, so health lists
+//! these libraries separately, and can compare the installed versions with a
+//! small table of minimum safe releases in `system_libs.toml`.
+
+use crate::utils::cargo::{Metadata, MetadataPackage};
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+const TABLE: &str = include_str!("system_libs.toml");
+
+/// What is known about one system library
+#[derive(Debug, Clone, Deserialize)]
+pub struct KnownLibrary {
+    /// The `links` value of the crates linking it
+    pub links: String,
+    pub name: String,
+    pub pkg_config: String,
+    pub min_safe: String,
+    #[serde(default)]
+    pub known_bad: Vec<String>,
+    /// Features of the -sys crate that compile the library in
+    #[serde(default)]
+    pub bundled_features: Vec<String>,
+    pub note: String,
+}
+
+#[derive(Deserialize)]
+struct Table {
+    library: Vec<KnownLibrary>,
+}
+
+/// A native library linked through a -sys crate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SystemLibrary {
+    /// Display name, e.g. "OpenSSL"; the crate name for unknown libraries
+    pub name: String,
+    pub links: Option<String>,
+    /// The crate doing the linking
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub version: Version,
+    /// Compiled in through a feature such as `vendored`, so the system copy
+    /// isn't used
+    pub bundled: bool,
+    /// Version reported by `pkg-config`, when it was asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_safe: Option<String>,
+    /// The installed version is below `min_safe` or a known bad release
+    #[serde(default)]
+    pub unsafe_install: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// The built-in table of well-known system libraries
+pub fn known_libraries() -> Result<Vec<KnownLibrary>> {
+    let table: Table = toml::from_str(TABLE).context("Failed to parse the system library table")?;
+    Ok(table.library)
+}
+
+/// Packages in the resolved graph that link a native library: those with a
+/// `links` key the table knows, and -sys crates with a build script (which
+/// leaves out pure bindings such as windows-sys and js-sys). `probe` looks up
+/// the installed version of a pkg-config module, if at all.
+pub fn system_libraries(
+    metadata: &Metadata,
+    known: &[KnownLibrary],
+    probe: impl Fn(&str) -> Option<String>,
+) -> Vec<SystemLibrary> {
+    let mut found: Vec<SystemLibrary> = metadata
+        .packages
+        .iter()
+        .filter_map(|package| {
+            let library = package
+                .links
+                .as_deref()
+                .and_then(|links| known.iter().find(|k| k.links == links));
+            if library.is_none() && !is_native_sys_crate(package) {
+                return None;
+            }
+            Some(describe(metadata, package, library, &probe))
+        })
+        .collect();
+    found.sort_by(|a, b| (&a.name, &a.krate, &a.version).cmp(&(&b.name, &b.krate, &b.version)));
+    found.dedup();
+    found
+}
+
+/// The installed version of a pkg-config module, if pkg-config is available
+/// and knows it
+pub fn pkg_config_version(module: &str) -> Option<String> {
+    let output = Command::new("pkg-config")
+        .args(["--modversion", module])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!version.is_empty()).then_some(version)
+}
+
+fn is_native_sys_crate(package: &MetadataPackage) -> bool {
+    package.name.ends_with("-sys") && package.has_build_script()
+}
+
+fn describe(
+    metadata: &Metadata,
+    package: &MetadataPackage,
+    library: Option<&KnownLibrary>,
+    probe: &impl Fn(&str) -> Option<String>,
+) -> SystemLibrary {
+    let features = metadata
+        .node(&package.id)
+        .map(|node| node.features.as_slice())
+        .unwrap_or_default();
+    let bundled = library.is_some_and(|l| l.bundled_features.iter().any(|f| features.contains(f)));
+    let installed = match library {
+        Some(library) if !bundled => probe(&library.pkg_config),
+        _ => None,
+    };
+    let unsafe_install = match (library, &installed) {
+        (Some(library), Some(installed)) => {
+            library.known_bad.contains(installed)
+                || version_parts(installed) < version_parts(&library.min_safe)
+        }
+        _ => false,
+    };
+
+    SystemLibrary {
+        name: library.map_or_else(|| package.name.clone(), |l| l.name.clone()),
+        links: package.links.clone(),
+        krate: package.name.clone(),
+        version: package.version.clone(),
+        bundled,
+        installed,
+        min_safe: library.map(|l| l.min_safe.clone()),
+        unsafe_install,
+        note: library.map(|l| l.note.clone()),
+    }
+}
+
+/// Numeric components of a library version, e.g. "1.1.1w" -> [1, 1, 1]
+fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .split('.')
+        .map_while(|part| {
+            let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> Metadata {
+        let package = |name: &str, version: &str, links: Option<&str>, build: bool| {
+            let kinds: Vec<&str> = if build {
+                vec!["lib", "custom-build"]
+            } else {
+                vec!["lib"]
+            };
+            serde_json::json!({
+                "id": format!("{} {}", name, version),
+                "name": name,
+                "version": version,
+                "source": "registry+https://github.com/rust-lang/crates.io-index",
+                "manifest_path": format!("/{}/Cargo.toml", name),
+                "targets": [{"name": name, "kind": kinds}],
+                "links": links,
+            })
+        };
+        let node = |id: &str, features: &[&str]| serde_json::json!({"id": id, "deps": [], "features": features});
+
+        let json = serde_json::json!({
+            "packages": [
+                package("openssl-sys", "0.9.102", Some("openssl"), true),
+                package("libsqlite3-sys", "0.28.0", Some("sqlite3"), true),
+                package("libz-sys", "1.1.18", Some("z"), true),
+                package("foo-sys", "0.1.0", None, true),
+                package("windows-sys", "0.52.0", None, false),
+                package("ring", "0.17.8", Some("ring_core_0_17_8_"), true),
+            ],
+            "resolve": {
+                "nodes": [
+                    node("openssl-sys 0.9.102", &[]),
+                    node("libsqlite3-sys 0.28.0", &["bundled"]),
+                    node("libz-sys 1.1.18", &[]),
+                ],
+                "root": null
+            },
+            "workspace_members": [],
+            "workspace_root": "/app",
+            "version": 1
+        });
+        Metadata::parse(&json.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_table_is_well_formed() {
+        for library in known_libraries().unwrap() {
+            assert!(
+                !version_parts(&library.min_safe).is_empty(),
+                "{}",
+                library.name
+            );
+            assert!(!library.note.is_empty(), "{}", library.name);
+        }
+    }
+
+    #[test]
+    fn test_finds_system_linked_crates() {
+        let probe = |module: &str| match module {
+            "openssl" => Some("1.1.1w".to_string()),
+            "zlib" => Some("1.3.1".to_string()),
+            _ => None,
+        };
+        let found = system_libraries(&metadata(), &known_libraries().unwrap(), probe);
+        let summary: Vec<(&str, &str, bool, Option<&str>, bool)> = found
+            .iter()
+            .map(|l| {
+                (
+                    l.name.as_str(),
+                    l.krate.as_str(),
+                    l.bundled,
+                    l.installed.as_deref(),
+                    l.unsafe_install,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("OpenSSL", "openssl-sys", false, Some("1.1.1w"), true),
+                ("SQLite", "libsqlite3-sys", true, None, false),
+                ("foo-sys", "foo-sys", false, None, false),
+                ("zlib", "libz-sys", false, Some("1.3.1"), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_version_parts() {
+        assert_eq!(version_parts("3.0.13"), vec![3, 0, 13]);
+        assert_eq!(version_parts("1.1.1w"), vec![1, 1, 1]);
+        assert!(version_parts("1.1.1w") < version_parts("3.0.0"));
+        assert!(version_parts("8.4.0") >= version_parts("8.4"));
+    }
+}
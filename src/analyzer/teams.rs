@@ -0,0 +1,184 @@
+//! Which team owns each direct dependency
+//!
+//! `.cargo-sane/owners.toml` maps crates to the team answering for them,
+//! like a CODEOWNERS file for dependencies:
+//!
+//! ```toml
+//! serde = "platform"
+//! tokio = "runtime"
+//! ```
+//!
+//! With the file in place, findings carry the `owner` of the direct
+//! dependency they concern, and check, health and report group them by
+//! team. A transitive package belongs to the team owning the direct
+//! dependency that brings it in. Crates the file doesn't map are
+//! "unassigned".
+
+use crate::analyzer::health::HealthReport;
+use crate::core::dependency::Dependency;
+use crate::utils::cache::STATE_DIR;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const OWNERS_FILE: &str = "owners.toml";
+
+/// The group of findings no team owns
+pub const UNASSIGNED: &str = "unassigned";
+
+/// Owning team by crate name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Teams {
+    pub crates: BTreeMap<String, String>,
+}
+
+impl Teams {
+    /// The team mapping of the project in `root`; `None` when there's no file
+    pub fn load(root: &Path) -> Result<Option<Self>> {
+        let path = Self::path(root);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content =
+            fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+        let crates: BTreeMap<String, String> =
+            toml::from_str(&content).context(format!("Failed to parse {}", path.display()))?;
+        if let Some((name, _)) = crates.iter().find(|(_, team)| team.trim().is_empty()) {
+            anyhow::bail!("{}: {} has an empty team name", path.display(), name);
+        }
+        Ok(Some(Self { crates }))
+    }
+
+    pub fn path(root: &Path) -> PathBuf {
+        root.join(STATE_DIR).join(OWNERS_FILE)
+    }
+
+    pub fn team_of(&self, name: &str) -> Option<&str> {
+        self.crates.get(name).map(String::as_str)
+    }
+
+    /// Set the owner of each dependency the file maps
+    pub fn apply(&self, dependencies: &mut [Dependency]) {
+        for dep in dependencies {
+            dep.owner = self.team_of(&dep.name).map(str::to_string);
+        }
+    }
+
+    /// Set the owner of each affected package: the team of the direct
+    /// dependency bringing it in
+    pub fn apply_health(&self, report: &mut HealthReport) {
+        for package in report.vulnerable.iter_mut() {
+            package.owner = self
+                .team_of(&package.attribution.direct_parent)
+                .map(str::to_string);
+        }
+    }
+
+    /// Crates the file maps that aren't among `names`, most likely removed
+    /// or misspelled
+    pub fn unknown<'a>(&'a self, names: &[&str]) -> Vec<&'a str> {
+        self.crates
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !names.contains(name))
+            .collect()
+    }
+}
+
+/// `items` grouped by their owner, teams in name order and the unassigned
+/// last, keeping the order of `items` within each group
+pub fn group_by_team<'a, T>(
+    items: &[&'a T],
+    owner: impl Fn(&T) -> Option<&str>,
+) -> Vec<(String, Vec<&'a T>)> {
+    let mut teams: BTreeMap<&str, Vec<&'a T>> = BTreeMap::new();
+    let mut unassigned = Vec::new();
+    for &item in items {
+        match owner(item) {
+            Some(team) => teams.entry(team).or_default().push(item),
+            None => unassigned.push(item),
+        }
+    }
+    let mut groups: Vec<(String, Vec<&'a T>)> = teams
+        .into_iter()
+        .map(|(team, items)| (team.to_string(), items))
+        .collect();
+    if !unassigned.is_empty() {
+        groups.push((UNASSIGNED.to_string(), unassigned));
+    }
+    groups
+}
+
+/// How many of the distinct `dependencies` no team owns
+pub fn unassigned_count(dependencies: &[Dependency]) -> usize {
+    dependencies
+        .iter()
+        .filter(|d| d.owner.is_none())
+        .map(|d| d.name.as_str())
+        .collect::<BTreeSet<&str>>()
+        .len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semver::Version;
+
+    fn dep(name: &str) -> Dependency {
+        Dependency::new(name.to_string(), Version::new(1, 0, 0), true)
+    }
+
+    #[test]
+    fn test_load_and_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Teams::load(dir.path()).unwrap().is_none());
+
+        fs::create_dir(dir.path().join(STATE_DIR)).unwrap();
+        fs::write(
+            Teams::path(dir.path()),
+            "serde = \"platform\"\ntokio = \"runtime\"\nrand = \"platform\"\n",
+        )
+        .unwrap();
+        let teams = Teams::load(dir.path()).unwrap().unwrap();
+
+        let mut deps = vec![dep("tokio"), dep("serde"), dep("anyhow")];
+        teams.apply(&mut deps);
+        assert_eq!(deps[0].owner.as_deref(), Some("runtime"));
+        assert_eq!(deps[2].owner, None);
+        assert_eq!(unassigned_count(&deps), 1);
+        assert_eq!(teams.unknown(&["tokio", "serde", "anyhow"]), vec!["rand"]);
+
+        fs::write(Teams::path(dir.path()), "serde = \" \"\n").unwrap();
+        let error = Teams::load(dir.path()).unwrap_err().to_string();
+        assert!(error.ends_with("serde has an empty team name"), "{}", error);
+    }
+
+    #[test]
+    fn test_group_by_team() {
+        let mut deps = [dep("tokio"), dep("anyhow"), dep("serde"), dep("rand")];
+        deps[0].owner = Some("runtime".to_string());
+        deps[2].owner = Some("platform".to_string());
+        deps[3].owner = Some("platform".to_string());
+        let refs: Vec<&Dependency> = deps.iter().collect();
+
+        let groups = group_by_team(&refs, |d| d.owner.as_deref());
+        let summary: Vec<(&str, Vec<&str>)> = groups
+            .iter()
+            .map(|(team, deps)| {
+                (
+                    team.as_str(),
+                    deps.iter().map(|d| d.name.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("platform", vec!["serde", "rand"]),
+                ("runtime", vec!["tokio"]),
+                ("unassigned", vec!["anyhow"]),
+            ]
+        );
+    }
+}
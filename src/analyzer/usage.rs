@@ -0,0 +1,374 @@
+//! Find dependencies that are never referenced from source code
+
+use crate::analyzer::cfg_usage::{scan_cfgs, CfgScan, ConditionalUsage};
+use crate::analyzer::std_replacements::StdReplacement;
+use crate::analyzer::usage_cache::UsageCache;
+use crate::core::manifest::{DependencySection, Manifest};
+use anyhow::Result;
+use rayon::prelude::*;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// A declared dependency with no reference in any scanned source file
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UnusedDependency {
+    pub name: String,
+    pub section: DependencySection,
+    pub line: Option<usize>,
+}
+
+/// Everything `cargo sane clean` found in a single package
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CleanReport {
+    pub unused: Vec<UnusedDependency>,
+    /// Used dependencies the standard library could replace at the
+    /// package's rust-version; advice only, `clean` never removes them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub std_replacements: Vec<StdReplacement>,
+    /// Used dependencies the default build leaves out, every reference
+    /// being behind a `#[cfg]`; reported, never removed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditionally_used: Vec<ConditionalUsage>,
+}
+
+/// What one source file references, and what of it only under `#[cfg]`s
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileUsage {
+    pub idents: BTreeSet<String>,
+    #[serde(default)]
+    pub cfg: CfgScan,
+}
+
+impl FileUsage {
+    pub fn scan(source: &str) -> Self {
+        Self {
+            idents: extract_crate_idents(source),
+            cfg: scan_cfgs(source),
+        }
+    }
+}
+
+/// Unused declarations across a workspace, aggregated per crate
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkspaceUsage {
+    /// Every declaration of every member, and whether that member's code
+    /// references it
+    pub matrix: BTreeMap<String, Vec<DeclarationUsage>>,
+    /// Crates with at least one unused declaration
+    pub crates: Vec<CrateUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeclarationUsage {
+    pub name: String,
+    pub section: DependencySection,
+    pub used: bool,
+}
+
+/// One crate across all members that declare it
+#[derive(Debug, Clone, Serialize)]
+pub struct CrateUsage {
+    pub name: String,
+    pub declared_in: Vec<String>,
+    pub used_in: Vec<String>,
+    /// The declarations to remove
+    pub remove_from: Vec<MemberDeclaration>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberDeclaration {
+    pub member: String,
+    pub manifest: PathBuf,
+    pub section: DependencySection,
+    pub line: Option<usize>,
+}
+
+impl CrateUsage {
+    /// Members keeping the crate because their code uses it
+    pub fn keep_in(&self) -> &[String] {
+        &self.used_in
+    }
+}
+
+/// Identifiers used as the root of a path in Rust source, e.g. `serde` in
+/// `use serde::Deserialize`, `tokio` in `#[tokio::main]`, or `log` in
+/// `extern crate log`. Over-matching (comments, local modules) is fine here:
+/// it can only make a dependency look used, never unused.
+pub fn extract_crate_idents(source: &str) -> BTreeSet<String> {
+    static PATTERNS: OnceLock<[Regex; 2]> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        [
+            // foo::bar and ::foo::bar, but not the `bar` in foo::bar::baz
+            Regex::new(r"(?:^|[^A-Za-z0-9_:])(?:::)?([A-Za-z_][A-Za-z0-9_]*)\s*::")
+                .expect("valid path pattern"),
+            // use foo; / use foo as bar; / extern crate foo;
+            Regex::new(r"\b(?:use|extern\s+crate)\s+(?:::)?([A-Za-z_][A-Za-z0-9_]*)\b")
+                .expect("valid use pattern"),
+        ]
+    });
+
+    patterns
+        .iter()
+        .flat_map(|re| re.captures_iter(source))
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+/// The identifier a dependency is referred to by in code
+pub fn crate_ident(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Every crate identifier referenced from `files`, through `cache`
+fn used_idents(files: &[PathBuf], cache: &UsageCache) -> Result<BTreeSet<String>> {
+    let mut used = BTreeSet::new();
+    for file in files {
+        used.extend(cache.idents(file)?);
+    }
+    Ok(used)
+}
+
+/// Compare declared dependencies against the identifiers used in `files`.
+/// Artifact dependencies are built, not imported, so they never count.
+pub fn find_unused_dependencies(
+    manifest: &Manifest,
+    files: &[PathBuf],
+    cache: &UsageCache,
+) -> Result<Vec<UnusedDependency>> {
+    let used = used_idents(files, cache)?;
+
+    Ok(manifest
+        .declarations()
+        .into_iter()
+        .filter(|(_, name, spec)| !spec.is_artifact_only() && !used.contains(&crate_ident(name)))
+        .map(|(section, name, _)| UnusedDependency {
+            line: manifest.location_in(&name, &section).map(|(line, _)| line),
+            name,
+            section,
+        })
+        .collect())
+}
+
+/// Check each member's declarations against its own source files (tests,
+/// benches and examples included) and group the results per crate
+pub fn workspace_usage(
+    members: &[(String, &Manifest, Vec<PathBuf>)],
+    cache: &UsageCache,
+) -> Result<WorkspaceUsage> {
+    let mut matrix = BTreeMap::new();
+    let mut crates: BTreeMap<String, CrateUsage> = BTreeMap::new();
+    // Reading the sources is the slow part, so members are scanned in
+    // parallel and only the bookkeeping below runs in order
+    let used: Vec<BTreeSet<String>> = members
+        .par_iter()
+        .map(|(_, _, files)| used_idents(files, cache))
+        .collect::<Result<_>>()?;
+
+    for ((member, manifest, _), used) in members.iter().zip(used) {
+        let mut declarations = Vec::new();
+
+        for (section, name, spec) in manifest.declarations() {
+            if spec.is_artifact_only() {
+                continue;
+            }
+            let is_used = used.contains(&crate_ident(&name));
+            let entry = crates.entry(name.clone()).or_insert_with(|| CrateUsage {
+                name: name.clone(),
+                declared_in: Vec::new(),
+                used_in: Vec::new(),
+                remove_from: Vec::new(),
+            });
+            if !entry.declared_in.contains(member) {
+                entry.declared_in.push(member.clone());
+            }
+            if is_used {
+                if !entry.used_in.contains(member) {
+                    entry.used_in.push(member.clone());
+                }
+            } else {
+                entry.remove_from.push(MemberDeclaration {
+                    member: member.clone(),
+                    manifest: manifest.path.clone(),
+                    line: manifest.location_in(&name, &section).map(|(line, _)| line),
+                    section: section.clone(),
+                });
+            }
+            declarations.push(DeclarationUsage {
+                name,
+                section,
+                used: is_used,
+            });
+        }
+        matrix.insert(member.clone(), declarations);
+    }
+
+    Ok(WorkspaceUsage {
+        matrix,
+        crates: crates
+            .into_values()
+            .filter(|c| !c.remove_from.is_empty())
+            .collect(),
+    })
+}
+
+/// The files of `all` that belong to the member in `dir`, leaving out those
+/// of other members nested below it
+pub fn member_files(dir: &Path, member_dirs: &[&Path], all: &[PathBuf]) -> Vec<PathBuf> {
+    let nested: Vec<&Path> = member_dirs
+        .iter()
+        .copied()
+        .filter(|other| *other != dir && other.starts_with(dir))
+        .collect();
+    all.iter()
+        .filter(|file| file.starts_with(dir))
+        .filter(|file| !nested.iter().any(|n| file.starts_with(n)))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_extract_crate_idents() {
+        let idents = extract_crate_idents(
+            r#"
+use serde::{Deserialize, Serialize};
+use anyhow;
+extern crate log;
+use std::collections::HashMap;
+
+#[tokio::main]
+async fn main() {
+    let v = ::serde_json::json!({});
+    regex::Regex::new("a::b").unwrap();
+}
+"#,
+        );
+
+        for expected in [
+            "serde",
+            "anyhow",
+            "log",
+            "std",
+            "tokio",
+            "serde_json",
+            "regex",
+        ] {
+            assert!(idents.contains(expected), "missing {}", expected);
+        }
+        assert!(!idents.contains("collections"));
+        assert!(!idents.contains("Regex"));
+    }
+
+    #[test]
+    fn test_crate_ident() {
+        assert_eq!(crate_ident("serde-json"), "serde_json");
+        assert_eq!(crate_ident("tokio"), "tokio");
+    }
+
+    #[test]
+    fn test_artifact_dependencies_are_never_unused() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib.rs");
+        fs::write(&lib, "fn main() {}").unwrap();
+        let manifest = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            "[dependencies]\nserde = \"1\"\n\n[build-dependencies]\nprotoc = { version = \"1\", artifact = \"bin\" }\nlinked = { version = \"1\", artifact = \"cdylib\", lib = true }\n",
+        )
+        .unwrap();
+
+        let unused =
+            find_unused_dependencies(&manifest, &[lib], &UsageCache::disabled(dir.path())).unwrap();
+        let names: Vec<&str> = unused.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, ["serde", "linked"]);
+    }
+
+    #[test]
+    fn test_workspace_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let manifest = |name: &str| {
+            Manifest::parse(
+                PathBuf::from(format!("crates/{}/Cargo.toml", name)),
+                "[package]\nname = \"x\"\n\n[dependencies]\nserde = \"1\"\n\n[dev-dependencies]\ninsta = \"1\"\n",
+            )
+            .unwrap()
+        };
+        let (a, b, c) = (manifest("a"), manifest("b"), manifest("c"));
+        let a_files = vec![write("a/src/lib.rs", "use serde::Serialize;")];
+        let b_files = vec![write("b/src/lib.rs", "fn f() {}")];
+        let c_files = vec![
+            write("c/src/lib.rs", "use serde::Serialize;"),
+            write(
+                "c/tests/snap.rs",
+                "#[test] fn t() { insta::assert_snapshot!(1); }",
+            ),
+        ];
+
+        let cache = UsageCache::disabled(dir.path());
+        let usage = workspace_usage(
+            &[
+                ("a".to_string(), &a, a_files),
+                ("b".to_string(), &b, b_files),
+                ("c".to_string(), &c, c_files),
+            ],
+            &cache,
+        )
+        .unwrap();
+        assert_eq!(cache.stats().parsed, 4);
+
+        assert_eq!(usage.matrix.len(), 3);
+        assert!(!usage.matrix["b"].iter().any(|d| d.used));
+
+        let names: Vec<&str> = usage.crates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["insta", "serde"]);
+
+        let insta = &usage.crates[0];
+        assert_eq!(insta.declared_in, vec!["a", "b", "c"]);
+        assert_eq!(insta.keep_in(), ["c"]);
+        let removals: Vec<&str> = insta
+            .remove_from
+            .iter()
+            .map(|r| r.member.as_str())
+            .collect();
+        assert_eq!(removals, vec!["a", "b"]);
+        assert_eq!(
+            insta.remove_from[0].manifest,
+            PathBuf::from("crates/a/Cargo.toml")
+        );
+        assert_eq!(insta.remove_from[0].line, Some(8));
+
+        assert_eq!(usage.crates[1].remove_from.len(), 1);
+        assert_eq!(usage.crates[1].remove_from[0].member, "b");
+    }
+
+    #[test]
+    fn test_member_files_skip_nested_members() {
+        let root = Path::new("/ws");
+        let nested = Path::new("/ws/crates/core");
+        let files = vec![
+            PathBuf::from("/ws/src/main.rs"),
+            PathBuf::from("/ws/crates/core/src/lib.rs"),
+        ];
+        assert_eq!(
+            member_files(root, &[root, nested], &files),
+            vec![PathBuf::from("/ws/src/main.rs")]
+        );
+        assert_eq!(
+            member_files(nested, &[root, nested], &files),
+            vec![PathBuf::from("/ws/crates/core/src/lib.rs")]
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Per-file results of the source scan behind `cargo sane clean`
+//!
+//! Reading every source file is most of what `clean` does, yet few of them
+//! change between two runs. Each file's identifiers, and which of them sit
+//! behind `#[cfg]`s, are kept under
+//! `.cargo-sane/usage-cache/` along with its size, modification time and
+//! content hash: a file whose size and time still match isn't read again,
+//! and one whose content hashes the same isn't parsed again. Only what the
+//! files reference is cached, never the manifest's dependencies, so editing
+//! Cargo.toml leaves every entry valid. A missing or outdated cache is
+//! quietly scanned over and rewritten, a corrupt one is set aside first as
+//! any cache is, and under `--test-mode` nothing is cached.
+
+use crate::analyzer::usage::FileUsage;
+use crate::core::manifest::Manifest;
+use crate::utils::cache::{fingerprint, read_json, STATE_DIR};
+use crate::utils::test_mode::Scenario;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Directory of the cache, under `.cargo-sane/`
+pub const USAGE_DIR: &str = "usage-cache";
+
+const CACHE_FILE: &str = "files.json";
+
+/// A file written this close to its scan may be written again within the
+/// same clock tick without its modification time changing, so its time
+/// alone can't vouch for it next run
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheContents {
+    /// The identifiers found depend on the scanner, so another version's
+    /// entries are thrown away
+    version: String,
+    /// By path relative to the project root
+    files: BTreeMap<String, CachedScan>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedScan {
+    size: u64,
+    /// Nanoseconds since the epoch
+    modified: u64,
+    /// Whether `modified` was too recent at scan time to be relied on
+    racy: bool,
+    hash: String,
+    #[serde(flatten)]
+    usage: FileUsage,
+}
+
+/// How the files of a run were scanned
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ScanStats {
+    /// Files read and parsed
+    pub parsed: usize,
+    /// Files answered from the cache
+    pub reused: usize,
+}
+
+/// The cached scans of one project, and what this run scanned
+pub struct UsageCache {
+    root: PathBuf,
+    path: Option<PathBuf>,
+    previous: BTreeMap<String, CachedScan>,
+    current: Mutex<BTreeMap<String, CachedScan>>,
+    parsed: AtomicUsize,
+    reused: AtomicUsize,
+    /// Files already scanned this run, answered again without counting
+    seen: Mutex<BTreeMap<String, FileUsage>>,
+}
+
+impl UsageCache {
+    /// The cache of the project owning `manifest`, or one that scans every
+    /// file afresh and keeps nothing unless `enabled`
+    pub fn for_manifest(manifest: &Manifest, enabled: bool) -> Self {
+        let root = manifest.path.parent().unwrap_or(Path::new("."));
+        if enabled && Scenario::active().is_none() {
+            Self::new(root, root.join(STATE_DIR).join(USAGE_DIR))
+        } else {
+            Self::disabled(root)
+        }
+    }
+
+    /// The cache in `dir` of the project at `root`
+    pub fn new(root: &Path, dir: PathBuf) -> Self {
+        let path = dir.join(CACHE_FILE);
+        // Corrupt or foreign contents only cost this run a full scan
+        let previous = read_json::<CacheContents>(&path)
+            .filter(|contents| contents.version == env!("CARGO_PKG_VERSION"))
+            .map(|contents| contents.files)
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            previous,
+            ..Self::disabled(root)
+        }
+    }
+
+    /// A cache that neither reads nor writes anything
+    pub fn disabled(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            path: None,
+            previous: BTreeMap::new(),
+            current: Mutex::new(BTreeMap::new()),
+            parsed: AtomicUsize::new(0),
+            reused: AtomicUsize::new(0),
+            seen: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// The crate identifiers referenced from `file`, as
+    /// [`extract_crate_idents`] finds them
+    ///
+    /// [`extract_crate_idents`]: crate::analyzer::usage::extract_crate_idents
+    pub fn idents(&self, file: &Path) -> Result<BTreeSet<String>> {
+        Ok(self.scan(file)?.idents)
+    }
+
+    /// What `file` references, and what of it only under `#[cfg]`s
+    pub fn scan(&self, file: &Path) -> Result<FileUsage> {
+        let key = self.key(file);
+        if let Some(usage) = self
+            .seen
+            .lock()
+            .ok()
+            .and_then(|seen| seen.get(&key).cloned())
+        {
+            return Ok(usage);
+        }
+        let usage = self.scan_file(file, &key)?;
+        if let Ok(mut seen) = self.seen.lock() {
+            seen.insert(key, usage.clone());
+        }
+        Ok(usage)
+    }
+
+    fn scan_file(&self, file: &Path, key: &str) -> Result<FileUsage> {
+        let read =
+            || fs::read_to_string(file).context(format!("Failed to read {}", file.display()));
+        if self.path.is_none() {
+            self.parsed.fetch_add(1, Ordering::Relaxed);
+            return Ok(FileUsage::scan(&read()?));
+        }
+
+        let metadata = fs::metadata(file).context(format!("Failed to read {}", file.display()))?;
+        let size = metadata.len();
+        let modified = metadata.modified().map(nanos).unwrap_or(0);
+        let cached = self.previous.get(key);
+
+        let scan = match cached {
+            Some(cached) if !cached.racy && cached.size == size && cached.modified == modified => {
+                self.reused.fetch_add(1, Ordering::Relaxed);
+                cached.clone()
+            }
+            _ => {
+                let source = read()?;
+                let hash = fingerprint(&source);
+                let usage = match cached.filter(|cached| cached.hash == hash) {
+                    Some(cached) => {
+                        self.reused.fetch_add(1, Ordering::Relaxed);
+                        cached.usage.clone()
+                    }
+                    None => {
+                        self.parsed.fetch_add(1, Ordering::Relaxed);
+                        FileUsage::scan(&source)
+                    }
+                };
+                let now = nanos(SystemTime::now());
+                CachedScan {
+                    size,
+                    modified,
+                    racy: now.saturating_sub(modified) < RACY_WINDOW.as_nanos() as u64,
+                    hash,
+                    usage,
+                }
+            }
+        };
+
+        let usage = scan.usage.clone();
+        if let Ok(mut current) = self.current.lock() {
+            current.insert(key.to_string(), scan);
+        }
+        Ok(usage)
+    }
+
+    /// How this run's files were scanned so far
+    pub fn stats(&self) -> ScanStats {
+        ScanStats {
+            parsed: self.parsed.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Keep this run's scans for the next one, dropping files it didn't
+    /// see. Nothing is written when nothing changed.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let current = self
+            .current
+            .lock()
+            .map_err(|_| anyhow::anyhow!("The usage cache was poisoned"))?;
+        if *current == self.previous && path.exists() {
+            return Ok(());
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        let contents = CacheContents {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            files: current.clone(),
+        };
+        fs::write(path, serde_json::to_string(&contents)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+
+    /// `file` relative to the project root, with forward slashes
+    fn key(&self, file: &Path) -> String {
+        file.strip_prefix(&self.root)
+            .unwrap_or(file)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+}
+
+fn nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| u64::try_from(d.as_nanos()).unwrap_or(u64::MAX))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::cache::quarantine_path;
+
+    fn scanned(parsed: usize, reused: usize) -> ScanStats {
+        ScanStats { parsed, reused }
+    }
+
+    #[test]
+    fn test_unchanged_content_is_not_parsed_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join(STATE_DIR).join(USAGE_DIR);
+        let lib = dir.path().join("src/lib.rs");
+        fs::create_dir_all(lib.parent().unwrap()).unwrap();
+        fs::write(&lib, "use serde::Serialize;\n").unwrap();
+
+        let cold = UsageCache::new(dir.path(), cache_dir.clone());
+        assert_eq!(
+            cold.idents(&lib).unwrap(),
+            BTreeSet::from(["serde".to_string()])
+        );
+        cold.save().unwrap();
+        assert_eq!(cold.stats(), scanned(1, 0));
+
+        // Just written, so the file is hashed rather than trusted by its time
+        let warm = UsageCache::new(dir.path(), cache_dir.clone());
+        assert!(warm.previous["src/lib.rs"].racy);
+        warm.idents(&lib).unwrap();
+        assert_eq!(warm.stats(), scanned(0, 1));
+
+        // The same size and, on a coarse clock, the same time
+        fs::write(&lib, "use tokio::spawn;\n").unwrap();
+        let changed = UsageCache::new(dir.path(), cache_dir);
+        assert_eq!(
+            changed.idents(&lib).unwrap(),
+            BTreeSet::from(["tokio".to_string()])
+        );
+        assert_eq!(changed.stats(), scanned(1, 0));
+    }
+
+    #[test]
+    fn test_corrupt_cache_is_set_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join(STATE_DIR).join(USAGE_DIR);
+        fs::create_dir_all(&cache_dir).unwrap();
+        fs::write(cache_dir.join(CACHE_FILE), "{\"version\": \"tru").unwrap();
+
+        let cache = UsageCache::new(dir.path(), cache_dir.clone());
+        assert!(cache.previous.is_empty());
+        assert!(!cache_dir.join(CACHE_FILE).exists());
+        assert!(quarantine_path(&cache_dir.join(CACHE_FILE)).exists());
+    }
+
+    #[test]
+    fn test_disabled_cache_keeps_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib.rs");
+        fs::write(&lib, "fn main() { log::info!(\"hi\"); }").unwrap();
+
+        let cache = UsageCache::disabled(dir.path());
+        assert_eq!(
+            cache.idents(&lib).unwrap(),
+            BTreeSet::from(["log".to_string()])
+        );
+        cache.save().unwrap();
+        assert_eq!(cache.stats(), scanned(1, 0));
+        assert!(!dir.path().join(STATE_DIR).exists());
+    }
+}
@@ -0,0 +1,248 @@
+//! Tool pins in GitHub Actions workflows
+//!
+//! CI often installs tools with `cargo install cargo-deny --version 0.14.0`,
+//! and those pins go stale out of sight. Only that exact command shape is
+//! recognized, so a workflow that merely mentions cargo isn't misread.
+
+use crate::utils::registry::RegistryProvider;
+use futures::stream::{self, StreamExt};
+use regex::Regex;
+use schemars::JsonSchema;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const WORKFLOWS_DIR: &str = ".github/workflows";
+
+/// A crate a workflow installs at a pinned version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WorkflowPin {
+    pub name: String,
+    /// The `--version` argument as written
+    pub requirement: String,
+    /// The workflow file, relative to the directory holding `.github`
+    pub file: PathBuf,
+    /// 1-based line of the `cargo install`
+    pub line: usize,
+    /// The newest release, when the registry could be asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest: Option<Version>,
+}
+
+impl WorkflowPin {
+    /// A newer release exists than the pin allows. `cargo install` reads a
+    /// bare version as exact, anything else as a requirement.
+    pub fn is_outdated(&self) -> bool {
+        let Some(latest) = &self.latest else {
+            return false;
+        };
+        if let Ok(pinned) = Version::parse(&self.requirement) {
+            return latest > &pinned;
+        }
+        VersionReq::parse(&self.requirement).is_ok_and(|req| !req.matches(latest))
+    }
+}
+
+/// The pins in every workflow of the nearest directory at or above `start`
+/// with a `.github/workflows` directory, by file and line
+pub fn find_workflow_pins(start: &Path) -> Vec<WorkflowPin> {
+    let Some(root) = start
+        .ancestors()
+        .find(|dir| dir.join(WORKFLOWS_DIR).is_dir())
+    else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(root.join(WORKFLOWS_DIR)) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == "yml" || ext == "yaml")
+        })
+        .collect();
+    files.sort();
+
+    files
+        .iter()
+        .filter_map(|path| {
+            let content = fs::read_to_string(path).ok()?;
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            Some(scan_workflow(relative, &content))
+        })
+        .flatten()
+        .collect()
+}
+
+/// Find `cargo install <crate> --version <version>` in workflow text, run as
+/// a command: at the start of a line or `run:`, or after `;`, `&&`, `|` or
+/// `(`. The version must be literal; templated ones like `${{ env.V }}` are
+/// skipped.
+pub fn scan_workflow(file: &Path, content: &str) -> Vec<WorkflowPin> {
+    let pattern = Regex::new(
+        r#"(?:^\s*(?:-\s*)?(?:run:\s*)?|[;&|(]\s*)cargo\s+install\s+([A-Za-z0-9_-]+)\s+--version(?:\s+|=)(["']?)([=^~]?[0-9][0-9A-Za-z.+-]*)(["']?)(?:$|[\s;&|)])"#,
+    )
+    .expect("valid workflow pattern");
+
+    let mut pins = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        for caps in pattern.captures_iter(line) {
+            if caps[2] != caps[4] {
+                continue;
+            }
+            pins.push(WorkflowPin {
+                name: caps[1].to_string(),
+                requirement: caps[3].to_string(),
+                file: file.to_path_buf(),
+                line: index + 1,
+                latest: None,
+            });
+        }
+    }
+    pins
+}
+
+/// Fill in the newest release of each pinned crate, looking every crate up
+/// once. Crates the registry can't answer for keep `latest` unset.
+pub async fn check_pins<P: RegistryProvider>(
+    provider: &P,
+    pins: &mut [WorkflowPin],
+    concurrency: usize,
+) {
+    let mut names: Vec<&str> = pins.iter().map(|pin| pin.name.as_str()).collect();
+    names.sort_unstable();
+    names.dedup();
+
+    let latest: Vec<(String, Option<Version>)> = stream::iter(names)
+        .map(|name| async move {
+            (
+                name.to_string(),
+                provider.get_latest_version(name).await.ok(),
+            )
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    for pin in pins.iter_mut() {
+        pin.latest = latest
+            .iter()
+            .find(|(name, _)| name == &pin.name)
+            .and_then(|(_, version)| version.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::version::PublishedVersion;
+    use crate::Result;
+
+    const CI: &str = r#"name: CI
+on: [push]
+jobs:
+  lint:
+    runs-on: ubuntu-latest
+    steps:
+      - run: cargo install cargo-deny --version 0.14.0
+      - run: |
+          cargo install cargo-nextest --version=0.9.70 --locked
+          cargo install cargo-audit --version "^0.20"
+      # - run: cargo install cargo-udeps --version 0.1.40
+      - run: cargo install cargo-machete
+      - run: cargo install --version 1.0.0 cargo-about
+      - run: cargo install cargo-hack@0.6.0
+      - run: cargo install cargo-sort --version ${{ env.SORT_VERSION }}
+      - run: cargo install typos-cli --version "1.0.0'
+      - run: echo "run cargo install taplo-cli --version 0.9.0 later"
+      - run: cargo fetch && cargo install cargo-llvm-cov --version 0.6.9
+      - run: mycargo install cargo-mutants --version 24.1.0
+"#;
+
+    fn pins() -> Vec<WorkflowPin> {
+        scan_workflow(Path::new(".github/workflows/ci.yml"), CI)
+    }
+
+    #[test]
+    fn test_scan_matches_exact_install_commands_only() {
+        let pins = pins();
+        let found: Vec<(&str, &str, usize)> = pins
+            .iter()
+            .map(|pin| (pin.name.as_str(), pin.requirement.as_str(), pin.line))
+            .collect();
+
+        assert_eq!(
+            found,
+            [
+                ("cargo-deny", "0.14.0", 7),
+                ("cargo-nextest", "0.9.70", 9),
+                ("cargo-audit", "^0.20", 10),
+                ("cargo-llvm-cov", "0.6.9", 18),
+            ]
+        );
+        assert_eq!(pins[0].file, Path::new(".github/workflows/ci.yml"));
+    }
+
+    #[test]
+    fn test_outdated_exact_and_requirement_pins() {
+        let pin = |requirement: &str, latest: Option<Version>| WorkflowPin {
+            name: "tool".to_string(),
+            requirement: requirement.to_string(),
+            file: PathBuf::from("ci.yml"),
+            line: 1,
+            latest,
+        };
+
+        assert!(pin("0.14.0", Some(Version::new(0, 14, 1))).is_outdated());
+        assert!(!pin("0.14.0", Some(Version::new(0, 14, 0))).is_outdated());
+        assert!(!pin("^0.20", Some(Version::new(0, 20, 4))).is_outdated());
+        assert!(pin("^0.20", Some(Version::new(0, 21, 0))).is_outdated());
+        assert!(!pin("0.14.0", None).is_outdated());
+    }
+
+    struct Latest(Vec<(&'static str, Version)>);
+
+    impl RegistryProvider for Latest {
+        async fn get_latest_version(&self, crate_name: &str) -> Result<Version> {
+            self.0
+                .iter()
+                .find(|(name, _)| *name == crate_name)
+                .map(|(_, version)| version.clone())
+                .ok_or_else(|| anyhow::anyhow!("unknown crate {}", crate_name))
+        }
+
+        async fn get_versions(&self, _crate_name: &str) -> Result<Vec<Version>> {
+            anyhow::bail!("not used by this test")
+        }
+
+        async fn get_published_versions(&self, _crate_name: &str) -> Result<Vec<PublishedVersion>> {
+            anyhow::bail!("not used by this test")
+        }
+    }
+
+    #[test]
+    fn test_check_pins_fills_in_latest() {
+        let mut pins = pins();
+        let registry = Latest(vec![
+            ("cargo-deny", Version::new(0, 16, 1)),
+            ("cargo-audit", Version::new(0, 20, 1)),
+        ]);
+        futures::executor::block_on(check_pins(&registry, &mut pins, 2));
+
+        let outdated: Vec<&str> = pins
+            .iter()
+            .filter(|pin| pin.is_outdated())
+            .map(|pin| pin.name.as_str())
+            .collect();
+        assert_eq!(outdated, ["cargo-deny"]);
+        assert_eq!(pins[2].latest, Some(Version::new(0, 20, 1)));
+        assert_eq!(pins[1].latest, None);
+    }
+}
@@ -0,0 +1,263 @@
+//! Roll per-member check results up into a workspace view
+
+use crate::analyzer::features::FeatureUsage;
+use crate::analyzer::freshness::BudgetViolation;
+use crate::analyzer::lock_mismatch::LockMismatch;
+use crate::analyzer::priority::{Class, Significance};
+use crate::core::dependency::{Dependency, UpdateType};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Check results for a whole workspace: per member, and per crate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceReport {
+    pub root: PathBuf,
+    pub members: Vec<MemberReport>,
+    /// Every checked crate once, with the members that declare it
+    pub crates: Vec<WorkspaceCrate>,
+    /// Declared crates that are the workspace's own, and so weren't checked
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub internal: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberReport {
+    pub name: String,
+    pub manifest: PathBuf,
+    pub summary: UpdateSummary,
+    pub dependencies: Vec<Dependency>,
+    /// Requested vs resolved features, when cargo metadata was available
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<FeatureUsage>,
+    /// Dependencies further behind than the freshness budget allows
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub freshness: Vec<BudgetViolation>,
+    /// Requirements the workspace's Cargo.lock no longer satisfies
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lock_mismatches: Vec<LockMismatch>,
+}
+
+/// Dependency counts by available update
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateSummary {
+    pub total: usize,
+    pub up_to_date: usize,
+    pub patch: usize,
+    pub minor: usize,
+    pub major: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceCrate {
+    pub name: String,
+    pub latest_version: Option<Version>,
+    pub declared_by: Vec<MemberRequirement>,
+}
+
+/// How one member declares a crate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberRequirement {
+    pub member: String,
+    pub requirement: Option<String>,
+    pub current_version: Version,
+}
+
+impl UpdateSummary {
+    pub fn of(dependencies: &[Dependency]) -> Self {
+        let mut summary = Self {
+            total: dependencies.len(),
+            ..Self::default()
+        };
+        for dep in dependencies {
+            match dep.update_type() {
+                UpdateType::UpToDate => summary.up_to_date += 1,
+                UpdateType::Patch => summary.patch += 1,
+                UpdateType::Minor => summary.minor += 1,
+                UpdateType::Major => summary.major += 1,
+            }
+        }
+        summary
+    }
+}
+
+impl WorkspaceCrate {
+    /// The largest update any declaring member would get
+    pub fn update_type(&self) -> UpdateType {
+        let rank = |t: &UpdateType| match t {
+            UpdateType::UpToDate => 0,
+            UpdateType::Patch => 1,
+            UpdateType::Minor => 2,
+            UpdateType::Major => 3,
+        };
+        self.declared_by
+            .iter()
+            .map(|d| self.as_dependency(d).update_type())
+            .max_by_key(rank)
+            .unwrap_or(UpdateType::UpToDate)
+    }
+
+    pub fn has_update(&self) -> bool {
+        self.update_type() != UpdateType::UpToDate
+    }
+
+    /// Whether members disagree on the requirement
+    pub fn requirements_diverge(&self) -> bool {
+        self.declared_by
+            .windows(2)
+            .any(|pair| pair[0].requirement != pair[1].requirement)
+    }
+
+    /// The significance of the most significant declaration
+    pub fn significance(&self) -> Significance {
+        self.declared_by
+            .iter()
+            .map(|d| Significance::of_dependency(&self.as_dependency(d), false))
+            .max()
+            .unwrap_or_else(|| Significance::new(Class::UpToDate, &Version::new(0, 0, 0), None))
+    }
+
+    fn as_dependency(&self, declaration: &MemberRequirement) -> Dependency {
+        let dep = Dependency::new(self.name.clone(), declaration.current_version.clone(), true);
+        match &self.latest_version {
+            Some(latest) => dep.with_latest(latest.clone()),
+            None => dep,
+        }
+    }
+}
+
+impl WorkspaceReport {
+    /// Build the report from each member's checked dependencies, members
+    /// ordered by name
+    pub fn rollup(root: PathBuf, mut members: Vec<(String, PathBuf, Vec<Dependency>)>) -> Self {
+        members.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut crates: BTreeMap<String, WorkspaceCrate> = BTreeMap::new();
+
+        for (member, _, dependencies) in &members {
+            for dep in dependencies {
+                let entry = crates
+                    .entry(dep.name.clone())
+                    .or_insert_with(|| WorkspaceCrate {
+                        name: dep.name.clone(),
+                        latest_version: None,
+                        declared_by: Vec::new(),
+                    });
+                if entry.latest_version.is_none() {
+                    entry.latest_version = dep.latest_version.clone();
+                }
+                entry.declared_by.push(MemberRequirement {
+                    member: member.clone(),
+                    requirement: dep.requirement.clone(),
+                    current_version: dep.current_version.clone(),
+                });
+            }
+        }
+
+        Self {
+            root,
+            members: members
+                .into_iter()
+                .map(|(name, manifest, dependencies)| MemberReport {
+                    summary: UpdateSummary::of(&dependencies),
+                    name,
+                    manifest,
+                    dependencies,
+                    features: Vec::new(),
+                    freshness: Vec::new(),
+                    lock_mismatches: Vec::new(),
+                })
+                .collect(),
+            crates: crates.into_values().collect(),
+            internal: Vec::new(),
+        }
+    }
+
+    /// The same report restricted to one member, if it exists
+    pub fn scoped_to(&self, member: &str) -> Option<Self> {
+        let report = self.members.iter().find(|m| m.name == member)?;
+        let mut scoped = Self::rollup(
+            self.root.clone(),
+            vec![(
+                report.name.clone(),
+                report.manifest.clone(),
+                report.dependencies.clone(),
+            )],
+        );
+        scoped.internal = self.internal.clone();
+        scoped.members[0].features = report.features.clone();
+        scoped.members[0].freshness = report.freshness.clone();
+        scoped.members[0].lock_mismatches = report.lock_mismatches.clone();
+        Some(scoped)
+    }
+
+    /// Whether every member keeps to its freshness budget and is satisfied
+    /// by Cargo.lock, as a single package's check requires
+    pub fn passed(&self) -> bool {
+        self.members
+            .iter()
+            .all(|m| m.freshness.is_empty() && m.lock_mismatches.is_empty())
+    }
+
+    /// Crates with an update available for at least one member
+    pub fn outdated(&self) -> Vec<&WorkspaceCrate> {
+        self.crates.iter().filter(|c| c.has_update()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dep(name: &str, requirement: &str, current: &str, latest: &str) -> Dependency {
+        Dependency::new(name.to_string(), Version::parse(current).unwrap(), true)
+            .with_requirement(requirement)
+            .with_latest(Version::parse(latest).unwrap())
+    }
+
+    #[test]
+    fn test_rollup_deduplicates_crates() {
+        let report = WorkspaceReport::rollup(
+            PathBuf::from("Cargo.toml"),
+            vec![
+                (
+                    "core".to_string(),
+                    PathBuf::from("core/Cargo.toml"),
+                    vec![
+                        dep("serde", "1.0", "1.0.0", "1.0.200"),
+                        dep("log", "0.4.20", "0.4.20", "0.4.20"),
+                    ],
+                ),
+                (
+                    "web".to_string(),
+                    PathBuf::from("web/Cargo.toml"),
+                    vec![dep("serde", "1.0.150", "1.0.150", "1.0.200")],
+                ),
+            ],
+        );
+
+        assert_eq!(
+            report.members[0].summary,
+            UpdateSummary {
+                total: 2,
+                up_to_date: 1,
+                patch: 1,
+                minor: 0,
+                major: 0
+            }
+        );
+        assert_eq!(report.crates.len(), 2);
+
+        let outdated = report.outdated();
+        assert_eq!(outdated.len(), 1);
+        assert_eq!(outdated[0].name, "serde");
+        assert_eq!(outdated[0].declared_by.len(), 2);
+        assert!(outdated[0].requirements_diverge());
+        assert_eq!(outdated[0].update_type(), UpdateType::Patch);
+
+        let scoped = report.scoped_to("web").unwrap();
+        assert_eq!(scoped.members.len(), 1);
+        assert_eq!(scoped.crates.len(), 1);
+        assert!(report.scoped_to("missing").is_none());
+    }
+}
@@ -0,0 +1,90 @@
+//! `cargo sane accept`: advisories and duplicated crates recorded as
+//! reviewed risks
+
+use crate::analyzer::accepted::{AcceptedRisk, AcceptedRisks, RiskSubject};
+use crate::cli::commands::find_manifest;
+use crate::cli::output::{self, Paint};
+use crate::utils::cache;
+use crate::utils::formatting::parse_date;
+use crate::Result;
+use anyhow::Context;
+use std::path::Path;
+
+/// Record, remove or list accepted-risk decisions. `id` is an advisory id,
+/// or a crate name when `conflict` or `build_script` is set.
+#[allow(clippy::too_many_arguments)]
+pub fn accept_command(
+    manifest_path: Option<String>,
+    id: Option<String>,
+    conflict: bool,
+    build_script: bool,
+    reason: Option<String>,
+    expires: Option<String>,
+    by: Option<String>,
+    remove: bool,
+    list: bool,
+) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let mut accepted = AcceptedRisks::load(root)?;
+    let now = cache::unix_now();
+
+    if list {
+        if accepted.is_empty() {
+            output::print_info("No accepted risks recorded.");
+        }
+        for risk in &accepted.accepted {
+            let expired = if risk.is_expired(now) {
+                format!(" {}", "(expired)".bad())
+            } else {
+                String::new()
+            };
+            println!("  • {}{}", risk.summary(), expired);
+        }
+        return Ok(());
+    }
+
+    let id =
+        id.context("Give an advisory id, or a crate name with --conflict or --build-script")?;
+    let subject = if conflict {
+        RiskSubject::Conflict { package: id }
+    } else if build_script {
+        RiskSubject::BuildScript { package: id }
+    } else {
+        RiskSubject::Advisory { id }
+    };
+    if remove {
+        if !accepted.remove(&subject) {
+            anyhow::bail!("No acceptance of {} is recorded", subject);
+        }
+        accepted.save(root)?;
+        output::print_success(&format!("Removed the acceptance of {}", subject));
+        return Ok(());
+    }
+
+    if let Some(date) = &expires {
+        let at = parse_date(date).context(format!(
+            "Invalid expiry date '{}', expected YYYY-MM-DD",
+            date
+        ))?;
+        if at <= now {
+            anyhow::bail!("The expiry date {} has already passed", date);
+        }
+    }
+    let risk = AcceptedRisk {
+        subject,
+        reason,
+        expires,
+        accepted_by: by.or_else(|| {
+            std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .ok()
+        }),
+    };
+    let summary = risk.summary();
+    accepted.accept(risk);
+    let path = accepted.save(root)?;
+    output::print_success(&format!("Accepted {}", summary));
+    output::print_info(&format!("Recorded in {}", path.display()));
+    Ok(())
+}
@@ -0,0 +1,114 @@
+//! `cargo sane audit`: the log of changes cargo-sane made
+
+use crate::cli::commands::find_manifest;
+use crate::cli::output::{self, Paint};
+use crate::utils::audit::{AuditChange, AuditEntry, AuditFilter, AuditLog};
+use crate::utils::formatting::{display_path, parse_date};
+use crate::Result;
+use colored::Colorize;
+
+/// Show the changes recorded in the audit log, optionally only those since
+/// a date or touching one crate
+pub fn audit_command(
+    manifest_path: Option<String>,
+    since: Option<String>,
+    name: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let since = since
+        .map(|date| {
+            parse_date(&date)
+                .ok_or_else(|| anyhow::anyhow!("Invalid date '{}', expected YYYY-MM-DD", date))
+        })
+        .transpose()?;
+    let filter = AuditFilter { since, name };
+    let log = AuditLog::for_manifest(&manifest.path);
+    let (entries, malformed) = log.read()?;
+    let entries: Vec<AuditEntry> = entries.iter().filter_map(|e| filter.apply(e)).collect();
+
+    if json {
+        output::print_json(&serde_json::json!({ "entries": entries }))?;
+        return Ok(());
+    }
+
+    output::print_header("📜 cargo-sane audit");
+    println!();
+    output::print_info(&format!("Log: {}", display_path(log.path())));
+    if malformed > 0 {
+        output::print_warning(&format!(
+            "Skipped {} unreadable line(s) of the log",
+            malformed
+        ));
+    }
+    println!();
+
+    if entries.is_empty() {
+        output::print_info("No recorded changes.");
+        return Ok(());
+    }
+    for entry in &entries {
+        let verified = if entry.verified {
+            "verified".good()
+        } else {
+            "not verified".bad()
+        };
+        let user = entry
+            .user
+            .as_deref()
+            .map(|user| format!(" by {}", user))
+            .unwrap_or_default();
+        println!(
+            "{} {}{} ({})",
+            entry.timestamp.dimmed(),
+            entry.command.bold(),
+            user,
+            verified
+        );
+        let args = if entry.args.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", entry.args.join(" ").dimmed())
+        };
+        println!("  {}{}", display_path(&entry.manifest), args);
+        for change in &entry.changes {
+            println!("  • {}", describe_change(change));
+        }
+        if let Some(backup) = &entry.backup {
+            println!("  {}", format!("backup: {}", display_path(backup)).dimmed());
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// e.g. `serde "1.0" → "1.0.200" [dependencies]`
+fn describe_change(change: &AuditChange) -> String {
+    let quoted = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(|v| format!("\"{}\"", v))
+            .unwrap_or_default()
+    };
+    match (&change.old, &change.new) {
+        (Some(_), Some(_)) => format!(
+            "{} {} → {} [{}]",
+            change.name.bold(),
+            quoted(&change.old).dimmed(),
+            quoted(&change.new).cyan(),
+            change.section
+        ),
+        (Some(_), None) => format!(
+            "{} {} removed from [{}]",
+            change.name.bold(),
+            quoted(&change.old).dimmed(),
+            change.section
+        ),
+        (None, _) => format!(
+            "{} {} added to [{}]",
+            change.name.bold(),
+            quoted(&change.new).cyan(),
+            change.section
+        ),
+    }
+}
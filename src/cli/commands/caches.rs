@@ -0,0 +1,149 @@
+//! `cargo sane cache`: showing, clearing and pruning the caches under
+//! .cargo-sane
+
+use crate::cli::commands::find_manifest;
+use crate::cli::output::{self, Paint};
+use crate::core::config::Config;
+use crate::utils::cache;
+use crate::utils::cache_store::{CacheFile, CacheKind, CacheStore};
+use crate::utils::formatting::{display_path, format_bytes, format_since, plural};
+use crate::Result;
+use colored::Colorize;
+use std::path::Path;
+
+/// Where the caches are, how big and how old
+pub fn cache_status_command(manifest_path: Option<String>, json: bool) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let config = Config::load(root)?;
+    let store = CacheStore::for_manifest(&manifest);
+    let files = store.files()?;
+    let total: u64 = files.iter().map(|file| file.size).sum();
+    let max_size = cache_budget(&config);
+
+    if json {
+        output::print_json(&serde_json::json!({
+            "directory": store.dir(),
+            "total_size": total,
+            "max_size": max_size,
+            "caches": files,
+        }))?;
+        return Ok(());
+    }
+
+    output::print_header("🗄 cargo-sane cache");
+    println!();
+    output::print_info(&format!(
+        "Caches in {} ({})",
+        display_path(store.dir()),
+        format_bytes(total)
+    ));
+    if let Some(max_size) = max_size {
+        output::print_info(&format!(
+            "Budget: {} (cache_max_size_mb); `cargo sane cache gc` prunes to it",
+            format_bytes(max_size)
+        ));
+    }
+
+    let now = cache::unix_now();
+    for kind in CacheKind::ALL {
+        println!();
+        println!("{}", kind.title().bold());
+        let of_kind: Vec<&CacheFile> = files.iter().filter(|file| file.kind == kind).collect();
+        if of_kind.is_empty() {
+            println!("  {}", "nothing cached".dimmed());
+        }
+        for file in of_kind {
+            let entries = match file.entries {
+                Some(entries) => plural(entries as u64, "entry"),
+                None if file.corrupt => "corrupt".bad().to_string(),
+                None => "unreadable".caution().to_string(),
+            };
+            println!(
+                "  {:<28} {:>12} {:>9}  written {}",
+                file.path,
+                entries,
+                format_bytes(file.size),
+                format_since(file.modified_at, now)
+            );
+        }
+    }
+
+    let corrupt = files.iter().filter(|file| file.corrupt).count();
+    if corrupt > 0 {
+        println!();
+        output::print_warning(&format!(
+            "{} set aside as corrupt; `cargo sane cache clear` or `cache gc` removes them",
+            plural(corrupt as u64, "file")
+        ));
+    }
+    Ok(())
+}
+
+/// Delete the caches of `kinds`; commands rebuild them as needed
+pub fn cache_clear_command(manifest_path: Option<String>, kinds: Vec<CacheKind>) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let store = CacheStore::for_manifest(&manifest);
+    let removed = store.clear(&kinds)?;
+
+    if removed.is_empty() {
+        output::print_info("Nothing cached to clear");
+        return Ok(());
+    }
+    for file in &removed {
+        println!("  {} {}", "-".bad(), file.path);
+    }
+    output::print_success(&format!(
+        "Cleared {} ({})",
+        plural(removed.len() as u64, "cache file"),
+        format_bytes(removed.iter().map(|file| file.size).sum())
+    ));
+    Ok(())
+}
+
+/// Delete corrupt and expired caches, then the oldest until the rest fit
+/// the config's `cache_max_size_mb`
+pub fn cache_gc_command(manifest_path: Option<String>, dry_run: bool) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let config = Config::load(root)?;
+    let store = CacheStore::for_manifest(&manifest);
+    let report = store.gc(
+        config.cache_ttl_minutes,
+        cache_budget(&config),
+        cache::unix_now(),
+        dry_run,
+    )?;
+
+    let groups = [
+        ("corrupt", &report.corrupt),
+        ("expired", &report.expired),
+        ("over the size budget", &report.over_budget),
+    ];
+    for (reason, files) in groups {
+        for file in files {
+            println!("  {} {} ({})", "-".bad(), file.path, reason.dimmed());
+        }
+    }
+
+    let removed: Vec<&CacheFile> = report.removed().collect();
+    let summary = format!(
+        "{} ({}), leaving {}",
+        plural(removed.len() as u64, "cache file"),
+        format_bytes(removed.iter().map(|file| file.size).sum()),
+        format_bytes(report.remaining_size)
+    );
+    if dry_run {
+        output::print_info(&format!("Dry-run mode: would remove {}", summary));
+    } else if removed.is_empty() {
+        output::print_info("Nothing to collect");
+    } else {
+        output::print_success(&format!("Removed {}", summary));
+    }
+    Ok(())
+}
+
+/// The config's cache size budget in bytes, if it sets one
+fn cache_budget(config: &Config) -> Option<u64> {
+    (config.cache_max_size_mb > 0).then(|| config.cache_max_size_mb * 1_000_000)
+}
@@ -0,0 +1,1706 @@
+//! `cargo sane check`: which dependencies have updates available
+
+use crate::analyzer::checker::{CheckReport, DependencyChecker};
+use crate::analyzer::detail::{crate_detail, declarations_of, CrateDetail};
+use crate::analyzer::features::FeatureUsage;
+use crate::analyzer::freshness::{budget_violations, BudgetViolation};
+use crate::analyzer::history::{dependency_history, DependencyOrigin};
+use crate::analyzer::lock_mismatch::{find_lock_mismatches, LockMismatch};
+use crate::analyzer::ownership::{ownership_changes, OwnershipChange};
+use crate::analyzer::priority::{rank, truncate, Significance};
+use crate::analyzer::redundancy::{find_redundancies, redundancy_groups, Redundancy};
+use crate::analyzer::snoozed::Snoozes;
+use crate::analyzer::stats::{dependency_stats, maintainer_groups, DependencyStats};
+use crate::analyzer::teams::{group_by_team, unassigned_count, Teams};
+use crate::analyzer::workflows::{check_pins, find_workflow_pins, WorkflowPin};
+use crate::analyzer::workspace::WorkspaceReport;
+use crate::cli::commands::fix::print_declaration_conflicts;
+use crate::cli::commands::update::{
+    inherited_marker, major_api_diffs, policy_marker, print_api_diff, print_deny_violation,
+    print_license_change, target_marker,
+};
+use crate::cli::commands::{
+    find_manifest, internal_crates, load_policy, print_more, print_network_hint, print_partial,
+    print_unknown_team_crates, runtime, workspace_implied, write_metrics,
+};
+use crate::cli::csv::check_csv;
+use crate::cli::markdown::check_markdown;
+use crate::cli::metrics::Metrics;
+use crate::cli::output::{self, FileFormat, OutputFile, OutputFormat, Paint, Status};
+use crate::core::config::Config;
+use crate::core::deny::DenyPolicy;
+use crate::core::dependency::{
+    Dependency, DependencySource, ForkedDependency, PathDependency, SkipCause, SkippedDependency,
+    UpdateType,
+};
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::core::policy::PolicyStatus;
+use crate::core::version::PublishedVersion;
+use crate::core::workspace::Workspace;
+use crate::utils::advisory_db::{database_path, AdvisoryIndex};
+use crate::utils::cache::{self, ReportCache, STATE_DIR};
+use crate::utils::cancel::Cancellation;
+use crate::utils::cargo::{self, CargoOptions};
+use crate::utils::crates_io::CratesIoClient;
+use crate::utils::formatting::{
+    display_path, format_bytes, format_count, format_duration, format_since, plural,
+};
+use crate::utils::owners::{crate_owners, Owners};
+use crate::utils::progress::{Progress, ProgressMode};
+use crate::utils::registry::RegistryProvider;
+use crate::utils::snapshots::SnapshotStore;
+use crate::utils::timings;
+use crate::Result;
+use anyhow::Context;
+use colored::Colorize;
+use semver::Version;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What `check` reports on and how
+#[derive(Debug, Clone, Default)]
+pub struct CheckOptions {
+    pub verbose: bool,
+    pub format: OutputFormat,
+    pub output_path: Option<PathBuf>,
+    pub metrics_out: Option<PathBuf>,
+    pub refresh: bool,
+    pub pre: bool,
+    pub workspace: bool,
+    pub package: Option<String>,
+    pub redundancy: bool,
+    /// Entries shown per section (0 shows all)
+    pub limit: usize,
+    pub stats: bool,
+    pub owners: bool,
+    pub explain_skipped: bool,
+    pub api_diff: bool,
+    pub workflows: bool,
+    /// Check this one crate instead of the whole manifest
+    pub crate_name: Option<String>,
+    pub history: bool,
+    pub output_files: Vec<OutputFile>,
+}
+
+pub fn check_command(manifest_path: Option<String>, options: CheckOptions) -> Result<bool> {
+    // Load Cargo.toml
+    let manifest = {
+        let _span = timings::span("manifest");
+        find_manifest(manifest_path)?
+    };
+    let json = options.format.is_machine_readable();
+    let passed = check_manifest(manifest.clone(), options)?;
+    if !json {
+        output::print_timings();
+    }
+    print_network_hint(&manifest, None, true, json);
+    Ok(passed)
+}
+
+fn check_manifest(manifest: Manifest, options: CheckOptions) -> Result<bool> {
+    let CheckOptions {
+        verbose,
+        format,
+        output_path,
+        metrics_out,
+        refresh,
+        pre,
+        workspace,
+        package,
+        redundancy,
+        limit,
+        stats,
+        owners,
+        explain_skipped,
+        api_diff,
+        workflows,
+        crate_name,
+        history,
+        output_files,
+    } = options;
+    let json = format.is_machine_readable();
+
+    if let Some(name) = crate_name {
+        if format == OutputFormat::Csv {
+            anyhow::bail!("--format csv covers the full check; drop the crate name");
+        }
+        if metrics_out.is_some() {
+            anyhow::bail!("--metrics-out covers the full check; drop the crate name");
+        }
+        if !output_files.is_empty() {
+            anyhow::bail!("--output-file covers the full check; drop the crate name");
+        }
+        check_crate(&manifest, &name, json, refresh, history)?;
+        return Ok(true);
+    }
+
+    let workspace = workspace
+        || workspace_implied(
+            &manifest,
+            false,
+            &[
+                ("--format csv", format == OutputFormat::Csv),
+                ("--metrics-out", metrics_out.is_some()),
+                ("--output-file", !output_files.is_empty()),
+                ("--redundancy", redundancy),
+                ("--stats", stats),
+                ("--owners", owners),
+                ("--explain-skipped", explain_skipped),
+                ("--api-diff", api_diff),
+            ],
+        )?;
+    if workspace || package.is_some() {
+        if format == OutputFormat::Csv {
+            anyhow::bail!("--format csv covers a single package; drop --workspace/--package");
+        }
+        if metrics_out.is_some() {
+            anyhow::bail!("--metrics-out covers a single package; drop --workspace/--package");
+        }
+        if !output_files.is_empty() {
+            anyhow::bail!("--output-file covers a single package; drop --workspace/--package");
+        }
+        let progress = ProgressMode::detect(json).build(verbose);
+        let report = run_workspace_check(manifest, package.as_deref(), pre, progress)?;
+        if json {
+            output::print_json(&report)?;
+        } else {
+            print_workspace_report(&report, verbose, limit);
+            print_member_findings(&report);
+        }
+        return Ok(report.passed());
+    }
+
+    if json {
+        let (mut report, _) = run_check(
+            &manifest,
+            refresh,
+            pre,
+            ProgressMode::detect(json).build(verbose),
+        )?;
+        extend_check_report(
+            &manifest,
+            &mut report,
+            redundancy,
+            stats,
+            owners,
+            workflows,
+            true,
+        )?;
+        report.partial |= Cancellation::global().cut_short();
+        if let Some(path) = &metrics_out {
+            write_metrics(path, &Metrics::new(&manifest).with_check(&report), true)?;
+        }
+        write_check_files(&output_files, &report, true)?;
+        if format == OutputFormat::Csv {
+            output::write_csv(
+                &check_csv(&report, cache::unix_now()),
+                output_path.as_deref(),
+            )?;
+        } else {
+            output::print_json(&report)?;
+        }
+        return Ok(report.freshness.is_empty() && report.lock_mismatches.is_empty());
+    }
+
+    output::print_header("🧠 cargo-sane check");
+    println!();
+
+    if let Some(name) = manifest.package_name() {
+        output::print_info(&format!("Package: {}", name));
+    }
+    output::print_info(&format!("Manifest: {}", display_path(&manifest.path)));
+    println!();
+
+    // Check dependencies
+    let progress = ProgressMode::detect(false).build(verbose);
+    let (mut report, cache_age) = run_check(&manifest, refresh, pre, progress)?;
+    extend_check_report(
+        &manifest,
+        &mut report,
+        redundancy,
+        stats,
+        owners,
+        workflows,
+        false,
+    )?;
+    report.partial |= Cancellation::global().cut_short();
+    if let Some(path) = &metrics_out {
+        write_metrics(path, &Metrics::new(&manifest).with_check(&report), false)?;
+    }
+    write_check_files(&output_files, &report, false)?;
+    let dependencies = &report.dependencies;
+
+    if let Some(age) = cache_age {
+        output::print_info(&format!(
+            "Using cached results ({} old; --refresh to re-check)",
+            format_duration(age)
+        ));
+        println!();
+    }
+    if report.partial {
+        print_partial(report.unchecked, "dependency");
+    }
+
+    if !report.declaration_conflicts.is_empty() {
+        print_declaration_conflicts(&report.declaration_conflicts);
+        println!(
+            "{}",
+            "Run `cargo sane fix` to consolidate these declarations.".dimmed()
+        );
+        println!();
+    }
+
+    if dependencies.is_empty()
+        && report.git_dependencies.is_empty()
+        && report.path_dependencies.is_empty()
+        && report.internal.is_empty()
+    {
+        output::print_warning("No dependencies found in Cargo.toml");
+        return Ok(true);
+    }
+
+    // Categorize dependencies
+    let mut off_policy = Vec::new();
+    let mut up_to_date = Vec::new();
+    let mut patch_updates = Vec::new();
+    let mut minor_updates = Vec::new();
+    let mut major_updates = Vec::new();
+    let mut snoozed = Vec::new();
+    let mut unpatched = Vec::new();
+
+    for dep in dependencies {
+        if dep.is_off_policy() {
+            off_policy.push(dep);
+            continue;
+        }
+        if dep.is_unpatched() {
+            unpatched.push(dep);
+            continue;
+        }
+        if dep.snoozed {
+            snoozed.push(dep);
+            continue;
+        }
+        match dep.update_type() {
+            UpdateType::UpToDate => up_to_date.push(dep),
+            UpdateType::Patch => patch_updates.push(dep),
+            UpdateType::Minor => minor_updates.push(dep),
+            UpdateType::Major => major_updates.push(dep),
+        }
+    }
+    for section in [
+        &mut off_policy,
+        &mut up_to_date,
+        &mut patch_updates,
+        &mut minor_updates,
+        &mut major_updates,
+    ] {
+        rank(section, |dep| Significance::of_dependency(dep, false));
+    }
+
+    // Print summary
+    println!("{}", output::plain("📊 Update Summary:"));
+    println!(
+        "  {} Up to date: {}",
+        output::marker(Status::UpToDate),
+        format_count(up_to_date.len())
+    );
+    println!(
+        "  {} Patch updates available: {}",
+        output::marker(Status::Patch),
+        format_count(patch_updates.len())
+    );
+    println!(
+        "  {} Minor updates available: {}",
+        output::marker(Status::Minor),
+        format_count(minor_updates.len())
+    );
+    println!(
+        "  {} Major updates available: {}",
+        output::marker(Status::Major),
+        format_count(major_updates.len())
+    );
+    if !unpatched.is_empty() {
+        println!(
+            "  {} Vulnerable, no fixed release: {}",
+            output::plain("🚨"),
+            format_count(unpatched.len())
+        );
+    }
+    if !off_policy.is_empty() {
+        println!(
+            "  {} Off-policy: {}",
+            output::plain("📜").magenta(),
+            format_count(off_policy.len())
+        );
+    }
+    if !snoozed.is_empty() {
+        println!(
+            "  {} Snoozed: {}",
+            output::plain("💤"),
+            format_count(snoozed.len())
+        );
+    }
+    println!();
+
+    print_unpatched(&unpatched, limit);
+    print_off_policy(&off_policy, limit);
+
+    // Show patch updates
+    if !patch_updates.is_empty() {
+        println!("{}", output::heading(Status::Patch, "Patch updates:"));
+        let (shown, hidden) = truncate(&patch_updates, limit);
+        for dep in shown {
+            if let Some(latest) = &dep.latest_version {
+                println!(
+                    "  • {}{}{}{}{} {} → {}{}",
+                    dep.name.bold(),
+                    yanked_marker(dep),
+                    artifact_marker(dep),
+                    inherited_marker(dep),
+                    target_marker(dep),
+                    dep.current_version.to_string().dimmed(),
+                    latest.to_string().good(),
+                    policy_marker(dep)
+                );
+                print_license_change(dep, "    ");
+                print_deny_violation(dep, "    ");
+                if verbose {
+                    println!("    (patch update - likely safe)");
+                    print_size_change(dep, "    ");
+                }
+            }
+        }
+        print_more(hidden);
+        println!();
+    }
+
+    // Show minor updates
+    if !minor_updates.is_empty() {
+        println!("{}", output::heading(Status::Minor, "Minor updates:"));
+        let (shown, hidden) = truncate(&minor_updates, limit);
+        for dep in shown {
+            if let Some(latest) = &dep.latest_version {
+                println!(
+                    "  • {}{}{}{}{} {} → {}{}",
+                    dep.name.bold(),
+                    yanked_marker(dep),
+                    artifact_marker(dep),
+                    inherited_marker(dep),
+                    target_marker(dep),
+                    dep.current_version.to_string().dimmed(),
+                    latest.to_string().caution(),
+                    policy_marker(dep)
+                );
+                print_license_change(dep, "    ");
+                print_deny_violation(dep, "    ");
+                if verbose {
+                    println!("    (minor update - should be backwards compatible)");
+                    print_size_change(dep, "    ");
+                }
+            }
+        }
+        print_more(hidden);
+        println!();
+    }
+
+    // Show major updates
+    if !major_updates.is_empty() {
+        println!("{}", output::heading(Status::Major, "Major updates:"));
+        let (shown, hidden) = truncate(&major_updates, limit);
+        let diffs = if api_diff {
+            major_api_diffs(&manifest, shown)
+        } else {
+            Vec::new()
+        };
+        for (i, dep) in shown.iter().enumerate() {
+            if let Some(latest) = &dep.latest_version {
+                println!(
+                    "  • {}{}{}{}{} {} → {}{}",
+                    dep.name.bold(),
+                    yanked_marker(dep),
+                    artifact_marker(dep),
+                    inherited_marker(dep),
+                    target_marker(dep),
+                    dep.current_version.to_string().dimmed(),
+                    latest.to_string().bad(),
+                    policy_marker(dep)
+                );
+                print_license_change(dep, "    ");
+                print_deny_violation(dep, "    ");
+                if let Some(Some(diff)) = diffs.get(i) {
+                    print_api_diff(diff, "    ");
+                }
+                if verbose {
+                    println!("    (major update - may contain breaking changes)");
+                    print_size_change(dep, "    ");
+                }
+            }
+        }
+        print_more(hidden);
+        println!();
+    }
+
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    if let Some(teams) = Teams::load(root)? {
+        print_unknown_team_crates(&teams, &manifest);
+        print_updates_by_team(dependencies);
+    }
+    if verbose {
+        print_snoozed(&snoozed);
+    }
+    print_skipped_releases(dependencies, limit);
+    print_redundancies(&report.redundancies);
+    print_ownership_changes(&report.ownership_changes);
+    print_budget_violations(&report.freshness);
+    print_lock_mismatches(&report.lock_mismatches);
+    if workflows {
+        print_workflow_pins(&report.workflow_pins);
+    }
+    print_internal(&report.internal);
+    print_skipped_dependencies(&report.skipped, explain_skipped);
+    if let Some(stats) = &report.stats {
+        print_dependency_stats(stats);
+    }
+
+    // Show up to date if verbose
+    if verbose && !up_to_date.is_empty() {
+        println!("{}", output::heading(Status::UpToDate, "Up to date:"));
+        let (shown, hidden) = truncate(&up_to_date, limit);
+        for dep in shown {
+            println!(
+                "  • {}{}{}{}{} {}",
+                dep.name,
+                yanked_marker(dep),
+                artifact_marker(dep),
+                inherited_marker(dep),
+                target_marker(dep),
+                dep.current_version.to_string().good()
+            );
+        }
+        print_more(hidden);
+        println!();
+    }
+
+    if verbose {
+        print_feature_usage(&report.features);
+    }
+
+    if !report.git_dependencies.is_empty() {
+        println!(
+            "{}",
+            output::plain("🔗 Git dependencies (not version-checked):")
+                .cyan()
+                .bold()
+        );
+        for dep in &report.git_dependencies {
+            let locked = match (&dep.locked_version, &dep.locked_commit) {
+                (Some(version), Some(commit)) => {
+                    format!(
+                        " locked at {} ({})",
+                        version,
+                        &commit[..commit.len().min(8)]
+                    )
+                }
+                (Some(version), None) => format!(" locked at {}", version),
+                _ => String::new(),
+            };
+            println!(
+                "  • {} {} ({}){}",
+                dep.name.bold(),
+                dep.url.dimmed(),
+                dep.reference,
+                locked
+            );
+        }
+        println!();
+    }
+
+    print_stale_path_dependencies(&report.path_dependencies);
+    print_forks(&report.forks);
+
+    if patch_updates.is_empty()
+        && minor_updates.is_empty()
+        && major_updates.is_empty()
+        && off_policy.is_empty()
+    {
+        if report.partial {
+            output::print_info("No updates among the dependencies looked up");
+        } else {
+            output::print_success("All dependencies are up to date! 🎉");
+        }
+    } else {
+        println!(
+            "{}",
+            "Run `cargo sane update` to update dependencies interactively.".dimmed()
+        );
+    }
+
+    if !report.freshness.is_empty() {
+        output::print_error(&format!(
+            "Freshness budget exceeded: {}",
+            plural(report.freshness.len() as u64, "violation")
+        ));
+    }
+    if !report.lock_mismatches.is_empty() {
+        output::print_error(&format!(
+            "Cargo.lock doesn't satisfy {}; run `cargo sane fix` or the commands above",
+            plural(report.lock_mismatches.len() as u64, "requirement")
+        ));
+    }
+    Ok(report.freshness.is_empty() && report.lock_mismatches.is_empty())
+}
+
+/// Check every member of the workspace rooted at `manifest`, optionally
+/// scoped to the member called `package`
+pub(super) fn run_workspace_check(
+    manifest: Manifest,
+    package: Option<&str>,
+    pre: bool,
+    progress: Arc<dyn Progress>,
+) -> Result<WorkspaceReport> {
+    let root = manifest
+        .path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+    let manifest_path = manifest.path.clone();
+    let Some(workspace) = Workspace::load(manifest)? else {
+        anyhow::bail!(
+            "{} is not a workspace root (no [workspace] table)",
+            manifest_path.display()
+        );
+    };
+    if let Some(package) = package {
+        if workspace.member(package).is_none() {
+            anyhow::bail!("No workspace member named '{}'", package);
+        }
+    }
+
+    let config = Config::load(&root)?;
+    let mut checker = DependencyChecker::with_provider(
+        CratesIoClient::new().context("building the crates.io client")?,
+    )
+    .with_concurrency(config.concurrency)
+    .with_progress(progress)
+    .with_advisories(AdvisoryIndex::load(&database_path(&workspace.root)))
+    .with_policy(load_policy(&config, &root)?.unwrap_or_default())
+    .with_licenses(config.licenses.clone())
+    .with_deny(DenyPolicy::load(&root)?.unwrap_or_default())
+    .with_prereleases(pre)
+    .with_internal(internal_crates(&workspace.root, &config));
+    // Run once at the root, which covers every member; as with a single
+    // package, the check runs without it
+    if let Ok(metadata) =
+        cargo::metadata(&manifest_path, &CargoOptions::for_project(&config, &root)?)
+    {
+        checker = checker.with_metadata(metadata);
+    }
+    let mut report = runtime()?.block_on(checker.check_workspace(&workspace))?;
+    add_member_findings(&workspace, &mut report, &config);
+
+    Ok(match package {
+        Some(package) => report.scoped_to(package).unwrap_or(report),
+        None => report,
+    })
+}
+
+/// What a single package's check fails on, for each member: dependencies
+/// past the freshness budget, and requirements the workspace's Cargo.lock,
+/// read once for all of them, doesn't satisfy
+fn add_member_findings(workspace: &Workspace, report: &mut WorkspaceReport, config: &Config) {
+    let lockfile = Lockfile::for_manifest(&workspace.root).ok().flatten();
+    for member in &mut report.members {
+        member.freshness = budget_violations(&member.dependencies, &config.freshness);
+        let manifest = workspace.members.iter().find(|m| m.path == member.manifest);
+        if let (Some(manifest), Some(lockfile)) = (manifest, &lockfile) {
+            member.lock_mismatches = find_lock_mismatches(manifest, lockfile);
+        }
+    }
+}
+
+/// Each member's freshness budget violations and lock mismatches, then
+/// what they add up to
+fn print_member_findings(report: &WorkspaceReport) {
+    let mut violations = 0;
+    let mut mismatches = 0;
+    for member in &report.members {
+        if member.freshness.is_empty() && member.lock_mismatches.is_empty() {
+            continue;
+        }
+        println!("{}", output::plain(&format!("📦 {}", member.name)).bold());
+        print_budget_violations(&member.freshness);
+        print_lock_mismatches(&member.lock_mismatches);
+        violations += member.freshness.len();
+        mismatches += member.lock_mismatches.len();
+    }
+
+    if violations > 0 {
+        output::print_error(&format!(
+            "Freshness budget exceeded: {}",
+            plural(violations as u64, "violation")
+        ));
+    }
+    if mismatches > 0 {
+        output::print_error(&format!(
+            "Cargo.lock doesn't satisfy {}; run `cargo sane fix` or the commands above",
+            plural(mismatches as u64, "requirement")
+        ));
+    }
+}
+
+fn print_workspace_report(report: &WorkspaceReport, verbose: bool, limit: usize) {
+    output::print_header("🧠 cargo-sane check --workspace");
+    println!();
+    output::print_info(&format!("Workspace: {}", report.root.display()));
+    println!();
+
+    if report.members.is_empty() {
+        output::print_warning("No workspace members found");
+        return;
+    }
+
+    let width = report
+        .members
+        .iter()
+        .map(|m| m.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Member".len());
+    println!("{}", output::plain("📦 Workspace members:"));
+    println!(
+        "  {:<width$}  {:>5}  {:>5}  {:>5}  {:>5}",
+        "Member".bold(),
+        "Deps",
+        "Patch",
+        "Minor",
+        "Major",
+        width = width
+    );
+    for member in &report.members {
+        let s = member.summary;
+        println!(
+            "  {:<width$}  {:>5}  {:>5}  {:>5}  {:>5}",
+            member.name,
+            s.total,
+            s.patch.to_string().good(),
+            s.minor.to_string().caution(),
+            s.major.to_string().bad(),
+            width = width
+        );
+    }
+    println!();
+    print_internal(&report.internal);
+
+    let mut outdated = report.outdated();
+    if outdated.is_empty() {
+        output::print_success("All dependencies are up to date! 🎉");
+        return;
+    }
+    rank(&mut outdated, |krate| krate.significance());
+
+    println!("{}", output::plain("📋 Outdated crates:").bold());
+    let (shown, hidden) = truncate(&outdated, limit);
+    for krate in shown {
+        let Some(latest) = &krate.latest_version else {
+            continue;
+        };
+        let status = Status::of_update(krate.update_type());
+        let marker = output::label(status);
+        let latest = output::paint(latest.to_string().as_str(), status.tone());
+        let diverge = if krate.requirements_diverge() {
+            format!(" {}", "(requirements differ)".dimmed())
+        } else {
+            String::new()
+        };
+        println!("  {} {} → {}{}", marker, krate.name.bold(), latest, diverge);
+        for declaration in &krate.declared_by {
+            let requirement = declaration
+                .requirement
+                .clone()
+                .unwrap_or_else(|| declaration.current_version.to_string());
+            println!("      {}: {}", declaration.member, requirement.dimmed());
+        }
+    }
+    print_more(hidden);
+    println!();
+
+    if verbose {
+        println!(
+            "{}",
+            format!(
+                "{} distinct crates across {} members",
+                report.crates.len(),
+                report.members.len()
+            )
+            .dimmed()
+        );
+        println!();
+    }
+}
+
+/// Check a manifest, reusing a cached report when the project config enables
+/// caching and neither the dependency declarations, the advisory database nor
+/// Cargo.lock changed since it was made. Returns the report and, when it came
+/// from the cache, its age.
+pub(super) fn run_check(
+    manifest: &Manifest,
+    refresh: bool,
+    pre: bool,
+    progress: Arc<dyn Progress>,
+) -> Result<(CheckReport, Option<Duration>)> {
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let config = Config::load(root)?;
+    let policy = load_policy(&config, root)?;
+    let cache = ReportCache::for_manifest(manifest, config.cache_ttl_minutes);
+    let mut key = cache::check_key(manifest);
+    if let Some(policy) = &policy {
+        key = cache::fingerprint(&format!("{}\n{}", key, policy));
+    }
+    if pre {
+        key = cache::fingerprint(&format!("{}\npre", key));
+    }
+    if config.treat_internal_as_external {
+        key = cache::fingerprint(&format!("{}\nexternal", key));
+    }
+    if !config.ignore_crates.is_empty() {
+        key = cache::fingerprint(&format!("{}\n{}", key, config.ignore_crates.join(",")));
+    }
+    if !config.licenses.is_empty() {
+        key = cache::fingerprint(&format!("{}\n{}", key, config.licenses.key()));
+    }
+    if !config.intentional_forks.is_empty() {
+        key = cache::fingerprint(&format!(
+            "{}\nforks {}",
+            key,
+            config.intentional_forks.join(",")
+        ));
+    }
+    let deny = DenyPolicy::load(root)?.unwrap_or_default();
+    if !deny.is_empty() {
+        key = cache::fingerprint(&format!("{}\ndeny {}", key, deny.key()));
+    }
+
+    // Snoozes and teams apply after the cache, so changing them needs no
+    // fresh check
+    let snoozes = Snoozes::load(root)?;
+    let teams = Teams::load(root)?;
+    if !refresh {
+        if let Some((mut report, age)) = cache.load::<CheckReport>(&key) {
+            snoozes.apply(&mut report.dependencies, cache::unix_now());
+            if let Some(teams) = &teams {
+                teams.apply(&mut report.dependencies);
+            }
+            return Ok((report, Some(age)));
+        }
+    }
+
+    let mut checker = DependencyChecker::with_provider(
+        CratesIoClient::new().context("building the crates.io client")?,
+    )
+    .with_concurrency(config.concurrency)
+    .with_progress(progress)
+    .with_advisories(AdvisoryIndex::load(&database_path(manifest)))
+    .with_policy(policy.unwrap_or_default())
+    .with_licenses(config.licenses.clone())
+    .with_deny(deny)
+    .with_prereleases(pre)
+    .with_internal(internal_crates(manifest, &config))
+    .with_ignored(config.ignore_crates.clone())
+    .with_intentional_forks(config.intentional_forks.clone());
+    // Metadata only adds resolved feature sets, so the check runs without it
+    if let Ok(metadata) =
+        cargo::metadata(&manifest.path, &CargoOptions::for_project(&config, root)?)
+    {
+        checker = checker.with_metadata(metadata);
+    }
+    let mut report = runtime()?.block_on(checker.check(manifest))?;
+    // A run cut short by --timeout mustn't stand in for a full one
+    if !report.partial {
+        if let Err(e) = cache.store(&key, &report) {
+            output::eprint_warning(&format!("Could not write check cache: {}", e));
+        }
+    }
+    snoozes.apply(&mut report.dependencies, cache::unix_now());
+    if let Some(teams) = &teams {
+        teams.apply(&mut report.dependencies);
+    }
+
+    Ok((report, None))
+}
+
+/// Check a single dependency and print everything known about it
+fn check_crate(
+    manifest: &Manifest,
+    name: &str,
+    json: bool,
+    refresh: bool,
+    history: bool,
+) -> Result<()> {
+    // Before any lookup, so a typo fails right away
+    let declarations = declarations_of(manifest, name)?;
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let config = Config::load(root)?;
+
+    let published = if declarations
+        .iter()
+        .any(|d| d.source == DependencySource::Registry)
+    {
+        published_versions(root, name, config.cache_ttl_minutes, refresh)?
+    } else {
+        Vec::new()
+    };
+    let lockfile = Lockfile::for_manifest(manifest).unwrap_or_else(|e| {
+        output::print_warning(&format!("Ignoring Cargo.lock: {:#}", e));
+        None
+    });
+    let mut detail = crate_detail(
+        name,
+        declarations,
+        lockfile.as_ref(),
+        &published,
+        &AdvisoryIndex::load(&database_path(manifest)),
+    );
+    if history {
+        if let Some(origins) = load_history(manifest, json) {
+            detail.history = origins.into_iter().filter(|o| o.name == name).collect();
+        }
+    }
+
+    if json {
+        output::print_json(&detail)?;
+    } else {
+        print_crate_detail(&detail);
+    }
+    Ok(())
+}
+
+/// Every release of `name`, from the per-crate cache while it's fresh
+fn published_versions(
+    root: &Path,
+    name: &str,
+    ttl_minutes: u64,
+    refresh: bool,
+) -> Result<Vec<PublishedVersion>> {
+    let cache = ReportCache::new(
+        root.join(STATE_DIR)
+            .join("crates")
+            .join(format!("{}.json", name)),
+        ttl_minutes,
+    );
+    if !refresh {
+        if let Some((published, _)) = cache.load(name) {
+            return Ok(published);
+        }
+    }
+    let client = CratesIoClient::new().context("building the crates.io client")?;
+    let published = {
+        let _span = timings::span("registry");
+        runtime()?.block_on(client.get_published_versions(name))?
+    };
+    if let Err(e) = cache.store(name, &published) {
+        output::eprint_warning(&format!("Could not write check cache: {}", e));
+    }
+    Ok(published)
+}
+
+fn print_crate_detail(detail: &CrateDetail) {
+    let now = cache::unix_now();
+    let version = |version: &Option<semver::Version>| match version {
+        Some(version) => version.to_string(),
+        None => "unknown".dimmed().to_string(),
+    };
+    let released = |at: Option<u64>| match at {
+        Some(at) => format!(
+            " {}",
+            format!("released {}", format_since(at, now)).dimmed()
+        ),
+        None => String::new(),
+    };
+
+    println!("{}", output::plain(&format!("📦 {}", detail.name)).bold());
+    for declaration in &detail.declarations {
+        let requirement = match (&declaration.requirement, declaration.source) {
+            (Some(requirement), _) => format!("\"{}\"", requirement),
+            (None, DependencySource::Git) => "git".to_string(),
+            (None, DependencySource::Path) => "path".to_string(),
+            (None, DependencySource::Registry) => "*".to_string(),
+        };
+        let added = match detail
+            .history
+            .iter()
+            .find(|o| o.section == declaration.section)
+        {
+            Some(origin) => format!(" {}", origin_note(origin).dimmed()),
+            None => String::new(),
+        };
+        println!(
+            "  {:<20} {}{}",
+            format!("[{}]", declaration.section),
+            requirement,
+            added
+        );
+    }
+    println!();
+
+    let mut locked = version(&detail.locked);
+    if detail.yanked {
+        locked = format!("{} {}", locked, "(yanked)".bad().bold());
+    }
+    println!(
+        "  {:<20} {}{}",
+        "Locked:",
+        locked,
+        released(detail.locked_released_at)
+    );
+    if detail
+        .declarations
+        .iter()
+        .all(|d| d.source != DependencySource::Registry)
+    {
+        println!();
+        output::print_info("Not a registry dependency; there are no releases to compare.");
+        return;
+    }
+    let compatible = match (&detail.latest_compatible, &detail.locked) {
+        (Some(target), Some(locked)) if target > locked => target.to_string().caution().to_string(),
+        (target, _) => version(target),
+    };
+    println!("  {:<20} {}", "Latest compatible:", compatible);
+    let latest = match (&detail.latest, &detail.latest_compatible) {
+        (Some(latest), Some(compatible)) if latest > compatible => {
+            latest.to_string().bad().to_string()
+        }
+        (latest, _) => version(latest),
+    };
+    println!(
+        "  {:<20} {}{}",
+        "Latest:",
+        latest,
+        released(detail.latest_released_at)
+    );
+    if detail.releases_behind > 0 {
+        let mut behind = plural(detail.releases_behind as u64, "release");
+        if let Some(gap) = detail.release_gap() {
+            behind = format!(
+                "{}, {} of releases",
+                behind,
+                format_duration(Duration::from_secs(gap))
+            );
+        }
+        println!("  {:<20} {}", "Behind:", behind);
+    }
+    if !detail.advisories.is_empty() {
+        println!(
+            "  {:<20} {}",
+            "Advisories:",
+            detail.advisories.join(", ").bad().bold()
+        );
+    }
+    println!();
+
+    if detail.commands.is_empty() {
+        output::print_success("Up to date.");
+    } else {
+        println!("{}", "To update:".bold());
+        for command in &detail.commands {
+            println!("  {}", command.cyan());
+        }
+    }
+}
+
+/// The dependency history for `--history`, or `None` with a warning when
+/// git can't give one
+pub(super) fn load_history(manifest: &Manifest, json: bool) -> Option<Vec<DependencyOrigin>> {
+    let _span = timings::span("git history");
+    match dependency_history(manifest) {
+        Ok(origins) => Some(origins),
+        Err(e) => {
+            if !json {
+                output::print_warning(&format!("No dependency history: {:#}", e));
+            }
+            None
+        }
+    }
+}
+
+/// e.g. "added 2021-03 by @alice (1a2b3c4)"
+pub(super) fn origin_note(origin: &DependencyOrigin) -> String {
+    format!("{} ({})", origin.summary(), &origin.commit[..7])
+}
+
+/// Age and lag statistics over `dependencies`, with maintainer groups when
+/// `owners` asks for them
+pub(super) fn collect_stats(
+    dependencies: &[Dependency],
+    owners: Option<&Owners>,
+) -> DependencyStats {
+    let mut stats = dependency_stats(dependencies, cache::unix_now());
+    if let Some(owners) = owners {
+        let lists: Vec<Vec<String>> = owners.values().cloned().collect();
+        stats.maintainer_groups = Some(maintainer_groups(&lists));
+    }
+    stats
+}
+
+/// The optional parts of a check: overlapping crates, statistics, and owner
+/// lookups with the ownership changes they reveal
+fn extend_check_report(
+    manifest: &Manifest,
+    report: &mut CheckReport,
+    redundancy: bool,
+    stats: bool,
+    owners: bool,
+    workflows: bool,
+    quiet: bool,
+) -> Result<()> {
+    if redundancy {
+        report.redundancies = redundancies(report)?;
+    }
+    let owners = if owners {
+        let names = report.dependencies.iter().map(|d| d.name.clone()).collect();
+        let owners = fetch_owners(manifest, names, quiet)?;
+        report.ownership_changes =
+            ownership_changes(&snapshot_owners(manifest)?, &owners, &report.dependencies);
+        Some(owners)
+    } else {
+        None
+    };
+    if stats {
+        report.stats = Some(collect_stats(&report.dependencies, owners.as_ref()));
+    }
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    report.freshness = budget_violations(&report.dependencies, &Config::load(root)?.freshness);
+    report.lock_mismatches = lock_mismatches(manifest);
+    if workflows {
+        report.workflow_pins = workflow_pins(manifest, quiet)?;
+    }
+    Ok(())
+}
+
+/// Write the OpenMetrics text for `--metrics-out`
+/// Write the check report to each `--output-file`
+fn write_check_files(files: &[OutputFile], report: &CheckReport, quiet: bool) -> Result<()> {
+    output::write_output_files(files, quiet, |format| match format {
+        FileFormat::Json => output::render_json(report),
+        FileFormat::Csv => Ok(check_csv(report, cache::unix_now())),
+        FileFormat::Markdown => Ok(check_markdown(report)),
+    })
+}
+
+/// `cargo install --version` pins in the project's CI workflows, with the
+/// newest release of each crate
+fn workflow_pins(manifest: &Manifest, quiet: bool) -> Result<Vec<WorkflowPin>> {
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let mut pins = find_workflow_pins(&root.canonicalize().unwrap_or(root.to_path_buf()));
+    if pins.is_empty() {
+        return Ok(pins);
+    }
+    let concurrency = Config::load(root)?.settings().concurrency;
+    let client = CratesIoClient::new().context("building the crates.io client")?;
+    runtime()?.block_on(check_pins(&client, &mut pins, concurrency));
+    let unknown = pins.iter().filter(|pin| pin.latest.is_none()).count();
+    if unknown > 0 && !quiet {
+        output::print_warning(&format!(
+            "Latest versions of {} could not be fetched",
+            plural(unknown as u64, "workflow tool")
+        ));
+    }
+    Ok(pins)
+}
+
+fn print_workflow_pins(pins: &[WorkflowPin]) {
+    println!("{}", output::plain("🧰 Workflow tool pins:").bold());
+    if pins.is_empty() {
+        println!(
+            "  {}",
+            "No `cargo install <crate> --version` commands in .github/workflows".dimmed()
+        );
+        println!();
+        return;
+    }
+
+    let outdated: Vec<&WorkflowPin> = pins.iter().filter(|pin| pin.is_outdated()).collect();
+    for pin in &outdated {
+        let latest = pin
+            .latest
+            .as_ref()
+            .map(Version::to_string)
+            .unwrap_or_default();
+        println!(
+            "  • {} {} → {} {}",
+            pin.name.bold(),
+            pin.requirement.dimmed(),
+            latest.caution(),
+            format!("({}:{})", pin.file.display(), pin.line).dimmed()
+        );
+    }
+    let current = pins.len() - outdated.len();
+    if current > 0 {
+        let noun = if outdated.is_empty() {
+            "pin"
+        } else {
+            "other pin"
+        };
+        println!(
+            "  {}",
+            format!("{} up to date", plural(current as u64, noun)).dimmed()
+        );
+    }
+    println!();
+}
+
+/// Requirements of `manifest` its Cargo.lock no longer satisfies; none when
+/// there's no readable Cargo.lock
+pub(super) fn lock_mismatches(manifest: &Manifest) -> Vec<LockMismatch> {
+    match Lockfile::for_manifest(manifest) {
+        Ok(Some(lockfile)) => find_lock_mismatches(manifest, &lockfile),
+        _ => Vec::new(),
+    }
+}
+
+pub(super) fn print_lock_mismatches(mismatches: &[LockMismatch]) {
+    if mismatches.is_empty() {
+        return;
+    }
+
+    println!(
+        "{}",
+        output::plain("🔒 Cargo.lock out of date:").bad().bold()
+    );
+    for mismatch in mismatches {
+        let locked: Vec<String> = mismatch.locked.iter().map(Version::to_string).collect();
+        let line = mismatch
+            .line
+            .map(|l| format!(" (line {})", l))
+            .unwrap_or_default();
+        println!(
+            "  • {} \"{}\" [{}]{}, but Cargo.lock has {}",
+            mismatch.name.bold(),
+            mismatch.requirement,
+            mismatch.table(),
+            line.dimmed(),
+            locked.join(", ").bad()
+        );
+        println!("    fix: {}", mismatch.command.cyan());
+    }
+    println!();
+}
+
+/// Owner lists of `names` from crates.io, cached for a day
+pub(super) fn fetch_owners(
+    manifest: &Manifest,
+    mut names: Vec<String>,
+    quiet: bool,
+) -> Result<Owners> {
+    names.sort();
+    names.dedup();
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let concurrency = Config::load(root)?.settings().concurrency;
+    let client = CratesIoClient::new().context("building the crates.io client")?;
+    let owners = runtime()?.block_on(crate_owners(&client, &names, root, concurrency))?;
+    if owners.len() < names.len() && !quiet {
+        output::print_warning(&format!(
+            "Owners of {} could not be fetched and are left out",
+            plural((names.len() - owners.len()) as u64, "crate")
+        ));
+    }
+    Ok(owners)
+}
+
+/// Owner lists from the newest snapshot that recorded them
+pub(super) fn snapshot_owners(manifest: &Manifest) -> Result<Owners> {
+    let store = SnapshotStore::for_manifest(manifest);
+    for entry in store.list()?.iter().rev() {
+        match store.load(entry) {
+            Ok(snapshot) if !snapshot.owners.is_empty() => return Ok(snapshot.owners),
+            _ => continue,
+        }
+    }
+    Ok(Owners::new())
+}
+
+/// Dependencies without an update check and why, in full with
+/// `--explain-skipped`. Otherwise only the ones that point at a problem are
+/// counted, since git, path and internal crates are listed elsewhere.
+fn print_skipped_dependencies(skipped: &[SkippedDependency], explain: bool) {
+    if !explain {
+        let problems = skipped
+            .iter()
+            .filter(|s| matches!(s.reason, SkipCause::ParseFailure | SkipCause::RegistryError))
+            .count();
+        if problems > 0 {
+            let noun = if problems == 1 {
+                "dependency"
+            } else {
+                "dependencies"
+            };
+            output::print_warning(&format!(
+                "{} {} not checked; --explain-skipped says why",
+                problems, noun
+            ));
+            println!();
+        }
+        return;
+    }
+    if skipped.is_empty() {
+        return;
+    }
+
+    println!("{}", output::plain("🔍 Skipped dependencies:").bold());
+    for skip in skipped {
+        let detail = skip
+            .detail
+            .as_ref()
+            .map(|d| format!(": {}", d))
+            .unwrap_or_default();
+        println!(
+            "  • {} [{}] {}{}",
+            skip.name.bold(),
+            skip.reason.code().cyan(),
+            skip.reason.describe(),
+            detail
+        );
+        println!("    decided by: {}", skip.reason.decided_by().dimmed());
+    }
+    println!();
+}
+
+/// The project's own crates, which were set aside rather than looked up
+fn print_internal(names: &[String]) {
+    if names.is_empty() {
+        return;
+    }
+    output::print_info(&format!("Internal, not checked: {}", names.join(", ")));
+    println!();
+}
+
+/// Dependencies behind their category's budget, with the category so the
+/// policy behind each one is visible
+pub(super) fn print_budget_violations(violations: &[BudgetViolation]) {
+    if violations.is_empty() {
+        return;
+    }
+
+    println!(
+        "{}",
+        output::plain("⏱️  Freshness budget violations:")
+            .bad()
+            .bold()
+    );
+    for violation in violations {
+        println!(
+            "  • {} [{}] {} → {}",
+            violation.name.bold(),
+            violation.category.cyan(),
+            violation.current.to_string().dimmed(),
+            violation.latest
+        );
+        println!("    {}", violation.describe());
+    }
+    println!();
+}
+
+/// List the updates a snooze hides, dimmed
+fn print_snoozed(snoozed: &[&Dependency]) {
+    if snoozed.is_empty() {
+        return;
+    }
+    println!("{}", output::plain("💤 Snoozed updates:").bold());
+    for dep in snoozed {
+        if let Some(latest) = &dep.latest_version {
+            println!(
+                "  • {}",
+                format!("{} {} → {}", dep.name, dep.current_version, latest).dimmed()
+            );
+        }
+    }
+    println!();
+}
+
+pub(super) fn print_ownership_changes(changes: &[OwnershipChange]) {
+    if changes.is_empty() {
+        return;
+    }
+
+    println!(
+        "{}",
+        output::plain("👥 Ownership changes (informational):").bold()
+    );
+    for change in changes {
+        let mut parts = Vec::new();
+        if !change.added.is_empty() {
+            parts.push(format!("owners added: {}", change.added.join(", ")));
+        }
+        if !change.removed.is_empty() {
+            parts.push(format!("owners removed: {}", change.removed.join(", ")));
+        }
+        if let Some(publisher) = &change.unfamiliar_publisher {
+            parts.push(format!(
+                "{} was published by {}, who isn't a known owner",
+                publisher.version, publisher.login
+            ));
+        }
+        println!("  • {}: {}", change.name.bold(), parts.join("; "));
+        println!("    {}", change.url.dimmed());
+    }
+    println!(
+        "  {}",
+        "Usually a routine handover; worth a look before updating.".dimmed()
+    );
+    println!();
+}
+
+pub(super) fn print_dependency_stats(stats: &DependencyStats) {
+    const BAR_WIDTH: usize = 20;
+
+    println!("{}", output::plain("📈 Dependency age:").bold());
+    if let (Some(median), Some(mean)) = (stats.median_age_days, stats.mean_age_days) {
+        let days = |d: u64| format_duration(Duration::from_secs(d * 86_400));
+        println!(
+            "  Median {}, mean {} ({} of {} dated)",
+            days(median),
+            days(mean),
+            stats.dated,
+            stats.total
+        );
+        let widest = stats
+            .histogram
+            .buckets()
+            .iter()
+            .map(|(_, count)| *count)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        for (label, count) in stats.histogram.buckets() {
+            let width = (count * BAR_WIDTH).div_ceil(widest);
+            println!("  {:<12} {:<20} {}", label, "█".repeat(width), count);
+        }
+    } else {
+        println!("  {}", "no release dates available".dimmed());
+    }
+    println!(
+        "  More than one major version behind: {}",
+        format_count(stats.majors_behind)
+    );
+    if let Some(groups) = stats.maintainer_groups {
+        println!("  Distinct maintainer groups: {}", groups);
+    }
+    println!();
+}
+
+/// Groups of direct dependencies with overlapping functionality
+fn redundancies(report: &CheckReport) -> Result<Vec<Redundancy>> {
+    let names = report
+        .dependencies
+        .iter()
+        .map(|d| d.name.as_str())
+        .chain(report.git_dependencies.iter().map(|d| d.name.as_str()));
+    Ok(find_redundancies(&redundancy_groups()?, names))
+}
+
+fn print_redundancies(redundancies: &[Redundancy]) {
+    if redundancies.is_empty() {
+        return;
+    }
+
+    println!("{}", output::plain("🔁 Overlapping dependencies:").bold());
+    for redundancy in redundancies {
+        println!(
+            "  • {}: {}",
+            redundancy.group.bold(),
+            redundancy.crates.join(", ")
+        );
+        println!("    {}", redundancy.note.dimmed());
+    }
+    println!();
+}
+
+/// Newer releases passed over as update targets, and why
+fn print_skipped_releases(dependencies: &[Dependency], limit: usize) {
+    let mut skipping: Vec<&Dependency> = dependencies
+        .iter()
+        .filter(|dep| !dep.skipped_versions.is_empty())
+        .collect();
+    if skipping.is_empty() {
+        return;
+    }
+    rank(&mut skipping, |dep| Significance::of_dependency(dep, false));
+
+    println!("{}", output::plain("⏭️  Skipped releases:").bold());
+    let (shown, hidden) = truncate(&skipping, limit);
+    for dep in shown {
+        let note = dep.skip_note().unwrap_or_default();
+        println!("  • {} {}", dep.name.bold(), note.dimmed());
+    }
+    print_more(hidden);
+    println!();
+}
+
+/// Path dependencies whose published release is newer than the local copy
+fn print_stale_path_dependencies(path_dependencies: &[PathDependency]) {
+    let stale: Vec<&PathDependency> = path_dependencies.iter().filter(|d| d.is_stale()).collect();
+    if stale.is_empty() {
+        return;
+    }
+
+    println!(
+        "{}",
+        output::plain("📁 Path dependencies behind crates.io:")
+            .cyan()
+            .bold()
+    );
+    for dep in stale {
+        println!(
+            "  • {} {} local {}, published {} — local checkout may be stale",
+            dep.name.bold(),
+            dep.path.display().to_string().dimmed(),
+            dep.local_version,
+            dep.published_version.to_string().cyan()
+        );
+    }
+    println!();
+}
+
+pub(super) fn print_forks(forks: &[ForkedDependency]) {
+    if forks.is_empty() {
+        return;
+    }
+
+    println!(
+        "{}",
+        output::plain("🍴 Git forks behind crates.io (a guess from the package name):")
+            .cyan()
+            .bold()
+    );
+    for fork in forks {
+        let summary = fork.summary();
+        println!(
+            "  • {} {} {}",
+            fork.name.bold(),
+            fork.url.dimmed(),
+            if fork.fixed_upstream.is_empty() {
+                summary.normal()
+            } else {
+                summary.caution()
+            }
+        );
+    }
+    println!(
+        "  {}",
+        "List deliberate forks under `intentional_forks` in .cargo-sane.toml to silence this."
+            .dimmed()
+    );
+    println!();
+}
+
+/// Dependencies outside the blessed requirement of the versions file, with
+/// the blessed version to move to or why there is none
+/// Dependencies an advisory affects with no clean release to move to
+fn print_unpatched(unpatched: &[&Dependency], limit: usize) {
+    if unpatched.is_empty() {
+        return;
+    }
+
+    println!(
+        "{}",
+        output::plain("🚨 Vulnerable, no fixed release:")
+            .bad()
+            .bold()
+    );
+    let (shown, hidden) = truncate(unpatched, limit);
+    for dep in shown {
+        println!(
+            "  • {}{}{} {} {}",
+            dep.name.bold(),
+            yanked_marker(dep),
+            target_marker(dep),
+            dep.current_version.to_string().bad(),
+            format!("({})", dep.unpatched_advisories.join(", ")).dimmed()
+        );
+    }
+    print_more(hidden);
+    println!();
+}
+
+fn print_off_policy(off_policy: &[&Dependency], limit: usize) {
+    if off_policy.is_empty() {
+        return;
+    }
+
+    println!("{}", output::plain("📜 Off-policy:").magenta().bold());
+    let (shown, hidden) = truncate(off_policy, limit);
+    for dep in shown {
+        let Some(policy) = &dep.policy else {
+            continue;
+        };
+        let requirement = dep
+            .requirement
+            .clone()
+            .unwrap_or_else(|| dep.current_version.to_string());
+        match (&policy.status, &dep.latest_version) {
+            (PolicyStatus::Conflict(reason), _) => {
+                println!(
+                    "  • {} {} {} {} conflicts with \"{}\": {}",
+                    dep.name.bold(),
+                    dep.current_version.to_string().dimmed(),
+                    output::plain("✗").bad(),
+                    policy.blessed(),
+                    requirement,
+                    reason
+                );
+            }
+            (_, Some(latest)) => println!(
+                "  • {}{}{}{}{} {} → {}{}",
+                dep.name.bold(),
+                yanked_marker(dep),
+                artifact_marker(dep),
+                inherited_marker(dep),
+                target_marker(dep),
+                dep.current_version.to_string().dimmed(),
+                latest.to_string().magenta(),
+                policy_marker(dep)
+            ),
+            (_, None) => continue,
+        }
+    }
+    print_more(hidden);
+    println!(
+        "{}",
+        "Conflicts are left for you to resolve: change the requirement or ask for the policy to be updated."
+            .dimmed()
+    );
+    println!();
+}
+
+/// Warn that the update target is published under a new license
+/// e.g. "📦 2.1 MB → 4.7 MB", when the registry records both sizes
+fn print_size_change(dep: &Dependency, indent: &str) {
+    let Some((from, to)) = dep.size_change() else {
+        return;
+    };
+    let note = format!("📦 {} → {}", format_bytes(from), format_bytes(to));
+    println!("{}{}", indent, output::plain(&note).dimmed());
+}
+
+/// Flag a dependency whose current version was yanked
+pub(super) fn yanked_marker(dep: &Dependency) -> String {
+    if dep.yanked {
+        format!(" {}", "(yanked)".bad())
+    } else {
+        String::new()
+    }
+}
+
+/// Name what an artifact dependency builds, since it's no ordinary library
+fn artifact_marker(dep: &Dependency) -> String {
+    if dep.artifact.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " {}",
+            format!("(artifact: {})", dep.artifact.join(", ")).dimmed()
+        )
+    }
+}
+
+/// Features breakdown for `check --verbose`, limited to declarations that
+/// request features, disable defaults or resolve to more than they asked for
+fn print_feature_usage(features: &[FeatureUsage]) {
+    let notable: Vec<&FeatureUsage> = features.iter().filter(|f| f.is_notable()).collect();
+    if notable.is_empty() {
+        return;
+    }
+
+    println!("{}", output::plain("🧩 Features enabled:").blue().bold());
+    for usage in notable {
+        let requested = if usage.requested.is_empty() {
+            "no explicit features".dimmed().to_string()
+        } else {
+            usage.requested.join(", ")
+        };
+        let defaults = if usage.default_features {
+            String::new()
+        } else {
+            format!(" {}", "(default features disabled)".caution())
+        };
+        println!(
+            "  • {} [{}]: {}{}",
+            usage.name.bold(),
+            usage.section,
+            requested,
+            defaults
+        );
+
+        if let Some(resolved) = &usage.resolved {
+            let unrequested = usage.unrequested();
+            let resolved: Vec<String> = resolved
+                .iter()
+                .map(|f| {
+                    if unrequested.contains(&f.as_str()) {
+                        format!("+{}", f).caution().to_string()
+                    } else {
+                        f.clone()
+                    }
+                })
+                .collect();
+            println!("    resolved: {}", resolved.join(", "));
+        }
+    }
+    println!();
+}
+
+/// The open updates again, under the team owning each crate
+fn print_updates_by_team(dependencies: &[Dependency]) {
+    let mut seen = BTreeSet::new();
+    let updates: Vec<&Dependency> = dependencies
+        .iter()
+        .filter(|d| d.has_update() && !d.snoozed && seen.insert(d.name.as_str()))
+        .collect();
+    if !updates.is_empty() {
+        println!("{}", output::plain("👥 Updates by team:").bold());
+        for (team, deps) in group_by_team(&updates, |d| d.owner.as_deref()) {
+            println!("  {} ({})", team.bold(), deps.len());
+            for dep in deps {
+                let status = Status::of_update(dep.update_type());
+                let latest = dep.latest_version.as_ref().unwrap().to_string();
+                println!(
+                    "    {} {} {} → {}",
+                    output::label(status),
+                    dep.name,
+                    dep.current_version.to_string().dimmed(),
+                    output::paint(latest.as_str(), status.tone())
+                );
+            }
+        }
+        println!();
+    }
+    print_unassigned_nudge(dependencies);
+}
+
+/// How many dependencies no team owns yet, as a nudge to map them
+pub(super) fn print_unassigned_nudge(dependencies: &[Dependency]) {
+    let unassigned = unassigned_count(dependencies);
+    if unassigned == 0 {
+        return;
+    }
+    let total = dependencies
+        .iter()
+        .map(|d| d.name.as_str())
+        .collect::<BTreeSet<&str>>()
+        .len();
+    println!(
+        "{}",
+        format!(
+            "{} of {} dependencies have no owning team; assign them in {}",
+            unassigned,
+            total,
+            Teams::path(Path::new("")).display()
+        )
+        .dimmed()
+    );
+    println!();
+}
@@ -0,0 +1,391 @@
+//! `cargo sane clean`: unused dependencies
+
+use crate::analyzer::cfg_usage::{find_conditionally_used, ConditionalUsage, Gating};
+use crate::analyzer::std_replacements::{find_std_replacements, std_equivalents, StdReplacement};
+use crate::analyzer::usage::{
+    find_unused_dependencies, member_files, workspace_usage, CleanReport, WorkspaceUsage,
+};
+use crate::analyzer::usage_cache::{ScanStats, UsageCache};
+use crate::cli::commands::{find_manifest, record_audit, workspace_implied};
+use crate::cli::output::{self, Paint};
+use crate::cli::prompt;
+use crate::core::config::Config;
+use crate::core::manifest::{DependencySection, DependencySpec, Manifest};
+use crate::core::workspace::Workspace;
+use crate::updater::DependencyUpdater;
+use crate::utils::audit::{AuditChange, AuditEntry};
+use crate::utils::files::{collect_rust_files, WalkOptions};
+use crate::utils::formatting::display_path;
+use crate::utils::progress::ProgressMode;
+use crate::Result;
+use colored::Colorize;
+use rayon::prelude::*;
+use semver::Version;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::time::Instant;
+
+/// The audit record of removing `name` from `section`, with the requirement
+/// it had
+fn removal_change(
+    declarations: &[(DependencySection, String, DependencySpec)],
+    section: &DependencySection,
+    name: &str,
+) -> AuditChange {
+    let old = declarations
+        .iter()
+        .find(|(s, n, _)| s == section && n == name)
+        .and_then(|(_, _, spec)| spec.version())
+        .map(str::to_string);
+    AuditChange {
+        name: name.to_string(),
+        old,
+        new: None,
+        section: section.to_string(),
+    }
+}
+
+pub fn clean_command(
+    manifest_path: Option<String>,
+    dry_run: bool,
+    workspace: bool,
+    no_cache: bool,
+    json: bool,
+) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let scans = UsageCache::for_manifest(&manifest, !no_cache);
+    if workspace_implied(&manifest, workspace, &[])? {
+        return clean_workspace(manifest, &scans, dry_run, json);
+    }
+
+    let root = manifest
+        .path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let config = Config::load(&root)?;
+    let files = collect_rust_files(&root, &WalkOptions::from_config(&config))?;
+    let unused = find_unused_dependencies(&manifest, &files, &scans)?;
+    let conditionally_used = find_conditionally_used(&manifest, &files, &scans)?;
+    // A cache that can't be written only costs the next run a full scan
+    let _ = scans.save();
+    let std_replacements: Vec<StdReplacement> =
+        find_std_replacements(&std_equivalents()?, &manifest)
+            .into_iter()
+            .filter(|r| !unused.iter().any(|u| u.name == r.name))
+            .collect();
+
+    if json {
+        output::print_json(&CleanReport {
+            unused,
+            std_replacements,
+            conditionally_used,
+        })?;
+        return Ok(());
+    }
+
+    output::print_header("🧠 cargo-sane clean");
+    println!();
+    output::print_info(&format!("Manifest: {}", display_path(&manifest.path)));
+    output::print_info(&scanned_files(files.len(), scans.stats()));
+    println!();
+
+    print_std_replacements(&std_replacements, manifest.rust_version().as_ref());
+    print_conditionally_used(&conditionally_used);
+
+    if unused.is_empty() {
+        output::print_success("No unused dependencies found! 🎉");
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        output::plain("🧹 Unused dependencies:").caution().bold()
+    );
+    for dep in &unused {
+        let line = dep
+            .line
+            .map(|l| format!(" (line {})", l))
+            .unwrap_or_default();
+        println!("  • {} [{}]{}", dep.name.bold(), dep.section, line.dimmed());
+    }
+    println!();
+
+    if dry_run {
+        output::print_info("Dry-run mode: No changes will be made.");
+        return Ok(());
+    }
+
+    if !prompt::confirm(
+        &format!("Remove {} unused dependencies?", unused.len()),
+        false,
+    )? {
+        output::print_info("Clean cancelled.");
+        return Ok(());
+    }
+
+    let manifest_path = manifest.path.clone();
+    let declarations = manifest.declarations();
+    let mut updater = DependencyUpdater::new(manifest)?;
+    let mut changes = Vec::new();
+    for dep in &unused {
+        match updater.remove_declaration(&dep.section, &dep.name) {
+            Ok(_) => {
+                println!("  ✓ Removed {}", dep.name.good());
+                changes.push(removal_change(&declarations, &dep.section, &dep.name));
+            }
+            Err(e) => eprintln!("  ✗ Failed to remove {}: {}", dep.name.bad(), e),
+        }
+    }
+
+    let backup = updater.save()?;
+    record_audit(
+        &manifest_path,
+        AuditEntry::new("clean", &manifest_path)
+            .with_changes(changes)
+            .with_backup(Some(backup.clone())),
+    );
+    println!();
+    output::print_success("Cargo.toml updated successfully!");
+    output::print_info(&format!("Backup saved as {}", display_path(&backup)));
+
+    Ok(())
+}
+
+/// "Scanned 120 source files (115 unchanged since the last run)"
+fn scanned_files(files: usize, stats: ScanStats) -> String {
+    let mut line = format!("Scanned {} source files", files);
+    if stats.reused > 0 {
+        line.push_str(&format!(" ({} unchanged since the last run)", stats.reused));
+    }
+    line
+}
+
+/// Dependencies std could replace; advice, never part of the removal
+fn print_std_replacements(replacements: &[StdReplacement], msrv: Option<&Version>) {
+    if replacements.is_empty() {
+        return;
+    }
+    let heading = match msrv {
+        Some(msrv) => format!("📦 Replaceable by std at rust-version {}:", msrv),
+        None => "📦 Replaceable by std (no rust-version set):".to_string(),
+    };
+    println!("{}", output::plain(&heading).cyan().bold());
+    for replacement in replacements {
+        println!(
+            "  • {} → {} {}",
+            replacement.name.bold(),
+            replacement.std,
+            format!("(Rust {}+)", replacement.since).dimmed()
+        );
+        println!("      {}", replacement.note.dimmed());
+    }
+    println!(
+        "{}",
+        "These are suggestions; migrate the code before dropping the dependency.".dimmed()
+    );
+    println!();
+}
+
+/// Dependencies only referenced behind `#[cfg]`s the default build leaves
+/// false; advice, never part of the removal
+fn print_conditionally_used(conditional: &[ConditionalUsage]) {
+    if conditional.is_empty() {
+        return;
+    }
+    println!("{}", output::plain("🔀 Conditionally used:").cyan().bold());
+    for dep in conditional {
+        let line = dep
+            .line
+            .map(|l| format!(" (line {})", l))
+            .unwrap_or_default();
+        let gating = match dep.gating {
+            Gating::Unreachable => "no feature enables any of these",
+            Gating::NonDefault => "off with the default features",
+        };
+        println!(
+            "  • {} [{}]{} — {}",
+            dep.name.bold(),
+            dep.section,
+            line.dimmed(),
+            gating
+        );
+        for condition in &dep.conditions {
+            println!("      cfg({})", condition.dimmed());
+        }
+    }
+    println!(
+        "{}",
+        "Only referenced behind these conditions; check the features before dropping them."
+            .dimmed()
+    );
+    println!();
+}
+
+/// `clean --workspace`: judge each declaration against its own member's code
+/// and aggregate per crate, so one recommendation covers every member
+fn clean_workspace(
+    manifest: Manifest,
+    scans: &UsageCache,
+    dry_run: bool,
+    json: bool,
+) -> Result<()> {
+    let root = manifest
+        .path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+    let manifest_path = manifest.path.clone();
+    let Some(workspace) = Workspace::load(manifest)? else {
+        anyhow::bail!(
+            "{} is not a workspace root (no [workspace] table)",
+            manifest_path.display()
+        );
+    };
+    let config = Config::load(&root)?;
+    let options = WalkOptions::from_config(&config);
+
+    let dirs: Vec<&Path> = workspace
+        .members
+        .iter()
+        .map(|m| m.path.parent().unwrap_or(Path::new(".")))
+        .collect();
+    let progress = ProgressMode::detect(json).build(false);
+    progress.start(workspace.members.len() as u64, "Scanning members");
+    let members = workspace
+        .members
+        .par_iter()
+        .zip(dirs.par_iter())
+        .map(|(member, dir)| {
+            let started = Instant::now();
+            let files = member_files(dir, &dirs, &collect_rust_files(dir, &options)?);
+            let name = Workspace::member_name(member);
+            progress.item_done(&name, started.elapsed());
+            Ok((name, member, files))
+        })
+        .collect::<Result<Vec<_>>>();
+    progress.finish();
+    let members = members?;
+    let usage = workspace_usage(&members, scans)?;
+    let _ = scans.save();
+
+    if json {
+        output::print_json(&usage)?;
+        return Ok(());
+    }
+
+    output::print_header("🧠 cargo-sane clean --workspace");
+    println!();
+    output::print_info(&format!("Workspace: {}", manifest_path.display()));
+    output::print_info(&format!("Members: {}", members.len()));
+    let files = members.iter().map(|(_, _, files)| files.len()).sum();
+    output::print_info(&scanned_files(files, scans.stats()));
+    println!();
+
+    if usage.crates.is_empty() {
+        output::print_success("No unused dependencies found! 🎉");
+        return Ok(());
+    }
+    print_workspace_usage(&usage);
+
+    let removals: usize = usage.crates.iter().map(|c| c.remove_from.len()).sum();
+    if dry_run {
+        output::print_info("Dry-run mode: No changes will be made.");
+        return Ok(());
+    }
+
+    let confirm = prompt::confirm(
+        &format!(
+            "Remove {} unused declarations from member manifests?",
+            removals
+        ),
+        false,
+    )?;
+    if !confirm {
+        output::print_info("Clean cancelled.");
+        return Ok(());
+    }
+
+    for member in &workspace.members {
+        let targets: Vec<_> = usage
+            .crates
+            .iter()
+            .flat_map(|c| c.remove_from.iter().map(move |r| (c, r)))
+            .filter(|(_, r)| r.manifest == member.path)
+            .collect();
+        if targets.is_empty() {
+            continue;
+        }
+
+        let name = Workspace::member_name(member);
+        let declarations = member.declarations();
+        let mut updater = DependencyUpdater::new(member.clone())?;
+        let mut changes = Vec::new();
+        for (krate, removal) in targets {
+            match updater.remove_declaration(&removal.section, &krate.name) {
+                Ok(_) => {
+                    println!("  ✓ Removed {} from {}", krate.name.good(), name);
+                    changes.push(removal_change(&declarations, &removal.section, &krate.name));
+                }
+                Err(e) => eprintln!(
+                    "  ✗ Failed to remove {} from {}: {}",
+                    krate.name.bad(),
+                    name,
+                    e
+                ),
+            }
+        }
+        let backup = updater.save()?;
+        record_audit(
+            &manifest_path,
+            AuditEntry::new("clean", &member.path)
+                .with_changes(changes)
+                .with_backup(Some(backup)),
+        );
+    }
+
+    println!();
+    output::print_success("Member manifests updated successfully!");
+    output::print_info("Backups saved as Cargo.toml.backup next to each manifest");
+    Ok(())
+}
+
+fn print_workspace_usage(usage: &WorkspaceUsage) {
+    println!(
+        "{}",
+        output::plain("🧹 Unused across the workspace:")
+            .caution()
+            .bold()
+    );
+    for krate in &usage.crates {
+        let sections: BTreeSet<String> = krate
+            .remove_from
+            .iter()
+            .map(|r| r.section.to_string())
+            .collect();
+        println!(
+            "  • {} [{}]",
+            krate.name.bold(),
+            sections.into_iter().collect::<Vec<_>>().join(", ")
+        );
+        println!("      declared in: {}", krate.declared_in.join(", "));
+        let used = if krate.used_in.is_empty() {
+            "none".dimmed().to_string()
+        } else {
+            krate.used_in.join(", ")
+        };
+        println!("      used in:     {}", used);
+
+        let remove: Vec<&str> = krate
+            .remove_from
+            .iter()
+            .map(|r| r.member.as_str())
+            .collect();
+        let mut advice = format!("remove from {}", remove.join(", "));
+        if !krate.keep_in().is_empty() {
+            advice.push_str(&format!("; keep in {}", krate.keep_in().join(", ")));
+        }
+        println!("      {} {}", output::plain("→").cyan(), advice);
+    }
+    println!();
+}
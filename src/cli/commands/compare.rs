@@ -0,0 +1,199 @@
+//! `cargo sane compare`: versions and features against another project
+
+use crate::analyzer::compare::{
+    compare, newest_targets, suggest_targets, Comparison, ConvergenceBasis, Declared,
+};
+use crate::cli::commands::{find_manifest, runtime};
+use crate::cli::markdown::compare_markdown;
+use crate::cli::output::{self, Paint};
+use crate::core::config::Config;
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::utils::crates_io::CratesIoClient;
+use crate::utils::formatting::plural;
+use crate::utils::git::GitRepo;
+use crate::Result;
+use anyhow::Context;
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+
+/// Compare the dependencies against those of another project, given as a
+/// path or a git URL, with a version to converge on per divergent crate
+pub fn compare_command(
+    manifest_path: Option<String>,
+    with: &str,
+    json: bool,
+    markdown: bool,
+    offline: bool,
+) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let clone = is_git_url(with)
+        .then(|| ClonedProject::clone(with))
+        .transpose()?;
+    let other = match &clone {
+        Some(clone) => Manifest::find(Some(clone.dir.display().to_string()))
+            .with_context(|| format!("{} has no Cargo.toml at its root", with))?,
+        None => Manifest::find(Some(with.to_string()))?,
+    };
+
+    let lockfile = Lockfile::for_manifest(&manifest)?;
+    // A --lockfile given for this project is no use for the other
+    let other_lockfile = Lockfile::resolve(&other, None)?;
+    let mut comparison = compare(
+        (&manifest, lockfile.as_ref()),
+        (&other, other_lockfile.as_ref()),
+    );
+    if clone.is_some() && other.package_name().is_none() {
+        comparison.theirs = with.to_string();
+    }
+
+    if offline || comparison.versions.is_empty() {
+        newest_targets(&mut comparison);
+    } else {
+        let concurrency = Config::load(root)?.settings().concurrency;
+        let client = CratesIoClient::new().context("building the crates.io client")?;
+        runtime()?.block_on(suggest_targets(&mut comparison, &client, concurrency));
+    }
+
+    if json {
+        output::print_json(&comparison)?;
+    } else if markdown {
+        print!("{}", compare_markdown(&comparison));
+    } else {
+        print_comparison(&comparison);
+    }
+    Ok(())
+}
+
+fn is_git_url(location: &str) -> bool {
+    location.contains("://") || location.starts_with("git@")
+}
+
+/// A shallow clone of another project, deleted when dropped
+struct ClonedProject {
+    dir: PathBuf,
+}
+
+impl ClonedProject {
+    fn clone(url: &str) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("cargo-sane-compare-{}", std::process::id()));
+        // Left over from a run that was killed
+        let _ = std::fs::remove_dir_all(&dir);
+        GitRepo::clone_shallow(url, &dir)?;
+        Ok(Self { dir })
+    }
+}
+
+impl Drop for ClonedProject {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn print_comparison(comparison: &Comparison) {
+    let (ours, theirs) = (&comparison.ours, &comparison.theirs);
+    output::print_header(&format!("🔀 {} and {}", ours, theirs));
+    println!();
+    println!(
+        "  {} shared, {} at different versions",
+        plural(comparison.shared as u64, "dependency"),
+        comparison.versions.len()
+    );
+
+    if !comparison.versions.is_empty() {
+        let version = |declared: &Declared| {
+            declared
+                .version
+                .as_ref()
+                .map_or(declared.requirement.clone(), ToString::to_string)
+        };
+        let rows: Vec<[String; 3]> = comparison
+            .versions
+            .iter()
+            .map(|d| [d.name.clone(), version(&d.ours), version(&d.theirs)])
+            .collect();
+        let widths: Vec<usize> = (0..3)
+            .map(|i| {
+                let header = [String::new(), ours.clone(), theirs.clone()][i]
+                    .chars()
+                    .count();
+                rows.iter()
+                    .map(|row| row[i].chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .max(header)
+            })
+            .collect();
+        println!("\n{}", "Versions:".bold());
+        println!(
+            "  {:<w0$}  {:<w1$}  {:<w2$}  converge on",
+            "",
+            ours,
+            theirs,
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2]
+        );
+        for (row, divergence) in rows.iter().zip(&comparison.versions) {
+            let target = match &divergence.target {
+                Some(target) => {
+                    let basis = match target.basis {
+                        ConvergenceBasis::BothRequirements => "both requirements allow it",
+                        ConvergenceBasis::Newest => "the newer one",
+                    };
+                    format!(
+                        "{} {}",
+                        target.version.to_string().good(),
+                        format!("({})", basis).dimmed()
+                    )
+                }
+                None => String::new(),
+            };
+            // Padded before styling, which would count toward the width
+            println!(
+                "  {}  {:<w1$}  {:<w2$}  {}",
+                format!("{:<w0$}", row[0], w0 = widths[0]).bold(),
+                row[1],
+                row[2],
+                target,
+                w1 = widths[1],
+                w2 = widths[2]
+            );
+        }
+    }
+
+    if !comparison.features.is_empty() {
+        println!("\n{}", "Features:".bold());
+        for divergence in &comparison.features {
+            let mut sides = Vec::new();
+            for (side, features) in [
+                (ours, &divergence.only_ours),
+                (theirs, &divergence.only_theirs),
+            ] {
+                if !features.is_empty() {
+                    sides.push(format!("only {} enables {}", side, features.join(", ")));
+                }
+            }
+            println!("  • {}: {}", divergence.name.bold(), sides.join("; "));
+        }
+    }
+
+    for (side, only) in [
+        (ours, &comparison.only_ours),
+        (theirs, &comparison.only_theirs),
+    ] {
+        if !only.is_empty() {
+            println!(
+                "\n{} {}",
+                format!("Only in {}:", side).bold(),
+                only.join(", ")
+            );
+        }
+    }
+
+    println!();
+    if comparison.is_converged() {
+        output::print_success("Both projects use the same dependencies, versions and features");
+    }
+}
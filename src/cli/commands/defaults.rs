@@ -0,0 +1,95 @@
+//! `cargo sane defaults` and `schema`: the defaults in effect, and the
+//! JSON Schema of each --json document
+
+use crate::cli::commands::find_manifest;
+use crate::cli::defaults::Defaults;
+use crate::cli::output;
+use crate::cli::schema::{self, SchemaKind};
+use crate::core::config::{Config, CONFIG_FILE};
+use crate::utils::formatting::{display_path, format_duration};
+use crate::Result;
+use colored::Colorize;
+use std::path::Path;
+use std::time::Duration;
+
+/// Print the JSON Schema of `kind`'s `--json` output
+/// Print the defaults in effect for the project: its settings, the caches
+/// and their lifetimes, where lookups go, and the contract versions
+pub fn defaults_command(manifest_path: Option<String>, json: bool) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let defaults = Defaults::for_project(root, &Config::load(root)?);
+    if json {
+        return output::print_json(&defaults);
+    }
+
+    output::print_header("⚙ cargo-sane defaults");
+    println!();
+    match &defaults.config_file {
+        Some(path) => output::print_info(&format!("Settings from {}", display_path(path))),
+        None => output::print_info(&format!(
+            "No {} here; the built-in defaults apply",
+            CONFIG_FILE
+        )),
+    }
+
+    println!();
+    println!("{}", "Contracts".bold());
+    let contracts = &defaults.contracts;
+    for (name, version) in [
+        ("classification", contracts.classification),
+        ("exit codes", contracts.exit_codes),
+        ("JSON schema", contracts.schema),
+        ("plugin protocol", contracts.plugin_protocol),
+    ] {
+        println!("  {:<16} {}", name, format!("v{}", version).dimmed());
+    }
+    for exit in &defaults.exit_codes {
+        println!("  exit {:<11} {}", exit.code, exit.meaning.dimmed());
+    }
+
+    println!();
+    println!("{}", "Settings".bold());
+    if let serde_json::Value::Object(settings) = serde_json::to_value(&defaults.settings)? {
+        for (key, value) in settings {
+            let value = match value {
+                serde_json::Value::Null => "unset".dimmed().to_string(),
+                serde_json::Value::String(text) => text,
+                value => value.to_string(),
+            };
+            println!("  {} = {}", key, value);
+        }
+    }
+
+    println!();
+    println!(
+        "{} {}",
+        "Caches".bold(),
+        format!("in {}", display_path(&defaults.state_dir)).dimmed()
+    );
+    for cache in &defaults.caches {
+        let lifetime = match cache.ttl_secs {
+            None => "until replaced".to_string(),
+            Some(0) => "off".to_string(),
+            Some(secs) => format!("for {}", format_duration(Duration::from_secs(secs))),
+        };
+        println!("  {:<20} {}", cache.path, lifetime.dimmed());
+    }
+
+    println!();
+    println!("{}", "Sources".bold());
+    let sources = &defaults.sources;
+    for (name, url) in [
+        ("registry", &sources.registry),
+        ("index", &sources.index),
+        ("advisories", &sources.advisories),
+    ] {
+        println!("  {:<11} {}", name, url.dimmed());
+    }
+    Ok(())
+}
+
+pub fn schema_command(kind: SchemaKind) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&schema::schema(kind))?);
+    Ok(())
+}
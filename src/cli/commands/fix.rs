@@ -0,0 +1,990 @@
+//! `cargo sane fix` and `apply`: planning and carrying out fixes for
+//! duplicated crates, lock mismatches and split declarations
+
+use crate::analyzer::accepted::{AcceptedRisk, AcceptedRisks, RiskSubject};
+use crate::analyzer::build_units::{find_duplicate_units, DuplicateBuildUnit};
+use crate::analyzer::conflicts::{
+    find_conflicts, Conflict, ConflictReport, SourceSplit, CRATES_IO,
+};
+use crate::analyzer::declarations::{find_declaration_conflicts, DeclarationConflict};
+use crate::analyzer::health::{AffectedPackage, HealthChecker};
+use crate::analyzer::inheritance::{
+    find_inheritance_candidates, snippets as inheritance_snippets, InheritanceSuggestion,
+};
+use crate::analyzer::lock_mismatch::LockMismatch;
+use crate::cli::commands::check::{lock_mismatches, print_lock_mismatches};
+use crate::cli::commands::health::{print_accepted, print_expired_acceptances};
+use crate::cli::commands::{
+    advisory_db_options, cargo_options, find_manifest, record_audit, runtime,
+};
+use crate::cli::output::{self, Paint};
+use crate::cli::prompt;
+use crate::cli::wizard::run_conflict_wizard;
+use crate::core::config::Config;
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::core::version::is_compatible;
+use crate::core::workspace::Workspace;
+use crate::updater::plan::{ActionType, Plan, PlannedAction};
+use crate::updater::update::{backup_path, save_all};
+use crate::updater::DependencyUpdater;
+use crate::utils::audit::{AuditChange, AuditEntry};
+use crate::utils::cache;
+use crate::utils::cargo::{self, CargoOptions, Metadata};
+use crate::utils::formatting::{display_path, plural};
+use crate::utils::progress::ProgressMode;
+use crate::Result;
+use colored::Colorize;
+use semver::Version;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Apply a plan written by `update --plan-out`, or any other saved plan,
+/// exactly as recorded
+pub fn apply_command(plan: PathBuf, toolchain: Option<String>) -> Result<()> {
+    output::print_header("🧠 cargo-sane apply");
+    println!();
+
+    let manifest = Manifest::from_path(&Plan::load(&plan)?.manifest)?;
+    let cargo = cargo_options(&manifest)?.with_toolchain(toolchain)?;
+    apply_saved_plan("apply", manifest, &plan, &cargo)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn fix_command(
+    manifest_path: Option<String>,
+    auto: bool,
+    dry_run: bool,
+    json: bool,
+    plan: Option<String>,
+    toolchain: Option<String>,
+    suggest_inheritance: bool,
+    apply: bool,
+) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    if suggest_inheritance {
+        return fix_inheritance(manifest, apply, json);
+    }
+    let cargo = cargo_options(&manifest)?.with_toolchain(toolchain)?;
+    if let Some(plan) = plan {
+        return apply_saved_plan("fix", manifest, Path::new(&plan), &cargo);
+    }
+
+    if !json {
+        output::print_header("🧠 cargo-sane fix");
+        println!();
+        output::print_info(&format!("Manifest: {}", display_path(&manifest.path)));
+        println!();
+    }
+
+    // Bringing Cargo.lock in line with the requirements comes first
+    let mismatches = lock_mismatches(&manifest);
+    if !json {
+        print_lock_mismatches(&mismatches);
+    }
+    let mut actions = lock_mismatch_actions(&manifest, &mismatches);
+
+    // On a terminal, duplicates are resolved one by one in a wizard;
+    // otherwise they join the plan, the most impactful first
+    let wizard = !auto && !dry_run && !json && prompt::is_interactive();
+    let conflicts = duplicated_crates(&manifest, &cargo, json, dry_run)?;
+    if wizard {
+        if !conflicts.is_empty() {
+            run_conflict_wizard(&manifest, &conflicts, &cargo)?;
+        }
+    } else {
+        if !json && !conflicts.is_empty() {
+            print_version_conflicts(&conflicts);
+        }
+        actions.extend(conflict_actions(&manifest, &conflicts));
+    }
+    actions.extend(declaration_actions(&manifest, json));
+    let units = duplicate_build_units(&manifest, &cargo, json)?;
+    if !json && !units.is_empty() {
+        print_duplicate_build_units(&units);
+    }
+    let mut plan = Plan::new(&manifest, actions)?;
+    plan.duplicate_build_units = units;
+
+    if json {
+        output::print_json(&plan)?;
+        return Ok(());
+    }
+
+    print_plan(&plan);
+    if plan.executable().next().is_none() {
+        return Ok(());
+    }
+    if dry_run {
+        output::print_info("Dry-run mode: No changes will be made.");
+        return Ok(());
+    }
+
+    if !auto {
+        let confirm = prompt::confirm(
+            &format!("Apply {} planned change(s)?", plan.executable().count()),
+            true,
+        )?;
+        if !confirm {
+            output::print_info("Fix cancelled.");
+            return Ok(());
+        }
+    }
+
+    apply_plan("fix", &plan, manifest, &cargo)
+}
+
+/// Suggest moving crates several members declare to
+/// `[workspace.dependencies]`, printing the TOML each manifest would get;
+/// `apply` makes the edits, every manifest or none
+fn fix_inheritance(manifest: Manifest, apply: bool, json: bool) -> Result<()> {
+    let manifest_path = manifest.path.clone();
+    let Some(workspace) = Workspace::load(manifest)? else {
+        anyhow::bail!(
+            "{} is not a workspace root (no [workspace] table)",
+            manifest_path.display()
+        );
+    };
+    let suggestions = find_inheritance_candidates(&workspace);
+    let snippets = inheritance_snippets(&workspace.root, &suggestions);
+
+    if json {
+        if apply {
+            apply_inheritance(&workspace, &suggestions)?;
+        }
+        return output::print_json(&serde_json::json!({
+            "suggestions": suggestions,
+            "snippets": snippets,
+            "applied": apply && !suggestions.is_empty(),
+        }));
+    }
+
+    output::print_header("🧠 cargo-sane fix");
+    println!();
+    output::print_info(&format!("Workspace: {}", display_path(&manifest_path)));
+    println!();
+
+    if suggestions.is_empty() {
+        output::print_success("No dependency is declared the same way by two members 🎉");
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        output::plain("🧬 Dependencies members could inherit from the workspace:").bold()
+    );
+    for suggestion in &suggestions {
+        println!(
+            "  • {} {} ({})",
+            suggestion.name.bold(),
+            suggestion.entry.to_toml().dimmed(),
+            plural(suggestion.members() as u64, "member")
+        );
+    }
+    println!();
+
+    if apply {
+        apply_inheritance(&workspace, &suggestions)?;
+        for suggestion in &suggestions {
+            println!(
+                "  ✓ Moved {} to [workspace.dependencies], inherited by {}",
+                suggestion.name.bold(),
+                plural(suggestion.members() as u64, "member")
+            );
+        }
+        println!();
+        output::print_info("Backups saved next to each Cargo.toml as Cargo.toml.backup");
+        return Ok(());
+    }
+
+    for snippet in &snippets {
+        println!("# {}", display_path(&snippet.manifest));
+        for line in snippet.toml.lines() {
+            println!("{}", line.cyan());
+        }
+        println!();
+    }
+    println!(
+        "{}",
+        "Run `cargo sane fix --suggest-inheritance --apply` to make these edits.".dimmed()
+    );
+    Ok(())
+}
+
+/// Make the edits of `suggestions` in memory, then write every manifest
+/// they touch as one
+fn apply_inheritance(workspace: &Workspace, suggestions: &[InheritanceSuggestion]) -> Result<()> {
+    let mut updaters: BTreeMap<PathBuf, (DependencyUpdater, Vec<AuditChange>)> = BTreeMap::new();
+    let mut edit = |path: &Path,
+                    change: AuditChange,
+                    apply: &dyn Fn(&mut DependencyUpdater) -> Result<()>|
+     -> Result<()> {
+        if !updaters.contains_key(path) {
+            let updater = DependencyUpdater::new(Manifest::from_path(path)?)?;
+            updaters.insert(path.to_path_buf(), (updater, Vec::new()));
+        }
+        let (updater, changes) = updaters.get_mut(path).expect("inserted above");
+        apply(updater)?;
+        changes.push(change);
+        Ok(())
+    };
+
+    // Entries go in at the top of the table, so adding them last first
+    // leaves them in name order
+    for suggestion in suggestions.iter().rev() {
+        let name = &suggestion.name;
+        for declaration in &suggestion.declarations {
+            let value = declaration.to_toml();
+            edit(
+                &declaration.manifest,
+                AuditChange {
+                    name: name.clone(),
+                    old: Some(declaration.requirement.clone()),
+                    new: Some(value),
+                    section: declaration.section.to_string(),
+                },
+                &|updater| {
+                    updater.inherit_from_workspace(
+                        &declaration.section,
+                        name,
+                        &declaration.features,
+                    )
+                },
+            )?;
+        }
+        let spec = suggestion.entry.to_toml();
+        edit(
+            &workspace.root.path,
+            AuditChange {
+                name: name.clone(),
+                old: None,
+                new: Some(spec.clone()),
+                section: "workspace.dependencies".to_string(),
+            },
+            &|updater| updater.add_workspace_dependency(name, &spec),
+        )?;
+    }
+
+    let backups = save_all(updaters.values().map(|(updater, _)| updater))?;
+    for ((path, (_, changes)), backup) in updaters.into_iter().zip(backups) {
+        record_audit(
+            &path,
+            AuditEntry::new("fix", &path)
+                .with_changes(changes)
+                .with_backup(Some(backup)),
+        );
+    }
+    Ok(())
+}
+
+/// One `cargo update` per requirement Cargo.lock no longer satisfies,
+/// moving the first offending locked version
+fn lock_mismatch_actions(manifest: &Manifest, mismatches: &[LockMismatch]) -> Vec<PlannedAction> {
+    mismatches
+        .iter()
+        .filter_map(|mismatch| {
+            let locked = mismatch.locked.first()?;
+            Some(PlannedAction::lockfile_refresh(
+                manifest,
+                &mismatch.package,
+                locked,
+                format!(
+                    "lock mismatch: [{}] {} = \"{}\"",
+                    mismatch.table(),
+                    mismatch.name,
+                    mismatch.requirement
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// Duplicated crates, annotated with advisories and ordered by impact, less
+/// those the config ignores
+fn duplicated_crates(
+    manifest: &Manifest,
+    cargo: &CargoOptions,
+    quiet: bool,
+    dry_run: bool,
+) -> Result<Vec<Conflict>> {
+    let warn = |message: String| {
+        if !quiet {
+            output::print_warning(&message);
+        }
+    };
+
+    let mut report = match find_conflicts(manifest, cargo) {
+        Ok(report) => report,
+        // A metadata file that can't be used is the user's to fix
+        Err(e) if Metadata::file().is_some() => return Err(e),
+        Err(e) => {
+            warn(format!("Could not look for duplicated crates: {}", e));
+            return Ok(Vec::new());
+        }
+    };
+    if !quiet && !report.source_splits.is_empty() {
+        print_source_splits(&report.source_splits);
+    }
+    if report.conflicts.is_empty() {
+        if !quiet {
+            output::print_success("No duplicated crates found! 🎉");
+            println!();
+        }
+        return Ok(Vec::new());
+    }
+
+    match conflict_advisories(manifest, &report, quiet, dry_run) {
+        Ok(affected) => report.annotate(&affected),
+        Err(e) => warn(format!(
+            "Could not check duplicated versions for advisories: {}",
+            e
+        )),
+    }
+
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let mut ignored = Config::load(root)
+        .map(|config| config.ignore_conflicts)
+        .unwrap_or_default();
+    match AcceptedRisks::load(root) {
+        Ok(accepted) => {
+            let now = cache::unix_now();
+            let names = accepted.conflicts(now);
+            if !quiet {
+                print_expired_acceptances(&accepted, now);
+                let present: Vec<String> = report
+                    .conflicts
+                    .iter()
+                    .filter_map(|c| {
+                        let subject = RiskSubject::Conflict {
+                            package: c.name.clone(),
+                        };
+                        accepted.find(&subject, now).map(AcceptedRisk::summary)
+                    })
+                    .collect();
+                print_accepted(&present);
+            }
+            ignored.extend(names);
+        }
+        Err(e) => warn(format!("Could not read accepted risks: {}", e)),
+    }
+    let mut report = report.without(&ignored);
+    report.sort_by_impact();
+    Ok(report.conflicts)
+}
+
+/// Converge each duplicated crate onto one version with `cargo update
+/// --precise`. Versions not semver-compatible with the target need their
+/// dependents upgraded and are only listed.
+fn conflict_actions(manifest: &Manifest, conflicts: &[Conflict]) -> Vec<PlannedAction> {
+    let mut actions = Vec::new();
+    for conflict in conflicts {
+        let Some(target) = conflict.target() else {
+            continue;
+        };
+        for version in conflict.versions.iter().filter(|v| &v.version != target) {
+            let reason = if version.advisories.is_empty() {
+                format!(
+                    "conflict: {} versions of {}",
+                    conflict.versions.len(),
+                    conflict.name
+                )
+            } else {
+                version.advisories.join(", ")
+            };
+            actions.push(if is_compatible(&version.version, target) {
+                PlannedAction::lockfile_update(
+                    manifest,
+                    &conflict.name,
+                    &version.version,
+                    target,
+                    reason,
+                )
+            } else {
+                PlannedAction::unavailable(&conflict.name, &version.version.to_string(), reason)
+            });
+        }
+    }
+    actions
+}
+
+/// One `cargo update --precise` run for a duplicated version
+#[derive(Debug)]
+pub(crate) struct PackageFix {
+    pub name: String,
+    pub from: Version,
+    pub to: Version,
+    /// Why cargo failed, its stderr included
+    pub error: Option<String>,
+    /// Cargo succeeded, yet Cargo.lock still holds `from`
+    pub still_locked: bool,
+}
+
+/// What converging duplicated crates in the lockfile came to
+#[derive(Debug, Default)]
+pub(crate) struct FixOutcome {
+    pub fixes: Vec<PackageFix>,
+    /// Crates Cargo.lock still holds at several versions the target could
+    /// have replaced afterwards
+    pub still_duplicated: Vec<String>,
+    /// Crates left at versions semver-incompatible with the target, which
+    /// only a new release of a dependent can merge
+    pub needs_upgrade: Vec<String>,
+    /// Cargo.lock couldn't be read back, so nothing was verified
+    pub unverified: bool,
+}
+
+impl FixOutcome {
+    /// Every update ran and took effect
+    pub fn is_clean(&self) -> bool {
+        self.fixes
+            .iter()
+            .all(|fix| fix.error.is_none() && !fix.still_locked)
+    }
+}
+
+/// Move every compatible off-target version of each conflict to its target
+/// with `cargo update -p name@version --precise`, then read Cargo.lock back
+/// to confirm each version is gone. A failed update doesn't stop the rest;
+/// the ones that succeeded are recorded in the audit log.
+pub(crate) fn run_conflict_fixes(
+    manifest: &Manifest,
+    conflicts: &[Conflict],
+    cargo: &CargoOptions,
+) -> FixOutcome {
+    let mut outcome = FixOutcome::default();
+    for conflict in conflicts {
+        let Some(target) = conflict.target() else {
+            continue;
+        };
+        for (version, compatible) in conflict.movable() {
+            if !compatible {
+                continue;
+            }
+            let result = cargo::update_precise(
+                &manifest.path,
+                &conflict.name,
+                &version.version,
+                target,
+                cargo,
+            );
+            outcome.fixes.push(PackageFix {
+                name: conflict.name.clone(),
+                from: version.version.clone(),
+                to: target.clone(),
+                error: result.err().map(|e| format!("{:#}", e)),
+                still_locked: false,
+            });
+        }
+    }
+    if outcome.fixes.is_empty() {
+        return outcome;
+    }
+
+    let targets: Vec<(String, Version)> = conflicts
+        .iter()
+        .filter_map(|c| Some((c.name.clone(), c.target()?.clone())))
+        .collect();
+    verify_convergence(manifest, &targets, &mut outcome);
+    let changes = outcome
+        .fixes
+        .iter()
+        .filter(|fix| fix.error.is_none() && !fix.still_locked)
+        .map(|fix| AuditChange {
+            name: fix.name.clone(),
+            old: Some(fix.from.to_string()),
+            new: Some(fix.to.to_string()),
+            section: "Cargo.lock".to_string(),
+        })
+        .collect();
+    record_audit(
+        &manifest.path,
+        AuditEntry::new("fix", &manifest.path).with_changes(changes),
+    );
+    outcome
+}
+
+/// Read Cargo.lock back after the updates of `outcome` ran: mark those
+/// whose old version is still locked, and sort each `(crate, target)` still
+/// locked at other versions into still duplicated or needing an upgrade
+fn verify_convergence(
+    manifest: &Manifest,
+    targets: &[(String, Version)],
+    outcome: &mut FixOutcome,
+) {
+    let Ok(Some(lockfile)) = Lockfile::for_manifest(manifest) else {
+        outcome.unverified = true;
+        return;
+    };
+    for fix in outcome.fixes.iter_mut().filter(|fix| fix.error.is_none()) {
+        fix.still_locked = lockfile
+            .packages_named(&fix.name)
+            .iter()
+            .any(|p| p.version == fix.from);
+    }
+    for (name, target) in targets {
+        let (compatible, incompatible): (Vec<_>, Vec<_>) = lockfile
+            .packages_named(name)
+            .into_iter()
+            .filter(|p| &p.version != target)
+            .partition(|p| is_compatible(&p.version, target));
+        if !compatible.is_empty() {
+            outcome.still_duplicated.push(name.clone());
+        } else if !incompatible.is_empty() {
+            outcome.needs_upgrade.push(name.clone());
+        }
+    }
+}
+
+/// One line per update, with cargo's stderr under a failed one
+pub(crate) fn print_fix_outcome(outcome: &FixOutcome) {
+    for fix in &outcome.fixes {
+        let change = format!("{} v{} → v{}", fix.name, fix.from, fix.to);
+        match &fix.error {
+            Some(error) => {
+                eprintln!("  {} {}", output::plain("✗").bad(), change.bad());
+                for line in error.lines().filter(|line| !line.trim().is_empty()) {
+                    eprintln!("      {}", line.dimmed());
+                }
+            }
+            None if fix.still_locked => println!(
+                "  {} {} {}",
+                output::plain("⚠").caution(),
+                change,
+                format!("(Cargo.lock still locks v{})", fix.from).caution()
+            ),
+            None => println!("  {} {}", output::plain("✓").good(), change),
+        }
+    }
+    print_leftover_duplicates(outcome);
+}
+
+/// What the updates of `outcome` left duplicated, or that they couldn't
+/// be checked
+fn print_leftover_duplicates(outcome: &FixOutcome) {
+    if outcome.unverified {
+        output::print_warning("Could not read Cargo.lock back to verify the updates");
+    } else if !outcome.still_duplicated.is_empty() {
+        output::print_warning(&format!(
+            "Still duplicated: {}",
+            outcome.still_duplicated.join(", ")
+        ));
+    }
+    if !outcome.needs_upgrade.is_empty() {
+        output::print_info(&format!(
+            "Semver-incompatible versions remain, for a dependent's new release to merge: {}",
+            outcome.needs_upgrade.join(", ")
+        ));
+    }
+}
+
+/// Align the requirements of crates whose overlapping declarations disagree.
+/// A features or default-features mismatch needs a human to decide which one
+/// is intended, so only differing requirements are planned.
+fn declaration_actions(manifest: &Manifest, quiet: bool) -> Vec<PlannedAction> {
+    let conflicts = find_declaration_conflicts(manifest);
+    if conflicts.is_empty() {
+        if !quiet {
+            output::print_success("No declaration conflicts found! 🎉");
+            println!();
+        }
+        return Vec::new();
+    }
+    if !quiet {
+        print_declaration_conflicts(&conflicts);
+    }
+
+    let mut actions = Vec::new();
+    for conflict in &conflicts {
+        let reason = "conflict: overlapping declarations".to_string();
+        match conflict.consolidation_target() {
+            Some(target) if conflict.requirement_mismatch => {
+                for declaration in conflict.divergent_declarations() {
+                    actions.push(PlannedAction::manifest_edit(
+                        &declaration.section,
+                        &conflict.name,
+                        declaration.requirement.as_deref().unwrap_or("*"),
+                        target,
+                        reason.clone(),
+                    ));
+                }
+            }
+            _ => actions.push(PlannedAction::unavailable(
+                &conflict.name,
+                conflict.consolidation_target().unwrap_or("*"),
+                reason,
+            )),
+        }
+    }
+    actions
+}
+
+/// Load a plan saved from `--dry-run --json` and execute it as is
+pub(super) fn apply_saved_plan(
+    command: &str,
+    manifest: Manifest,
+    path: &Path,
+    cargo: &CargoOptions,
+) -> Result<()> {
+    let plan = Plan::load(path)?;
+    if plan.manifest.canonicalize().ok() != manifest.path.canonicalize().ok() {
+        anyhow::bail!(
+            "The plan was made for {}, not {}",
+            plan.manifest.display(),
+            manifest.path.display()
+        );
+    }
+    plan.verify(&manifest)?;
+
+    output::print_info(&format!("Applying plan {}", path.display()));
+    println!();
+    print_plan(&plan);
+    if plan.executable().next().is_none() {
+        return Ok(());
+    }
+    apply_plan(command, &plan, manifest, cargo)
+}
+
+/// List the planned actions, executable ones first
+pub(super) fn print_plan(plan: &Plan) {
+    if plan.actions.is_empty() {
+        output::print_success("Nothing to change.");
+        return;
+    }
+
+    println!("{}", output::plain("📝 Planned changes:").bold());
+    for action in plan.executable() {
+        println!(
+            "  • {} {} → {} ({})",
+            action.name.bold(),
+            action.from_version.dimmed(),
+            action
+                .to_version
+                .as_deref()
+                .unwrap_or(NEWEST_ALLOWED)
+                .cyan(),
+            action.reason
+        );
+        if let Some(change) = &action.change {
+            println!("      {}", change.dimmed());
+        }
+    }
+    let manual: Vec<&PlannedAction> = plan
+        .actions
+        .iter()
+        .filter(|a| a.action == ActionType::NoActionAvailable)
+        .collect();
+    if !manual.is_empty() {
+        println!("{}", "  Needs manual attention:".caution());
+        for action in manual {
+            println!(
+                "  • {} {} ({})",
+                action.name.bold(),
+                action.from_version.dimmed(),
+                action.reason
+            );
+        }
+    }
+    println!();
+}
+
+/// Execute `plan`, recording what took effect in the audit log. Crates the
+/// plan converges with `cargo update --precise` are checked in Cargo.lock
+/// afterwards; a failed action or a crate left duplicated fails the run.
+pub(super) fn apply_plan(
+    command: &str,
+    plan: &Plan,
+    manifest: Manifest,
+    cargo: &CargoOptions,
+) -> Result<()> {
+    println!("{}", output::plain("🔄 Applying changes...").bold());
+    let outcomes = plan.apply(manifest.clone(), cargo)?;
+
+    let mut edited = false;
+    let mut applied = Vec::new();
+    let mut convergence = FixOutcome::default();
+    let mut failed = 0;
+    for (action, outcome) in plan.actions.iter().zip(outcomes) {
+        if action.action == ActionType::NoActionAvailable {
+            continue;
+        }
+        let to = action.to_version.as_deref().unwrap_or(NEWEST_ALLOWED);
+        match outcome {
+            Ok(()) => {
+                edited |= action.action == ActionType::ManifestEdit;
+                convergence.fixes.extend(precise_fix(action));
+                applied.push(action);
+                println!(
+                    "  ✓ {} {} → {}",
+                    action.name.good(),
+                    action.from_version.dimmed(),
+                    to.cyan()
+                );
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("  ✗ Failed to update {}: {}", action.name.bad(), e);
+            }
+        }
+    }
+    println!();
+
+    if !convergence.fixes.is_empty() {
+        let mut targets: Vec<(String, Version)> = convergence
+            .fixes
+            .iter()
+            .map(|fix| (fix.name.clone(), fix.to.clone()))
+            .collect();
+        targets.sort();
+        targets.dedup();
+        verify_convergence(&manifest, &targets, &mut convergence);
+        for fix in convergence.fixes.iter().filter(|fix| fix.still_locked) {
+            output::print_warning(&format!(
+                "{} v{} → v{} ran, yet Cargo.lock still locks v{}",
+                fix.name, fix.from, fix.to, fix.from
+            ));
+        }
+        print_leftover_duplicates(&convergence);
+    }
+
+    // An update Cargo.lock didn't take isn't a change
+    let changes = applied
+        .into_iter()
+        .filter(|action| {
+            !convergence.fixes.iter().any(|fix| {
+                fix.still_locked
+                    && fix.name == action.name
+                    && fix.from.to_string() == action.from_version
+            })
+        })
+        .map(|action| AuditChange {
+            name: action.name.clone(),
+            old: Some(action.from_version.clone()),
+            new: action.to_version.clone(),
+            section: match (&action.action, &action.section) {
+                (ActionType::ManifestEdit, Some(section)) => section.to_string(),
+                _ => "Cargo.lock".to_string(),
+            },
+        })
+        .collect();
+    record_audit(
+        &plan.manifest,
+        AuditEntry::new(command, &plan.manifest)
+            .with_changes(changes)
+            .with_backup(edited.then(|| backup_path(&plan.manifest))),
+    );
+
+    if edited {
+        output::print_success("Cargo.toml updated successfully!");
+        output::print_info(&format!(
+            "Backup saved as {}",
+            display_path(&backup_path(&plan.manifest))
+        ));
+    }
+    if failed > 0 {
+        anyhow::bail!(
+            "{} of {} planned change(s) failed; see above",
+            failed,
+            plan.executable().count()
+        );
+    }
+    // The other changes stand on their own, but a run that left crates
+    // duplicated mustn't pass in CI
+    if !convergence.is_clean() || !convergence.still_duplicated.is_empty() {
+        anyhow::bail!("Some duplicated crates couldn't be converged; see above");
+    }
+    if !edited {
+        output::print_success("Done.");
+    }
+    Ok(())
+}
+
+/// How a lockfile update without a fixed target is shown
+const NEWEST_ALLOWED: &str = "newest allowed";
+
+/// The duplicated version a `cargo update --precise` action moves
+fn precise_fix(action: &PlannedAction) -> Option<PackageFix> {
+    if action.action != ActionType::LockfileUpdate {
+        return None;
+    }
+    Some(PackageFix {
+        name: action.name.clone(),
+        from: Version::parse(&action.from_version).ok()?,
+        to: Version::parse(action.to_version.as_deref()?).ok()?,
+        error: None,
+        still_locked: false,
+    })
+}
+
+/// Look the duplicated versions up in the project's advisory database
+fn conflict_advisories(
+    manifest: &Manifest,
+    report: &ConflictReport,
+    quiet: bool,
+    dry_run: bool,
+) -> Result<Vec<AffectedPackage>> {
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let config = Config::load(root)?;
+    let (checker, _) = HealthChecker::open(manifest, advisory_db_options(&config, false, false))?;
+    let checker = checker
+        .with_concurrency(config.concurrency)
+        .with_progress(ProgressMode::detect(quiet).build(false));
+    let affected = runtime()?.block_on(checker.check_packages(&report.packages()))?;
+    if !dry_run {
+        if let Err(e) = checker.source().save() {
+            output::print_error(&format!("Could not save the advisory database: {}", e));
+        }
+    }
+    Ok(affected)
+}
+
+/// Print crates present at several versions, with the version to converge on
+/// Packages compiled more than once at the same version; a failed lookup
+/// only costs the section
+fn duplicate_build_units(
+    manifest: &Manifest,
+    cargo: &CargoOptions,
+    quiet: bool,
+) -> Result<Vec<DuplicateBuildUnit>> {
+    match find_duplicate_units(manifest, cargo) {
+        Ok(units) => Ok(units),
+        Err(e) if Metadata::file().is_some() => Err(e),
+        Err(e) => {
+            if !quiet {
+                output::print_warning(&format!("Could not look for duplicate build units: {}", e));
+            }
+            Ok(Vec::new())
+        }
+    }
+}
+
+fn print_duplicate_build_units(units: &[DuplicateBuildUnit]) {
+    println!(
+        "{}",
+        output::plain("🧱 Duplicate build units:").caution().bold()
+    );
+    for unit in units {
+        println!(
+            "  • {} v{} {}",
+            unit.name.bold(),
+            unit.version,
+            unit.describe()
+        );
+        println!("      {}", unit.suggestion().dimmed());
+    }
+    if units.iter().any(|u| !u.confirmed) {
+        println!(
+            "  {}",
+            "Inferred from cargo metadata; --toolchain nightly reads the unit graph to confirm"
+                .dimmed()
+        );
+    }
+    println!();
+}
+
+fn print_version_conflicts(conflicts: &[Conflict]) {
+    println!(
+        "{}",
+        output::plain("🔀 Duplicated crates:").caution().bold()
+    );
+    for conflict in conflicts {
+        let marker = if conflict.security_relevant {
+            format!(" {}", "(security-relevant)".bad().bold())
+        } else {
+            String::new()
+        };
+        println!("  • {}{}", conflict.name.bold(), marker);
+
+        let target = conflict.target();
+        for version in &conflict.versions {
+            let mut line = format!("v{}", version.version);
+            if Some(&version.version) == target {
+                line = format!("{} (target)", line).good().to_string();
+            }
+            if !version.advisories.is_empty() {
+                line.push_str(&format!(" {}", version.advisories.join(", ").bad()));
+            }
+            if version.sources.iter().any(|s| s != CRATES_IO) {
+                line.push_str(&format!(
+                    " {}",
+                    format!("from {}", version.sources.join(", ")).dimmed()
+                ));
+            }
+            println!("      {}", line);
+            if !version.dependents.is_empty() {
+                println!(
+                    "        {}",
+                    format!("required by {}", version.dependents.join(", ")).dimmed()
+                );
+            }
+        }
+    }
+    println!();
+}
+
+/// Print versions resolved from more than one registry or repository
+fn print_source_splits(splits: &[SourceSplit]) {
+    println!(
+        "{}",
+        output::plain("🔀 Same version from several sources:")
+            .caution()
+            .bold()
+    );
+    for split in splits {
+        println!("  • {} v{}", split.name.bold(), split.version);
+        for source in &split.sources {
+            println!("      {}", source);
+        }
+        if !split.dependents.is_empty() {
+            println!(
+                "        {}",
+                format!("required by {}", split.dependents.join(", ")).dimmed()
+            );
+        }
+    }
+    println!();
+}
+
+/// Print crates declared in several overlapping sections
+pub(super) fn print_declaration_conflicts(conflicts: &[DeclarationConflict]) {
+    println!(
+        "{}",
+        output::plain("⚠️  Declaration conflicts:").caution().bold()
+    );
+    for conflict in conflicts {
+        let mut problems = Vec::new();
+        if conflict.requirement_mismatch {
+            problems.push("version requirements differ");
+        }
+        if conflict.features_mismatch {
+            problems.push("features differ");
+        }
+        if conflict.default_features_mismatch {
+            problems.push("default-features differs");
+        }
+        println!("  • {} ({})", conflict.name.bold(), problems.join(", "));
+
+        for declaration in &conflict.declarations {
+            let mut details = vec![format!(
+                "\"{}\"",
+                declaration.requirement.as_deref().unwrap_or("*")
+            )];
+            if !declaration.default_features {
+                details.push("default-features = false".to_string());
+            }
+            if !declaration.features.is_empty() || conflict.features_mismatch {
+                details.push(format!("features = [{}]", declaration.features.join(", ")));
+            }
+            let line = declaration
+                .line
+                .map(|l| format!(" (line {})", l))
+                .unwrap_or_default();
+            println!(
+                "      [{}] {}{}",
+                declaration.section,
+                details.join(", "),
+                line.dimmed()
+            );
+        }
+    }
+    println!();
+}
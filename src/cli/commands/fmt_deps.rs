@@ -0,0 +1,74 @@
+//! `cargo sane fmt-deps`: sorted dependency tables and consistent
+//! declarations
+
+use crate::cli::commands::{find_manifest, record_audit};
+use crate::cli::output::{self, Paint};
+use crate::core::config::Config;
+use crate::updater::fmt_deps::{diff_lines, restyled, DiffLine};
+use crate::updater::DependencyUpdater;
+use crate::utils::audit::{AuditChange, AuditEntry};
+use crate::utils::formatting::display_path;
+use crate::Result;
+use std::path::Path;
+
+/// Lay the dependency tables of Cargo.toml out canonically. Under `check`
+/// nothing is written, and the result says whether the manifest already
+/// was canonical.
+pub fn fmt_deps_command(manifest_path: Option<String>, check: bool, dry_run: bool) -> Result<bool> {
+    let manifest = find_manifest(manifest_path)?;
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let inline_max_keys = Config::load(root)?.settings().fmt_inline_max_keys;
+
+    let mut updater = DependencyUpdater::new(manifest.clone())?;
+    let before = updater.get_content().to_string();
+    if !updater.format_dependencies(inline_max_keys)? {
+        output::print_success(&format!(
+            "{} is already laid out canonically",
+            display_path(&manifest.path)
+        ));
+        return Ok(true);
+    }
+
+    output::print_header("🧹 cargo-sane fmt-deps");
+    println!();
+    for line in diff_lines(&before, updater.get_content()) {
+        match line {
+            DiffLine::Same(_) => {}
+            DiffLine::Removed(text) => println!("{}", format!("- {}", text).bad()),
+            DiffLine::Added(text) => println!("{}", format!("+ {}", text).good()),
+        }
+    }
+    println!();
+
+    if check {
+        output::print_error(&format!(
+            "{} isn't laid out canonically; run `cargo sane fmt-deps` to fix it",
+            display_path(&manifest.path)
+        ));
+        return Ok(false);
+    }
+    if dry_run {
+        output::print_info("Dry-run mode: No changes will be made.");
+        return Ok(true);
+    }
+
+    let changes = restyled(&before, updater.get_content())?
+        .into_iter()
+        .map(|r| AuditChange {
+            name: r.name,
+            old: Some(r.before),
+            new: Some(r.after),
+            section: r.table,
+        })
+        .collect();
+    let backup = updater.save()?;
+    record_audit(
+        &manifest.path,
+        AuditEntry::new("fmt-deps", &manifest.path)
+            .with_changes(changes)
+            .with_backup(Some(backup.clone())),
+    );
+    output::print_success(&format!("Reformatted {}", display_path(&manifest.path)));
+    output::print_info(&format!("Backup saved as {}", display_path(&backup)));
+    Ok(true)
+}
@@ -0,0 +1,1192 @@
+//! `cargo sane health`: security advisories and maintenance status
+
+use crate::analyzer::accepted::AcceptedRisks;
+use crate::analyzer::build_time::{build_time_surface, BuildTimeSurface};
+use crate::analyzer::checker::{git_dependencies, DependencyChecker};
+use crate::analyzer::checksums::{crates_io_packages, verify_checksums, ChecksumReport};
+use crate::analyzer::embedded::{find_embedded_crates, own_manifests, EmbeddedCrate};
+use crate::analyzer::enrichment::{enrich, untrusted_input_parsers, Candidate, Enrichment};
+use crate::analyzer::health::{AffectedPackage, HealthChecker, HealthReport};
+use crate::analyzer::internal::InternalCrates;
+use crate::analyzer::ownership::ownership_changes;
+use crate::analyzer::priority::{rank, truncate, Class, Significance};
+use crate::analyzer::system_libs::{
+    known_libraries, pkg_config_version, system_libraries, SystemLibrary,
+};
+use crate::analyzer::teams::{group_by_team, Teams};
+use crate::cli::commands::check::{
+    fetch_owners, print_forks, print_ownership_changes, snapshot_owners,
+};
+use crate::cli::commands::fix::{apply_plan, apply_saved_plan, print_plan};
+use crate::cli::commands::{
+    advisory_db_options, cargo_options, find_manifest, internal_crates, load_policy, print_more,
+    print_network_hint, print_partial, print_unknown_team_crates, runtime, workspace_implied,
+    write_metrics,
+};
+use crate::cli::csv::health_csv;
+use crate::cli::markdown::health_markdown;
+use crate::cli::metrics::Metrics;
+use crate::cli::output::{self, FileFormat, OutputFile, OutputFormat, Paint, Status};
+use crate::core::config::Config;
+use crate::core::dependency::DependencySource;
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::core::policy::VersionPolicy;
+use crate::core::version::is_compatible;
+use crate::updater::plan::{Plan, PlannedAction};
+use crate::utils::advisories::AdvisorySource;
+use crate::utils::advisory_db::{
+    archive_path, database_path, refresh_archive, AdvisoryIndex, DatabaseInfo, DbMode, DbOptions,
+};
+use crate::utils::cache;
+use crate::utils::cancel::Cancellation;
+use crate::utils::cargo::{self, Metadata};
+use crate::utils::checksums::published_checksums;
+use crate::utils::crates_io::CratesIoClient;
+use crate::utils::files::{collect_files, WalkOptions};
+use crate::utils::formatting::{display_path, format_count, format_since, plural};
+use crate::utils::progress::ProgressMode;
+use crate::utils::registry::DEFAULT_CONCURRENCY;
+use crate::utils::snapshots::SnapshotStore;
+use crate::utils::sparse_index::SparseIndexClient;
+use crate::Result;
+use anyhow::Context;
+use colored::Colorize;
+use semver::Version;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Warn about acceptances past their date: their findings count again
+pub(super) fn print_expired_acceptances(accepted: &AcceptedRisks, now: u64) {
+    let expired = accepted.expired(now);
+    if expired.is_empty() {
+        return;
+    }
+    for risk in &expired {
+        output::print_warning(&format!("Acceptance expired: {}", risk.summary()));
+    }
+    output::print_info(
+        "Their findings count again; renew them with `cargo sane accept` or drop them with --remove",
+    );
+    println!();
+}
+
+/// List findings acknowledged as accepted risks, apart from the alarming ones
+pub(super) fn print_accepted(summaries: &[String]) {
+    if summaries.is_empty() {
+        return;
+    }
+    println!(
+        "{}",
+        format!("✔️  Accepted ({}):", summaries.len()).good().bold()
+    );
+    for summary in summaries {
+        println!("  • {}", summary.dimmed());
+    }
+    println!();
+}
+
+/// A vulnerable package with its advisories, and for transitive ones the
+/// path that brings it in
+fn print_affected(package: &AffectedPackage, indent: &str) {
+    let source = match package.source {
+        DependencySource::Git => " (git)".dimmed().to_string(),
+        _ => String::new(),
+    };
+    println!(
+        "{}• {} {}{}",
+        indent,
+        package.name.bold(),
+        package.version,
+        source
+    );
+    if package.attribution.depth > 1 {
+        println!(
+            "{}  {}",
+            indent,
+            package.attribution.path.join(" → ").dimmed()
+        );
+    }
+    if !package.also_via.is_empty() {
+        let parents: Vec<&str> = package
+            .also_via
+            .iter()
+            .map(|a| a.direct_parent.as_str())
+            .collect();
+        println!(
+            "{}  {}",
+            indent,
+            format!("also via {}", parents.join(", ")).dimmed()
+        );
+    }
+    for advisory in &package.advisories {
+        let label = match &advisory.informational {
+            Some(kind) => format!("[{}]", kind.to_uppercase()).caution(),
+            None => output::badge(Status::of_severity(advisory.severity)),
+        };
+        println!("{}  {} {} {}", indent, label, advisory.id, advisory.title);
+        if !advisory.patched_versions.is_empty() {
+            println!(
+                "{}    patched: {}",
+                indent,
+                advisory.patched_versions.join(", ")
+            );
+        }
+        println!("{}    {}", indent, advisory.url.dimmed());
+    }
+}
+
+/// Advisories that matched one of the project's own crates by name only
+fn print_internal_advisories(packages: &[AffectedPackage]) {
+    if packages.is_empty() {
+        return;
+    }
+    println!(
+        "{}",
+        output::plain("🏠 Internal crates (advisories likely about a published namesake):").bold()
+    );
+    for package in packages {
+        let ids: Vec<&str> = package.advisories.iter().map(|a| a.id.as_str()).collect();
+        println!(
+            "  • {} {} {}",
+            package.name,
+            package.version,
+            ids.join(", ").dimmed()
+        );
+    }
+    println!();
+}
+
+/// Likely copies of crates in the source tree of the project owning
+/// `manifest`, with the advisories against the versions they look like
+fn embedded_crates<A: AdvisorySource>(
+    checker: &HealthChecker<A>,
+    manifest: &Manifest,
+    config: &Config,
+    lockfile: Option<&Lockfile>,
+) -> Result<Vec<EmbeddedCrate>> {
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let files = collect_files(root, &WalkOptions::from_config(config), |path| {
+        path.file_name().is_some_and(|name| name == "Cargo.toml")
+            || path.extension().is_some_and(|ext| ext == "rs")
+    })?;
+    let mut known: BTreeSet<String> = AdvisoryIndex::load(&database_path(manifest))
+        .packages()
+        .map(str::to_string)
+        .collect();
+    known.extend(
+        lockfile
+            .iter()
+            .flat_map(|l| &l.packages)
+            .map(|p| p.name.clone()),
+    );
+    let mut embedded =
+        find_embedded_crates(root, &files, &own_manifests(manifest), &known, lockfile);
+
+    let versions: Vec<(String, Version)> = embedded
+        .iter()
+        .filter_map(|e| Some((e.name.clone(), e.version.clone()?)))
+        .collect();
+    let affected = runtime()?.block_on(checker.check_packages(&versions))?;
+    for copy in &mut embedded {
+        if let Some(package) = affected
+            .iter()
+            .find(|p| p.name == copy.name && Some(&p.version) == copy.version.as_ref())
+        {
+            copy.advisories = package.advisories.clone();
+        }
+    }
+    Ok(embedded)
+}
+
+/// The likely embedded crates `health --scan-embedded` found, or that it
+/// found none
+fn print_embedded(embedded: &[EmbeddedCrate], scanned: bool) {
+    if !scanned {
+        return;
+    }
+    if embedded.is_empty() {
+        output::print_success("No embedded copies of crates found in the source tree");
+        println!();
+        return;
+    }
+
+    println!(
+        "{}",
+        output::plain("🧩 Likely embedded copies of crates (informational):")
+            .cyan()
+            .bold()
+    );
+    for copy in embedded {
+        let version = match &copy.version {
+            Some(version) => version.to_string(),
+            None => "version unknown".dimmed().to_string(),
+        };
+        println!(
+            "  • {} {} {}",
+            display_path(&copy.path).bold(),
+            copy.name,
+            version
+        );
+        for marker in &copy.markers {
+            println!(
+                "      {}",
+                format!("{}: {}", marker.kind.describe(), display_path(&marker.file)).dimmed()
+            );
+        }
+        for advisory in &copy.advisories {
+            println!(
+                "      {} {} {}",
+                output::badge(Status::of_severity(advisory.severity)),
+                advisory.id,
+                advisory.title
+            );
+        }
+    }
+    println!(
+        "  {}",
+        "Copied code gets no updates or advisory checks; depend on the crate instead, \
+         or track its upstream by hand."
+            .dimmed()
+    );
+    println!();
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn health_command(
+    manifest_path: Option<String>,
+    format: OutputFormat,
+    output_path: Option<PathBuf>,
+    metrics_out: Option<PathBuf>,
+    update_db: bool,
+    offline: bool,
+    fix: bool,
+    dry_run: bool,
+    plan: Option<String>,
+    limit: usize,
+    system_libs: bool,
+    owners: bool,
+    transitive: bool,
+    enrich: Option<Option<usize>>,
+    scan_embedded: bool,
+    fail_on_new_build_scripts: bool,
+    workspace: bool,
+    output_files: Vec<OutputFile>,
+) -> Result<bool> {
+    let manifest = find_manifest(manifest_path)?;
+    let json = format.is_machine_readable();
+    let passed = health_manifest(
+        manifest.clone(),
+        format,
+        output_path,
+        metrics_out,
+        update_db,
+        offline,
+        fix,
+        dry_run,
+        plan,
+        limit,
+        system_libs,
+        owners,
+        transitive,
+        enrich,
+        scan_embedded,
+        fail_on_new_build_scripts,
+        workspace,
+        output_files,
+    )?;
+    print_network_hint(&manifest, Some(offline), false, json);
+    Ok(passed)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn health_manifest(
+    manifest: Manifest,
+    format: OutputFormat,
+    output_path: Option<PathBuf>,
+    metrics_out: Option<PathBuf>,
+    update_db: bool,
+    offline: bool,
+    fix: bool,
+    dry_run: bool,
+    plan: Option<String>,
+    limit: usize,
+    system_libs: bool,
+    owners: bool,
+    transitive: bool,
+    enrich: Option<Option<usize>>,
+    scan_embedded: bool,
+    fail_on_new_build_scripts: bool,
+    workspace: bool,
+    output_files: Vec<OutputFile>,
+) -> Result<bool> {
+    let json = format.is_machine_readable();
+    if fix && format == OutputFormat::Csv {
+        anyhow::bail!("--fix plans are JSON only; use --json instead of --format csv");
+    }
+    let cargo = cargo_options(&manifest)?;
+    if let Some(plan) = plan {
+        apply_saved_plan("health", manifest, Path::new(&plan), &cargo)?;
+        return Ok(true);
+    }
+    if fix && json && !dry_run {
+        anyhow::bail!("--fix --json needs --dry-run: review the plan, then apply it with --plan");
+    }
+    let package_only = [
+        ("--fix", fix),
+        ("--format csv", format == OutputFormat::Csv),
+        ("--metrics-out", metrics_out.is_some()),
+        ("--output-file", !output_files.is_empty()),
+        ("--system-libs", system_libs),
+        ("--owners", owners),
+        ("--enrich", enrich.is_some()),
+        ("--scan-embedded", scan_embedded),
+        ("--fail-on-new-build-scripts", fail_on_new_build_scripts),
+    ];
+    if workspace_implied(&manifest, workspace, &package_only)? {
+        let options = advisory_db_options(
+            &Config::load(manifest.path.parent().unwrap_or(Path::new(".")))?,
+            update_db,
+            offline,
+        );
+        return health_workspace(manifest, options, offline, transitive, json, limit);
+    }
+
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let config = Config::load(root)?;
+    let lockfile = Lockfile::for_manifest(&manifest)?;
+    let internal = internal_crates(&manifest, &config);
+    // Only --fix picks releases, so only it needs the versions file
+    let policy = if fix {
+        load_policy(&config, root)?.unwrap_or_default()
+    } else {
+        VersionPolicy::default()
+    };
+
+    let db_options = advisory_db_options(&config, update_db, offline);
+    refresh_advisory_archive(&manifest, &config, db_options, json)?;
+    let (checker, _) = HealthChecker::open(&manifest, db_options)?;
+    let checker = checker
+        .with_concurrency(config.concurrency)
+        .with_progress(ProgressMode::detect(json).build(false))
+        .with_internal(internal.clone())
+        .with_transitive(transitive);
+    let mut report = runtime()?.block_on(checker.check(&manifest, lockfile.as_ref()))?;
+    // A dry run leaves the project as it found it, advisory database included
+    if !dry_run {
+        if let Err(e) = checker.source().save() {
+            output::print_error(&format!("Could not save the advisory database: {}", e));
+        }
+    }
+    let metadata = cargo::metadata(&manifest.path, &cargo).ok();
+    report.system_libraries = linked_system_libraries(metadata.as_ref(), system_libs)?;
+    report.build_time = metadata
+        .as_ref()
+        .map(|metadata| build_time_surface(metadata, &manifest.path));
+    // Comparing forks with their releases needs the registry
+    let git = git_dependencies(&manifest, lockfile.as_ref());
+    if !offline && !git.is_empty() {
+        let forks = DependencyChecker::with_provider(
+            CratesIoClient::new().context("building the crates.io client")?,
+        )
+        .with_concurrency(config.concurrency)
+        .with_advisories(AdvisoryIndex::load(&database_path(&manifest)))
+        .with_internal(internal.clone())
+        .with_intentional_forks(config.intentional_forks.clone());
+        report.forks = runtime()?.block_on(forks.check_forks(&git));
+    }
+    if let Some(lockfile) = lockfile.as_ref().filter(|_| !offline) {
+        report.checksums = Some(lockfile_checksums(&manifest, lockfile, config.concurrency)?);
+    }
+    if scan_embedded {
+        report.embedded = embedded_crates(&checker, &manifest, &config, lockfile.as_ref())?;
+    }
+    if let Some(limit) = enrich {
+        let limit = limit.unwrap_or(config.settings().enrich_limit);
+        report.enrichment = Some(enrich_packages(
+            &checker,
+            &manifest,
+            lockfile.as_ref(),
+            &internal,
+            limit,
+            config.concurrency,
+        )?);
+    }
+    if owners {
+        // A crate declared for several targets has one set of owners
+        let mut names: Vec<String> = manifest
+            .get_dependencies()
+            .into_iter()
+            .filter(|(name, spec, _)| spec.is_crates_io() && !internal.contains(name))
+            .map(|(name, _, _)| name)
+            .collect();
+        names.dedup();
+        let owners = fetch_owners(&manifest, names, json)?;
+        report.ownership_changes = ownership_changes(&snapshot_owners(&manifest)?, &owners, &[]);
+    }
+    report.partial |= Cancellation::global().cut_short();
+    let accepted = AcceptedRisks::load(root)?;
+    let now = cache::unix_now();
+    report.accepted = accepted.take_accepted(&mut report.vulnerable, now);
+    let baseline = if fail_on_new_build_scripts {
+        let surface = report
+            .build_time
+            .as_mut()
+            .context("--fail-on-new-build-scripts needs cargo metadata, which couldn't be read")?;
+        let (baseline, label) = build_time_baseline(&manifest)?;
+        let approved = accepted.build_scripts(now);
+        surface.new_since_baseline = Some(
+            surface
+                .new_since(&baseline)
+                .into_iter()
+                .map(|p| p.name.clone())
+                .filter(|name| !approved.contains(name))
+                .collect(),
+        );
+        Some(label)
+    } else {
+        None
+    };
+    // A lockfile that pins other code than crates.io published is Critical
+    let passed = report
+        .build_time
+        .as_ref()
+        .and_then(|surface| surface.new_since_baseline.as_ref())
+        .is_none_or(Vec::is_empty)
+        && report
+            .checksums
+            .as_ref()
+            .is_none_or(|checksums| checksums.mismatches.is_empty());
+    let teams = Teams::load(root)?;
+    if let Some(teams) = &teams {
+        teams.apply_health(&mut report);
+    }
+    if let Some(path) = &metrics_out {
+        write_metrics(path, &Metrics::new(&manifest).with_health(&report), json)?;
+    }
+    output::write_output_files(&output_files, json, |format| match format {
+        FileFormat::Json => output::render_json(&report),
+        FileFormat::Csv => Ok(health_csv(&report)),
+        FileFormat::Markdown => Ok(health_markdown(&report)),
+    })?;
+
+    if json && fix {
+        let plan = Plan::new(&manifest, remediation_actions(&manifest, &report, &policy))?;
+        output::print_json(&plan)?;
+        return Ok(passed);
+    }
+    if format == OutputFormat::Csv {
+        output::write_csv(&health_csv(&report), output_path.as_deref())?;
+        return Ok(passed);
+    }
+    if json {
+        output::print_json(&report)?;
+        return Ok(passed);
+    }
+
+    output::print_header("🏥 cargo-sane health");
+    println!();
+
+    if let Some(name) = manifest.package_name() {
+        output::print_info(&format!("Package: {}", name));
+    }
+    output::print_info(&format!("Manifest: {}", display_path(&manifest.path)));
+    if lockfile.is_none() {
+        output::print_warning(
+            "No Cargo.lock found; versions are estimated and git dependencies are skipped",
+        );
+    }
+    println!();
+
+    if let Some(database) = &report.database {
+        print_database_info(database);
+    }
+    println!(
+        "🛡️  Scanned {} for advisories",
+        plural(report.scanned as u64, "package")
+    );
+    println!();
+    if report.partial {
+        print_partial(report.unchecked, "package");
+    }
+    if let Some(checksums) = &report.checksums {
+        print_checksums(checksums);
+    }
+    if let Some(enrichment) = &report.enrichment {
+        print_enrichment(enrichment, now);
+    }
+
+    print_system_libraries(&report.system_libraries, system_libs);
+    print_embedded(&report.embedded, scan_embedded);
+    if let Some(surface) = &report.build_time {
+        print_build_time(surface, baseline.as_deref(), limit);
+    }
+    print_forks(&report.forks);
+    print_ownership_changes(&report.ownership_changes);
+    print_expired_acceptances(&accepted, now);
+    let summaries: Vec<String> = report.accepted.iter().map(|a| a.summary()).collect();
+    print_accepted(&summaries);
+    print_internal_advisories(&report.internal);
+    if let Some(teams) = &teams {
+        print_unknown_team_crates(teams, &manifest);
+    }
+
+    if report.vulnerable.is_empty() {
+        if report.partial {
+            output::print_info("No known advisories affect the packages looked up");
+        } else {
+            output::print_success("No known advisories affect your dependencies! 🎉");
+        }
+        return Ok(passed);
+    }
+
+    let mut vulnerable: Vec<&AffectedPackage> = report.vulnerable.iter().collect();
+    rank(&mut vulnerable, |package| {
+        Significance::new(
+            Class::Vulnerable,
+            &package.version,
+            package.fix_version().as_ref(),
+        )
+    });
+    let (shown, hidden) = truncate(&vulnerable, limit);
+    // Transitive findings go under the direct dependency bringing them in,
+    // groups in the order of their most significant finding
+    let mut groups: Vec<(&str, Vec<&AffectedPackage>)> = Vec::new();
+    for package in shown {
+        if package.attribution.depth <= 1 {
+            print_affected(package, "  ");
+            continue;
+        }
+        let parent = package.attribution.direct_parent.as_str();
+        match groups.iter_mut().find(|(name, _)| *name == parent) {
+            Some((_, packages)) => packages.push(package),
+            None => groups.push((parent, vec![package])),
+        }
+    }
+    for (parent, packages) in groups {
+        println!("  {} {}", "via".dimmed(), parent.bold());
+        for package in packages {
+            print_affected(package, "    ");
+        }
+    }
+    print_more(hidden);
+    println!();
+    if teams.is_some() {
+        print_advisories_by_team(&report.vulnerable);
+    }
+
+    output::print_warning(&format!(
+        "{} dependencies have known advisories",
+        report.vulnerable.len()
+    ));
+
+    if !fix {
+        return Ok(passed);
+    }
+    println!();
+    let plan = Plan::new(&manifest, remediation_actions(&manifest, &report, &policy))?;
+    print_plan(&plan);
+    if plan.executable().next().is_none() {
+        return Ok(passed);
+    }
+    if dry_run {
+        output::print_info("Dry-run mode: No changes will be made.");
+        return Ok(passed);
+    }
+    apply_plan("health", &plan, manifest, &cargo)?;
+    Ok(passed)
+}
+
+/// `health --workspace`: scan every member against the shared lockfile and
+/// list each member's findings under its name. Returns whether the lockfile's
+/// checksums all match crates.io, as for a single package.
+fn health_workspace(
+    manifest: Manifest,
+    options: DbOptions,
+    offline: bool,
+    transitive: bool,
+    json: bool,
+    limit: usize,
+) -> Result<bool> {
+    let root = manifest
+        .path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+    if manifest.content.workspace.is_none() {
+        anyhow::bail!(
+            "{} is not a workspace root (no [workspace] table)",
+            manifest.path.display()
+        );
+    }
+    let members = manifest.workspace_members()?;
+    let config = Config::load(&root)?;
+    let lockfile = Lockfile::for_manifest(&manifest)?;
+
+    refresh_advisory_archive(&manifest, &config, options, json)?;
+    let (checker, _) = HealthChecker::open(&manifest, options)?;
+    let checker = checker
+        .with_concurrency(config.concurrency)
+        .with_progress(ProgressMode::detect(json).build(false))
+        .with_internal(internal_crates(&manifest, &config))
+        .with_transitive(transitive);
+    let mut report =
+        runtime()?.block_on(checker.check_workspace(&manifest, &members, lockfile.as_ref()))?;
+    if let Err(e) = checker.source().save() {
+        output::print_error(&format!("Could not save the advisory database: {}", e));
+    }
+    // One `cargo metadata` of the root covers every member
+    if let Ok(metadata) = cargo::metadata(&manifest.path, &cargo_options(&manifest)?) {
+        for member in &mut report.members {
+            member.build_time = Some(build_time_surface(&metadata, &member.manifest));
+        }
+    }
+    // The members share one Cargo.lock, so it's verified once
+    if let Some(lockfile) = lockfile.as_ref().filter(|_| !offline) {
+        report.checksums = Some(lockfile_checksums(&manifest, lockfile, config.concurrency)?);
+    }
+    report.partial |= Cancellation::global().cut_short();
+    let accepted = AcceptedRisks::load(&root)?;
+    let now = cache::unix_now();
+    for member in &mut report.members {
+        member.accepted = accepted.take_accepted(&mut member.vulnerable, now);
+    }
+    // A lockfile that pins other code than crates.io published is Critical
+    let passed = report
+        .checksums
+        .as_ref()
+        .is_none_or(|checksums| checksums.mismatches.is_empty());
+
+    if json {
+        output::print_json(&report)?;
+        return Ok(passed);
+    }
+
+    output::print_header("🏥 cargo-sane health --workspace");
+    println!();
+    output::print_info(&format!("Workspace: {}", display_path(&manifest.path)));
+    output::print_info(&format!("Members: {}", report.members.len()));
+    if lockfile.is_none() {
+        output::print_warning(
+            "No Cargo.lock found; versions are estimated and git dependencies are skipped",
+        );
+    }
+    println!();
+    if let Some(database) = &report.database {
+        print_database_info(database);
+    }
+    println!(
+        "🛡️  Scanned {} for advisories",
+        plural(report.scanned as u64, "package")
+    );
+    println!();
+    if report.partial {
+        print_partial(report.unchecked, "package");
+    }
+    if let Some(checksums) = &report.checksums {
+        print_checksums(checksums);
+    }
+    print_expired_acceptances(&accepted, now);
+
+    for member in &report.members {
+        let name = member
+            .package
+            .clone()
+            .unwrap_or_else(|| member.manifest.display().to_string());
+        if member.vulnerable.is_empty() {
+            println!(
+                "{} {}",
+                output::plain("📦").bold(),
+                format!("{}: no known advisories", name).dimmed()
+            );
+            continue;
+        }
+        println!(
+            "{} {} {}",
+            output::plain("📦").bold(),
+            name.bold(),
+            format!(
+                "({})",
+                plural(member.vulnerable.len() as u64, "affected package")
+            )
+            .bad()
+        );
+        let mut vulnerable: Vec<&AffectedPackage> = member.vulnerable.iter().collect();
+        rank(&mut vulnerable, |package| {
+            Significance::new(
+                Class::Vulnerable,
+                &package.version,
+                package.fix_version().as_ref(),
+            )
+        });
+        let (shown, hidden) = truncate(&vulnerable, limit);
+        for package in shown {
+            print_affected(package, "  ");
+        }
+        print_more(hidden);
+    }
+    println!();
+
+    let affected: BTreeSet<(&str, &Version)> = report
+        .vulnerable()
+        .map(|package| (package.name.as_str(), &package.version))
+        .collect();
+    if affected.is_empty() {
+        if report.partial {
+            output::print_info("No known advisories affect the packages looked up");
+        } else {
+            output::print_success("No known advisories affect any member! 🎉");
+        }
+    } else {
+        output::print_warning(&format!(
+            "{} have known advisories",
+            plural(affected.len() as u64, "package version")
+        ));
+    }
+    Ok(passed)
+}
+
+/// Compare Cargo.lock's checksums with the ones the crates.io index
+/// publishes
+fn lockfile_checksums(
+    manifest: &Manifest,
+    lockfile: &Lockfile,
+    concurrency: usize,
+) -> Result<ChecksumReport> {
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let concurrency = match concurrency {
+        0 => DEFAULT_CONCURRENCY,
+        n => n,
+    };
+    let index = SparseIndexClient::new()?;
+    let published = runtime()?.block_on(published_checksums(
+        &index,
+        &crates_io_packages(lockfile),
+        root,
+        concurrency,
+    ))?;
+    Ok(verify_checksums(lockfile, &published))
+}
+
+fn print_checksums(report: &ChecksumReport) {
+    let compared = report.verified + report.mismatches.len() + report.unverified.len();
+    if !report.mismatches.is_empty() {
+        println!(
+            "{}",
+            output::plain("🚨 Checksum mismatches with crates.io:")
+                .bad()
+                .bold()
+        );
+        for mismatch in &report.mismatches {
+            println!(
+                "  {} {} {}",
+                output::label(Status::Critical),
+                mismatch.name.bold(),
+                mismatch.version
+            );
+            println!("      Cargo.lock: {}", mismatch.locked);
+            println!("      crates.io:  {}", mismatch.published);
+        }
+        println!(
+            "{}",
+            "The code Cargo.lock pins is not what crates.io published for these versions. \
+             That takes a tampered registry or mirror, a corrupted or hand-edited Cargo.lock, \
+             or source replacement serving different code. Don't build or ship from this \
+             lockfile until you know which."
+                .bad()
+        );
+        println!();
+    }
+
+    println!(
+        "🔏 Checksums: {} of {} match the crates.io index",
+        report.verified,
+        plural(compared as u64, "package")
+    );
+    let mut unverified: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for package in &report.unverified {
+        unverified
+            .entry(package.reason.as_str())
+            .or_default()
+            .push(format!("{} {}", package.name, package.version));
+    }
+    for (reason, packages) in unverified {
+        println!(
+            "  {}",
+            format!("Not verified ({}): {}", reason, packages.join(", ")).dimmed()
+        );
+    }
+    if !report.skipped.is_empty() {
+        let skipped: Vec<String> = report
+            .skipped
+            .iter()
+            .map(|s| format!("{} {} ({})", s.name, s.version, s.reason))
+            .collect();
+        println!(
+            "  {}",
+            format!("Not from crates.io, skipped: {}", skipped.join(", ")).dimmed()
+        );
+    }
+    println!();
+}
+
+/// Native libraries the project links, with their installed versions when
+/// `probe` is set. Without `cargo metadata` there is nothing to go on, so
+/// the list is empty.
+fn linked_system_libraries(metadata: Option<&Metadata>, probe: bool) -> Result<Vec<SystemLibrary>> {
+    let Some(metadata) = metadata else {
+        return Ok(Vec::new());
+    };
+    let known = known_libraries()?;
+    Ok(system_libraries(metadata, &known, |module| {
+        if probe {
+            pkg_config_version(module)
+        } else {
+            None
+        }
+    }))
+}
+
+/// The build-time code of the newest snapshot that records it, with a label
+/// describing the snapshot
+fn build_time_baseline(manifest: &Manifest) -> Result<(BuildTimeSurface, String)> {
+    let store = SnapshotStore::for_manifest(manifest);
+    for entry in store.list()?.iter().rev() {
+        let snapshot = store.load(entry)?;
+        if let Some(surface) = snapshot.health.and_then(|health| health.build_time) {
+            return Ok((surface, format!("snapshot {}", entry.label())));
+        }
+    }
+    anyhow::bail!(
+        "No snapshot records build-time code to compare with; run `cargo sane snapshot save` first"
+    )
+}
+
+/// The dependencies running build-time code under each direct dependency,
+/// and which of them a baseline didn't have
+fn print_build_time(surface: &BuildTimeSurface, baseline: Option<&str>, limit: usize) {
+    println!(
+        "{} {} of {} run build-time code",
+        output::plain("🏗️  Build-time code:").bold(),
+        format_count(surface.packages.len()),
+        plural(surface.total as u64, "package")
+    );
+    let groups: Vec<_> = surface.by_parent().into_iter().collect();
+    let (shown, hidden) = truncate(&groups, limit);
+    for (parent, packages) in shown {
+        let packages: Vec<String> = packages
+            .iter()
+            .map(|p| {
+                let code: Vec<&str> = p.code.iter().map(|c| c.label()).collect();
+                format!("{} {}", p.name, format!("({})", code.join(", ")).dimmed())
+            })
+            .collect();
+        println!("  • {}: {}", parent.bold(), packages.join(", "));
+    }
+    print_more(hidden);
+    if let (Some(new), Some(baseline)) = (&surface.new_since_baseline, baseline) {
+        if new.is_empty() {
+            println!("  {}", format!("Nothing new since {}", baseline).good());
+        } else {
+            println!("  New since {}: {}", baseline, new.join(", ").bad());
+            println!(
+                "  {}",
+                "Review them, then `cargo sane accept --build-script <crate>` or save a new snapshot"
+                    .dimmed()
+            );
+        }
+    }
+    println!();
+}
+
+fn print_system_libraries(libraries: &[SystemLibrary], probed: bool) {
+    if libraries.is_empty() {
+        return;
+    }
+
+    println!("{}", output::plain("🔗 System libraries:").bold());
+    for library in libraries {
+        let links = match &library.links {
+            Some(links) if *links != library.name => format!(" (links \"{}\")", links),
+            _ => String::new(),
+        };
+        let status = if library.bundled {
+            "bundled, not the system copy".dimmed().to_string()
+        } else {
+            match (&library.installed, &library.min_safe) {
+                (Some(installed), Some(min_safe)) if library.unsafe_install => format!(
+                    "installed {}, {}",
+                    installed,
+                    format!("below the minimum safe {}", min_safe).bad()
+                ),
+                (Some(installed), _) => format!("installed {}", installed.good()),
+                (None, Some(_)) if probed => "not found by pkg-config".dimmed().to_string(),
+                (None, _) => String::new(),
+            }
+        };
+        let separator = if status.is_empty() { "" } else { " — " };
+        let library_name = if library.name == library.krate {
+            "a native library".to_string()
+        } else {
+            library.name.bold().to_string()
+        };
+        println!(
+            "  • links {}{} via {} {}{}{}",
+            library_name, links, library.krate, library.version, separator, status
+        );
+        if library.unsafe_install {
+            if let Some(note) = &library.note {
+                println!("    {}", note.dimmed());
+            }
+        }
+    }
+    println!(
+        "{}",
+        "Vulnerabilities in system libraries are fixed by OS updates, not `cargo update`.".dimmed()
+    );
+    if !probed {
+        println!(
+            "{}",
+            "Run with --system-libs to compare installed versions via pkg-config.".dimmed()
+        );
+    }
+    println!();
+}
+
+/// Move each vulnerable dependency to its first patched release: through
+/// Cargo.lock when the requirement already allows it, otherwise by raising
+/// the requirement in every section declaring the crate. A crate the
+/// versions file tracks a series of only moves within it.
+fn remediation_actions(
+    manifest: &Manifest,
+    report: &HealthReport,
+    policy: &VersionPolicy,
+) -> Vec<PlannedAction> {
+    let mut actions = Vec::new();
+    for package in &report.vulnerable {
+        let ids: Vec<&str> = package
+            .advisories
+            .iter()
+            .filter(|a| a.informational.is_none())
+            .map(|a| a.id.as_str())
+            .collect();
+        if ids.is_empty() {
+            continue;
+        }
+        let mut reason = ids.join(", ");
+        let current = package.version.to_string();
+
+        let series = policy.track(&package.name);
+        let fix = match series {
+            Some(series) => package.fix_version_in(series),
+            None => package.fix_version(),
+        };
+        let target = match fix {
+            Some(target) if package.source == DependencySource::Registry => target,
+            _ => {
+                if let Some(series) = series.filter(|_| package.fix_version().is_some()) {
+                    reason = format!("{}; no patched release in the tracked {}", reason, series);
+                }
+                actions.push(PlannedAction::unavailable(&package.name, &current, reason));
+                continue;
+            }
+        };
+        if is_compatible(&package.version, &target) {
+            actions.push(PlannedAction::lockfile_update(
+                manifest,
+                &package.name,
+                &package.version,
+                &target,
+                reason,
+            ));
+            continue;
+        }
+
+        let edits: Vec<PlannedAction> = manifest
+            .declarations()
+            .into_iter()
+            .filter(|(_, name, spec)| name == &package.name && spec.is_crates_io())
+            .filter_map(|(section, name, spec)| {
+                Some(PlannedAction::manifest_edit(
+                    &section,
+                    &name,
+                    spec.version()?,
+                    &target.to_string(),
+                    reason.clone(),
+                ))
+            })
+            .collect();
+        if edits.is_empty() {
+            actions.push(PlannedAction::unavailable(&package.name, &current, reason));
+        } else {
+            actions.extend(edits);
+        }
+    }
+    actions
+}
+
+/// The affected packages again, under the team owning the direct
+/// dependency that brings each in
+fn print_advisories_by_team(vulnerable: &[AffectedPackage]) {
+    let packages: Vec<&AffectedPackage> = vulnerable.iter().collect();
+    println!("{}", output::plain("👥 Advisories by team:").bold());
+    for (team, packages) in group_by_team(&packages, |p| p.owner.as_deref()) {
+        println!("  {} ({})", team.bold(), packages.len());
+        for package in packages {
+            let ids: Vec<&str> = package.advisories.iter().map(|a| a.id.as_str()).collect();
+            let via = if package.attribution.depth > 1 {
+                format!(
+                    " {}",
+                    format!("via {}", package.attribution.direct_parent).dimmed()
+                )
+            } else {
+                String::new()
+            };
+            println!(
+                "    • {} {}{}: {}",
+                package.name.bold(),
+                package.version,
+                via,
+                ids.join(", ")
+            );
+        }
+    }
+    println!();
+}
+
+/// Refresh the advisory database archive the config names, if any, when the
+/// database itself would be. A failure leaves the previous archive in place
+/// and only warns, unless the run must refresh (`--update-db` or a strict
+/// config).
+fn refresh_advisory_archive(
+    manifest: &Manifest,
+    config: &Config,
+    options: DbOptions,
+    json: bool,
+) -> Result<()> {
+    let Some(url) = &config.advisory_db_archive else {
+        return Ok(());
+    };
+    let progress = ProgressMode::detect(json).build(false);
+    let path = archive_path(manifest);
+    match runtime()?.block_on(refresh_archive(url, &path, options, progress.as_ref())) {
+        Ok(_) => Ok(()),
+        Err(e) if options.mode == DbMode::Update || options.strict => {
+            Err(e.context("Could not refresh the advisory database archive"))
+        }
+        Err(e) => {
+            if !json {
+                output::print_warning(&format!(
+                    "Could not refresh the advisory database archive: {:#}",
+                    e
+                ));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Look the most exposed scanned packages up on crates.io, within `limit`
+fn enrich_packages<A: AdvisorySource>(
+    checker: &HealthChecker<A>,
+    manifest: &Manifest,
+    lockfile: Option<&Lockfile>,
+    internal: &InternalCrates,
+    limit: usize,
+    concurrency: usize,
+) -> Result<Enrichment> {
+    let parsers = untrusted_input_parsers()?;
+    let candidates = checker
+        .scanned_packages(manifest, lockfile)
+        .into_iter()
+        .filter(|(name, _, _)| !internal.contains(name))
+        .map(|(name, version, direct)| Candidate::new(name, version, direct, &parsers))
+        .collect();
+    let concurrency = match concurrency {
+        0 => DEFAULT_CONCURRENCY,
+        n => n,
+    };
+    let client = CratesIoClient::new().context("building the crates.io client")?;
+    Ok(runtime()?.block_on(enrich(&client, candidates, limit, concurrency)))
+}
+
+/// What `--enrich` looked up, and plainly how much of the scan it covers
+fn print_enrichment(enrichment: &Enrichment, now: u64) {
+    let looked_up = enrichment.enriched.len() + enrichment.failed.len();
+    if enrichment.advisory_only.is_empty() {
+        println!(
+            "🔎 Looked up all {} on crates.io",
+            plural(looked_up as u64, "scanned package")
+        );
+    } else {
+        println!(
+            "🔎 Looked up the {} most exposed of {} on crates.io",
+            looked_up,
+            plural(
+                (looked_up + enrichment.advisory_only.len()) as u64,
+                "scanned package"
+            )
+        );
+        println!(
+            "   {}",
+            format!(
+                "The other {} were only matched against advisories: this is not a full audit. \
+                 Raise --enrich-limit to cover more.",
+                enrichment.advisory_only.len()
+            )
+            .caution()
+        );
+    }
+    if !enrichment.failed.is_empty() {
+        output::print_warning(&format!(
+            "Could not look up {}: {}",
+            plural(enrichment.failed.len() as u64, "package"),
+            enrichment.failed.join(", ")
+        ));
+    }
+    for package in &enrichment.enriched {
+        let concerns = package.concerns(now);
+        if !concerns.is_empty() {
+            println!(
+                "  • {} {}: {}",
+                package.name.bold(),
+                package.version,
+                concerns.join("; ")
+            );
+        }
+    }
+    println!();
+}
+
+fn print_database_info(database: &DatabaseInfo) {
+    let fetched = match database.fetched_at {
+        Some(at) => format!("fetched {}", format_since(at, cache::unix_now())),
+        None => "never fetched".to_string(),
+    };
+    output::print_info(&format!(
+        "Advisory DB: {}, {}, {} advisories",
+        database.source,
+        fetched,
+        format_count(database.advisory_count)
+    ));
+
+    if database.stale {
+        let message = format!(
+            "ADVISORY DATABASE IS STALE: older than {} days{}",
+            database.max_age_days,
+            if database.offline {
+                " (accepted with --offline)"
+            } else {
+                "; run `cargo sane health --update-db`"
+            }
+        );
+        output::print_warning(&message.caution().bold().to_string());
+    }
+    if database.missing > 0 {
+        output::print_warning(&format!(
+            "{} packages are not in the local advisory database and were not checked",
+            database.missing
+        ));
+    }
+    println!();
+}
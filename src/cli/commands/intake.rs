@@ -0,0 +1,61 @@
+//! `cargo sane intake`: dependencies added since a git revision, as a
+//! review checklist
+
+use crate::analyzer::intake::{added_dependencies, Intake};
+use crate::analyzer::redundancy::redundancy_groups;
+use crate::cli::commands::{find_manifest, runtime};
+use crate::cli::markdown::intake_markdown;
+use crate::cli::output;
+use crate::core::config::Config;
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::utils::advisories::OsvClient;
+use crate::utils::cache;
+use crate::utils::crates_io::CratesIoClient;
+use crate::utils::git::GitRepo;
+use crate::Result;
+use anyhow::Context;
+use std::path::Path;
+
+/// Review the crates.io dependencies the manifest declares now and didn't
+/// at `from`, printing a Markdown checklist per crate for the pull request,
+/// or the findings as JSON
+pub fn intake_command(manifest_path: Option<String>, from: &str, json: bool) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let repo = GitRepo::discover(root).context(
+        "cargo sane intake compares Cargo.toml against git history, but this isn't a git repository",
+    )?;
+    let before = repo
+        .file_at(from, &manifest.path)?
+        .map(|text| Manifest::parse(manifest.path.clone(), &text))
+        .transpose()
+        .with_context(|| format!("Failed to parse Cargo.toml at {}", from))?;
+    let added = added_dependencies(before.as_ref(), &manifest);
+
+    let reviews = if added.is_empty() {
+        Vec::new()
+    } else {
+        let config = Config::load(root)?;
+        let lockfile = Lockfile::for_manifest(&manifest)?;
+        let groups = redundancy_groups()?;
+        let intake = Intake {
+            manifest: &manifest,
+            lockfile: lockfile.as_ref(),
+            policy: &config.licenses,
+            groups: &groups,
+            concurrency: config.settings().concurrency,
+            now: cache::unix_now(),
+        };
+        let crates_io = CratesIoClient::new().context("building the crates.io client")?;
+        let osv = OsvClient::new()?;
+        runtime()?.block_on(intake.review(added, &crates_io, &osv))
+    };
+
+    if json {
+        output::print_json(&serde_json::json!({ "from": from, "added": reviews }))?;
+    } else {
+        print!("{}", intake_markdown(&reviews, from));
+    }
+    Ok(())
+}
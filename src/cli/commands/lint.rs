@@ -0,0 +1,393 @@
+//! `cargo sane lint`: requirements that make builds drift, and `[features]`
+//! entries naming dependencies that aren't there
+
+use crate::analyzer::feature_consistency::{
+    feature_aliases, find_feature_inconsistencies, FeatureInconsistency,
+};
+use crate::analyzer::feature_table::{find_feature_issues, FeatureFinding};
+use crate::analyzer::lint::{lint_manifest, LintSeverity};
+use crate::cli::commands::update::describe_edit;
+use crate::cli::commands::{find_manifest, record_audit};
+use crate::cli::output::{self, Paint};
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::{Manifest, ManifestText};
+use crate::core::workspace::Workspace;
+use crate::updater::DependencyUpdater;
+use crate::utils::audit::{AuditChange, AuditEntry};
+use crate::utils::formatting::display_path;
+use crate::Result;
+use colored::{ColoredString, Colorize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Lint requirement styles that defeat reproducible builds. Returns whether
+/// the manifest passed, i.e. no warnings or errors remain.
+pub fn lint_command(
+    manifest_path: Option<String>,
+    fix: bool,
+    unify_features: bool,
+    json: bool,
+) -> Result<bool> {
+    let manifest = find_manifest(manifest_path)?;
+    let lockfile = Lockfile::for_manifest(&manifest)?;
+    let mut findings = lint_manifest(&manifest, lockfile.as_ref());
+    let text = ManifestText::read(&manifest.path)?;
+    let mut feature_findings = find_feature_issues(&manifest, &text.content);
+
+    // Members can only disagree with each other from the workspace root
+    let workspace = match manifest.content.workspace {
+        Some(_) => Workspace::load(manifest.clone())?,
+        None => None,
+    };
+    let mut inconsistencies = match &workspace {
+        Some(workspace) => find_feature_inconsistencies(workspace, &feature_aliases()?),
+        None => Vec::new(),
+    };
+    let mut unified = Vec::new();
+    if unify_features {
+        let Some(workspace) = &workspace else {
+            anyhow::bail!(
+                "{} is not a workspace root (no [workspace] table)",
+                manifest.path.display()
+            );
+        };
+        unified = unify_declarations(workspace, &inconsistencies)?;
+        inconsistencies.retain(|i| !unified.contains(&i.name));
+    }
+
+    let mut fixed = Vec::new();
+    let mut removed_entries: Vec<FeatureFinding> = Vec::new();
+    if fix
+        && (findings.iter().any(|f| f.is_fixable())
+            || feature_findings.iter().any(|f| f.is_fixable()))
+    {
+        let mut updater = DependencyUpdater::new(manifest.clone())?;
+        for finding in findings.iter().filter(|f| f.is_fixable()) {
+            let version = finding.resolved_version.as_deref().unwrap_or_default();
+            let edit = if finding.workspace {
+                updater.update_workspace_dependency(&finding.name, version)
+            } else {
+                updater.update_declaration(&finding.section, &finding.name, version)
+            };
+            match edit {
+                Ok(edit) => fixed.push(edit),
+                Err(e) => output::print_warning(&format!("Could not fix {}: {}", finding.name, e)),
+            }
+        }
+        let (removable, kept): (Vec<_>, Vec<_>) = feature_findings
+            .into_iter()
+            .partition(FeatureFinding::is_fixable);
+        feature_findings = kept;
+        for finding in removable {
+            let (Some(feature), Some(reference)) = (&finding.feature, &finding.reference) else {
+                continue;
+            };
+            match updater.remove_feature_entry(feature, reference) {
+                Ok(()) => removed_entries.push(finding),
+                Err(e) => {
+                    output::print_warning(&format!("Could not fix {}: {}", feature, e));
+                    feature_findings.push(finding);
+                }
+            }
+        }
+        let backup = updater.save()?;
+        let mut changes: Vec<AuditChange> = fixed.iter().map(AuditChange::from).collect();
+        changes.extend(removed_entries.iter().map(|f| AuditChange {
+            name: f.feature.clone().unwrap_or_default(),
+            old: f.reference.clone(),
+            new: None,
+            section: "features".to_string(),
+        }));
+        record_audit(
+            &manifest.path,
+            AuditEntry::new("lint", &manifest.path)
+                .with_changes(changes)
+                .with_backup(Some(backup)),
+        );
+        findings.retain(|f| {
+            !(f.is_fixable()
+                && fixed.iter().any(|e| {
+                    e.name == f.name && e.section == f.section && e.workspace == f.workspace
+                }))
+        });
+    }
+
+    let passed = findings.iter().all(|f| f.severity < LintSeverity::Warning)
+        && feature_findings
+            .iter()
+            .all(|f| f.severity < LintSeverity::Warning)
+        && inconsistencies.is_empty();
+
+    if json {
+        output::print_json(&serde_json::json!({
+            "findings": findings,
+            "fixed": fixed,
+            "feature_findings": feature_findings,
+            "removed_feature_entries": removed_entries,
+            "feature_inconsistencies": inconsistencies,
+            "unified": unified,
+        }))?;
+        return Ok(passed);
+    }
+
+    output::print_header("🧠 cargo-sane lint");
+    println!();
+    output::print_info(&format!("Manifest: {}", display_path(&manifest.path)));
+    if lockfile.is_none() {
+        output::print_warning("No Cargo.lock found; suggestions need resolved versions");
+    }
+    println!();
+
+    for edit in &fixed {
+        println!("  ✓ Pinned {}", describe_edit(edit));
+    }
+    for name in &unified {
+        println!(
+            "  ✓ Moved {} to [workspace.dependencies], inherited by every member",
+            name.bold()
+        );
+    }
+    for finding in &removed_entries {
+        println!(
+            "  ✓ Removed \"{}\" from feature {}",
+            finding.reference.as_deref().unwrap_or_default(),
+            finding.feature.as_deref().unwrap_or_default().bold()
+        );
+    }
+    if !fixed.is_empty() || !unified.is_empty() || !removed_entries.is_empty() {
+        println!();
+    }
+    print_feature_inconsistencies(&inconsistencies);
+    print_feature_findings(&feature_findings);
+
+    if findings.is_empty() {
+        if inconsistencies.is_empty() && feature_findings.is_empty() {
+            output::print_success("No reproducibility issues found! 🎉");
+        }
+        return Ok(passed);
+    }
+
+    for finding in &findings {
+        let severity = severity_label(finding.severity);
+        let line = finding
+            .line
+            .map(|l| format!(" (line {})", l))
+            .unwrap_or_default();
+        println!(
+            "  {}: {} [{}]{}: {}",
+            severity,
+            finding.name.bold(),
+            finding.table(),
+            line.dimmed(),
+            finding.message
+        );
+        if let Some(root) = &finding.inherited_from {
+            println!(
+                "      {}",
+                format!(
+                    "inherited from [workspace.dependencies] in {}",
+                    display_path(root)
+                )
+                .dimmed()
+            );
+        }
+        if let Some(suggestion) = &finding.suggestion {
+            println!("      suggestion: {}", suggestion.cyan());
+        }
+    }
+    println!();
+
+    if findings.iter().any(|f| f.is_fixable()) {
+        println!(
+            "{}",
+            "Run `cargo sane lint --fix` to pin wildcard requirements.".dimmed()
+        );
+    }
+    Ok(passed)
+}
+
+pub(super) fn severity_label(severity: LintSeverity) -> ColoredString {
+    match severity {
+        LintSeverity::Error => severity.to_string().bad().bold(),
+        LintSeverity::Warning => severity.to_string().caution().bold(),
+        LintSeverity::Info => severity.to_string().blue().bold(),
+    }
+}
+
+/// Print what's wrong with the `[features]` table, one reference per line
+fn print_feature_findings(findings: &[FeatureFinding]) {
+    if findings.is_empty() {
+        return;
+    }
+    for finding in findings {
+        let location = match &finding.feature {
+            Some(feature) => format!("features.{}", feature),
+            None => "features".to_string(),
+        };
+        let line = finding
+            .line
+            .map(|l| format!(" (line {})", l))
+            .unwrap_or_default();
+        println!(
+            "  {}: {} [{}]{}: {}",
+            severity_label(finding.severity),
+            finding.name.bold(),
+            location,
+            line.dimmed(),
+            finding.message
+        );
+        if let Some(suggestion) = &finding.suggestion {
+            println!("      suggestion: {}", suggestion.cyan());
+        }
+    }
+    println!();
+    if findings.iter().any(FeatureFinding::is_fixable) {
+        println!(
+            "{}",
+            "Run `cargo sane lint --fix` to remove the dead [features] entries.".dimmed()
+        );
+        println!();
+    }
+}
+
+/// Promote each inconsistently declared dependency to
+/// `[workspace.dependencies]` with its unified declaration, and have every
+/// member declaring it inherit that instead. Nothing is written unless
+/// every edit succeeds. Returns the names promoted.
+fn unify_declarations(
+    workspace: &Workspace,
+    inconsistencies: &[FeatureInconsistency],
+) -> Result<Vec<String>> {
+    let existing = workspace
+        .root
+        .content
+        .workspace
+        .as_ref()
+        .map(|table| &table.dependencies);
+    type Editors = BTreeMap<PathBuf, (DependencyUpdater, Vec<AuditChange>)>;
+    fn editor<'a>(
+        updaters: &'a mut Editors,
+        path: &Path,
+    ) -> Result<&'a mut (DependencyUpdater, Vec<AuditChange>)> {
+        if !updaters.contains_key(path) {
+            let updater = DependencyUpdater::new(Manifest::from_path(path)?)?;
+            updaters.insert(path.to_path_buf(), (updater, Vec::new()));
+        }
+        Ok(updaters.get_mut(path).expect("inserted above"))
+    }
+    let mut updaters = Editors::new();
+
+    let mut unified = Vec::new();
+    for inconsistency in inconsistencies {
+        let name = &inconsistency.name;
+        if existing.is_some_and(|deps| deps.contains_key(name)) {
+            output::print_warning(&format!(
+                "{} is already in [workspace.dependencies]; unify its declarations by hand",
+                name
+            ));
+            continue;
+        }
+        for declaration in &inconsistency.declarations {
+            let (updater, changes) = editor(&mut updaters, &declaration.manifest)?;
+            updater.inherit_from_workspace(&declaration.section, name, &[])?;
+            changes.push(AuditChange {
+                name: name.clone(),
+                old: declaration.requirement.clone(),
+                new: Some("{ workspace = true }".to_string()),
+                section: declaration.section.to_string(),
+            });
+        }
+        let spec = inconsistency.unified.to_toml();
+        let (updater, changes) = editor(&mut updaters, &workspace.root.path)?;
+        updater.add_workspace_dependency(name, &spec)?;
+        changes.push(AuditChange {
+            name: name.clone(),
+            old: None,
+            new: Some(spec),
+            section: "workspace.dependencies".to_string(),
+        });
+        unified.push(name.clone());
+    }
+
+    for (path, (updater, changes)) in updaters {
+        let backup = updater.save()?;
+        record_audit(
+            &path,
+            AuditEntry::new("lint", &path)
+                .with_changes(changes)
+                .with_backup(Some(backup)),
+        );
+    }
+    Ok(unified)
+}
+
+fn print_feature_inconsistencies(inconsistencies: &[FeatureInconsistency]) {
+    if inconsistencies.is_empty() {
+        return;
+    }
+
+    println!(
+        "{}",
+        output::plain("🧩 Dependencies the workspace members declare differently:")
+            .caution()
+            .bold()
+    );
+    for inconsistency in inconsistencies {
+        let mut issues = Vec::new();
+        if inconsistency.features_differ {
+            issues.push("features differ");
+        }
+        if inconsistency.default_features_differ {
+            issues.push("default-features differs");
+        }
+        if !inconsistency.legacy_names.is_empty() {
+            issues.push("legacy feature names");
+        }
+        println!(
+            "  {}: {} ({})",
+            "warning".caution().bold(),
+            inconsistency.name.bold(),
+            issues.join(", ")
+        );
+        for declaration in &inconsistency.declarations {
+            let features = if declaration.features.is_empty() {
+                "no features".to_string()
+            } else {
+                declaration.features.join(", ")
+            };
+            let defaults = if declaration.default_features {
+                ""
+            } else {
+                ", no default features"
+            };
+            let inherited = if declaration.inherited {
+                ", inherited"
+            } else {
+                ""
+            };
+            println!(
+                "      {} [{}]: {}{}{}",
+                declaration.member, declaration.section, features, defaults, inherited
+            );
+        }
+        for legacy in &inconsistency.legacy_names {
+            println!(
+                "      {} spells {} as {}",
+                legacy.member,
+                legacy.feature.cyan(),
+                legacy.legacy
+            );
+        }
+        println!("      suggestion:");
+        for line in inconsistency.snippet().lines() {
+            println!("        {}", line.cyan());
+        }
+    }
+    println!();
+    println!(
+        "{}",
+        "Run `cargo sane lint --unify-features` to promote them to [workspace.dependencies]."
+            .dimmed()
+    );
+    println!();
+}
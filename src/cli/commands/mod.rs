@@ -0,0 +1,308 @@
+//! Command implementations, a module per command; what several of them
+//! share lives here
+
+mod accept;
+mod audit;
+mod caches;
+mod check;
+mod clean;
+mod compare;
+mod defaults;
+mod fix;
+mod fmt_deps;
+mod health;
+mod intake;
+mod lint;
+mod report;
+mod size;
+mod snapshot;
+mod snooze;
+mod tour;
+mod update;
+
+pub use accept::accept_command;
+pub use audit::audit_command;
+pub use caches::{cache_clear_command, cache_gc_command, cache_status_command};
+pub use check::{check_command, CheckOptions};
+pub use clean::clean_command;
+pub use compare::compare_command;
+pub use defaults::{defaults_command, schema_command};
+pub use fix::{apply_command, fix_command};
+pub(crate) use fix::{print_fix_outcome, run_conflict_fixes, FixOutcome};
+pub use fmt_deps::fmt_deps_command;
+pub use health::health_command;
+pub use intake::intake_command;
+pub use lint::lint_command;
+pub use report::report_command;
+pub use size::size_command;
+pub use snapshot::{snapshot_diff_command, snapshot_list_command, snapshot_save_command};
+pub use snooze::snooze_command;
+pub use tour::tour_command;
+pub use update::update_command;
+
+use crate::analyzer::internal::InternalCrates;
+use crate::analyzer::lint::LintSeverity;
+use crate::analyzer::snapshot::ProjectReport;
+use crate::analyzer::teams::Teams;
+use crate::cli::hints::{self, FastPaths};
+use crate::cli::metrics::Metrics;
+use crate::cli::output::{self, Paint};
+use crate::core::advisory::Severity;
+use crate::core::config::Config;
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::core::policy::VersionPolicy;
+use crate::core::workspace::Workspace;
+use crate::utils::advisory_db::{DbMode, DbOptions};
+use crate::utils::audit::{AuditEntry, AuditLog};
+use crate::utils::cargo::{self, CargoOptions, Metadata};
+use crate::utils::formatting::{display_path, format_count, plural};
+use crate::utils::timings;
+use crate::utils::versions_file::load_versions_file;
+use crate::Result;
+use anyhow::Context;
+use colored::Colorize;
+use std::path::Path;
+
+/// Append `entry` to the audit log of the project owning `project`, after
+/// checking the edited files still parse, unless the config turns the log
+/// off. The change has already happened, so a failure to log is reported
+/// but doesn't fail the command.
+pub(crate) fn record_audit(project: &Path, entry: AuditEntry) {
+    if entry.changes.is_empty() {
+        return;
+    }
+    let root = project.parent().unwrap_or(Path::new("."));
+    if Config::load(root).is_ok_and(|config| config.disable_audit_log) {
+        return;
+    }
+    let log = AuditLog::for_manifest(project);
+    if let Err(e) = log.append(&entry.verified()) {
+        output::print_error(&format!("Could not write the audit log: {:#}", e));
+    }
+}
+
+/// The project's own crates, unless the config has them checked like any
+/// other dependency
+fn internal_crates(manifest: &Manifest, config: &Config) -> InternalCrates {
+    if config.treat_internal_as_external {
+        return InternalCrates::default();
+    }
+    let lockfile = Lockfile::for_manifest(manifest).ok().flatten();
+    InternalCrates::discover(manifest, lockfile.as_ref())
+}
+
+fn write_metrics(path: &Path, metrics: &Metrics, quiet: bool) -> Result<()> {
+    std::fs::write(path, metrics.render())
+        .context(format!("Failed to write {}", path.display()))?;
+    if !quiet {
+        output::print_info(&format!("Metrics written to {}", display_path(path)));
+        println!();
+    }
+    Ok(())
+}
+
+/// Say that `--timeout` stopped lookups, with how many of the main ones
+/// were never made
+fn print_partial(unchecked: usize, noun: &str) {
+    let missing = if unchecked > 0 {
+        format!("{} not looked up", plural(unchecked as u64, noun))
+    } else {
+        "some lookups were never made".to_string()
+    };
+    output::print_warning(&format!(
+        "Stopped at the --timeout: {}, so these results are partial",
+        missing
+    ));
+    println!();
+}
+
+/// Footer for a section cut short by `--limit`
+fn print_more(hidden: usize) {
+    if hidden > 0 {
+        println!(
+            "  {}",
+            format!("… and {} more (use --limit 0)", format_count(hidden)).dimmed()
+        );
+    }
+}
+
+/// The organization's versions file, when the config names one
+fn load_policy(config: &Config, root: &Path) -> Result<Option<VersionPolicy>> {
+    match &config.versions_file {
+        Some(source) => Ok(Some(runtime()?.block_on(load_versions_file(source, root))?)),
+        None => Ok(None),
+    }
+}
+
+/// Point a run that waited long on the network at the fast paths the
+/// configuration of `manifest` leaves off: `--offline` when the command
+/// takes it (`offline`), and the check cache when it reads through it
+fn print_network_hint(
+    manifest: &Manifest,
+    offline: Option<bool>,
+    check_cache: bool,
+    machine_readable: bool,
+) {
+    let spent = timings::network_time();
+    if spent < hints::SLOW_NETWORK {
+        return;
+    }
+    let config = manifest
+        .path
+        .parent()
+        .and_then(|root| Config::load(root).ok())
+        .unwrap_or_default();
+    let paths = FastPaths {
+        offline,
+        cache_ttl_minutes: check_cache.then_some(config.cache_ttl_minutes),
+        concurrency: config.concurrency,
+    };
+    hints::print_network_hint(spent, &paths, machine_readable);
+}
+
+/// Load the manifest at `manifest_path`, or in the current directory
+fn find_manifest(manifest_path: Option<String>) -> Result<Manifest> {
+    let manifest = Manifest::find(manifest_path)?;
+    // A config that doesn't parse fails the command with the reason later
+    if let Ok(config) = Config::load(manifest.path.parent().unwrap_or(Path::new("."))) {
+        output::apply_config(&config);
+    }
+    note_workspace_context(&manifest);
+    Ok(manifest)
+}
+
+/// Say up front, on stderr, when cargo resolves `manifest` inside a
+/// workspace rooted elsewhere, since its lockfile and resolution then come
+/// from there. When cargo can't tell, the cargo runs that need it fail with
+/// the reason later. With a metadata file, cargo isn't run at all.
+fn note_workspace_context(manifest: &Manifest) {
+    if Metadata::file().is_some() {
+        return;
+    }
+    let root = cargo_options(manifest)
+        .and_then(|cargo| cargo::enclosing_workspace(&manifest.path, &cargo));
+    let Ok(Some(root)) = root else {
+        return;
+    };
+    let text = if Workspace::is_standalone() {
+        format!(
+            "{} belongs to the workspace at {}; --no-workspace-discovery analyzes it on its own \
+             and won't run cargo on it",
+            manifest.path.display(),
+            root.display()
+        )
+    } else {
+        format!(
+            "{} is resolved in the workspace at {}: Cargo.lock and dependency resolution come \
+             from there (--no-workspace-discovery treats it on its own)",
+            manifest.path.display(),
+            root.display()
+        )
+    };
+    eprintln!(
+        "{} {}",
+        output::plain("⚠").caution().bold(),
+        output::plain(&text)
+    );
+}
+
+/// How to run cargo for the project owning `manifest`
+fn cargo_options(manifest: &Manifest) -> Result<CargoOptions> {
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    CargoOptions::for_project(&Config::load(root)?, root)
+}
+
+/// The async runtime that drives network-bound analyzers
+pub(crate) fn runtime() -> Result<tokio::runtime::Runtime> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start async runtime")
+}
+
+/// Whether to analyze every workspace member: when `--workspace` asks for
+/// it, or when `manifest` is a virtual one with no dependencies of its own
+/// and none of the options covering a single package is given.
+/// `package_only` pairs each such option with whether it was given.
+fn workspace_implied(
+    manifest: &Manifest,
+    workspace: bool,
+    package_only: &[(&str, bool)],
+) -> Result<bool> {
+    let given: Vec<&str> = package_only
+        .iter()
+        .filter(|(_, given)| *given)
+        .map(|(option, _)| *option)
+        .collect();
+    if workspace && !given.is_empty() {
+        anyhow::bail!(
+            "{} covers a single package; drop --workspace",
+            given.join(", ")
+        );
+    }
+    Ok(workspace || (manifest.is_virtual() && given.is_empty()))
+}
+
+/// Whether `--fail-on threshold` fails the report: an analyzer failed, or
+/// one of its findings or an advisory reaches the threshold
+fn reaches(report: &ProjectReport, threshold: LintSeverity) -> bool {
+    let advisories = report
+        .health
+        .iter()
+        .flat_map(|health| &health.vulnerable)
+        .flat_map(|package| &package.advisories);
+    report
+        .analyzers
+        .iter()
+        .any(|analyzer| analyzer.error.is_some() || analyzer.reaches(threshold))
+        || advisories
+            .map(|advisory| advisory_severity(advisory.severity))
+            .any(|severity| severity >= threshold)
+}
+
+/// How `--fail-on` weighs an advisory: critical and high ones as errors,
+/// the rest as warnings
+fn advisory_severity(severity: Option<Severity>) -> LintSeverity {
+    match severity {
+        Some(Severity::Critical | Severity::High) => LintSeverity::Error,
+        _ => LintSeverity::Warning,
+    }
+}
+
+/// Warn about crates `.cargo-sane/owners.toml` assigns that the manifest
+/// doesn't declare, most likely removed or misspelled
+fn print_unknown_team_crates(teams: &Teams, manifest: &Manifest) {
+    let mut declared: Vec<String> = manifest
+        .get_dependencies()
+        .into_iter()
+        .map(|(name, _, _)| name)
+        .collect();
+    declared.dedup();
+    let declared: Vec<&str> = declared.iter().map(String::as_str).collect();
+    let unknown = teams.unknown(&declared);
+    if !unknown.is_empty() {
+        output::print_warning(&format!(
+            "{} assigns crates that aren't dependencies: {}",
+            Teams::path(Path::new("")).display(),
+            unknown.join(", ")
+        ));
+        println!();
+    }
+}
+
+/// Advisory database settings from the config and the health flags
+fn advisory_db_options(config: &Config, update_db: bool, offline: bool) -> DbOptions {
+    let mode = if offline {
+        DbMode::Offline
+    } else if update_db {
+        DbMode::Update
+    } else {
+        DbMode::Auto
+    };
+    DbOptions {
+        mode,
+        max_age_days: config.advisory_db_max_age_days,
+        strict: config.advisory_db_strict,
+    }
+}
@@ -0,0 +1,348 @@
+//! `cargo sane report`: check, health and conflicts combined
+
+use crate::analyzer::freshness::budget_violations;
+use crate::analyzer::history::DependencyOrigin;
+use crate::analyzer::lint::LintSeverity;
+use crate::analyzer::plugin::{
+    registered, run_analyzers, Analyzer, AnalyzerFindings, ExternalAnalyzer, ProjectAnalysis,
+};
+use crate::analyzer::priority::{rank, truncate, Significance};
+use crate::analyzer::snapshot::{ProjectReport, Snapshot};
+use crate::analyzer::teams::{group_by_team, Teams};
+use crate::analyzer::usage::find_unused_dependencies;
+use crate::analyzer::usage_cache::UsageCache;
+use crate::cli::commands::check::{
+    collect_stats, fetch_owners, load_history, origin_note, print_budget_violations,
+    print_dependency_stats, print_unassigned_nudge, yanked_marker,
+};
+use crate::cli::commands::lint::severity_label;
+use crate::cli::commands::snapshot::{collect_snapshot, load_baseline, print_snapshot_diff};
+use crate::cli::commands::{
+    find_manifest, print_more, print_unknown_team_crates, reaches, write_metrics,
+};
+use crate::cli::digest::{analyzer_markdown, render_digest, DEFAULT_OUTSTANDING_LIMIT, DIGEST_TAG};
+use crate::cli::metrics::Metrics;
+use crate::cli::output::{self, FileFormat, OutputFile, Paint};
+use crate::core::config::Config;
+use crate::core::dependency::{Dependency, DependencyKind};
+use crate::core::manifest::DependencySection;
+use crate::utils::files::{collect_rust_files, WalkOptions};
+use crate::utils::formatting::{display_path, format_count, format_timestamp, plural};
+use crate::utils::snapshots::SnapshotStore;
+use crate::Result;
+use anyhow::Context;
+use colored::Colorize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Combined check, health and conflict report, optionally with the changes
+/// since an earlier snapshot, and the findings of the compiled-in analyzers
+/// and `plugins`. Returns false when an analyzer failed or a finding
+/// reaches `fail_on`.
+#[allow(clippy::too_many_arguments)]
+pub fn report_command(
+    manifest_path: Option<String>,
+    since: Option<String>,
+    json: bool,
+    limit: usize,
+    owners: bool,
+    metrics_out: Option<PathBuf>,
+    digest: Option<PathBuf>,
+    since_last: bool,
+    history: bool,
+    output_files: Vec<OutputFile>,
+    plugins: Vec<PathBuf>,
+    fail_on: Option<LintSeverity>,
+) -> Result<bool> {
+    let manifest = find_manifest(manifest_path)?;
+    let store = SnapshotStore::for_manifest(&manifest);
+    let baseline = if since_last {
+        match store.find(DIGEST_TAG)? {
+            Some(entry) => Some((
+                store.load(&entry)?,
+                format!("the last digest ({})", format_timestamp(entry.created_at)),
+            )),
+            None => None,
+        }
+    } else {
+        since
+            .as_deref()
+            .map(|reference| load_baseline(&manifest, reference))
+            .transpose()?
+    };
+    let current = collect_snapshot(&manifest, json)?;
+    let diff = baseline.as_ref().map(|(b, _)| b.diff(&current));
+    let owners = if owners {
+        let names = current
+            .check
+            .dependencies
+            .iter()
+            .map(|d| d.name.clone())
+            .collect();
+        Some(fetch_owners(&manifest, names, json)?)
+    } else {
+        None
+    };
+    let stats = collect_stats(&current.check.dependencies, owners.as_ref());
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let config = Config::load(root)?;
+    let freshness = budget_violations(&current.check.dependencies, &config.freshness);
+    let history = if history {
+        load_history(&manifest, json)
+    } else {
+        None
+    };
+    if let Some(path) = &metrics_out {
+        let mut metrics = Metrics::new(&manifest).with_check(&current.check);
+        if let Some(health) = &current.health {
+            metrics = metrics.with_health(health);
+        }
+        if let Some(conflicts) = &current.conflicts {
+            metrics = metrics.with_conflicts(conflicts);
+        }
+        let files = collect_rust_files(root, &WalkOptions::from_config(&config))?;
+        let scans = UsageCache::for_manifest(&manifest, true);
+        metrics = metrics.with_unused(find_unused_dependencies(&manifest, &files, &scans)?.len());
+        let _ = scans.save();
+        write_metrics(path, &metrics, json)?;
+    }
+    let mut analyzers = registered();
+    analyzers.extend(
+        plugins
+            .into_iter()
+            .map(|program| Arc::new(ExternalAnalyzer::new(program)) as Arc<dyn Analyzer>),
+    );
+    let analyzers = if analyzers.is_empty() {
+        Vec::new()
+    } else {
+        run_analyzers(&analyzers, &ProjectAnalysis::new(&current))
+    };
+    let digest_limit = if limit == 0 {
+        DEFAULT_OUTSTANDING_LIMIT
+    } else {
+        limit
+    };
+    let markdown = || {
+        render_digest(&current, baseline.as_ref().map(|(b, _)| b), digest_limit)
+            + &analyzer_markdown(&analyzers)
+    };
+    if let Some(path) = &digest {
+        let text = markdown();
+        std::fs::write(path, text).context(format!("Failed to write {}", path.display()))?;
+        // The next --since-last compares with what this digest described
+        store.save(&current.clone().with_tag(Some(DIGEST_TAG.to_string())))?;
+        if !json {
+            output::print_info(&format!("Digest written to {}", display_path(path)));
+            println!();
+        }
+    }
+
+    let report = ProjectReport {
+        check: current.check.clone(),
+        health: current.health.clone(),
+        conflicts: current.conflicts.clone(),
+        stats,
+        freshness,
+        since: diff,
+        history,
+        analyzers: analyzers.clone(),
+    };
+    output::write_output_files(&output_files, json, |format| match format {
+        FileFormat::Markdown => Ok(markdown()),
+        _ => output::render_json(&report),
+    })?;
+    let passed = fail_on.is_none_or(|threshold| !reaches(&report, threshold));
+    if json {
+        output::print_json(&report)?;
+        return Ok(passed);
+    }
+
+    output::print_header("📋 cargo-sane report");
+    println!();
+    if let Some(name) = manifest.package_name() {
+        output::print_info(&format!("Package: {}", name));
+    }
+    output::print_info(&format!("Manifest: {}", display_path(&manifest.path)));
+    println!();
+
+    let dependencies = &current.check.dependencies;
+    let outdated = dependencies.iter().filter(|d| d.has_update()).count();
+    println!(
+        "📊 Dependencies: {} ({} outdated)",
+        dependencies.len(),
+        outdated
+    );
+    match &current.health {
+        Some(health) => println!(
+            "🛡️  Advisories: {} affected of {} scanned",
+            health.vulnerable.len(),
+            health.scanned
+        ),
+        None => println!("🛡️  Advisories: {}", "unavailable".dimmed()),
+    }
+    if let Some(surface) = current.health.as_ref().and_then(|h| h.build_time.as_ref()) {
+        println!(
+            "🏗️  Build-time code: {} of {}",
+            format_count(surface.packages.len()),
+            plural(surface.total as u64, "package")
+        );
+    }
+    match &current.conflicts {
+        Some(conflicts) => println!("🔀 Duplicated crates: {}", conflicts.conflicts.len()),
+        None => println!("🔀 Duplicated crates: {}", "unavailable".dimmed()),
+    }
+    println!();
+
+    print_dependency_stats(&report.stats);
+    print_budget_violations(&report.freshness);
+    let teams = Teams::load(root)?;
+    if let Some(teams) = &teams {
+        print_unknown_team_crates(teams, &manifest);
+    }
+    print_attention(&current, limit, teams.is_some());
+    if let Some(history) = &report.history {
+        print_history(history);
+    }
+    if let (Some(diff), Some((_, label))) = (&report.since, &baseline) {
+        print_snapshot_diff(diff, label);
+    }
+    print_analyzer_findings(&report.analyzers);
+    if let (false, Some(threshold)) = (passed, fail_on) {
+        output::print_warning(&format!(
+            "Failing: an analyzer failed or a finding reaches --fail-on {}",
+            threshold
+        ));
+    }
+    Ok(passed)
+}
+
+/// What each analyzer found, under its name
+fn print_analyzer_findings(analyzers: &[AnalyzerFindings]) {
+    if analyzers.is_empty() {
+        return;
+    }
+    println!("{}", output::plain("🧩 Analyzer findings:").bold());
+    for analyzer in analyzers {
+        if let Some(error) = &analyzer.error {
+            println!(
+                "  {} {}",
+                analyzer.analyzer.bold(),
+                format!("failed: {}", error).bad()
+            );
+            continue;
+        }
+        if analyzer.findings.is_empty() {
+            println!(
+                "  {} {}",
+                analyzer.analyzer.bold(),
+                "nothing found".dimmed()
+            );
+            continue;
+        }
+        println!("  {}", analyzer.analyzer.bold());
+        for finding in &analyzer.findings {
+            let dependency = finding
+                .dependency
+                .as_ref()
+                .map(|name| format!(" {}", name.bold()))
+                .unwrap_or_default();
+            println!(
+                "    {}: {}{}: {}",
+                severity_label(finding.severity),
+                finding.code.dimmed(),
+                dependency,
+                finding.message
+            );
+        }
+    }
+    println!();
+}
+
+/// Dependencies with an update or an advisory, most significant first,
+/// under the team owning them when `by_team` is set
+fn print_attention(snapshot: &Snapshot, limit: usize, by_team: bool) {
+    let vulnerable: BTreeSet<&str> = snapshot
+        .health
+        .iter()
+        .flat_map(|health| &health.vulnerable)
+        .map(|package| package.name.as_str())
+        .collect();
+    let mut attention: Vec<(&Dependency, bool)> = snapshot
+        .check
+        .dependencies
+        .iter()
+        .map(|dep| (dep, vulnerable.contains(dep.name.as_str())))
+        .filter(|(dep, vulnerable)| *vulnerable || dep.has_update() || dep.yanked)
+        .collect();
+    if attention.is_empty() {
+        return;
+    }
+    rank(&mut attention, |(dep, vulnerable)| {
+        Significance::of_dependency(dep, *vulnerable)
+    });
+
+    println!("{}", output::plain("🔎 Needs attention:").bold());
+    let (shown, hidden) = truncate(&attention, limit);
+    let line = |(dep, vulnerable): &(&Dependency, bool)| {
+        let latest = match &dep.latest_version {
+            Some(latest) if dep.has_update() => format!(" → {}", latest.to_string().good()),
+            _ => String::new(),
+        };
+        let advisory = if *vulnerable {
+            format!(" {}", "(advisory)".bad())
+        } else {
+            String::new()
+        };
+        format!(
+            "• {}{}{} {}{}",
+            dep.name.bold(),
+            yanked_marker(dep),
+            advisory,
+            dep.current_version,
+            latest
+        )
+    };
+    if by_team {
+        let shown: Vec<&(&Dependency, bool)> = shown.iter().collect();
+        for (team, items) in group_by_team(&shown, |(dep, _)| dep.owner.as_deref()) {
+            println!("  {} ({})", team.bold(), items.len());
+            for item in items {
+                println!("    {}", line(item));
+            }
+        }
+    } else {
+        for item in shown {
+            println!("  {}", line(item));
+        }
+    }
+    print_more(hidden);
+    println!();
+    if by_team {
+        print_unassigned_nudge(&snapshot.check.dependencies);
+    }
+}
+
+/// Who added each dependency and when, oldest first
+fn print_history(history: &[DependencyOrigin]) {
+    println!("{}", output::plain("📜 Dependency history:").bold());
+    if history.is_empty() {
+        println!("  {}", "No committed dependency declarations.".dimmed());
+    }
+    let mut history: Vec<&DependencyOrigin> = history.iter().collect();
+    history.sort_by_key(|origin| origin.added_at);
+    for origin in history {
+        let section = if origin.section == DependencySection::new(DependencyKind::Normal) {
+            String::new()
+        } else {
+            format!(" [{}]", origin.section)
+        };
+        println!(
+            "  • {}{} {}",
+            origin.name.bold(),
+            section.dimmed(),
+            origin_note(origin)
+        );
+    }
+    println!();
+}
@@ -0,0 +1,98 @@
+//! `cargo sane size`: the build-time cost of the dependency graph
+
+use crate::analyzer::size::{analyze_size, BuildTimings};
+use crate::cli::commands::{cargo_options, find_manifest};
+use crate::cli::output;
+use crate::utils::cargo;
+use crate::utils::formatting::{display_path, format_count, format_seconds, plural};
+use crate::Result;
+use anyhow::Context;
+use colored::Colorize;
+
+pub fn size_command(
+    manifest_path: Option<String>,
+    timings: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let metadata = cargo::metadata(&manifest.path, &cargo_options(&manifest)?)?;
+    let root = metadata
+        .package_for_manifest(&manifest.path)
+        .map(|p| p.id.clone())
+        .or_else(|| metadata.resolve.as_ref()?.root.clone())
+        .context("Could not find the package in cargo metadata output")?;
+
+    let timings = match timings {
+        Some(path) => {
+            let text = std::fs::read_to_string(&path)
+                .context(format!("Failed to read timings file {}", path))?;
+            Some(BuildTimings::parse(&text))
+        }
+        None => None,
+    };
+    let report = analyze_size(&metadata, &root, timings.as_ref());
+
+    if json {
+        output::print_json(&report)?;
+        return Ok(());
+    }
+
+    output::print_header("🧠 cargo-sane size");
+    println!();
+    output::print_info(&format!("Manifest: {}", display_path(&manifest.path)));
+    println!();
+
+    let seconds = if report.measured {
+        "measured"
+    } else {
+        "estimated"
+    };
+    println!("{}", output::plain("📦 Build graph:"));
+    println!("  Packages: {}", format_count(report.total_packages));
+    println!("  Build scripts: {}", report.build_scripts.len());
+    println!("  Proc macros: {}", report.proc_macros.len());
+    println!(
+        "  Clean build: ~{} ({})",
+        format_seconds(report.estimated_seconds),
+        seconds
+    );
+    println!();
+
+    if !report.heavy.is_empty() {
+        println!("{}", output::plain("🐘 Heaviest crates:").bold());
+        for package in report.heavy.iter().take(10) {
+            println!(
+                "  • {} {} ~{}",
+                package.name.bold(),
+                package.version.to_string().dimmed(),
+                format_seconds(package.seconds)
+            );
+        }
+        println!();
+    }
+
+    if !report.direct.is_empty() {
+        println!(
+            "{}",
+            output::plain("✂️  Removing a direct dependency would drop:").bold()
+        );
+        for direct in &report.direct {
+            println!(
+                "  • {} {}, ~{}",
+                direct.name.bold(),
+                plural(direct.unique_packages.len() as u64, "package"),
+                format_seconds(direct.seconds)
+            );
+        }
+        println!();
+    }
+
+    if !report.measured {
+        println!(
+            "{}",
+            "Seconds are rough estimates; pass --timings <file> with `cargo build --timings=json` output for real numbers."
+                .dimmed()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,248 @@
+//! `cargo sane snapshot`: saved dependency snapshots, and comparing
+//! against them
+
+use crate::analyzer::build_time::build_time_surface;
+use crate::analyzer::conflicts::find_conflicts;
+use crate::analyzer::health::HealthChecker;
+use crate::analyzer::snapshot::{Snapshot, SnapshotDiff};
+use crate::analyzer::teams::Teams;
+use crate::cli::commands::check::{fetch_owners, run_check};
+use crate::cli::commands::{advisory_db_options, cargo_options, find_manifest, runtime};
+use crate::cli::output::{self, Paint, Status};
+use crate::core::config::Config;
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::utils::cache;
+use crate::utils::cargo;
+use crate::utils::formatting::format_timestamp;
+use crate::utils::progress::ProgressMode;
+use crate::utils::snapshots::SnapshotStore;
+use crate::Result;
+use colored::Colorize;
+use std::path::Path;
+
+pub fn snapshot_save_command(
+    manifest_path: Option<String>,
+    tag: Option<String>,
+    owners: bool,
+) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let config = Config::load(root)?;
+
+    output::print_header("📸 cargo-sane snapshot");
+    println!();
+
+    let mut snapshot = collect_snapshot(&manifest, false)?.with_tag(tag);
+    if owners {
+        let names = snapshot
+            .check
+            .dependencies
+            .iter()
+            .map(|d| d.name.clone())
+            .collect();
+        snapshot = snapshot.with_owners(fetch_owners(&manifest, names, false)?);
+    }
+    let store = SnapshotStore::for_manifest(&manifest);
+    let path = store.save(&snapshot)?;
+    let pruned = store.prune(config.snapshot_retention)?;
+
+    output::print_success(&format!("Saved snapshot to {}", path.display()));
+    if !pruned.is_empty() {
+        output::print_info(&format!(
+            "Pruned {} old snapshot(s) (snapshot_retention = {})",
+            pruned.len(),
+            config.snapshot_retention
+        ));
+    }
+    Ok(())
+}
+
+pub fn snapshot_list_command(manifest_path: Option<String>) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let entries = SnapshotStore::for_manifest(&manifest).list()?;
+
+    if entries.is_empty() {
+        output::print_info("No snapshots yet; run `cargo sane snapshot save`");
+        return Ok(());
+    }
+    for entry in entries {
+        let tag = entry
+            .tag
+            .as_deref()
+            .map(|t| t.bold().to_string())
+            .unwrap_or_default();
+        println!("  {}  {}", format_timestamp(entry.created_at), tag);
+    }
+    Ok(())
+}
+
+pub fn snapshot_diff_command(
+    manifest_path: Option<String>,
+    reference: String,
+    json: bool,
+) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let (baseline, label) = load_baseline(&manifest, &reference)?;
+    let current = collect_snapshot(&manifest, json)?;
+    let diff = baseline.diff(&current);
+
+    if json {
+        output::print_json(&diff)?;
+        return Ok(());
+    }
+
+    output::print_header("📸 cargo-sane snapshot diff");
+    println!();
+    print_snapshot_diff(&diff, &label);
+    Ok(())
+}
+
+/// Find a stored snapshot by tag or date, with a label describing it
+pub(super) fn load_baseline(manifest: &Manifest, reference: &str) -> Result<(Snapshot, String)> {
+    let store = SnapshotStore::for_manifest(manifest);
+    let entry = store.find(reference)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "No snapshot matches '{}'; give a tag or a YYYY-MM-DD date",
+            reference
+        )
+    })?;
+    let label = match &entry.tag {
+        Some(tag) => format!("{} ({})", tag, format_timestamp(entry.created_at)),
+        None => entry.label(),
+    };
+    Ok((store.load(&entry)?, label))
+}
+
+/// Run check, health and conflict detection into one snapshot. Health and
+/// conflicts are optional: their failure is reported and left out.
+pub(super) fn collect_snapshot(manifest: &Manifest, quiet: bool) -> Result<Snapshot> {
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let config = Config::load(root)?;
+    let warn = |what: &str, e: anyhow::Error| {
+        if !quiet {
+            output::print_warning(&format!("{} skipped: {}", what, e));
+        }
+    };
+
+    let progress = ProgressMode::detect(quiet).build(false);
+    let (check, _) = run_check(manifest, false, false, progress.clone())?;
+
+    let lockfile = Lockfile::for_manifest(manifest)?;
+    let health = HealthChecker::open(manifest, advisory_db_options(&config, false, false))
+        .map(|(checker, _)| {
+            checker
+                .with_concurrency(config.concurrency)
+                .with_progress(progress)
+        })
+        .and_then(|checker| {
+            let mut report = runtime()?.block_on(checker.check(manifest, lockfile.as_ref()))?;
+            checker.source().save()?;
+            report.build_time = cargo_options(manifest)
+                .and_then(|cargo| cargo::metadata(&manifest.path, &cargo))
+                .ok()
+                .map(|metadata| build_time_surface(&metadata, &manifest.path));
+            if let Some(teams) = Teams::load(root)? {
+                teams.apply_health(&mut report);
+            }
+            Ok(report)
+        })
+        .map_err(|e| warn("Advisory scan", e))
+        .ok();
+    let conflicts = cargo_options(manifest)
+        .and_then(|cargo| find_conflicts(manifest, &cargo))
+        .map_err(|e| warn("Conflict detection", e))
+        .ok();
+
+    Ok(Snapshot::new(cache::unix_now(), check, health, conflicts))
+}
+
+pub(super) fn print_snapshot_diff(diff: &SnapshotDiff, label: &str) {
+    println!("{}", format!("📈 Since {}:", label).blue().bold());
+
+    let delta = diff.dependency_delta();
+    println!(
+        "  Dependencies: {} → {} ({}{})",
+        diff.dependencies_before,
+        diff.dependencies_after,
+        if delta >= 0 { "+" } else { "" },
+        delta
+    );
+    for name in &diff.added {
+        println!("    {} {}", "+".good(), name);
+    }
+    for name in &diff.removed {
+        println!("    {} {}", "-".bad(), name);
+    }
+
+    if diff.is_empty() {
+        println!();
+        output::print_success("No changes");
+        return;
+    }
+
+    if !diff.newly_outdated.is_empty() {
+        println!("  Newly outdated:");
+        for dep in &diff.newly_outdated {
+            println!(
+                "    • {} {} → {}",
+                dep.name.bold(),
+                dep.current_version,
+                dep.latest_version.to_string().good()
+            );
+        }
+    }
+    if !diff.no_longer_outdated.is_empty() {
+        println!(
+            "  No longer outdated: {}",
+            diff.no_longer_outdated.join(", ").good()
+        );
+    }
+
+    match &diff.new_advisories {
+        Some(advisories) if !advisories.is_empty() => {
+            println!("  New advisories:");
+            for advisory in advisories {
+                println!(
+                    "    • {} {} in {} {}: {}",
+                    output::badge(Status::of_severity(advisory.severity)),
+                    advisory.id,
+                    advisory.package.bold(),
+                    advisory.version,
+                    advisory.title
+                );
+            }
+        }
+        Some(_) => {}
+        None => println!(
+            "  Advisories: {}",
+            "not compared (missing in a snapshot)".dimmed()
+        ),
+    }
+    if let Some(resolved) = diff.resolved_advisories.as_ref().filter(|r| !r.is_empty()) {
+        let resolved: Vec<String> = resolved
+            .iter()
+            .map(|a| format!("{} in {}", a.id, a.package))
+            .collect();
+        println!("  Resolved advisories: {}", resolved.join(", ").good());
+    }
+
+    match (&diff.resolved_conflicts, &diff.new_conflicts) {
+        (Some(resolved), Some(new)) => {
+            if !resolved.is_empty() {
+                println!("  Resolved conflicts: {}", resolved.join(", ").good());
+            }
+            if !new.is_empty() {
+                println!("  New conflicts: {}", new.join(", ").caution());
+            }
+        }
+        _ => println!(
+            "  Conflicts: {}",
+            "not compared (missing in a snapshot)".dimmed()
+        ),
+    }
+    if let Some(new) = diff.new_build_time_code.as_ref().filter(|n| !n.is_empty()) {
+        println!("  New build-time code: {}", new.join(", ").caution());
+    }
+    println!();
+}
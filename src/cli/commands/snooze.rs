@@ -0,0 +1,115 @@
+//! `cargo sane snooze`: hiding a crate's update until a newer release or
+//! until the snooze runs out
+
+use crate::analyzer::snoozed::{parse_length, Snooze, Snoozes};
+use crate::cli::commands::check::run_check;
+use crate::cli::commands::find_manifest;
+use crate::cli::output::{self, Paint};
+use crate::utils::cache;
+use crate::utils::formatting::{format_date, plural};
+use crate::utils::progress::ProgressMode;
+use crate::Result;
+use anyhow::Context;
+use semver::Version;
+use std::path::Path;
+
+/// Put off the update of `name` until a release newer than `until_version`,
+/// or the current update target, or until `length` ("30d") has passed
+pub fn snooze_command(
+    manifest_path: Option<String>,
+    name: Option<String>,
+    until_version: Option<String>,
+    length: Option<String>,
+    list: bool,
+    clear: bool,
+) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let mut snoozes = Snoozes::load(root)?;
+    let now = cache::unix_now();
+
+    if list {
+        if snoozes.is_empty() {
+            output::print_info("No snoozed updates recorded.");
+        }
+        for snooze in &snoozes.snoozed {
+            let expired = if snooze.is_expired(now) {
+                format!(" {}", "(expired)".bad())
+            } else {
+                String::new()
+            };
+            println!("  • {}{}", snooze.summary(), expired);
+        }
+        return Ok(());
+    }
+
+    if clear {
+        match name {
+            Some(name) => {
+                if !snoozes.remove(&name) {
+                    anyhow::bail!("No snooze of {} is recorded", name);
+                }
+                snoozes.save(root)?;
+                output::print_success(&format!("Removed the snooze of {}", name));
+            }
+            None => {
+                let count = snoozes.snoozed.len();
+                Snoozes::default().save(root)?;
+                output::print_success(&format!("Removed {}", plural(count as u64, "snooze")));
+            }
+        }
+        return Ok(());
+    }
+
+    let name = name.context("Give the crate whose update to snooze")?;
+    let version = match until_version {
+        Some(version) => Version::parse(&version).context(format!(
+            "Invalid version '{}', expected e.g. 4.6.0",
+            version
+        ))?,
+        None => {
+            let progress = ProgressMode::detect(false).build(false);
+            let (report, _) = run_check(&manifest, false, false, progress)?;
+            let dep = report
+                .dependencies
+                .iter()
+                .find(|d| d.name == name)
+                .context(format!(
+                    "{} is not a checked dependency of this package",
+                    name
+                ))?;
+            match &dep.latest_version {
+                Some(latest) if dep.has_update() => latest.clone(),
+                _ => anyhow::bail!(
+                    "{} is up to date; give --until-version to snooze releases to come",
+                    name
+                ),
+            }
+        }
+    };
+    let until = length
+        .map(|length| {
+            parse_length(&length)
+                .map(|seconds| format_date(now + seconds))
+                .context(format!(
+                    "Invalid length '{}', expected e.g. 30d or 2w",
+                    length
+                ))
+        })
+        .transpose()?;
+
+    let snooze = Snooze {
+        name,
+        version,
+        until,
+        snoozed_by: std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .ok(),
+    };
+    let summary = snooze.summary();
+    snoozes.snooze(snooze);
+    let path = snoozes.save(root)?;
+    output::print_success(&format!("Snoozed {}", summary));
+    output::print_info(&format!("Recorded in {}", path.display()));
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! `cargo sane tour`: a guided first run
+
+use crate::cli::commands::find_manifest;
+use crate::cli::commands::snapshot::collect_snapshot;
+use crate::cli::output;
+use crate::cli::prompt;
+use crate::cli::tour::{render_tour, suggested_config};
+use crate::core::config::CONFIG_FILE;
+use crate::utils::formatting::display_path;
+use crate::Result;
+use anyhow::Context;
+use std::path::Path;
+
+/// Walk a newcomer through check, conflict detection and health on their
+/// own project, pausing between sections on a terminal
+pub fn tour_command(manifest_path: Option<String>) -> Result<()> {
+    let manifest = find_manifest(manifest_path)?;
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let config_path = root.join(CONFIG_FILE);
+    let has_config = config_path.exists();
+
+    let snapshot = collect_snapshot(&manifest, false)?;
+    let sections = render_tour(&snapshot, has_config);
+    for (i, section) in sections.iter().enumerate() {
+        if i > 0 {
+            prompt::pause()?;
+            println!();
+        }
+        println!("{}", output::plain(section));
+    }
+
+    if !has_config && prompt::can_confirm() {
+        println!();
+        if prompt::confirm(&format!("Write these settings to {}?", CONFIG_FILE), true)? {
+            std::fs::write(&config_path, suggested_config(&snapshot))
+                .context(format!("Failed to write {}", config_path.display()))?;
+            output::print_success(&format!("Wrote {}", display_path(&config_path)));
+        }
+    }
+    Ok(())
+}
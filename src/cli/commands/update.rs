@@ -0,0 +1,1345 @@
+//! `cargo sane update`: interactive dependency updates
+
+use crate::analyzer::impact::{update_impact, UpdateImpact};
+use crate::analyzer::msrv::{msrv_conflicts, MsrvConflict};
+use crate::analyzer::workspace::WorkspaceCrate;
+use crate::cli::commands::check::{run_check, run_workspace_check};
+use crate::cli::commands::{
+    cargo_options, find_manifest, print_network_hint, record_audit, runtime, workspace_implied,
+};
+use crate::cli::output::{self, Paint, Status};
+use crate::cli::prompt;
+use crate::core::config::Config;
+use crate::core::deny::DenyViolation;
+use crate::core::dependency::{download_delta, Dependency, UpdateType};
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::{DependencySection, DependencySpec, Manifest};
+use crate::core::policy::PolicyStatus;
+use crate::core::version::{format_rust_version, is_compatible, parse_rust_version};
+use crate::core::workspace::Workspace;
+use crate::updater::answers::Answers;
+use crate::updater::features::review_features;
+use crate::updater::plan::Plan;
+use crate::updater::update::{save_all, ManifestEdit};
+use crate::updater::DependencyUpdater;
+use crate::utils::api_diff::{api_diffs, ApiDiff, ApiDiffClient};
+use crate::utils::audit::{AuditChange, AuditEntry};
+use crate::utils::cargo::{self, CargoOptions};
+use crate::utils::changelog::{
+    fetch_changelogs, render_changelog, ChangelogClient, ChangelogSource, ChangelogUpdate,
+};
+use crate::utils::formatting::{display_path, format_bytes, format_count, format_duration, plural};
+use crate::utils::git::GitRepo;
+use crate::utils::progress::ProgressMode;
+use crate::utils::registry::DEFAULT_CONCURRENCY;
+use crate::utils::sparse_index::SparseIndexClient;
+use crate::Result;
+use anyhow::Context;
+use colored::Colorize;
+use futures::stream::{self, StreamExt};
+use semver::Version;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_command(
+    manifest_path: Option<String>,
+    dry_run: bool,
+    all: bool,
+    refresh: bool,
+    pre: bool,
+    impact: bool,
+    api_diff: bool,
+    workspace: bool,
+    package: Option<String>,
+    changelog: Option<PathBuf>,
+    allow_dirty: bool,
+    keep_features: bool,
+    plan_out: Option<PathBuf>,
+    verify: bool,
+    ignore_rust_version: bool,
+    answers: Option<PathBuf>,
+    write_answers: Option<PathBuf>,
+    ignore_deny: bool,
+) -> Result<()> {
+    output::print_header("🧠 cargo-sane update");
+    println!();
+
+    // Load Cargo.toml
+    let manifest = find_manifest(manifest_path)?;
+    update_manifest(
+        manifest.clone(),
+        dry_run,
+        all,
+        refresh,
+        pre,
+        impact,
+        api_diff,
+        workspace,
+        package,
+        changelog,
+        allow_dirty,
+        keep_features,
+        plan_out,
+        verify,
+        ignore_rust_version,
+        answers,
+        write_answers,
+        ignore_deny,
+    )?;
+    print_network_hint(&manifest, None, true, false);
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_manifest(
+    manifest: Manifest,
+    dry_run: bool,
+    all: bool,
+    refresh: bool,
+    pre: bool,
+    impact: bool,
+    api_diff: bool,
+    workspace: bool,
+    package: Option<String>,
+    changelog: Option<PathBuf>,
+    allow_dirty: bool,
+    keep_features: bool,
+    plan_out: Option<PathBuf>,
+    verify: bool,
+    ignore_rust_version: bool,
+    answers: Option<PathBuf>,
+    write_answers: Option<PathBuf>,
+    ignore_deny: bool,
+) -> Result<()> {
+    let workspace = workspace
+        || workspace_implied(
+            &manifest,
+            false,
+            &[
+                ("--impact", impact),
+                ("--api-diff", api_diff),
+                ("--plan-out", plan_out.is_some()),
+                ("--verify", verify),
+                ("--answers", answers.is_some()),
+                ("--write-answers", write_answers.is_some()),
+            ],
+        )?;
+    if workspace || package.is_some() {
+        if plan_out.is_some() {
+            anyhow::bail!("--plan-out covers a single manifest, not a workspace");
+        }
+        return update_workspace(
+            manifest,
+            package.as_deref(),
+            dry_run,
+            all,
+            pre,
+            changelog.as_deref(),
+            allow_dirty,
+            keep_features,
+            ignore_rust_version,
+            ignore_deny,
+        );
+    }
+
+    if let Some(name) = manifest.package_name() {
+        output::print_info(&format!("Package: {}", name));
+    }
+    output::print_info(&format!("Manifest: {}", display_path(&manifest.path)));
+    println!();
+
+    // Check dependencies
+    let progress = ProgressMode::detect(false).build(false);
+    let (report, cache_age) = run_check(&manifest, refresh, pre, progress)?;
+    let dependencies = report.dependencies;
+
+    if let Some(age) = cache_age {
+        output::print_info(&format!(
+            "Using cached results ({} old; --refresh to re-check)",
+            format_duration(age)
+        ));
+        println!();
+    }
+
+    print_policy_conflicts(&dependencies);
+
+    // Filter only dependencies with updates
+    let updatable: Vec<&Dependency> = dependencies
+        .iter()
+        .filter(|d| d.has_update() && !d.snoozed)
+        .collect();
+    let snoozed = dependencies.iter().filter(|d| d.snoozed).count();
+    if snoozed > 0 {
+        output::print_info(&format!(
+            "Not offering {} (`cargo sane snooze --list` shows them)",
+            plural(snoozed as u64, "snoozed update")
+        ));
+        println!();
+    }
+
+    // An answers file approves updates of snoozed crates too
+    let answers = answers
+        .map(|path| Answers::load(&path).map(|answers| (path, answers)))
+        .transpose()?;
+    let interactive = !all && answers.is_none();
+
+    if updatable.is_empty() && answers.is_none() {
+        output::print_success("All dependencies are up to date! 🎉");
+        return Ok(());
+    }
+
+    println!(
+        "Found {} dependencies with updates available.\n",
+        format_count(updatable.len())
+    );
+
+    if let Some(path) = &write_answers {
+        Answers::suggest(&updatable).write(path, &updatable)?;
+        output::print_success(&format!(
+            "Answers written to {}: {} suggested",
+            display_path(path),
+            plural(updatable.len() as u64, "update")
+        ));
+        output::print_info(&format!(
+            "Review it, then run `cargo sane update --answers {}`",
+            path.display()
+        ));
+        return Ok(());
+    }
+
+    // Select which dependencies to update
+    let to_update = if let Some((path, answers)) = &answers {
+        let available: Vec<&Dependency> = dependencies.iter().filter(|d| d.has_update()).collect();
+        let selection = answers
+            .select(&available, &updatable)
+            .context(format!("Failed to apply {}", display_path(path)))?;
+        if !selection.unmentioned.is_empty() {
+            let names: Vec<String> = selection
+                .unmentioned
+                .iter()
+                .map(|d| format!("{} → {}", d.name, d.latest_version.as_ref().unwrap()))
+                .collect();
+            output::print_warning(&format!(
+                "Not in {}, so not updated: {}",
+                display_path(path),
+                names.join(", ")
+            ));
+        }
+        selection.approved
+    } else if all {
+        without_denied(updatable, ignore_deny)
+    } else {
+        select_dependencies_to_update(&updatable)?
+    };
+
+    if to_update.is_empty() {
+        output::print_info("No dependencies selected for update.");
+        return Ok(());
+    }
+
+    let impacts = if impact {
+        update_impacts(&manifest, &to_update)
+    } else {
+        Vec::new()
+    };
+    let diffs = if api_diff {
+        major_api_diffs(&manifest, &to_update)
+    } else {
+        Vec::new()
+    };
+
+    // Show what will be updated
+    println!("\n{}", output::plain("📝 Updates to apply:").bold());
+    for (i, dep) in to_update.iter().enumerate() {
+        if let Some(latest) = &dep.latest_version {
+            let update_type = output::label(Status::of_update(dep.update_type()));
+            println!(
+                "  {} {}{}{} {} → {}{}",
+                update_type,
+                dep.name.bold(),
+                inherited_marker(dep),
+                target_marker(dep),
+                dep.current_version.to_string().dimmed(),
+                latest.to_string().cyan(),
+                policy_marker(dep)
+            );
+            if let Some(note) = dep.skip_note() {
+                println!("      {}", note.dimmed());
+            }
+            print_license_change(dep, "      ");
+            print_deny_violation(dep, "      ");
+            if let Some(Some(diff)) = diffs.get(i) {
+                print_api_diff(diff, "      ");
+            }
+            if let Some(Some(impact)) = impacts.get(i) {
+                print_update_impact(impact);
+            }
+        }
+    }
+    print_download_delta(&to_update);
+    println!();
+
+    if let Some(path) = &plan_out {
+        let plan = Plan::for_updates(&manifest, &to_update)?;
+        let mut json = serde_json::to_string_pretty(&plan)?;
+        json.push('\n');
+        std::fs::write(path, json).context(format!("Failed to write {}", path.display()))?;
+        output::print_success(&format!(
+            "Plan written to {}: {} planned",
+            display_path(path),
+            plural(plan.actions.len() as u64, "update")
+        ));
+        output::print_info(&format!(
+            "Run `cargo sane apply {}` to apply it",
+            path.display()
+        ));
+        return Ok(());
+    }
+
+    // Confirm unless --all or an answers file decided
+    if interactive && !dry_run && !prompt::confirm("Apply these updates?", true)? {
+        output::print_info("Update cancelled.");
+        return Ok(());
+    }
+
+    if dry_run {
+        output::print_info("Dry-run mode: No changes will be made.");
+        if let Some(path) = &changelog {
+            write_changelog(&manifest.path, path, &to_update)?;
+        }
+        return Ok(());
+    }
+
+    // Requirements inherited with `workspace = true` are changed in the
+    // root's [workspace.dependencies], for every member at once
+    let root = to_update
+        .iter()
+        .filter_map(|dep| dep.inherited_from.clone())
+        .find(|root| *root != manifest.path);
+    let manifests: Vec<PathBuf> = std::iter::once(manifest.path.clone())
+        .chain(root.clone())
+        .collect();
+    let Some(dirty) = check_worktree(&manifests, allow_dirty, interactive)? else {
+        output::print_info("Update cancelled.");
+        return Ok(());
+    };
+
+    let verification = verify
+        .then(|| Verification::prepare(&manifest))
+        .transpose()?;
+
+    // Create updater
+    let manifest_path = manifest.path.clone();
+    let declarations = manifest.declarations();
+    let mut updater = DependencyUpdater::new(manifest)?;
+    let mut root_updater = root
+        .as_deref()
+        .map(|root| DependencyUpdater::new(Manifest::from_path(root)?))
+        .transpose()?;
+    let features = FeatureCheck::new(keep_features, interactive)?;
+
+    // Apply updates
+    println!("\n{}", output::plain("🔄 Applying updates...").bold());
+    let mut edits = Vec::new();
+    let mut root_edits = Vec::new();
+    let mut feature_changes = Vec::new();
+    let mut updated = Vec::new();
+    for dep in to_update {
+        if let Some(latest) = &dep.latest_version {
+            let latest_str = latest.to_string();
+            let result = match (&dep.inherited_from, root_updater.as_mut()) {
+                (Some(from), Some(root_updater)) if *from != manifest_path => root_updater
+                    .update_workspace_dependency(&dep.name, &latest_str)
+                    .map(|edit| (edit, true)),
+                (Some(_), _) => updater
+                    .update_workspace_dependency(&dep.name, &latest_str)
+                    .map(|edit| (edit, false)),
+                (None, _) => updater
+                    .update_dependency(dep, &latest_str)
+                    .map(|edit| (edit, false)),
+            };
+            match result {
+                Ok((edit, in_root)) => {
+                    println!("  ✓ Updated {}", describe_edit(&edit));
+                    // Features a member adds on top of an inherited
+                    // declaration are its own; leave them be
+                    if let (Some(features), false) = (&features, edit.workspace) {
+                        feature_changes.extend(features.apply(
+                            &mut updater,
+                            &declarations,
+                            &edit,
+                            &dep.current_version,
+                            latest,
+                        )?);
+                    }
+                    if in_root {
+                        root_edits.push(edit);
+                    } else {
+                        edits.push(edit);
+                    }
+                    updated.push(dep);
+                }
+                Err(e) => {
+                    eprintln!("  ✗ Failed to update {}: {}", dep.name.bad(), e);
+                }
+            }
+        }
+    }
+    if edits.is_empty() && root_edits.is_empty() {
+        println!();
+        output::print_warning("Nothing was changed.");
+        return Ok(());
+    }
+
+    // Save changes, the member and the workspace root together
+    let mut saved: Vec<(&DependencyUpdater, PathBuf, Vec<AuditChange>)> = Vec::new();
+    if !edits.is_empty() {
+        let changes = edits
+            .iter()
+            .map(AuditChange::from)
+            .chain(feature_changes)
+            .collect();
+        saved.push((&updater, manifest_path.clone(), changes));
+    }
+    if let (Some(root_updater), Some(root)) = (&root_updater, &root) {
+        if !root_edits.is_empty() {
+            let changes = root_edits.iter().map(AuditChange::from).collect();
+            saved.push((root_updater, root.clone(), changes));
+        }
+    }
+    let backups = save_all(saved.iter().map(|(updater, _, _)| *updater))?;
+    let restore: Vec<(PathBuf, PathBuf)> = saved
+        .iter()
+        .map(|(_, path, _)| path.clone())
+        .zip(backups.iter().cloned())
+        .collect();
+    if let Some(verification) = &verification {
+        verification.run(&manifest_path, &restore)?;
+    }
+    for ((_, path, changes), backup) in saved.into_iter().zip(&backups) {
+        record_audit(
+            &manifest_path,
+            AuditEntry::new("update", &path)
+                .with_changes(changes)
+                .with_backup(Some(backup.clone())),
+        );
+    }
+    println!();
+    output::print_success(&format!(
+        "Cargo.toml updated: {} changed",
+        plural((edits.len() + root_edits.len()) as u64, "requirement")
+    ));
+    for backup in &backups {
+        output::print_info(&format!("Backup saved as {}", display_path(backup)));
+    }
+    print_dirty_note(&dirty);
+    if let Some(path) = &changelog {
+        write_changelog(&manifest_path, path, &updated)?;
+    }
+    if verification.is_none() {
+        println!();
+        println!(
+            "{}",
+            "Don't forget to run `cargo check` to verify everything still compiles!".dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+/// What `update --verify` needs to undo an update cargo rejects
+struct Verification {
+    cargo: CargoOptions,
+    lockfile: PathBuf,
+    /// Cargo.lock before the update, which `cargo check` may rewrite
+    locked: Option<Vec<u8>>,
+}
+
+impl Verification {
+    fn prepare(manifest: &Manifest) -> Result<Self> {
+        let lockfile = Lockfile::path_for(manifest);
+        Ok(Self {
+            cargo: cargo_options(manifest)?,
+            locked: std::fs::read(&lockfile).ok(),
+            lockfile,
+        })
+    }
+
+    /// Run `cargo check` on the updated manifest. When it fails, restore
+    /// each edited manifest from its backup and Cargo.lock as it was, and
+    /// fail the run.
+    fn run(&self, manifest_path: &Path, backups: &[(PathBuf, PathBuf)]) -> Result<()> {
+        println!(
+            "\n{}",
+            output::plain("🔍 Verifying with cargo check...").bold()
+        );
+        let Err(e) = cargo::check(manifest_path, &self.cargo) else {
+            output::print_success("cargo check passed");
+            return Ok(());
+        };
+        output::print_error(&format!("{:#}", e));
+
+        for (path, backup) in backups {
+            std::fs::copy(backup, path)
+                .context(format!("Failed to restore {}", display_path(path)))?;
+        }
+        match &self.locked {
+            Some(locked) => std::fs::write(&self.lockfile, locked),
+            None if self.lockfile.exists() => std::fs::remove_file(&self.lockfile),
+            None => Ok(()),
+        }
+        .context(format!(
+            "Failed to restore {}",
+            display_path(&self.lockfile)
+        ))?;
+        let restored: Vec<String> = backups
+            .iter()
+            .map(|(path, _)| display_path(path))
+            .chain(std::iter::once(display_path(&self.lockfile)))
+            .collect();
+        anyhow::bail!(
+            "The updates didn't pass cargo check, so they were rolled back: {} are as they were",
+            restored.join(" and ")
+        )
+    }
+}
+
+/// Check git for uncommitted changes to `manifests` and the lockfiles
+/// beside them before editing. Returns the dirty files when editing may go
+/// ahead, `None` when the user declined. Without `allow_dirty`, dirty files
+/// need a confirmation, or fail the run when `prompt` is false. Outside a
+/// git repository nothing is checked.
+fn check_worktree(
+    manifests: &[PathBuf],
+    allow_dirty: bool,
+    prompt: bool,
+) -> Result<Option<Vec<String>>> {
+    let Some(first) = manifests.first() else {
+        return Ok(Some(Vec::new()));
+    };
+    let Some(repo) = GitRepo::discover(first.parent().unwrap_or(Path::new("."))) else {
+        return Ok(Some(Vec::new()));
+    };
+    let files: Vec<PathBuf> = manifests
+        .iter()
+        .flat_map(|manifest| {
+            let lockfile = Manifest::from_path(manifest)
+                .map(|m| Lockfile::path_for(&m))
+                .unwrap_or_else(|_| manifest.with_file_name("Cargo.lock"));
+            [manifest.clone(), lockfile]
+        })
+        .collect();
+    let dirty = repo.dirty(&files)?;
+    if dirty.is_empty() || allow_dirty {
+        return Ok(Some(dirty));
+    }
+
+    let (verb, pronoun) = if dirty.len() == 1 {
+        ("has", "it")
+    } else {
+        ("have", "them")
+    };
+    if !prompt {
+        anyhow::bail!(
+            "{} {} uncommitted changes; commit or stash {} first, or pass --allow-dirty",
+            dirty.join(", "),
+            verb,
+            pronoun
+        );
+    }
+    let confirm = prompt::confirm(
+        &format!(
+            "{} {} uncommitted changes in git; edit on top of {} anyway?",
+            dirty.join(", "),
+            verb,
+            pronoun
+        ),
+        false,
+    )?;
+    Ok(confirm.then_some(dirty))
+}
+
+/// Remind that the diff now mixes the update with earlier changes
+fn print_dirty_note(dirty: &[String]) {
+    if !dirty.is_empty() {
+        output::print_warning(&format!(
+            "{} also had uncommitted changes from before this update; review `git diff` before committing.",
+            dirty.join(", ")
+        ));
+    }
+}
+
+/// Write the changelog entries of `updates` to `path`. Crates whose notes
+/// can't be fetched get a link, so only writing the file can fail.
+fn write_changelog(manifest_path: &Path, path: &Path, updates: &[&Dependency]) -> Result<()> {
+    let mut updates: Vec<ChangelogUpdate> = updates
+        .iter()
+        .filter_map(|dep| {
+            Some(ChangelogUpdate {
+                name: dep.name.clone(),
+                from: dep.current_version.clone(),
+                to: dep.latest_version.clone()?,
+            })
+        })
+        .collect();
+    updates.sort_by(|a, b| a.name.cmp(&b.name));
+    updates.dedup();
+
+    let root = manifest_path.parent().unwrap_or(Path::new("."));
+    let concurrency = Config::load(root)?.settings().concurrency;
+    let client = ChangelogClient::new()?;
+    let changelogs = runtime()?.block_on(fetch_changelogs(&client, &updates, concurrency));
+    std::fs::write(path, render_changelog(&changelogs))
+        .context(format!("Failed to write {}", display_path(path)))?;
+
+    let linked = changelogs
+        .iter()
+        .filter(|c| c.source == ChangelogSource::Link)
+        .count();
+    let mut message = format!(
+        "Changelog of {} written to {}",
+        plural(changelogs.len() as u64, "update"),
+        display_path(path)
+    );
+    if linked > 0 {
+        message.push_str(&format!(" ({} with a link only)", linked));
+    }
+    output::print_info(&message);
+    Ok(())
+}
+
+/// What an edit changed and where, e.g.
+/// `serde "1.0" → "1.0.200" ([dependencies], line 7)`
+/// Checks the features declared beside major updates against the target
+/// release in the sparse index, and rewrites them in the same edit
+struct FeatureCheck {
+    index: SparseIndexClient,
+    /// Ask for replacements and removals instead of deciding alone
+    interactive: bool,
+}
+
+impl FeatureCheck {
+    /// None when `--keep-features` leaves features alone
+    fn new(keep_features: bool, interactive: bool) -> Result<Option<Self>> {
+        if keep_features {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            index: SparseIndexClient::new()?,
+            interactive,
+        }))
+    }
+
+    /// Review the features of the declaration `edit` changed when it moves
+    /// across a major version. Missing features are dropped unless
+    /// replacements are picked; features that became defaults are dropped
+    /// only when confirmed. When the target can't be looked up the features
+    /// stay as they are. Returns the change made, if any.
+    fn apply(
+        &self,
+        updater: &mut DependencyUpdater,
+        declarations: &[(DependencySection, String, DependencySpec)],
+        edit: &ManifestEdit,
+        current: &semver::Version,
+        target: &semver::Version,
+    ) -> Result<Option<AuditChange>> {
+        if is_compatible(current, target) {
+            return Ok(None);
+        }
+        let Some((_, _, spec)) = declarations
+            .iter()
+            .find(|(section, name, _)| section == &edit.section && name == &edit.name)
+        else {
+            return Ok(None);
+        };
+        if spec.features().is_empty() {
+            return Ok(None);
+        }
+
+        let entry = runtime()?
+            .block_on(self.index.entries(&edit.name))
+            .map(|entries| entries.into_iter().find(|e| &e.vers == target));
+        let entry = match entry {
+            Ok(Some(entry)) => entry,
+            Ok(None) => {
+                output::print_warning(&format!(
+                    "{} {} isn't in the index; its features were kept as they are",
+                    edit.name, target
+                ));
+                return Ok(None);
+            }
+            Err(e) => {
+                output::print_warning(&format!(
+                    "Could not check the features of {} {}: {}",
+                    edit.name, target, e
+                ));
+                return Ok(None);
+            }
+        };
+
+        let review = review_features(
+            &edit.name,
+            &edit.section,
+            spec.features(),
+            spec.default_features(),
+            &entry,
+        );
+        if review.is_clean() {
+            return Ok(None);
+        }
+
+        let mut features = review.present();
+        if !review.missing.is_empty() {
+            let replacements = review.replacements();
+            output::print_warning(&format!(
+                "{} {} has no feature {}; available: {}",
+                edit.name,
+                target,
+                review.missing.join(", "),
+                if replacements.is_empty() {
+                    "none".to_string()
+                } else {
+                    replacements.join(", ")
+                }
+            ));
+            if self.interactive && !replacements.is_empty() {
+                let chosen = prompt::multi_select(
+                    &format!(
+                        "Replacements for {} (Space to select, Enter to confirm)",
+                        review.missing.join(", ")
+                    ),
+                    &replacements,
+                )?;
+                features.extend(chosen.into_iter().map(|i| replacements[i].to_string()));
+            }
+        }
+        if !review.now_default.is_empty() {
+            let message = format!(
+                "{} {} enables {} by default",
+                edit.name,
+                target,
+                review.now_default.join(", ")
+            );
+            let drop = self.interactive
+                && prompt::confirm(&format!("{}; remove from features?", message), true)?;
+            if drop {
+                features.retain(|f| !review.now_default.contains(f));
+            } else if !self.interactive {
+                output::print_info(&format!("{}; it can be removed from features", message));
+            }
+        }
+        if features == review.declared {
+            return Ok(None);
+        }
+
+        if let Err(e) = updater.set_features(&edit.section, &edit.name, &features) {
+            eprintln!(
+                "  ✗ Failed to update the features of {}: {}",
+                edit.name.bad(),
+                e
+            );
+            return Ok(None);
+        }
+        let render = |list: &[String]| format!("[{}]", list.join(", "));
+        println!(
+            "  ✓ Features of {} {} → {} ([{}])",
+            edit.name.good(),
+            render(&review.declared).dimmed(),
+            render(&features).cyan(),
+            edit.section
+        );
+        Ok(Some(AuditChange {
+            name: edit.name.clone(),
+            old: Some(render(&review.declared)),
+            new: Some(render(&features)),
+            section: format!("{}.{}.features", edit.section, edit.name),
+        }))
+    }
+}
+
+pub(super) fn describe_edit(edit: &ManifestEdit) -> String {
+    format!(
+        "{} \"{}\" → \"{}\" ([{}], line {})",
+        edit.name.good(),
+        edit.old_requirement.dimmed(),
+        edit.new_requirement.cyan(),
+        edit.table(),
+        edit.line
+    )
+}
+
+/// Update crates across a workspace: each selected crate is bumped in every
+/// member that declares it
+#[allow(clippy::too_many_arguments)]
+fn update_workspace(
+    manifest: Manifest,
+    package: Option<&str>,
+    dry_run: bool,
+    all: bool,
+    pre: bool,
+    changelog: Option<&Path>,
+    allow_dirty: bool,
+    keep_features: bool,
+    ignore_rust_version: bool,
+    ignore_deny: bool,
+) -> Result<()> {
+    let manifest_path = manifest.path.clone();
+    let progress = ProgressMode::detect(false).build(false);
+    let report = run_workspace_check(manifest, package, pre, progress)?;
+    for member in &report.members {
+        print_policy_conflicts(&member.dependencies);
+    }
+    let outdated = report.outdated();
+
+    if outdated.is_empty() {
+        output::print_success("All dependencies are up to date! 🎉");
+        return Ok(());
+    }
+
+    let selected: Vec<&WorkspaceCrate> = if all {
+        let denied: Vec<&Dependency> = report
+            .members
+            .iter()
+            .flat_map(|m| &m.dependencies)
+            .filter(|d| is_denied(d, ignore_deny))
+            .collect();
+        for dep in &denied {
+            warn_denied(dep, ignore_deny);
+        }
+        outdated
+            .into_iter()
+            .filter(|c| !denied.iter().any(|d| d.name == c.name))
+            .collect()
+    } else {
+        let items: Vec<String> = outdated
+            .iter()
+            .map(|c| {
+                let members: Vec<&str> = c.declared_by.iter().map(|d| d.member.as_str()).collect();
+                format!(
+                    "{} → {} ({})",
+                    c.name,
+                    c.latest_version
+                        .as_ref()
+                        .map(|v| v.to_string())
+                        .unwrap_or_default(),
+                    members.join(", ")
+                )
+            })
+            .collect();
+        let selections = prompt::multi_select(
+            "Select crates to update (Space to select, Enter to confirm)",
+            &items,
+        )?;
+        selections.iter().map(|&i| outdated[i]).collect()
+    };
+
+    if selected.is_empty() {
+        output::print_info("No dependencies selected for update.");
+        return Ok(());
+    }
+
+    // One Cargo.lock serves every member, so an update reaches members
+    // that promise an older Rust than the new release needs
+    let msrv = workspace_msrv_conflicts(&manifest_path, &selected);
+    for conflict in &msrv {
+        if ignore_rust_version {
+            output::print_warning(&format!("Updating anyway: {}", conflict));
+        } else {
+            output::print_warning(&format!("Not updating {}: {}", conflict.name, conflict));
+        }
+    }
+    let selected: Vec<&str> = selected
+        .iter()
+        .map(|c| c.name.as_str())
+        .filter(|name| ignore_rust_version || !msrv.iter().any(|c| c.name == *name))
+        .collect();
+    if selected.is_empty() {
+        output::print_info("No dependencies left to update.");
+        print_msrv_summary(&msrv, ignore_rust_version);
+        return Ok(());
+    }
+
+    println!("\n{}", output::plain("📝 Updates to apply:").bold());
+    let mut planned = Vec::new();
+    for member in &report.members {
+        let deps: Vec<&Dependency> = member
+            .dependencies
+            .iter()
+            .filter(|d| d.has_update() && selected.contains(&d.name.as_str()))
+            .collect();
+        for dep in &deps {
+            println!(
+                "  {} {} {} → {}{}",
+                member.name.dimmed(),
+                dep.name.bold(),
+                dep.current_version.to_string().dimmed(),
+                dep.latest_version.as_ref().unwrap().to_string().cyan(),
+                policy_marker(dep)
+            );
+        }
+        if !deps.is_empty() {
+            planned.push((member, deps));
+        }
+    }
+    let planned_deps: Vec<&Dependency> = planned.iter().flat_map(|(_, d)| d.clone()).collect();
+    print_download_delta(&planned_deps);
+    println!();
+
+    if dry_run {
+        output::print_info("Dry-run mode: No changes will be made.");
+        print_msrv_summary(&msrv, ignore_rust_version);
+        if let Some(path) = changelog {
+            write_changelog(&manifest_path, path, &planned_deps)?;
+        }
+        return Ok(());
+    }
+
+    if !all && !prompt::confirm("Apply these updates?", true)? {
+        output::print_info("Update cancelled.");
+        return Ok(());
+    }
+
+    let manifests: Vec<PathBuf> = std::iter::once(manifest_path.clone())
+        .chain(planned.iter().map(|(member, _)| member.manifest.clone()))
+        .collect();
+    let Some(dirty) = check_worktree(&manifests, allow_dirty, !all)? else {
+        output::print_info("Update cancelled.");
+        return Ok(());
+    };
+
+    // Requirements inherited with `workspace = true` are changed once, in
+    // the root's [workspace.dependencies]
+    let mut inherited: Vec<&Dependency> = Vec::new();
+    let mut targets: Vec<(&str, &Path, Vec<&Dependency>)> = Vec::new();
+    for (member, deps) in &planned {
+        let (from_root, own): (Vec<&Dependency>, Vec<&Dependency>) =
+            deps.iter().partition(|dep| dep.inherited_from.is_some());
+        for dep in from_root {
+            if !inherited.iter().any(|d| d.name == dep.name) {
+                inherited.push(dep);
+            }
+        }
+        targets.push((&member.name, &member.manifest, own));
+    }
+    if !inherited.is_empty() {
+        match targets
+            .iter_mut()
+            .find(|(_, path, _)| *path == manifest_path)
+        {
+            Some((_, _, deps)) => deps.extend(inherited),
+            None => targets.insert(0, ("workspace root", &manifest_path, inherited)),
+        }
+    }
+    targets.retain(|(_, _, deps)| !deps.is_empty());
+
+    println!("\n{}", output::plain("🔄 Applying updates...").bold());
+    let features = FeatureCheck::new(keep_features, !all)?;
+    let mut updated = Vec::new();
+    for (name, path, deps) in targets {
+        let member_manifest = Manifest::from_path(path)?;
+        let declarations = member_manifest.declarations();
+        let mut updater = DependencyUpdater::new(member_manifest)?;
+        let mut changes = Vec::new();
+        for dep in deps {
+            let latest = dep.latest_version.as_ref().unwrap();
+            let result = if dep.inherited_from.is_some() {
+                updater.update_workspace_dependency(&dep.name, &latest.to_string())
+            } else {
+                updater.update_dependency(dep, &latest.to_string())
+            };
+            match result {
+                Ok(edit) => {
+                    println!("  ✓ Updated {} in {}", describe_edit(&edit), name);
+                    changes.push(AuditChange::from(&edit));
+                    if let (Some(features), false) = (&features, edit.workspace) {
+                        changes.extend(features.apply(
+                            &mut updater,
+                            &declarations,
+                            &edit,
+                            &dep.current_version,
+                            latest,
+                        )?);
+                    }
+                    updated.push(dep);
+                }
+                Err(e) => eprintln!("  ✗ Failed to update {} in {}: {}", dep.name.bad(), name, e),
+            }
+        }
+        let backup = updater.save()?;
+        record_audit(
+            &manifest_path,
+            AuditEntry::new("update", path)
+                .with_changes(changes)
+                .with_backup(Some(backup)),
+        );
+    }
+    println!();
+    output::print_success("Workspace manifests updated successfully!");
+    output::print_info("Backups saved next to each Cargo.toml as Cargo.toml.backup");
+    print_msrv_summary(&msrv, ignore_rust_version);
+    print_dirty_note(&dirty);
+    if let Some(path) = changelog {
+        write_changelog(&manifest_path, path, &updated)?;
+    }
+
+    Ok(())
+}
+
+/// Members whose `rust-version` the updates of `crates` would outgrow,
+/// going by the `rust-version` each target release declares in the index.
+/// Releases the index can't be asked about are let through.
+fn workspace_msrv_conflicts(manifest_path: &Path, crates: &[&WorkspaceCrate]) -> Vec<MsrvConflict> {
+    let Some(workspace) = Manifest::from_path(manifest_path)
+        .ok()
+        .and_then(|root| Workspace::load(root).ok().flatten())
+    else {
+        return Vec::new();
+    };
+    let (Ok(index), Ok(rt)) = (SparseIndexClient::new(), runtime()) else {
+        return Vec::new();
+    };
+    let concurrency = manifest_path
+        .parent()
+        .and_then(|root| Config::load(root).ok())
+        .map(|c| c.settings().concurrency)
+        .unwrap_or(DEFAULT_CONCURRENCY);
+
+    let required: Vec<Option<(&str, &Version, Version)>> = rt.block_on(
+        stream::iter(crates)
+            .map(|krate| {
+                let index = &index;
+                async move {
+                    let target = krate.latest_version.as_ref()?;
+                    let entries = index.entries(&krate.name).await.ok()?;
+                    let entry = entries.into_iter().find(|e| e.vers == *target)?;
+                    let required = parse_rust_version(entry.rust_version.as_deref()?)?;
+                    Some((krate.name.as_str(), target, required))
+                }
+            })
+            .buffered(concurrency)
+            .collect(),
+    );
+    required
+        .into_iter()
+        .flatten()
+        .flat_map(|(name, target, required)| msrv_conflicts(&workspace, name, target, &required))
+        .collect()
+}
+
+/// Name the members whose `rust-version` decided what happened to an update
+fn print_msrv_summary(conflicts: &[MsrvConflict], ignored: bool) {
+    let mut by_crate: BTreeMap<(&str, &Version), Vec<String>> = BTreeMap::new();
+    for conflict in conflicts {
+        by_crate
+            .entry((conflict.name.as_str(), &conflict.target))
+            .or_default()
+            .push(format!(
+                "{} ({})",
+                conflict.member,
+                format_rust_version(&conflict.declared)
+            ));
+    }
+    for ((name, target), members) in by_crate {
+        if ignored {
+            output::print_warning(&format!(
+                "{} may not build with {} {}: raise the rust-version or pin {}",
+                members.join(", "),
+                name,
+                target,
+                name
+            ));
+        } else {
+            output::print_info(&format!(
+                "Held back {} {} for {}; --ignore-rust-version updates it anyway",
+                name,
+                target,
+                members.join(", ")
+            ));
+        }
+    }
+}
+
+/// Warn about dependencies the versions file can't be applied to; `update`
+/// leaves them alone rather than guessing which side is right
+fn print_policy_conflicts(dependencies: &[Dependency]) {
+    for dep in dependencies {
+        let Some(policy) = &dep.policy else {
+            continue;
+        };
+        if let PolicyStatus::Conflict(reason) = &policy.status {
+            output::print_warning(&format!(
+                "{}: {} conflicts with the declared requirement ({}); not updating",
+                dep.name,
+                policy.blessed(),
+                reason
+            ));
+        }
+    }
+}
+
+/// Label an update target chosen by the versions file, with any newer
+/// release a tracked series leaves out
+pub(super) fn policy_marker(dep: &Dependency) -> String {
+    let Some(policy) = &dep.policy else {
+        return String::new();
+    };
+    let label = match (&policy.track, &policy.newer_series) {
+        (Some(series), Some(newer)) => format!("(tracked {}; {} is out)", series, newer),
+        (Some(series), None) => format!("(tracked {})", series),
+        (None, _) => format!("(org policy: {})", policy.requirement),
+    };
+    format!(" {}", label.dimmed())
+}
+
+/// How much more the selected updates download, below the list of them
+fn print_download_delta(deps: &[&Dependency]) {
+    let Some((delta, counted)) = download_delta(deps) else {
+        return;
+    };
+    let sign = if delta < 0 { "-" } else { "+" };
+    println!(
+        "  {}",
+        format!(
+            "Download size: {}{} across {}",
+            sign,
+            format_bytes(delta.unsigned_abs()),
+            plural(counted as u64, "update")
+        )
+        .dimmed()
+    );
+}
+
+pub(super) fn print_license_change(dep: &Dependency, indent: &str) {
+    let Some(change) = &dep.license_change else {
+        return;
+    };
+    let note = format!("⚠ license changes: {} → {}", change.from, change.to);
+    if change.denied {
+        println!(
+            "{}{} {}",
+            indent,
+            note.caution(),
+            "(denied by [licenses])".bad().bold()
+        );
+    } else {
+        println!("{}{}", indent, note.caution());
+    }
+}
+
+/// Note an update target the project's `deny.toml` won't take
+pub(super) fn print_deny_violation(dep: &Dependency, indent: &str) {
+    if let Some(violation) = &dep.deny {
+        println!("{}{}", indent, format!("⛔ {}", violation).bad());
+    }
+}
+
+/// `--all` never applies an update to a license the config denies, nor,
+/// unless `--ignore-deny`, one `deny.toml` won't take; those have to be
+/// picked by hand
+fn without_denied(deps: Vec<&Dependency>, ignore_deny: bool) -> Vec<&Dependency> {
+    deps.into_iter()
+        .filter(|dep| {
+            let denied = is_denied(dep, ignore_deny);
+            if denied {
+                warn_denied(dep, ignore_deny);
+            }
+            !denied
+        })
+        .collect()
+}
+
+fn is_denied(dep: &Dependency, ignore_deny: bool) -> bool {
+    dep.license_change.as_ref().is_some_and(|c| c.denied) || (!ignore_deny && dep.deny.is_some())
+}
+
+fn warn_denied(dep: &Dependency, ignore_deny: bool) {
+    let Some(latest) = &dep.latest_version else {
+        return;
+    };
+    if let Some(change) = dep.license_change.as_ref().filter(|c| c.denied) {
+        output::print_warning(&format!(
+            "Not updating {} with --all: {} is under {}, which [licenses] denies",
+            dep.name, latest, change.to
+        ));
+    } else if let Some(violation) = dep.deny.as_ref().filter(|_| !ignore_deny) {
+        output::print_warning(&format!(
+            "Not updating {} with --all: {} is {}; --ignore-deny applies it anyway",
+            dep.name,
+            latest,
+            match violation {
+                DenyViolation::Banned { .. } => violation.to_string(),
+                DenyViolation::License { license } =>
+                    format!("under {}, which deny.toml denies", license),
+            }
+        ));
+    }
+}
+
+/// Point out a requirement inherited from `[workspace.dependencies]`, which
+/// is where updating it happens
+pub(super) fn inherited_marker(dep: &Dependency) -> String {
+    if dep.inherited_from.is_some() {
+        format!(" {}", "(workspace)".dimmed())
+    } else {
+        String::new()
+    }
+}
+
+/// Point out a dependency declared under `[target.<predicate>]`, with the
+/// platform it's for
+pub(super) fn target_marker(dep: &Dependency) -> String {
+    match &dep.target {
+        Some(target) => format!(" {}", format!("({})", target).dimmed()),
+        None => String::new(),
+    }
+}
+
+/// Diff the dependency lists of each selected update. Anything that fails
+/// to resolve yields `None` so the update itself is never blocked.
+fn update_impacts(manifest: &Manifest, deps: &[&Dependency]) -> Vec<Option<UpdateImpact>> {
+    let Ok(index) = SparseIndexClient::new() else {
+        return vec![None; deps.len()];
+    };
+    let Ok(rt) = runtime() else {
+        return vec![None; deps.len()];
+    };
+    let metadata = cargo_options(manifest)
+        .and_then(|cargo| cargo::metadata(&manifest.path, &cargo))
+        .ok();
+    let concurrency = manifest
+        .path
+        .parent()
+        .and_then(|root| Config::load(root).ok())
+        .map(|c| c.settings().concurrency)
+        .unwrap_or(DEFAULT_CONCURRENCY);
+
+    rt.block_on(
+        stream::iter(deps)
+            .map(|dep| {
+                let index = &index;
+                let metadata = metadata.as_ref();
+                async move {
+                    let latest = dep.latest_version.as_ref()?;
+                    update_impact(index, &dep.name, &dep.current_version, latest, metadata)
+                        .await
+                        .ok()
+                }
+            })
+            .buffered(concurrency)
+            .collect(),
+    )
+}
+
+/// Breaking-surface estimates for the major updates among `deps`. Anything
+/// without one, minor and patch updates included, yields `None`.
+pub(super) fn major_api_diffs(manifest: &Manifest, deps: &[&Dependency]) -> Vec<Option<ApiDiff>> {
+    let majors: Vec<(&str, &Version, &Version)> = deps
+        .iter()
+        .filter(|dep| dep.update_type() == UpdateType::Major)
+        .filter_map(|dep| {
+            Some((
+                dep.name.as_str(),
+                &dep.current_version,
+                dep.latest_version.as_ref()?,
+            ))
+        })
+        .collect();
+    if majors.is_empty() {
+        return vec![None; deps.len()];
+    }
+
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let concurrency = Config::load(root)
+        .map(|c| c.settings().concurrency)
+        .unwrap_or(DEFAULT_CONCURRENCY);
+    let found = ApiDiffClient::new(cargo_options(manifest).ok())
+        .and_then(|client| runtime()?.block_on(api_diffs(&client, &majors, root, concurrency)));
+    let Ok(found) = found else {
+        return vec![None; deps.len()];
+    };
+
+    deps.iter()
+        .map(|dep| {
+            found
+                .iter()
+                .flatten()
+                .find(|diff| diff.name == dep.name && diff.from == dep.current_version)
+                .filter(|_| dep.update_type() == UpdateType::Major)
+                .cloned()
+        })
+        .collect()
+}
+
+pub(super) fn print_api_diff(diff: &ApiDiff, indent: &str) {
+    println!(
+        "{}{} {}",
+        indent,
+        diff.describe().caution(),
+        format!("({})", diff.link()).dimmed()
+    );
+}
+
+fn print_update_impact(impact: &UpdateImpact) {
+    if impact.is_empty() {
+        println!("      {}", "no dependency changes".dimmed());
+        return;
+    }
+
+    let mut parts = Vec::new();
+    if !impact.added.is_empty() {
+        let added: Vec<String> = impact
+            .added
+            .iter()
+            .map(|(name, req)| format!("{} {}", name, req))
+            .collect();
+        parts.push(format!("adds: {}", added.join(", ")).caution().to_string());
+    }
+    if !impact.removed.is_empty() {
+        parts.push(format!("removes: {}", impact.removed.join(", ")));
+    }
+    println!("      {}", parts.join(" / "));
+
+    if let Some(count) = impact.new_packages.filter(|&c| c > 0) {
+        println!(
+            "      {}",
+            format!("~{} new packages in the dependency graph", count).dimmed()
+        );
+    }
+}
+
+/// Interactive selection of dependencies to update
+fn select_dependencies_to_update<'a>(deps: &[&'a Dependency]) -> Result<Vec<&'a Dependency>> {
+    let items: Vec<String> = deps
+        .iter()
+        .map(|d| {
+            let update_type = output::label(Status::of_update(d.update_type())).clear();
+            let policy = match d.policy.as_ref().and_then(|p| p.track.as_ref()) {
+                Some(series) => format!(" (tracked {})", series),
+                None if d.policy.is_some() => " (org policy)".to_string(),
+                None => String::new(),
+            };
+            let deny = match &d.deny {
+                Some(violation) => format!(" ⛔ {}", violation),
+                None => String::new(),
+            };
+            format!(
+                "{} {} {} → {}{}{}",
+                update_type,
+                d.name,
+                d.current_version,
+                d.latest_version.as_ref().unwrap(),
+                policy,
+                deny
+            )
+        })
+        .collect();
+
+    let selections = prompt::multi_select(
+        "Select dependencies to update (Space to select, Enter to confirm)",
+        &items,
+    )?;
+
+    let selected: Vec<&Dependency> = selections.iter().map(|&i| deps[i]).collect();
+    Ok(selected)
+}
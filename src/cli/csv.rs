@@ -0,0 +1,125 @@
+//! CSV renderings of check and health reports, for spreadsheets
+//!
+//! Every output starts with a header row, even when there are no findings,
+//! and has one row per finding. Fields are quoted per RFC 4180 when they
+//! contain a comma, quote or line break.
+
+use crate::analyzer::checker::CheckReport;
+use crate::analyzer::health::HealthReport;
+use crate::core::dependency::{DependencyKind, UpdateType};
+use std::borrow::Cow;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+pub const CHECK_HEADER: [&str; 7] = [
+    "name",
+    "kind",
+    "current",
+    "latest",
+    "update_type",
+    "age_days",
+    "yanked",
+];
+
+pub const HEALTH_HEADER: [&str; 6] = [
+    "name",
+    "version",
+    "advisory_id",
+    "severity",
+    "patched_version",
+    "url",
+];
+
+/// One row per dependency; `age_days` is the age of the current version as
+/// of the Unix time `now`, empty when the registry didn't say
+pub fn check_csv(report: &CheckReport, now: u64) -> String {
+    let rows = report.dependencies.iter().map(|dep| {
+        let kind = match dep.kind {
+            DependencyKind::Normal => "normal",
+            DependencyKind::Dev => "dev",
+            DependencyKind::Build => "build",
+        };
+        let update_type = match dep.update_type() {
+            UpdateType::UpToDate => "up-to-date",
+            UpdateType::Patch => "patch",
+            UpdateType::Minor => "minor",
+            UpdateType::Major => "major",
+        };
+        vec![
+            dep.name.clone(),
+            kind.to_string(),
+            dep.current_version.to_string(),
+            dep.latest_version
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            update_type.to_string(),
+            dep.released_at
+                .map(|released| (now.saturating_sub(released) / SECONDS_PER_DAY).to_string())
+                .unwrap_or_default(),
+            dep.yanked.to_string(),
+        ]
+    });
+    to_csv(&CHECK_HEADER, rows)
+}
+
+/// One row per advisory, so a crate with two advisories yields two rows
+pub fn health_csv(report: &HealthReport) -> String {
+    let rows = report.vulnerable.iter().flat_map(|package| {
+        package.advisories.iter().map(|advisory| {
+            vec![
+                package.name.clone(),
+                package.version.to_string(),
+                advisory.id.clone(),
+                advisory.severity.map(|s| s.to_string()).unwrap_or_default(),
+                advisory.patched_versions.join(" || "),
+                advisory.url.clone(),
+            ]
+        })
+    });
+    to_csv(&HEALTH_HEADER, rows)
+}
+
+fn to_csv(header: &[&str], rows: impl Iterator<Item = Vec<String>>) -> String {
+    let mut out = String::new();
+    push_record(&mut out, header.iter().copied());
+    for row in rows {
+        push_record(&mut out, row.iter().map(String::as_str));
+    }
+    out
+}
+
+fn push_record<'a>(out: &mut String, fields: impl Iterator<Item = &'a str>) {
+    let fields: Vec<Cow<str>> = fields.map(escape).collect();
+    out.push_str(&fields.join(","));
+    out.push('\n');
+}
+
+fn escape(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("serde"), "serde");
+        assert_eq!(escape(">=1.2, <2"), "\">=1.2, <2\"");
+        assert_eq!(escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_header_without_rows() {
+        assert_eq!(
+            to_csv(&HEALTH_HEADER, std::iter::empty()),
+            "name,version,advisory_id,severity,patched_version,url\n"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! What `cargo sane defaults` reports: the settings a run goes by and the
+//! versions of the behavior scripts rely on
+//!
+//! Each contract version is bumped when the behavior it covers changes in a
+//! way a script could notice, so CI pinning `defaults --json` fails when a
+//! new cargo-sane changes something it depends on.
+
+use crate::analyzer::plugin::PROTOCOL_VERSION;
+use crate::cli::schema::SchemaKind;
+use crate::core::config::{Config, Settings, CONFIG_FILE};
+use crate::core::dependency::CLASSIFICATION_VERSION;
+use crate::utils::advisories::OSV_API;
+use crate::utils::cache::STATE_DIR;
+use crate::utils::cache_store::{catalog, CacheDefault};
+use crate::utils::crates_io::CRATES_IO_API;
+use crate::utils::formatting::SCHEMA_VERSION;
+use crate::utils::sparse_index::CRATES_IO_INDEX;
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Version of what the exit codes in [`EXIT_CODES`] mean. Bumped when a
+/// code changes meaning or a command starts exiting with a new one.
+pub const EXIT_CODES_VERSION: u32 = 1;
+
+/// An error, or findings the command fails on
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Arguments clap can't parse
+pub const EXIT_USAGE: i32 = 2;
+
+/// `--timeout` cut the run short, with the default `--timeout-exit`
+pub const EXIT_TIMEOUT: i32 = 124;
+
+/// Every exit code, with what it means
+pub const EXIT_CODES: [(i32, &str); 4] = [
+    (0, "success, and nothing the command fails on was found"),
+    (EXIT_FAILURE, "an error, or findings the command fails on"),
+    (EXIT_USAGE, "invalid arguments"),
+    (EXIT_TIMEOUT, "cut short by --timeout"),
+];
+
+/// The versions of the behavioral contracts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Contracts {
+    /// How updates are classified as patch, minor or major
+    pub classification: u32,
+    /// What the exit codes mean
+    pub exit_codes: u32,
+    /// The shape of the `--json` documents
+    pub schema: u32,
+    /// What `report --plugin` programs read and print
+    pub plugin_protocol: u32,
+}
+
+/// An exit code and what it means
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ExitCode {
+    pub code: i32,
+    pub meaning: String,
+}
+
+/// Where lookups go
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Sources {
+    /// The crates.io API, for releases, owners and download counts
+    pub registry: String,
+    /// The sparse index, for features and checksums
+    pub index: String,
+    /// The OSV.dev API advisories come from, kept in the local advisory
+    /// database for `advisory_db_max_age_days`
+    pub advisories: String,
+}
+
+/// Every default in effect for one project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Defaults {
+    pub contracts: Contracts,
+    pub exit_codes: Vec<ExitCode>,
+    /// The version of each `--json` document, by its `cargo sane schema`
+    /// name
+    pub schemas: BTreeMap<String, u32>,
+    /// The config file the settings come from, when the project has one
+    pub config_file: Option<PathBuf>,
+    pub settings: Settings,
+    /// Where per-project state and caches are kept
+    pub state_dir: PathBuf,
+    pub caches: Vec<CacheDefault>,
+    pub sources: Sources,
+}
+
+impl Defaults {
+    /// The defaults of the project in `root`, which has `config`
+    pub fn for_project(root: &Path, config: &Config) -> Self {
+        let settings = config.settings();
+        let config_file = root.join(CONFIG_FILE);
+        Self {
+            contracts: Contracts {
+                classification: CLASSIFICATION_VERSION,
+                exit_codes: EXIT_CODES_VERSION,
+                schema: SCHEMA_VERSION,
+                plugin_protocol: PROTOCOL_VERSION,
+            },
+            exit_codes: EXIT_CODES
+                .iter()
+                .map(|(code, meaning)| ExitCode {
+                    code: *code,
+                    meaning: meaning.to_string(),
+                })
+                .collect(),
+            schemas: SchemaKind::value_variants()
+                .iter()
+                .map(|kind| (kind.name().to_string(), kind.version()))
+                .collect(),
+            config_file: config_file.exists().then_some(config_file),
+            caches: catalog(settings.cache_ttl_minutes),
+            settings,
+            state_dir: root.join(STATE_DIR),
+            sources: Sources {
+                registry: CRATES_IO_API.to_string(),
+                index: CRATES_IO_INDEX.to_string(),
+                advisories: OSV_API.to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_settings_cover_every_config_setting() {
+        // Lists and tables, such as ignore_crates or [freshness], aren't
+        // defaults so much as the project's own data
+        let config = serde_json::to_value(Config::default()).unwrap();
+        let mut expected: Vec<&String> = config
+            .as_object()
+            .unwrap()
+            .iter()
+            .filter(|(_, value)| !value.is_array() && !value.is_object())
+            .map(|(key, _)| key)
+            .collect();
+        let settings = serde_json::to_value(Settings::DEFAULT).unwrap();
+        let mut actual: Vec<&String> = settings.as_object().unwrap().keys().collect();
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_every_schema_and_cache_is_listed() {
+        let dir = tempfile::tempdir().unwrap();
+        let defaults = Defaults::for_project(dir.path(), &Config::default());
+        assert_eq!(defaults.schemas.len(), SchemaKind::value_variants().len());
+        assert_eq!(defaults.config_file, None);
+        let json = serde_json::to_value(&defaults).unwrap();
+        let kinds: Vec<&Value> = json["caches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|cache| &cache["kind"])
+            .collect();
+        for kind in ["registry", "advisories", "metadata"] {
+            assert!(kinds.iter().any(|k| *k == kind), "{}", kind);
+        }
+    }
+}
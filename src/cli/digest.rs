@@ -0,0 +1,382 @@
+//! The Markdown digest `report --digest` writes for a team
+//!
+//! A digest leads with what changed since the previous one: updates that
+//! became available, new advisories and duplicates, and what got resolved.
+//! Issues that were already open stay visible in a capped "Still
+//! outstanding" section, split into a section per team when
+//! `.cargo-sane/owners.toml` assigned them. The state the previous digest
+//! described is the snapshot tagged [`DIGEST_TAG`]. Rendering depends on nothing but the two
+//! snapshots, so the same state always gives the same file.
+
+use crate::analyzer::plugin::{AnalyzerFindings, Finding};
+use crate::analyzer::priority::{rank, truncate, Significance};
+use crate::analyzer::snapshot::{NewAdvisory, Snapshot, SnapshotDiff};
+use crate::analyzer::teams::group_by_team;
+use crate::core::advisory::Severity;
+use crate::core::dependency::{Dependency, UpdateType};
+use crate::utils::formatting::format_date;
+use std::collections::BTreeSet;
+
+/// The snapshot tag holding the state the last digest described
+pub const DIGEST_TAG: &str = "digest";
+
+/// How many outstanding issues a digest lists when no `--limit` is given
+pub const DEFAULT_OUTSTANDING_LIMIT: usize = 15;
+
+/// Render the digest of `current`, with the changes since `previous` when
+/// there is an earlier state to compare with, listing at most `limit` outstanding issues
+/// (0 lists all)
+pub fn render_digest(current: &Snapshot, previous: Option<&Snapshot>, limit: usize) -> String {
+    let mut out = match &current.check.package {
+        Some(package) => format!("# Dependency digest: {}\n\n", package),
+        None => String::from("# Dependency digest\n\n"),
+    };
+    let generated = format!(
+        "Generated {} by cargo-sane {}",
+        format_date(current.created_at),
+        current.tool_version
+    );
+    match previous {
+        Some(previous) => out.push_str(&format!(
+            "{}, covering changes since {}.\n\n",
+            generated,
+            format_date(previous.created_at)
+        )),
+        None => out.push_str(&format!(
+            "{}. There is no earlier state to compare with, so every open issue is listed \
+             as outstanding.\n\n",
+            generated
+        )),
+    }
+
+    summary(&mut out, current, previous);
+    if let Some(previous) = previous {
+        let diff = previous.diff(current);
+        new_since(&mut out, current, &diff);
+        resolved_since(&mut out, &diff);
+    }
+    outstanding(&mut out, current, previous, limit);
+    out
+}
+
+/// An "Analyzer findings" section with a list per analyzer, for a report
+/// that ran any; appended to the digest by `report`
+pub fn analyzer_markdown(analyzers: &[AnalyzerFindings]) -> String {
+    if analyzers.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from("## Analyzer findings\n\n");
+    for analyzer in analyzers {
+        let lines: Vec<String> = match &analyzer.error {
+            Some(error) => vec![format!("**FAILED**: {}", error)],
+            None => analyzer.findings.iter().map(finding_line).collect(),
+        };
+        if section(&mut out, &analyzer.analyzer, &lines) {
+            out.push_str(&format!("### {}\n\nNothing found.\n\n", analyzer.analyzer));
+        }
+    }
+    out
+}
+
+/// Dependencies, outdated dependencies, affected packages and duplicated
+/// crates, each `None` when the run couldn't tell
+fn counts(snapshot: &Snapshot) -> [Option<usize>; 4] {
+    let names = |outdated: bool| {
+        snapshot
+            .check
+            .dependencies
+            .iter()
+            .filter(|d| !outdated || d.has_update())
+            .map(|d| d.name.as_str())
+            .collect::<BTreeSet<&str>>()
+            .len()
+    };
+    [
+        Some(names(false)),
+        Some(names(true)),
+        snapshot.health.as_ref().map(|h| h.vulnerable.len()),
+        snapshot.conflicts.as_ref().map(|c| c.conflicts.len()),
+    ]
+}
+
+fn summary(out: &mut String, current: &Snapshot, previous: Option<&Snapshot>) {
+    const ROWS: [&str; 4] = [
+        "Dependencies",
+        "Outdated",
+        "Packages with advisories",
+        "Duplicated crates",
+    ];
+    let now = counts(current);
+    let before = previous.map(counts);
+
+    out.push_str("| | Now | Change |\n|---|---:|---:|\n");
+    for (i, row) in ROWS.iter().enumerate() {
+        let value = now[i].map_or("unavailable".to_string(), |n| n.to_string());
+        let change = match (now[i], before.and_then(|b| b[i])) {
+            (Some(now), Some(before)) if now > before => format!("+{}", now - before),
+            (Some(now), Some(before)) if now < before => format!("-{}", before - now),
+            (Some(_), Some(_)) => "0".to_string(),
+            _ => "–".to_string(),
+        };
+        out.push_str(&format!("| {} | {} | {} |\n", row, value, change));
+    }
+    out.push('\n');
+}
+
+fn new_since(out: &mut String, current: &Snapshot, diff: &SnapshotDiff) {
+    out.push_str("## New\n\n");
+    let mut empty = true;
+
+    let updates: Vec<String> = diff
+        .newly_outdated
+        .iter()
+        .map(|dep| {
+            let kind = current
+                .check
+                .dependencies
+                .iter()
+                .find(|d| d.name == dep.name)
+                .map_or("", |d| update_label(d));
+            format!(
+                "`{}` {} → {}{}",
+                dep.name, dep.current_version, dep.latest_version, kind
+            )
+        })
+        .collect();
+    empty &= section(out, "Updates available", &updates);
+
+    match &diff.new_advisories {
+        Some(advisories) => {
+            let advisories: Vec<String> = advisories.iter().map(advisory_line).collect();
+            empty &= section(out, "Advisories", &advisories);
+        }
+        None => {
+            out.push_str(
+                "_Advisories weren't compared: one of the runs has no advisory scan._\n\n",
+            );
+        }
+    }
+    match &diff.new_conflicts {
+        Some(conflicts) => {
+            let conflicts: Vec<String> = conflicts.iter().map(|c| format!("`{}`", c)).collect();
+            empty &= section(out, "Duplicated crates", &conflicts);
+        }
+        None => {
+            out.push_str("_Duplicates weren't compared: one of the runs has no `cargo tree`._\n\n");
+        }
+    }
+    if let Some(new) = &diff.new_build_time_code {
+        let new: Vec<String> = new.iter().map(|name| format!("`{}`", name)).collect();
+        empty &= section(out, "Build-time code", &new);
+    }
+    let added: Vec<String> = diff
+        .added
+        .iter()
+        .map(|name| format!("`{}`", name))
+        .collect();
+    empty &= section(out, "Added dependencies", &added);
+
+    if empty {
+        out.push_str("Nothing new.\n\n");
+    }
+}
+
+fn resolved_since(out: &mut String, diff: &SnapshotDiff) {
+    out.push_str("## Resolved\n\n");
+    let mut lines: Vec<String> = Vec::new();
+    lines.extend(
+        diff.no_longer_outdated
+            .iter()
+            .map(|name| format!("- `{}` is up to date\n", name)),
+    );
+    lines.extend(
+        diff.removed
+            .iter()
+            .map(|name| format!("- `{}` is no longer a dependency\n", name)),
+    );
+    lines.extend(diff.resolved_advisories.iter().flatten().map(|a| {
+        format!(
+            "- {} no longer affects `{}` {}\n",
+            a.id, a.package, a.version
+        )
+    }));
+    lines.extend(
+        diff.resolved_conflicts
+            .iter()
+            .flatten()
+            .map(|name| format!("- `{}` is no longer duplicated\n", name)),
+    );
+
+    if lines.is_empty() {
+        out.push_str("Nothing resolved.\n\n");
+    } else {
+        out.push_str(&lines.concat());
+        out.push('\n');
+    }
+}
+
+/// Open issues, advisories first: all of them for a first digest, else
+/// those the previous digest already had
+fn outstanding(out: &mut String, current: &Snapshot, previous: Option<&Snapshot>, limit: usize) {
+    out.push_str(match previous {
+        Some(_) => "## Still outstanding\n\n",
+        None => "## Outstanding\n\n",
+    });
+
+    // Each with the team owning it
+    let mut items: Vec<(Option<&str>, String)> = Vec::new();
+    if let Some(health) = &current.health {
+        let known: Option<BTreeSet<(&str, &semver::Version)>> = previous.map(|previous| {
+            previous
+                .health
+                .iter()
+                .flat_map(|h| &h.vulnerable)
+                .map(|p| (p.name.as_str(), &p.version))
+                .collect()
+        });
+        let mut affected: Vec<_> = health
+            .vulnerable
+            .iter()
+            .filter(|p| {
+                known
+                    .as_ref()
+                    .is_none_or(|known| known.contains(&(p.name.as_str(), &p.version)))
+            })
+            .map(|p| (p, p.advisories.iter().filter_map(|a| a.severity).max()))
+            .collect();
+        affected.sort_by_key(|(_, severity)| std::cmp::Reverse(*severity));
+        items.extend(affected.into_iter().map(|(package, severity)| {
+            let ids: Vec<&str> = package.advisories.iter().map(|a| a.id.as_str()).collect();
+            let line = format!(
+                "**{}** `{}` {}: {}",
+                severity_label(severity),
+                package.name,
+                package.version,
+                ids.join(", ")
+            );
+            (package.owner.as_deref(), line)
+        }));
+    }
+
+    let was_open: Option<BTreeSet<&str>> = previous.map(|previous| {
+        previous
+            .check
+            .dependencies
+            .iter()
+            .filter(|d| is_open(d))
+            .map(|d| d.name.as_str())
+            .collect()
+    });
+    let mut seen = BTreeSet::new();
+    let mut dependencies: Vec<&Dependency> = current
+        .check
+        .dependencies
+        .iter()
+        .filter(|d| is_open(d) && seen.insert(d.name.as_str()))
+        .filter(|d| {
+            was_open
+                .as_ref()
+                .is_none_or(|open| open.contains(d.name.as_str()))
+        })
+        .collect();
+    rank(&mut dependencies, |dep| {
+        Significance::of_dependency(dep, false)
+    });
+    items.extend(dependencies.into_iter().map(|dep| {
+        let mut line = format!("`{}` {}", dep.name, dep.current_version);
+        if let Some(latest) = dep.latest_version.as_ref().filter(|_| dep.has_update()) {
+            line.push_str(&format!(" → {}{}", latest, update_label(dep)));
+        }
+        if dep.yanked {
+            line.push_str(", yanked");
+        }
+        (dep.owner.as_deref(), line)
+    }));
+
+    if items.is_empty() {
+        out.push_str("Nothing outstanding.\n");
+        return;
+    }
+    let (shown, hidden) = truncate(&items, limit);
+    if items.iter().any(|(owner, _)| owner.is_some()) {
+        let shown: Vec<&(Option<&str>, String)> = shown.iter().collect();
+        for (i, (team, items)) in group_by_team(&shown, |(owner, _)| *owner)
+            .into_iter()
+            .enumerate()
+        {
+            if i > 0 {
+                out.push('\n');
+            }
+            out.push_str(&format!("### {}\n\n", team));
+            for (_, line) in items {
+                out.push_str(&format!("- {}\n", line));
+            }
+        }
+    } else {
+        for (_, line) in shown {
+            out.push_str(&format!("- {}\n", line));
+        }
+    }
+    if hidden > 0 {
+        out.push_str(&format!("- …and {} more\n", hidden));
+    }
+}
+
+/// An update or a yanked version; snoozed updates are left to their snooze
+fn is_open(dep: &Dependency) -> bool {
+    (dep.has_update() && !dep.snoozed) || dep.yanked
+}
+
+/// A `### title` list, unless there's nothing to list; true when empty
+fn section(out: &mut String, title: &str, lines: &[String]) -> bool {
+    if lines.is_empty() {
+        return true;
+    }
+    out.push_str(&format!("### {}\n\n", title));
+    for line in lines {
+        out.push_str(&format!("- {}\n", line));
+    }
+    out.push('\n');
+    false
+}
+
+fn advisory_line(advisory: &NewAdvisory) -> String {
+    format!(
+        "**{}** {} in `{}` {}: {}",
+        severity_label(advisory.severity),
+        advisory.id,
+        advisory.package,
+        advisory.version,
+        advisory.title
+    )
+}
+
+fn finding_line(finding: &Finding) -> String {
+    let dependency = finding
+        .dependency
+        .as_ref()
+        .map(|name| format!(" in `{}`", name))
+        .unwrap_or_default();
+    format!(
+        "**{}** `{}`{}: {}",
+        finding.severity.to_string().to_uppercase(),
+        finding.code,
+        dependency,
+        finding.message
+    )
+}
+
+pub(crate) fn severity_label(severity: Option<Severity>) -> String {
+    severity
+        .map(|s| s.to_string().to_uppercase())
+        .unwrap_or_else(|| "UNRATED".to_string())
+}
+
+pub(crate) fn update_label(dep: &Dependency) -> &'static str {
+    match dep.update_type() {
+        UpdateType::Patch => " (patch)",
+        UpdateType::Minor => " (minor)",
+        UpdateType::Major => " (major)",
+        UpdateType::UpToDate => "",
+    }
+}
@@ -0,0 +1,159 @@
+//! One-time hints at faster ways to run
+//!
+//! A run that waited long on the network ends with a dimmed line naming the
+//! settings that would have saved the time, chosen from the ones the
+//! current configuration leaves off. Machine-readable output and
+//! `--progress none` get no hint, and no run gets more than one.
+
+use crate::core::config::CONFIG_FILE;
+use crate::utils::formatting::format_duration;
+use crate::utils::progress::ProgressMode;
+use crate::utils::registry::DEFAULT_CONCURRENCY;
+use colored::Colorize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Network time past which a run is worth a hint
+pub const SLOW_NETWORK: Duration = Duration::from_secs(15);
+
+/// The `cache_ttl_minutes` and `concurrency` the hint suggests
+const SUGGESTED_TTL_MINUTES: u64 = 60;
+const SUGGESTED_CONCURRENCY: usize = 16;
+
+static SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// The fast paths open to the command that just ran, and whether they're on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FastPaths {
+    /// Whether `--offline` was given, for commands that take it
+    pub offline: Option<bool>,
+    /// `cache_ttl_minutes`, for commands the check cache serves
+    pub cache_ttl_minutes: Option<u64>,
+    /// `concurrency` (0 for the default)
+    pub concurrency: usize,
+}
+
+impl FastPaths {
+    /// The remedies still open, each with the flag or setting to use
+    pub fn remedies(&self) -> Vec<String> {
+        let mut remedies = Vec::new();
+        if self.offline == Some(false) {
+            remedies.push("--offline".to_string());
+        }
+        if self.cache_ttl_minutes == Some(0) {
+            remedies.push(format!(
+                "enabling the cache (cache_ttl_minutes = {} in {})",
+                SUGGESTED_TTL_MINUTES, CONFIG_FILE
+            ));
+        }
+        let concurrency = match self.concurrency {
+            0 => DEFAULT_CONCURRENCY,
+            n => n,
+        };
+        if concurrency < SUGGESTED_CONCURRENCY {
+            remedies.push(format!(
+                "more lookups at once (concurrency = {} in {})",
+                SUGGESTED_CONCURRENCY, CONFIG_FILE
+            ));
+        }
+        remedies
+    }
+}
+
+/// The hint for a run that spent `spent` on the network, if it was slow
+/// and something in `paths` would help
+pub fn network_hint(spent: Duration, paths: &FastPaths) -> Option<String> {
+    if spent < SLOW_NETWORK {
+        return None;
+    }
+    let mut remedies = paths.remedies();
+    let last = remedies.pop()?;
+    let consider = if remedies.is_empty() {
+        last
+    } else {
+        format!("{}, or {}", remedies.join(", "), last)
+    };
+    Some(format!(
+        "This run spent {} on the network; consider {}",
+        format_duration(spent),
+        consider
+    ))
+}
+
+/// Print the network hint for this run, unless one was shown already or
+/// the output is for machines
+pub fn print_network_hint(spent: Duration, paths: &FastPaths, machine_readable: bool) {
+    if machine_readable || ProgressMode::detect(false) == ProgressMode::Hidden {
+        return;
+    }
+    let Some(hint) = network_hint(spent, paths) else {
+        return;
+    };
+    if !SHOWN.swap(true, Ordering::Relaxed) {
+        println!();
+        println!("{}", hint.dimmed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLOW: Duration = Duration::from_secs(48);
+
+    fn paths(
+        offline: Option<bool>,
+        cache_ttl_minutes: Option<u64>,
+        concurrency: usize,
+    ) -> FastPaths {
+        FastPaths {
+            offline,
+            cache_ttl_minutes,
+            concurrency,
+        }
+    }
+
+    #[test]
+    fn test_fast_runs_get_no_hint() {
+        let everything_off = paths(Some(false), Some(0), 0);
+        assert_eq!(network_hint(Duration::from_secs(14), &everything_off), None);
+        assert!(network_hint(SLOW_NETWORK, &everything_off).is_some());
+    }
+
+    #[test]
+    fn test_every_remedy_left_off() {
+        assert_eq!(
+            network_hint(SLOW, &paths(Some(false), Some(0), 0)).unwrap(),
+            "This run spent 48 seconds on the network; consider --offline, enabling the cache \
+             (cache_ttl_minutes = 60 in .cargo-sane.toml), or more lookups at once \
+             (concurrency = 16 in .cargo-sane.toml)"
+        );
+    }
+
+    #[test]
+    fn test_remedies_follow_the_configuration() {
+        let remedies = |offline, ttl, concurrency| paths(offline, ttl, concurrency).remedies();
+        let offline = "--offline";
+        let cache = "enabling the cache (cache_ttl_minutes = 60 in .cargo-sane.toml)";
+        let concurrency = "more lookups at once (concurrency = 16 in .cargo-sane.toml)";
+
+        // Commands without --offline or the check cache don't suggest them
+        assert_eq!(remedies(None, None, 0), [concurrency]);
+        assert_eq!(remedies(Some(true), None, 4), [concurrency]);
+        assert_eq!(remedies(Some(false), None, 32), [offline]);
+        assert_eq!(remedies(None, Some(0), 8), [cache, concurrency]);
+        assert!(remedies(None, Some(30), 16).is_empty());
+        assert_eq!(remedies(Some(false), Some(0), 16), [offline, cache]);
+        assert_eq!(remedies(Some(false), Some(30), 0), [offline, concurrency]);
+    }
+
+    #[test]
+    fn test_nothing_left_to_suggest() {
+        assert_eq!(network_hint(SLOW, &paths(Some(true), Some(60), 16)), None);
+        assert_eq!(
+            network_hint(SLOW, &paths(None, None, 0)).unwrap(),
+            "This run spent 48 seconds on the network; consider more lookups at once \
+             (concurrency = 16 in .cargo-sane.toml)"
+        );
+    }
+}
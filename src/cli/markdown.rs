@@ -0,0 +1,508 @@
+//! Markdown renderings of check, health, intake and compare reports, for CI
+//! artifacts and pull request comments
+//!
+//! Like the digest, each rendering depends on nothing but its report, so
+//! the same findings always give the same document.
+
+use crate::analyzer::checker::CheckReport;
+use crate::analyzer::compare::{Comparison, ConvergenceBasis, Declared};
+use crate::analyzer::health::HealthReport;
+use crate::analyzer::intake::IntakeReview;
+use crate::analyzer::priority::{rank, Class, Significance};
+use crate::cli::digest::{severity_label, update_label};
+use crate::core::dependency::Dependency;
+use crate::utils::formatting::{format_date, plural};
+use std::collections::BTreeSet;
+
+/// The dependencies with updates, then the yanked versions in use
+pub fn check_markdown(report: &CheckReport) -> String {
+    let mut out = match &report.package {
+        Some(package) => format!("# Dependency check: {}\n\n", package),
+        None => String::from("# Dependency check\n\n"),
+    };
+
+    // One line per crate, however many sections declare it
+    let mut seen = BTreeSet::new();
+    let dependencies: Vec<&Dependency> = report
+        .dependencies
+        .iter()
+        .filter(|d| seen.insert(d.name.as_str()))
+        .collect();
+    let mut outdated: Vec<&Dependency> = dependencies
+        .iter()
+        .copied()
+        .filter(|d| d.has_update())
+        .collect();
+    rank(&mut outdated, |dep| Significance::of_dependency(dep, false));
+    let yanked: Vec<&Dependency> = dependencies.iter().copied().filter(|d| d.yanked).collect();
+
+    out.push_str(&format!(
+        "{}, {} with an update available.\n",
+        plural(dependencies.len() as u64, "dependency"),
+        outdated.len()
+    ));
+    if !outdated.is_empty() {
+        out.push_str("\n## Updates\n\n");
+        for dep in &outdated {
+            let latest = dep
+                .latest_version
+                .as_ref()
+                .map_or(String::new(), ToString::to_string);
+            let mut line = format!(
+                "- `{}` {} → {}{}",
+                dep.name,
+                dep.current_version,
+                latest,
+                update_label(dep)
+            );
+            if dep.snoozed {
+                line.push_str(", snoozed");
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    if !yanked.is_empty() {
+        out.push_str("\n## Yanked\n\n");
+        for dep in &yanked {
+            out.push_str(&format!("- `{}` {}\n", dep.name, dep.current_version));
+        }
+    }
+    out
+}
+
+/// The advisories, most significant first, then any Cargo.lock checksums
+/// crates.io disagrees with
+pub fn health_markdown(report: &HealthReport) -> String {
+    let mut out = match &report.package {
+        Some(package) => format!("# Dependency health: {}\n\n", package),
+        None => String::from("# Dependency health\n\n"),
+    };
+    out.push_str(&format!(
+        "Scanned {} for advisories.\n",
+        plural(report.scanned as u64, "package")
+    ));
+
+    let mut vulnerable: Vec<_> = report.vulnerable.iter().collect();
+    rank(&mut vulnerable, |package| {
+        Significance::new(
+            Class::Vulnerable,
+            &package.version,
+            package.fix_version().as_ref(),
+        )
+    });
+    out.push_str("\n## Advisories\n\n");
+    if vulnerable.is_empty() {
+        out.push_str("No known advisories affect the dependencies.\n");
+    }
+    for package in vulnerable {
+        for advisory in &package.advisories {
+            let mut line = format!(
+                "- **{}** {} in `{}` {}: {}",
+                severity_label(advisory.severity),
+                advisory.id,
+                package.name,
+                package.version,
+                advisory.title
+            );
+            if package.attribution.direct_parent != package.name {
+                line.push_str(&format!(", via `{}`", package.attribution.direct_parent));
+            }
+            match package.fix_version() {
+                Some(fix) => line.push_str(&format!("; fixed in {}", fix)),
+                None => line.push_str("; no fixed release"),
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    if !report.accepted.is_empty() {
+        out.push_str(&format!(
+            "\n{} accepted as reviewed risks.\n",
+            plural(report.accepted.len() as u64, "advisory")
+        ));
+    }
+
+    if let Some(checksums) = report.checksums.as_ref() {
+        if !checksums.mismatches.is_empty() {
+            out.push_str("\n## Checksum mismatches with crates.io\n\n");
+            for mismatch in &checksums.mismatches {
+                out.push_str(&format!(
+                    "- `{}` {}: Cargo.lock has `{}`, crates.io publishes `{}`\n",
+                    mismatch.name, mismatch.version, mismatch.locked, mismatch.published
+                ));
+            }
+        }
+    }
+
+    if !report.embedded.is_empty() {
+        out.push_str("\n## Likely embedded copies of crates\n\n");
+        for copy in &report.embedded {
+            let version = copy
+                .version
+                .as_ref()
+                .map_or("version unknown".to_string(), ToString::to_string);
+            let ids: Vec<&str> = copy.advisories.iter().map(|a| a.id.as_str()).collect();
+            let mut line = format!("- `{}`: `{}` {}", copy.path.display(), copy.name, version);
+            if !ids.is_empty() {
+                line.push_str(&format!(", affected by {}", ids.join(", ")));
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// A checklist per added crate, for the pull request that adds it. A check
+/// is ticked when nothing in it needs a reviewer's judgement; owners always
+/// do.
+pub fn intake_markdown(reviews: &[IntakeReview], from: &str) -> String {
+    let mut out = format!("# Dependency intake since `{}`\n\n", from);
+    if reviews.is_empty() {
+        out.push_str("No dependencies added.\n");
+        return out;
+    }
+    out.push_str(&format!(
+        "{} added.\n",
+        plural(reviews.len() as u64, "dependency")
+    ));
+
+    for review in reviews {
+        let version = review
+            .version
+            .as_ref()
+            .map_or(review.requirement.clone(), ToString::to_string);
+        let sections: Vec<String> = review
+            .sections
+            .iter()
+            .map(|s| format!("`[{}]`", s))
+            .collect();
+        out.push_str(&format!(
+            "\n## `{}` {}\n\nRequired as `{}` in {}.\n\n",
+            review.name,
+            version,
+            review.requirement,
+            sections.join(", ")
+        ));
+        let failed = |lookup: &str| review.failed.iter().any(|f| f == lookup);
+        let unknown = "could not be looked up".to_string();
+
+        let (advisories_ok, advisories) = if failed("advisories") {
+            (false, unknown.clone())
+        } else if !review.affecting.is_empty() {
+            (
+                false,
+                format!("{} is affected by {}", version, review.affecting.join(", ")),
+            )
+        } else if review.advisories.is_empty() {
+            (true, "none on record".to_string())
+        } else {
+            let ids: Vec<&str> = review.advisories.iter().map(|a| a.id.as_str()).collect();
+            (
+                true,
+                format!("none affect {} ({} on record)", version, ids.join(", ")),
+            )
+        };
+        out.push_str(&check(advisories_ok, "Advisories", &advisories));
+
+        let (license_ok, license) = match review.registry.as_ref().map(|p| &p.license) {
+            None => (false, unknown.clone()),
+            Some(None) => (false, "none on crates.io".to_string()),
+            Some(Some(license)) => match review.license_permitted {
+                Some(true) => (true, format!("`{}`, permitted by the policy", license)),
+                Some(false) => (
+                    false,
+                    format!("`{}` is not permitted by the policy", license),
+                ),
+                None => (
+                    false,
+                    format!("`{}`, with no `[licenses]` policy to check", license),
+                ),
+            },
+        };
+        out.push_str(&check(license_ok, "License", &license));
+
+        let (maintenance_ok, maintenance) = match &review.registry {
+            None => (false, unknown.clone()),
+            Some(_) if !review.concerns.is_empty() => (false, review.concerns.join("; ")),
+            Some(package) => {
+                let released = package
+                    .last_release
+                    .map_or("release date unknown".to_string(), |at| {
+                        format!("last release {}", format_date(at))
+                    });
+                (true, released)
+            }
+        };
+        out.push_str(&check(maintenance_ok, "Maintenance", &maintenance));
+
+        let (footprint_ok, footprint) = match &review.new_packages {
+            None => (false, "not in Cargo.lock yet".to_string()),
+            Some(packages) if packages.is_empty() => {
+                (true, "brings no other packages into Cargo.lock".to_string())
+            }
+            Some(packages) => (
+                false,
+                format!(
+                    "brings {} into Cargo.lock: {}",
+                    plural(packages.len() as u64, "other package"),
+                    packages.join(", ")
+                ),
+            ),
+        };
+        out.push_str(&check(footprint_ok, "Footprint", &footprint));
+
+        let owners = match &review.owners {
+            None => unknown,
+            Some(owners) if owners.is_empty() => "none listed on crates.io".to_string(),
+            Some(owners) => owners.join(", "),
+        };
+        out.push_str(&check(false, "Owners", &owners));
+
+        if review.overlaps.is_empty() {
+            out.push_str(&check(
+                true,
+                "Overlap",
+                "no direct dependency covers the same ground",
+            ));
+        }
+        for overlap in &review.overlaps {
+            let crates: Vec<String> = overlap.crates.iter().map(|c| format!("`{}`", c)).collect();
+            out.push_str(&check(
+                false,
+                "Overlap",
+                &format!(
+                    "{} already covers {}. {}",
+                    crates.join(", "),
+                    overlap.group,
+                    overlap.note
+                ),
+            ));
+        }
+    }
+    out
+}
+
+/// A table of the version divergences with their convergence targets, then
+/// the feature divergences and the crates only one side has
+pub fn compare_markdown(comparison: &Comparison) -> String {
+    let (ours, theirs) = (&comparison.ours, &comparison.theirs);
+    let mut out = format!("# Dependencies of `{}` and `{}`\n\n", ours, theirs);
+    out.push_str(&format!(
+        "{} shared, {} at different versions.\n",
+        plural(comparison.shared as u64, "dependency"),
+        comparison.versions.len()
+    ));
+
+    if !comparison.versions.is_empty() {
+        out.push_str(&format!(
+            "\n## Versions\n\n| Crate | `{}` | `{}` | Converge on |\n|---|---|---|---|\n",
+            ours, theirs
+        ));
+        for divergence in &comparison.versions {
+            let target = match &divergence.target {
+                Some(target) if target.basis == ConvergenceBasis::BothRequirements => {
+                    format!("{} (both requirements allow it)", target.version)
+                }
+                Some(target) => format!("{} (the newer one)", target.version),
+                None => String::new(),
+            };
+            out.push_str(&format!(
+                "| `{}` | {} | {} | {} |\n",
+                divergence.name,
+                declared_version(&divergence.ours),
+                declared_version(&divergence.theirs),
+                target
+            ));
+        }
+    }
+
+    if !comparison.features.is_empty() {
+        out.push_str("\n## Features\n\n");
+        for divergence in &comparison.features {
+            let mut sides = Vec::new();
+            for (side, features) in [
+                (ours, &divergence.only_ours),
+                (theirs, &divergence.only_theirs),
+            ] {
+                if !features.is_empty() {
+                    sides.push(format!("only `{}` enables {}", side, features.join(", ")));
+                }
+            }
+            out.push_str(&format!("- `{}`: {}\n", divergence.name, sides.join("; ")));
+        }
+    }
+
+    for (side, only) in [
+        (ours, &comparison.only_ours),
+        (theirs, &comparison.only_theirs),
+    ] {
+        if !only.is_empty() {
+            let names: Vec<String> = only.iter().map(|name| format!("`{}`", name)).collect();
+            out.push_str(&format!(
+                "\n## Only in `{}`\n\n{}\n",
+                side,
+                names.join(", ")
+            ));
+        }
+    }
+    out
+}
+
+fn declared_version(declared: &Declared) -> String {
+    declared
+        .version
+        .as_ref()
+        .map_or(format!("`{}`", declared.requirement), ToString::to_string)
+}
+
+fn check(done: bool, name: &str, detail: &str) -> String {
+    format!(
+        "- [{}] {}: {}\n",
+        if done { "x" } else { " " },
+        name,
+        detail
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::attribution::Attribution;
+    use crate::analyzer::checker::test_support::check_report;
+    use crate::analyzer::health::AffectedPackage;
+    use crate::core::advisory::{Advisory, Severity};
+    use crate::core::dependency::test_support::dep;
+    use crate::core::dependency::DependencySource;
+    use semver::Version;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_check_markdown() {
+        let mut yanked = dep("memchr", "2.0.0", "2.0.0");
+        yanked.yanked = true;
+        let report = check_report(vec![
+            dep("serde", "1.0.100", "1.0.200"),
+            dep("clap", "3.2.0", "4.5.0"),
+            // A dev-dependency declaration of the same crate
+            dep("clap", "3.2.0", "4.5.0"),
+            yanked,
+        ]);
+        assert_eq!(
+            check_markdown(&report),
+            "# Dependency check: demo\n\n\
+             3 dependencies, 2 with an update available.\n\n\
+             ## Updates\n\n\
+             - `clap` 3.2.0 → 4.5.0 (major)\n\
+             - `serde` 1.0.100 → 1.0.200 (patch)\n\n\
+             ## Yanked\n\n\
+             - `memchr` 2.0.0\n"
+        );
+    }
+
+    #[test]
+    fn test_health_markdown() {
+        let mut attribution = Attribution::direct("chrono");
+        attribution.path.push("time".to_string());
+        attribution.depth = 2;
+        let report = HealthReport {
+            package: None,
+            manifest: PathBuf::from("Cargo.toml"),
+            scanned: 12,
+            vulnerable: vec![AffectedPackage {
+                name: "time".to_string(),
+                version: Version::new(0, 1, 45),
+                source: DependencySource::Registry,
+                attribution,
+                also_via: Vec::new(),
+                owner: None,
+                advisories: vec![Advisory {
+                    id: "RUSTSEC-2020-0071".to_string(),
+                    package: "time".to_string(),
+                    title: "Potential segfault in the time crate".to_string(),
+                    severity: Some(Severity::Medium),
+                    cvss: None,
+                    aliases: Vec::new(),
+                    patched_versions: vec![">=0.2.23".to_string()],
+                    informational: None,
+                    url: String::new(),
+                }],
+            }],
+            database: None,
+            system_libraries: Vec::new(),
+            forks: Vec::new(),
+            enrichment: None,
+            checksums: None,
+            embedded: Vec::new(),
+            build_time: None,
+            partial: false,
+            unchecked: 0,
+            ownership_changes: Vec::new(),
+            accepted: Vec::new(),
+            internal: Vec::new(),
+        };
+        assert_eq!(
+            health_markdown(&report),
+            "# Dependency health\n\n\
+             Scanned 12 packages for advisories.\n\n\
+             ## Advisories\n\n\
+             - **MEDIUM** RUSTSEC-2020-0071 in `time` 0.1.45: Potential segfault in the \
+             time crate, via `chrono`; fixed in 0.2.23\n"
+        );
+    }
+
+    #[test]
+    fn test_intake_markdown() {
+        use crate::analyzer::enrichment::{EnrichedPackage, Exposure};
+        use crate::analyzer::intake::Overlap;
+        use crate::core::dependency::DependencyKind;
+        use crate::core::manifest::DependencySection;
+
+        let review = IntakeReview {
+            name: "eyre".to_string(),
+            requirement: "0.6".to_string(),
+            sections: vec![DependencySection::new(DependencyKind::Normal)],
+            version: Some(Version::new(0, 6, 12)),
+            advisories: Vec::new(),
+            affecting: Vec::new(),
+            registry: Some(EnrichedPackage {
+                name: "eyre".to_string(),
+                version: Version::new(0, 6, 12),
+                exposure: Exposure::Direct,
+                license: Some("MIT OR Apache-2.0".to_string()),
+                repository: Some("https://github.com/eyre-rs/eyre".to_string()),
+                // 2024-01-26
+                last_release: Some(1_706_227_200),
+                yanked: false,
+            }),
+            license_permitted: Some(true),
+            concerns: Vec::new(),
+            new_packages: Some(vec!["indenter 0.3.3".to_string()]),
+            owners: None,
+            overlaps: vec![Overlap {
+                group: "error handling".to_string(),
+                crates: vec!["anyhow".to_string()],
+                note: "Pick one".to_string(),
+            }],
+            failed: vec!["owners".to_string()],
+        };
+        assert_eq!(
+            intake_markdown(&[review], "main"),
+            "# Dependency intake since `main`\n\n\
+             1 dependency added.\n\n\
+             ## `eyre` 0.6.12\n\n\
+             Required as `0.6` in `[dependencies]`.\n\n\
+             - [x] Advisories: none on record\n\
+             - [x] License: `MIT OR Apache-2.0`, permitted by the policy\n\
+             - [x] Maintenance: last release 2024-01-26\n\
+             - [ ] Footprint: brings 1 other package into Cargo.lock: indenter 0.3.3\n\
+             - [ ] Owners: could not be looked up\n\
+             - [ ] Overlap: `anyhow` already covers error handling. Pick one\n"
+        );
+        assert_eq!(
+            intake_markdown(&[], "HEAD"),
+            "# Dependency intake since `HEAD`\n\nNo dependencies added.\n"
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! OpenMetrics text for scheduled runs, written by `--metrics-out`
+//!
+//! The metric names and labels below are a stable interface for dashboards;
+//! they only ever gain members. Every sample has a `package` label holding
+//! the package name, or for a virtual workspace manifest the name of its
+//! directory. All metrics are gauges describing the latest run.
+//!
+//! | metric | labels | value |
+//! |---|---|---|
+//! | `cargo_sane_outdated_total` | `type`: `patch`, `minor`, `major` | dependencies with an update of that type |
+//! | `cargo_sane_vulnerable_total` | `severity`: `critical`, `high`, `medium`, `low`, `none`, `unknown` | affected packages, by their most severe advisory |
+//! | `cargo_sane_conflicts_total` | | crates locked at more than one version |
+//! | `cargo_sane_unused_deps_total` | | declared dependencies no source file uses |
+//! | `cargo_sane_health_score` | | 0 to 100, see [`health_score`] |
+//!
+//! A command only writes the metrics it measured: `check` the outdated
+//! counts, `health` the advisories and score, `report` all of them.
+
+use crate::analyzer::checker::CheckReport;
+use crate::analyzer::conflicts::ConflictReport;
+use crate::analyzer::health::HealthReport;
+use crate::core::advisory::Severity;
+use crate::core::dependency::UpdateType;
+use crate::core::manifest::Manifest;
+
+const UPDATE_TYPES: [&str; 3] = ["patch", "minor", "major"];
+
+const SEVERITIES: [&str; 6] = ["critical", "high", "medium", "low", "none", "unknown"];
+
+/// The measurements of one run, rendered with [`Metrics::render`]
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    package: String,
+    outdated: Option<[usize; 3]>,
+    vulnerable: Option<[usize; 6]>,
+    health_score: Option<u32>,
+    conflicts: Option<usize>,
+    unused: Option<usize>,
+}
+
+impl Metrics {
+    /// Metrics labeled for the package of `manifest`
+    pub fn new(manifest: &Manifest) -> Self {
+        let package = match manifest.package_name() {
+            Some(name) => name.to_string(),
+            None => manifest
+                .path
+                .canonicalize()
+                .ok()
+                .and_then(|path| Some(path.parent()?.file_name()?.to_string_lossy().to_string()))
+                .unwrap_or_default(),
+        };
+        Self {
+            package,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_check(mut self, report: &CheckReport) -> Self {
+        let mut outdated = [0; 3];
+        for dep in &report.dependencies {
+            let index = match dep.update_type() {
+                UpdateType::Patch => 0,
+                UpdateType::Minor => 1,
+                UpdateType::Major => 2,
+                UpdateType::UpToDate => continue,
+            };
+            outdated[index] += 1;
+        }
+        self.outdated = Some(outdated);
+        self
+    }
+
+    pub fn with_health(mut self, report: &HealthReport) -> Self {
+        let mut vulnerable = [0; 6];
+        for package in &report.vulnerable {
+            let severity = package.advisories.iter().filter_map(|a| a.severity).max();
+            let index = match severity {
+                Some(Severity::Critical) => 0,
+                Some(Severity::High) => 1,
+                Some(Severity::Medium) => 2,
+                Some(Severity::Low) => 3,
+                Some(Severity::None) => 4,
+                None => 5,
+            };
+            vulnerable[index] += 1;
+        }
+        self.vulnerable = Some(vulnerable);
+        self.health_score = Some(health_score(&vulnerable));
+        self
+    }
+
+    pub fn with_conflicts(mut self, report: &ConflictReport) -> Self {
+        self.conflicts = Some(report.conflicts.len());
+        self
+    }
+
+    pub fn with_unused(mut self, count: usize) -> Self {
+        self.unused = Some(count);
+        self
+    }
+
+    /// The OpenMetrics text exposition, ending in `# EOF`
+    pub fn render(&self) -> String {
+        let package = format!("package=\"{}\"", escape(&self.package));
+        let mut out = String::new();
+        if let Some(outdated) = &self.outdated {
+            family(
+                &mut out,
+                "cargo_sane_outdated_total",
+                "Dependencies with an available update, by update type",
+            );
+            for (kind, count) in UPDATE_TYPES.iter().zip(outdated) {
+                sample(
+                    &mut out,
+                    "cargo_sane_outdated_total",
+                    &format!("{},type=\"{}\"", package, kind),
+                    *count,
+                );
+            }
+        }
+        if let Some(vulnerable) = &self.vulnerable {
+            family(
+                &mut out,
+                "cargo_sane_vulnerable_total",
+                "Packages with known advisories, by their most severe advisory",
+            );
+            for (severity, count) in SEVERITIES.iter().zip(vulnerable) {
+                sample(
+                    &mut out,
+                    "cargo_sane_vulnerable_total",
+                    &format!("{},severity=\"{}\"", package, severity),
+                    *count,
+                );
+            }
+        }
+        if let Some(conflicts) = self.conflicts {
+            family(
+                &mut out,
+                "cargo_sane_conflicts_total",
+                "Crates locked at more than one version",
+            );
+            sample(&mut out, "cargo_sane_conflicts_total", &package, conflicts);
+        }
+        if let Some(unused) = self.unused {
+            family(
+                &mut out,
+                "cargo_sane_unused_deps_total",
+                "Declared dependencies no source file uses",
+            );
+            sample(&mut out, "cargo_sane_unused_deps_total", &package, unused);
+        }
+        if let Some(score) = self.health_score {
+            family(
+                &mut out,
+                "cargo_sane_health_score",
+                "Advisory health from 0 to 100, higher is better",
+            );
+            sample(
+                &mut out,
+                "cargo_sane_health_score",
+                &package,
+                score as usize,
+            );
+        }
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+/// 100 less a penalty per affected package by its most severe advisory:
+/// 40 critical, 20 high, 10 medium, 5 low, 2 for none or unrated, and
+/// never below 0. `vulnerable` is counted in [`SEVERITIES`] order.
+pub fn health_score(vulnerable: &[usize; 6]) -> u32 {
+    const PENALTIES: [usize; 6] = [40, 20, 10, 5, 2, 2];
+    let penalty: usize = vulnerable
+        .iter()
+        .zip(PENALTIES)
+        .map(|(count, penalty)| count.saturating_mul(penalty))
+        .fold(0, usize::saturating_add);
+    100usize.saturating_sub(penalty) as u32
+}
+
+fn family(out: &mut String, name: &str, help: &str) {
+    out.push_str(&format!(
+        "# TYPE {} gauge\n# HELP {} {}\n",
+        name, name, help
+    ));
+}
+
+fn sample(out: &mut String, name: &str, labels: &str, value: usize) {
+    out.push_str(&format!("{}{{{}}} {}\n", name, labels, value));
+}
+
+/// Escape a label value: backslash, double quote and line feed
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::checker::test_support::check_report;
+    use crate::core::dependency::test_support::dep;
+    use regex::Regex;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    /// Check the line format: families declared once before their samples,
+    /// samples named after their family, label values quoted and escaped,
+    /// and a final `# EOF`
+    fn validate(text: &str) -> Vec<(String, String, u64)> {
+        let type_line = Regex::new(r"^# TYPE ([a-zA-Z_:][a-zA-Z0-9_:]*) gauge$").unwrap();
+        let help_line = Regex::new(r"^# HELP ([a-zA-Z_:][a-zA-Z0-9_:]*) \S.*$").unwrap();
+        let sample_line = Regex::new(
+            r#"^([a-zA-Z_:][a-zA-Z0-9_:]*)\{((?:[a-zA-Z_][a-zA-Z0-9_]*="(?:[^"\\\n]|\\.)*",?)*)\} ([0-9]+)$"#,
+        )
+        .unwrap();
+
+        assert!(text.ends_with("# EOF\n"), "{}", text);
+        let mut declared = HashSet::new();
+        let mut current = String::new();
+        let mut samples = Vec::new();
+        for line in text.lines().take_while(|line| *line != "# EOF") {
+            if let Some(caps) = type_line.captures(line) {
+                assert!(declared.insert(caps[1].to_string()), "{}", line);
+                current = caps[1].to_string();
+            } else if let Some(caps) = help_line.captures(line) {
+                assert_eq!(caps[1], current, "{}", line);
+            } else if let Some(caps) = sample_line.captures(line) {
+                assert_eq!(caps[1], current, "{}", line);
+                samples.push((
+                    caps[1].to_string(),
+                    caps[2].to_string(),
+                    caps[3].parse().unwrap(),
+                ));
+            } else {
+                panic!("not an OpenMetrics line: {:?}", line);
+            }
+        }
+        assert_eq!(text.matches("# EOF").count(), 1);
+        samples
+    }
+
+    #[test]
+    fn test_check_metrics_exposition() {
+        let report = check_report(vec![
+            dep("demo", "1.0.0", "1.0.1"),
+            dep("demo", "1.0.0", "2.0.0"),
+            dep("demo", "1.0.0", "3.0.0"),
+            dep("demo", "1.0.0", "1.0.0"),
+        ]);
+        let manifest = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            "[package]\nname = \"we\\\"ird\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        let text = Metrics::new(&manifest).with_check(&report).render();
+
+        let samples = validate(&text);
+        let values: Vec<(&str, u64)> = samples
+            .iter()
+            .map(|(_, labels, value)| (labels.as_str(), *value))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("package=\"we\\\"ird\",type=\"patch\"", 1),
+                ("package=\"we\\\"ird\",type=\"minor\"", 0),
+                ("package=\"we\\\"ird\",type=\"major\"", 2),
+            ]
+        );
+        assert!(!text.contains("cargo_sane_health_score"));
+    }
+
+    #[test]
+    fn test_every_metric_is_valid_together() {
+        let manifest = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        let mut metrics = Metrics::new(&manifest)
+            .with_check(&check_report(Vec::new()))
+            .with_conflicts(&ConflictReport::default())
+            .with_unused(3);
+        metrics.vulnerable = Some([1, 0, 2, 0, 0, 1]);
+        metrics.health_score = Some(health_score(&[1, 0, 2, 0, 0, 1]));
+
+        let names: Vec<String> = validate(&metrics.render())
+            .into_iter()
+            .map(|(name, _, _)| name)
+            .collect();
+        for name in [
+            "cargo_sane_outdated_total",
+            "cargo_sane_vulnerable_total",
+            "cargo_sane_conflicts_total",
+            "cargo_sane_unused_deps_total",
+            "cargo_sane_health_score",
+        ] {
+            assert!(names.iter().any(|n| n == name), "{}", name);
+        }
+        assert!(metrics
+            .render()
+            .contains("cargo_sane_health_score{package=\"demo\"} 38\n"));
+    }
+
+    #[test]
+    fn test_health_score() {
+        assert_eq!(health_score(&[0; 6]), 100);
+        assert_eq!(health_score(&[0, 1, 1, 1, 1, 1]), 61);
+        assert_eq!(health_score(&[3, 0, 0, 0, 0, 0]), 0);
+        assert_eq!(health_score(&[usize::MAX, 0, 0, 0, 0, 0]), 0);
+    }
+}
@@ -1,4 +1,14 @@
 //! CLI-related functionality
 
 pub mod commands;
+pub mod csv;
+pub mod defaults;
+pub mod digest;
+pub mod hints;
+pub mod markdown;
+pub mod metrics;
 pub mod output;
+pub mod prompt;
+pub mod schema;
+pub mod tour;
+pub mod wizard;
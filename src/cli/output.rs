@@ -1,23 +1,576 @@
 //! Terminal output formatting
 
-use colored::Colorize;
+use crate::core::advisory::Severity;
+use crate::core::config::{Accessibility, Config, Palette};
+use crate::core::dependency::UpdateType;
+use crate::utils::cache::unix_now;
+use crate::utils::formatting::{display_path, Stamped};
+use crate::utils::timings;
+use crate::Result;
+use anyhow::Context;
+use colored::{ColoredString, Colorize};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// How a report is rendered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    /// This format, or JSON when the `--json` shorthand was given
+    pub fn or_json(self, json: bool) -> Self {
+        if json {
+            OutputFormat::Json
+        } else {
+            self
+        }
+    }
+
+    /// Machine-readable formats keep stdout free of progress and chatter
+    pub fn is_machine_readable(self) -> bool {
+        self != OutputFormat::Text
+    }
+}
+
+/// The format of an `--output-file`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FileFormat {
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl FileFormat {
+    /// The format a file name's extension implies
+    fn of_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(FileFormat::Json),
+            "csv" => Some(FileFormat::Csv),
+            "md" | "markdown" => Some(FileFormat::Markdown),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FileFormat::Json => "json",
+            FileFormat::Csv => "csv",
+            FileFormat::Markdown => "markdown",
+        }
+    }
+}
+
+/// A file a command writes its report to besides what it prints, so one
+/// run can leave the human view on the console and documents for CI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputFile {
+    pub path: PathBuf,
+    pub format: FileFormat,
+}
+
+impl OutputFile {
+    /// Pair each of `paths` with the `formats` given in the same order, or
+    /// with the format its extension implies when no `--output-format` is
+    /// given. `command` names what `supported` formats belong to in errors.
+    pub fn pair(
+        paths: Vec<PathBuf>,
+        formats: Vec<FileFormat>,
+        supported: &[FileFormat],
+        command: &str,
+    ) -> Result<Vec<Self>> {
+        if !formats.is_empty() && formats.len() != paths.len() {
+            anyhow::bail!(
+                "Got {} --output-file and {} --output-format; give one format per file, \
+                 or none to go by the file extensions",
+                paths.len(),
+                formats.len()
+            );
+        }
+        let mut files = Vec::with_capacity(paths.len());
+        for (i, path) in paths.into_iter().enumerate() {
+            let format = match formats.get(i) {
+                Some(&format) => format,
+                None => FileFormat::of_path(&path).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Can't tell the format of {} from its extension; add --output-format",
+                        path.display()
+                    )
+                })?,
+            };
+            if !supported.contains(&format) {
+                let names: Vec<&str> = supported.iter().map(|f| f.name()).collect();
+                anyhow::bail!(
+                    "{} has no {} output ({}); it writes {}",
+                    command,
+                    format.name(),
+                    path.display(),
+                    names.join(", ")
+                );
+            }
+            if files.iter().any(|f: &OutputFile| f.path == path) {
+                anyhow::bail!("{} is given as --output-file twice", path.display());
+            }
+            files.push(OutputFile { path, format });
+        }
+        Ok(files)
+    }
+}
+
+/// Write each of `files`, rendering its content in its format with
+/// `render`. Unless `quiet`, each written file is mentioned.
+pub fn write_output_files(
+    files: &[OutputFile],
+    quiet: bool,
+    render: impl Fn(FileFormat) -> Result<String>,
+) -> Result<()> {
+    for file in files {
+        let mut content = render(file.format)?;
+        if !content.ends_with('\n') {
+            content.push('\n');
+        }
+        fs::write(&file.path, content)
+            .context(format!("Failed to write {}", file.path.display()))?;
+        if !quiet {
+            print_info(&format!(
+                "{} report written to {}",
+                match file.format {
+                    FileFormat::Json => "JSON",
+                    FileFormat::Csv => "CSV",
+                    FileFormat::Markdown => "Markdown",
+                },
+                display_path(&file.path)
+            ));
+        }
+    }
+    if !quiet && !files.is_empty() {
+        println!();
+    }
+    Ok(())
+}
+
+/// Environment variable forcing ASCII output on ("1") or off ("0")
+pub const ASCII_ENV: &str = "CARGO_SANE_ASCII";
+
+/// Whether to print plain ASCII instead of emoji and symbols. The default is
+/// on for legacy Windows consoles (cmd.exe and PowerShell outside Windows
+/// Terminal), whose code pages garble them, and off elsewhere.
+pub fn ascii() -> bool {
+    static ASCII: OnceLock<bool> = OnceLock::new();
+    *ASCII.get_or_init(|| match std::env::var(ASCII_ENV).as_deref() {
+        Ok("1") | Ok("true") => true,
+        Ok("0") | Ok("false") => false,
+        _ => cfg!(windows) && legacy_console(),
+    })
+}
+
+/// No sign of a terminal that renders Unicode: Windows Terminal, VS Code,
+/// ConEmu and mintty/MSYS all set one of these
+fn legacy_console() -> bool {
+    ["WT_SESSION", "TERM_PROGRAM", "ConEmuANSI", "TERM"]
+        .iter()
+        .all(|name| std::env::var_os(name).is_none())
+}
+
+/// `text` with symbols replaced by ASCII stand-ins and remaining emoji
+/// dropped, when printing ASCII
+pub fn plain(text: &str) -> Cow<'_, str> {
+    if !ascii() || text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(to_ascii(text))
+}
+
+fn to_ascii(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '✓' | '✔' => out.push('+'),
+            '✗' | '✘' => out.push('x'),
+            '⚠' => out.push('!'),
+            'ℹ' => out.push('i'),
+            '•' => out.push('*'),
+            '→' => out.push_str("->"),
+            '←' => out.push_str("<-"),
+            '–' | '—' => out.push('-'),
+            '≥' => out.push_str(">="),
+            '≤' => out.push_str("<="),
+            c if c.is_ascii() => out.push(c),
+            // Emoji, variation selectors and other symbols
+            _ => {}
+        }
+    }
+    // Dropped leading emoji leave their separating space behind
+    match out.strip_prefix(' ') {
+        Some(rest) if !text.starts_with(' ') => rest.to_string(),
+        _ => out,
+    }
+}
+
+static SYMBOLS: OnceLock<bool> = OnceLock::new();
+static PALETTE: OnceLock<Palette> = OnceLock::new();
+
+/// Tag every status with text from now on (`--symbols`)
+pub fn use_symbols() {
+    let _ = SYMBOLS.set(true);
+}
+
+/// Take the accessibility settings of the project's config, unless the
+/// command line already decided them
+pub fn apply_config(config: &Config) {
+    let _ = SYMBOLS.set(config.accessibility == Accessibility::Symbols);
+    let _ = PALETTE.set(config.palette);
+}
+
+fn symbols() -> bool {
+    SYMBOLS.get().copied().unwrap_or(false)
+}
+
+fn palette() -> Palette {
+    PALETTE.get().copied().unwrap_or_default()
+}
+
+/// What a color says about a status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    Good,
+    Caution,
+    Bad,
+}
+
+/// Status colors in the configured palette, used instead of `green()`,
+/// `yellow()` and `red()`
+pub trait Paint: Colorize + Sized {
+    fn good(self) -> ColoredString {
+        paint_in(self, Tone::Good, palette())
+    }
+
+    fn caution(self) -> ColoredString {
+        paint_in(self, Tone::Caution, palette())
+    }
+
+    fn bad(self) -> ColoredString {
+        paint_in(self, Tone::Bad, palette())
+    }
+}
+
+impl<T: Colorize> Paint for T {}
+
+/// `text` in the color of `tone` in the configured palette
+pub fn paint<T: Colorize>(text: T, tone: Tone) -> ColoredString {
+    paint_in(text, tone, palette())
+}
+
+fn paint_in<T: Colorize>(text: T, tone: Tone, palette: Palette) -> ColoredString {
+    match (palette, tone) {
+        (Palette::Standard, Tone::Good) => text.green(),
+        (Palette::Standard, Tone::Caution) => text.yellow(),
+        (Palette::Standard, Tone::Bad) => text.red(),
+        (Palette::Colorblind, Tone::Good) => text.truecolor(0, 114, 178),
+        (Palette::Colorblind, Tone::Caution) => text.truecolor(240, 228, 66),
+        (Palette::Colorblind, Tone::Bad) => text.truecolor(213, 94, 0),
+    }
+}
+
+/// A status shown by a colored marker, which must also read without color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    UpToDate,
+    Patch,
+    Minor,
+    Major,
+    Critical,
+    High,
+    Medium,
+    Low,
+    /// Rated, with a CVSS score of 0
+    NoSeverity,
+    Unrated,
+}
+
+impl Status {
+    pub fn of_update(update_type: UpdateType) -> Self {
+        match update_type {
+            UpdateType::UpToDate => Status::UpToDate,
+            UpdateType::Patch => Status::Patch,
+            UpdateType::Minor => Status::Minor,
+            UpdateType::Major => Status::Major,
+        }
+    }
+
+    pub fn of_severity(severity: Option<Severity>) -> Self {
+        match severity {
+            Some(Severity::Critical) => Status::Critical,
+            Some(Severity::High) => Status::High,
+            Some(Severity::Medium) => Status::Medium,
+            Some(Severity::Low) => Status::Low,
+            Some(Severity::None) => Status::NoSeverity,
+            None => Status::Unrated,
+        }
+    }
+
+    pub fn tone(self) -> Tone {
+        match self {
+            Status::UpToDate | Status::Patch => Tone::Good,
+            Status::Minor | Status::Medium | Status::Low | Status::NoSeverity | Status::Unrated => {
+                Tone::Caution
+            }
+            Status::Major | Status::Critical | Status::High => Tone::Bad,
+        }
+    }
+
+    /// Severities have no emoji of their own
+    fn emoji(self) -> Option<&'static str> {
+        match self {
+            Status::UpToDate => Some("✅"),
+            Status::Patch => Some("🟢"),
+            Status::Minor => Some("🟡"),
+            Status::Major => Some("🔴"),
+            _ => None,
+        }
+    }
+
+    /// The status in words, e.g. "MAJOR" or "CRITICAL"
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::UpToDate => "UP-TO-DATE",
+            Status::Patch => "PATCH",
+            Status::Minor => "MINOR",
+            Status::Major => "MAJOR",
+            Status::Critical => "CRITICAL",
+            Status::High => "HIGH",
+            Status::Medium => "MEDIUM",
+            Status::Low => "LOW",
+            Status::NoSeverity => "NONE",
+            Status::Unrated => "UNRATED",
+        }
+    }
+
+    /// The ASCII tag `--symbols` adds, e.g. "[MAJOR]" or "[CRIT]"
+    pub fn tag(self) -> &'static str {
+        match self {
+            Status::UpToDate => "[OK]",
+            Status::Patch => "[PATCH]",
+            Status::Minor => "[MINOR]",
+            Status::Major => "[MAJOR]",
+            Status::Critical => "[CRIT]",
+            Status::High => "[HIGH]",
+            Status::Medium => "[MED]",
+            Status::Low => "[LOW]",
+            Status::NoSeverity => "[NONE]",
+            Status::Unrated => "[UNRATED]",
+        }
+    }
+}
+
+/// The marker of a status next to text that already names it, e.g. the
+/// "🔴" of "🔴 Major updates:"; with symbols, "🔴 [MAJOR]"
+pub fn marker(status: Status) -> ColoredString {
+    paint(
+        compose(status, symbols(), ascii(), false).as_str(),
+        status.tone(),
+    )
+}
+
+/// A status standing on its own, e.g. "🔴 MAJOR"; with symbols, "🔴 [MAJOR]"
+pub fn label(status: Status) -> ColoredString {
+    paint(
+        compose(status, symbols(), ascii(), true).as_str(),
+        status.tone(),
+    )
+}
+
+/// A bold heading over things of one status, e.g. "🔴 Major updates:"
+pub fn heading(status: Status, text: &str) -> String {
+    let marker = marker(status);
+    let text = paint(text, status.tone()).bold();
+    if marker.is_empty() {
+        text.to_string()
+    } else {
+        format!("{} {}", marker.bold(), text)
+    }
+}
+
+/// A severity in brackets, e.g. "[CRITICAL]"; with symbols, "[CRIT]"
+pub fn badge(status: Status) -> ColoredString {
+    let text = if symbols() {
+        status.tag().to_string()
+    } else {
+        format!("[{}]", status.name())
+    };
+    paint(text.as_str(), status.tone())
+}
+
+fn compose(status: Status, symbols: bool, ascii: bool, words: bool) -> String {
+    let emoji = status.emoji().filter(|_| !ascii);
+    let text = if symbols {
+        Some(status.tag())
+    } else if words {
+        Some(status.name())
+    } else {
+        None
+    };
+    match (emoji, text) {
+        (Some(emoji), Some(text)) => format!("{} {}", emoji, text),
+        (Some(emoji), None) => emoji.to_string(),
+        (None, text) => text.unwrap_or_default().to_string(),
+    }
+}
 
 pub fn print_header(text: &str) {
-    println!("\n{}", text.bold().cyan());
+    println!("\n{}", plain(text).bold().cyan());
 }
 
 pub fn print_success(text: &str) {
-    println!("{} {}", "✓".green().bold(), text);
+    println!("{} {}", plain("✓").good().bold(), plain(text));
 }
 
 pub fn print_warning(text: &str) {
-    println!("{} {}", "⚠".yellow().bold(), text);
+    println!("{} {}", plain("⚠").caution().bold(), plain(text));
+}
+
+/// [`print_warning`] on stderr, for warnings that mustn't end up in JSON or
+/// CSV written to stdout
+pub fn eprint_warning(text: &str) {
+    eprintln!("{} {}", plain("⚠").caution().bold(), plain(text));
 }
 
 pub fn print_error(text: &str) {
-    eprintln!("{} {}", "✗".red().bold(), text);
+    eprintln!("{} {}", plain("✗").bad().bold(), plain(text));
 }
 
 pub fn print_info(text: &str) {
-    println!("{} {}", "ℹ".blue().bold(), text);
+    println!("{} {}", plain("ℹ").blue().bold(), plain(text));
+}
+
+/// Print a report as pretty JSON, stamped with the time and tool version
+pub fn print_json<T: Serialize>(report: &T) -> Result<()> {
+    println!("{}", render_json(report)?);
+    Ok(())
+}
+
+/// `report` as the JSON document `--json` prints, stamped and versioned
+pub fn render_json<T: Serialize>(report: &T) -> Result<String> {
+    let mut stamped = Stamped::new(report, unix_now());
+    stamped.timings = timings::snapshot();
+    Ok(serde_json::to_string_pretty(&stamped)?)
+}
+
+/// Print the one-line timing breakdown, when recording is on
+pub fn print_timings() {
+    if let Some(timings) = timings::snapshot() {
+        println!();
+        println!("{}", format!("Timings: {}", timings.summary()).dimmed());
+    }
+}
+
+/// Print CSV to stdout, or write it to `path`
+pub fn write_csv(csv: &str, path: Option<&Path>) -> Result<()> {
+    match path {
+        Some(path) => {
+            fs::write(path, csv).context(format!("Failed to write {}", path.display()))?;
+        }
+        None => print!("{}", csv),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pair_output_files() {
+        use FileFormat::*;
+        let paths = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+        let all = [Json, Csv, Markdown];
+
+        let files =
+            OutputFile::pair(paths(&["a.json", "b.MD"]), Vec::new(), &all, "check").unwrap();
+        let formats: Vec<FileFormat> = files.iter().map(|f| f.format).collect();
+        assert_eq!(formats, vec![Json, Markdown]);
+        // Given formats win over extensions, in order
+        let files = OutputFile::pair(
+            paths(&["out", "b.json"]),
+            vec![Csv, Markdown],
+            &all,
+            "check",
+        )
+        .unwrap();
+        assert_eq!(files[1].format, Markdown);
+
+        let error = |paths, formats, supported: &[FileFormat]| {
+            OutputFile::pair(paths, formats, supported, "report")
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(error(paths(&["out"]), Vec::new(), &all).contains("add --output-format"));
+        assert!(error(paths(&["a.json", "b.md"]), vec![Json], &all)
+            .starts_with("Got 2 --output-file and 1 --output-format"));
+        assert_eq!(
+            error(paths(&["r.csv"]), Vec::new(), &[Json, Markdown]),
+            "report has no csv output (r.csv); it writes json, markdown"
+        );
+        assert!(error(paths(&["a.json", "a.json"]), Vec::new(), &all).ends_with("twice"));
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        assert_eq!(to_ascii("🧠 cargo-sane check"), "cargo-sane check");
+        assert_eq!(
+            to_ascii("  ✓ serde 1.0 → 1.1 • ok 🎉"),
+            "  + serde 1.0 -> 1.1 * ok "
+        );
+        assert_eq!(
+            to_ascii("⚠️  Declaration conflicts:"),
+            "!  Declaration conflicts:"
+        );
+    }
+
+    #[test]
+    fn test_status_markers() {
+        // Next to text naming the status, the marker stays an emoji
+        assert_eq!(compose(Status::Major, false, false, false), "🔴");
+        assert_eq!(compose(Status::Major, false, false, true), "🔴 MAJOR");
+        assert_eq!(compose(Status::Major, true, false, false), "🔴 [MAJOR]");
+        assert_eq!(compose(Status::Patch, true, true, true), "[PATCH]");
+        // Dropping emoji keeps the words
+        assert_eq!(compose(Status::Minor, false, true, true), "MINOR");
+        assert_eq!(compose(Status::Minor, true, true, false), "[MINOR]");
+        assert_eq!(compose(Status::Critical, false, false, true), "CRITICAL");
+        assert_eq!(compose(Status::Critical, true, false, true), "[CRIT]");
+        assert_eq!(Status::of_severity(None).tag(), "[UNRATED]");
+    }
+
+    #[test]
+    fn test_palettes() {
+        use colored::Color;
+        let color = |tone, palette| paint_in("x", tone, palette).fgcolor;
+        assert_eq!(color(Tone::Good, Palette::Standard), Some(Color::Green));
+        assert_eq!(color(Tone::Bad, Palette::Standard), Some(Color::Red));
+        // No green and red to confuse in the colorblind palette
+        assert_eq!(
+            color(Tone::Good, Palette::Colorblind),
+            Some(Color::TrueColor {
+                r: 0,
+                g: 114,
+                b: 178
+            })
+        );
+        assert_eq!(
+            color(Tone::Bad, Palette::Colorblind),
+            Some(Color::TrueColor {
+                r: 213,
+                g: 94,
+                b: 0
+            })
+        );
+    }
 }
@@ -0,0 +1,211 @@
+//! Prompts, and the ways to answer them without a terminal
+//!
+//! `--yes` and `--no` answer every confirmation. Under `--test-mode`, the
+//! scenario's `answers` answer the prompts in the order they come up, and a
+//! prompt with no answer left fails the run instead of waiting for input.
+//! An answer given either way is echoed, so the output shows what was
+//! decided.
+
+use crate::cli::output::{self, Paint};
+use crate::utils::test_mode::{Answer, Scenario};
+use crate::Result;
+use colored::Colorize;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, MultiSelect, Select};
+use std::io::{IsTerminal, Write};
+use std::sync::OnceLock;
+
+static ANSWER_ALL: OnceLock<bool> = OnceLock::new();
+
+/// Answer every confirmation with `yes` from now on
+pub fn answer_all(yes: bool) {
+    let _ = ANSWER_ALL.set(yes);
+}
+
+/// Whether prompts can be shown: stdin and stdout are a terminal, or the
+/// `--test-mode` scenario says so
+pub fn is_interactive() -> bool {
+    match Scenario::active() {
+        Some(scenario) => scenario.interactive,
+        None => std::io::stdin().is_terminal() && std::io::stdout().is_terminal(),
+    }
+}
+
+/// Whether a confirmation gets an answer, from `--yes`/`--no` or the user
+pub fn can_confirm() -> bool {
+    ANSWER_ALL.get().is_some() || is_interactive()
+}
+
+/// Wait for enter before going on. Without a terminal there's nothing to
+/// wait for, and under `--test-mode` the prompt is only shown.
+pub fn pause() -> Result<()> {
+    if !is_interactive() {
+        return Ok(());
+    }
+    let prompt = "Press enter to continue";
+    if Scenario::active().is_some() {
+        println!("{}", prompt.dimmed());
+        return Ok(());
+    }
+    print!("{} ", prompt.dimmed());
+    std::io::stdout().flush()?;
+    std::io::stdin().read_line(&mut String::new())?;
+    Ok(())
+}
+
+/// Ask a yes/no question
+pub fn confirm(prompt: &str, default: bool) -> Result<bool> {
+    if let Some(&yes) = ANSWER_ALL.get() {
+        echo(prompt, yes_or_no(yes));
+        return Ok(yes);
+    }
+    if let Some(answer) = scripted(prompt)? {
+        let yes = match answer {
+            Answer::Bool(yes) => *yes,
+            Answer::Text(text) if matches!(text.to_lowercase().as_str(), "y" | "yes") => true,
+            Answer::Text(text) if matches!(text.to_lowercase().as_str(), "n" | "no") => false,
+            other => return Err(unusable(prompt, other, &[])),
+        };
+        echo(prompt, yes_or_no(yes));
+        return Ok(yes);
+    }
+    Ok(Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(default)
+        .interact()?)
+}
+
+/// Pick one of `items`, returning its index
+pub fn select<T: ToString>(prompt: &str, items: &[T], default: usize) -> Result<usize> {
+    let labels: Vec<String> = items.iter().map(ToString::to_string).collect();
+    if let Some(answer) = scripted(prompt)? {
+        let index = pick(&labels, answer).ok_or_else(|| unusable(prompt, answer, &labels))?;
+        echo(prompt, &labels[index]);
+        return Ok(index);
+    }
+    Ok(Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(&labels)
+        .default(default)
+        .interact()?)
+}
+
+/// Pick any number of `items`, returning their indexes
+pub fn multi_select<T: ToString>(prompt: &str, items: &[T]) -> Result<Vec<usize>> {
+    let labels: Vec<String> = items.iter().map(ToString::to_string).collect();
+    if let Some(answer) = scripted(prompt)? {
+        let answers = match answer {
+            Answer::List(answers) => answers.iter().collect(),
+            single => vec![single],
+        };
+        let mut indexes = Vec::new();
+        for each in answers {
+            let index = pick(&labels, each).ok_or_else(|| unusable(prompt, each, &labels))?;
+            if !indexes.contains(&index) {
+                indexes.push(index);
+            }
+        }
+        indexes.sort_unstable();
+        let chosen: Vec<&str> = indexes.iter().map(|&i| labels[i].as_str()).collect();
+        echo(prompt, &chosen.join(", "));
+        return Ok(indexes);
+    }
+    Ok(MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(&labels)
+        .interact()?)
+}
+
+/// Ask for a line of text
+pub fn input(prompt: &str) -> Result<String> {
+    if let Some(answer) = scripted(prompt)? {
+        let Answer::Text(text) = answer else {
+            return Err(unusable(prompt, answer, &[]));
+        };
+        echo(prompt, text);
+        return Ok(text.clone());
+    }
+    Ok(Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .interact_text()?)
+}
+
+/// The scenario's next answer under `--test-mode`, which must have one
+fn scripted(prompt: &str) -> Result<Option<&'static Answer>> {
+    let Some(scenario) = Scenario::active() else {
+        return Ok(None);
+    };
+    match scenario.next_answer() {
+        Some(answer) => Ok(Some(answer)),
+        None => anyhow::bail!(
+            "test mode: the scenario has no answer left for \"{}\"",
+            prompt
+        ),
+    }
+}
+
+/// The item `answer` names: by index, by its exact label, or by the only
+/// label containing the text
+fn pick(labels: &[String], answer: &Answer) -> Option<usize> {
+    match answer {
+        Answer::Index(index) => (*index < labels.len()).then_some(*index),
+        Answer::Text(text) => labels.iter().position(|l| l == text).or_else(|| {
+            let mut containing = labels.iter().enumerate().filter(|(_, l)| l.contains(text));
+            match (containing.next(), containing.next()) {
+                (Some((index, _)), None) => Some(index),
+                _ => None,
+            }
+        }),
+        _ => None,
+    }
+}
+
+/// The error for a scripted answer that doesn't fit the prompt, with the
+/// items it could have named
+fn unusable(prompt: &str, answer: &Answer, labels: &[String]) -> anyhow::Error {
+    let mut message = format!("test mode: {} doesn't answer \"{}\"", answer, prompt);
+    if !labels.is_empty() {
+        message.push_str(&format!("; the choices are: {}", labels.join(", ")));
+    }
+    anyhow::anyhow!(message)
+}
+
+fn yes_or_no(yes: bool) -> &'static str {
+    if yes {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Show an answer the way an answered prompt looks
+fn echo(prompt: &str, answer: &str) {
+    println!(
+        "{} {} {} {}",
+        output::plain("✔").good(),
+        prompt.bold(),
+        output::plain("·").dimmed(),
+        answer
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick() {
+        let labels: Vec<String> = ["🟢 serde 1.0.1 → 1.0.2", "🔴 syn 1.0.0 → 2.0.0", "Skip"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        let text = |t: &str| Answer::Text(t.to_string());
+        assert_eq!(pick(&labels, &Answer::Index(1)), Some(1));
+        assert_eq!(pick(&labels, &Answer::Index(3)), None);
+        assert_eq!(pick(&labels, &text("Skip")), Some(2));
+        assert_eq!(pick(&labels, &text("serde")), Some(0));
+        // Ambiguous or unknown text picks nothing
+        assert_eq!(pick(&labels, &text("→")), None);
+        assert_eq!(pick(&labels, &text("tokio")), None);
+        assert_eq!(pick(&labels, &Answer::Bool(true)), None);
+    }
+}
@@ -0,0 +1,112 @@
+//! JSON Schemas of the documents `--json` prints
+//!
+//! The schemas are generated from the report types themselves, so they can't
+//! drift from what is actually emitted. Every document carries
+//! `schema_version`; see [`SCHEMA_VERSION`] for when it changes. The plugin
+//! documents aren't stamped and follow [`PROTOCOL_VERSION`] instead.
+
+use crate::analyzer::checker::CheckReport;
+use crate::analyzer::health::HealthReport;
+use crate::analyzer::plugin::{PluginOutput, ProjectAnalysis, PROTOCOL_VERSION};
+use crate::analyzer::snapshot::ProjectReport;
+use crate::analyzer::usage::CleanReport;
+use crate::cli::defaults::Defaults;
+use crate::updater::plan::Plan;
+use crate::utils::formatting::{Stamped, SCHEMA_VERSION};
+use schemars::{JsonSchema, Schema};
+
+/// The commands whose JSON output has a published schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SchemaKind {
+    /// `check --json`, for a single package
+    Check,
+    /// `health --json`
+    Health,
+    /// `fix --dry-run --json` and `health --fix --dry-run --json`
+    Fix,
+    /// `clean --json`, for a single package
+    Clean,
+    /// `report --json`
+    Report,
+    /// What `report --plugin` programs read on stdin
+    PluginInput,
+    /// What `report --plugin` programs print on stdout
+    PluginOutput,
+    /// `defaults --json`
+    Defaults,
+}
+
+impl SchemaKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            SchemaKind::Check => "check",
+            SchemaKind::Health => "health",
+            SchemaKind::Fix => "fix",
+            SchemaKind::Clean => "clean",
+            SchemaKind::Report => "report",
+            SchemaKind::PluginInput => "plugin-input",
+            SchemaKind::PluginOutput => "plugin-output",
+            SchemaKind::Defaults => "defaults",
+        }
+    }
+
+    /// The version the document follows: [`PROTOCOL_VERSION`] for the
+    /// plugin documents, [`SCHEMA_VERSION`] for the rest
+    pub fn version(self) -> u32 {
+        match self {
+            SchemaKind::PluginInput | SchemaKind::PluginOutput => PROTOCOL_VERSION,
+            _ => SCHEMA_VERSION,
+        }
+    }
+}
+
+/// The JSON Schema of `kind`'s document, stamp included
+pub fn schema(kind: SchemaKind) -> Schema {
+    let mut schema = match kind {
+        SchemaKind::Check => stamped::<CheckReport>(),
+        SchemaKind::Health => stamped::<HealthReport>(),
+        SchemaKind::Fix => stamped::<Plan>(),
+        SchemaKind::Clean => stamped::<CleanReport>(),
+        SchemaKind::Report => stamped::<ProjectReport>(),
+        SchemaKind::PluginInput => schemars::schema_for!(ProjectAnalysis),
+        SchemaKind::PluginOutput => schemars::schema_for!(PluginOutput),
+        SchemaKind::Defaults => stamped::<Defaults>(),
+    };
+    let version = match kind {
+        SchemaKind::PluginInput | SchemaKind::PluginOutput => {
+            format!("protocol version {}", kind.version())
+        }
+        _ => format!("schema version {}", kind.version()),
+    };
+    schema.insert(
+        "title".to_string(),
+        format!("cargo-sane {} ({})", kind.name(), version).into(),
+    );
+    schema
+}
+
+fn stamped<T: JsonSchema + 'static>() -> Schema {
+    schemars::schema_for!(Stamped<'static, T>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schemas_cover_the_stamp() {
+        for kind in [
+            SchemaKind::Check,
+            SchemaKind::Fix,
+            SchemaKind::Report,
+            SchemaKind::Defaults,
+        ] {
+            let schema = schema(kind).to_value();
+            let required = schema["required"].as_array().unwrap();
+            for field in ["schema_version", "generated_at", "tool_version"] {
+                assert!(required.iter().any(|r| r == field), "{:?} {}", kind, field);
+            }
+            assert!(schema["properties"]["timings"].is_object());
+        }
+    }
+}
@@ -0,0 +1,429 @@
+//! `cargo sane tour`, a guided first run
+//!
+//! The tour explains what check, conflict detection and health look for,
+//! using what they find in the project at hand, and ends with settings
+//! suggested for it. Every section is rendered from one snapshot, so the
+//! analyses run once up front, and the same text is printed whether or not
+//! there's a terminal to pause in between.
+
+use crate::analyzer::conflicts::Resolvability;
+use crate::analyzer::snapshot::Snapshot;
+use crate::cli::output::{self, Status};
+use crate::core::config::CONFIG_FILE;
+use crate::core::dependency::{Dependency, UpdateType};
+use crate::utils::formatting::plural;
+
+/// How many examples a list names before summing up the rest
+const EXAMPLES: usize = 3;
+
+/// The sections of the tour of `snapshot`, in order. `has_config` says
+/// whether the project already has a config file, which the tour never
+/// overwrites.
+pub fn render_tour(snapshot: &Snapshot, has_config: bool) -> Vec<String> {
+    vec![
+        intro(snapshot),
+        updates(snapshot),
+        duplicates(snapshot),
+        health(snapshot),
+        settings(snapshot, has_config),
+    ]
+}
+
+/// Settings worth starting from in this project's config file
+pub fn suggested_config(snapshot: &Snapshot) -> String {
+    let mut out = String::from("# Suggested by `cargo sane tour`\n\n");
+    out.push_str("# Reuse check results for an hour while Cargo.toml is unchanged\n");
+    out.push_str("cache_ttl_minutes = 60\n");
+
+    let stuck = stuck_duplicates(snapshot);
+    if !stuck.is_empty() {
+        let names: Vec<String> = stuck.iter().map(|name| format!("\"{}\"", name)).collect();
+        out.push_str(
+            "\n# Duplicates only a new release of their dependents can merge;\n\
+             # `cargo sane fix` leaves these alone\n",
+        );
+        out.push_str(&format!("ignore_conflicts = [{}]\n", names.join(", ")));
+    }
+
+    out.push_str(
+        "\n# Uncomment to fail `cargo sane check` when a dependency falls more\n\
+         # than one breaking release behind\n\
+         # [freshness.budgets]\n\
+         # default = { max_majors_behind = 1 }\n",
+    );
+    out
+}
+
+fn intro(snapshot: &Snapshot) -> String {
+    let project = snapshot.check.package.as_deref().unwrap_or("this project");
+    format!(
+        "👋 Welcome to cargo-sane!\n\n\
+         This tour looks at {} the way cargo-sane's commands do and explains what it finds, \
+         in four steps: updates, duplicated crates, security advisories and settings. \
+         Everything below comes from one pass over the registry and the advisory database, \
+         and nothing is changed without asking.",
+        project
+    )
+}
+
+fn updates(snapshot: &Snapshot) -> String {
+    let mut groups: [Vec<&Dependency>; 4] = Default::default();
+    for dep in &snapshot.check.dependencies {
+        let index = match dep.update_type() {
+            UpdateType::UpToDate => 0,
+            UpdateType::Patch => 1,
+            UpdateType::Minor => 2,
+            UpdateType::Major => 3,
+        };
+        groups[index].push(dep);
+    }
+
+    let mut out = String::from("🧭 Step 1 of 4: updates (`cargo sane check`)\n\n");
+    out.push_str(&format!(
+        "check compares each of your {} with its newest release and sorts it into one of four \
+         groups:\n",
+        plural(snapshot.check.dependencies.len() as u64, "dependency")
+    ));
+    let explanations = [
+        (
+            UpdateType::UpToDate,
+            "Up to date",
+            "Already on the newest release; nothing to do.",
+        ),
+        (
+            UpdateType::Patch,
+            "Patch",
+            "Bug fixes within the release you use. Safe to take any time.",
+        ),
+        (
+            UpdateType::Minor,
+            "Minor",
+            "New features that stay compatible, from 1.0 on. Below 1.0 a minor release \
+             may break things, so read its changelog first.",
+        ),
+        (
+            UpdateType::Major,
+            "Major",
+            "A breaking release: expect to change code.",
+        ),
+    ];
+    for ((update_type, name, explanation), deps) in explanations.into_iter().zip(&groups) {
+        // Marked the way check marks them, so --symbols and --plain carry over
+        let marker = output::marker(Status::of_update(update_type));
+        let label = if marker.is_empty() {
+            name.to_string()
+        } else {
+            format!("{} {}", marker, name)
+        };
+        out.push_str(&format!("\n  {} ({})", label, deps.len()));
+        if !deps.is_empty() {
+            let names: Vec<String> = deps
+                .iter()
+                .map(|dep| match (dep.update_type(), &dep.latest_version) {
+                    (UpdateType::UpToDate, _) | (_, None) => dep.name.clone(),
+                    (_, Some(latest)) => {
+                        format!("{} {} → {}", dep.name, dep.current_version, latest)
+                    }
+                })
+                .collect();
+            out.push_str(&format!(": {}", examples(&names)));
+        }
+        out.push_str(&format!("\n     {}\n", explanation));
+    }
+    out.push_str(
+        "\n`cargo sane check` lists them all, and `cargo sane update` lets you pick which \
+         to apply, checking that the project still builds afterwards.",
+    );
+    out
+}
+
+fn duplicates(snapshot: &Snapshot) -> String {
+    let mut out = String::from("🧭 Step 2 of 4: duplicated crates (`cargo sane fix`)\n\n");
+    let Some(conflicts) = &snapshot.conflicts else {
+        out.push_str(
+            "Conflict detection couldn't run here, since it needs `cargo tree` to work. \
+             Once it does, `cargo sane fix` shows crates compiled in more than one version.",
+        );
+        return out;
+    };
+    if conflicts.conflicts.is_empty() {
+        out.push_str(
+            "Every crate in the dependency graph resolves to a single version, so there's \
+             nothing to merge. When two dependencies ask for incompatible versions of the \
+             same crate, `cargo sane fix` is where it shows up.",
+        );
+        return out;
+    }
+
+    let names: Vec<String> = conflicts
+        .conflicts
+        .iter()
+        .map(|conflict| {
+            let versions: Vec<String> = conflict
+                .versions
+                .iter()
+                .map(|v| v.version.to_string())
+                .collect();
+            format!("{} ({})", conflict.name, versions.join(", "))
+        })
+        .collect();
+    out.push_str(&format!(
+        "{} compiled in more than one version: {}\n\n",
+        match conflicts.conflicts.len() {
+            1 => "1 crate is".to_string(),
+            n => format!("{} crates are", n),
+        },
+        examples(&names)
+    ));
+    out.push_str(
+        "Cargo allows this when dependents ask for semver-incompatible versions, but each \
+         copy is built separately: builds take longer, binaries grow, and a type from one \
+         copy doesn't match the same type from another.\n\n",
+    );
+
+    let mergeable = conflicts
+        .conflicts
+        .iter()
+        .filter(|c| c.resolvability() == Resolvability::Lockfile)
+        .count();
+    let stuck = stuck_duplicates(snapshot).len();
+    if mergeable > 0 {
+        out.push_str(&format!(
+            "{} can be merged by updating Cargo.lock alone. ",
+            plural(mergeable as u64, "duplicate")
+        ));
+    }
+    if stuck > 0 {
+        out.push_str(&format!(
+            "{} can only be merged by a new release of a dependent. ",
+            plural(stuck as u64, "duplicate")
+        ));
+    }
+    out.push_str("`cargo sane fix` walks through them one by one.");
+    out
+}
+
+fn health(snapshot: &Snapshot) -> String {
+    let mut out = String::from("🧭 Step 3 of 4: security advisories (`cargo sane health`)\n\n");
+    let Some(health) = &snapshot.health else {
+        out.push_str(
+            "The advisory scan couldn't run here. `cargo sane health` looks every package \
+             in Cargo.lock up in the RustSec advisory database, and says what went wrong.",
+        );
+        return out;
+    };
+    out.push_str(&format!(
+        "health looked {} up in the RustSec advisory database, transitive dependencies \
+         included. ",
+        plural(health.scanned as u64, "package version")
+    ));
+    if health.vulnerable.is_empty() {
+        out.push_str("None has a known vulnerability.\n\n");
+    } else {
+        out.push_str(&format!(
+            "{} an advisory against {}:\n\n",
+            match health.vulnerable.len() {
+                1 => "1 has".to_string(),
+                n => format!("{} have", n),
+            },
+            if health.vulnerable.len() == 1 {
+                "it"
+            } else {
+                "them"
+            }
+        ));
+        for package in health.vulnerable.iter().take(EXAMPLES) {
+            let ids: Vec<String> = package
+                .advisories
+                .iter()
+                .map(|advisory| match advisory.severity {
+                    Some(severity) => format!("{} ({})", advisory.id, severity),
+                    None => advisory.id.clone(),
+                })
+                .collect();
+            out.push_str(&format!(
+                "  • {} {}: {}",
+                package.name,
+                package.version,
+                ids.join(", ")
+            ));
+            if package.attribution.direct_parent != package.name {
+                out.push_str(&format!(", via {}", package.attribution.direct_parent));
+            }
+            out.push('\n');
+        }
+        if health.vulnerable.len() > EXAMPLES {
+            out.push_str(&format!(
+                "  … and {} more\n",
+                health.vulnerable.len() - EXAMPLES
+            ));
+        }
+        out.push_str(
+            "\nAn advisory names the affected package, but the fix is usually an update of \
+             the direct dependency that brings it in. ",
+        );
+    }
+    out.push_str(
+        "`cargo sane health` shows the patched versions, and `cargo sane accept` records \
+         an advisory you've decided to live with, so it stops failing the run.",
+    );
+    out
+}
+
+fn settings(snapshot: &Snapshot, has_config: bool) -> String {
+    let mut out = String::from("🧭 Step 4 of 4: settings\n\n");
+    if has_config {
+        out.push_str(&format!(
+            "You already have a {}, which the tour leaves as it is. For comparison, these \
+             settings would suit this project:\n\n",
+            CONFIG_FILE
+        ));
+    } else {
+        out.push_str(&format!(
+            "cargo-sane reads its settings from {} next to Cargo.toml. These would suit \
+             this project:\n\n",
+            CONFIG_FILE
+        ));
+    }
+    for line in suggested_config(snapshot).lines() {
+        if !line.is_empty() {
+            out.push_str("    ");
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str(
+        "\nThat's the tour. From here on, `cargo sane report` runs all three checks at once.",
+    );
+    out
+}
+
+/// Duplicated crates no lockfile update can merge
+fn stuck_duplicates(snapshot: &Snapshot) -> Vec<&str> {
+    let Some(conflicts) = &snapshot.conflicts else {
+        return Vec::new();
+    };
+    conflicts
+        .conflicts
+        .iter()
+        .filter(|c| {
+            matches!(
+                c.resolvability(),
+                Resolvability::NeedsUpgrade | Resolvability::Blocked
+            )
+        })
+        .map(|c| c.name.as_str())
+        .collect()
+}
+
+/// The first few of `names`, then how many more there are
+fn examples(names: &[String]) -> String {
+    let shown = names.iter().take(EXAMPLES).cloned().collect::<Vec<_>>();
+    match names.len().saturating_sub(EXAMPLES) {
+        0 => shown.join(", "),
+        more => format!("{}, and {} more", shown.join(", "), more),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::checker::test_support::check_report;
+    use crate::analyzer::conflicts::{Conflict, ConflictReport, ConflictVersion};
+    use crate::core::config::Config;
+    use crate::core::dependency::test_support::dep;
+    use semver::Version;
+
+    fn conflict(name: &str, versions: &[&str]) -> Conflict {
+        Conflict {
+            name: name.to_string(),
+            versions: versions
+                .iter()
+                .map(|v| ConflictVersion {
+                    version: Version::parse(v).unwrap(),
+                    dependents: Vec::new(),
+                    advisories: Vec::new(),
+                    sources: Vec::new(),
+                })
+                .collect(),
+            security_relevant: false,
+        }
+    }
+
+    fn snapshot() -> Snapshot {
+        let check = check_report(vec![
+            dep("serde", "1.0.200", "1.0.200"),
+            dep("syn", "2.0.10", "2.0.48"),
+            dep("clap", "3.2.0", "4.5.0"),
+        ]);
+        let conflicts = ConflictReport {
+            conflicts: vec![
+                conflict("bitflags", &["1.3.2", "2.4.0"]),
+                conflict("syn", &["2.0.10", "2.0.48"]),
+            ],
+            source_splits: Vec::new(),
+        };
+        Snapshot::new(0, check, None, Some(conflicts))
+    }
+
+    #[test]
+    fn test_render_tour() {
+        let sections = render_tour(&snapshot(), false);
+        assert_eq!(sections.len(), 5);
+        assert!(sections[0].contains("looks at demo"), "{}", sections[0]);
+        assert!(
+            sections[1].contains("✅ Up to date (1): serde\n"),
+            "{}",
+            sections[1]
+        );
+        assert!(
+            sections[1].contains("🔴 Major (1): clap 3.2.0 → 4.5.0\n"),
+            "{}",
+            sections[1]
+        );
+        assert!(sections[1].contains("🟡 Minor (0)\n"), "{}", sections[1]);
+        assert!(
+            sections[2].contains("bitflags (1.3.2, 2.4.0), syn (2.0.10, 2.0.48)"),
+            "{}",
+            sections[2]
+        );
+        assert!(
+            sections[2].contains(
+                "1 duplicate can be merged by updating Cargo.lock alone. 1 duplicate can only \
+                 be merged by a new release of a dependent."
+            ),
+            "{}",
+            sections[2]
+        );
+        // Without a health report, the step says so rather than vanishing
+        assert!(
+            sections[3].contains("The advisory scan couldn't run here."),
+            "{}",
+            sections[3]
+        );
+        assert!(
+            sections[4].contains("    ignore_conflicts = [\"bitflags\"]\n"),
+            "{}",
+            sections[4]
+        );
+
+        let existing = render_tour(&snapshot(), true);
+        assert!(
+            existing[4].contains("which the tour leaves as it is"),
+            "{}",
+            existing[4]
+        );
+    }
+
+    #[test]
+    fn test_suggested_config_is_valid() {
+        let config: Config = toml::from_str(&suggested_config(&snapshot())).unwrap();
+        assert_eq!(config.cache_ttl_minutes, 60);
+        assert_eq!(config.ignore_conflicts, vec!["bitflags".to_string()]);
+        assert!(config.freshness.budgets.is_empty());
+
+        let mut merged = snapshot();
+        merged.conflicts = None;
+        assert!(!suggested_config(&merged).contains("ignore_conflicts"));
+    }
+}
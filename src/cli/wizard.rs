@@ -0,0 +1,447 @@
+//! Interactive resolution of duplicated crates for `fix`
+//!
+//! Conflicts are walked one at a time, most impactful first. Every choice is
+//! carried out right away and its result shown, so later conflicts are
+//! decided knowing what the earlier choices did. A summary at the end lists
+//! what was resolved and what is still left.
+
+use crate::analyzer::conflicts::{Conflict, Resolvability};
+use crate::cli::commands::{
+    print_fix_outcome, record_audit, run_conflict_fixes, runtime, FixOutcome,
+};
+use crate::cli::output::{self, Paint};
+use crate::cli::prompt;
+use crate::core::config::{Config, CONFIG_FILE};
+use crate::core::manifest::Manifest;
+use crate::updater::update::DependencyUpdater;
+use crate::utils::audit::{AuditChange, AuditEntry};
+use crate::utils::cargo::{self, CargoOptions};
+use crate::utils::crates_io::CratesIoClient;
+use crate::utils::formatting::display_path;
+use crate::utils::registry::RegistryProvider;
+use crate::Result;
+use anyhow::Context;
+use colored::Colorize;
+use std::path::Path;
+
+/// What can be done about one conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Choice {
+    Lockfile,
+    Bump,
+    Patch,
+    Ignore,
+    Skip,
+    Stop,
+}
+
+impl Choice {
+    fn label(self) -> &'static str {
+        match self {
+            Choice::Lockfile => "Update the lockfile to the target version",
+            Choice::Bump => "Bump a direct dependency that holds an old version",
+            Choice::Patch => "Add a [patch.crates-io] entry",
+            Choice::Ignore => "Ignore this crate from now on",
+            Choice::Skip => "Skip",
+            Choice::Stop => "Stop here",
+        }
+    }
+}
+
+/// Walk through `conflicts` in order, asking what to do with each
+pub fn run_conflict_wizard(
+    manifest: &Manifest,
+    conflicts: &[Conflict],
+    cargo: &CargoOptions,
+) -> Result<()> {
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    let mut done = Vec::new();
+    let mut remaining = Vec::new();
+
+    for (index, conflict) in conflicts.iter().enumerate() {
+        println!(
+            "{} {}",
+            format!("[{}/{}]", index + 1, conflicts.len()).dimmed(),
+            conflict.name.bold()
+        );
+        print_conflict(conflict);
+
+        let direct = direct_dependents(manifest, conflict);
+        let choices = choices(conflict, !direct.is_empty());
+        let labels: Vec<&str> = choices.iter().map(|c| c.label()).collect();
+        let selection = prompt::select(
+            &format!("What should be done about {}?", conflict.name),
+            &labels,
+            0,
+        )?;
+
+        let result = match choices[selection] {
+            Choice::Lockfile => {
+                // Each update is shown as it went, with cargo's stderr
+                let outcome = run_conflict_fixes(manifest, std::slice::from_ref(conflict), cargo);
+                print_fix_outcome(&outcome);
+                if !outcome.is_clean() {
+                    remaining.push(format!("{} (failed)", conflict.name));
+                } else {
+                    done.push(lockfile_summary(conflict, &outcome));
+                    if !outcome.still_duplicated.is_empty() {
+                        remaining.push(format!("{} (still duplicated)", conflict.name));
+                    } else if !outcome.needs_upgrade.is_empty() {
+                        remaining.push(format!(
+                            "{} (needs a dependent's new release)",
+                            conflict.name
+                        ));
+                    }
+                }
+                println!();
+                continue;
+            }
+            Choice::Bump => {
+                let selection = prompt::select("Which dependency?", &direct, 0)?;
+                bump_dependency(manifest, &direct[selection], cargo)
+            }
+            Choice::Patch => {
+                let source =
+                    prompt::input(&format!("Git URL or path of the patched {}", conflict.name))?;
+                add_patch(manifest, &conflict.name, source.trim(), cargo)
+            }
+            Choice::Ignore => Config::ignore_conflict(root, &conflict.name)
+                .map(|()| format!("ignored {} in {}", conflict.name, CONFIG_FILE)),
+            Choice::Skip => {
+                remaining.push(format!("{} (skipped)", conflict.name));
+                println!();
+                continue;
+            }
+            Choice::Stop => {
+                remaining.extend(conflicts[index..].iter().map(|c| c.name.clone()));
+                break;
+            }
+        };
+
+        match result {
+            Ok(message) => {
+                println!("  {} {}", output::plain("✓").good(), message);
+                done.push(message);
+            }
+            Err(e) => {
+                eprintln!("  {} {}", output::plain("✗").bad(), e);
+                remaining.push(format!("{} (failed)", conflict.name));
+            }
+        }
+        println!();
+    }
+
+    print_summary(&done, &remaining);
+    Ok(())
+}
+
+/// The choices that apply to `conflict`, the most direct fix first
+fn choices(conflict: &Conflict, has_direct_dependents: bool) -> Vec<Choice> {
+    let mut choices = Vec::new();
+    if conflict.movable().iter().any(|(_, compatible)| *compatible) {
+        choices.push(Choice::Lockfile);
+    }
+    if has_direct_dependents {
+        choices.push(Choice::Bump);
+    }
+    choices.extend([Choice::Patch, Choice::Ignore, Choice::Skip, Choice::Stop]);
+    choices
+}
+
+/// Dependents of off-target versions that the manifest declares itself,
+/// and so can be bumped here
+fn direct_dependents(manifest: &Manifest, conflict: &Conflict) -> Vec<String> {
+    let declared: Vec<String> = manifest
+        .declarations()
+        .into_iter()
+        .map(|(_, name, _)| name)
+        .collect();
+    conflict
+        .dependents_off_target()
+        .into_iter()
+        .filter(|name| declared.iter().any(|d| d == name))
+        .map(str::to_string)
+        .collect()
+}
+
+fn print_conflict(conflict: &Conflict) {
+    let target = conflict.target();
+    for version in &conflict.versions {
+        let mut line = format!("v{}", version.version);
+        if Some(&version.version) == target {
+            line = format!("{} (target)", line).good().to_string();
+        }
+        if !version.advisories.is_empty() {
+            line.push_str(&format!(" {}", version.advisories.join(", ").bad()));
+        }
+        println!("    {}", line);
+        if !version.dependents.is_empty() {
+            println!(
+                "      {}",
+                format!("required by {}", version.dependents.join(", ")).dimmed()
+            );
+        }
+    }
+
+    let resolvability = match conflict.resolvability() {
+        Resolvability::Lockfile => "resolvable in the lockfile".good(),
+        Resolvability::Partial => "partly resolvable in the lockfile".caution(),
+        Resolvability::NeedsUpgrade => "needs a dependent upgraded".caution(),
+        Resolvability::Blocked => "no clean version to converge on".bad(),
+    };
+    println!("    {}", resolvability);
+}
+
+/// What a clean lockfile update of `conflict` did, for the summary
+fn lockfile_summary(conflict: &Conflict, outcome: &FixOutcome) -> String {
+    let moved: Vec<String> = outcome
+        .fixes
+        .iter()
+        .map(|fix| format!("v{}", fix.from))
+        .collect();
+    format!(
+        "moved {} {} → v{} in Cargo.lock",
+        conflict.name,
+        moved.join(", "),
+        conflict.target().map(|t| t.to_string()).unwrap_or_default()
+    )
+}
+
+/// Raise every declaration of `name` to its latest release, then let the
+/// lockfile follow
+fn bump_dependency(manifest: &Manifest, name: &str, cargo: &CargoOptions) -> Result<String> {
+    let client = CratesIoClient::new().context("building the crates.io client")?;
+    let latest = runtime()?.block_on(client.get_latest_version(name))?;
+
+    // Re-read the manifest: earlier choices may have edited it
+    let manifest = Manifest::from_path(&manifest.path)?;
+    let declarations: Vec<_> = manifest
+        .declarations()
+        .into_iter()
+        .filter(|(_, declared, spec)| declared == name && spec.version().is_some())
+        .map(|(section, _, _)| section)
+        .collect();
+    if declarations.is_empty() {
+        anyhow::bail!("{} has no version requirement to bump", name);
+    }
+
+    let path = manifest.path.clone();
+    let mut updater = DependencyUpdater::new(manifest)?;
+    let mut changes = Vec::new();
+    for section in &declarations {
+        let edit = updater.update_declaration(section, name, &latest.to_string())?;
+        changes.push(AuditChange::from(&edit));
+    }
+    let backup = updater.save()?;
+    record_audit(
+        &path,
+        AuditEntry::new("fix", &path)
+            .with_changes(changes)
+            .with_backup(Some(backup.clone())),
+    );
+    cargo::update_package(&path, name, cargo)?;
+    Ok(format!(
+        "bumped {} to {} in Cargo.toml (backup saved as {})",
+        name,
+        latest,
+        display_path(&backup)
+    ))
+}
+
+/// Point crates-io's `name` at a git repository or a local path
+fn add_patch(
+    manifest: &Manifest,
+    name: &str,
+    source: &str,
+    cargo: &CargoOptions,
+) -> Result<String> {
+    if source.is_empty() {
+        anyhow::bail!("No git URL or path given for {}", name);
+    }
+    let key = if source.contains("://") || source.starts_with("git@") {
+        "git"
+    } else {
+        "path"
+    };
+    let spec = format!(
+        "{{ {} = {} }}",
+        key,
+        toml::Value::String(source.to_string())
+    );
+
+    let manifest = Manifest::from_path(&manifest.path)?;
+    let path = manifest.path.clone();
+    let mut updater = DependencyUpdater::new(manifest)?;
+    updater.add_patch(name, &spec)?;
+    let backup = updater.save()?;
+    record_audit(
+        &path,
+        AuditEntry::new("fix", &path)
+            .with_changes(vec![AuditChange {
+                name: name.to_string(),
+                old: None,
+                new: Some(spec.clone()),
+                section: "patch.crates-io".to_string(),
+            }])
+            .with_backup(Some(backup)),
+    );
+    cargo::update_package(&path, name, cargo)?;
+    Ok(format!("patched {} with {} in Cargo.toml", name, source))
+}
+
+fn print_summary(done: &[String], remaining: &[String]) {
+    println!("{}", output::plain("📋 Summary:").bold());
+    if done.is_empty() {
+        println!("  Nothing was changed.");
+    }
+    for item in done {
+        println!("  {} {}", output::plain("✓").good(), item);
+    }
+    for item in remaining {
+        println!("  {} {}", output::plain("•").caution(), item);
+    }
+    println!();
+
+    if remaining.is_empty() {
+        output::print_success("Every duplicated crate was handled.");
+    } else {
+        output::print_info(&format!(
+            "{} duplicated crate(s) remain; run `cargo sane fix` again to revisit them.",
+            remaining.len()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::conflicts::ConflictVersion;
+    use semver::Version;
+
+    fn conflict(versions: &[&str]) -> Conflict {
+        Conflict {
+            name: "syn".to_string(),
+            versions: versions
+                .iter()
+                .map(|v| ConflictVersion {
+                    version: Version::parse(v).unwrap(),
+                    dependents: vec!["serde_derive v1.0.100".to_string()],
+                    advisories: Vec::new(),
+                    sources: Vec::new(),
+                })
+                .collect(),
+            security_relevant: false,
+        }
+    }
+
+    #[test]
+    fn test_choices_depend_on_the_conflict() {
+        assert_eq!(
+            choices(&conflict(&["2.0.1", "2.0.40"]), false),
+            vec![
+                Choice::Lockfile,
+                Choice::Patch,
+                Choice::Ignore,
+                Choice::Skip,
+                Choice::Stop
+            ]
+        );
+        assert_eq!(
+            choices(&conflict(&["1.0.109", "2.0.40"]), true)[..2],
+            [Choice::Bump, Choice::Patch]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_conflict_fixes_report_failures_and_verify_the_lockfile() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let lock = |versions: &[&str]| {
+            versions
+                .iter()
+                .map(|v| {
+                    let (name, version) = v.split_once('@').unwrap();
+                    format!(
+                        "[[package]]\nname = \"{}\"\nversion = \"{}\"\n\n",
+                        name, version
+                    )
+                })
+                .collect::<String>()
+        };
+        std::fs::write(
+            dir.path().join("Cargo.lock"),
+            lock(&["syn@1.0.109", "syn@2.0.1", "syn@2.0.30", "syn@2.0.40"]),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("after.lock"),
+            lock(&[
+                "syn@1.0.109",
+                "syn@2.0.30",
+                "syn@2.0.40",
+                "quote@1.0.30",
+                "quote@1.0.35",
+                "bitflags@1.3.2",
+                "bitflags@2.4.0",
+            ]),
+        )
+        .unwrap();
+        // syn@2.0.1 moves, syn@2.0.30 fails, quote@1.0.30 "succeeds" in vain
+        let script = dir.path().join("fake-cargo");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\ncase \"$*\" in\n*syn@2.0.1\\ *) cp after.lock Cargo.lock ;;\n*syn@2.0.30\\ *) echo 'error: failed to select a version' >&2; echo '  required by serde_derive' >&2; exit 101 ;;\nesac\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let config = Config {
+            cargo_command: Some(script.display().to_string()),
+            ..Config::default()
+        };
+        let cargo = CargoOptions::for_project(&config, dir.path()).unwrap();
+        let manifest =
+            Manifest::parse(dir.path().join("Cargo.toml"), "[package]\nname = \"app\"\n").unwrap();
+
+        let mut quote = conflict(&["1.0.30", "1.0.35"]);
+        quote.name = "quote".to_string();
+        // bitflags 1 and 2 can't be merged in the lockfile, so they aren't
+        // counted against the fix
+        let mut bitflags = conflict(&["1.3.2", "2.4.0"]);
+        bitflags.name = "bitflags".to_string();
+        let conflicts = [
+            conflict(&["1.0.109", "2.0.1", "2.0.30", "2.0.40"]),
+            quote,
+            bitflags,
+        ];
+        let outcome = run_conflict_fixes(&manifest, &conflicts, &cargo);
+
+        let fixes: Vec<(String, bool, bool)> = outcome
+            .fixes
+            .iter()
+            .map(|f| {
+                (
+                    format!("{}@{}", f.name, f.from),
+                    f.error.is_some(),
+                    f.still_locked,
+                )
+            })
+            .collect();
+        assert_eq!(
+            fixes,
+            [
+                ("syn@2.0.1".to_string(), false, false),
+                ("syn@2.0.30".to_string(), true, false),
+                ("quote@1.0.30".to_string(), false, true),
+            ]
+        );
+        assert_eq!(
+            outcome.fixes[1].error.as_deref(),
+            Some("cargo update failed: error: failed to select a version\n  required by serde_derive")
+        );
+        assert_eq!(outcome.still_duplicated, ["syn", "quote"]);
+        assert_eq!(outcome.needs_upgrade, ["bitflags"]);
+        assert!(!outcome.is_clean());
+    }
+}
@@ -0,0 +1,214 @@
+//! Security advisory representation
+
+use schemars::JsonSchema;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A published advisory affecting a specific package version
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Advisory {
+    /// Primary identifier, e.g. RUSTSEC-2023-0001
+    pub id: String,
+    pub package: String,
+    pub title: String,
+    pub severity: Option<Severity>,
+    /// The CVSS vector the severity was derived from
+    pub cvss: Option<String>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Requirements describing versions with the fix, e.g. ">=1.2.3"
+    #[serde(default)]
+    pub patched_versions: Vec<String>,
+    /// Set for non-vulnerability notices such as "unmaintained"
+    pub informational: Option<String>,
+    pub url: String,
+}
+
+impl Advisory {
+    /// Whether `version` is outside every patched range. Without recorded
+    /// introduction ranges this errs towards "affected", which is the safe
+    /// side when choosing what to upgrade to.
+    pub fn affects(&self, version: &Version) -> bool {
+        !self
+            .patched_versions
+            .iter()
+            .filter_map(|req| VersionReq::parse(req).ok())
+            .any(|req| req.matches(version))
+    }
+}
+
+/// Qualitative severity rating as defined by CVSS v3
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    None,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// Rating for a CVSS v3 base score
+    pub fn from_score(score: f64) -> Self {
+        if score >= 9.0 {
+            Severity::Critical
+        } else if score >= 7.0 {
+            Severity::High
+        } else if score >= 4.0 {
+            Severity::Medium
+        } else if score > 0.0 {
+            Severity::Low
+        } else {
+            Severity::None
+        }
+    }
+
+    /// Rating for a CVSS v3 vector such as `CVSS:3.1/AV:N/AC:L/...`
+    pub fn from_cvss(vector: &str) -> Option<Self> {
+        cvss3_base_score(vector).map(Self::from_score)
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Severity::None => "none",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// Compute the CVSS v3.x base score of a vector string, following the
+/// specification's equations (section 7.1 of the v3.1 spec)
+pub fn cvss3_base_score(vector: &str) -> Option<f64> {
+    let mut metrics = std::collections::HashMap::new();
+    let mut parts = vector.split('/');
+    if !parts.next()?.starts_with("CVSS:3") {
+        return None;
+    }
+    for part in parts {
+        let (metric, value) = part.split_once(':')?;
+        metrics.insert(metric, value);
+    }
+
+    let scope_changed = match *metrics.get("S")? {
+        "U" => false,
+        "C" => true,
+        _ => return None,
+    };
+    let attack_vector = match *metrics.get("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        "P" => 0.2,
+        _ => return None,
+    };
+    let attack_complexity = match *metrics.get("AC")? {
+        "L" => 0.77,
+        "H" => 0.44,
+        _ => return None,
+    };
+    let privileges = match (*metrics.get("PR")?, scope_changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None,
+    };
+    let interaction = match *metrics.get("UI")? {
+        "N" => 0.85,
+        "R" => 0.62,
+        _ => return None,
+    };
+    let impact_of = |metric: &str| -> Option<f64> {
+        match *metrics.get(metric)? {
+            "H" => Some(0.56),
+            "L" => Some(0.22),
+            "N" => Some(0.0),
+            _ => None,
+        }
+    };
+    let (c, i, a) = (impact_of("C")?, impact_of("I")?, impact_of("A")?);
+
+    let iss = 1.0 - (1.0 - c) * (1.0 - i) * (1.0 - a);
+    let impact = if scope_changed {
+        7.52 * (iss - 0.029) - 3.25 * (iss - 0.02).powi(15)
+    } else {
+        6.42 * iss
+    };
+    let exploitability = 8.22 * attack_vector * attack_complexity * privileges * interaction;
+
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+    let score = if scope_changed {
+        (1.08 * (impact + exploitability)).min(10.0)
+    } else {
+        (impact + exploitability).min(10.0)
+    };
+    Some(round_up(score))
+}
+
+/// CVSS "Roundup": smallest number with one decimal place >= input, computed
+/// on integers to avoid floating point artifacts
+fn round_up(value: f64) -> f64 {
+    let int_input = (value * 100_000.0).round() as i64;
+    if int_input % 10_000 == 0 {
+        int_input as f64 / 100_000.0
+    } else {
+        ((int_input / 10_000) + 1) as f64 / 10.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cvss3_base_score() {
+        assert_eq!(
+            cvss3_base_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"),
+            Some(9.8)
+        );
+        assert_eq!(
+            cvss3_base_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H"),
+            Some(10.0)
+        );
+        assert_eq!(
+            cvss3_base_score("CVSS:3.0/AV:L/AC:H/PR:L/UI:R/S:U/C:L/I:N/A:N"),
+            Some(2.2)
+        );
+        assert_eq!(
+            cvss3_base_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:H"),
+            Some(7.5)
+        );
+        assert_eq!(
+            cvss3_base_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:N"),
+            Some(0.0)
+        );
+        assert_eq!(cvss3_base_score("CVSS:2.0/AV:N"), None);
+        assert_eq!(cvss3_base_score("CVSS:3.1/AV:X/AC:L"), None);
+    }
+
+    #[test]
+    fn test_severity_rating() {
+        assert_eq!(
+            Severity::from_cvss("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"),
+            Some(Severity::Critical)
+        );
+        assert_eq!(Severity::from_score(7.5), Severity::High);
+        assert_eq!(Severity::from_score(5.3), Severity::Medium);
+        assert_eq!(Severity::from_score(2.2), Severity::Low);
+        assert_eq!(Severity::from_score(0.0), Severity::None);
+        assert!(Severity::Critical > Severity::High);
+    }
+}
@@ -1,10 +1,344 @@
 //! Configuration file handling
 
+use crate::core::license::LicensePolicy;
+use anyhow::{Context, Result};
+use regex::Regex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Name of the per-project configuration file, looked up next to Cargo.toml
+pub const CONFIG_FILE: &str = ".cargo-sane.toml";
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct Config {
     pub auto_update_patch: bool,
     pub auto_update_minor: bool,
     pub ignore_crates: Vec<String>,
+    /// Scan hidden files and directories when looking for source files
+    pub scan_hidden: bool,
+    /// Scan paths excluded by .gitignore and friends
+    pub scan_ignored: bool,
+    /// Scan the `target/` build directory
+    pub scan_target: bool,
+    /// Follow symbolic links while scanning (cycles are still detected)
+    pub follow_symlinks: bool,
+    /// Reuse check results younger than this many minutes while the
+    /// dependency declarations are unchanged (0 disables the cache)
+    pub cache_ttl_minutes: u64,
+    /// Megabytes `cache gc` prunes the caches under .cargo-sane down to,
+    /// oldest files first (0 sets no budget)
+    pub cache_max_size_mb: u64,
+    /// Maximum number of registry requests in flight (0 uses the default)
+    pub concurrency: usize,
+    /// Untagged snapshots to keep under .cargo-sane/snapshots (0 keeps all)
+    pub snapshot_retention: usize,
+    /// Days after which the local advisory database counts as stale
+    /// (0 uses the default of 7)
+    pub advisory_db_max_age_days: u64,
+    /// Fail health checks when a stale advisory database can't be refreshed,
+    /// instead of warning and using it anyway
+    pub advisory_db_strict: bool,
+    /// URL of a whole advisory database archive (gzip) that `health` keeps
+    /// in .cargo-sane/advisory-db.tar.gz and refreshes along with the
+    /// database; none is downloaded when unset
+    pub advisory_db_archive: Option<String>,
+    /// The organization's blessed versions: a TOML or JSON file mapping crate
+    /// names to requirements or `{ track = "0.14" }` release series, as a
+    /// path relative to the project or an https:// URL
+    pub versions_file: Option<String>,
+    /// The command used to run cargo, e.g. "ci/cargo-wrapper.sh", instead of
+    /// `$CARGO` or `cargo` on the PATH. Words after the first are passed
+    /// before cargo-sane's own arguments.
+    pub cargo_command: Option<String>,
+    /// Duplicated crates `fix` leaves alone, e.g. ones a dependency pins on
+    /// purpose
+    pub ignore_conflicts: Vec<String>,
+    /// Git dependencies that fork a crates.io crate on purpose, so `check`
+    /// and `health` don't point out how far they trail its releases
+    pub intentional_forks: Vec<String>,
+    /// How far behind the latest release each category of dependency may
+    /// fall before `check` fails
+    pub freshness: FreshnessConfig,
+    /// Don't record changes in .cargo-sane/audit.log
+    pub disable_audit_log: bool,
+    /// Look workspace members, path crates and unpublished crates from the
+    /// project's own repository up like any other dependency, instead of
+    /// setting them aside as internal
+    pub treat_internal_as_external: bool,
+    /// Dependency declarations with more keys than this are laid out as
+    /// `[dependencies.name]` sections by `fmt-deps` (0 uses the default
+    /// of 4)
+    pub fmt_inline_max_keys: usize,
+    /// Packages `health --enrich` looks up on crates.io, most exposed first
+    /// (0 uses the default of 50)
+    pub enrich_limit: usize,
+    /// Licenses update targets may and may not be published under
+    pub licenses: LicensePolicy,
+    /// "symbols" tags every status with text such as [MAJOR] or [HIGH], the
+    /// same as `--symbols`
+    pub accessibility: Accessibility,
+    /// "colorblind" paints statuses blue, yellow and vermillion instead of
+    /// green, yellow and red
+    pub palette: Palette,
+}
+
+/// The settings a run goes by: the config's, with the built-in defaults
+/// standing in for what it leaves unset. Named as in the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Settings {
+    pub auto_update_patch: bool,
+    pub auto_update_minor: bool,
+    pub scan_hidden: bool,
+    pub scan_ignored: bool,
+    pub scan_target: bool,
+    pub follow_symlinks: bool,
+    /// 0 when the check cache is off
+    pub cache_ttl_minutes: u64,
+    /// 0 when `cache gc` has no size budget
+    pub cache_max_size_mb: u64,
+    pub concurrency: usize,
+    /// 0 keeps every snapshot
+    pub snapshot_retention: usize,
+    pub advisory_db_max_age_days: u64,
+    pub advisory_db_strict: bool,
+    /// None downloads no archive
+    pub advisory_db_archive: Option<String>,
+    pub versions_file: Option<String>,
+    /// None runs `$CARGO`, or `cargo` on the PATH
+    pub cargo_command: Option<String>,
+    pub disable_audit_log: bool,
+    pub treat_internal_as_external: bool,
+    pub fmt_inline_max_keys: usize,
+    pub enrich_limit: usize,
+    pub accessibility: Accessibility,
+    pub palette: Palette,
+}
+
+impl Settings {
+    /// What a project without a config file runs with
+    pub const DEFAULT: Settings = Settings {
+        auto_update_patch: false,
+        auto_update_minor: false,
+        scan_hidden: false,
+        scan_ignored: false,
+        scan_target: false,
+        follow_symlinks: false,
+        cache_ttl_minutes: 0,
+        cache_max_size_mb: 0,
+        concurrency: 8,
+        snapshot_retention: 0,
+        advisory_db_max_age_days: 7,
+        advisory_db_strict: false,
+        advisory_db_archive: None,
+        versions_file: None,
+        cargo_command: None,
+        disable_audit_log: false,
+        treat_internal_as_external: false,
+        fmt_inline_max_keys: 4,
+        enrich_limit: 50,
+        accessibility: Accessibility::Default,
+        palette: Palette::Standard,
+    };
+}
+
+/// How statuses are told apart besides their color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Accessibility {
+    /// Emoji markers, with text wherever a marker would stand alone
+    #[default]
+    Default,
+    /// An ASCII tag on every status
+    Symbols,
+}
+
+/// The colors statuses are painted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    #[default]
+    Standard,
+    /// Colors that stay apart with red-green color blindness (Okabe-Ito)
+    Colorblind,
+}
+
+/// The `[freshness]` table:
+///
+/// ```toml
+/// [freshness.budgets]
+/// security = { max_majors_behind = 0, max_minors_behind = 1 }
+/// default = { max_majors_behind = 1 }
+///
+/// [freshness.categories]
+/// my-parser = "security"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct FreshnessConfig {
+    /// Budget of each category; crates whose category has none use the
+    /// "default" budget, and no budgets at all disables the check
+    pub budgets: BTreeMap<String, FreshnessBudget>,
+    /// Category of individual crates, overriding the built-in one
+    pub categories: BTreeMap<String, String>,
+}
+
+/// Limits on how many releases a dependency may trail the latest by; an
+/// unset limit isn't checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct FreshnessBudget {
+    /// Breaking releases: majors, or minors below 1.0
+    pub max_majors_behind: Option<u64>,
+    /// Compatible minor releases (patches below 1.0) within the breaking
+    /// release in use
+    pub max_minors_behind: Option<u64>,
+}
+
+impl Config {
+    /// Load the configuration for the project in `dir`, falling back to the
+    /// defaults when no config file exists
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content =
+            fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).context(format!("Failed to parse {}", path.display()))
+    }
+
+    /// The settings this config runs with, where 0 stands for the default
+    /// in the settings that have one
+    pub fn settings(&self) -> Settings {
+        let default = Settings::DEFAULT;
+        Settings {
+            auto_update_patch: self.auto_update_patch,
+            auto_update_minor: self.auto_update_minor,
+            scan_hidden: self.scan_hidden,
+            scan_ignored: self.scan_ignored,
+            scan_target: self.scan_target,
+            follow_symlinks: self.follow_symlinks,
+            cache_ttl_minutes: self.cache_ttl_minutes,
+            cache_max_size_mb: self.cache_max_size_mb,
+            concurrency: nonzero_or(self.concurrency, default.concurrency),
+            snapshot_retention: self.snapshot_retention,
+            advisory_db_max_age_days: nonzero_or(
+                self.advisory_db_max_age_days,
+                default.advisory_db_max_age_days,
+            ),
+            advisory_db_strict: self.advisory_db_strict,
+            advisory_db_archive: self.advisory_db_archive.clone(),
+            versions_file: self.versions_file.clone(),
+            cargo_command: self.cargo_command.clone(),
+            disable_audit_log: self.disable_audit_log,
+            treat_internal_as_external: self.treat_internal_as_external,
+            fmt_inline_max_keys: nonzero_or(self.fmt_inline_max_keys, default.fmt_inline_max_keys),
+            enrich_limit: nonzero_or(self.enrich_limit, default.enrich_limit),
+            accessibility: self.accessibility,
+            palette: self.palette,
+        }
+    }
+
+    /// Add `name` to `ignore_conflicts` in the config file of the project in
+    /// `dir`, creating the file if needed. The rest of the file, comments
+    /// included, is kept as is.
+    pub fn ignore_conflict(dir: &Path, name: &str) -> Result<()> {
+        let path = dir.join(CONFIG_FILE);
+        let content = if path.exists() {
+            fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?
+        } else {
+            String::new()
+        };
+        let mut ignored = Self::load(dir)?.ignore_conflicts;
+        if ignored.iter().any(|n| n == name) {
+            return Ok(());
+        }
+        ignored.push(name.to_string());
+
+        let line = format!(
+            "ignore_conflicts = {}",
+            toml::Value::Array(ignored.into_iter().map(toml::Value::String).collect())
+        );
+        let existing = Regex::new(r"(?ms)^ignore_conflicts\s*=\s*\[.*?\]")
+            .context("Invalid config pattern")?;
+        let updated = match existing.find(&content) {
+            Some(found) => format!(
+                "{}{}{}",
+                &content[..found.start()],
+                line,
+                &content[found.end()..]
+            ),
+            None => {
+                // Top-level keys must come before the first table header
+                let header = Regex::new(r"(?m)^\s*\[").context("Invalid config pattern")?;
+                let at = header.find(&content).map_or(content.len(), |m| m.start());
+                let separator = if at > 0 && !content[..at].ends_with('\n') {
+                    "\n"
+                } else {
+                    ""
+                };
+                format!(
+                    "{}{}{}\n{}",
+                    &content[..at],
+                    separator,
+                    line,
+                    &content[at..]
+                )
+            }
+        };
+        fs::write(&path, updated).context(format!("Failed to write {}", path.display()))
+    }
+}
+
+/// `value`, or `default` when it's 0
+fn nonzero_or<T: PartialEq + Default>(value: T, default: T) -> T {
+    if value == T::default() {
+        default
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_conflict_keeps_the_rest_of_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        Config::ignore_conflict(dir.path(), "syn").unwrap();
+        assert_eq!(
+            Config::load(dir.path()).unwrap().ignore_conflicts,
+            vec!["syn"]
+        );
+
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            "# team settings\nconcurrency = 4\nignore_conflicts = [\n  \"syn\",\n]\n",
+        )
+        .unwrap();
+        Config::ignore_conflict(dir.path(), "bitflags").unwrap();
+        Config::ignore_conflict(dir.path(), "syn").unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join(CONFIG_FILE)).unwrap(),
+            "# team settings\nconcurrency = 4\nignore_conflicts = [\"syn\", \"bitflags\"]\n"
+        );
+    }
+
+    #[test]
+    fn test_settings_fill_in_the_defaults() {
+        assert_eq!(Config::default().settings(), Settings::DEFAULT);
+
+        let config: Config =
+            toml::from_str("concurrency = 4\nenrich_limit = 0\ncache_ttl_minutes = 30\n").unwrap();
+        let settings = config.settings();
+        assert_eq!(settings.concurrency, 4);
+        assert_eq!(settings.enrich_limit, Settings::DEFAULT.enrich_limit);
+        assert_eq!(settings.cache_ttl_minutes, 30);
+    }
 }
@@ -0,0 +1,287 @@
+//! The parts of a cargo-deny `deny.toml` that bear on updates
+//!
+//! A project that runs cargo-deny in CI already says which crates and
+//! licenses it won't take. An update whose target a `[bans]` entry denies,
+//! or whose license the `[licenses]` table doesn't accept, would only fail
+//! there later, so it's flagged before it's applied. Only those two tables
+//! are read, and only the keys below; cargo-deny itself is never run.
+
+use crate::core::license::LicensePolicy;
+use anyhow::Context;
+use schemars::JsonSchema;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where cargo-deny looks for its config, in each directory from the
+/// project's up
+const DENY_FILES: [&str; 3] = ["deny.toml", ".deny.toml", ".cargo/deny.toml"];
+
+/// The `[bans]` and `[licenses]` tables of a `deny.toml`:
+///
+/// ```toml
+/// [bans]
+/// deny = [
+///     "openssl",
+///     { name = "time", version = "<0.2" },
+///     { crate = "chrono@<0.4.20", reason = "RUSTSEC-2020-0159" },
+/// ]
+///
+/// [licenses]
+/// allow = ["MIT", "Apache-2.0"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct DenyPolicy {
+    pub bans: Bans,
+    pub licenses: LicensePolicy,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Bans {
+    pub deny: Vec<Ban>,
+}
+
+/// A `[bans] deny` entry: a crate, or a range of its versions
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawBan")]
+pub struct Ban {
+    pub name: String,
+    /// The versions banned; every one when unset
+    pub version: Option<VersionReq>,
+    pub reason: Option<String>,
+}
+
+/// The forms cargo-deny takes a ban in: `"name"`, `"name@<range>"`, or a
+/// table with either `name` and `version` or a `crate` spec
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawBan {
+    Spec(String),
+    Table {
+        name: Option<String>,
+        #[serde(rename = "crate")]
+        spec: Option<String>,
+        version: Option<String>,
+        reason: Option<String>,
+    },
+}
+
+impl TryFrom<RawBan> for Ban {
+    type Error = String;
+
+    fn try_from(raw: RawBan) -> Result<Self, Self::Error> {
+        let (spec, version, reason) = match raw {
+            RawBan::Spec(spec) => (spec, None, None),
+            RawBan::Table {
+                name,
+                spec,
+                version,
+                reason,
+            } => match (name, spec) {
+                (Some(name), None) => (name, version, reason),
+                (None, Some(spec)) => (spec, None, reason),
+                _ => return Err("a ban needs either `name` or `crate`".to_string()),
+            },
+        };
+        let (name, version) = match (spec.split_once('@'), version) {
+            (Some((name, range)), None) => (name.to_string(), Some(range.to_string())),
+            (Some(_), Some(_)) => {
+                return Err(format!("'{}' gives a version twice", spec));
+            }
+            (None, version) => (spec, version),
+        };
+        let version = version
+            .map(|range| {
+                VersionReq::parse(&range)
+                    .map_err(|e| format!("invalid version range '{}' for {}: {}", range, name, e))
+            })
+            .transpose()?;
+        Ok(Ban {
+            name,
+            version,
+            reason,
+        })
+    }
+}
+
+impl Ban {
+    pub fn matches(&self, name: &str, version: &Version) -> bool {
+        self.name == name && self.version.as_ref().is_none_or(|req| req.matches(version))
+    }
+}
+
+impl std::fmt::Display for Ban {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{}@{}", self.name, version),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Why `deny.toml` won't take an update target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DenyViolation {
+    /// A `[bans] deny` entry covers the target
+    Banned {
+        /// The entry, as `name` or `name@range`
+        ban: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// The target's license isn't one `[licenses]` accepts
+    License { license: String },
+}
+
+impl std::fmt::Display for DenyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DenyViolation::Banned {
+                ban,
+                reason: Some(reason),
+            } => write!(f, "banned by deny.toml ({}: {})", ban, reason),
+            DenyViolation::Banned { ban, reason: None } => {
+                write!(f, "banned by deny.toml ({})", ban)
+            }
+            DenyViolation::License { license } => {
+                write!(f, "{} is denied by deny.toml", license)
+            }
+        }
+    }
+}
+
+impl DenyPolicy {
+    /// The policy of the `deny.toml` cargo-deny would pick up for the
+    /// project in `dir`, if there is one
+    pub fn load(dir: &Path) -> crate::Result<Option<Self>> {
+        let Some(path) = find(dir) else {
+            return Ok(None);
+        };
+        let content =
+            fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+        Self::parse(&content)
+            .map(Some)
+            .context(format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn parse(content: &str) -> crate::Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bans.deny.is_empty() && self.licenses.is_empty()
+    }
+
+    /// What stops `name` from moving to `version`, published under
+    /// `license` when the registry records one
+    pub fn violation(
+        &self,
+        name: &str,
+        version: &Version,
+        license: Option<&str>,
+    ) -> Option<DenyViolation> {
+        if let Some(ban) = self.bans.deny.iter().find(|b| b.matches(name, version)) {
+            return Some(DenyViolation::Banned {
+                ban: ban.to_string(),
+                reason: ban.reason.clone(),
+            });
+        }
+        let license = license?;
+        (!self.licenses.permits(license)).then(|| DenyViolation::License {
+            license: license.to_string(),
+        })
+    }
+
+    /// A fingerprintable rendering, for cache keys
+    pub fn key(&self) -> String {
+        let bans: Vec<String> = self.bans.deny.iter().map(Ban::to_string).collect();
+        format!("bans={};{}", bans.join(","), self.licenses.key())
+    }
+}
+
+fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .flat_map(|dir| DENY_FILES.iter().map(move |file| dir.join(file)))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(v: &str) -> Version {
+        Version::parse(v).unwrap()
+    }
+
+    #[test]
+    fn test_bans_with_version_ranges() {
+        let policy = DenyPolicy::parse(
+            r#"
+            [graph]
+            all-features = true
+
+            [bans]
+            multiple-versions = "warn"
+            deny = [
+                "openssl",
+                { name = "time", version = "<0.2" },
+                { crate = "chrono@<0.4.20", reason = "RUSTSEC-2020-0159" },
+                "tokio@>=2",
+            ]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            policy.violation("openssl", &version("0.10.66"), None),
+            Some(DenyViolation::Banned {
+                ban: "openssl".to_string(),
+                reason: None
+            })
+        );
+        assert!(policy.violation("time", &version("0.1.45"), None).is_some());
+        assert_eq!(policy.violation("time", &version("0.3.36"), None), None);
+        assert_eq!(
+            policy.violation("chrono", &version("0.4.19"), None),
+            Some(DenyViolation::Banned {
+                ban: "chrono@<0.4.20".to_string(),
+                reason: Some("RUSTSEC-2020-0159".to_string())
+            })
+        );
+        assert_eq!(policy.violation("chrono", &version("0.4.38"), None), None);
+        assert!(policy.violation("tokio", &version("2.0.0"), None).is_some());
+        assert_eq!(policy.violation("tokio", &version("1.40.0"), None), None);
+
+        let invalid = DenyPolicy::parse("[bans]\ndeny = [{ name = \"time\", version = \"soon\" }]");
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_license_denials() {
+        let policy = DenyPolicy::parse(
+            r#"
+            [licenses]
+            allow = ["MIT", "Apache-2.0"]
+            confidence-threshold = 0.8
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            policy.violation("redis", &version("8.0.0"), Some("BUSL-1.1")),
+            Some(DenyViolation::License {
+                license: "BUSL-1.1".to_string()
+            })
+        );
+        assert_eq!(
+            policy.violation("serde", &version("1.0.210"), Some("MIT OR Apache-2.0")),
+            None
+        );
+        // A release without a recorded license can't be held to the list
+        assert_eq!(policy.violation("redis", &version("8.0.0"), None), None);
+    }
+}
@@ -1,16 +1,302 @@
 //! Dependency representation
 
+use crate::core::deny::DenyViolation;
+use crate::core::license::LicenseChange;
+use crate::core::policy::PolicyCheck;
+use crate::core::version::{is_newer, SkippedVersion, TargetSelection};
+use schemars::JsonSchema;
 use semver::Version;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Dependency {
     pub name: String,
     pub current_version: Version,
+    /// The requirement as written in Cargo.toml, e.g. "^1.0"
+    #[serde(default)]
+    pub requirement: Option<String>,
     pub latest_version: Option<Version>,
     pub is_direct: bool,
+    pub kind: DependencyKind,
+    #[serde(default)]
+    pub source: DependencySource,
+    /// Where the dependency is declared in Cargo.toml, if known
+    pub location: Option<Location>,
+    /// Newer releases passed over as update targets
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_versions: Vec<SkippedVersion>,
+    /// The current version has been yanked from the registry
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub yanked: bool,
+    /// Advisories against the current version that no clean published
+    /// release fixes, so there is no version to move to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unpatched_advisories: Vec<String>,
+    /// When the current version was published, as Unix seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub released_at: Option<u64>,
+    /// Login of the account that published the latest version, where the
+    /// registry records it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_published_by: Option<String>,
+    /// Standing against the organization's versions file, for crates it lists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PolicyCheck>,
+    /// What an artifact dependency builds, like `bin`, as declared
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifact: Vec<String>,
+    /// The workspace root whose `[workspace.dependencies]` the requirement
+    /// comes from, for `workspace = true` declarations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inherited_from: Option<PathBuf>,
+    /// The `cfg(...)` predicate or triple of the `[target]` table the
+    /// dependency is declared in, for platform-specific ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// The update target is published under another license than the
+    /// current version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license_change: Option<LicenseChange>,
+    /// The project's `deny.toml` won't take the update target
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deny: Option<DenyViolation>,
+    /// A `cargo sane snooze` hides the update for now
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub snoozed: bool,
+    /// Size in bytes of the current version's .crate file, where the
+    /// registry records it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_size: Option<u64>,
+    /// Size in bytes of the update target's .crate file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_size: Option<u64>,
+    /// The team `.cargo-sane/owners.toml` assigns the crate to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
 }
 
+/// Where a dependency's code comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencySource {
+    #[default]
+    Registry,
+    Git,
+    Path,
+}
+
+/// A git dependency. These can't be version-checked against the registry,
+/// but still belong in every report as part of the supply chain.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GitDependency {
+    pub name: String,
+    /// The package name, when the dependency renames it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    pub kind: DependencyKind,
+    pub source: DependencySource,
+    pub url: String,
+    pub reference: GitReference,
+    /// Version of the package as recorded in Cargo.lock
+    pub locked_version: Option<Version>,
+    /// Commit the package is locked to in Cargo.lock
+    pub locked_commit: Option<String>,
+    pub location: Option<Location>,
+    /// The `[target]` table the dependency is declared in, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+/// A path dependency whose package is also published to the registry
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PathDependency {
+    pub name: String,
+    /// The package name, which the registry knows it by
+    pub package: String,
+    pub path: PathBuf,
+    /// `[package] version` of the local copy
+    pub local_version: Version,
+    /// Newest non-yanked release on the registry
+    pub published_version: Version,
+    pub location: Option<Location>,
+}
+
+/// A `[dependencies]` entry that got no update check, or whose update is
+/// held back, and why. Failed lookups and held updates still have a row,
+/// which shows them as up to date.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SkippedDependency {
+    pub name: String,
+    pub reason: SkipCause,
+    /// Specifics, such as the registry's error or the version a policy holds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Why a dependency was skipped. The serialized names are stable reason
+/// codes for scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipCause {
+    /// Comes from a git repository, so there's no registry version to compare
+    Git,
+    /// Comes from a local path
+    Path,
+    /// One of the project's own crates
+    Internal,
+    /// Listed in the config's `ignore_crates`
+    Ignored,
+    /// The version requirement is missing or couldn't be parsed
+    ParseFailure,
+    /// Looking the crate up failed
+    RegistryError,
+    /// Not looked up, because the run reached its `--timeout`
+    TimedOut,
+    /// Checked, but the versions file holds it below the newest release
+    PolicyHeld,
+}
+
+impl SkippedDependency {
+    pub fn new(name: &str, reason: SkipCause) -> Self {
+        Self {
+            name: name.to_string(),
+            reason,
+            detail: None,
+        }
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+impl SkipCause {
+    /// The reason code, as serialized
+    pub fn code(self) -> &'static str {
+        match self {
+            SkipCause::Git => "git",
+            SkipCause::Path => "path",
+            SkipCause::Internal => "internal",
+            SkipCause::Ignored => "ignored",
+            SkipCause::ParseFailure => "parse-failure",
+            SkipCause::RegistryError => "registry-error",
+            SkipCause::TimedOut => "timed-out",
+            SkipCause::PolicyHeld => "policy-held",
+        }
+    }
+
+    /// The stage of the check that made the decision
+    pub fn decided_by(self) -> &'static str {
+        match self {
+            SkipCause::Git | SkipCause::Path | SkipCause::ParseFailure => "manifest declaration",
+            SkipCause::Internal => "internal crate discovery",
+            SkipCause::Ignored => "config (ignore_crates)",
+            SkipCause::RegistryError | SkipCause::TimedOut => "registry lookup",
+            SkipCause::PolicyHeld => "versions file",
+        }
+    }
+
+    /// A sentence on what it means for the dependency
+    pub fn describe(self) -> &'static str {
+        match self {
+            SkipCause::Git => "git dependency; listed with the git dependencies",
+            SkipCause::Path => "path dependency; compared with the registry only when published",
+            SkipCause::Internal => "the project's own crate, never looked up",
+            SkipCause::Ignored => "ignored by the config",
+            SkipCause::ParseFailure => "no usable version requirement",
+            SkipCause::RegistryError => "the registry lookup failed, so nothing newer is known",
+            SkipCause::TimedOut => "not looked up before the timeout, so nothing newer is known",
+            SkipCause::PolicyHeld => "held back by the versions file",
+        }
+    }
+}
+
+/// What a git dependency tracks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GitReference {
+    Branch(String),
+    Tag(String),
+    Rev(String),
+    /// No reference given, so the repository's default branch
+    DefaultBranch,
+}
+
+/// The manifest section a dependency is declared in
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    Normal,
+    Dev,
+    Build,
+}
+
+impl PathDependency {
+    /// The registry has moved past the local copy, which usually means the
+    /// checkout wasn't pulled
+    pub fn is_stale(&self) -> bool {
+        self.published_version > self.local_version
+    }
+}
+
+/// A git dependency whose package name is also on crates.io, and which
+/// trails the newest release there. Taking it for a fork of that crate is a
+/// heuristic: the shared name is all that ties the two together.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ForkedDependency {
+    pub name: String,
+    /// The package name, which crates.io knows it by
+    pub package: String,
+    pub url: String,
+    /// Version of the fork as recorded in Cargo.lock
+    pub based_on: Version,
+    /// Newest non-yanked release on crates.io
+    pub upstream: Version,
+    /// Advisories against `based_on` that `upstream` is patched for
+    pub fixed_upstream: Vec<String>,
+    pub location: Option<Location>,
+}
+
+impl ForkedDependency {
+    /// "fork is based on 1.2.0; upstream is at 1.5.3 (includes RUSTSEC-… fix)"
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "fork is based on {}; upstream is at {}",
+            self.based_on, self.upstream
+        );
+        if !self.fixed_upstream.is_empty() {
+            let noun = if self.fixed_upstream.len() == 1 {
+                "fix"
+            } else {
+                "fixes"
+            };
+            summary.push_str(&format!(
+                " (includes {} {})",
+                self.fixed_upstream.join(", "),
+                noun
+            ));
+        }
+        summary
+    }
+}
+
+/// A 1-based line/column position in a manifest file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Version of the rules [`Dependency::update_type`] classifies updates by:
+/// a higher major is a major update, else a higher minor a minor one, else
+/// a patch, with `0.x` releases compared the same way and build metadata
+/// ignored. Bumped whenever an update may land in a different class.
+pub const CLASSIFICATION_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum UpdateType {
     Patch,
@@ -24,22 +310,82 @@ impl Dependency {
         Self {
             name,
             current_version,
+            requirement: None,
             latest_version: None,
             is_direct,
+            kind: DependencyKind::Normal,
+            source: DependencySource::Registry,
+            location: None,
+            skipped_versions: Vec::new(),
+            yanked: false,
+            unpatched_advisories: Vec::new(),
+            released_at: None,
+            latest_published_by: None,
+            policy: None,
+            artifact: Vec::new(),
+            inherited_from: None,
+            target: None,
+            license_change: None,
+            deny: None,
+            snoozed: false,
+            current_size: None,
+            latest_size: None,
+            owner: None,
         }
     }
 
+    pub fn with_kind(mut self, kind: DependencyKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_requirement(mut self, requirement: &str) -> Self {
+        self.requirement = Some(requirement.to_string());
+        self
+    }
+
+    pub fn with_location(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        self
+    }
+
     pub fn with_latest(mut self, latest: Version) -> Self {
         self.latest_version = Some(latest);
         self
     }
 
+    /// Take the update target, and what was skipped, from a version selection
+    pub fn with_selection(mut self, selection: TargetSelection) -> Self {
+        self.latest_version = selection.target;
+        self.skipped_versions = selection.skipped;
+        self
+    }
+
+    /// Affected by an advisory no release fixes yet
+    pub fn is_unpatched(&self) -> bool {
+        !self.unpatched_advisories.is_empty()
+    }
+
+    /// Outside the blessed requirement of the versions file
+    pub fn is_off_policy(&self) -> bool {
+        self.policy.as_ref().is_some_and(PolicyCheck::is_off_policy)
+    }
+
+    /// e.g. "1.4.2 skipped: yanked; suggesting 1.4.1"
+    pub fn skip_note(&self) -> Option<String> {
+        TargetSelection {
+            target: self.latest_version.clone(),
+            skipped: self.skipped_versions.clone(),
+        }
+        .note()
+    }
+
     /// Determine the type of update available
     pub fn update_type(&self) -> UpdateType {
         match &self.latest_version {
             None => UpdateType::UpToDate,
             Some(latest) => {
-                if latest <= &self.current_version {
+                if !is_newer(latest, &self.current_version) {
                     UpdateType::UpToDate
                 } else if latest.major > self.current_version.major {
                     UpdateType::Major
@@ -56,4 +402,156 @@ impl Dependency {
     pub fn has_update(&self) -> bool {
         self.update_type() != UpdateType::UpToDate
     }
+
+    /// The .crate sizes of the current version and the update target, when
+    /// the registry records both
+    pub fn size_change(&self) -> Option<(u64, u64)> {
+        self.latest_version.as_ref()?;
+        Some((self.current_size?, self.latest_size?))
+    }
+}
+
+/// How much more, or less when negative, downloading the update targets of
+/// `deps` costs than their current versions, and how many of them that
+/// covers. Dependencies without both sizes are left out, and a crate several
+/// workspace members update counts once, as it downloads once.
+pub fn download_delta(deps: &[&Dependency]) -> Option<(i64, usize)> {
+    let mut seen = std::collections::HashSet::new();
+    let changes: Vec<(u64, u64)> = deps
+        .iter()
+        .filter(|d| seen.insert((&d.name, &d.current_version, &d.latest_version)))
+        .filter_map(|d| d.size_change())
+        .collect();
+    if changes.is_empty() {
+        return None;
+    }
+    let delta = changes
+        .iter()
+        .map(|&(from, to)| to as i64 - from as i64)
+        .sum();
+    Some((delta, changes.len()))
+}
+
+impl DependencyKind {
+    /// Name of the manifest table holding this kind of dependency
+    pub fn section(&self) -> &'static str {
+        match self {
+            DependencyKind::Normal => "dependencies",
+            DependencyKind::Dev => "dev-dependencies",
+            DependencyKind::Build => "build-dependencies",
+        }
+    }
+
+    /// Map a manifest table name (including the legacy underscore spellings) to a kind
+    pub fn from_section(section: &str) -> Option<Self> {
+        match section {
+            "dependencies" => Some(DependencyKind::Normal),
+            "dev-dependencies" | "dev_dependencies" => Some(DependencyKind::Dev),
+            "build-dependencies" | "build_dependencies" => Some(DependencyKind::Build),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for GitReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitReference::Branch(branch) => write!(f, "branch {}", branch),
+            GitReference::Tag(tag) => write!(f, "tag {}", tag),
+            GitReference::Rev(rev) => write!(f, "rev {}", rev),
+            GitReference::DefaultBranch => write!(f, "default branch"),
+        }
+    }
+}
+
+/// Fixtures shared by the unit tests of modules that work on dependencies
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::Dependency;
+    use semver::Version;
+
+    /// A registry dependency on `current` whose newest release is `latest`
+    pub(crate) fn dep(name: &str, current: &str, latest: &str) -> Dependency {
+        Dependency::new(name.to_string(), Version::parse(current).unwrap(), true)
+            .with_latest(Version::parse(latest).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update_type(current: &str, latest: &str) -> UpdateType {
+        test_support::dep("demo", current, latest).update_type()
+    }
+
+    #[test]
+    fn test_fork_summary() {
+        let mut fork = ForkedDependency {
+            name: "serde".to_string(),
+            package: "serde".to_string(),
+            url: "https://github.com/me/serde".to_string(),
+            based_on: Version::new(1, 2, 0),
+            upstream: Version::new(1, 5, 3),
+            fixed_upstream: Vec::new(),
+            location: None,
+        };
+        assert_eq!(
+            fork.summary(),
+            "fork is based on 1.2.0; upstream is at 1.5.3"
+        );
+        fork.fixed_upstream = vec!["RUSTSEC-1".to_string(), "RUSTSEC-2".to_string()];
+        assert_eq!(
+            fork.summary(),
+            "fork is based on 1.2.0; upstream is at 1.5.3 (includes RUSTSEC-1, RUSTSEC-2 fixes)"
+        );
+    }
+
+    #[test]
+    fn test_update_type_with_tags() {
+        for (current, latest, expected) in [
+            // Build metadata alone is never an update
+            ("1.2.3", "1.2.3+build.6", UpdateType::UpToDate),
+            ("1.2.3+build.5", "1.2.3", UpdateType::UpToDate),
+            ("0.16.1+1.7.1", "0.16.2+1.7.2", UpdateType::Patch),
+            // A prerelease comes before its release, never after
+            ("1.0.0-rc.1", "1.0.0", UpdateType::Patch),
+            ("1.0.0", "1.0.0-rc.1", UpdateType::UpToDate),
+            ("1.0.0-beta.1", "1.0.0-beta.2", UpdateType::Patch),
+            ("0.12.0-beta.1", "0.13.0-alpha.1", UpdateType::Minor),
+            ("1.9.0", "2.0.0-beta.1", UpdateType::Major),
+        ] {
+            assert_eq!(
+                update_type(current, latest),
+                expected,
+                "{} -> {}",
+                current,
+                latest
+            );
+        }
+    }
+
+    #[test]
+    fn test_download_delta() {
+        let sized = |name: &str, from: Option<u64>, to: Option<u64>| {
+            let mut dep = Dependency::new(name.to_string(), Version::new(1, 0, 0), true)
+                .with_latest(Version::new(1, 1, 0));
+            dep.current_size = from;
+            dep.latest_size = to;
+            dep
+        };
+        let grows = sized("tokio", Some(2_100_000), Some(4_700_000));
+        let shrinks = sized("serde", Some(80_000), Some(30_000));
+        let unknown = sized("syn", None, Some(250_000));
+        // Updated by a second workspace member
+        let again = grows.clone();
+
+        assert_eq!(grows.size_change(), Some((2_100_000, 4_700_000)));
+        assert_eq!(unknown.size_change(), None);
+        assert_eq!(
+            download_delta(&[&grows, &shrinks, &unknown, &again]),
+            Some((2_550_000, 2))
+        );
+        assert_eq!(download_delta(&[&unknown]), None);
+    }
 }
@@ -0,0 +1,208 @@
+//! License changes between releases
+//!
+//! crates.io records the SPDX expression each release was published under.
+//! A crate that relicenses between the version in use and its update target
+//! deserves a look before updating, and a closer one when the new license
+//! is one the project's `[licenses]` config won't take.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// The `[licenses]` table:
+///
+/// ```toml
+/// [licenses]
+/// allow = ["MIT", "Apache-2.0", "BSD-3-Clause"]
+/// deny = ["BUSL-1.1"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LicensePolicy {
+    /// License ids a release may be used under; empty allows any not denied
+    pub allow: Vec<String>,
+    /// License ids a release may never be used under
+    pub deny: Vec<String>,
+}
+
+/// A release's license differs from the one in use
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LicenseChange {
+    /// The license of the version in use
+    pub from: String,
+    /// The license of the update target
+    pub to: String,
+    /// The `[licenses]` config doesn't accept the new license
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub denied: bool,
+}
+
+impl LicensePolicy {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Whether a release under `expression` may be used: some choice the
+    /// expression offers has only licenses that aren't denied and, with an
+    /// allow list, are on it. An expression that doesn't parse is only
+    /// accepted without any lists.
+    pub fn permits(&self, expression: &str) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Some(choices) = choices(expression) else {
+            return false;
+        };
+        choices.iter().any(|licenses| {
+            licenses.iter().all(|license| {
+                let license = license.split(" WITH ").next().unwrap_or(license);
+                !self.deny.iter().any(|d| d == license)
+                    && (self.allow.is_empty() || self.allow.iter().any(|a| a == license))
+            })
+        })
+    }
+
+    /// A fingerprintable rendering, for cache keys
+    pub fn key(&self) -> String {
+        format!(
+            "allow={};deny={}",
+            self.allow.join(","),
+            self.deny.join(",")
+        )
+    }
+}
+
+/// The change from `from` to `to`, unless the two mean the same: the old
+/// `MIT/Apache-2.0` reads as `MIT OR Apache-2.0`, and the order of choices
+/// doesn't matter
+pub fn license_change(from: &str, to: &str, policy: &LicensePolicy) -> Option<LicenseChange> {
+    let same = match (choices(from), choices(to)) {
+        (Some(a), Some(b)) => a == b,
+        _ => from.trim() == to.trim(),
+    };
+    (!same).then(|| LicenseChange {
+        from: from.trim().to_string(),
+        to: to.trim().to_string(),
+        denied: !policy.permits(to),
+    })
+}
+
+/// The license combinations an expression lets a user pick from, each the
+/// set of licenses that all apply together, or `None` when it doesn't
+/// parse. `A OR (B AND C)` gives `{A}` and `{B, C}`.
+fn choices(expression: &str) -> Option<BTreeSet<BTreeSet<String>>> {
+    let spaced = expression
+        .replace('/', " OR ")
+        .replace('(', " ( ")
+        .replace(')', " ) ");
+    let tokens: Vec<&str> = spaced.split_whitespace().collect();
+    let mut position = 0;
+    let choices = parse_or(&tokens, &mut position)?;
+    (position == tokens.len()).then_some(choices)
+}
+
+type Choices = BTreeSet<BTreeSet<String>>;
+
+fn parse_or(tokens: &[&str], position: &mut usize) -> Option<Choices> {
+    let mut choices = parse_and(tokens, position)?;
+    while tokens.get(*position) == Some(&"OR") {
+        *position += 1;
+        choices.extend(parse_and(tokens, position)?);
+    }
+    Some(choices)
+}
+
+fn parse_and(tokens: &[&str], position: &mut usize) -> Option<Choices> {
+    let mut choices = parse_license(tokens, position)?;
+    while tokens.get(*position) == Some(&"AND") {
+        *position += 1;
+        let right = parse_license(tokens, position)?;
+        choices = choices
+            .iter()
+            .flat_map(|left| {
+                right
+                    .iter()
+                    .map(move |right| left.union(right).cloned().collect())
+            })
+            .collect();
+    }
+    Some(choices)
+}
+
+fn parse_license(tokens: &[&str], position: &mut usize) -> Option<Choices> {
+    let token = *tokens.get(*position)?;
+    *position += 1;
+    match token {
+        "(" => {
+            let inner = parse_or(tokens, position)?;
+            (tokens.get(*position) == Some(&")")).then_some(())?;
+            *position += 1;
+            Some(inner)
+        }
+        ")" | "OR" | "AND" | "WITH" => None,
+        license => {
+            let mut license = license.to_string();
+            if tokens.get(*position) == Some(&"WITH") {
+                let exception = tokens.get(*position + 1)?;
+                license = format!("{} WITH {}", license, exception);
+                *position += 2;
+            }
+            Some(BTreeSet::from([BTreeSet::from([license])]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allow: &[&str], deny: &[&str]) -> LicensePolicy {
+        LicensePolicy {
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            deny: deny.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_equivalent_expressions_are_no_change() {
+        let none = LicensePolicy::default();
+        assert_eq!(
+            license_change("MIT/Apache-2.0", "Apache-2.0 OR MIT", &none),
+            None
+        );
+        assert_eq!(license_change("MIT", " MIT ", &none), None);
+        assert_eq!(
+            license_change(
+                "(MIT OR Apache-2.0) AND Unicode-3.0",
+                "Unicode-3.0 AND (Apache-2.0 OR MIT)",
+                &none
+            ),
+            None
+        );
+
+        let change = license_change("MIT", "BUSL-1.1", &none).unwrap();
+        assert_eq!(
+            (change.from.as_str(), change.to.as_str()),
+            ("MIT", "BUSL-1.1")
+        );
+        assert!(!change.denied);
+    }
+
+    #[test]
+    fn test_policy_takes_any_acceptable_choice() {
+        let strict = policy(&["MIT", "Apache-2.0"], &["BUSL-1.1"]);
+        assert!(strict.permits("MIT OR BUSL-1.1"));
+        assert!(strict.permits("Apache-2.0 WITH LLVM-exception"));
+        assert!(!strict.permits("BUSL-1.1"));
+        assert!(!strict.permits("MIT AND GPL-3.0"));
+        assert!(!strict.permits("MIT OR"));
+
+        let deny_only = policy(&[], &["BUSL-1.1"]);
+        assert!(deny_only.permits("GPL-3.0"));
+        assert!(
+            license_change("MIT", "BUSL-1.1", &deny_only)
+                .unwrap()
+                .denied
+        );
+    }
+}
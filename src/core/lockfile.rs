@@ -0,0 +1,383 @@
+//! Cargo.lock parsing
+//!
+//! Cargo keeps one lockfile per workspace, next to the root manifest, so a
+//! member's lockfile is found by walking up to the workspace it belongs to.
+//! `--lockfile-path` points every command at a lockfile somewhere else, and
+//! `--no-workspace-discovery` keeps the search in the package's directory.
+
+use crate::core::manifest::Manifest;
+use crate::core::workspace::Workspace;
+use anyhow::{bail, Context, Result};
+use semver::Version;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub struct Lockfile {
+    pub path: PathBuf,
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: Version,
+    pub source: Option<String>,
+    pub checksum: Option<String>,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LockfileContent {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+/// A parsed `git+<url>?<reference>#<commit>` package source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitSource {
+    pub url: String,
+    /// The `branch=`, `tag=` or `rev=` query, if any
+    pub reference: Option<(String, String)>,
+    pub commit: Option<String>,
+}
+
+impl Lockfile {
+    /// Read the lockfile at `path` for the rest of the process instead of
+    /// looking for one
+    pub fn set_override(path: PathBuf) {
+        let _ = OVERRIDE.set(path);
+    }
+
+    /// Load the Cargo.lock cargo uses for `manifest`, if there is one
+    pub fn for_manifest(manifest: &Manifest) -> Result<Option<Self>> {
+        Self::resolve(manifest, OVERRIDE.get().map(PathBuf::as_path))
+    }
+
+    /// Load `explicit`, which must have been generated for `manifest`, or
+    /// else the lockfile at the root of the workspace `manifest` belongs to
+    pub fn resolve(manifest: &Manifest, explicit: Option<&Path>) -> Result<Option<Self>> {
+        if let Some(path) = explicit {
+            let lockfile = Self::from_path(path)?;
+            lockfile.ensure_locks(manifest)?;
+            return Ok(Some(lockfile));
+        }
+        let path = workspace_dir(manifest).join("Cargo.lock");
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::from_path(&path).map(Some)
+    }
+
+    /// Where the lockfile for `manifest` is, or would be written
+    pub fn path_for(manifest: &Manifest) -> PathBuf {
+        match OVERRIDE.get() {
+            Some(path) => path.clone(),
+            None => workspace_dir(manifest).join("Cargo.lock"),
+        }
+    }
+
+    /// Fail unless every local package `manifest` builds is locked here; a
+    /// lockfile from another project would otherwise read as all stale
+    fn ensure_locks(&self, manifest: &Manifest) -> Result<()> {
+        let expected: Vec<String> = match Workspace::load(manifest.clone())? {
+            Some(workspace) => workspace
+                .members
+                .iter()
+                .filter_map(|member| member.package_name().map(str::to_string))
+                .collect(),
+            None => manifest
+                .package_name()
+                .map(str::to_string)
+                .into_iter()
+                .collect(),
+        };
+        let missing: Vec<String> = expected
+            .into_iter()
+            .filter(|name| {
+                !self
+                    .packages
+                    .iter()
+                    .any(|p| p.source.is_none() && &p.name == name)
+            })
+            .collect();
+        if !missing.is_empty() {
+            bail!(
+                "{} is not the lockfile for {}: it doesn't lock {}",
+                self.path.display(),
+                manifest.path.display(),
+                missing.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Load a lockfile from a specific path
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .context(format!("Failed to read Cargo.lock at {}", path.display()))?;
+        Self::parse(path.to_path_buf(), &content)
+    }
+
+    /// Parse lockfile text that was read from `path`
+    pub fn parse(path: PathBuf, content: &str) -> Result<Self> {
+        let content: LockfileContent =
+            toml::from_str(content).context("Failed to parse Cargo.lock")?;
+
+        Ok(Self {
+            path,
+            packages: content.package,
+        })
+    }
+
+    /// All locked versions of a package, lowest first
+    pub fn packages_named(&self, name: &str) -> Vec<&LockedPackage> {
+        let mut packages: Vec<&LockedPackage> =
+            self.packages.iter().filter(|p| p.name == name).collect();
+        packages.sort_by(|a, b| a.version.cmp(&b.version));
+        packages
+    }
+
+    /// The locked packages `package` depends on. Its `dependencies` entries
+    /// are `name`, `name version` or `name version (source)`, qualified only
+    /// as far as needed to tell locked packages apart.
+    pub fn dependencies_of(&self, package: &LockedPackage) -> Vec<&LockedPackage> {
+        package
+            .dependencies
+            .iter()
+            .filter_map(|entry| {
+                let mut parts = entry.splitn(3, ' ');
+                let name = parts.next()?;
+                let version = parts.next().and_then(|v| Version::parse(v).ok());
+                let source = parts
+                    .next()
+                    .map(|s| s.trim_start_matches('(').trim_end_matches(')'));
+                self.packages.iter().find(|p| {
+                    p.name == name
+                        && version.as_ref().is_none_or(|v| &p.version == v)
+                        && source.is_none_or(|s| p.source.as_deref() == Some(s))
+                })
+            })
+            .collect()
+    }
+
+    /// The locked git package for a crate, if it comes from a git source
+    pub fn git_package(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages
+            .iter()
+            .find(|p| p.name == name && p.git_source().is_some())
+    }
+}
+
+/// The directory of the workspace root `manifest` belongs to. Like cargo,
+/// only the nearest workspace above a package is considered, and a package
+/// it doesn't list stays on its own.
+fn workspace_dir(manifest: &Manifest) -> PathBuf {
+    let root = match manifest.workspace_root() {
+        _ if Workspace::is_standalone() => manifest.path.clone(),
+        Some(root) => root.to_path_buf(),
+        None => match Workspace::root_of(manifest) {
+            Some(root) => root.path,
+            None => manifest.path.clone(),
+        },
+    };
+    root.parent().unwrap_or(Path::new(".")).to_path_buf()
+}
+
+/// How Cargo.lock names crates.io, through the git and the sparse index
+const CRATES_IO_SOURCES: [&str; 2] = [
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
+impl LockedPackage {
+    /// Downloaded from crates.io, rather than another registry
+    pub fn is_crates_io(&self) -> bool {
+        self.source
+            .as_deref()
+            .is_some_and(|s| CRATES_IO_SOURCES.contains(&s))
+    }
+
+    pub fn is_registry(&self) -> bool {
+        self.source
+            .as_deref()
+            .is_some_and(|s| s.starts_with("registry+") || s.starts_with("sparse+"))
+    }
+
+    /// The git repository this package was locked from, if any
+    pub fn git_source(&self) -> Option<GitSource> {
+        let source = self.source.as_deref()?.strip_prefix("git+")?;
+        let (location, commit) = match source.split_once('#') {
+            Some((location, commit)) => (location, Some(commit.to_string())),
+            None => (source, None),
+        };
+        let (url, reference) = match location.split_once('?') {
+            Some((url, query)) => (
+                url,
+                query
+                    .split_once('=')
+                    .map(|(k, v)| (k.to_string(), v.to_string())),
+            ),
+            None => (location, None),
+        };
+
+        Some(GitSource {
+            url: url.to_string(),
+            reference,
+            commit,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCKFILE: &str = r#"
+version = 3
+
+[[package]]
+name = "demo"
+version = "0.1.0"
+dependencies = [
+ "forked",
+ "serde 1.0.100",
+ "serde 1.0.200",
+]
+
+[[package]]
+name = "forked"
+version = "1.2.0"
+source = "git+https://github.com/ourorg/forked?branch=main#0123456789abcdef"
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "abc"
+
+[[package]]
+name = "serde"
+version = "1.0.100"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "def"
+"#;
+
+    #[test]
+    fn test_parse_packages() {
+        let lockfile = Lockfile::parse(PathBuf::from("Cargo.lock"), LOCKFILE).unwrap();
+        assert_eq!(lockfile.packages.len(), 4);
+
+        let serde = lockfile.packages_named("serde");
+        assert_eq!(serde.len(), 2);
+        assert_eq!(serde[0].version, Version::new(1, 0, 100));
+        assert!(serde[0].is_registry());
+        assert_eq!(serde[1].checksum.as_deref(), Some("abc"));
+
+        let demo = &lockfile.packages_named("demo")[0];
+        assert!(demo.source.is_none());
+        assert_eq!(demo.dependencies.len(), 3);
+
+        let versions: Vec<String> = lockfile
+            .dependencies_of(demo)
+            .iter()
+            .map(|p| format!("{} {}", p.name, p.version))
+            .collect();
+        assert_eq!(versions, ["forked 1.2.0", "serde 1.0.100", "serde 1.0.200"]);
+    }
+
+    #[test]
+    fn test_git_source() {
+        let lockfile = Lockfile::parse(PathBuf::from("Cargo.lock"), LOCKFILE).unwrap();
+        let forked = lockfile.git_package("forked").unwrap();
+        assert_eq!(forked.version, Version::new(1, 2, 0));
+        assert_eq!(
+            forked.git_source(),
+            Some(GitSource {
+                url: "https://github.com/ourorg/forked".to_string(),
+                reference: Some(("branch".to_string(), "main".to_string())),
+                commit: Some("0123456789abcdef".to_string()),
+            })
+        );
+        assert!(lockfile.git_package("serde").is_none());
+    }
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn package(name: &str) -> String {
+        format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\n", name)
+    }
+
+    fn locked(names: &[&str]) -> String {
+        names
+            .iter()
+            .map(|name| format!("[[package]]\nname = \"{}\"\nversion = \"0.1.0\"\n\n", name))
+            .collect()
+    }
+
+    #[test]
+    fn test_member_uses_the_workspace_lockfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\"]\n",
+        );
+        write(root, "Cargo.lock", &locked(&["app", "core"]));
+        write(root, "crates/app/Cargo.toml", &package("app"));
+        write(root, "crates/core/Cargo.toml", &package("core"));
+        // Not a member: cargo gives it a lockfile of its own
+        write(root, "tools/gen/Cargo.toml", &package("gen"));
+
+        let member = Manifest::from_path(&root.join("crates/app/Cargo.toml")).unwrap();
+        let lockfile = Lockfile::for_manifest(&member).unwrap().unwrap();
+        assert_eq!(
+            fs::canonicalize(&lockfile.path).unwrap(),
+            fs::canonicalize(root.join("Cargo.lock")).unwrap()
+        );
+
+        let outsider = Manifest::from_path(&root.join("tools/gen/Cargo.toml")).unwrap();
+        assert!(Lockfile::for_manifest(&outsider).unwrap().is_none());
+        assert_eq!(
+            Lockfile::path_for(&outsider),
+            root.join("tools/gen/Cargo.lock")
+        );
+    }
+
+    #[test]
+    fn test_explicit_lockfile_must_lock_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(root, "app/Cargo.toml", &package("app"));
+        write(root, "locks/app.lock", &locked(&["app", "serde"]));
+        write(root, "locks/other.lock", &locked(&["other", "serde"]));
+
+        let manifest = Manifest::from_path(&root.join("app/Cargo.toml")).unwrap();
+        assert!(Lockfile::resolve(&manifest, None).unwrap().is_none());
+
+        let lockfile = Lockfile::resolve(&manifest, Some(&root.join("locks/app.lock")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(lockfile.packages.len(), 2);
+
+        let error = Lockfile::resolve(&manifest, Some(&root.join("locks/other.lock")))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("other.lock is not the lockfile for"),
+            "{}",
+            error
+        );
+        assert!(error.ends_with("it doesn't lock app"), "{}", error);
+    }
+}
@@ -1,15 +1,71 @@
 //! Cargo.toml manifest handling
 
+use crate::core::dependency::{DependencyKind, GitReference, Location};
+use crate::core::version::parse_rust_version;
+use crate::core::workspace::Workspace;
 use anyhow::{Context, Result};
-use serde::Deserialize;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+/// The UTF-8 byte order mark some editors and generators prepend
+const BOM: &str = "\u{feff}";
+
+/// Refuse manifests larger than this before reading them. Real ones are a
+/// few kilobytes; a bigger file means the path names something else, like
+/// an archive.
+pub const MAX_MANIFEST_BYTES: u64 = 4 * 1024 * 1024;
+
 #[derive(Debug, Clone)]
 pub struct Manifest {
     pub path: PathBuf,
     pub content: ManifestContent,
+    locations: HashMap<(DependencySection, String), Location>,
+    /// Where each `[workspace.dependencies]` entry of this manifest is
+    workspace_locations: HashMap<String, Location>,
+    /// The workspace root manifest and its `[workspace.dependencies]`, which
+    /// `workspace = true` declarations take their requirement from
+    inherited: Option<(PathBuf, HashMap<String, DependencySpec>)>,
+}
+
+/// Manifest text as stored on disk: decoded, with any byte order mark split
+/// off so parsing and line-anchored edits see what Cargo sees
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestText {
+    pub content: String,
+    /// The file started with a byte order mark, to be written back as found
+    pub bom: bool,
+}
+
+/// A requirement a manifest is answerable for, with any `workspace = true`
+/// resolved: what analyses of requirements go through
+#[derive(Debug, Clone)]
+pub struct ResolvedDeclaration {
+    pub section: DependencySection,
+    pub name: String,
+    pub spec: DependencySpec,
+    /// 1-based line of the declaration, or of the table entry
+    pub line: Option<usize>,
+    /// An entry of this manifest's `[workspace.dependencies]` rather than a
+    /// declaration in `section`
+    pub workspace: bool,
+    /// The workspace root whose `[workspace.dependencies]` the requirement
+    /// comes from, for `workspace = true` declarations of a member
+    pub inherited_from: Option<PathBuf>,
+}
+
+/// A manifest table that declares dependencies, e.g. `[dependencies]` or
+/// `[target.'cfg(unix)'.dev-dependencies]`
+#[derive(
+    Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+pub struct DependencySection {
+    pub kind: DependencyKind,
+    pub target: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -20,35 +76,108 @@ pub struct ManifestContent {
     pub dev_dependencies: Option<HashMap<String, DependencySpec>>,
     #[serde(rename = "build-dependencies")]
     pub build_dependencies: Option<HashMap<String, DependencySpec>>,
+    pub target: Option<HashMap<String, TargetDependencies>>,
+    pub workspace: Option<WorkspaceTable>,
+    /// `[features]`, each feature with what it enables
+    #[serde(default)]
+    pub features: HashMap<String, Vec<String>>,
+}
+
+/// The `[workspace]` table of a workspace root manifest
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WorkspaceTable {
+    #[serde(default)]
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// `[workspace.package]`, the keys members may inherit
+    #[serde(default)]
+    pub package: Option<WorkspacePackage>,
+    /// `[workspace.dependencies]`, which members inherit with
+    /// `workspace = true`
+    #[serde(default)]
+    pub dependencies: HashMap<String, DependencySpec>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WorkspacePackage {
+    #[serde(default)]
+    pub repository: Option<String>,
+    #[serde(default, rename = "rust-version")]
+    pub rust_version: Option<String>,
+}
+
+/// Dependency tables nested under `[target.<triple-or-cfg>]`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TargetDependencies {
+    pub dependencies: Option<HashMap<String, DependencySpec>>,
+    #[serde(rename = "dev-dependencies")]
+    pub dev_dependencies: Option<HashMap<String, DependencySpec>>,
+    #[serde(rename = "build-dependencies")]
+    pub build_dependencies: Option<HashMap<String, DependencySpec>>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Package {
     pub name: String,
-    pub version: String,
+    /// A string, or `{ workspace = true }` in workspace members
+    #[serde(default)]
+    pub version: Option<toml::Value>,
+    /// `false`, or a list of registries the package may be published to
+    #[serde(default)]
+    pub publish: Option<toml::Value>,
+    /// A string, or `{ workspace = true }` in workspace members
+    #[serde(default)]
+    pub repository: Option<toml::Value>,
+    /// A string, or `{ workspace = true }` in workspace members
+    #[serde(default, rename = "rust-version")]
+    pub rust_version: Option<toml::Value>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+// A manifest holds a few hundred specs at most, so boxing buys nothing
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum DependencySpec {
     Simple(String),
     Detailed(DetailedDependency),
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DetailedDependency {
     pub version: Option<String>,
+    /// `workspace = true`: the rest comes from the root's
+    /// `[workspace.dependencies]`
+    pub workspace: Option<bool>,
     pub git: Option<String>,
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    pub rev: Option<String>,
     pub path: Option<String>,
     pub features: Option<Vec<String>>,
     pub optional: Option<bool>,
     #[serde(rename = "default-features")]
     pub default_features: Option<bool>,
+    /// The binaries or libraries an artifact dependency builds, like
+    /// `"bin"` or `["bin:tool", "cdylib"]`
+    pub artifact: Option<Artifact>,
+    /// Whether an artifact dependency's library is linked as well
+    pub lib: Option<bool>,
+    /// The target an artifact dependency is built for
+    pub target: Option<String>,
     // Ignore other fields
     #[serde(flatten)]
     pub other: Option<HashMap<String, toml::Value>>,
 }
 
+/// The `artifact` key, one kind or a list of them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Artifact {
+    One(String),
+    Many(Vec<String>),
+}
+
 impl Manifest {
     /// Find Cargo.toml in current directory or specified path
     pub fn find(path: Option<String>) -> Result<Self> {
@@ -60,7 +189,49 @@ impl Manifest {
             current.join("Cargo.toml")
         };
 
-        Self::from_path(&manifest_path)
+        let manifest_path = Self::resolve(&manifest_path)?;
+        let manifest = Self::from_path(&manifest_path)?;
+        Ok(match Workspace::root_of(&manifest) {
+            Some(root) => manifest.with_workspace_root(&root),
+            None => manifest,
+        })
+    }
+
+    /// Validate a manifest path before anything reads it or runs cargo in
+    /// its directory: a directory means its Cargo.toml, any other file name
+    /// is refused, and the result is canonical, with `..` and symlinks
+    /// resolved, and no larger than [`MAX_MANIFEST_BYTES`]
+    pub fn resolve(path: &Path) -> Result<PathBuf> {
+        let path = if path.is_dir() {
+            path.join("Cargo.toml")
+        } else {
+            path.to_path_buf()
+        };
+        if path.file_name().is_none_or(|name| name != "Cargo.toml") {
+            anyhow::bail!(
+                "The manifest path must be a Cargo.toml or a directory containing one: {}",
+                path.display()
+            );
+        }
+        let Ok(canonical) = path.canonicalize() else {
+            anyhow::bail!("Cargo.toml not found at: {}", path.display());
+        };
+
+        let metadata =
+            fs::metadata(&canonical).context(format!("Failed to read {}", canonical.display()))?;
+        if !metadata.is_file() {
+            anyhow::bail!("{} is not a file", canonical.display());
+        }
+        if metadata.len() > MAX_MANIFEST_BYTES {
+            anyhow::bail!(
+                "{} is {} bytes, over the {} bytes a Cargo.toml may be; \
+                 check that the manifest path names the right file",
+                canonical.display(),
+                metadata.len(),
+                MAX_MANIFEST_BYTES
+            );
+        }
+        Ok(canonical)
     }
 
     /// Load manifest from specific path
@@ -69,35 +240,616 @@ impl Manifest {
             anyhow::bail!("Cargo.toml not found at: {}", path.display());
         }
 
-        let content_str = fs::read_to_string(path)
-            .context(format!("Failed to read Cargo.toml at {}", path.display()))?;
+        let text = ManifestText::read(path)?;
+        Self::parse(path.to_path_buf(), &text.content)
+            .context(format!("Invalid manifest {}", path.display()))
+    }
 
+    /// Parse manifest text that was read from `path`
+    pub fn parse(path: PathBuf, content_str: &str) -> Result<Self> {
+        let content_str = content_str.strip_prefix(BOM).unwrap_or(content_str);
         let content: ManifestContent =
-            toml::from_str(&content_str).context("Failed to parse Cargo.toml")?;
+            toml::from_str(content_str).context("Failed to parse Cargo.toml")?;
 
+        let inherited = content
+            .workspace
+            .as_ref()
+            .map(|workspace| (path.clone(), workspace.dependencies.clone()));
+        let (locations, workspace_locations) = scan_locations(content_str);
         Ok(Self {
-            path: path.to_path_buf(),
+            path,
             content,
+            locations,
+            workspace_locations,
+            inherited,
         })
     }
 
-    /// Get all dependencies (direct only)
-    pub fn get_dependencies(&self) -> Vec<(String, DependencySpec)> {
+    /// The same manifest as a member of the workspace rooted at `root`,
+    /// inheriting its `[workspace.dependencies]`
+    pub fn with_workspace_root(mut self, root: &Manifest) -> Self {
+        if let Some(workspace) = &root.content.workspace {
+            self.inherited = Some((root.path.clone(), workspace.dependencies.clone()));
+        }
+        self
+    }
+
+    /// The root manifest `workspace = true` declarations inherit from, once
+    /// known: this one when it has a `[workspace]` table
+    pub fn workspace_root(&self) -> Option<&Path> {
+        self.inherited.as_ref().map(|(root, _)| root.as_path())
+    }
+
+    /// What a `workspace = true` declaration of `name` builds with: the
+    /// root's `[workspace.dependencies]` entry, with the features the
+    /// declaration adds and its `optional`. `None` for declarations that
+    /// don't inherit, or whose entry isn't known.
+    pub fn resolve_inherited(&self, name: &str, spec: &DependencySpec) -> Option<DependencySpec> {
+        if !spec.inherits_workspace() {
+            return None;
+        }
+        let (_, dependencies) = self.inherited.as_ref()?;
+        let mut resolved = match dependencies.get(name)? {
+            DependencySpec::Simple(version) => DetailedDependency {
+                version: Some(version.clone()),
+                ..DetailedDependency::default()
+            },
+            DependencySpec::Detailed(entry) => entry.clone(),
+        };
+        let mut features = resolved.features.take().unwrap_or_default();
+        for feature in spec.features() {
+            if !features.contains(feature) {
+                features.push(feature.clone());
+            }
+        }
+        resolved.features = (!features.is_empty()).then_some(features);
+        resolved.optional = spec.is_optional().then_some(true);
+        resolved.workspace = None;
+        Some(DependencySpec::Detailed(resolved))
+    }
+
+    /// Get all dependencies (direct only): `[dependencies]` and every
+    /// `[target.<triple-or-cfg>]` dependency table, each with the section
+    /// it's declared in. A crate declared for several targets comes once
+    /// per section, next to each other.
+    pub fn get_dependencies(&self) -> Vec<(String, DependencySpec, DependencySection)> {
         let mut deps = Vec::new();
 
         if let Some(ref dependencies) = self.content.dependencies {
             for (name, spec) in dependencies {
-                deps.push((name.clone(), spec.clone()));
+                let section = DependencySection::new(DependencyKind::Normal);
+                deps.push((name.clone(), spec.clone(), section));
+            }
+        }
+        for (target, tables) in self.content.target.iter().flatten() {
+            let kinds = [
+                DependencyKind::Normal,
+                DependencyKind::Dev,
+                DependencyKind::Build,
+            ];
+            let tables = [
+                &tables.dependencies,
+                &tables.dev_dependencies,
+                &tables.build_dependencies,
+            ];
+            for (kind, table) in kinds.into_iter().zip(tables) {
+                for (name, spec) in table.iter().flatten() {
+                    let section = DependencySection {
+                        kind,
+                        target: Some(target.clone()),
+                    };
+                    deps.push((name.clone(), spec.clone(), section));
+                }
             }
         }
 
+        // Keep output stable regardless of hash map ordering
+        deps.sort_by(|a, b| (&a.0, &a.2).cmp(&(&b.0, &b.2)));
         deps
     }
 
+    /// Whether this is a virtual workspace manifest: a `[workspace]` with
+    /// no `[package]`, so nothing of its own to analyze
+    pub fn is_virtual(&self) -> bool {
+        self.content.workspace.is_some() && self.content.package.is_none()
+    }
+
+    /// The member manifests of the workspace rooted here, from the
+    /// `members` and `exclude` globs; none without a `[workspace]` table
+    pub fn workspace_members(&self) -> Result<Vec<Manifest>> {
+        Ok(Workspace::load(self.clone())?
+            .map(|workspace| workspace.members)
+            .unwrap_or_default())
+    }
+
     /// Get package name
     pub fn package_name(&self) -> Option<&str> {
         self.content.package.as_ref().map(|p| p.name.as_str())
     }
+
+    /// The `[package] version`, unless it's missing, inherited from the
+    /// workspace or not valid semver
+    pub fn package_version(&self) -> Option<Version> {
+        let version = self.content.package.as_ref()?.version.as_ref()?;
+        Version::parse(version.as_str()?).ok()
+    }
+
+    /// The manifests of `[dependencies]` given by path, with the dependency
+    /// name. Ones that can't be loaded are left out.
+    pub fn path_dependencies(&self) -> Vec<(String, Manifest)> {
+        let root = self.path.parent().unwrap_or(Path::new("."));
+        let mut deps: Vec<(String, Manifest)> = self
+            .get_dependencies()
+            .into_iter()
+            .filter_map(|(name, spec, _)| {
+                let path = root.join(spec.path()?).join("Cargo.toml");
+                Some((name, Manifest::from_path(&path).ok()?))
+            })
+            .collect();
+        deps.dedup_by(|a, b| a.0 == b.0);
+        deps
+    }
+
+    /// Whether the package may be published, i.e. `publish` isn't `false` or
+    /// an empty registry list. Manifests without a `[package]` never are.
+    pub fn is_publishable(&self) -> bool {
+        let Some(package) = &self.content.package else {
+            return false;
+        };
+        match &package.publish {
+            Some(toml::Value::Boolean(publish)) => *publish,
+            Some(toml::Value::Array(registries)) => !registries.is_empty(),
+            _ => true,
+        }
+    }
+
+    /// The `[package] repository`, falling back to the one under
+    /// `[workspace.package]`
+    pub fn repository(&self) -> Option<&str> {
+        let own = self
+            .content
+            .package
+            .as_ref()
+            .and_then(|p| p.repository.as_ref()?.as_str());
+        own.or_else(|| {
+            let workspace = self.content.workspace.as_ref()?.package.as_ref()?;
+            workspace.repository.as_deref()
+        })
+    }
+
+    /// The `[package] rust-version`, falling back to the one under
+    /// `[workspace.package]`, as a version: "1.70" reads as 1.70.0
+    pub fn rust_version(&self) -> Option<Version> {
+        let own = self
+            .content
+            .package
+            .as_ref()
+            .and_then(|p| p.rust_version.as_ref()?.as_str());
+        let text = own.or_else(|| {
+            let workspace = self.content.workspace.as_ref()?.package.as_ref()?;
+            workspace.rust_version.as_deref()
+        })?;
+        parse_rust_version(text)
+    }
+
+    /// `rust-version.workspace = true`: the member takes the workspace
+    /// root's `[workspace.package] rust-version`
+    pub fn inherits_rust_version(&self) -> bool {
+        self.content
+            .package
+            .as_ref()
+            .and_then(|p| p.rust_version.as_ref()?.get("workspace")?.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// Every dependency declaration in the manifest, across the top-level and
+    /// target-specific tables, ordered by section and then name
+    pub fn declarations(&self) -> Vec<(DependencySection, String, DependencySpec)> {
+        let mut declarations = Vec::new();
+        let content = &self.content;
+
+        let mut collect =
+            |target: Option<&String>, tables: [&Option<HashMap<String, DependencySpec>>; 3]| {
+                let kinds = [
+                    DependencyKind::Normal,
+                    DependencyKind::Dev,
+                    DependencyKind::Build,
+                ];
+                for (kind, table) in kinds.into_iter().zip(tables) {
+                    for (name, spec) in table.iter().flatten() {
+                        let section = DependencySection {
+                            kind,
+                            target: target.cloned(),
+                        };
+                        declarations.push((section, name.clone(), spec.clone()));
+                    }
+                }
+            };
+
+        collect(
+            None,
+            [
+                &content.dependencies,
+                &content.dev_dependencies,
+                &content.build_dependencies,
+            ],
+        );
+        for (target, tables) in content.target.iter().flatten() {
+            collect(
+                Some(target),
+                [
+                    &tables.dependencies,
+                    &tables.dev_dependencies,
+                    &tables.build_dependencies,
+                ],
+            );
+        }
+
+        declarations.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        declarations
+    }
+
+    /// Every declaration with `workspace = true` resolved against the root,
+    /// then this manifest's own `[workspace.dependencies]` entries by name.
+    /// At the root, inheriting declarations are left to the entry they
+    /// inherit, and declarations whose entry isn't known are left out.
+    pub fn resolved_declarations(&self) -> Vec<ResolvedDeclaration> {
+        let mut resolved = Vec::new();
+        for (section, name, spec) in self.declarations() {
+            let line = self.location_in(&name, &section).map(|(line, _)| line);
+            let mut declaration = ResolvedDeclaration {
+                section,
+                name,
+                spec,
+                line,
+                workspace: false,
+                inherited_from: None,
+            };
+            if declaration.spec.inherits_workspace() {
+                if self.content.workspace.is_some() {
+                    continue;
+                }
+                let Some(spec) = self.resolve_inherited(&declaration.name, &declaration.spec)
+                else {
+                    continue;
+                };
+                declaration.spec = spec;
+                declaration.inherited_from = self.workspace_root().map(Path::to_path_buf);
+            }
+            resolved.push(declaration);
+        }
+
+        let mut entries: Vec<_> = self
+            .content
+            .workspace
+            .iter()
+            .flat_map(|workspace| &workspace.dependencies)
+            .collect();
+        entries.sort_by_key(|(name, _)| *name);
+        for (name, spec) in entries {
+            resolved.push(ResolvedDeclaration {
+                section: DependencySection::new(DependencyKind::Normal),
+                name: name.clone(),
+                spec: spec.clone(),
+                line: self.workspace_location_of(name).map(|(line, _)| line),
+                workspace: true,
+                inherited_from: None,
+            });
+        }
+        resolved
+    }
+
+    /// Line and column (both 1-based) where a dependency is declared
+    pub fn location_of(&self, name: &str, kind: DependencyKind) -> Option<(usize, usize)> {
+        self.location_in(name, &DependencySection::new(kind))
+    }
+
+    /// Line and column (both 1-based) of a declaration in a specific section
+    pub fn location_in(&self, name: &str, section: &DependencySection) -> Option<(usize, usize)> {
+        self.locations
+            .get(&(section.clone(), name.to_string()))
+            .map(|loc| (loc.line, loc.column))
+    }
+
+    /// Line and column (both 1-based) of an entry in this manifest's
+    /// `[workspace.dependencies]`
+    pub fn workspace_location_of(&self, name: &str) -> Option<(usize, usize)> {
+        self.workspace_locations
+            .get(name)
+            .map(|loc| (loc.line, loc.column))
+    }
+}
+
+impl ManifestText {
+    pub fn read(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).context(format!("Failed to read {}", path.display()))?;
+        Self::decode(bytes).context(format!("Failed to read {}", path.display()))
+    }
+
+    /// Decode raw manifest bytes. Invalid UTF-8 is reported with its
+    /// position, since Cargo rejects it too.
+    pub fn decode(bytes: Vec<u8>) -> Result<Self> {
+        let content = String::from_utf8(bytes).map_err(|e| {
+            let bytes = e.as_bytes();
+            let offset = e.utf8_error().valid_up_to();
+            let line = bytes[..offset].iter().filter(|&&b| b == b'\n').count() + 1;
+            let line_start = bytes[..offset]
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .map_or(0, |i| i + 1);
+            anyhow::anyhow!(
+                "invalid UTF-8 byte 0x{:02X} at offset {} (line {}, column {}); \
+                 Cargo manifests must be UTF-8, so re-save the file with that encoding \
+                 (a Latin-1 comment is a common culprit)",
+                bytes[offset],
+                offset,
+                line,
+                offset - line_start + 1
+            )
+        })?;
+
+        Ok(match content.strip_prefix(BOM) {
+            Some(rest) => Self {
+                content: rest.to_string(),
+                bom: true,
+            },
+            None => Self {
+                content,
+                bom: false,
+            },
+        })
+    }
+
+    /// The text to write back, with the byte order mark restored
+    pub fn encode(&self) -> String {
+        if self.bom {
+            format!("{}{}", BOM, self.content)
+        } else {
+            self.content.clone()
+        }
+    }
+}
+
+impl DependencySection {
+    /// A top-level (non target-specific) section
+    pub fn new(kind: DependencyKind) -> Self {
+        Self { kind, target: None }
+    }
+
+    /// Parse a table header path such as `["target", "cfg(unix)", "dependencies"]`
+    fn from_path(path: &[String]) -> Option<Self> {
+        match path {
+            [table] => DependencyKind::from_section(table).map(Self::new),
+            [target, predicate, table] if target == "target" => DependencyKind::from_section(table)
+                .map(|kind| Self {
+                    kind,
+                    target: Some(predicate.clone()),
+                }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DependencySection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.target {
+            None => write!(f, "{}", self.kind.section()),
+            Some(target) => {
+                let bare = target
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                if bare {
+                    write!(f, "target.{}.{}", target, self.kind.section())
+                } else {
+                    write!(f, "target.'{}'.{}", target, self.kind.section())
+                }
+            }
+        }
+    }
+}
+
+/// Find the declaration position of every dependency key in raw manifest text.
+///
+/// The serde TOML parser throws spans away, so this is a second, deliberately
+/// shallow pass that only understands table headers and keys. It recognizes:
+///   serde = "1.0"                  (simple)
+///   tokio = { version = "1" }      (inline table)
+///   [dependencies.regex]           (table section)
+///   clap.version = "4"             (dotted key)
+fn scan_locations(
+    text: &str,
+) -> (
+    HashMap<(DependencySection, String), Location>,
+    HashMap<String, Location>,
+) {
+    let mut locations = HashMap::new();
+    let mut workspace_locations = HashMap::new();
+    // The dependency table we are currently inside, if any
+    let mut section: Option<DependencySection> = None;
+    let mut in_workspace = false;
+    let mut in_root = true;
+    let mut open_string = None;
+
+    for (idx, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let location = Location {
+            line: idx + 1,
+            column: line.len() - trimmed.len() + 1,
+        };
+
+        // Skip the body of multi-line strings, which may contain anything
+        let inside = open_string.is_some();
+        open_string = open_string_after(line, open_string);
+        if inside {
+            continue;
+        }
+
+        if let Some(header) = trimmed.strip_prefix('[') {
+            in_root = false;
+            section = None;
+            in_workspace = false;
+
+            // Arrays of tables ([[bin]] etc.) never hold dependencies
+            if header.starts_with('[') {
+                continue;
+            }
+
+            if let Some((path, rest)) = parse_key_path(header) {
+                if !rest.starts_with(']') {
+                    continue;
+                }
+                section = DependencySection::from_path(&path);
+                // [workspace.dependencies] or [workspace.dependencies.tokio]
+                match path.as_slice() {
+                    [workspace, dependencies]
+                        if workspace == "workspace" && dependencies == "dependencies" =>
+                    {
+                        in_workspace = true;
+                    }
+                    [workspace, dependencies, name]
+                        if workspace == "workspace" && dependencies == "dependencies" =>
+                    {
+                        workspace_locations.entry(name.clone()).or_insert(location);
+                    }
+                    _ => {}
+                }
+                // [dependencies.regex] or [target.'cfg(unix)'.dependencies.libc]
+                if section.is_none() {
+                    if let Some((name, table)) = path.split_last() {
+                        if let Some(declared) = DependencySection::from_path(table) {
+                            locations
+                                .entry((declared, name.clone()))
+                                .or_insert(location);
+                        }
+                    }
+                }
+            }
+            continue;
+        }
+
+        let Some((path, rest)) = parse_key_path(trimmed) else {
+            continue;
+        };
+        if !rest.starts_with('=') {
+            continue;
+        }
+
+        if in_workspace {
+            if let Some(name) = path.first() {
+                workspace_locations.entry(name.clone()).or_insert(location);
+            }
+            continue;
+        }
+
+        let declared = match (&section, path.as_slice()) {
+            (Some(section), [name, ..]) => Some((section.clone(), name)),
+            (None, [table, name, ..]) if in_root => {
+                DependencyKind::from_section(table).map(|kind| (DependencySection::new(kind), name))
+            }
+            _ => None,
+        };
+
+        if let Some((section, name)) = declared {
+            locations.entry((section, name.clone())).or_insert(location);
+        }
+    }
+
+    (locations, workspace_locations)
+}
+
+/// The multi-line string (`\"\"\"` or `'''`) still open at the end of `line`,
+/// given the one open at its start. Single-line strings and comments are
+/// skipped over, so delimiters quoted inside them don't count.
+fn open_string_after(line: &str, open: Option<&'static str>) -> Option<&'static str> {
+    let mut open = open;
+    let mut rest = line;
+    loop {
+        if let Some(delimiter) = open {
+            // Basic strings escape with backslashes; literal strings can't
+            let end = if delimiter == "\"\"\"" {
+                find_unescaped(rest, delimiter)
+            } else {
+                rest.find(delimiter)
+            };
+            match end {
+                Some(end) => {
+                    rest = &rest[end + delimiter.len()..];
+                    open = None;
+                }
+                None => return open,
+            }
+        }
+
+        let start = rest.find(['"', '\'', '#'])?;
+        rest = &rest[start..];
+        if rest.starts_with('#') {
+            return None;
+        }
+        if let Some(delimiter) = ["\"\"\"", "'''"].into_iter().find(|d| rest.starts_with(d)) {
+            open = Some(delimiter);
+            rest = &rest[delimiter.len()..];
+            continue;
+        }
+        let quote = &rest[..1];
+        let body = &rest[1..];
+        let end = if quote == "\"" {
+            find_unescaped(body, quote)
+        } else {
+            body.find(quote)
+        };
+        match end {
+            Some(end) => rest = &body[end + 1..],
+            // Unterminated: a syntax error the TOML parser reports
+            None => return None,
+        }
+    }
+}
+
+/// Where `delimiter` first appears in `s` without a backslash escaping it
+fn find_unescaped(s: &str, delimiter: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if s[i..].starts_with(delimiter) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Parse a (possibly dotted, possibly quoted) TOML key from the start of `s`,
+/// returning its segments and whatever follows the key.
+fn parse_key_path(s: &str) -> Option<(Vec<String>, &str)> {
+    let mut segments = Vec::new();
+    let mut rest = s.trim_start();
+
+    loop {
+        let (segment, after) = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            (&quoted[..end], &quoted[end + 1..])
+        } else if let Some(quoted) = rest.strip_prefix('\'') {
+            let end = quoted.find('\'')?;
+            (&quoted[..end], &quoted[end + 1..])
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return None;
+            }
+            (&rest[..end], &rest[end..])
+        };
+
+        segments.push(segment.to_string());
+        let after = after.trim_start();
+        match after.strip_prefix('.') {
+            Some(next) => rest = next.trim_start(),
+            None => return Some((segments, after)),
+        }
+    }
 }
 
 impl DependencySpec {
@@ -117,6 +869,24 @@ impl DependencySpec {
         }
     }
 
+    /// The repository and reference of a git dependency
+    pub fn git(&self) -> Option<(&str, GitReference)> {
+        let DependencySpec::Detailed(d) = self else {
+            return None;
+        };
+        let url = d.git.as_deref()?;
+        let reference = if let Some(rev) = &d.rev {
+            GitReference::Rev(rev.clone())
+        } else if let Some(tag) = &d.tag {
+            GitReference::Tag(tag.clone())
+        } else if let Some(branch) = &d.branch {
+            GitReference::Branch(branch.clone())
+        } else {
+            GitReference::DefaultBranch
+        };
+        Some((url, reference))
+    }
+
     /// Check if this is a path dependency
     pub fn is_path(&self) -> bool {
         match self {
@@ -125,8 +895,554 @@ impl DependencySpec {
         }
     }
 
+    /// The directory of a path dependency, as written
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            DependencySpec::Simple(_) => None,
+            DependencySpec::Detailed(d) => d.path.as_deref(),
+        }
+    }
+
+    /// The package name of a renamed dependency, `package = "..."`
+    pub fn package(&self) -> Option<&str> {
+        match self {
+            DependencySpec::Simple(_) => None,
+            DependencySpec::Detailed(d) => d.other.as_ref()?.get("package")?.as_str(),
+        }
+    }
+
+    /// The artifacts of an artifact dependency, as written; empty for a
+    /// regular one
+    pub fn artifacts(&self) -> &[String] {
+        match self {
+            DependencySpec::Detailed(DetailedDependency {
+                artifact: Some(Artifact::One(kind)),
+                ..
+            }) => std::slice::from_ref(kind),
+            DependencySpec::Detailed(DetailedDependency {
+                artifact: Some(Artifact::Many(kinds)),
+                ..
+            }) => kinds,
+            _ => &[],
+        }
+    }
+
+    /// An artifact dependency without `lib = true`, which source code can't
+    /// import
+    pub fn is_artifact_only(&self) -> bool {
+        match self {
+            DependencySpec::Simple(_) => false,
+            DependencySpec::Detailed(d) => d.artifact.is_some() && d.lib != Some(true),
+        }
+    }
+
+    /// `{ workspace = true }`: the declaration comes from
+    /// `[workspace.dependencies]`
+    pub fn inherits_workspace(&self) -> bool {
+        match self {
+            DependencySpec::Simple(_) => false,
+            DependencySpec::Detailed(d) => d.workspace.unwrap_or(false),
+        }
+    }
+
+    /// `optional = true`
+    pub fn is_optional(&self) -> bool {
+        match self {
+            DependencySpec::Simple(_) => false,
+            DependencySpec::Detailed(d) => d.optional.unwrap_or(false),
+        }
+    }
+
     /// Check if this is from crates.io (not git or path)
     pub fn is_crates_io(&self) -> bool {
         !self.is_git() && !self.is_path()
     }
+
+    /// Explicitly requested features
+    pub fn features(&self) -> &[String] {
+        match self {
+            DependencySpec::Simple(_) => &[],
+            DependencySpec::Detailed(d) => d.features.as_deref().unwrap_or(&[]),
+        }
+    }
+
+    /// Whether the crate's default features are enabled
+    pub fn default_features(&self) -> bool {
+        match self {
+            DependencySpec::Simple(_) => true,
+            DependencySpec::Detailed(d) => d.default_features.unwrap_or(true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Manifest {
+        Manifest::parse(PathBuf::from("Cargo.toml"), text).unwrap()
+    }
+
+    fn find_error(path: &Path) -> String {
+        let error = Manifest::find(Some(path.to_string_lossy().to_string())).unwrap_err();
+        format!("{:#}", error)
+    }
+
+    #[test]
+    fn test_find_resolves_the_manifest_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join("app")).unwrap();
+        fs::write(root.join("app/Cargo.toml"), "[package]\nname = \"app\"\n").unwrap();
+
+        // A directory means its Cargo.toml, and `..` segments are resolved
+        let manifest = Manifest::find(Some(root.join("app").display().to_string())).unwrap();
+        assert_eq!(manifest.path, root.join("app/Cargo.toml"));
+        let detour = root.join("app/../app/Cargo.toml");
+        let manifest = Manifest::find(Some(detour.display().to_string())).unwrap();
+        assert_eq!(manifest.path, root.join("app/Cargo.toml"));
+    }
+
+    #[test]
+    fn test_find_rejects_bad_manifest_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+
+        let tarball = root.join("project.tar.gz");
+        fs::write(&tarball, b"\x1f\x8b").unwrap();
+        assert_eq!(
+            find_error(&tarball),
+            format!(
+                "The manifest path must be a Cargo.toml or a directory containing one: {}",
+                tarball.display()
+            )
+        );
+
+        let empty = root.join("empty");
+        fs::create_dir(&empty).unwrap();
+        assert_eq!(
+            find_error(&empty),
+            format!(
+                "Cargo.toml not found at: {}",
+                empty.join("Cargo.toml").display()
+            )
+        );
+
+        let huge = root.join("huge");
+        fs::create_dir(&huge).unwrap();
+        let file = fs::File::create(huge.join("Cargo.toml")).unwrap();
+        file.set_len(MAX_MANIFEST_BYTES + 1).unwrap();
+        assert_eq!(
+            find_error(&huge.join("Cargo.toml")),
+            format!(
+                "{} is 4194305 bytes, over the 4194304 bytes a Cargo.toml may be; \
+                 check that the manifest path names the right file",
+                huge.join("Cargo.toml").display()
+            )
+        );
+
+        let broken = root.join("broken");
+        fs::create_dir(&broken).unwrap();
+        fs::write(broken.join("Cargo.toml"), "[package\n").unwrap();
+        let error = find_error(&broken.join("Cargo.toml"));
+        assert!(
+            error.starts_with(&format!(
+                "Invalid manifest {}: Failed to parse Cargo.toml: TOML parse error at line 1",
+                broken.join("Cargo.toml").display()
+            )),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_decode_splits_off_bom() {
+        let text = ManifestText::decode(b"\xEF\xBB\xBF[package]\n".to_vec()).unwrap();
+        assert_eq!(text.content, "[package]\n");
+        assert!(text.bom);
+        assert_eq!(text.encode().as_bytes(), b"\xEF\xBB\xBF[package]\n");
+
+        let text = ManifestText::decode(b"[package]\n".to_vec()).unwrap();
+        assert!(!text.bom);
+        assert_eq!(text.encode(), "[package]\n");
+    }
+
+    #[test]
+    fn test_decode_reports_invalid_utf8_position() {
+        let bytes = b"[package]\n# Caf\xE9\nname = \"demo\"\n".to_vec();
+        let error = ManifestText::decode(bytes).unwrap_err().to_string();
+        assert!(
+            error.contains("byte 0xE9 at offset 15 (line 2, column 6)"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_location_of_declaration_styles() {
+        let manifest = parse(
+            r#"[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+serde = "1.0"
+  tokio = { version = "1", features = ["full"] }
+clap.version = "4"
+clap.features = ["derive"]
+"quoted-name" = "0.2"
+
+[dependencies.regex]
+version = "1"
+features = [
+    "unicode",
+]
+
+[dev-dependencies]
+tempfile = "3"
+
+[build-dependencies.cc]
+version = "1"
+"#,
+        );
+
+        assert_eq!(
+            manifest.location_of("serde", DependencyKind::Normal),
+            Some((6, 1))
+        );
+        assert_eq!(
+            manifest.location_of("tokio", DependencyKind::Normal),
+            Some((7, 3))
+        );
+        assert_eq!(
+            manifest.location_of("clap", DependencyKind::Normal),
+            Some((8, 1))
+        );
+        assert_eq!(
+            manifest.location_of("quoted-name", DependencyKind::Normal),
+            Some((10, 1))
+        );
+        assert_eq!(
+            manifest.location_of("regex", DependencyKind::Normal),
+            Some((12, 1))
+        );
+        assert_eq!(
+            manifest.location_of("tempfile", DependencyKind::Dev),
+            Some((19, 1))
+        );
+        assert_eq!(
+            manifest.location_of("cc", DependencyKind::Build),
+            Some((21, 1))
+        );
+    }
+
+    #[test]
+    fn test_location_of_respects_kind() {
+        let manifest = parse(
+            r#"[dependencies]
+serde = "1.0"
+
+[dev-dependencies]
+serde = "1.0"
+"#,
+        );
+
+        assert_eq!(
+            manifest.location_of("serde", DependencyKind::Normal),
+            Some((2, 1))
+        );
+        assert_eq!(
+            manifest.location_of("serde", DependencyKind::Dev),
+            Some((5, 1))
+        );
+        assert_eq!(manifest.location_of("serde", DependencyKind::Build), None);
+    }
+
+    #[test]
+    fn test_location_of_ignores_non_dependency_keys() {
+        let manifest = parse(
+            r#"dependencies.anyhow = "1"
+
+[package]
+name = "demo"
+version = "0.1.0"
+description = """
+serde = "not a dependency"
+"""
+
+[features]
+serde = []
+
+[[bin]]
+name = "demo"
+"#,
+        );
+
+        assert_eq!(
+            manifest.location_of("anyhow", DependencyKind::Normal),
+            Some((1, 1))
+        );
+        assert_eq!(manifest.location_of("serde", DependencyKind::Normal), None);
+        assert_eq!(manifest.location_of("name", DependencyKind::Normal), None);
+    }
+
+    #[test]
+    fn test_location_of_after_quoted_string_delimiters() {
+        let manifest = parse(
+            r#"[package]
+name = "demo"
+description = 'Triple quotes (""") stay quoted' # and '''
+readme = "README \"\"\" .md"
+keywords = ["""a""", '''b''']
+documentation = """
+[dependencies]
+fake = "1" \"""
+"""
+
+[dependencies]
+serde = "1"
+"#,
+        );
+
+        assert_eq!(
+            manifest.location_of("serde", DependencyKind::Normal),
+            Some((12, 1))
+        );
+        assert_eq!(manifest.location_of("fake", DependencyKind::Normal), None);
+    }
+
+    #[test]
+    fn test_location_in_target_sections() {
+        let manifest = parse(
+            r#"[dependencies]
+libc = "0.2"
+
+[target.'cfg(unix)'.dependencies]
+libc = { version = "0.2", features = ["extra_traits"] }
+
+[target.x86_64-pc-windows-msvc.dev-dependencies.winapi]
+version = "0.3"
+"#,
+        );
+
+        let unix = DependencySection {
+            kind: DependencyKind::Normal,
+            target: Some("cfg(unix)".to_string()),
+        };
+        let windows = DependencySection {
+            kind: DependencyKind::Dev,
+            target: Some("x86_64-pc-windows-msvc".to_string()),
+        };
+
+        assert_eq!(
+            manifest.location_of("libc", DependencyKind::Normal),
+            Some((2, 1))
+        );
+        assert_eq!(manifest.location_in("libc", &unix), Some((5, 1)));
+        assert_eq!(manifest.location_in("winapi", &windows), Some((7, 1)));
+        assert_eq!(unix.to_string(), "target.'cfg(unix)'.dependencies");
+        assert_eq!(
+            windows.to_string(),
+            "target.x86_64-pc-windows-msvc.dev-dependencies"
+        );
+        assert_eq!(manifest.declarations().len(), 3);
+    }
+
+    #[test]
+    fn test_get_dependencies_includes_target_tables() {
+        let manifest = parse(
+            r#"[dependencies]
+serde = "1.0"
+
+[target.'cfg(windows)'.dependencies]
+winapi = "0.3"
+
+[target.'cfg(unix)'.dependencies]
+nix = "0.27"
+
+[target.'cfg(unix)'.dev-dependencies]
+tempfile = "3"
+
+[target.'cfg(windows)'.build-dependencies]
+winres = "0.1"
+"#,
+        );
+
+        let deps: Vec<(String, DependencyKind, Option<String>)> = manifest
+            .get_dependencies()
+            .into_iter()
+            .map(|(name, _, section)| (name, section.kind, section.target))
+            .collect();
+        let target = |t: &str| Some(t.to_string());
+        assert_eq!(
+            deps,
+            [
+                (
+                    "nix".to_string(),
+                    DependencyKind::Normal,
+                    target("cfg(unix)")
+                ),
+                ("serde".to_string(), DependencyKind::Normal, None),
+                (
+                    "tempfile".to_string(),
+                    DependencyKind::Dev,
+                    target("cfg(unix)")
+                ),
+                (
+                    "winapi".to_string(),
+                    DependencyKind::Normal,
+                    target("cfg(windows)")
+                ),
+                (
+                    "winres".to_string(),
+                    DependencyKind::Build,
+                    target("cfg(windows)")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_artifact_dependencies() {
+        let manifest = parse(
+            r#"[dependencies]
+tool = { version = "1.0", artifact = "bin" }
+both = { version = "1.0", artifact = ["bin:gen", "cdylib"], target = "wasm32-unknown-unknown", lib = true }
+plain = { version = "1.0", package = "plain-rs" }
+"#,
+        );
+        let spec = |name: &str| {
+            manifest
+                .get_dependencies()
+                .into_iter()
+                .find(|(n, _, _)| n == name)
+                .unwrap()
+                .1
+        };
+
+        assert_eq!(spec("tool").artifacts(), ["bin"]);
+        assert!(spec("tool").is_artifact_only());
+        assert_eq!(spec("both").artifacts(), ["bin:gen", "cdylib"]);
+        assert!(!spec("both").is_artifact_only());
+        assert_eq!(spec("both").version(), Some("1.0"));
+        assert!(spec("plain").artifacts().is_empty());
+        assert_eq!(spec("plain").package(), Some("plain-rs"));
+    }
+
+    #[test]
+    fn test_rust_version() {
+        let version = |text: &str| parse(text).rust_version();
+
+        assert_eq!(
+            version("[package]\nname = \"a\"\nrust-version = \"1.70\"\n"),
+            Some(Version::new(1, 70, 0))
+        );
+        assert_eq!(
+            version("[package]\nname = \"a\"\nrust-version = \"1.74.1\"\n"),
+            Some(Version::new(1, 74, 1))
+        );
+        assert_eq!(
+            version(
+                "[package]\nname = \"a\"\nrust-version.workspace = true\n\n[workspace]\n\n[workspace.package]\nrust-version = \"1.80\"\n"
+            ),
+            Some(Version::new(1, 80, 0))
+        );
+        assert_eq!(version("[package]\nname = \"a\"\n"), None);
+        assert_eq!(
+            version("[package]\nname = \"a\"\nrust-version = \"stable\"\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_workspace_members() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, text: &str| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, text).unwrap();
+            path
+        };
+        let root = write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/scratch\"]\n",
+        );
+        for name in ["web", "core", "scratch"] {
+            write(
+                &format!("crates/{}/Cargo.toml", name),
+                &format!("[package]\nname = \"{}\"\n", name),
+            );
+        }
+
+        let root = Manifest::from_path(&root).unwrap();
+        assert!(root.is_virtual());
+        let members: Vec<String> = root
+            .workspace_members()
+            .unwrap()
+            .iter()
+            .map(|m| m.package_name().unwrap().to_string())
+            .collect();
+        assert_eq!(members, ["core", "web"]);
+
+        let member = Manifest::from_path(&dir.path().join("crates/web/Cargo.toml")).unwrap();
+        assert!(!member.is_virtual());
+        assert!(member.workspace_members().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_inherited() {
+        let root = Manifest::parse(
+            PathBuf::from("Cargo.toml"),
+            r#"[workspace]
+members = ["crates/*"]
+
+[workspace.dependencies]
+serde = { version = "1.0.190", features = ["std"] }
+"log" = "0.4"
+
+[workspace.dependencies.tokio]
+version = "1.38"
+default-features = false
+"#,
+        )
+        .unwrap();
+        assert_eq!(root.workspace_root(), Some(Path::new("Cargo.toml")));
+        assert_eq!(root.workspace_location_of("serde"), Some((5, 1)));
+        assert_eq!(root.workspace_location_of("log"), Some((6, 1)));
+        assert_eq!(root.workspace_location_of("tokio"), Some((8, 1)));
+        assert!(root.location_of("serde", DependencyKind::Normal).is_none());
+
+        let member = Manifest::parse(
+            PathBuf::from("crates/api/Cargo.toml"),
+            r#"[package]
+name = "api"
+
+[dependencies]
+serde = { workspace = true, features = ["derive", "std"] }
+log.workspace = true
+tokio = { workspace = true, optional = true }
+regex = { workspace = true }
+rand = "0.8"
+"#,
+        )
+        .unwrap();
+        assert_eq!(member.workspace_root(), None);
+        let member = member.with_workspace_root(&root);
+        let resolved = |name: &str| {
+            let spec = &member.content.dependencies.as_ref().unwrap()[name];
+            member.resolve_inherited(name, spec)
+        };
+
+        let serde = resolved("serde").unwrap();
+        assert_eq!(serde.version(), Some("1.0.190"));
+        assert_eq!(serde.features(), ["std", "derive"]);
+        assert!(!serde.inherits_workspace());
+        assert_eq!(resolved("log").unwrap().version(), Some("0.4"));
+        let tokio = resolved("tokio").unwrap();
+        assert!(tokio.is_optional());
+        assert!(!tokio.default_features());
+        // Not in the root's table, and not inherited at all
+        assert!(resolved("regex").is_none());
+        assert!(resolved("rand").is_none());
+    }
 }
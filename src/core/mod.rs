@@ -1,6 +1,12 @@
 //! Core domain models and types
 
+pub mod advisory;
 pub mod config;
+pub mod deny;
 pub mod dependency;
+pub mod license;
+pub mod lockfile;
 pub mod manifest;
+pub mod policy;
 pub mod version;
+pub mod workspace;
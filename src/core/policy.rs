@@ -0,0 +1,433 @@
+//! Organization version policy ("blessed versions")
+//!
+//! A versions file maps crate names to the requirement the organization has
+//! approved, e.g. `serde = "=1.0.197"` or `tokio = "~1.36"`. Crates it lists
+//! are checked against that requirement instead of the newest release.
+//!
+//! A crate can instead track a release series, e.g.
+//! `hyper = { track = "0.14" }` for a vendor backporting security fixes to
+//! 0.14.x alongside 1.x: updates stay within the series, and newer series
+//! are only mentioned.
+
+use crate::core::version::{
+    is_newer, select_target_version, Prereleases, PublishedVersion, Series, TargetSelection,
+};
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What the versions file says about each crate it lists
+#[derive(Debug, Clone, Default)]
+pub struct VersionPolicy {
+    versions: BTreeMap<String, Blessed>,
+}
+
+#[derive(Debug, Clone)]
+enum Blessed {
+    Requirement(VersionReq),
+    Track(Series),
+}
+
+/// An entry of the versions file as written
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawEntry {
+    Requirement(String),
+    Track(RawTrack),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTrack {
+    track: String,
+}
+
+/// Where a dependency stands against the versions file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PolicyCheck {
+    /// The blessed requirement; for a tracked series, the one accepting its
+    /// releases
+    pub requirement: String,
+    pub status: PolicyStatus,
+    /// The tracked release series, e.g. "0.14.x"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track: Option<String>,
+    /// The newest release outside the tracked series, when it's newer than
+    /// the target; for information only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newer_series: Option<Version>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", tag = "status", content = "reason")]
+pub enum PolicyStatus {
+    /// The current version satisfies the blessed requirement
+    Compliant,
+    /// Outside the requirement, but a blessed release is newer to move to
+    OffPolicy,
+    /// Outside the requirement with no way forward short of a downgrade or
+    /// a policy change; reported, never resolved automatically
+    Conflict(String),
+}
+
+impl VersionPolicy {
+    /// Parse a versions file, either a TOML or a JSON table of crate names
+    /// to requirements or `{ track = "MAJOR.MINOR" }` tables
+    pub fn parse(content: &str, json: bool) -> Result<Self> {
+        let raw: BTreeMap<String, RawEntry> = if json {
+            serde_json::from_str(content).context("Failed to parse versions file as JSON")?
+        } else {
+            toml::from_str(content).context("Failed to parse versions file as TOML")?
+        };
+
+        let versions = raw
+            .into_iter()
+            .map(|(name, entry)| {
+                let blessed = match entry {
+                    RawEntry::Requirement(requirement) => {
+                        Blessed::Requirement(VersionReq::parse(&requirement).context(format!(
+                            "Invalid requirement '{}' for {} in versions file",
+                            requirement, name
+                        ))?)
+                    }
+                    RawEntry::Track(RawTrack { track }) => {
+                        Blessed::Track(Series::parse(&track).context(format!(
+                            "Invalid series '{}' tracked for {} in versions file; expected \
+                             MAJOR or MAJOR.MINOR, e.g. \"0.14\"",
+                            track, name
+                        ))?)
+                    }
+                };
+                Ok((name, blessed))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { versions })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+
+    /// The blessed requirement of `name`, unless it tracks a series
+    pub fn requirement(&self, name: &str) -> Option<&VersionReq> {
+        match self.versions.get(name)? {
+            Blessed::Requirement(req) => Some(req),
+            Blessed::Track(_) => None,
+        }
+    }
+
+    /// The release series `name` tracks, if it does
+    pub fn track(&self, name: &str) -> Option<&Series> {
+        match self.versions.get(name)? {
+            Blessed::Track(series) => Some(series),
+            Blessed::Requirement(_) => None,
+        }
+    }
+
+    /// Pick the update target for `name` among its blessed releases and
+    /// classify `current` against the policy. `None` when the crate isn't
+    /// listed. `prereleases` applies within a tracked series; a blessed
+    /// requirement says for itself which prereleases it accepts.
+    pub fn evaluate(
+        &self,
+        name: &str,
+        current: &Version,
+        published: &[PublishedVersion],
+        prereleases: &Prereleases,
+        advisories: impl Fn(&Version) -> Vec<String>,
+    ) -> Option<(TargetSelection, PolicyCheck)> {
+        let req = match self.versions.get(name)? {
+            Blessed::Requirement(req) => req,
+            Blessed::Track(series) => {
+                return Some(track(series, current, published, prereleases, advisories))
+            }
+        };
+        let blessed: Vec<PublishedVersion> = published
+            .iter()
+            .filter(|p| req.matches(&p.version))
+            .cloned()
+            .collect();
+        let mut selection = select_target_version(&blessed, &Prereleases::All, advisories);
+
+        let status = if req.matches(current) {
+            PolicyStatus::Compliant
+        } else {
+            match &selection.target {
+                Some(target) if is_newer(target, current) => PolicyStatus::OffPolicy,
+                Some(target) => {
+                    let reason = format!(
+                        "{} is newer than the blessed {}; not downgrading",
+                        current, target
+                    );
+                    selection.target = None;
+                    PolicyStatus::Conflict(reason)
+                }
+                None => PolicyStatus::Conflict("no usable release is blessed".to_string()),
+            }
+        };
+        Some((
+            selection,
+            PolicyCheck {
+                requirement: req.to_string(),
+                status,
+                track: None,
+                newer_series: None,
+            },
+        ))
+    }
+}
+
+/// Target the newest release of the tracked `series`, noting a newer one
+/// outside it. A version already past the series is never moved back.
+fn track(
+    series: &Series,
+    current: &Version,
+    published: &[PublishedVersion],
+    prereleases: &Prereleases,
+    advisories: impl Fn(&Version) -> Vec<String>,
+) -> (TargetSelection, PolicyCheck) {
+    let mut selection = series.select_target(published, prereleases, &advisories);
+    let newer_series = select_target_version(published, prereleases, &advisories)
+        .target
+        .filter(|newest| !series.contains(newest))
+        .filter(|newest| {
+            selection
+                .target
+                .as_ref()
+                .is_none_or(|t| is_newer(newest, t))
+        });
+
+    let status = if series.contains(current) {
+        PolicyStatus::Compliant
+    } else if series.is_ahead_of(current) {
+        match &selection.target {
+            Some(_) => PolicyStatus::OffPolicy,
+            None => PolicyStatus::Conflict(format!("no usable {} release", series)),
+        }
+    } else {
+        selection.target = None;
+        PolicyStatus::Conflict(format!(
+            "{} is past the tracked {}; not downgrading",
+            current, series
+        ))
+    };
+    (
+        selection,
+        PolicyCheck {
+            requirement: series.requirement(),
+            status,
+            track: Some(series.to_string()),
+            newer_series,
+        },
+    )
+}
+
+impl PolicyCheck {
+    /// The dependency needs attention under the policy
+    pub fn is_off_policy(&self) -> bool {
+        self.status != PolicyStatus::Compliant
+    }
+
+    /// What the policy asks for, e.g. "blessed =1.0.197" or "tracked 0.14.x"
+    pub fn blessed(&self) -> String {
+        match &self.track {
+            Some(series) => format!("tracked {}", series),
+            None => format!("blessed {}", self.requirement),
+        }
+    }
+}
+
+/// One `name = "requirement"` or `name = { track = "series" }` line per
+/// crate, in name order
+impl fmt::Display for VersionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, blessed) in &self.versions {
+            match blessed {
+                Blessed::Requirement(req) => writeln!(f, "{} = \"{}\"", name, req)?,
+                Blessed::Track(series) => writeln!(
+                    f,
+                    "{} = {{ track = \"{}\" }}",
+                    name,
+                    series.to_string().trim_end_matches(".x")
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PolicyStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyStatus::Compliant => write!(f, "compliant"),
+            PolicyStatus::OffPolicy => write!(f, "off-policy"),
+            PolicyStatus::Conflict(reason) => write!(f, "conflict: {}", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn published(list: &[(&str, bool)]) -> Vec<PublishedVersion> {
+        list.iter()
+            .map(|(v, yanked)| PublishedVersion {
+                version: Version::parse(v).unwrap(),
+                yanked: *yanked,
+                created_at: None,
+                published_by: None,
+                license: None,
+                crate_size: None,
+            })
+            .collect()
+    }
+
+    fn policy() -> VersionPolicy {
+        VersionPolicy::parse("serde = \"=1.0.197\"\ntokio = \"~1.36\"\n", false).unwrap()
+    }
+
+    #[test]
+    fn test_parse_toml_and_json() {
+        let json =
+            VersionPolicy::parse(r#"{"serde": "=1.0.197", "tokio": "~1.36"}"#, true).unwrap();
+        assert_eq!(json.to_string(), policy().to_string());
+        assert_eq!(policy().requirement("tokio").unwrap().to_string(), "~1.36");
+        assert!(policy().requirement("rand").is_none());
+        assert!(VersionPolicy::parse("serde = \"not a version\"", false).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_targets_the_blessed_release() {
+        let versions = published(&[
+            ("1.37.0", false),
+            ("1.36.2", true),
+            ("1.36.1", false),
+            ("1.36.0", false),
+        ]);
+
+        let current = Version::new(1, 36, 0);
+        let (selection, check) = policy()
+            .evaluate("tokio", &current, &versions, &Prereleases::Stable, |_| {
+                Vec::new()
+            })
+            .unwrap();
+        assert_eq!(selection.target, Some(Version::new(1, 36, 1)));
+        assert_eq!(check.status, PolicyStatus::Compliant);
+
+        let current = Version::new(1, 30, 0);
+        let (selection, check) = policy()
+            .evaluate("tokio", &current, &versions, &Prereleases::Stable, |_| {
+                Vec::new()
+            })
+            .unwrap();
+        assert_eq!(selection.target, Some(Version::new(1, 36, 1)));
+        assert_eq!(check.status, PolicyStatus::OffPolicy);
+        assert!(policy()
+            .evaluate("rand", &current, &versions, &Prereleases::Stable, |_| {
+                Vec::new()
+            })
+            .is_none());
+    }
+
+    fn tracked() -> VersionPolicy {
+        VersionPolicy::parse("hyper = { track = \"0.14\" }\nserde = \"1\"\n", false).unwrap()
+    }
+
+    #[test]
+    fn test_parse_tracked_series() {
+        let json =
+            VersionPolicy::parse(r#"{"hyper": {"track": "0.14"}, "serde": "1"}"#, true).unwrap();
+        assert_eq!(json.to_string(), tracked().to_string());
+        assert_eq!(
+            tracked().to_string(),
+            "hyper = { track = \"0.14\" }\nserde = \"^1\"\n"
+        );
+        assert_eq!(tracked().track("hyper"), Series::parse("0.14").as_ref());
+        assert!(tracked().requirement("hyper").is_none());
+        assert!(tracked().track("serde").is_none());
+
+        let error = VersionPolicy::parse("hyper = { track = \"0.14.2\" }", false).unwrap_err();
+        assert!(format!("{:#}", error).contains("Invalid series '0.14.2' tracked for hyper"));
+        assert!(VersionPolicy::parse("hyper = { branch = \"0.14\" }", false).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_tracked_series() {
+        let versions = published(&[
+            ("1.2.0", false),
+            ("0.14.30-rc.1", false),
+            ("0.14.28", false),
+            ("0.14.20", false),
+            ("0.13.5", false),
+        ]);
+        let evaluate = |current: &str, prereleases: &Prereleases| {
+            tracked()
+                .evaluate(
+                    "hyper",
+                    &Version::parse(current).unwrap(),
+                    &versions,
+                    prereleases,
+                    |_| Vec::new(),
+                )
+                .unwrap()
+        };
+
+        let (selection, check) = evaluate("0.14.20", &Prereleases::Stable);
+        assert_eq!(selection.target, Some(Version::new(0, 14, 28)));
+        assert_eq!(check.status, PolicyStatus::Compliant);
+        assert_eq!(check.track.as_deref(), Some("0.14.x"));
+        assert_eq!(check.requirement, "~0.14");
+        assert_eq!(check.blessed(), "tracked 0.14.x");
+        // The newer series is only reported
+        assert_eq!(check.newer_series, Some(Version::new(1, 2, 0)));
+
+        let (selection, _) = evaluate("0.14.20", &Prereleases::All);
+        assert_eq!(
+            selection.target,
+            Some(Version::parse("0.14.30-rc.1").unwrap())
+        );
+
+        let (selection, check) = evaluate("0.13.5", &Prereleases::Stable);
+        assert_eq!(selection.target, Some(Version::new(0, 14, 28)));
+        assert_eq!(check.status, PolicyStatus::OffPolicy);
+
+        let (selection, check) = evaluate("1.2.0", &Prereleases::Stable);
+        assert_eq!(selection.target, None);
+        assert_eq!(
+            check.status,
+            PolicyStatus::Conflict("1.2.0 is past the tracked 0.14.x; not downgrading".to_string())
+        );
+    }
+
+    #[test]
+    fn test_evaluate_reports_conflicts() {
+        let versions = published(&[("1.0.200", false), ("1.0.197", false)]);
+        let current = Version::new(1, 0, 200);
+        let (selection, check) = policy()
+            .evaluate("serde", &current, &versions, &Prereleases::Stable, |_| {
+                Vec::new()
+            })
+            .unwrap();
+        assert_eq!(selection.target, None);
+        assert!(matches!(check.status, PolicyStatus::Conflict(_)));
+        assert!(check.is_off_policy());
+
+        let (_, check) = policy()
+            .evaluate(
+                "serde",
+                &current,
+                &published(&[("1.0.197", true)]),
+                &Prereleases::Stable,
+                |_| Vec::new(),
+            )
+            .unwrap();
+        assert_eq!(
+            check.status,
+            PolicyStatus::Conflict("no usable release is blessed".to_string())
+        );
+    }
+}
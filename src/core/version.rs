@@ -1,6 +1,10 @@
 //! Version comparison utilities
 
+use schemars::JsonSchema;
 use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
 
 pub fn is_major_update(current: &Version, latest: &Version) -> bool {
     latest.major > current.major
@@ -13,3 +17,500 @@ pub fn is_minor_update(current: &Version, latest: &Version) -> bool {
 pub fn is_patch_update(current: &Version, latest: &Version) -> bool {
     latest.major == current.major && latest.minor == current.minor && latest.patch > current.patch
 }
+
+/// Whether `candidate` is a later release than `current`. Build metadata
+/// doesn't count: `0.16.2+1.7.2` is the same release as `0.16.2`.
+pub fn is_newer(candidate: &Version, current: &Version) -> bool {
+    candidate.cmp_precedence(current) == Ordering::Greater
+}
+
+/// Whether `a` and `b` are the same release, build metadata aside
+pub fn same_release(a: &Version, b: &Version) -> bool {
+    a.cmp_precedence(b) == Ordering::Equal
+}
+
+/// `requirement` without build metadata, which cargo ignores in
+/// requirements and warns about: "0.16.2+1.7.2" -> "0.16.2"
+pub fn without_build_metadata(requirement: &str) -> String {
+    requirement
+        .split(',')
+        .map(|comparator| comparator.split('+').next().unwrap_or(comparator))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// A `rust-version` as a version: "1.70" reads as 1.70.0
+pub fn parse_rust_version(text: &str) -> Option<Version> {
+    let text = text.trim();
+    let padded = match text.matches('.').count() {
+        0 => format!("{}.0.0", text),
+        1 => format!("{}.0", text),
+        _ => text.to_string(),
+    };
+    Version::parse(&padded).ok()
+}
+
+/// A Rust version the way `rust-version` is usually written: "1.70" for
+/// 1.70.0
+pub fn format_rust_version(version: &Version) -> String {
+    if version.patch == 0 && version.pre.is_empty() {
+        format!("{}.{}", version.major, version.minor)
+    } else {
+        version.to_string()
+    }
+}
+
+/// Whether a caret requirement on `a` also accepts `b`, i.e. the two share
+/// their leftmost non-zero component
+pub fn is_compatible(a: &Version, b: &Version) -> bool {
+    match (a.major, a.minor) {
+        (0, 0) => b.major == 0 && b.minor == 0 && a.patch == b.patch,
+        (0, minor) => b.major == 0 && b.minor == minor,
+        (major, _) => b.major == major,
+    }
+}
+
+/// A release series a crate can be held to, e.g. `0.14` for every 0.14.x
+/// release, prereleases included, or `1` for every 1.x release
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Series {
+    major: u64,
+    minor: Option<u64>,
+}
+
+impl Series {
+    /// Parse `MAJOR` or `MAJOR.MINOR`, optionally followed by `.x` or `.*`
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let text = text
+            .strip_suffix(".x")
+            .or_else(|| text.strip_suffix(".*"))
+            .unwrap_or(text);
+        let mut parts = text.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().map(str::parse).transpose().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self { major, minor })
+    }
+
+    pub fn contains(&self, version: &Version) -> bool {
+        version.major == self.major && self.minor.is_none_or(|minor| version.minor == minor)
+    }
+
+    /// Whether every release of the series comes after `version`
+    pub fn is_ahead_of(&self, version: &Version) -> bool {
+        (version.major, self.minor.map(|_| version.minor)) < (self.major, self.minor)
+    }
+
+    /// The requirement accepting the stable releases of the series, e.g.
+    /// "~0.14"
+    pub fn requirement(&self) -> String {
+        match self.minor {
+            Some(minor) => format!("~{}.{}", self.major, minor),
+            None => format!("~{}", self.major),
+        }
+    }
+
+    /// The release to suggest within the series, by the same rules as
+    /// [`select_target_version`] over the series' releases alone
+    pub fn select_target(
+        &self,
+        versions: &[PublishedVersion],
+        prereleases: &Prereleases,
+        advisories: impl Fn(&Version) -> Vec<String>,
+    ) -> TargetSelection {
+        let within: Vec<PublishedVersion> = versions
+            .iter()
+            .filter(|p| self.contains(&p.version))
+            .cloned()
+            .collect();
+        select_target_version(&within, prereleases, advisories)
+    }
+}
+
+/// e.g. "0.14.x"
+impl fmt::Display for Series {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.minor {
+            Some(minor) => write!(f, "{}.{}.x", self.major, minor),
+            None => write!(f, "{}.x", self.major),
+        }
+    }
+}
+
+/// A published release of a crate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublishedVersion {
+    pub version: Version,
+    pub yanked: bool,
+    /// When it was published, as Unix seconds, if the registry says
+    pub created_at: Option<u64>,
+    /// Login of the account that published it, if the registry says
+    pub published_by: Option<String>,
+    /// The SPDX license expression it was published under, if the registry
+    /// says
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Size in bytes of the .crate file, if the registry says
+    #[serde(default)]
+    pub crate_size: Option<u64>,
+}
+
+/// Why a newer release was passed over as an update target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "reason", content = "advisory", rename_all = "lowercase")]
+pub enum SkipReason {
+    Yanked,
+    /// An unpatched advisory, by id
+    Advisory(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SkippedVersion {
+    pub version: Version,
+    #[serde(flatten)]
+    pub reason: SkipReason,
+}
+
+/// Which prereleases may become an update target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Prereleases {
+    /// Stable releases only, unless the crate never had one
+    Stable,
+    /// Also prereleases of this major.minor.patch, to follow a prerelease
+    /// already in use through to its release (`1.0.0-beta.1` -> `1.0.0-rc.1`)
+    Of(Version),
+    /// Any prerelease, as with `--pre`
+    All,
+}
+
+impl Prereleases {
+    /// The prereleases to consider for a dependency at `current`, with `pre`
+    /// opting in to every prerelease
+    pub fn for_current(current: &Version, pre: bool) -> Self {
+        if pre {
+            Prereleases::All
+        } else if current.pre.is_empty() {
+            Prereleases::Stable
+        } else {
+            Prereleases::Of(Version::new(current.major, current.minor, current.patch))
+        }
+    }
+
+    fn allows(&self, version: &Version) -> bool {
+        version.pre.is_empty()
+            || match self {
+                Prereleases::Stable => false,
+                Prereleases::Of(release) => {
+                    (version.major, version.minor, version.patch)
+                        == (release.major, release.minor, release.patch)
+                }
+                Prereleases::All => true,
+            }
+    }
+}
+
+/// The release to suggest updating to, and the newer ones that were skipped
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetSelection {
+    pub target: Option<Version>,
+    pub skipped: Vec<SkippedVersion>,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Yanked => write!(f, "yanked"),
+            SkipReason::Advisory(id) => write!(f, "advisory {}", id),
+        }
+    }
+}
+
+impl fmt::Display for SkippedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} skipped: {}", self.version, self.reason)
+    }
+}
+
+impl TargetSelection {
+    /// e.g. "1.4.2 skipped: yanked; suggesting 1.4.1"
+    pub fn note(&self) -> Option<String> {
+        if self.skipped.is_empty() {
+            return None;
+        }
+        let skipped: Vec<String> = self.skipped.iter().map(|s| s.to_string()).collect();
+        Some(match &self.target {
+            Some(target) => format!("{}; suggesting {}", skipped.join(", "), target),
+            None => format!("{}; no clean version left", skipped.join(", ")),
+        })
+    }
+}
+
+/// Pick the newest release that is neither yanked nor affected by an
+/// advisory, per `advisories` (which returns the ids affecting a version).
+///
+/// Prereleases are only considered as far as `prereleases` allows, or when
+/// the crate has no stable release at all. Every newer release passed over
+/// is recorded with the reason.
+pub fn select_target_version(
+    versions: &[PublishedVersion],
+    prereleases: &Prereleases,
+    advisories: impl Fn(&Version) -> Vec<String>,
+) -> TargetSelection {
+    let has_stable = versions.iter().any(|v| v.version.pre.is_empty());
+    let mut candidates: Vec<&PublishedVersion> = versions
+        .iter()
+        .filter(|v| !has_stable || prereleases.allows(&v.version))
+        .collect();
+    candidates.sort_by(|a, b| b.version.cmp(&a.version));
+
+    let mut selection = TargetSelection::default();
+    for candidate in candidates {
+        if candidate.yanked {
+            selection.skipped.push(SkippedVersion {
+                version: candidate.version.clone(),
+                reason: SkipReason::Yanked,
+            });
+            continue;
+        }
+        if let Some(id) = advisories(&candidate.version).into_iter().next() {
+            selection.skipped.push(SkippedVersion {
+                version: candidate.version.clone(),
+                reason: SkipReason::Advisory(id),
+            });
+            continue;
+        }
+        selection.target = Some(candidate.version.clone());
+        break;
+    }
+    selection
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(list: &[(&str, bool)]) -> Vec<PublishedVersion> {
+        list.iter()
+            .map(|(v, yanked)| PublishedVersion {
+                version: Version::parse(v).unwrap(),
+                yanked: *yanked,
+                created_at: None,
+                published_by: None,
+                license: None,
+                crate_size: None,
+            })
+            .collect()
+    }
+
+    fn none(_: &Version) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[test]
+    fn test_is_compatible() {
+        let v = |s: &str| Version::parse(s).unwrap();
+        assert!(is_compatible(&v("1.2.0"), &v("1.9.3")));
+        assert!(!is_compatible(&v("1.2.0"), &v("2.0.0")));
+        assert!(is_compatible(&v("0.3.30"), &v("0.3.36")));
+        assert!(!is_compatible(&v("0.1.45"), &v("0.3.36")));
+        assert!(!is_compatible(&v("0.0.1"), &v("0.0.2")));
+    }
+
+    #[test]
+    fn test_newest_clean_version() {
+        let list = versions(&[("1.4.0", false), ("1.4.1", false), ("1.3.9", false)]);
+        let selection = select_target_version(&list, &Prereleases::Stable, none);
+        assert_eq!(selection.target, Some(Version::new(1, 4, 1)));
+        assert!(selection.skipped.is_empty());
+        assert_eq!(selection.note(), None);
+    }
+
+    #[test]
+    fn test_skips_yanked() {
+        let list = versions(&[("1.4.1", false), ("1.4.2", true), ("1.4.0", false)]);
+        let selection = select_target_version(&list, &Prereleases::Stable, none);
+        assert_eq!(selection.target, Some(Version::new(1, 4, 1)));
+        assert_eq!(
+            selection.note().as_deref(),
+            Some("1.4.2 skipped: yanked; suggesting 1.4.1")
+        );
+    }
+
+    #[test]
+    fn test_skips_advisories() {
+        let list = versions(&[("2.0.0", false), ("2.0.1", true), ("1.9.0", false)]);
+        let selection = select_target_version(&list, &Prereleases::Stable, |v| {
+            if v.major == 2 {
+                vec!["RUSTSEC-2024-0001".to_string()]
+            } else {
+                Vec::new()
+            }
+        });
+        assert_eq!(selection.target, Some(Version::new(1, 9, 0)));
+        assert_eq!(
+            selection.skipped,
+            vec![
+                SkippedVersion {
+                    version: Version::new(2, 0, 1),
+                    reason: SkipReason::Yanked
+                },
+                SkippedVersion {
+                    version: Version::new(2, 0, 0),
+                    reason: SkipReason::Advisory("RUSTSEC-2024-0001".to_string())
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prereleases() {
+        let list = versions(&[("1.0.0", false), ("1.1.0-beta.1", false)]);
+        assert_eq!(
+            select_target_version(&list, &Prereleases::Stable, none).target,
+            Some(Version::new(1, 0, 0))
+        );
+        assert_eq!(
+            select_target_version(&list, &Prereleases::All, none).target,
+            Some(Version::parse("1.1.0-beta.1").unwrap())
+        );
+
+        // Nothing stable was ever released, so the newest prerelease it is
+        let only_pre = versions(&[("0.1.0-alpha.1", false), ("0.1.0-alpha.2", false)]);
+        assert_eq!(
+            select_target_version(&only_pre, &Prereleases::Stable, none).target,
+            Some(Version::parse("0.1.0-alpha.2").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_prereleases_follow_the_current_one() {
+        let v = |s: &str| Version::parse(s).unwrap();
+        let list = versions(&[
+            ("0.11.0", false),
+            ("0.12.0-beta.1", false),
+            ("0.12.0-rc.1", false),
+            ("0.13.0-alpha.1", false),
+        ]);
+        let target = |current: &str, pre: bool| {
+            select_target_version(&list, &Prereleases::for_current(&v(current), pre), none).target
+        };
+        // On a beta: on to its release candidate, not the next alpha
+        assert_eq!(target("0.12.0-beta.1", false), Some(v("0.12.0-rc.1")));
+        // On a stable release: never onto a prerelease without --pre
+        assert_eq!(target("0.11.0", false), Some(v("0.11.0")));
+        assert_eq!(target("0.11.0", true), Some(v("0.13.0-alpha.1")));
+
+        assert_eq!(
+            Prereleases::for_current(&v("1.0.0-beta.1+build.2"), false),
+            Prereleases::Of(v("1.0.0"))
+        );
+    }
+
+    #[test]
+    fn test_build_metadata() {
+        let v = |s: &str| Version::parse(s).unwrap();
+        assert!(!is_newer(&v("0.16.2+1.7.2"), &v("0.16.2")));
+        assert!(!is_newer(&v("0.16.2+1.7.2"), &v("0.16.2+1.7.1")));
+        assert!(is_newer(&v("0.16.3+1.7.2"), &v("0.16.2+1.8.0")));
+        assert!(is_newer(&v("1.0.0"), &v("1.0.0-rc.1")));
+        assert!(!is_newer(&v("1.0.0-rc.1"), &v("1.0.0")));
+        assert!(same_release(&v("0.16.2+1.7.2"), &v("0.16.2")));
+        assert!(!same_release(&v("1.0.0-rc.1"), &v("1.0.0")));
+
+        assert_eq!(without_build_metadata("0.16.2+1.7.2"), "0.16.2");
+        assert_eq!(without_build_metadata("=1.2.3-beta.1+b5"), "=1.2.3-beta.1");
+        assert_eq!(without_build_metadata(">=1.2.3+a, <2"), ">=1.2.3, <2");
+        assert_eq!(without_build_metadata("1.35"), "1.35");
+    }
+
+    #[test]
+    fn test_series() {
+        let v = |s: &str| Version::parse(s).unwrap();
+        let minor = Series::parse("0.14").unwrap();
+        assert_eq!(minor, Series::parse("0.14.x").unwrap());
+        assert_eq!(minor.to_string(), "0.14.x");
+        assert_eq!(minor.requirement(), "~0.14");
+        assert!(minor.contains(&v("0.14.28")));
+        assert!(minor.contains(&v("0.14.29-rc.1")));
+        assert!(!minor.contains(&v("0.15.0")));
+        assert!(!minor.contains(&v("1.14.0")));
+        assert!(minor.is_ahead_of(&v("0.13.9")));
+        assert!(!minor.is_ahead_of(&v("0.14.0")));
+        assert!(!minor.is_ahead_of(&v("1.0.0")));
+
+        let major = Series::parse("1.*").unwrap();
+        assert_eq!(major.to_string(), "1.x");
+        assert_eq!(major.requirement(), "~1");
+        assert!(major.contains(&v("1.99.0")));
+        assert!(major.is_ahead_of(&v("0.14.0")));
+
+        for invalid in ["", "x", "1.2.3", "0.14.x.x", "v1"] {
+            assert_eq!(Series::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_select_within_a_series() {
+        let list = versions(&[
+            ("1.2.0", false),
+            ("0.14.30-rc.1", false),
+            ("0.14.29", true),
+            ("0.14.28", false),
+            ("0.13.5", false),
+        ]);
+        let series = Series::parse("0.14").unwrap();
+        let current = Version::parse("0.14.20").unwrap();
+
+        // The newest clean release of the branch, not of the crate
+        let selection =
+            series.select_target(&list, &Prereleases::for_current(&current, false), none);
+        assert_eq!(selection.target, Some(Version::new(0, 14, 28)));
+        assert_eq!(
+            selection.note().as_deref(),
+            Some("0.14.29 skipped: yanked; suggesting 0.14.28")
+        );
+
+        // Prereleases on the branch are taken with --pre, or when following
+        // one already in use through to its release
+        let selection = series.select_target(&list, &Prereleases::All, none);
+        assert_eq!(
+            selection.target,
+            Some(Version::parse("0.14.30-rc.1").unwrap())
+        );
+        let on_rc = Version::parse("0.14.30-beta.2").unwrap();
+        let selection = series.select_target(&list, &Prereleases::for_current(&on_rc, false), none);
+        assert_eq!(
+            selection.target,
+            Some(Version::parse("0.14.30-rc.1").unwrap())
+        );
+
+        // A branch with nothing stable yet offers its prereleases
+        let early = versions(&[("2.0.0-alpha.2", false), ("1.2.0", false)]);
+        let selection =
+            Series::parse("2")
+                .unwrap()
+                .select_target(&early, &Prereleases::Stable, none);
+        assert_eq!(
+            selection.target,
+            Some(Version::parse("2.0.0-alpha.2").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_nothing_clean() {
+        let list = versions(&[("1.0.0", true)]);
+        let selection = select_target_version(&list, &Prereleases::Stable, none);
+        assert_eq!(selection.target, None);
+        assert_eq!(
+            selection.note().as_deref(),
+            Some("1.0.0 skipped: yanked; no clean version left")
+        );
+        assert_eq!(
+            select_target_version(&[], &Prereleases::Stable, none),
+            TargetSelection::default()
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Cargo workspace discovery
+//!
+//! `--no-workspace-discovery` turns off looking above a package for the
+//! workspace it belongs to, for the rest of the process.
+
+use crate::core::manifest::Manifest;
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use semver::Version;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static STANDALONE: OnceLock<bool> = OnceLock::new();
+
+/// A workspace root and the member manifests it lists
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub root: Manifest,
+    pub members: Vec<Manifest>,
+}
+
+impl Workspace {
+    /// Treat every package as its own workspace root from now on
+    pub fn set_standalone() {
+        let _ = STANDALONE.set(true);
+    }
+
+    /// Whether `--no-workspace-discovery` is in effect
+    pub fn is_standalone() -> bool {
+        STANDALONE.get().copied().unwrap_or(false)
+    }
+
+    /// Load the workspace rooted at `root`, expanding the `members` globs and
+    /// dropping anything matched by `exclude`. Returns `None` when the manifest
+    /// has no `[workspace]` table.
+    pub fn load(root: Manifest) -> Result<Option<Self>> {
+        let Some(table) = root.content.workspace.clone() else {
+            return Ok(None);
+        };
+        let dir = root.path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+        let excluded: Vec<PathBuf> = table
+            .exclude
+            .iter()
+            .flat_map(|pattern| expand_pattern(&dir, pattern))
+            .collect();
+
+        let mut member_dirs: Vec<PathBuf> = table
+            .members
+            .iter()
+            .flat_map(|pattern| expand_pattern(&dir, pattern))
+            .filter(|path| path.join("Cargo.toml").is_file())
+            .filter(|path| !excluded.iter().any(|ex| path.starts_with(ex)))
+            .collect();
+        member_dirs.sort();
+        member_dirs.dedup();
+
+        let mut members = Vec::new();
+        // A root manifest with a [package] is itself a member
+        if root.content.package.is_some() {
+            members.push(root.clone());
+        }
+        // Loaded in parallel; collecting keeps them in directory order
+        let loaded: Vec<Manifest> = member_dirs
+            .par_iter()
+            .filter(|member_dir| **member_dir != dir)
+            .map(|member_dir| {
+                Manifest::from_path(&member_dir.join("Cargo.toml"))
+                    .map(|member| member.with_workspace_root(&root))
+                    .context(format!(
+                        "Failed to load workspace member {}",
+                        member_dir.display()
+                    ))
+            })
+            .collect::<Result<_>>()?;
+        members.extend(loaded);
+
+        Ok(Some(Self { root, members }))
+    }
+
+    /// The root of the workspace `manifest` is a member of, found by
+    /// looking through the directories above it. `None` for a manifest
+    /// that is its own root, or belongs to none, or under
+    /// `--no-workspace-discovery`.
+    pub fn root_of(manifest: &Manifest) -> Option<Manifest> {
+        if manifest.content.workspace.is_some() || Self::is_standalone() {
+            return None;
+        }
+        let dir = manifest.path.parent().unwrap_or(Path::new("."));
+        let absolute = fs::canonicalize(if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        })
+        .ok()?;
+
+        // The nearest manifest with a [workspace] decides, member or not
+        let root = absolute.ancestors().skip(1).find_map(|ancestor| {
+            Manifest::from_path(&ancestor.join("Cargo.toml"))
+                .ok()
+                .filter(|root| root.content.workspace.is_some())
+        })?;
+        let workspace = Self::load(root).ok().flatten()?;
+        let member = workspace
+            .members
+            .iter()
+            .any(|m| fs::canonicalize(&m.path).is_ok_and(|p| p == absolute.join("Cargo.toml")));
+        member.then_some(workspace.root)
+    }
+
+    /// The member whose package is called `name`
+    pub fn member(&self, name: &str) -> Option<&Manifest> {
+        self.members.iter().find(|m| m.package_name() == Some(name))
+    }
+
+    /// The `rust-version` `member` declares, following
+    /// `rust-version.workspace = true` to the root's `[workspace.package]`
+    pub fn rust_version_of(&self, member: &Manifest) -> Option<Version> {
+        if member.inherits_rust_version() {
+            self.root.rust_version()
+        } else {
+            member.rust_version()
+        }
+    }
+
+    /// Where `member` lives relative to the workspace root, e.g.
+    /// "crates/embedded"; the root package goes by its name
+    pub fn member_dir(&self, member: &Manifest) -> String {
+        let root = self.root.path.parent().unwrap_or(Path::new("."));
+        member
+            .path
+            .parent()
+            .and_then(|dir| dir.strip_prefix(root).ok())
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(|dir| dir.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_else(|| Self::member_name(member))
+    }
+
+    /// A display name for a member: its package name, else its directory
+    pub fn member_name(manifest: &Manifest) -> String {
+        manifest
+            .package_name()
+            .map(str::to_string)
+            .unwrap_or_else(|| manifest.path.display().to_string())
+    }
+}
+
+/// Expand a workspace member pattern relative to `dir`. Cargo accepts glob
+/// patterns here; `*` and `?` within a path component cover what is used
+/// in practice (`crates/*`).
+fn expand_pattern(dir: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut paths = vec![dir.to_path_buf()];
+
+    for component in pattern.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        let mut next = Vec::new();
+        for path in &paths {
+            if !component.contains(['*', '?']) {
+                next.push(path.join(component));
+                continue;
+            }
+            let Ok(entries) = fs::read_dir(path) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if entry.path().is_dir() && wildcard_match(component, &name) {
+                    next.push(entry.path());
+                }
+            }
+        }
+        paths = next;
+    }
+
+    paths
+}
+
+/// Match `text` against a pattern where `*` is any run of characters and `?`
+/// any single character
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let (mut star, mut mark) = (None, 0);
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some(p);
+            mark = t;
+            p += 1;
+        } else if let Some(s) = star {
+            p = s + 1;
+            mark += 1;
+            t = mark;
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("sane-*", "sane-core"));
+        assert!(wildcard_match("a?c", "abc"));
+        assert!(!wildcard_match("sane-*", "other"));
+        assert!(!wildcard_match("a?c", "ac"));
+    }
+
+    #[test]
+    fn test_load_members() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\", \"tools/cli\"]\nexclude = [\"crates/scratch\"]\n",
+        );
+        for member in ["crates/core", "crates/web", "crates/scratch", "tools/cli"] {
+            let name = member.rsplit('/').next().unwrap();
+            write(
+                root,
+                &format!("{}/Cargo.toml", member),
+                &format!("[package]\nname = \"{}\"\nversion.workspace = true\n", name),
+            );
+        }
+        // Directories without a manifest are not members
+        fs::create_dir_all(root.join("crates/docs")).unwrap();
+
+        let manifest = Manifest::from_path(&root.join("Cargo.toml")).unwrap();
+        let workspace = Workspace::load(manifest).unwrap().unwrap();
+
+        let names: Vec<String> = workspace
+            .members
+            .iter()
+            .map(Workspace::member_name)
+            .collect();
+        assert_eq!(names, vec!["core", "web", "cli"]);
+        assert!(workspace.member("web").is_some());
+    }
+
+    #[test]
+    fn test_not_a_workspace() {
+        let manifest =
+            Manifest::parse(PathBuf::from("Cargo.toml"), "[package]\nname = \"a\"\n").unwrap();
+        assert!(Workspace::load(manifest).unwrap().is_none());
+    }
+}
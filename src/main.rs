@@ -1,5 +1,20 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use cargo_sane::analyzer::lint::LintSeverity;
+use cargo_sane::cli::defaults::{EXIT_FAILURE, EXIT_TIMEOUT};
+use cargo_sane::cli::output::{self, FileFormat, OutputFile, OutputFormat};
+use cargo_sane::cli::prompt;
+use cargo_sane::cli::schema::SchemaKind;
+use cargo_sane::core::lockfile::Lockfile;
+use cargo_sane::core::workspace::Workspace;
+use cargo_sane::utils::cache_store::CacheKind;
+use cargo_sane::utils::cancel::Cancellation;
+use cargo_sane::utils::cargo::Metadata;
+use cargo_sane::utils::progress::ProgressMode;
+use cargo_sane::utils::test_mode::Scenario;
+use cargo_sane::utils::timings;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(
@@ -13,6 +28,71 @@ use clap::{Parser, Subcommand};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// How to show progress: plain lines, an in-place bar, or none
+    /// (default: plain in CI or when stdout isn't a terminal, else bar)
+    #[arg(long, global = true, value_enum)]
+    progress: Option<ProgressMode>,
+
+    /// Read this Cargo.lock instead of the one at the workspace root; it
+    /// must lock the manifest's packages
+    #[arg(long, global = true, value_name = "PATH")]
+    lockfile_path: Option<PathBuf>,
+
+    /// Treat the manifest as a project of its own, even when a workspace
+    /// above it lists it as a member
+    #[arg(long, global = true)]
+    no_workspace_discovery: bool,
+
+    /// Stop starting network lookups after this many seconds, finish the
+    /// ones in flight and report what was found, marked as partial
+    #[arg(long, global = true, value_name = "SECS")]
+    timeout: Option<u64>,
+
+    /// How a run cut short by --timeout exits: 124 (timeout), 1 (error), or
+    /// as its findings decide (findings)
+    #[arg(long, global = true, value_enum, default_value_t = TimeoutExit::Timeout)]
+    timeout_exit: TimeoutExit,
+
+    /// Answer yes to every confirmation
+    #[arg(long, global = true, conflicts_with = "no")]
+    yes: bool,
+
+    /// Answer no to every confirmation
+    #[arg(long, global = true)]
+    no: bool,
+
+    /// Tag every status with text such as [MAJOR] or [HIGH] instead of
+    /// telling them apart by color
+    #[arg(long, global = true)]
+    symbols: bool,
+
+    /// Answer registry and advisory lookups, cargo runs and prompts from
+    /// this scenario file, for end-to-end testing (see CONTRIBUTING.md)
+    #[arg(long, global = true, hide = true, value_name = "SCENARIO")]
+    test_mode: Option<PathBuf>,
+
+    /// With --test-mode, the seed deciding which lookups an injected
+    /// failure rate fails, instead of the scenario's
+    #[arg(
+        long,
+        global = true,
+        hide = true,
+        value_name = "N",
+        requires = "test_mode"
+    )]
+    seed: Option<u64>,
+}
+
+/// The exit code of a run `--timeout` cut short
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TimeoutExit {
+    /// Exit as the findings decide, as if the run had finished
+    Findings,
+    /// Exit 1, like a failed run
+    Error,
+    /// Exit 124, like timeout(1)
+    Timeout,
 }
 
 #[derive(Subcommand)]
@@ -20,13 +100,104 @@ enum Commands {
     /// Analyze your dependencies and show update availability
     #[command(alias = "c")]
     Check {
+        /// Check only this dependency, in detail
+        #[arg(
+            value_name = "CRATE",
+            conflicts_with_all = ["workspace", "package", "redundancy", "stats", "owners", "pre", "explain_skipped", "api_diff"]
+        )]
+        crate_name: Option<String>,
+
         /// Path to Cargo.toml (default: current directory)
         #[arg(short, long)]
         manifest_path: Option<String>,
 
-        /// Show detailed information
+        /// Show detailed information, and where the time went
         #[arg(short, long)]
         verbose: bool,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+
+        /// Output format; `--json` is short for `--format json`
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text, conflicts_with = "json")]
+        format: OutputFormat,
+
+        /// Write `--format csv` output to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Write OpenMetrics text for dashboards to this file
+        #[arg(long, value_name = "PATH")]
+        metrics_out: Option<PathBuf>,
+
+        /// Also write the report to this file, keeping the console output;
+        /// repeat for several files
+        #[arg(long, value_name = "PATH")]
+        output_file: Vec<PathBuf>,
+
+        /// The format of each --output-file, in the same order: json, csv
+        /// or markdown (default: from the file's extension)
+        #[arg(long, value_enum, value_name = "FORMAT", requires = "output_file")]
+        output_format: Vec<FileFormat>,
+
+        /// Ignore cached results and query the registry again
+        #[arg(long)]
+        refresh: bool,
+
+        /// Also suggest prereleases; without it, only dependencies already
+        /// on a prerelease move on to a newer one of the same version
+        #[arg(long)]
+        pre: bool,
+
+        /// Check every member of the workspace (the default for a virtual
+        /// manifest)
+        #[arg(long)]
+        workspace: bool,
+
+        /// Only the workspace member with this package name
+        #[arg(short, long)]
+        package: Option<String>,
+
+        /// Also report direct dependencies that duplicate each other's
+        /// functionality
+        #[arg(long, conflicts_with_all = ["workspace", "package"])]
+        redundancy: bool,
+
+        /// Show at most N entries per section, most significant first (0 shows all)
+        #[arg(long, default_value_t = 0)]
+        limit: usize,
+
+        /// Summarize the age of the versions in use and how far behind they are
+        #[arg(long, conflicts_with_all = ["workspace", "package"])]
+        stats: bool,
+
+        /// Look up crate owners on crates.io (cached for a day) to flag owner
+        /// changes since the last `snapshot save --owners`, and latest
+        /// releases published by someone who isn't an owner; with --stats,
+        /// also count distinct maintainer groups
+        #[arg(long, conflicts_with_all = ["workspace", "package"])]
+        owners: bool,
+
+        /// List every dependency that got no update check, or whose update a
+        /// policy holds back, with the reason (`--json` always includes them)
+        #[arg(long, conflicts_with_all = ["workspace", "package"])]
+        explain_skipped: bool,
+
+        /// Estimate how much of the public API each major update breaks,
+        /// with cargo-semver-checks when installed, else from docs.rs
+        #[arg(long, conflicts_with_all = ["workspace", "package"])]
+        api_diff: bool,
+
+        /// Also check the tools CI workflows pin with
+        /// `cargo install <crate> --version`
+        #[arg(long)]
+        workflows: bool,
+
+        /// With a crate name, also show the commit that added it to
+        /// Cargo.toml, from git history
+        #[arg(long, requires = "crate_name")]
+        history: bool,
     },
 
     /// Update dependencies interactively
@@ -43,6 +214,86 @@ enum Commands {
         /// Update all dependencies without prompting
         #[arg(short, long)]
         all: bool,
+
+        /// Ignore cached results and query the registry again
+        #[arg(long)]
+        refresh: bool,
+
+        /// Also update to prereleases; without it, only dependencies already
+        /// on a prerelease move on to a newer one of the same version
+        #[arg(long)]
+        pre: bool,
+
+        /// Show which dependencies each update adds or removes
+        #[arg(long)]
+        impact: bool,
+
+        /// Estimate how much of the public API each major update breaks,
+        /// with cargo-semver-checks when installed, else from docs.rs
+        #[arg(long)]
+        api_diff: bool,
+
+        /// Update every member of the workspace (the default for a virtual
+        /// manifest)
+        #[arg(long)]
+        workspace: bool,
+
+        /// Only the workspace member with this package name
+        #[arg(short, long)]
+        package: Option<String>,
+
+        /// Write the changelog entries of the applied (or, with --dry-run,
+        /// proposed) updates to this Markdown file
+        #[arg(long, value_name = "FILE")]
+        changelog: Option<PathBuf>,
+
+        /// Edit Cargo.toml even when git reports uncommitted changes to it
+        /// or to Cargo.lock
+        #[arg(long)]
+        allow_dirty: bool,
+
+        /// Keep `features` as declared across major updates instead of
+        /// checking them against the new version
+        #[arg(long)]
+        keep_features: bool,
+
+        /// Write the selected updates to this file as a plan for `apply`
+        /// instead of editing Cargo.toml
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["dry_run", "workspace", "package"])]
+        plan_out: Option<PathBuf>,
+
+        /// Run `cargo check` after editing, and put Cargo.toml and
+        /// Cargo.lock back as they were if it fails
+        #[arg(long, conflicts_with_all = ["dry_run", "plan_out", "workspace", "package"])]
+        verify: bool,
+
+        /// In a workspace, apply updates whose release needs a newer Rust
+        /// than a member's `rust-version` instead of holding them back
+        #[arg(long)]
+        ignore_rust_version: bool,
+
+        /// Apply exactly the updates this answers file approves, without
+        /// prompting; fails when an approved version is no longer the latest
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["all", "workspace", "package", "write_answers"]
+        )]
+        answers: Option<PathBuf>,
+
+        /// Write the suggested updates to this answers file for review
+        #[arg(
+            long,
+            value_name = "FILE",
+            requires = "dry_run",
+            conflicts_with_all = ["workspace", "package"]
+        )]
+        write_answers: Option<PathBuf>,
+
+        /// With --all, also apply updates the project's deny.toml bans or
+        /// whose license it denies
+        #[arg(long)]
+        ignore_deny: bool,
     },
 
     /// Fix dependency conflicts
@@ -52,9 +303,50 @@ enum Commands {
         #[arg(short, long)]
         manifest_path: Option<String>,
 
-        /// Automatically apply fixes without prompting
+        /// Automatically apply fixes without prompting. On a terminal, fix
+        /// otherwise walks through duplicated crates one at a time
         #[arg(short, long)]
         auto: bool,
+
+        /// Show the planned changes without applying them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Print the plan as JSON (with --dry-run)
+        #[arg(short, long, requires = "dry_run")]
+        json: bool,
+
+        /// Apply a plan saved from `--dry-run --json`, exactly as planned
+        #[arg(long, conflicts_with_all = ["dry_run", "json"])]
+        plan: Option<String>,
+
+        /// Run cargo under this rustup toolchain, as `cargo +<toolchain>`
+        #[arg(long)]
+        toolchain: Option<String>,
+
+        /// Read dependency data from this `cargo metadata --format-version 1`
+        /// output instead of running cargo
+        #[arg(long, value_name = "PATH")]
+        metadata_file: Option<PathBuf>,
+
+        /// Instead of fixing conflicts, suggest moving crates that several
+        /// workspace members declare to [workspace.dependencies]
+        #[arg(long, conflicts_with_all = ["auto", "plan"])]
+        suggest_inheritance: bool,
+
+        /// Make the edits --suggest-inheritance suggests, with backups
+        #[arg(long, requires = "suggest_inheritance", conflicts_with = "dry_run")]
+        apply: bool,
+    },
+
+    /// Apply a saved plan, refusing if Cargo.toml changed since it was made
+    Apply {
+        /// The plan file, as written by `update --plan-out`
+        plan: PathBuf,
+
+        /// Run cargo under this rustup toolchain, as `cargo +<toolchain>`
+        #[arg(long)]
+        toolchain: Option<String>,
     },
 
     /// Clean unused dependencies
@@ -67,6 +359,73 @@ enum Commands {
         /// Perform a dry run
         #[arg(short = 'n', long)]
         dry_run: bool,
+
+        /// Check every workspace member against its own code and aggregate
+        /// per crate (the default for a virtual manifest)
+        #[arg(long)]
+        workspace: bool,
+
+        /// Read every source file again instead of reusing the scans of
+        /// files unchanged since the last run
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Flag requirements that make builds drift (wildcards, git branches, ...)
+    /// and `[features]` entries naming dependencies that aren't there
+    #[command(alias = "l")]
+    Lint {
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+
+        /// Rewrite wildcard requirements to the version in Cargo.lock, and
+        /// remove `[features]` entries naming missing dependencies
+        #[arg(long)]
+        fix: bool,
+
+        /// In a workspace root, promote dependencies the members declare
+        /// with different features to `[workspace.dependencies]`
+        #[arg(long, conflicts_with = "fix")]
+        unify_features: bool,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Sort dependency tables and lay declarations out consistently
+    FmtDeps {
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+
+        /// Fail if Cargo.toml isn't laid out canonically, without changing it
+        #[arg(long, conflicts_with = "dry_run")]
+        check: bool,
+
+        /// Show the changes without writing them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+
+    /// Estimate the build-time cost of the dependency graph
+    Size {
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+
+        /// JSON output of `cargo build --timings=json` to attribute real seconds
+        #[arg(long)]
+        timings: Option<String>,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
     },
 
     /// Check dependency health (security, maintenance status)
@@ -79,6 +438,439 @@ enum Commands {
         /// Output as JSON
         #[arg(short, long)]
         json: bool,
+
+        /// Output format; `--json` is short for `--format json`
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text, conflicts_with = "json")]
+        format: OutputFormat,
+
+        /// Write `--format csv` output to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Write OpenMetrics text for dashboards to this file
+        #[arg(long, value_name = "PATH")]
+        metrics_out: Option<PathBuf>,
+
+        /// Also write the report to this file, keeping the console output;
+        /// repeat for several files
+        #[arg(long, value_name = "PATH")]
+        output_file: Vec<PathBuf>,
+
+        /// The format of each --output-file, in the same order: json, csv
+        /// or markdown (default: from the file's extension)
+        #[arg(long, value_enum, value_name = "FORMAT", requires = "output_file")]
+        output_format: Vec<FileFormat>,
+
+        /// Refresh the local advisory database before scanning
+        #[arg(long, conflicts_with = "offline")]
+        update_db: bool,
+
+        /// Scan from the local advisory database only, however old it is
+        #[arg(long)]
+        offline: bool,
+
+        /// Upgrade vulnerable dependencies to their first patched release
+        #[arg(long)]
+        fix: bool,
+
+        /// With --fix, show the planned changes without applying them
+        #[arg(long, requires = "fix")]
+        dry_run: bool,
+
+        /// Apply a plan saved from `--fix --dry-run --json`, exactly as planned
+        #[arg(long, conflicts_with_all = ["fix", "json", "update_db", "offline", "output_file"])]
+        plan: Option<String>,
+
+        /// Show at most N advisories, most significant first (0 shows all)
+        #[arg(long, default_value_t = 0)]
+        limit: usize,
+
+        /// Compare installed system libraries (OpenSSL, SQLite, ...) with
+        /// minimum safe versions, via pkg-config
+        #[arg(long)]
+        system_libs: bool,
+
+        /// Flag direct dependencies whose crates.io owners changed since the
+        /// last `snapshot save --owners`
+        #[arg(long)]
+        owners: bool,
+
+        /// Also scan the packages Cargo.lock resolves below the direct
+        /// dependencies, attributing each finding to its direct parents
+        #[arg(long)]
+        transitive: bool,
+
+        /// Look the most exposed scanned packages up on crates.io for
+        /// license, release and repository details: direct dependencies,
+        /// then crates parsing untrusted input, then the most downloaded
+        #[arg(long, conflicts_with = "offline")]
+        enrich: bool,
+
+        /// Also look through the source tree for crates copied into it,
+        /// which no manifest records: nested Cargo.toml files, license
+        /// headers over #![crate_name], directories named after crates
+        #[arg(long)]
+        scan_embedded: bool,
+
+        /// Fail when a dependency runs a build script or proc-macro that
+        /// the newest snapshot recording them didn't, unless accepted with
+        /// `cargo sane accept --build-script`
+        #[arg(long)]
+        fail_on_new_build_scripts: bool,
+
+        /// List the advisories of every workspace member under its name,
+        /// looking each package version up once (the default for a virtual
+        /// manifest)
+        #[arg(long, conflicts_with_all = ["fix", "plan", "metrics_out", "output_file", "system_libs", "owners", "enrich", "scan_embedded", "fail_on_new_build_scripts"])]
+        workspace: bool,
+
+        /// With --enrich, look up at most N packages instead of the config's
+        /// `enrich_limit` (0 looks up all)
+        #[arg(long, value_name = "N", requires = "enrich")]
+        enrich_limit: Option<usize>,
+
+        /// Read dependency data from this `cargo metadata --format-version 1`
+        /// output instead of running cargo
+        #[arg(long, value_name = "PATH")]
+        metadata_file: Option<PathBuf>,
+    },
+
+    /// Accept a known advisory or duplicated crate as a reviewed risk, so
+    /// health and fix report it apart from the findings needing action
+    Accept {
+        /// Advisory id (e.g. RUSTSEC-2020-0071), or a crate name with
+        /// --conflict or --build-script
+        #[arg(required_unless_present = "list")]
+        id: Option<String>,
+
+        /// Accept the duplicated versions of the crate named by ID
+        #[arg(long)]
+        conflict: bool,
+
+        /// Accept the build script or proc-macro of the crate named by ID
+        #[arg(long, conflicts_with = "conflict")]
+        build_script: bool,
+
+        /// Why the risk is acceptable
+        #[arg(long, required_unless_present_any = ["remove", "list"])]
+        reason: Option<String>,
+
+        /// Date (YYYY-MM-DD) from which the acceptance no longer applies
+        #[arg(long)]
+        expires: Option<String>,
+
+        /// Who accepted the risk (defaults to $USER)
+        #[arg(long)]
+        by: Option<String>,
+
+        /// Remove the acceptance instead of recording it
+        #[arg(long, conflicts_with_all = ["reason", "expires", "by"])]
+        remove: bool,
+
+        /// List recorded acceptances, marking expired ones
+        #[arg(long, conflicts_with_all = ["id", "conflict", "build_script", "reason", "expires", "by", "remove"])]
+        list: bool,
+
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+    },
+
+    /// Hide a crate's update from check and update until a newer release
+    /// comes out or the snooze runs out
+    Snooze {
+        /// The crate whose update to put off
+        #[arg(required_unless_present_any = ["list", "clear"])]
+        name: Option<String>,
+
+        /// Snooze releases up to this version (default: the current update
+        /// target)
+        #[arg(long, value_name = "VERSION")]
+        until_version: Option<String>,
+
+        /// How long the snooze lasts, e.g. 30d or 2w (default: until a newer
+        /// release)
+        #[arg(long = "for", value_name = "LENGTH")]
+        length: Option<String>,
+
+        /// List recorded snoozes, marking expired ones
+        #[arg(long, conflicts_with_all = ["name", "until_version", "length", "clear"])]
+        list: bool,
+
+        /// Remove the crate's snooze, or every snooze without a crate
+        #[arg(long, conflicts_with_all = ["until_version", "length"])]
+        clear: bool,
+
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+    },
+
+    /// Show the changes cargo-sane made, from .cargo-sane/audit.log
+    Audit {
+        /// Only changes on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only changes to this crate
+        #[arg(long = "crate", value_name = "NAME")]
+        name: Option<String>,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+    },
+
+    /// Save dependency snapshots and compare against them
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+
+    /// Show, clear and prune the caches under .cargo-sane
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+
+    /// Compare dependency versions and features with another project, and
+    /// suggest a version for both to converge on
+    Compare {
+        /// The other project: a Cargo.toml, its directory, or a git URL
+        #[arg(long, value_name = "PATH|URL")]
+        with: String,
+
+        /// Output as JSON
+        #[arg(short, long, conflicts_with = "markdown")]
+        json: bool,
+
+        /// Output as Markdown
+        #[arg(long)]
+        markdown: bool,
+
+        /// Don't look releases up on crates.io; suggest the newer of the two
+        /// versions in use
+        #[arg(long)]
+        offline: bool,
+
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+    },
+
+    /// Check every crates.io dependency added since a git revision: its
+    /// advisories, license, maintenance, footprint, owners and overlap with
+    /// existing dependencies, as a Markdown checklist for the pull request
+    Intake {
+        /// The git revision to compare Cargo.toml against
+        #[arg(long, value_name = "REV", default_value = "HEAD")]
+        from: String,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+    },
+
+    /// Combined check, health and conflict report
+    Report {
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+
+        /// Include what changed since this snapshot tag or date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Include what changed since the last --digest was written
+        #[arg(long, conflicts_with = "since")]
+        since_last: bool,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+
+        /// Show at most N dependencies needing attention (0 shows all; a
+        /// digest then lists 15 outstanding issues)
+        #[arg(long, default_value_t = 0)]
+        limit: usize,
+
+        /// Count distinct maintainer groups (one crates.io request per
+        /// dependency)
+        #[arg(long)]
+        owners: bool,
+
+        /// Write OpenMetrics text for dashboards to this file
+        #[arg(long, value_name = "PATH")]
+        metrics_out: Option<PathBuf>,
+
+        /// Also write the report to this file, keeping the console output;
+        /// repeat for several files
+        #[arg(long, value_name = "PATH")]
+        output_file: Vec<PathBuf>,
+
+        /// The format of each --output-file, in the same order: json, or
+        /// markdown for a digest (default: from the file's extension)
+        #[arg(long, value_enum, value_name = "FORMAT", requires = "output_file")]
+        output_format: Vec<FileFormat>,
+
+        /// Write a Markdown digest of what changed and what is still
+        /// outstanding to this file, and remember its state for --since-last
+        #[arg(long, value_name = "PATH")]
+        digest: Option<PathBuf>,
+
+        /// Read dependency data from this `cargo metadata --format-version 1`
+        /// output instead of running cargo
+        #[arg(long, value_name = "PATH")]
+        metadata_file: Option<PathBuf>,
+
+        /// Show who added each dependency to Cargo.toml and when, from git
+        /// history (cached until HEAD or Cargo.toml changes)
+        #[arg(long)]
+        history: bool,
+
+        /// Run this program as an analyzer: it reads the project's check,
+        /// advisory and conflict results as JSON on stdin and prints findings
+        /// on stdout (see `cargo sane schema plugin-input` and
+        /// `plugin-output`); repeat for several
+        #[arg(long, value_name = "PROGRAM")]
+        plugin: Vec<PathBuf>,
+
+        /// Exit 1 when an analyzer fails or a finding reaches this severity;
+        /// advisories count too, critical and high ones as errors and the
+        /// rest as warnings
+        #[arg(long, value_enum, value_name = "SEVERITY")]
+        fail_on: Option<LintSeverity>,
+    },
+
+    /// A guided first run: what check, conflict detection and health find
+    /// in this project, and settings to start from
+    Tour {
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+    },
+
+    /// Print every default in effect here: settings, cache lifetimes,
+    /// lookup sources, and the versions of the exit code, classification
+    /// and output contracts
+    Defaults {
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Print the JSON Schema of a command's --json output
+    Schema {
+        /// Which command's output
+        #[arg(value_enum)]
+        command: SchemaKind,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotAction {
+    /// Record the current dependency state under .cargo-sane/snapshots
+    Save {
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+
+        /// Name the snapshot; tagged snapshots are never pruned
+        #[arg(short, long)]
+        tag: Option<String>,
+
+        /// Record each dependency's crates.io owners, the baseline for
+        /// `check --owners` and `health --owners`
+        #[arg(long)]
+        owners: bool,
+    },
+
+    /// Compare the current state with a snapshot tag or date (YYYY-MM-DD)
+    Diff {
+        /// Snapshot tag or date
+        reference: String,
+
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// List saved snapshots
+    List {
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Where the caches are, with their sizes, entry counts and ages
+    Status {
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+
+        /// Output as JSON
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Delete caches; commands fetch or work out their content again
+    #[command(group(
+        clap::ArgGroup::new("caches")
+            .args(["registry", "advisories", "metadata", "all"])
+            .required(true)
+            .multiple(true)
+    ))]
+    Clear {
+        /// Answers from crates.io, its index and docs.rs
+        #[arg(long)]
+        registry: bool,
+
+        /// The local advisory database
+        #[arg(long)]
+        advisories: bool,
+
+        /// What was worked out from the project, such as git history
+        #[arg(long)]
+        metadata: bool,
+
+        /// Every cache
+        #[arg(long, conflicts_with_all = ["registry", "advisories", "metadata"])]
+        all: bool,
+
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
+    },
+
+    /// Delete corrupt caches and those past their lifetime, then the oldest
+    /// until the rest fit the config's `cache_max_size_mb`
+    Gc {
+        /// Show what would be deleted without deleting it
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Path to Cargo.toml
+        #[arg(short, long)]
+        manifest_path: Option<String>,
     },
 }
 
@@ -93,32 +885,421 @@ fn main() -> Result<()> {
         args
     };
 
+    // Legacy Windows consoles need ANSI colors switched on explicitly
+    #[cfg(windows)]
+    let _ = colored::control::set_virtual_terminal(true);
+
     let cli = Cli::parse_from(args);
+    if let Some(mode) = cli.progress {
+        ProgressMode::set_preference(mode);
+    }
+    if let Some(path) = cli.lockfile_path {
+        Lockfile::set_override(path);
+    }
+    if cli.no_workspace_discovery {
+        Workspace::set_standalone();
+    }
+    if cli.symbols {
+        output::use_symbols();
+    }
+    if cli.yes || cli.no {
+        prompt::answer_all(cli.yes);
+    }
+    if let Some(path) = cli.test_mode {
+        Scenario::activate(&path, cli.seed)?;
+    }
+    if let Some(secs) = cli.timeout {
+        Cancellation::set_global(Cancellation::with_deadline(Duration::from_secs(secs)));
+    }
+    let timeout_exit = cli.timeout_exit;
 
     // Import commands module
-    use cargo_sane::cli::commands;
+    use cargo_sane::cli::commands::{self, CheckOptions};
 
-    match cli.command {
+    let result = match cli.command {
         Commands::Check {
+            crate_name,
             manifest_path,
             verbose,
-        } => commands::check_command(manifest_path, verbose),
+            json,
+            format,
+            output,
+            metrics_out,
+            refresh,
+            pre,
+            workspace,
+            package,
+            redundancy,
+            limit,
+            stats,
+            owners,
+            explain_skipped,
+            api_diff,
+            workflows,
+            history,
+            output_file,
+            output_format,
+        } => {
+            let format = format.or_json(json);
+            let output_files = OutputFile::pair(
+                output_file,
+                output_format,
+                &[FileFormat::Json, FileFormat::Csv, FileFormat::Markdown],
+                "check",
+            )?;
+            if verbose {
+                timings::enable();
+            }
+            // Freshness budget violations and a Cargo.lock out of step with
+            // the requirements fail the run so CI can enforce them
+            let options = CheckOptions {
+                verbose,
+                format,
+                output_path: output,
+                metrics_out,
+                refresh,
+                pre,
+                workspace,
+                package,
+                redundancy,
+                limit,
+                stats,
+                owners,
+                explain_skipped,
+                api_diff,
+                workflows,
+                crate_name,
+                history,
+                output_files,
+            };
+            let passed = commands::check_command(manifest_path, options)?;
+            exit_if_timed_out(timeout_exit);
+            if !passed {
+                std::process::exit(EXIT_FAILURE);
+            }
+            Ok(())
+        }
         Commands::Update {
             manifest_path,
             dry_run,
             all,
-        } => commands::update_command(manifest_path, dry_run, all),
+            refresh,
+            pre,
+            impact,
+            api_diff,
+            workspace,
+            package,
+            changelog,
+            allow_dirty,
+            keep_features,
+            plan_out,
+            verify,
+            ignore_rust_version,
+            answers,
+            write_answers,
+            ignore_deny,
+        } => {
+            commands::update_command(
+                manifest_path,
+                dry_run,
+                all,
+                refresh,
+                pre,
+                impact,
+                api_diff,
+                workspace,
+                package,
+                changelog,
+                allow_dirty,
+                keep_features,
+                plan_out,
+                verify,
+                ignore_rust_version,
+                answers,
+                write_answers,
+                ignore_deny,
+            )?;
+            Ok(())
+        }
         Commands::Fix {
             manifest_path,
             auto,
-        } => commands::fix_command(manifest_path, auto),
+            dry_run,
+            json,
+            plan,
+            toolchain,
+            metadata_file,
+            suggest_inheritance,
+            apply,
+        } => {
+            use_metadata_file(metadata_file);
+            commands::fix_command(
+                manifest_path,
+                auto,
+                dry_run,
+                json,
+                plan,
+                toolchain,
+                suggest_inheritance,
+                apply,
+            )
+        }
+        Commands::Apply { plan, toolchain } => commands::apply_command(plan, toolchain),
         Commands::Clean {
             manifest_path,
             dry_run,
-        } => commands::clean_command(manifest_path, dry_run),
+            workspace,
+            no_cache,
+            json,
+        } => commands::clean_command(manifest_path, dry_run, workspace, no_cache, json),
+        Commands::Lint {
+            manifest_path,
+            fix,
+            unify_features,
+            json,
+        } => {
+            // Findings fail the run so CI can enforce the lint
+            if !commands::lint_command(manifest_path, fix, unify_features, json)? {
+                std::process::exit(EXIT_FAILURE);
+            }
+            Ok(())
+        }
+        Commands::FmtDeps {
+            manifest_path,
+            check,
+            dry_run,
+        } => {
+            // Under --check, a manifest needing changes fails the run
+            if !commands::fmt_deps_command(manifest_path, check, dry_run)? {
+                std::process::exit(EXIT_FAILURE);
+            }
+            Ok(())
+        }
+        Commands::Size {
+            manifest_path,
+            timings,
+            json,
+        } => commands::size_command(manifest_path, timings, json),
         Commands::Health {
             manifest_path,
             json,
-        } => commands::health_command(manifest_path, json),
+            format,
+            output,
+            metrics_out,
+            update_db,
+            offline,
+            fix,
+            dry_run,
+            plan,
+            limit,
+            system_libs,
+            owners,
+            transitive,
+            enrich,
+            enrich_limit,
+            scan_embedded,
+            fail_on_new_build_scripts,
+            workspace,
+            metadata_file,
+            output_file,
+            output_format,
+        } => {
+            use_metadata_file(metadata_file);
+            let output_files = OutputFile::pair(
+                output_file,
+                output_format,
+                &[FileFormat::Json, FileFormat::Csv, FileFormat::Markdown],
+                "health",
+            )?;
+            let format = format.or_json(json);
+            // New build-time code fails the run so CI can gate on it
+            let passed = commands::health_command(
+                manifest_path,
+                format,
+                output,
+                metrics_out,
+                update_db,
+                offline,
+                fix,
+                dry_run,
+                plan,
+                limit,
+                system_libs,
+                owners,
+                transitive,
+                enrich.then_some(enrich_limit),
+                scan_embedded,
+                fail_on_new_build_scripts,
+                workspace,
+                output_files,
+            )?;
+            exit_if_timed_out(timeout_exit);
+            if !passed {
+                std::process::exit(EXIT_FAILURE);
+            }
+            Ok(())
+        }
+        Commands::Accept {
+            id,
+            conflict,
+            build_script,
+            reason,
+            expires,
+            by,
+            remove,
+            list,
+            manifest_path,
+        } => commands::accept_command(
+            manifest_path,
+            id,
+            conflict,
+            build_script,
+            reason,
+            expires,
+            by,
+            remove,
+            list,
+        ),
+        Commands::Snooze {
+            name,
+            until_version,
+            length,
+            list,
+            clear,
+            manifest_path,
+        } => commands::snooze_command(manifest_path, name, until_version, length, list, clear),
+        Commands::Audit {
+            since,
+            name,
+            json,
+            manifest_path,
+        } => commands::audit_command(manifest_path, since, name, json),
+        Commands::Snapshot { action } => match action {
+            SnapshotAction::Save {
+                manifest_path,
+                tag,
+                owners,
+            } => commands::snapshot_save_command(manifest_path, tag, owners),
+            SnapshotAction::Diff {
+                reference,
+                manifest_path,
+                json,
+            } => commands::snapshot_diff_command(manifest_path, reference, json),
+            SnapshotAction::List { manifest_path } => {
+                commands::snapshot_list_command(manifest_path)
+            }
+        },
+        Commands::Compare {
+            with,
+            json,
+            markdown,
+            offline,
+            manifest_path,
+        } => commands::compare_command(manifest_path, &with, json, markdown, offline),
+        Commands::Intake {
+            from,
+            json,
+            manifest_path,
+        } => commands::intake_command(manifest_path, &from, json),
+        Commands::Cache { action } => match action {
+            CacheAction::Status {
+                manifest_path,
+                json,
+            } => commands::cache_status_command(manifest_path, json),
+            CacheAction::Clear {
+                registry,
+                advisories,
+                metadata,
+                all,
+                manifest_path,
+            } => {
+                let kinds = [
+                    (registry, CacheKind::Registry),
+                    (advisories, CacheKind::Advisories),
+                    (metadata, CacheKind::Metadata),
+                ]
+                .into_iter()
+                .filter(|(selected, _)| all || *selected)
+                .map(|(_, kind)| kind)
+                .collect();
+                commands::cache_clear_command(manifest_path, kinds)
+            }
+            CacheAction::Gc {
+                dry_run,
+                manifest_path,
+            } => commands::cache_gc_command(manifest_path, dry_run),
+        },
+        Commands::Report {
+            manifest_path,
+            since,
+            json,
+            limit,
+            since_last,
+            owners,
+            metrics_out,
+            digest,
+            metadata_file,
+            history,
+            output_file,
+            output_format,
+            plugin,
+            fail_on,
+        } => {
+            use_metadata_file(metadata_file);
+            let output_files = OutputFile::pair(
+                output_file,
+                output_format,
+                &[FileFormat::Json, FileFormat::Markdown],
+                "report",
+            )?;
+            let passed = commands::report_command(
+                manifest_path,
+                since,
+                json,
+                limit,
+                owners,
+                metrics_out,
+                digest,
+                since_last,
+                history,
+                output_files,
+                plugin,
+                fail_on,
+            )?;
+            exit_if_timed_out(timeout_exit);
+            if !passed {
+                std::process::exit(EXIT_FAILURE);
+            }
+            Ok(())
+        }
+        Commands::Tour { manifest_path } => commands::tour_command(manifest_path),
+        Commands::Defaults {
+            manifest_path,
+            json,
+        } => commands::defaults_command(manifest_path, json),
+        Commands::Schema { command } => commands::schema_command(command),
+    };
+    result?;
+    exit_if_timed_out(timeout_exit);
+    Ok(())
+}
+
+/// Exit as `policy` says when `--timeout` stopped a lookup
+fn exit_if_timed_out(policy: TimeoutExit) {
+    if !Cancellation::global().cut_short() {
+        return;
+    }
+    match policy {
+        TimeoutExit::Findings => {}
+        TimeoutExit::Error => std::process::exit(EXIT_FAILURE),
+        TimeoutExit::Timeout => std::process::exit(EXIT_TIMEOUT),
+    }
+}
+
+/// Read `cargo metadata` output from `--metadata-file`, when given, instead
+/// of running cargo
+fn use_metadata_file(path: Option<PathBuf>) {
+    if let Some(path) = path {
+        Metadata::set_file(path);
     }
 }
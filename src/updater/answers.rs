@@ -0,0 +1,255 @@
+//! Pre-approved answers for scripted `update` runs
+//!
+//! An answers file lists the updates a reviewer approved, one target version
+//! or "skip" per crate:
+//!
+//! ```toml
+//! [updates]
+//! serde = "1.0.200"
+//! tokio = "skip"
+//! ```
+//!
+//! `update --answers` applies exactly those, without prompting, and fails
+//! when the registry has moved on from what was approved, so CI never
+//! applies an update nobody reviewed. `update --dry-run --write-answers`
+//! writes one from the current suggestions.
+
+use crate::core::dependency::{Dependency, UpdateType};
+use crate::Result;
+use anyhow::Context;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// What the answers say about one crate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Answer {
+    /// Update to exactly this version
+    Update(Version),
+    /// Leave it as it is
+    Skip,
+}
+
+impl TryFrom<String> for Answer {
+    type Error = String;
+
+    fn try_from(text: String) -> std::result::Result<Self, String> {
+        if text == "skip" {
+            return Ok(Answer::Skip);
+        }
+        Version::parse(&text)
+            .map(Answer::Update)
+            .map_err(|e| format!("expected a version or \"skip\", got \"{}\": {}", text, e))
+    }
+}
+
+impl From<Answer> for String {
+    fn from(answer: Answer) -> Self {
+        answer.to_string()
+    }
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Answer::Update(version) => write!(f, "{}", version),
+            Answer::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Answers {
+    #[serde(default)]
+    pub updates: BTreeMap<String, Answer>,
+}
+
+/// The updates an answers file approves, and those it doesn't mention
+#[derive(Debug)]
+pub struct Selection<'a> {
+    pub approved: Vec<&'a Dependency>,
+    /// Crates with an update available the file says nothing about
+    pub unmentioned: Vec<&'a Dependency>,
+}
+
+impl Answers {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).context(format!("Failed to parse answers {}", path.display()))
+    }
+
+    /// Every update in `updates` approved at its latest version
+    pub fn suggest(updates: &[&Dependency]) -> Self {
+        let updates = updates
+            .iter()
+            .filter_map(|dep| {
+                let latest = dep.latest_version.clone()?;
+                Some((dep.name.clone(), Answer::Update(latest)))
+            })
+            .collect();
+        Self { updates }
+    }
+
+    /// The file's content, with the version each update starts from as a
+    /// comment for the reviewer
+    pub fn render(&self, updates: &[&Dependency]) -> String {
+        let mut out = String::from(
+            "# Updates for `cargo sane update --answers`. Set a crate to \"skip\" to\n\
+             # leave it as it is; crates left out are reported, not updated.\n\n[updates]\n",
+        );
+        for (name, answer) in &self.updates {
+            let line = format!("{} = \"{}\"", name, answer);
+            match updates.iter().find(|d| d.name == *name) {
+                Some(dep) => out.push_str(&format!(
+                    "{:<40} # from {} ({})\n",
+                    line,
+                    dep.current_version,
+                    kind(dep.update_type())
+                )),
+                None => out.push_str(&format!("{}\n", line)),
+            }
+        }
+        out
+    }
+
+    pub fn write(&self, path: &Path, updates: &[&Dependency]) -> Result<()> {
+        fs::write(path, self.render(updates)).context(format!("Failed to write {}", path.display()))
+    }
+
+    /// The dependencies the answers approve an update of. `available` are
+    /// the dependencies with an update; `offered` the subset a prompt would
+    /// show, which are reported when the file leaves them out. Fails, naming
+    /// every mismatch, when an approved version isn't the latest any more or
+    /// the crate has no update at all.
+    pub fn select<'a>(
+        &self,
+        available: &[&'a Dependency],
+        offered: &[&'a Dependency],
+    ) -> Result<Selection<'a>> {
+        let mut approved = Vec::new();
+        let mut problems = Vec::new();
+        for (name, answer) in &self.updates {
+            let Answer::Update(version) = answer else {
+                continue;
+            };
+            let matching: Vec<&Dependency> = available
+                .iter()
+                .copied()
+                .filter(|d| d.name == *name)
+                .collect();
+            if matching.is_empty() {
+                problems.push(format!(
+                    "{}: approved at {}, but it has no update available",
+                    name, version
+                ));
+                continue;
+            }
+            for dep in matching {
+                match &dep.latest_version {
+                    Some(latest) if latest == version => approved.push(dep),
+                    Some(latest) => problems.push(format!(
+                        "{}: approved at {}, but the latest is now {}",
+                        name, version, latest
+                    )),
+                    None => problems.push(format!(
+                        "{}: approved at {}, but it has no update available",
+                        name, version
+                    )),
+                }
+            }
+        }
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "The answers file no longer matches the available updates:\n  {}",
+                problems.join("\n  ")
+            );
+        }
+
+        let unmentioned = offered
+            .iter()
+            .copied()
+            .filter(|d| !self.updates.contains_key(&d.name))
+            .collect();
+        Ok(Selection {
+            approved,
+            unmentioned,
+        })
+    }
+}
+
+fn kind(update_type: UpdateType) -> &'static str {
+    match update_type {
+        UpdateType::Major => "major",
+        UpdateType::Minor => "minor",
+        UpdateType::Patch => "patch",
+        UpdateType::UpToDate => "up to date",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dependency::test_support::dep;
+
+    #[test]
+    fn test_round_trip() {
+        let serde = dep("serde", "1.0.100", "1.0.200");
+        let syn = dep("syn", "1.0.0", "2.0.48");
+        let updates = [&serde, &syn];
+        let mut answers = Answers::suggest(&updates);
+        answers.updates.insert("syn".to_string(), Answer::Skip);
+
+        let text = answers.render(&updates);
+        assert!(text.contains("serde = \"1.0.200\""), "{}", text);
+        assert!(text.contains("# from 1.0.100 (patch)"), "{}", text);
+        assert!(text.contains("syn = \"skip\""), "{}", text);
+        assert_eq!(toml::from_str::<Answers>(&text).unwrap(), answers);
+
+        let invalid = toml::from_str::<Answers>("[updates]\nserde = \"latest\"\n");
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        let serde = dep("serde", "1.0.100", "1.0.200");
+        let syn = dep("syn", "1.0.0", "2.0.48");
+        let nix = dep("nix", "0.20.0", "0.20.5");
+        let available = [&serde, &syn, &nix];
+        let answers: Answers =
+            toml::from_str("[updates]\nserde = \"1.0.200\"\nsyn = \"skip\"\n").unwrap();
+
+        let selection = answers.select(&available, &available).unwrap();
+        let approved: Vec<&str> = selection.approved.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(approved, vec!["serde"]);
+        let unmentioned: Vec<&str> = selection
+            .unmentioned
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect();
+        assert_eq!(unmentioned, vec!["nix"]);
+
+        // Drift and vanished updates are all reported
+        let answers: Answers =
+            toml::from_str("[updates]\nserde = \"1.0.199\"\nrand = \"0.9.0\"\n").unwrap();
+        let error = answers
+            .select(&available, &available)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("serde: approved at 1.0.199, but the latest is now 1.0.200"),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("rand: approved at 0.9.0, but it has no update available"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Carry declared features across a major update
+//!
+//! Major releases rename, split and drop features. Keeping `features = [...]`
+//! as it was fails the build on the first feature the target no longer has,
+//! so each declared feature is checked against the target's index entry.
+
+use crate::core::manifest::DependencySection;
+use crate::utils::sparse_index::IndexEntry;
+use semver::Version;
+use serde::Serialize;
+
+/// How the declared features of one dependency fare in the target release
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FeatureReview {
+    pub name: String,
+    pub section: DependencySection,
+    pub target: Version,
+    /// The features as declared before the update
+    pub declared: Vec<String>,
+    /// Declared features the target doesn't have
+    pub missing: Vec<String>,
+    /// Declared features the target enables by default anyway
+    pub now_default: Vec<String>,
+    /// Every feature of the target, for choosing replacements
+    pub available: Vec<String>,
+}
+
+impl FeatureReview {
+    /// Whether every declared feature carries over as it is
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.now_default.is_empty()
+    }
+
+    /// Declared features that exist in the target, in their original order
+    pub fn present(&self) -> Vec<String> {
+        self.declared
+            .iter()
+            .filter(|f| !self.missing.contains(f))
+            .cloned()
+            .collect()
+    }
+
+    /// Features of the target that could stand in for the missing ones:
+    /// everything not declared already, apart from `default`
+    pub fn replacements(&self) -> Vec<&str> {
+        self.available
+            .iter()
+            .filter(|f| *f != "default" && !self.declared.contains(f))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Check `declared` features against `entry`, the index entry of the
+/// target release. Features only count as now-default when the declaration
+/// keeps default features on.
+pub fn review_features(
+    name: &str,
+    section: &DependencySection,
+    declared: &[String],
+    default_features: bool,
+    entry: &IndexEntry,
+) -> FeatureReview {
+    let available = entry.available_features();
+    let defaults = if default_features {
+        entry.default_features()
+    } else {
+        Default::default()
+    };
+    // `dep/feature` reaches into a dependency of the dependency; those
+    // aren't features of the target itself and are left alone
+    let own = |feature: &&String| !feature.contains('/');
+
+    FeatureReview {
+        name: name.to_string(),
+        section: section.clone(),
+        target: entry.vers.clone(),
+        declared: declared.to_vec(),
+        missing: declared
+            .iter()
+            .filter(own)
+            .filter(|f| !available.contains(*f))
+            .cloned()
+            .collect(),
+        now_default: declared
+            .iter()
+            .filter(own)
+            .filter(|f| defaults.contains(*f))
+            .cloned()
+            .collect(),
+        available: available.into_iter().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dependency::DependencyKind;
+    use crate::utils::sparse_index::parse_entries;
+
+    /// tokio-like feature maps: 0.2 had `tcp` and `dns`, 1.0 folded them
+    /// into `net` and made `macros` a default of the (made up) target
+    const INDEX: &str = r#"{"name":"tokio","vers":"0.2.25","deps":[],"features":{"default":[],"tcp":[],"dns":[],"macros":[],"rt-threaded":[]},"yanked":false}
+{"name":"tokio","vers":"1.0.0","deps":[],"features":{"default":["macros"],"net":[],"macros":[],"rt-multi-thread":[]},"yanked":false}
+"#;
+
+    fn review(declared: &[&str], default_features: bool) -> FeatureReview {
+        let entries = parse_entries(INDEX).unwrap();
+        let declared: Vec<String> = declared.iter().map(|f| f.to_string()).collect();
+        review_features(
+            "tokio",
+            &DependencySection::new(DependencyKind::Normal),
+            &declared,
+            default_features,
+            &entries[1],
+        )
+    }
+
+    #[test]
+    fn test_review_features() {
+        let review = review(&["tcp", "macros", "rt-threaded", "serde/std"], true);
+        assert_eq!(review.target, Version::new(1, 0, 0));
+        assert_eq!(review.missing, vec!["tcp", "rt-threaded"]);
+        assert_eq!(review.now_default, vec!["macros"]);
+        assert_eq!(review.present(), vec!["macros", "serde/std"]);
+        assert_eq!(review.replacements(), vec!["net", "rt-multi-thread"]);
+        assert!(!review.is_clean());
+    }
+
+    #[test]
+    fn test_defaults_only_count_when_enabled() {
+        let review = review(&["macros", "net"], false);
+        assert!(review.now_default.is_empty());
+        assert!(review.is_clean());
+    }
+}
@@ -0,0 +1,472 @@
+//! Canonical layout for dependency tables
+//!
+//! `fmt-deps` sorts each dependency table by name, writes short declarations
+//! as inline tables and long ones as `[dependencies.name]` sections, drops
+//! repeated features and lines up trailing comments. The edits go through
+//! toml_edit, so comments and everything outside the dependency tables stay
+//! as they were, and the result is parsed again and compared with the
+//! original before it's used.
+
+use crate::core::config::Settings;
+use crate::Result;
+use anyhow::Context;
+use std::collections::{BTreeMap, HashSet};
+use toml_edit::{DocumentMut, Item, Key, Table, Value};
+
+/// Declarations with more keys than this become table sections when the
+/// config doesn't say
+pub const DEFAULT_INLINE_MAX_KEYS: usize = Settings::DEFAULT.fmt_inline_max_keys;
+
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// Lay the dependency tables of `content` out canonically: declarations
+/// with at most `inline_max_keys` keys inline, longer ones as sections.
+/// Fails rather than return anything that means something else.
+pub fn format_dependencies(content: &str, inline_max_keys: usize) -> Result<String> {
+    let mut document: DocumentMut = content.parse().context("Failed to parse Cargo.toml")?;
+    for_each_dependency_table(document.as_table_mut(), |_, table| {
+        format_table(table, inline_max_keys)
+    });
+    let mut formatted = document.to_string();
+    // toml_edit writes the lines it touches with \n
+    if content.contains("\r\n") {
+        formatted = formatted.replace("\r\n", "\n").replace('\n', "\r\n");
+    }
+
+    if meaning(content)? != meaning(&formatted)? {
+        anyhow::bail!("Reformatting would change what Cargo.toml declares; leaving it as it is");
+    }
+    Ok(formatted)
+}
+
+/// Call `f` on `[dependencies]`, `[dev-dependencies]` and
+/// `[build-dependencies]`, their `[target.*]` variants and
+/// `[workspace.dependencies]`, along with the table's dotted path
+fn for_each_dependency_table(root: &mut Table, mut f: impl FnMut(&str, &mut Table)) {
+    let mut visit = |prefix: &str, parent: &mut Table| {
+        for name in DEPENDENCY_TABLES {
+            if let Some(table) = parent.get_mut(name).and_then(Item::as_table_mut) {
+                f(&format!("{}{}", prefix, name), table);
+            }
+        }
+    };
+    visit("", root);
+    if let Some(targets) = root.get_mut("target").and_then(Item::as_table_mut) {
+        for (predicate, target) in targets.iter_mut() {
+            if let Some(target) = target.as_table_mut() {
+                visit(&format!("target.{}.", predicate.display_repr()), target);
+            }
+        }
+    }
+    if let Some(workspace) = root.get_mut("workspace").and_then(Item::as_table_mut) {
+        if let Some(table) = workspace
+            .get_mut("dependencies")
+            .and_then(Item::as_table_mut)
+        {
+            f("workspace.dependencies", table);
+        }
+    }
+}
+
+/// A declaration formatting laid out differently or moved within its table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Restyled {
+    /// Dotted path of the table, e.g. `dependencies`
+    pub table: String,
+    pub name: String,
+    /// The declaration as written before and after, e.g. `serde = "1"` or
+    /// `[dependencies.serde] { version = "1" }` for a section
+    pub before: String,
+    pub after: String,
+}
+
+/// The declarations `after` writes differently from `before`, or puts
+/// elsewhere in their table, in table and name order
+pub fn restyled(before: &str, after: &str) -> Result<Vec<Restyled>> {
+    let old = layouts(before)?;
+    Ok(layouts(after)?
+        .into_iter()
+        .filter_map(|((table, name), (position, after))| {
+            let (old_position, before) = old.get(&(table.clone(), name.clone()))?;
+            (*old_position != position || *before != after).then(|| Restyled {
+                table,
+                name,
+                before: before.clone(),
+                after,
+            })
+        })
+        .collect())
+}
+
+/// Where each declaration sits in its table and how it's written, by table
+/// and name
+fn layouts(content: &str) -> Result<BTreeMap<(String, String), (usize, String)>> {
+    let mut document: DocumentMut = content.parse().context("Failed to parse Cargo.toml")?;
+    let mut layouts = BTreeMap::new();
+    for_each_dependency_table(document.as_table_mut(), |path, table| {
+        for (position, (name, item)) in table.iter().enumerate() {
+            let written = match item {
+                Item::Value(value) => {
+                    let mut value = value.clone();
+                    value.decor_mut().set_prefix("");
+                    format!("{} = {}", name, value.to_string().trim_end())
+                }
+                Item::Table(section) => {
+                    let mut inline = section.clone().into_inline_table();
+                    inline.fmt();
+                    format!("[{}.{}] {}", path, name, inline)
+                }
+                other => other.to_string(),
+            };
+            layouts.insert((path.to_string(), name.to_string()), (position, written));
+        }
+    });
+    Ok(layouts)
+}
+
+fn format_table(table: &mut Table, inline_max_keys: usize) {
+    let position = table.position();
+    for (mut key, item) in table.iter_mut() {
+        dedup_features(item);
+        if restyle(item, inline_max_keys) {
+            key.leaf_decor_mut().clear();
+        }
+        // Sections follow their table, in name order
+        if let (Item::Table(section), Some(position)) = (item, position) {
+            if !section.is_dotted() {
+                section.set_position(position);
+            }
+        }
+    }
+    table.sort_values();
+    align_comments(table);
+}
+
+/// Switch `item` to the style its number of keys calls for, returning
+/// whether it changed. Declarations with comments inside keep their style,
+/// since there's no telling where the comments would belong afterwards.
+fn restyle(item: &mut Item, inline_max_keys: usize) -> bool {
+    let (keys, plain) = match item {
+        Item::Value(Value::InlineTable(inline)) => (inline.len(), true),
+        Item::Table(table) => (table.len(), table.iter().all(|(_, value)| value.is_value())),
+        _ => return false,
+    };
+    if !plain {
+        return false;
+    }
+    let commented = match &*item {
+        Item::Value(Value::InlineTable(inline)) => inline.to_string().contains('#'),
+        other => other.to_string().contains('#'),
+    };
+
+    match std::mem::take(item) {
+        // `{ version = "1" }` is just "1", trailing comment and all
+        Item::Value(Value::InlineTable(inline))
+            if keys == 1 && inline.get("version").is_some_and(Value::is_str) =>
+        {
+            let mut version = inline.get("version").cloned().unwrap_or_else(|| "".into());
+            *version.decor_mut() = inline.decor().clone();
+            *item = Item::Value(version);
+            true
+        }
+        unchanged if commented => {
+            *item = unchanged;
+            false
+        }
+        Item::Value(Value::InlineTable(inline)) if keys > inline_max_keys => {
+            let mut section = inline.into_table();
+            section.set_implicit(false);
+            *item = Item::Table(section);
+            true
+        }
+        Item::Table(table) if keys <= inline_max_keys => {
+            *item = Item::Value(Value::InlineTable(table.into_inline_table()));
+            // Which may make it a plain version
+            restyle(item, inline_max_keys);
+            true
+        }
+        Item::Table(mut table) if table.is_dotted() => {
+            table.set_dotted(false);
+            *item = Item::Table(table);
+            true
+        }
+        unchanged => {
+            *item = unchanged;
+            false
+        }
+    }
+}
+
+/// Drop later repeats of a feature from `features = [...]`
+fn dedup_features(item: &mut Item) {
+    let features = match item {
+        Item::Value(Value::InlineTable(inline)) => inline.get_mut("features"),
+        Item::Table(table) => table.get_mut("features").and_then(Item::as_value_mut),
+        _ => None,
+    };
+    if let Some(Value::Array(features)) = features {
+        let mut seen = HashSet::new();
+        features.retain(|feature| feature.as_str().is_none_or(|f| seen.insert(f.to_string())));
+    }
+}
+
+/// Start the trailing comments of a table's one-line entries in the same
+/// column, one space past the longest of those lines
+fn align_comments(table: &mut Table) {
+    let widths: Vec<usize> = table
+        .iter()
+        .filter_map(|(name, item)| {
+            let key = table.key(name)?;
+            commented_width(key, item.as_value()?)
+        })
+        .collect();
+    let Some(column) = widths.iter().max().map(|width| width + 1) else {
+        return;
+    };
+
+    let names: Vec<String> = table.iter().map(|(name, _)| name.to_string()).collect();
+    for name in names {
+        let Some(key) = table.key(&name) else {
+            continue;
+        };
+        let Some(value) = table.get(&name).and_then(Item::as_value) else {
+            continue;
+        };
+        let Some(width) = commented_width(key, value) else {
+            continue;
+        };
+        let Some(value) = table.get_mut(&name).and_then(Item::as_value_mut) else {
+            continue;
+        };
+        let comment = decor_str(value.decor().suffix()).trim_start().to_string();
+        value
+            .decor_mut()
+            .set_suffix(format!("{}{}", " ".repeat(column - width), comment));
+    }
+}
+
+/// How wide the `key = value` line is, when it ends in a comment and fits
+/// on one line
+fn commented_width(key: &Key, value: &Value) -> Option<usize> {
+    if !decor_str(value.decor().suffix())
+        .trim_start()
+        .starts_with('#')
+    {
+        return None;
+    }
+    let mut bare = value.clone();
+    bare.decor_mut().clear();
+    let body = bare.to_string();
+    if body.contains('\n') {
+        return None;
+    }
+
+    let key_decor = key.leaf_decor();
+    // Only what follows the last line break shares the line
+    let indent = decor_str(key_decor.prefix())
+        .rsplit('\n')
+        .next()
+        .unwrap_or("");
+    let before_equals = key_decor
+        .suffix()
+        .map_or(" ", |suffix| suffix.as_str().unwrap_or(" "));
+    let after_equals = value
+        .decor()
+        .prefix()
+        .map_or(" ", |prefix| prefix.as_str().unwrap_or(" "));
+    Some(
+        indent.len()
+            + key.display_repr().len()
+            + before_equals.len()
+            + 1
+            + after_equals.len()
+            + body.len(),
+    )
+}
+
+fn decor_str(raw: Option<&toml_edit::RawString>) -> &str {
+    raw.and_then(|raw| raw.as_str()).unwrap_or("")
+}
+
+/// A line of a diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The lines of `after` against those of `before`, keeping as many lines
+/// as possible in common
+pub fn diff_lines<'a>(before: &'a str, after: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // common[i][j]: longest common run of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+            lines.push(DiffLine::Added(new[j]));
+            j += 1;
+        } else {
+            lines.push(DiffLine::Removed(old[i]));
+            i += 1;
+        }
+    }
+    lines
+}
+
+/// What `content` declares, as data. Features are deduplicated and
+/// `{ version = "1" }` read as "1", since Cargo doesn't tell them apart.
+fn meaning(content: &str) -> Result<toml::Table> {
+    let mut document: DocumentMut = content.parse().context("Failed to parse Cargo.toml")?;
+    for_each_dependency_table(document.as_table_mut(), |_, table| {
+        for (_, item) in table.iter_mut() {
+            dedup_features(item);
+            let version = match &*item {
+                Item::Value(Value::InlineTable(inline)) if inline.len() == 1 => {
+                    inline.get("version").cloned()
+                }
+                Item::Table(table) if table.len() == 1 => {
+                    table.get("version").and_then(Item::as_value).cloned()
+                }
+                _ => None,
+            };
+            if let Some(version) = version.filter(Value::is_str) {
+                *item = Item::Value(version);
+            }
+        }
+    });
+    toml::from_str(&document.to_string()).context("Failed to parse Cargo.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_dependencies() {
+        let content = r#"[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+tokio = { version = "1", features = ["rt", "macros", "rt"] }
+anyhow = "1" # errors
+serde = { version = "1.0" } # serialization
+clap = { version = "4", features = ["derive"], default-features = false, optional = true, package = "clap" }
+
+[dependencies.regex]
+version = "1"
+
+[dev-dependencies]
+tempfile = "3"
+assert_cmd = "2"
+"#;
+        let formatted = format_dependencies(content, 3).unwrap();
+        assert_eq!(
+            formatted,
+            r#"[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+anyhow = "1"  # errors
+regex = "1"
+serde = "1.0" # serialization
+tokio = { version = "1", features = ["rt", "macros"] }
+
+[dependencies.clap]
+version = "4"
+features = ["derive"]
+default-features = false
+optional = true
+package = "clap"
+
+[dev-dependencies]
+assert_cmd = "2"
+tempfile = "3"
+"#
+        );
+        // Already canonical
+        assert_eq!(format_dependencies(&formatted, 3).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_commented_sections_keep_their_style() {
+        let content = "[dependencies.regex]\n# pinned for MSRV\nversion = \"1\"\n";
+        assert_eq!(format_dependencies(content, 3).unwrap(), content);
+    }
+
+    #[test]
+    fn test_restyled() {
+        let before = "[dependencies]\nserde = { version = \"1\" }\nanyhow = \"1\"\n\n\
+                      [dependencies.regex]\nversion = \"1\"\n\n\
+                      [target.'cfg(unix)'.dependencies]\nlibc = \"0.2\"\n";
+        let after = format_dependencies(before, 3).unwrap();
+        let restyled: Vec<(String, String, String, String)> = restyled(before, &after)
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.table, r.name, r.before, r.after))
+            .collect();
+        let change = |name: &str, before: &str, after: &str| {
+            (
+                "dependencies".to_string(),
+                name.to_string(),
+                before.to_string(),
+                after.to_string(),
+            )
+        };
+        // anyhow only moved; libc is left out as it is
+        assert_eq!(
+            restyled,
+            [
+                change("anyhow", "anyhow = \"1\"", "anyhow = \"1\""),
+                change(
+                    "regex",
+                    "[dependencies.regex] { version = \"1\" }",
+                    "regex = \"1\""
+                ),
+                change("serde", "serde = { version = \"1\" }", "serde = \"1\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        assert_eq!(
+            diff_lines("a\nb\nc\n", "a\nc\nd\n"),
+            vec![
+                DiffLine::Same("a"),
+                DiffLine::Removed("b"),
+                DiffLine::Same("c"),
+                DiffLine::Added("d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_meaning_ignores_repeated_features() {
+        assert_eq!(
+            meaning("[dependencies]\nserde = { version = \"1\", features = [\"a\", \"a\"] }\n")
+                .unwrap(),
+            meaning("[dependencies]\nserde = { version = \"1\", features = [\"a\"] }\n").unwrap()
+        );
+    }
+}
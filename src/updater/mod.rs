@@ -1,5 +1,9 @@
 //! Dependency update logic
 
+pub mod answers;
+pub mod features;
+pub mod fmt_deps;
+pub mod plan;
 pub mod resolver;
 pub mod update;
 
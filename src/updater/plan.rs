@@ -0,0 +1,488 @@
+//! Planned dependency changes that can be reviewed and replayed
+//!
+//! The mutating commands first describe what they would do as a `Plan`.
+//! `--dry-run --json` prints it, `update --plan-out` saves one; `--plan <file>`
+//! and `apply` replay a saved plan exactly, refusing to run when Cargo.toml or
+//! Cargo.lock no longer match the ones it was made for.
+
+use crate::analyzer::build_units::DuplicateBuildUnit;
+use crate::core::dependency::Dependency;
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::{DependencySection, Manifest};
+use crate::core::version::SkipReason;
+use crate::updater::DependencyUpdater;
+use crate::utils::cache::fingerprint_bytes;
+use crate::utils::cargo::{self, CargoOptions};
+use crate::Result;
+use anyhow::Context;
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Plan {
+    pub manifest: PathBuf,
+    /// Fingerprint of Cargo.toml and its Cargo.lock when the plan was made
+    pub manifest_hash: String,
+    pub actions: Vec<PlannedAction>,
+    /// Packages `fix` found compiled more than once at one version; they
+    /// take no action, so replaying the plan ignores them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicate_build_units: Vec<DuplicateBuildUnit>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionType {
+    /// Change a version requirement in Cargo.toml
+    ManifestEdit,
+    /// Move a locked version with `cargo update --precise`
+    LockfileUpdate,
+    /// Nothing can be changed automatically; listed for the record
+    NoActionAvailable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PlannedAction {
+    #[serde(rename = "type")]
+    pub action: ActionType,
+    #[serde(rename = "crate")]
+    pub name: String,
+    /// A locked version, or the requirement for manifest edits
+    pub from_version: String,
+    /// None for a lockfile update leaving the version to cargo, the newest
+    /// the manifest allows
+    pub to_version: Option<String>,
+    /// An advisory id, the conflict being resolved, or for `update` plans
+    /// why the target was chosen: `latest`, `latest-non-yanked`, `policy`
+    /// or `advisory`
+    pub reason: String,
+    /// The table a manifest edit applies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section: Option<DependencySection>,
+    /// The exact command or TOML change that will be executed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change: Option<String>,
+}
+
+impl PlannedAction {
+    pub fn lockfile_update(
+        manifest: &Manifest,
+        name: &str,
+        from: &Version,
+        to: &Version,
+        reason: String,
+    ) -> Self {
+        Self {
+            action: ActionType::LockfileUpdate,
+            name: name.to_string(),
+            from_version: from.to_string(),
+            to_version: Some(to.to_string()),
+            reason,
+            section: None,
+            change: Some(format!(
+                "cargo update --package {}@{} --precise {} --manifest-path {}",
+                name,
+                from,
+                to,
+                manifest.path.display()
+            )),
+        }
+    }
+
+    /// Let cargo move `name` off the locked `from`, to the newest version
+    /// the manifest allows
+    pub fn lockfile_refresh(
+        manifest: &Manifest,
+        name: &str,
+        from: &Version,
+        reason: String,
+    ) -> Self {
+        Self {
+            action: ActionType::LockfileUpdate,
+            name: name.to_string(),
+            from_version: from.to_string(),
+            to_version: None,
+            reason,
+            section: None,
+            change: Some(format!(
+                "cargo update --package {}@{} --manifest-path {}",
+                name,
+                from,
+                manifest.path.display()
+            )),
+        }
+    }
+
+    pub fn manifest_edit(
+        section: &DependencySection,
+        name: &str,
+        from: &str,
+        to: &str,
+        reason: String,
+    ) -> Self {
+        Self {
+            action: ActionType::ManifestEdit,
+            name: name.to_string(),
+            from_version: from.to_string(),
+            to_version: Some(to.to_string()),
+            reason,
+            section: Some(section.clone()),
+            change: Some(format!(
+                "[{}] {}: \"{}\" -> \"{}\"",
+                section, name, from, to
+            )),
+        }
+    }
+
+    pub fn unavailable(name: &str, from: &str, reason: String) -> Self {
+        Self {
+            action: ActionType::NoActionAvailable,
+            name: name.to_string(),
+            from_version: from.to_string(),
+            to_version: None,
+            reason,
+            section: None,
+            change: None,
+        }
+    }
+}
+
+impl Plan {
+    pub fn new(manifest: &Manifest, actions: Vec<PlannedAction>) -> Result<Self> {
+        Ok(Self {
+            manifest: manifest.path.clone(),
+            manifest_hash: manifest_hash(manifest)?,
+            actions,
+            duplicate_build_units: Vec::new(),
+        })
+    }
+
+    /// The requirement edits `update` would make to move `updates` to their
+    /// latest versions. Actions are sorted by section and crate, so the same
+    /// selection always gives the same plan.
+    pub fn for_updates(manifest: &Manifest, updates: &[&Dependency]) -> Result<Self> {
+        let mut updater = DependencyUpdater::new(manifest.clone())?;
+        let mut actions = Vec::new();
+        for dep in updates {
+            let Some(latest) = &dep.latest_version else {
+                continue;
+            };
+            // A plan covers one Cargo.toml; the inherited requirement is
+            // the workspace root's to change
+            if let Some(root) = &dep.inherited_from {
+                actions.push(PlannedAction::unavailable(
+                    &dep.name,
+                    &dep.current_version.to_string(),
+                    format!(
+                        "inherited from [workspace.dependencies] in {}",
+                        root.display()
+                    ),
+                ));
+                continue;
+            }
+            let edit = updater.update_dependency(dep, &latest.to_string())?;
+            actions.push(PlannedAction::manifest_edit(
+                &edit.section,
+                &edit.name,
+                &edit.old_requirement,
+                &edit.new_requirement,
+                update_reason(dep).to_string(),
+            ));
+        }
+        actions
+            .sort_by_cached_key(|a| (a.section.as_ref().map(ToString::to_string), a.name.clone()));
+        Self::new(manifest, actions)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content).context(format!("Failed to parse plan {}", path.display()))
+    }
+
+    /// Actions that change something
+    pub fn executable(&self) -> impl Iterator<Item = &PlannedAction> {
+        self.actions
+            .iter()
+            .filter(|a| a.action != ActionType::NoActionAvailable)
+    }
+
+    /// Fail unless `manifest` is the Cargo.toml the plan was made for, and
+    /// neither it nor its Cargo.lock changed since
+    pub fn verify(&self, manifest: &Manifest) -> Result<()> {
+        let hash = manifest_hash(manifest)?;
+        if hash != self.manifest_hash {
+            anyhow::bail!(
+                "{} or its Cargo.lock changed since the plan was made (hash {} expected, \
+                 found {}); make a new plan",
+                manifest.path.display(),
+                self.manifest_hash,
+                hash
+            );
+        }
+        Ok(())
+    }
+
+    /// Execute every action in order, after checking the manifest hasn't
+    /// drifted. Returns one outcome per action; manifest edits are saved
+    /// together at the end.
+    pub fn apply(&self, manifest: Manifest, cargo: &CargoOptions) -> Result<Vec<Result<()>>> {
+        self.verify(&manifest)?;
+
+        let manifest_path = manifest.path.clone();
+        let mut updater = DependencyUpdater::new(manifest)?;
+        let mut edited = false;
+        let mut outcomes = Vec::new();
+
+        for action in &self.actions {
+            let outcome = match (action.action, &action.to_version) {
+                (ActionType::NoActionAvailable, _) => Ok(()),
+                (ActionType::LockfileUpdate, None) => {
+                    let package = format!("{}@{}", action.name, action.from_version);
+                    cargo::update_package(&manifest_path, &package, cargo)
+                }
+                (_, None) => Err(anyhow::anyhow!("No target version for {}", action.name)),
+                (ActionType::LockfileUpdate, Some(to)) => parse_versions(&action.from_version, to)
+                    .and_then(|(from, to)| {
+                        cargo::update_precise(&manifest_path, &action.name, &from, &to, cargo)
+                    }),
+                (ActionType::ManifestEdit, Some(to)) => match &action.section {
+                    Some(section) => {
+                        let result = updater.update_declaration(section, &action.name, to);
+                        edited |= result.is_ok();
+                        result.map(|_| ())
+                    }
+                    None => Err(anyhow::anyhow!("No section for {}", action.name)),
+                },
+            };
+            outcomes.push(outcome);
+        }
+
+        if edited {
+            updater.save()?;
+        }
+        Ok(outcomes)
+    }
+}
+
+/// Fingerprint of the exact contents of the manifest and of the Cargo.lock
+/// it resolves with, when there is one: lockfile updates are planned from
+/// the versions it locks
+pub fn manifest_hash(manifest: &Manifest) -> Result<String> {
+    let path = &manifest.path;
+    let mut content = fs::read(path).context(format!("Failed to read {}", path.display()))?;
+    let lock = Lockfile::path_for(manifest);
+    match fs::read(&lock) {
+        Ok(locked) => {
+            content.push(0);
+            content.extend(locked);
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).context(format!("Failed to read {}", lock.display())),
+    }
+    Ok(fingerprint_bytes(&content))
+}
+
+/// Why `update` picked its target: the organization's versions file, newer
+/// releases passed over for an advisory or as yanked, or just the newest
+fn update_reason(dep: &Dependency) -> &'static str {
+    if dep.policy.is_some() {
+        "policy"
+    } else if dep
+        .skipped_versions
+        .iter()
+        .any(|skipped| matches!(skipped.reason, SkipReason::Advisory(_)))
+    {
+        "advisory"
+    } else if dep
+        .skipped_versions
+        .iter()
+        .any(|skipped| skipped.reason == SkipReason::Yanked)
+    {
+        "latest-non-yanked"
+    } else {
+        "latest"
+    }
+}
+
+fn parse_versions(from: &str, to: &str) -> Result<(Version, Version)> {
+    let parse = |v: &str| Version::parse(v).context(format!("Invalid version '{}' in plan", v));
+    Ok((parse(from)?, parse(to)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dependency::DependencyKind;
+    use crate::core::version::SkippedVersion;
+
+    fn project() -> (tempfile::TempDir, Manifest) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Cargo.toml");
+        fs::write(
+            &path,
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1.0\"\n",
+        )
+        .unwrap();
+        let manifest = Manifest::from_path(&path).unwrap();
+        (dir, manifest)
+    }
+
+    fn section() -> DependencySection {
+        DependencySection {
+            kind: DependencyKind::Normal,
+            target: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_round_trips_as_json() {
+        let (_dir, manifest) = project();
+        let plan = Plan::new(
+            &manifest,
+            vec![
+                PlannedAction::manifest_edit(
+                    &section(),
+                    "serde",
+                    "1.0",
+                    "1.0.200",
+                    "RUSTSEC-0000-0000".to_string(),
+                ),
+                PlannedAction::unavailable("syn", "1.0.109", "conflict".to_string()),
+            ],
+        )
+        .unwrap();
+
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(json["actions"][0]["type"], "manifest_edit");
+        assert_eq!(json["actions"][0]["crate"], "serde");
+        assert_eq!(
+            json["actions"][0]["change"],
+            "[dependencies] serde: \"1.0\" -> \"1.0.200\""
+        );
+        assert_eq!(json["actions"][1]["type"], "no_action_available");
+
+        let parsed: Plan = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.actions, plan.actions);
+        assert_eq!(parsed.executable().count(), 1);
+    }
+
+    #[test]
+    fn test_update_plans_are_sorted_and_deterministic() {
+        let (_dir, manifest) = project();
+        fs::write(
+            &manifest.path,
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1.0\"\nanyhow = \"1.0\"\nlog = \"0.4\"\n",
+        )
+        .unwrap();
+        let manifest = Manifest::from_path(&manifest.path).unwrap();
+        let serde = Dependency::new("serde".to_string(), Version::new(1, 0, 0), true)
+            .with_latest(Version::new(1, 0, 200));
+        let mut log = Dependency::new("log".to_string(), Version::new(0, 4, 0), true)
+            .with_latest(Version::new(0, 4, 21));
+        log.skipped_versions.push(SkippedVersion {
+            version: Version::new(0, 4, 22),
+            reason: SkipReason::Yanked,
+        });
+        let mut anyhow = Dependency::new("anyhow".to_string(), Version::new(1, 0, 0), true)
+            .with_latest(Version::new(1, 0, 90));
+        anyhow.skipped_versions.push(SkippedVersion {
+            version: Version::new(1, 0, 91),
+            reason: SkipReason::Advisory("RUSTSEC-0000-0000".to_string()),
+        });
+
+        let plan = Plan::for_updates(&manifest, &[&serde, &log, &anyhow]).unwrap();
+        let names: Vec<&str> = plan.actions.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["anyhow", "log", "serde"]);
+        assert_eq!(plan.actions[0].reason, "advisory");
+        assert_eq!(plan.actions[1].reason, "latest-non-yanked");
+        assert_eq!(plan.actions[2].reason, "latest");
+        assert_eq!(plan.actions[2].from_version, "1.0");
+        assert_eq!(plan.actions[2].to_version.as_deref(), Some("1.0.200"));
+
+        // Selection order doesn't matter, and nothing is edited
+        let again = Plan::for_updates(&manifest, &[&anyhow, &serde, &log]).unwrap();
+        assert_eq!(
+            serde_json::to_string(&plan).unwrap(),
+            serde_json::to_string(&again).unwrap()
+        );
+        assert!(fs::read_to_string(&manifest.path)
+            .unwrap()
+            .contains("serde = \"1.0\"\n"));
+    }
+
+    #[test]
+    fn test_manifest_hash_covers_the_exact_bytes() {
+        let (_dir, manifest) = project();
+        fs::write(&manifest.path, b"[package]\nname = \"demo\"\n# \xff\n").unwrap();
+        let hash = manifest_hash(&manifest).unwrap();
+        fs::write(
+            &manifest.path,
+            b"[package]\r\nname = \"demo\"\r\n# \xff\r\n",
+        )
+        .unwrap();
+        assert_ne!(manifest_hash(&manifest).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_manifest_hash_covers_the_lockfile() {
+        let (dir, manifest) = project();
+        let unlocked = manifest_hash(&manifest).unwrap();
+        let lock = dir.path().join("Cargo.lock");
+        fs::write(&lock, "version = 3\n").unwrap();
+        let locked = manifest_hash(&manifest).unwrap();
+        assert_ne!(locked, unlocked);
+        fs::write(&lock, "version = 4\n").unwrap();
+        assert_ne!(manifest_hash(&manifest).unwrap(), locked);
+    }
+
+    #[test]
+    fn test_apply_refuses_drifted_manifest() {
+        let (_dir, manifest) = project();
+        let plan = Plan::new(
+            &manifest,
+            vec![PlannedAction::manifest_edit(
+                &section(),
+                "serde",
+                "1.0",
+                "1.0.200",
+                "RUSTSEC-0000-0000".to_string(),
+            )],
+        )
+        .unwrap();
+
+        let mut content = fs::read_to_string(&manifest.path).unwrap();
+        content.push_str("log = \"0.4\"\n");
+        fs::write(&manifest.path, &content).unwrap();
+        let drifted = Manifest::from_path(&manifest.path).unwrap();
+        assert!(plan.apply(drifted, &CargoOptions::default()).is_err());
+        assert_eq!(fs::read_to_string(&manifest.path).unwrap(), content);
+    }
+
+    #[test]
+    fn test_apply_executes_manifest_edits() {
+        let (_dir, manifest) = project();
+        let path = manifest.path.clone();
+        let plan = Plan::new(
+            &manifest,
+            vec![PlannedAction::manifest_edit(
+                &section(),
+                "serde",
+                "1.0",
+                "1.0.200",
+                "RUSTSEC-0000-0000".to_string(),
+            )],
+        )
+        .unwrap();
+
+        let outcomes = plan.apply(manifest, &CargoOptions::default()).unwrap();
+        assert!(outcomes[0].is_ok());
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("serde = \"1.0.200\""));
+    }
+}
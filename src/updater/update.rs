@@ -1,87 +1,1196 @@
 //! Update dependencies in Cargo.toml
 
-use crate::core::dependency::Dependency;
-use crate::core::manifest::Manifest;
+use crate::core::dependency::{Dependency, DependencyKind};
+use crate::core::manifest::{DependencySection, Manifest, ManifestText};
+use crate::core::version::without_build_metadata;
+use crate::updater::fmt_deps::format_dependencies;
+use crate::utils::audit::AuditChange;
+use crate::utils::formatting::display_path;
 use crate::Result;
 use anyhow::Context;
-use std::fs;
 use regex::Regex;
+use serde::Serialize;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// How a requirement was found in the manifest text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchStrategy {
+    /// `name = "1.0"`
+    Simple,
+    /// `name = { version = "1.0", ... }`
+    Inline,
+    /// `name.version = "1.0"`
+    Dotted,
+    /// `version = "1.0"` under a `[dependencies.name]` header
+    Table,
+}
+
+/// A version requirement changed in the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ManifestEdit {
+    pub name: String,
+    pub section: DependencySection,
+    pub old_requirement: String,
+    pub new_requirement: String,
+    /// 1-based line of the requirement
+    pub line: usize,
+    pub strategy: MatchStrategy,
+    /// The requirement is a `[workspace.dependencies]` entry rather than a
+    /// declaration in `section`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub workspace: bool,
+}
+
+impl ManifestEdit {
+    /// The table the requirement was changed in, e.g. `dependencies` or
+    /// `workspace.dependencies`
+    pub fn table(&self) -> String {
+        if self.workspace {
+            "workspace.dependencies".to_string()
+        } else {
+            self.section.to_string()
+        }
+    }
+}
+
+impl From<&ManifestEdit> for AuditChange {
+    fn from(edit: &ManifestEdit) -> Self {
+        AuditChange {
+            name: edit.name.clone(),
+            old: Some(edit.old_requirement.clone()),
+            new: Some(edit.new_requirement.clone()),
+            section: edit.table(),
+        }
+    }
+}
 
 pub struct DependencyUpdater {
     manifest: Manifest,
+    /// The manifest text without any byte order mark, so `(?m)^` anchors
+    /// match the first line too
     original_content: String,
+    bom: bool,
 }
 
 impl DependencyUpdater {
     pub fn new(manifest: Manifest) -> Result<Self> {
-        let original_content = fs::read_to_string(&manifest.path)
-            .context("Failed to read Cargo.toml")?;
+        let text = ManifestText::read(&manifest.path)?;
 
         Ok(Self {
             manifest,
-            original_content,
+            original_content: text.content,
+            bom: text.bom,
         })
     }
 
-    /// Update a single dependency to a new version
-    pub fn update_dependency(&mut self, dep: &Dependency, new_version: &str) -> Result<()> {
-        let dep_name = &dep.name;
-        
-        // Strategy 1: Detailed format - name = { version = "x.y.z", ... }
-        // Capture: everything up to and including opening quote, version, closing quote
-        let detailed_pattern = format!(
-            r#"(?m)^(\s*{}\s*=\s*\{{\s*version\s*=\s*")([^"]+)(")"#,
-            regex::escape(dep_name)
-        );
-        
-        if let Ok(re) = Regex::new(&detailed_pattern) {
-            if re.is_match(&self.original_content) {
-                let new_content = re.replace(&self.original_content, |caps: &regex::Captures| {
-                    format!("{}{}{}", &caps[1], new_version, &caps[3])
-                });
-                self.original_content = new_content.to_string();
-                return Ok(());
+    /// Update a single dependency to a new version, in the section of its
+    /// kind and target when it's declared there and otherwise in the first
+    /// section declaring it with a version requirement
+    pub fn update_dependency(
+        &mut self,
+        dep: &Dependency,
+        new_version: &str,
+    ) -> Result<ManifestEdit> {
+        let own = DependencySection {
+            kind: dep.kind,
+            target: dep.target.clone(),
+        };
+        let section = if self.manifest.location_in(&dep.name, &own).is_some() {
+            own
+        } else {
+            self.manifest
+                .declarations()
+                .into_iter()
+                .find(|(_, name, spec)| name == &dep.name && spec.version().is_some())
+                .map(|(section, _, _)| section)
+                .with_context(|| format!("Could not find dependency {} in Cargo.toml", dep.name))?
+        };
+        self.update_declaration(&section, &dep.name, new_version)
+    }
+
+    /// Set the version requirement of a declaration in one specific section,
+    /// leaving declarations of the same crate in other sections untouched.
+    /// Build metadata is dropped: cargo ignores it in requirements.
+    pub fn update_declaration(
+        &mut self,
+        section: &DependencySection,
+        dep_name: &str,
+        new_version: &str,
+    ) -> Result<ManifestEdit> {
+        let (start, end) = self.declaration_region(section, dep_name)?;
+        self.rewrite_requirement(start..end, section, dep_name, new_version)?
+            .with_context(|| {
+                format!(
+                    "Could not find a version requirement for {} in [{}]",
+                    dep_name, section
+                )
+            })
+    }
+
+    /// Set the version requirement of an entry in this manifest's
+    /// `[workspace.dependencies]`, which every member declaring the crate
+    /// with `workspace = true` inherits
+    pub fn update_workspace_dependency(
+        &mut self,
+        dep_name: &str,
+        new_version: &str,
+    ) -> Result<ManifestEdit> {
+        let (line, _) = self
+            .manifest
+            .workspace_location_of(dep_name)
+            .with_context(|| format!("Could not find {} in [workspace.dependencies]", dep_name))?;
+        let (start, end) = self.region_at(line)?;
+        let section = DependencySection::new(DependencyKind::Normal);
+        let mut edit = self
+            .rewrite_requirement(start..end, &section, dep_name, new_version)?
+            .with_context(|| {
+                format!(
+                    "Could not find a version requirement for {} in [workspace.dependencies]",
+                    dep_name
+                )
+            })?;
+        edit.workspace = true;
+        Ok(edit)
+    }
+
+    /// Replace the requirement of `dep_name` in the declaration spanning
+    /// `region`. Build metadata is dropped: cargo ignores it in requirements.
+    /// `None` when no requirement was found there.
+    fn rewrite_requirement(
+        &mut self,
+        region: Range<usize>,
+        section: &DependencySection,
+        dep_name: &str,
+        new_version: &str,
+    ) -> Result<Option<ManifestEdit>> {
+        let new_version = &without_build_metadata(new_version);
+        let start = region.start;
+        let region = &self.original_content[region];
+
+        // The key exactly as written, bare or quoted, followed by `=` or `.`
+        let key = format!(r#"(?:{0}|"{0}"|'{0}')"#, regex::escape(dep_name));
+        let is_header = region.trim_start().starts_with('[');
+        let patterns = if is_header {
+            // [dependencies.name] followed by version = "..."
+            vec![(
+                MatchStrategy::Table,
+                r#"(?m)^([ \t]*version[ \t]*=[ \t]*")([^"]+)(")"#.to_string(),
+            )]
+        } else {
+            vec![
+                // name = { ..., version = "..." }
+                (
+                    MatchStrategy::Inline,
+                    format!(
+                        r#"\A([ \t]*{}[ \t]*=[ \t]*\{{(?:[^}}\n]*,)?[ \t]*version[ \t]*=[ \t]*")([^"]+)(")"#,
+                        key
+                    ),
+                ),
+                // name = "..."
+                (
+                    MatchStrategy::Simple,
+                    format!(r#"\A([ \t]*{}[ \t]*=[ \t]*")([^"]+)(")"#, key),
+                ),
+                // name.version = "..."
+                (
+                    MatchStrategy::Dotted,
+                    format!(
+                        r#"(?m)^([ \t]*{}[ \t]*\.[ \t]*version[ \t]*=[ \t]*")([^"]+)(")"#,
+                        key
+                    ),
+                ),
+            ]
+        };
+
+        for (strategy, pattern) in patterns {
+            let re = Regex::new(&pattern).context("Invalid dependency pattern")?;
+            if let Some(caps) = re.captures(region) {
+                let version = caps.get(2).expect("pattern has a version group");
+                let at = start + version.start();
+                let edit = ManifestEdit {
+                    name: dep_name.to_string(),
+                    section: section.clone(),
+                    old_requirement: version.as_str().to_string(),
+                    new_requirement: new_version.to_string(),
+                    line: self.original_content[..at].matches('\n').count() + 1,
+                    strategy,
+                    workspace: false,
+                };
+                self.original_content
+                    .replace_range(at..start + version.end(), new_version);
+                return Ok(Some(edit));
             }
         }
-        
-        // Strategy 2: Simple format - name = "x.y.z"
-        let simple_pattern = format!(
-            r#"(?m)^(\s*{}\s*=\s*")([^"]+)(")"#,
-            regex::escape(dep_name)
-        );
-        
-        if let Ok(re) = Regex::new(&simple_pattern) {
-            if re.is_match(&self.original_content) {
-                let new_content = re.replace(&self.original_content, |caps: &regex::Captures| {
-                    format!("{}{}{}", &caps[1], new_version, &caps[3])
-                });
-                self.original_content = new_content.to_string();
-                return Ok(());
+        Ok(None)
+    }
+
+    /// Replace the `features` array of a declaration in one specific section
+    pub fn set_features(
+        &mut self,
+        section: &DependencySection,
+        dep_name: &str,
+        features: &[String],
+    ) -> Result<()> {
+        let (start, end) = self.declaration_region(section, dep_name)?;
+        let region = &self.original_content[start..end];
+
+        let key = format!(r#"(?:{0}|"{0}"|'{0}')"#, regex::escape(dep_name));
+        let pattern = if region.trim_start().starts_with('[') {
+            r#"(?m)^[ \t]*features[ \t]*=[ \t]*(\[[^\]]*\])"#.to_string()
+        } else {
+            // Inline first; arrays may span lines even inside inline tables
+            format!(
+                r#"\A[ \t]*{0}[ \t]*=[ \t]*\{{[^}}]*?\bfeatures[ \t]*=[ \t]*(\[[^\]]*\])|(?m)^[ \t]*{0}[ \t]*\.[ \t]*features[ \t]*=[ \t]*(\[[^\]]*\])"#,
+                key
+            )
+        };
+        let re = Regex::new(&pattern).context("Invalid dependency pattern")?;
+        let array = re
+            .captures(region)
+            .and_then(|caps| caps.get(1).or_else(|| caps.get(2)))
+            .with_context(|| {
+                format!(
+                    "Could not find the features of {} in [{}]",
+                    dep_name, section
+                )
+            })?;
+
+        let rendered: Vec<String> = features
+            .iter()
+            .map(|f| toml::Value::String(f.clone()).to_string())
+            .collect();
+        let range = start + array.start()..start + array.end();
+        self.original_content
+            .replace_range(range, &format!("[{}]", rendered.join(", ")));
+
+        // A multi-line array became one line; re-scan the locations
+        self.manifest = Manifest::parse(self.manifest.path.clone(), &self.original_content)?;
+        Ok(())
+    }
+
+    /// Remove a declaration from one specific section
+    pub fn remove_declaration(
+        &mut self,
+        section: &DependencySection,
+        dep_name: &str,
+    ) -> Result<()> {
+        let (start, end) = self.declaration_region(section, dep_name)?;
+        let region = &self.original_content[start..end];
+
+        let remaining = if region.trim_start().starts_with('[') {
+            // [dependencies.name] owns everything up to the next header
+            String::new()
+        } else {
+            // name = ... or name.key = ... lines; other crates share the region
+            let key = Regex::new(&format!(r"^\s*{}\s*[=.]", regex::escape(dep_name)))
+                .context("Invalid dependency pattern")?;
+            region
+                .split_inclusive('\n')
+                .filter(|line| !key.is_match(line))
+                .collect()
+        };
+
+        self.original_content.replace_range(start..end, &remaining);
+
+        // Later declarations moved up; re-scan so their locations stay valid
+        self.manifest = Manifest::parse(self.manifest.path.clone(), &self.original_content)?;
+        Ok(())
+    }
+
+    /// Add `name = spec` to `[patch.crates-io]`, creating the table at the
+    /// end of the manifest if there is none. `spec` is the TOML value, e.g.
+    /// `{ git = "https://github.com/org/fork" }`.
+    pub fn add_patch(&mut self, name: &str, spec: &str) -> Result<()> {
+        let header = Regex::new(r#"(?m)^[ \t]*\[\s*patch\s*\.\s*"?crates-io"?\s*\][^\n]*\n?"#)
+            .context("Invalid patch pattern")?;
+        if !self.add_table_entry(&header, "patch.crates-io", name, spec)? {
+            anyhow::bail!("{} is already patched in [patch.crates-io]", name);
+        }
+        Ok(())
+    }
+
+    /// Add `name = spec` to `[workspace.dependencies]`, creating the table
+    /// at the end of the manifest if there is none
+    pub fn add_workspace_dependency(&mut self, name: &str, spec: &str) -> Result<()> {
+        let header = Regex::new(r"(?m)^[ \t]*\[\s*workspace\s*\.\s*dependencies\s*\][^\n]*\n?")
+            .context("Invalid workspace pattern")?;
+        if !self.add_table_entry(&header, "workspace.dependencies", name, spec)? {
+            anyhow::bail!("{} is already in [workspace.dependencies]", name);
+        }
+        Ok(())
+    }
+
+    /// Replace a declaration in one specific section with one inheriting
+    /// `[workspace.dependencies]`, keeping `optional = true` and adding
+    /// `features` on top of the inherited ones
+    pub fn inherit_from_workspace(
+        &mut self,
+        section: &DependencySection,
+        dep_name: &str,
+        features: &[String],
+    ) -> Result<()> {
+        let optional = self
+            .manifest
+            .declarations()
+            .into_iter()
+            .any(|(s, name, spec)| &s == section && name == dep_name && spec.is_optional());
+        let (start, end) = self.declaration_region(section, dep_name)?;
+        let region = &self.original_content[start..end];
+        let newline = if region.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+
+        let features = (!features.is_empty()).then(|| {
+            let quoted: Vec<String> = features
+                .iter()
+                .map(|f| toml::Value::String(f.clone()).to_string())
+                .collect();
+            format!("features = [{}]", quoted.join(", "))
+        });
+
+        let replacement = if region.trim_start().starts_with('[') {
+            // [dependencies.name] keeps its header and loses everything else
+            let header = region.split_inclusive('\n').next().unwrap_or(region);
+            let trailing = &region[region.trim_end().len()..];
+            let mut body = format!("workspace = true{}", newline);
+            if let Some(features) = &features {
+                body.push_str(&format!("{}{}", features, newline));
+            }
+            if optional {
+                body.push_str(&format!("optional = true{}", newline));
+            }
+            format!(
+                "{}{}{}",
+                header,
+                body,
+                trailing.strip_prefix(newline).unwrap_or("")
+            )
+        } else {
+            let key = Regex::new(&format!(r"^\s*{}\s*[=.]", regex::escape(dep_name)))
+                .context("Invalid dependency pattern")?;
+            let mut keys = vec!["workspace = true"];
+            keys.extend(features.as_deref());
+            if optional {
+                keys.push("optional = true");
+            }
+            let value = format!("{{ {} }}", keys.join(", "));
+            let mut replaced = String::new();
+            let mut placed = false;
+            for line in region.split_inclusive('\n') {
+                if !key.is_match(line) {
+                    replaced.push_str(line);
+                } else if !placed {
+                    replaced.push_str(&format!("{} = {}{}", dep_name, value, newline));
+                    placed = true;
+                }
+            }
+            replaced
+        };
+
+        self.original_content
+            .replace_range(start..end, &replacement);
+        self.manifest = Manifest::parse(self.manifest.path.clone(), &self.original_content)?;
+        Ok(())
+    }
+
+    /// Add `name = spec` to the table `header` finds, or to a new `[title]`
+    /// at the end of the manifest. False when the table already has `name`.
+    fn add_table_entry(
+        &mut self,
+        header: &Regex,
+        title: &str,
+        name: &str,
+        spec: &str,
+    ) -> Result<bool> {
+        let newline = if self.original_content.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let entry = format!("{} = {}{}", name, spec, newline);
+
+        match header.find(&self.original_content) {
+            Some(found) => {
+                let body = &self.original_content[found.end()..];
+                let body = &body[..body
+                    .match_indices('\n')
+                    .map(|(i, _)| i + 1)
+                    .find(|&i| body[i..].trim_start_matches([' ', '\t']).starts_with('['))
+                    .unwrap_or(body.len())];
+                let key = Regex::new(&format!(r"(?m)^\s*{}\s*=", regex::escape(name)))
+                    .context("Invalid table pattern")?;
+                if key.is_match(body) {
+                    return Ok(false);
+                }
+                let mut at = found.end();
+                if !self.original_content[..at].ends_with('\n') {
+                    self.original_content.insert_str(at, newline);
+                    at += newline.len();
+                }
+                self.original_content.insert_str(at, &entry);
+            }
+            None => {
+                if !self.original_content.is_empty() && !self.original_content.ends_with('\n') {
+                    self.original_content.push_str(newline);
+                }
+                self.original_content
+                    .push_str(&format!("{}[{}]{}{}", newline, title, newline, entry));
             }
         }
 
-        anyhow::bail!(
-            "Could not find dependency {} in Cargo.toml",
-            dep_name
-        );
+        self.manifest = Manifest::parse(self.manifest.path.clone(), &self.original_content)?;
+        Ok(true)
     }
 
-    /// Save the updated Cargo.toml
-    pub fn save(&self) -> Result<()> {
-        // Create backup
-        let backup_path = self.manifest.path.with_extension("toml.backup");
-        fs::copy(&self.manifest.path, &backup_path)
-            .context("Failed to create backup")?;
+    /// Lay the dependency tables out canonically (see [`fmt_deps`]),
+    /// returning whether anything moved
+    ///
+    /// [`fmt_deps`]: crate::updater::fmt_deps
+    pub fn format_dependencies(&mut self, inline_max_keys: usize) -> Result<bool> {
+        let formatted = format_dependencies(&self.original_content, inline_max_keys)?;
+        if formatted == self.original_content {
+            return Ok(false);
+        }
+        self.original_content = formatted;
+        self.manifest = Manifest::parse(self.manifest.path.clone(), &self.original_content)?;
+        Ok(true)
+    }
 
-        // Write updated content
-        fs::write(&self.manifest.path, &self.original_content)
-            .context("Failed to write updated Cargo.toml")?;
+    /// Remove `entry` from the array of `feature` in `[features]`, leaving
+    /// the rest of the array as it was laid out
+    pub fn remove_feature_entry(&mut self, feature: &str, entry: &str) -> Result<()> {
+        let mut document: toml_edit::DocumentMut = self
+            .original_content
+            .parse()
+            .context("Failed to parse Cargo.toml")?;
+        let array = document
+            .get_mut("features")
+            .and_then(|features| features.get_mut(feature))
+            .and_then(|item| item.as_array_mut())
+            .with_context(|| format!("Could not find {} in [features]", feature))?;
+        let index = array
+            .iter()
+            .position(|value| value.as_str() == Some(entry))
+            .with_context(|| format!("{} doesn't enable \"{}\"", feature, entry))?;
+        let removed = array.remove(index);
+        // The next entry takes the removed one's place, indentation and all
+        if let Some(next) = array.get_mut(index) {
+            let prefix = removed.decor().prefix().cloned();
+            if let Some(prefix) = prefix {
+                next.decor_mut().set_prefix(prefix);
+            }
+        }
 
+        let mut content = document.to_string();
+        // toml_edit writes the lines it touches with \n
+        if self.original_content.contains("\r\n") {
+            content = content.replace("\r\n", "\n").replace('\n', "\r\n");
+        }
+        self.original_content = content;
+        self.manifest = Manifest::parse(self.manifest.path.clone(), &self.original_content)?;
         Ok(())
     }
 
+    /// Byte range of a declaration, from its own line up to the next table header
+    fn declaration_region(
+        &self,
+        section: &DependencySection,
+        dep_name: &str,
+    ) -> Result<(usize, usize)> {
+        let (line, _) = self
+            .manifest
+            .location_in(dep_name, section)
+            .with_context(|| format!("Could not find {} in [{}]", dep_name, section))?;
+        self.region_at(line)
+    }
+
+    /// Byte range from the start of 1-based `line` up to the next table header
+    fn region_at(&self, line: usize) -> Result<(usize, usize)> {
+        let start = line_offset(&self.original_content, line - 1)
+            .context("Manifest changed since it was loaded")?;
+        let end = self.original_content[start..]
+            .match_indices('\n')
+            .map(|(i, _)| start + i + 1)
+            .find(|&i| {
+                self.original_content[i..]
+                    .trim_start_matches([' ', '\t'])
+                    .starts_with('[')
+            })
+            .unwrap_or(self.original_content.len());
+
+        Ok((start, end))
+    }
+
+    /// Save the updated Cargo.toml, backing the old one up first. Returns
+    /// the backup's path.
+    pub fn save(&self) -> Result<PathBuf> {
+        let path = &self.manifest.path;
+        // Some VCS checkouts leave files read-only; say so instead of
+        // failing with a bare "permission denied" halfway through
+        if is_read_only(path) {
+            anyhow::bail!(
+                "{} is read-only; make it writable and try again",
+                display_path(path)
+            );
+        }
+
+        // The backup is ours to overwrite, even if an earlier copy of a
+        // read-only manifest left it read-only
+        let backup = backup_path(path);
+        if is_read_only(&backup) {
+            set_writable(&backup)?;
+        }
+        fs::copy(path, &backup)
+            .context(format!("Failed to create backup {}", display_path(&backup)))?;
+        set_writable(&backup)?;
+
+        // Write updated content, byte order mark and all
+        let text = ManifestText {
+            content: self.original_content.clone(),
+            bom: self.bom,
+        };
+        fs::write(path, text.encode())
+            .context(format!("Failed to write {}", display_path(path)))?;
+
+        Ok(backup)
+    }
+
     /// Get the current content (for dry-run)
     pub fn get_content(&self) -> &str {
         &self.original_content
     }
-}
\ No newline at end of file
+}
+
+/// Save several updaters as one: each manifest is backed up and written in
+/// turn, and should one fail, those already written are put back from their
+/// backups. Returns the backups' paths.
+pub fn save_all<'a>(
+    updaters: impl IntoIterator<Item = &'a DependencyUpdater>,
+) -> Result<Vec<PathBuf>> {
+    let mut saved: Vec<(&Path, PathBuf)> = Vec::new();
+    for updater in updaters {
+        match updater.save() {
+            Ok(backup) => saved.push((&updater.manifest.path, backup)),
+            Err(e) => {
+                for (path, backup) in &saved {
+                    fs::copy(backup, path).context(format!(
+                        "Failed to restore {} from {}",
+                        display_path(path),
+                        display_path(backup)
+                    ))?;
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(saved.into_iter().map(|(_, backup)| backup).collect())
+}
+
+/// Where the backup of `manifest_path` goes: the same file name with
+/// `.backup` appended, e.g. `Cargo.toml.backup`, on every platform
+pub fn backup_path(manifest_path: &Path) -> PathBuf {
+    let mut name = manifest_path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_else(|| "Cargo.toml".into());
+    name.push(".backup");
+    manifest_path.with_file_name(name)
+}
+
+fn is_read_only(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().readonly())
+}
+
+fn set_writable(path: &Path) -> Result<()> {
+    let mut permissions = fs::metadata(path)
+        .context(format!("Failed to read {}", display_path(path)))?
+        .permissions();
+    if permissions.readonly() {
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(path, permissions)
+            .context(format!("Failed to make {} writable", display_path(path)))?;
+    }
+    Ok(())
+}
+
+/// Byte offset of the start of a 0-based line
+fn line_offset(content: &str, line: usize) -> Option<usize> {
+    if line == 0 {
+        return Some(0);
+    }
+    content
+        .match_indices('\n')
+        .nth(line - 1)
+        .map(|(i, _)| i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dependency::DependencyKind;
+    use semver::Version;
+
+    fn updater(text: &str) -> DependencyUpdater {
+        let manifest = Manifest::parse(PathBuf::from("Cargo.toml"), text).unwrap();
+        DependencyUpdater {
+            manifest,
+            original_content: text.to_string(),
+            bom: false,
+        }
+    }
+
+    fn target_section(target: &str) -> DependencySection {
+        DependencySection {
+            kind: DependencyKind::Normal,
+            target: Some(target.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_update_declaration_only_touches_its_section() {
+        let mut updater = updater(
+            r#"[dependencies]
+tokio = "1.35"
+
+[target.'cfg(unix)'.dependencies]
+tokio = { features = ["net"], version = "1.20" }
+"#,
+        );
+
+        updater
+            .update_declaration(&target_section("cfg(unix)"), "tokio", "1.35")
+            .unwrap();
+
+        assert_eq!(
+            updater.get_content(),
+            r#"[dependencies]
+tokio = "1.35"
+
+[target.'cfg(unix)'.dependencies]
+tokio = { features = ["net"], version = "1.35" }
+"#
+        );
+    }
+
+    #[test]
+    fn test_update_declaration_drops_build_metadata() {
+        let mut updater = updater(
+            r#"[dependencies]
+libgit2-sys = "0.16.1+1.7.1"
+beta = "=0.12.0-beta.1"
+"#,
+        );
+        let normal = DependencySection::new(DependencyKind::Normal);
+
+        let edit = updater
+            .update_declaration(&normal, "libgit2-sys", "0.16.2+1.7.2")
+            .unwrap();
+        assert_eq!(edit.new_requirement, "0.16.2");
+        updater
+            .update_declaration(&normal, "beta", "=0.12.0-rc.1")
+            .unwrap();
+
+        assert_eq!(
+            updater.get_content(),
+            r#"[dependencies]
+libgit2-sys = "0.16.2"
+beta = "=0.12.0-rc.1"
+"#
+        );
+    }
+
+    #[test]
+    fn test_update_declaration_in_table_section() {
+        let mut updater = updater(
+            r#"[dependencies]
+nix = "0.29"
+
+[target.'cfg(unix)'.dependencies.nix]
+version = "0.27"
+features = ["fs"]
+
+[dev-dependencies]
+tempfile = "3"
+"#,
+        );
+
+        updater
+            .update_declaration(&target_section("cfg(unix)"), "nix", "0.29")
+            .unwrap();
+
+        assert!(updater
+            .get_content()
+            .contains("version = \"0.29\"\nfeatures"));
+        assert!(updater
+            .get_content()
+            .starts_with("[dependencies]\nnix = \"0.29\""));
+    }
+
+    #[test]
+    fn test_update_artifact_dependencies_round_trip() {
+        let text = r#"[dependencies]
+gen = { artifact = ["bin:gen", "cdylib"], target = "wasm32-unknown-unknown", lib = true, version = "1.2" } # codegen
+
+[build-dependencies.protoc]
+artifact = "bin"
+version = "1.2"
+target = "target"
+"#;
+        let mut updater = updater(text);
+
+        updater
+            .update_declaration(
+                &DependencySection::new(DependencyKind::Normal),
+                "gen",
+                "1.4",
+            )
+            .unwrap();
+        updater
+            .update_declaration(
+                &DependencySection::new(DependencyKind::Build),
+                "protoc",
+                "1.4",
+            )
+            .unwrap();
+
+        assert_eq!(updater.get_content(), text.replace("\"1.2\"", "\"1.4\""));
+        let updated = Manifest::parse(PathBuf::from("Cargo.toml"), updater.get_content()).unwrap();
+        let artifacts: Vec<Vec<String>> = updated
+            .declarations()
+            .iter()
+            .map(|(_, _, spec)| spec.artifacts().to_vec())
+            .collect();
+        assert_eq!(artifacts, [vec!["bin:gen", "cdylib"], vec!["bin"]]);
+    }
+
+    #[test]
+    fn test_set_features() {
+        let mut updater = updater(
+            r#"[dependencies]
+tokio = { version = "0.2", features = [
+    "tcp",
+    "dns",
+] }
+hyper.version = "0.13"
+hyper.features = ["stream"]
+log = "0.4"
+
+[dev-dependencies.tokio]
+version = "0.2"
+features = ["macros"]
+"#,
+        );
+        let normal = DependencySection::new(DependencyKind::Normal);
+        let features = |list: &[&str]| list.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+
+        updater
+            .set_features(&normal, "tokio", &features(&["net"]))
+            .unwrap();
+        updater
+            .set_features(&normal, "hyper", &features(&["stream", "http1"]))
+            .unwrap();
+        updater
+            .set_features(&DependencySection::new(DependencyKind::Dev), "tokio", &[])
+            .unwrap();
+        // Locations were re-scanned after the array shrank
+        updater
+            .update_declaration(&normal, "log", "0.4.22")
+            .unwrap();
+        assert!(updater.set_features(&normal, "log", &[]).is_err());
+
+        assert_eq!(
+            updater.get_content(),
+            r#"[dependencies]
+tokio = { version = "0.2", features = ["net"] }
+hyper.version = "0.13"
+hyper.features = ["stream", "http1"]
+log = "0.4.22"
+
+[dev-dependencies.tokio]
+version = "0.2"
+features = []
+"#
+        );
+    }
+
+    #[test]
+    fn test_remove_declaration() {
+        let mut updater = updater(
+            r#"[dependencies]
+serde = "1.0"
+serde_json = "1.0"
+clap.version = "4"
+clap.features = ["derive"]
+
+[dependencies.regex]
+version = "1"
+
+[dev-dependencies]
+tempfile = "3"
+"#,
+        );
+        let normal = DependencySection::new(DependencyKind::Normal);
+
+        updater.remove_declaration(&normal, "serde").unwrap();
+        updater.remove_declaration(&normal, "clap").unwrap();
+        updater.remove_declaration(&normal, "regex").unwrap();
+
+        assert_eq!(
+            updater.get_content(),
+            r#"[dependencies]
+serde_json = "1.0"
+
+[dev-dependencies]
+tempfile = "3"
+"#
+        );
+    }
+
+    #[test]
+    fn test_remove_feature_entry() {
+        let mut updater = updater(
+            r#"[dependencies]
+rustls = { version = "0.23", optional = true }
+
+[features]
+# TLS backends
+tls = [
+    "dep:native-tls",
+    "dep:rustls", # the default
+]
+json = ["std", "serde_json/std"]
+legacy = ["openssl"]
+"#,
+        );
+
+        updater
+            .remove_feature_entry("tls", "dep:native-tls")
+            .unwrap();
+        updater
+            .remove_feature_entry("json", "serde_json/std")
+            .unwrap();
+        updater.remove_feature_entry("legacy", "openssl").unwrap();
+        assert!(updater.remove_feature_entry("json", "openssl").is_err());
+
+        assert_eq!(
+            updater.get_content(),
+            r#"[dependencies]
+rustls = { version = "0.23", optional = true }
+
+[features]
+# TLS backends
+tls = [
+    "dep:rustls", # the default
+]
+json = ["std"]
+legacy = []
+"#
+        );
+    }
+
+    #[test]
+    fn test_update_declaration_unknown_section() {
+        let mut updater = updater("[dependencies]\nserde = \"1.0\"\n");
+        assert!(updater
+            .update_declaration(&target_section("cfg(windows)"), "serde", "1.1")
+            .is_err());
+    }
+
+    #[test]
+    fn test_add_patch_creates_or_extends_the_table() {
+        let mut patched = updater("[dependencies]\nsyn = \"2\"\n");
+        patched
+            .add_patch("syn", r#"{ git = "https://github.com/org/syn" }"#)
+            .unwrap();
+        assert_eq!(
+            patched.get_content(),
+            "[dependencies]\nsyn = \"2\"\n\n[patch.crates-io]\nsyn = { git = \"https://github.com/org/syn\" }\n"
+        );
+
+        let mut patched = updater(
+            "[dependencies]\r\nsyn = \"2\"\r\n\r\n[patch.crates-io]\r\nlog = { path = \"../log\" }\r\n",
+        );
+        patched.add_patch("syn", r#"{ path = "../syn" }"#).unwrap();
+        assert!(patched.get_content().ends_with(
+            "[patch.crates-io]\r\nsyn = { path = \"../syn\" }\r\nlog = { path = \"../log\" }\r\n"
+        ));
+        assert!(patched.add_patch("log", r#"{ path = "x" }"#).is_err());
+    }
+
+    #[test]
+    fn test_declarations_promoted_to_the_workspace() {
+        let mut updater = updater(
+            r#"[workspace]
+members = ["crates/*"]
+
+[package]
+name = "root"
+version = "0.1.0"
+
+[dependencies]
+serde = { version = "1.0", features = ["serde_derive"] } # json
+log = "0.4"
+tokio.version = "1"
+tokio.optional = true
+
+[dependencies.clap]
+version = "4"
+features = ["derive"]
+
+[dev-dependencies]
+serde = "1"
+"#,
+        );
+        let normal = DependencySection::new(DependencyKind::Normal);
+        for name in ["serde", "tokio", "clap"] {
+            updater.inherit_from_workspace(&normal, name, &[]).unwrap();
+        }
+        updater
+            .add_workspace_dependency("serde", r#"{ version = "1.0", features = ["derive"] }"#)
+            .unwrap();
+        assert!(updater.add_workspace_dependency("serde", r#""1""#).is_err());
+
+        assert_eq!(
+            updater.get_content(),
+            r#"[workspace]
+members = ["crates/*"]
+
+[package]
+name = "root"
+version = "0.1.0"
+
+[dependencies]
+serde = { workspace = true }
+log = "0.4"
+tokio = { workspace = true, optional = true }
+
+[dependencies.clap]
+workspace = true
+
+[dev-dependencies]
+serde = "1"
+
+[workspace.dependencies]
+serde = { version = "1.0", features = ["derive"] }
+"#
+        );
+    }
+
+    #[test]
+    fn test_update_dependency_matches_exact_names() {
+        let text = r#"[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+serde_json = { version = "1.0.100" }
+serde = { features = ["derive"], version = "1.0.150" }
+serde-value = "0.7"
+"version" = "0.1"
+
+[dev-dependencies]
+serde_test = "1.0"
+"#;
+        let mut updater = updater(text);
+        let serde = Dependency::new("serde".to_string(), Version::new(1, 0, 150), true);
+        let edit = updater.update_dependency(&serde, "1.0.200").unwrap();
+        assert_eq!(
+            edit,
+            ManifestEdit {
+                name: "serde".to_string(),
+                section: DependencySection::new(DependencyKind::Normal),
+                old_requirement: "1.0.150".to_string(),
+                new_requirement: "1.0.200".to_string(),
+                line: 7,
+                strategy: MatchStrategy::Inline,
+                workspace: false,
+            }
+        );
+
+        let version = Dependency::new("version".to_string(), Version::new(0, 1, 0), true);
+        let edit = updater.update_dependency(&version, "0.2").unwrap();
+        assert_eq!((edit.line, edit.strategy), (9, MatchStrategy::Simple));
+
+        let mut dev = Dependency::new("serde_test".to_string(), Version::new(1, 0, 0), true);
+        dev.kind = DependencyKind::Dev;
+        let edit = updater.update_dependency(&dev, "1.0.177").unwrap();
+        assert_eq!(edit.section, DependencySection::new(DependencyKind::Dev));
+
+        assert_eq!(
+            updater.get_content(),
+            text.replace("1.0.150", "1.0.200")
+                .replace("\"version\" = \"0.1\"", "\"version\" = \"0.2\"")
+                .replace("serde_test = \"1.0\"", "serde_test = \"1.0.177\"")
+        );
+
+        let missing = Dependency::new("serde_j".to_string(), Version::new(1, 0, 0), true);
+        assert!(updater.update_dependency(&missing, "1.1").is_err());
+    }
+
+    #[test]
+    fn test_update_dependency_in_its_target_section() {
+        let text = r#"[dependencies]
+libc = "0.2.100"
+
+[target.'cfg(unix)'.dependencies]
+libc = { version = "0.2.100", features = ["extra_traits"] }
+
+[target.'cfg(windows)'.dependencies.winapi]
+version = "0.3.5"
+"#;
+        let mut updater = updater(text);
+        let mut libc = Dependency::new("libc".to_string(), Version::new(0, 2, 100), true);
+        libc.target = Some("cfg(unix)".to_string());
+        let edit = updater.update_dependency(&libc, "0.2.150").unwrap();
+        assert_eq!((edit.section, edit.line), (target_section("cfg(unix)"), 5));
+
+        let mut winapi = Dependency::new("winapi".to_string(), Version::new(0, 3, 5), true);
+        winapi.target = Some("cfg(windows)".to_string());
+        let edit = updater.update_dependency(&winapi, "0.3.9").unwrap();
+        assert_eq!((edit.line, edit.strategy), (8, MatchStrategy::Table));
+
+        assert_eq!(
+            updater.get_content(),
+            text.replace("{ version = \"0.2.100\"", "{ version = \"0.2.150\"")
+                .replace("0.3.5", "0.3.9")
+        );
+    }
+
+    #[test]
+    fn test_update_dependency_dotted_and_table_declarations() {
+        let mut updater = updater(
+            "[dependencies]\nclap.version = \"4.0\"\nclap.features = [\"derive\"]\n\n[dependencies.regex]\nversion = \"1.5\"\n",
+        );
+        let clap = Dependency::new("clap".to_string(), Version::new(4, 0, 0), true);
+        let regex = Dependency::new("regex".to_string(), Version::new(1, 5, 0), true);
+        let clap = updater.update_dependency(&clap, "4.5").unwrap();
+        let regex = updater.update_dependency(&regex, "1.10").unwrap();
+        assert_eq!((clap.line, clap.strategy), (2, MatchStrategy::Dotted));
+        assert_eq!((regex.line, regex.strategy), (6, MatchStrategy::Table));
+        assert_eq!(regex.old_requirement, "1.5");
+    }
+
+    #[test]
+    fn test_backup_path_appends_to_the_file_name() {
+        assert_eq!(
+            backup_path(Path::new("crates/core/Cargo.toml")),
+            Path::new("crates/core/Cargo.toml.backup")
+        );
+        assert_eq!(
+            backup_path(Path::new("manifest")),
+            Path::new("manifest.backup")
+        );
+    }
+
+    fn saved_project(text: &str) -> (tempfile::TempDir, DependencyUpdater) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Cargo.toml");
+        fs::write(&path, text).unwrap();
+        let manifest = Manifest::from_path(&path).unwrap();
+        let updater = DependencyUpdater::new(manifest).unwrap();
+        (dir, updater)
+    }
+
+    fn set_read_only(path: &Path) {
+        let mut permissions = fs::metadata(path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(path, permissions).unwrap();
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_backup_is_named_cargo_toml_backup() {
+        let (dir, mut updater) = saved_project("[dependencies]\r\nserde = \"1.0\"\r\n");
+        let serde = Dependency::new("serde".to_string(), Version::new(1, 0, 0), true);
+        updater.update_dependency(&serde, "1.0.200").unwrap();
+        let backup = updater.save().unwrap();
+
+        assert_eq!(backup, dir.path().join("Cargo.toml.backup"));
+        assert_eq!(
+            fs::read_to_string(&backup).unwrap(),
+            "[dependencies]\r\nserde = \"1.0\"\r\n"
+        );
+        assert!(!dir.path().join("Cargo.toml.toml.backup").exists());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_read_only_files() {
+        // A read-only backup left by an earlier run is replaced
+        let (dir, updater) = saved_project("[dependencies]\r\nserde = \"1.0\"\r\n");
+        let backup = dir.path().join("Cargo.toml.backup");
+        fs::write(&backup, "old").unwrap();
+        set_read_only(&backup);
+        updater.save().unwrap();
+        assert!(!fs::metadata(&backup).unwrap().permissions().readonly());
+
+        // A read-only manifest is reported, not half-written
+        set_read_only(&dir.path().join("Cargo.toml"));
+        let error = updater.save().unwrap_err().to_string();
+        assert!(error.contains("is read-only"), "{}", error);
+    }
+
+    #[test]
+    fn test_backup_of_a_read_only_copy_is_writable() {
+        let (dir, updater) = saved_project("[dependencies]\nserde = \"1.0\"\n");
+        let backup = dir.path().join("Cargo.toml.backup");
+        fs::write(&backup, "old").unwrap();
+        set_read_only(&backup);
+
+        assert_eq!(updater.save().unwrap(), backup);
+        assert!(!fs::metadata(&backup).unwrap().permissions().readonly());
+        assert_eq!(
+            fs::read_to_string(&backup).unwrap(),
+            "[dependencies]\nserde = \"1.0\"\n"
+        );
+    }
+
+    #[test]
+    fn test_update_workspace_dependency() {
+        let text = r#"[workspace]
+members = ["crates/*"]
+
+[workspace.dependencies]
+serde = { version = "1.0.150", features = ["derive"] }
+log = "0.4.1"
+
+[workspace.dependencies.tokio]
+default-features = false
+version = "1.30"
+
+[dependencies]
+log = { workspace = true }
+"#;
+        let mut updater = updater(text);
+        let edit = updater
+            .update_workspace_dependency("serde", "1.0.200")
+            .unwrap();
+        assert_eq!((edit.line, edit.strategy), (5, MatchStrategy::Inline));
+        assert!(edit.workspace);
+        assert_eq!(edit.table(), "workspace.dependencies");
+        assert_eq!(AuditChange::from(&edit).section, "workspace.dependencies");
+
+        let edit = updater
+            .update_workspace_dependency("log", "0.4.20")
+            .unwrap();
+        assert_eq!((edit.line, edit.strategy), (6, MatchStrategy::Simple));
+        let edit = updater
+            .update_workspace_dependency("tokio", "1.38")
+            .unwrap();
+        assert_eq!((edit.line, edit.strategy), (10, MatchStrategy::Table));
+        assert!(updater.update_workspace_dependency("regex", "1").is_err());
+
+        let content = updater.get_content();
+        assert!(content.contains("serde = { version = \"1.0.200\", features = [\"derive\"] }\n"));
+        assert!(content.contains("log = \"0.4.20\"\n"));
+        assert!(content.contains("version = \"1.38\"\n"));
+        // The member-style declaration has no requirement of its own
+        assert!(content.ends_with("[dependencies]\nlog = { workspace = true }\n"));
+    }
+}
@@ -0,0 +1,275 @@
+//! Security advisory lookups
+
+use crate::core::advisory::{Advisory, Severity};
+use crate::utils::advisory_db::DatabaseInfo;
+use crate::utils::cancel::Cancellation;
+use crate::utils::test_mode::Scenario;
+use anyhow::{Context, Result};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+
+pub const OSV_API: &str = "https://api.osv.dev/v1";
+const USER_AGENT: &str = "cargo-sane (https://github.com/chronocoders/cargo-sane)";
+
+/// A source of security advisories for published crate versions
+pub trait AdvisorySource {
+    /// Advisories affecting one exact version of a crate
+    fn advisories_for(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> impl Future<Output = Result<Vec<Advisory>>> + Send;
+
+    /// Where the answers come from and how current they are, for sources
+    /// backed by a local database
+    fn database_info(&self) -> Option<DatabaseInfo> {
+        None
+    }
+}
+
+/// Client for the OSV.dev vulnerability database, which mirrors the RustSec
+/// advisory database for the crates.io ecosystem
+pub struct OsvClient {
+    client: reqwest::Client,
+    base_url: String,
+    /// Answers lookups instead of the API under `--test-mode`
+    scenario: Option<&'static Scenario>,
+    cancellation: Cancellation,
+}
+
+#[derive(Serialize)]
+struct OsvQuery<'a> {
+    /// Without one, OSV answers every advisory against the package
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    package: OsvPackage<'a>,
+}
+
+#[derive(Serialize)]
+struct OsvPackage<'a> {
+    name: &'a str,
+    ecosystem: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvResponse {
+    #[serde(default)]
+    vulns: Vec<OsvVulnerability>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvVulnerability {
+    id: String,
+    #[serde(default)]
+    summary: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    severity: Vec<OsvSeverity>,
+    #[serde(default)]
+    affected: Vec<OsvAffected>,
+    #[serde(default)]
+    database_specific: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvSeverity {
+    #[serde(rename = "type")]
+    kind: String,
+    score: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvAffected {
+    #[serde(default)]
+    ranges: Vec<OsvRange>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvRange {
+    #[serde(default)]
+    events: Vec<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl OsvClient {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            scenario: Scenario::active(),
+            cancellation: Cancellation::global(),
+            ..Self::with_base_url(OSV_API)?
+        })
+    }
+
+    /// Create a client talking to an OSV-compatible API at `base_url`
+    pub fn with_base_url(base_url: &str) -> Result<Self> {
+        reqwest::Url::parse(base_url)
+            .with_context(|| format!("Invalid advisory API URL '{}'", base_url))?;
+        let client = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(15))
+            .build()
+            .context("Failed to create the HTTP client for OSV.dev")?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            scenario: None,
+            cancellation: Cancellation::new(),
+        })
+    }
+
+    /// Refuse lookups once `cancellation` stops them
+    pub fn with_cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+}
+
+impl OsvClient {
+    /// Every advisory on record against a crate, whichever versions it
+    /// affects
+    pub async fn advisories_of(&self, crate_name: &str) -> Result<Vec<Advisory>> {
+        self.cancellation.check()?;
+        if let Some(scenario) = self.scenario {
+            return scenario.advisories_of(crate_name);
+        }
+        self.query(crate_name, None).await
+    }
+
+    async fn query(&self, crate_name: &str, version: Option<&Version>) -> Result<Vec<Advisory>> {
+        let query = OsvQuery {
+            version: version.map(Version::to_string),
+            package: OsvPackage {
+                name: crate_name,
+                ecosystem: "crates.io",
+            },
+        };
+
+        let response = self
+            .client
+            .post(format!("{}/query", self.base_url))
+            .json(&query)
+            .send()
+            .await
+            .context(format!("Failed to query advisories for {}", crate_name))?;
+
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "OSV API returned error for {}: {}",
+                crate_name,
+                response.status()
+            );
+        }
+
+        let body: OsvResponse = response.json().await.context(format!(
+            "Failed to parse advisories for crate: {}",
+            crate_name
+        ))?;
+
+        Ok(body
+            .vulns
+            .into_iter()
+            .map(|v| advisory_from_osv(crate_name, v))
+            .collect())
+    }
+}
+
+impl AdvisorySource for OsvClient {
+    async fn advisories_for(&self, crate_name: &str, version: &Version) -> Result<Vec<Advisory>> {
+        self.cancellation.check()?;
+        if let Some(scenario) = self.scenario {
+            return scenario.advisories_for(crate_name, version);
+        }
+        self.query(crate_name, Some(version)).await
+    }
+}
+
+fn advisory_from_osv(crate_name: &str, vuln: OsvVulnerability) -> Advisory {
+    let cvss = vuln
+        .severity
+        .iter()
+        .find(|s| s.kind.starts_with("CVSS_V3"))
+        .map(|s| s.score.clone());
+    let severity = cvss.as_deref().and_then(Severity::from_cvss);
+
+    let patched_versions = vuln
+        .affected
+        .iter()
+        .flat_map(|a| &a.ranges)
+        .flat_map(|r| &r.events)
+        .filter_map(|event| event.get("fixed")?.as_str())
+        .map(|fixed| format!(">={}", fixed))
+        .collect();
+
+    let informational = vuln
+        .database_specific
+        .as_ref()
+        .and_then(|d| d.get("informational")?.as_str())
+        .map(str::to_string);
+
+    let url = if vuln.id.starts_with("RUSTSEC-") {
+        format!("https://rustsec.org/advisories/{}.html", vuln.id)
+    } else {
+        format!("https://osv.dev/vulnerability/{}", vuln.id)
+    };
+
+    Advisory {
+        package: crate_name.to_string(),
+        title: vuln.summary.unwrap_or_else(|| vuln.id.clone()),
+        severity,
+        cvss,
+        aliases: vuln.aliases,
+        patched_versions,
+        informational,
+        url,
+        id: vuln.id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advisory_from_osv() {
+        let response: OsvResponse = serde_json::from_str(
+            r#"{"vulns": [{
+                "id": "RUSTSEC-2021-0001",
+                "summary": "Use after free in Foo",
+                "aliases": ["CVE-2021-1234"],
+                "severity": [{"type": "CVSS_V3", "score": "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:H"}],
+                "affected": [{"ranges": [{"type": "SEMVER", "events": [
+                    {"introduced": "0.0.0-0"}, {"fixed": "1.2.3"},
+                    {"introduced": "2.0.0"}, {"fixed": "2.0.4"}
+                ]}]}]
+            }, {
+                "id": "RUSTSEC-2022-0002",
+                "database_specific": {"informational": "unmaintained"}
+            }]}"#,
+        )
+        .unwrap();
+
+        let advisories: Vec<Advisory> = response
+            .vulns
+            .into_iter()
+            .map(|v| advisory_from_osv("foo", v))
+            .collect();
+
+        assert_eq!(advisories[0].id, "RUSTSEC-2021-0001");
+        assert_eq!(advisories[0].severity, Some(Severity::High));
+        assert_eq!(advisories[0].patched_versions, vec![">=1.2.3", ">=2.0.4"]);
+        assert_eq!(
+            advisories[0].url,
+            "https://rustsec.org/advisories/RUSTSEC-2021-0001.html"
+        );
+        assert_eq!(advisories[1].title, "RUSTSEC-2022-0002");
+        assert_eq!(advisories[1].informational.as_deref(), Some("unmaintained"));
+        assert_eq!(advisories[1].severity, None);
+    }
+}
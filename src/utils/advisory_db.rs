@@ -0,0 +1,518 @@
+//! Local snapshot of advisory lookups
+//!
+//! Answers from the advisory source are kept in `.cargo-sane/advisory-db.json`
+//! along with when they were last refreshed. Scans reuse the snapshot while it
+//! is younger than the configured maximum age, refresh it once it is older,
+//! and can run entirely from it with `--offline`.
+//!
+//! A whole advisory database archive, when the config names one, is kept
+//! next to it and refreshed along with it by [`refresh_archive`]: tens of
+//! megabytes the first time, so the download shows its progress, resumes
+//! after an interruption, and only replaces the previous archive once the
+//! new one reads back whole.
+
+use crate::core::advisory::Advisory;
+use crate::core::config::Settings;
+use crate::core::manifest::Manifest;
+use crate::utils::advisories::{AdvisorySource, OsvClient};
+use crate::utils::cache::{read_json, unix_now, STATE_DIR};
+use crate::utils::download::{self, Downloaded};
+use crate::utils::progress::Progress;
+use crate::utils::timings;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Snapshot age, in days, after which the database counts as stale
+pub const DEFAULT_MAX_AGE_DAYS: u64 = Settings::DEFAULT.advisory_db_max_age_days;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+const USER_AGENT: &str = "cargo-sane (https://github.com/chronocoders/cargo-sane)";
+
+/// How long the archive download may go without receiving anything
+const ARCHIVE_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether the database may, or must, go to the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbMode {
+    /// Use the snapshot while fresh, refresh it when stale
+    Auto,
+    /// Refresh every lookup and fail if that isn't possible
+    Update,
+    /// Never touch the network, whatever the snapshot's age
+    Offline,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DbOptions {
+    pub mode: DbMode,
+    /// 0 uses `DEFAULT_MAX_AGE_DAYS`
+    pub max_age_days: u64,
+    /// Fail instead of warning when a stale snapshot can't be refreshed
+    pub strict: bool,
+}
+
+/// What a scan's advisory data is based on
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DatabaseInfo {
+    pub source: String,
+    /// Unix timestamp of the last complete refresh
+    pub fetched_at: Option<u64>,
+    /// Distinct advisories in the snapshot
+    pub advisory_count: usize,
+    pub max_age_days: u64,
+    pub stale: bool,
+    pub offline: bool,
+    /// Lookups that could not be answered (offline and not in the snapshot)
+    pub missing: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DbSnapshot {
+    source: String,
+    fetched_at: Option<u64>,
+    /// Advisories per `name@version`
+    entries: BTreeMap<String, Vec<Advisory>>,
+}
+
+/// An advisory source backed by a local snapshot of `A`'s answers
+pub struct AdvisoryDb<A = OsvClient> {
+    source: A,
+    path: PathBuf,
+    options: DbOptions,
+    /// Whether lookups go to the source even when the snapshot has them
+    refresh: bool,
+    refresh_failed: AtomicBool,
+    missing: AtomicUsize,
+    snapshot: Mutex<DbSnapshot>,
+}
+
+/// Advisories known to the local database, per crate, for judging releases
+/// that were never looked up themselves
+#[derive(Debug, Clone, Default)]
+pub struct AdvisoryIndex(HashMap<String, Vec<Advisory>>);
+
+/// Where the advisory database of the project owning `manifest` lives
+pub fn database_path(manifest: &Manifest) -> PathBuf {
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    root.join(STATE_DIR).join("advisory-db.json")
+}
+
+/// Where the advisory database archive of the project owning `manifest`
+/// is kept
+pub fn archive_path(manifest: &Manifest) -> PathBuf {
+    let root = manifest.path.parent().unwrap_or(Path::new("."));
+    root.join(STATE_DIR).join("advisory-db.tar.gz")
+}
+
+/// Download the advisory database archive at `url` to `path`, reporting
+/// bytes, speed and time left through `progress`. A partial download left
+/// by an earlier attempt is resumed, and the archive at `path` is only
+/// replaced by one that decompresses whole.
+pub async fn fetch_archive(url: &str, path: &Path, progress: &dyn Progress) -> Result<Downloaded> {
+    let _span = timings::span("advisory db");
+    let client = reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(Duration::from_secs(15))
+        .read_timeout(ARCHIVE_READ_TIMEOUT)
+        .build()
+        .context("Failed to create the HTTP client for the advisory database")?;
+    download::download(
+        &client,
+        url,
+        path,
+        "Downloading advisory database",
+        progress,
+        download::verify_gzip,
+    )
+    .await
+}
+
+/// Bring the archive at `path` up to date from `url` as `options` call for:
+/// on every `DbMode::Update` run, once it's missing or older than the
+/// maximum age in `DbMode::Auto`, and never offline. Returns the download,
+/// if one was needed.
+pub async fn refresh_archive(
+    url: &str,
+    path: &Path,
+    options: DbOptions,
+    progress: &dyn Progress,
+) -> Result<Option<Downloaded>> {
+    let refresh = match options.mode {
+        DbMode::Update => true,
+        DbMode::Auto => is_stale(modified_at(path), options.max_age_days()),
+        DbMode::Offline => false,
+    };
+    if !refresh {
+        return Ok(None);
+    }
+    fetch_archive(url, path, progress).await.map(Some)
+}
+
+fn modified_at(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+impl AdvisoryIndex {
+    /// Index the snapshot at `path`; a missing or unreadable one is empty
+    pub fn load(path: &Path) -> Self {
+        let _span = timings::span("advisory db");
+        let Some(snapshot) = read_json::<DbSnapshot>(path) else {
+            return Self::default();
+        };
+
+        let mut index: HashMap<String, Vec<Advisory>> = HashMap::new();
+        for advisory in snapshot.entries.into_values().flatten() {
+            let known = index.entry(advisory.package.clone()).or_default();
+            if !known.iter().any(|a| a.id == advisory.id) {
+                known.push(advisory);
+            }
+        }
+        Self(index)
+    }
+
+    /// The names of the packages with advisories, in no particular order
+    pub fn packages(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    /// Ids of vulnerability advisories against `name` that `version` isn't
+    /// patched for. Informational notices don't count.
+    pub fn affecting(&self, name: &str, version: &Version) -> Vec<String> {
+        self.0
+            .get(name)
+            .map(|advisories| {
+                advisories
+                    .iter()
+                    .filter(|a| a.informational.is_none() && a.affects(version))
+                    .map(|a| a.id.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl DbOptions {
+    fn max_age_days(&self) -> u64 {
+        if self.max_age_days == 0 {
+            DEFAULT_MAX_AGE_DAYS
+        } else {
+            self.max_age_days
+        }
+    }
+}
+
+impl<A: AdvisorySource> AdvisoryDb<A> {
+    /// Load the snapshot at `path` (a missing file is an empty, stale
+    /// snapshot) in front of `source`, described as `source_name`
+    pub fn open(source: A, source_name: &str, path: PathBuf, options: DbOptions) -> Result<Self> {
+        let _span = timings::span("advisory db");
+        // A corrupt snapshot is only a cache; start over
+        let mut snapshot: DbSnapshot = read_json(&path).unwrap_or_default();
+        if snapshot.source != source_name {
+            snapshot = DbSnapshot {
+                source: source_name.to_string(),
+                ..DbSnapshot::default()
+            };
+        }
+
+        let stale = is_stale(snapshot.fetched_at, options.max_age_days());
+        let refresh = match options.mode {
+            DbMode::Update => true,
+            DbMode::Auto => stale,
+            DbMode::Offline => false,
+        };
+
+        Ok(Self {
+            source,
+            path,
+            options,
+            refresh,
+            refresh_failed: AtomicBool::new(false),
+            missing: AtomicUsize::new(0),
+            snapshot: Mutex::new(snapshot),
+        })
+    }
+
+    /// Describe the data the lookups so far were answered from. A completed
+    /// refresh counts as fetched now even before `save`.
+    pub fn info(&self) -> DatabaseInfo {
+        let snapshot = self.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+        let fetched_at = self.effective_fetched_at(&snapshot);
+        let advisory_count = snapshot
+            .entries
+            .values()
+            .flatten()
+            .map(|a| a.id.as_str())
+            .collect::<BTreeSet<_>>()
+            .len();
+
+        DatabaseInfo {
+            source: snapshot.source.clone(),
+            fetched_at,
+            advisory_count,
+            max_age_days: self.options.max_age_days(),
+            stale: is_stale(fetched_at, self.options.max_age_days()),
+            offline: self.options.mode == DbMode::Offline,
+            missing: self.missing.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Write the snapshot back, stamping it as fetched now if every lookup
+    /// of a refresh succeeded
+    pub fn save(&self) -> Result<()> {
+        if self.options.mode == DbMode::Offline {
+            return Ok(());
+        }
+        let mut snapshot = self.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+        snapshot.fetched_at = self.effective_fetched_at(&snapshot);
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&self.path, serde_json::to_string(&*snapshot)?)
+            .context(format!("Failed to write {}", self.path.display()))
+    }
+
+    fn effective_fetched_at(&self, snapshot: &DbSnapshot) -> Option<u64> {
+        if self.refresh && !self.refresh_failed.load(Ordering::Relaxed) {
+            Some(unix_now())
+        } else {
+            snapshot.fetched_at
+        }
+    }
+
+    fn cached(&self, key: &str) -> Option<Vec<Advisory>> {
+        let snapshot = self.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+        snapshot.entries.get(key).cloned()
+    }
+}
+
+impl<A: AdvisorySource + Sync> AdvisorySource for AdvisoryDb<A> {
+    async fn advisories_for(&self, crate_name: &str, version: &Version) -> Result<Vec<Advisory>> {
+        let key = format!("{}@{}", crate_name, version);
+        let cached = self.cached(&key);
+
+        if self.options.mode == DbMode::Offline {
+            if cached.is_none() {
+                self.missing.fetch_add(1, Ordering::Relaxed);
+            }
+            return Ok(cached.unwrap_or_default());
+        }
+        if !self.refresh {
+            if let Some(advisories) = cached {
+                return Ok(advisories);
+            }
+        }
+
+        match self.source.advisories_for(crate_name, version).await {
+            Ok(advisories) => {
+                let mut snapshot = self.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+                snapshot.entries.insert(key, advisories.clone());
+                Ok(advisories)
+            }
+            Err(e) if self.refresh => {
+                self.refresh_failed.store(true, Ordering::Relaxed);
+                match cached {
+                    Some(advisories)
+                        if self.options.mode == DbMode::Auto && !self.options.strict =>
+                    {
+                        Ok(advisories)
+                    }
+                    _ if self.options.mode == DbMode::Update => {
+                        Err(e.context("Could not refresh the advisory database"))
+                    }
+                    _ => Err(e.context(format!(
+                        "Advisory database is older than {} days and could not be refreshed",
+                        self.options.max_age_days()
+                    ))),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn database_info(&self) -> Option<DatabaseInfo> {
+        Some(self.info())
+    }
+}
+
+fn is_stale(fetched_at: Option<u64>, max_age_days: u64) -> bool {
+    fetched_at.is_none_or(|at| unix_now().saturating_sub(at) > max_age_days * SECONDS_PER_DAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Answers one advisory per lookup, or fails when `online` is false
+    struct Source {
+        online: bool,
+        calls: AtomicUsize,
+    }
+
+    impl AdvisorySource for Source {
+        async fn advisories_for(&self, crate_name: &str, _: &Version) -> Result<Vec<Advisory>> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            if !self.online {
+                anyhow::bail!("network unreachable");
+            }
+            Ok(vec![Advisory {
+                id: format!("RUSTSEC-{}", crate_name),
+                package: crate_name.to_string(),
+                title: "bad".to_string(),
+                severity: None,
+                cvss: None,
+                aliases: Vec::new(),
+                patched_versions: Vec::new(),
+                informational: None,
+                url: String::new(),
+            }])
+        }
+    }
+
+    fn source(online: bool) -> Source {
+        Source {
+            online,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    fn options(mode: DbMode, strict: bool) -> DbOptions {
+        DbOptions {
+            mode,
+            max_age_days: 7,
+            strict,
+        }
+    }
+
+    fn lookup<A: AdvisorySource + Sync>(db: &AdvisoryDb<A>, name: &str) -> Result<Vec<Advisory>> {
+        futures::executor::block_on(db.advisories_for(name, &Version::new(1, 0, 0)))
+    }
+
+    /// Write a snapshot holding `foo@1.0.0`, fetched `age_days` ago
+    fn seed(path: &PathBuf, age_days: u64) {
+        let db = AdvisoryDb::open(
+            source(true),
+            "test",
+            path.clone(),
+            options(DbMode::Update, false),
+        )
+        .unwrap();
+        lookup(&db, "foo").unwrap();
+        db.save().unwrap();
+
+        let mut snapshot: DbSnapshot =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        snapshot.fetched_at = Some(unix_now() - age_days * SECONDS_PER_DAY);
+        fs::write(path, serde_json::to_string(&snapshot).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_fresh_snapshot_is_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("advisory-db.json");
+        seed(&path, 1);
+
+        let db =
+            AdvisoryDb::open(source(false), "test", path, options(DbMode::Auto, true)).unwrap();
+        assert_eq!(lookup(&db, "foo").unwrap().len(), 1);
+        assert_eq!(db.source.calls.load(Ordering::Relaxed), 0);
+
+        let info = db.info();
+        assert!(!info.stale);
+        assert_eq!(info.advisory_count, 1);
+        assert_eq!(info.source, "test");
+    }
+
+    #[test]
+    fn test_stale_snapshot_is_refreshed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("advisory-db.json");
+        seed(&path, 30);
+
+        let db = AdvisoryDb::open(
+            source(true),
+            "test",
+            path.clone(),
+            options(DbMode::Auto, true),
+        )
+        .unwrap();
+        lookup(&db, "foo").unwrap();
+        assert_eq!(db.source.calls.load(Ordering::Relaxed), 1);
+        db.save().unwrap();
+        assert!(!db.info().stale);
+    }
+
+    #[test]
+    fn test_stale_snapshot_without_network() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("advisory-db.json");
+        seed(&path, 30);
+
+        // Lenient: fall back to the stale data, which stays marked stale
+        let db = AdvisoryDb::open(
+            source(false),
+            "test",
+            path.clone(),
+            options(DbMode::Auto, false),
+        )
+        .unwrap();
+        assert_eq!(lookup(&db, "foo").unwrap().len(), 1);
+        assert!(db.info().stale);
+
+        // Strict: a deterministic failure for CI
+        let db = AdvisoryDb::open(
+            source(false),
+            "test",
+            path.clone(),
+            options(DbMode::Auto, true),
+        )
+        .unwrap();
+        assert!(lookup(&db, "foo").is_err());
+
+        // Offline: staleness accepted, unknown crates counted as missing
+        let db =
+            AdvisoryDb::open(source(false), "test", path, options(DbMode::Offline, true)).unwrap();
+        assert_eq!(lookup(&db, "foo").unwrap().len(), 1);
+        assert!(lookup(&db, "bar").unwrap().is_empty());
+        assert_eq!(db.source.calls.load(Ordering::Relaxed), 0);
+        let info = db.info();
+        assert!(info.stale && info.offline);
+        assert_eq!(info.missing, 1);
+    }
+
+    #[test]
+    fn test_index_of_open_advisories() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("advisory-db.json");
+        seed(&path, 1);
+
+        let mut snapshot: DbSnapshot =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        snapshot.entries.get_mut("foo@1.0.0").unwrap()[0].patched_versions =
+            vec![">=1.2.0".to_string()];
+        fs::write(&path, serde_json::to_string(&snapshot).unwrap()).unwrap();
+
+        let index = AdvisoryIndex::load(&path);
+        assert_eq!(
+            index.affecting("foo", &Version::new(1, 1, 9)),
+            vec!["RUSTSEC-foo"]
+        );
+        assert!(index.affecting("foo", &Version::new(1, 2, 0)).is_empty());
+        assert!(index.affecting("bar", &Version::new(1, 0, 0)).is_empty());
+        assert!(AdvisoryIndex::load(&dir.path().join("missing.json"))
+            .affecting("foo", &Version::new(1, 0, 0))
+            .is_empty());
+    }
+}
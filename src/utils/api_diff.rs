@@ -0,0 +1,421 @@
+//! How much of a crate's public API a major update breaks
+//!
+//! The estimate starts from the rustdoc JSON docs.rs builds for each
+//! release. When cargo-semver-checks is installed, it's run on the two
+//! builds and every breaking change it reports counts. Otherwise the public
+//! item paths of the builds are compared, and items the new release no
+//! longer has count as removed or renamed. Without JSON for both releases
+//! there's nothing to go on, and nothing is reported rather than a guess.
+//! Estimates are cached under `.cargo-sane/`, since releases never change.
+
+use crate::utils::cache::{read_json, STATE_DIR};
+use crate::utils::cargo::{run_cargo, run_cargo_status, CargoOptions};
+use crate::utils::crates_io::USER_AGENT;
+use crate::utils::formatting::plural;
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use futures::stream::{self, StreamExt};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const DOCS_RS: &str = "https://docs.rs";
+
+const CACHE_FILE: &str = "api-diff.json";
+
+/// What an estimate is based on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApiDiffSource {
+    SemverChecks,
+    DocsRs,
+}
+
+impl fmt::Display for ApiDiffSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiDiffSource::SemverChecks => write!(f, "cargo-semver-checks"),
+            ApiDiffSource::DocsRs => write!(f, "docs.rs"),
+        }
+    }
+}
+
+/// The breaking surface of moving `name` from `from` to `to`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiDiff {
+    pub name: String,
+    pub from: Version,
+    pub to: Version,
+    pub source: ApiDiffSource,
+    /// Breaking changes cargo-semver-checks reported, or public items the
+    /// new release no longer has
+    pub breaking: usize,
+}
+
+impl ApiDiff {
+    /// E.g. "47 items removed/renamed according to docs.rs"
+    pub fn describe(&self) -> String {
+        match self.source {
+            ApiDiffSource::SemverChecks => format!(
+                "{} according to {}",
+                plural(self.breaking as u64, "breaking change"),
+                self.source
+            ),
+            ApiDiffSource::DocsRs => format!(
+                "{} removed/renamed according to {}",
+                plural(self.breaking as u64, "item"),
+                self.source
+            ),
+        }
+    }
+
+    /// The source changes between the two releases
+    pub fn link(&self) -> String {
+        format!("https://diff.rs/{}/{}/{}", self.name, self.from, self.to)
+    }
+
+    fn cache_key(name: &str, from: &Version, to: &Version) -> String {
+        format!("{}@{}..{}", name, from, to)
+    }
+}
+
+/// The part of rustdoc's JSON output the estimate needs
+#[derive(Debug, Deserialize)]
+struct RustdocJson {
+    paths: HashMap<String, ItemSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemSummary {
+    crate_id: u32,
+    path: Vec<String>,
+    kind: String,
+}
+
+pub struct ApiDiffClient {
+    client: reqwest::Client,
+    docs_rs: String,
+    /// How to run cargo-semver-checks; `None` compares item paths only
+    cargo: Option<CargoOptions>,
+    semver_checks: OnceLock<bool>,
+}
+
+impl ApiDiffClient {
+    pub fn new(cargo: Option<CargoOptions>) -> Result<Self> {
+        Self::with_base_url(DOCS_RS, cargo)
+    }
+
+    /// Create a client fetching rustdoc JSON from a docs.rs-compatible host
+    pub fn with_base_url(docs_rs: &str, cargo: Option<CargoOptions>) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(60))
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self {
+            client,
+            docs_rs: docs_rs.trim_end_matches('/').to_string(),
+            cargo,
+            semver_checks: OnceLock::new(),
+        })
+    }
+
+    /// The breaking surface of moving `name` from `from` to `to`, or `None`
+    /// when docs.rs has no JSON for either release
+    pub async fn api_diff(&self, name: &str, from: &Version, to: &Version) -> Option<ApiDiff> {
+        let (old, new) = futures::join!(self.rustdoc(name, from), self.rustdoc(name, to));
+        let (old, new) = (old?, new?);
+
+        let diff = |source, breaking| ApiDiff {
+            name: name.to_string(),
+            from: from.clone(),
+            to: to.clone(),
+            source,
+            breaking,
+        };
+        if let Some(cargo) = self
+            .cargo
+            .as_ref()
+            .filter(|cargo| self.has_semver_checks(cargo))
+        {
+            let dir = std::env::temp_dir().join(format!(
+                "cargo-sane-api-diff-{}-{}-{}",
+                std::process::id(),
+                name,
+                to
+            ));
+            if let Some(breaking) = semver_checks(cargo, &dir, &old, &new) {
+                return Some(diff(ApiDiffSource::SemverChecks, breaking));
+            }
+        }
+        let removed = public_items(&old)?.difference(&public_items(&new)?).count();
+        Some(diff(ApiDiffSource::DocsRs, removed))
+    }
+
+    /// The rustdoc JSON docs.rs built for `name` `version`
+    async fn rustdoc(&self, name: &str, version: &Version) -> Option<Vec<u8>> {
+        let url = format!("{}/crate/{}/{}/json.gz", self.docs_rs, name, version);
+        let response = self.client.get(&url).send().await.ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        let compressed = response.bytes().await.ok()?;
+        let mut json = Vec::new();
+        GzDecoder::new(&compressed[..])
+            .read_to_end(&mut json)
+            .ok()?;
+        Some(json)
+    }
+
+    /// Whether `cargo semver-checks` runs, asked once per client
+    fn has_semver_checks(&self, cargo: &CargoOptions) -> bool {
+        *self.semver_checks.get_or_init(|| {
+            run_cargo(
+                &["semver-checks", "--version"],
+                &std::env::temp_dir(),
+                cargo,
+            )
+            .is_ok()
+        })
+    }
+}
+
+/// Estimates for each of `updates`, from the cache in the project at `root`
+/// where there, and from docs.rs otherwise
+pub async fn api_diffs(
+    client: &ApiDiffClient,
+    updates: &[(&str, &Version, &Version)],
+    root: &Path,
+    concurrency: usize,
+) -> Result<Vec<Option<ApiDiff>>> {
+    let path = cache_path(root);
+    let mut cache: BTreeMap<String, ApiDiff> = read_json(&path).unwrap_or_default();
+
+    let missing: Vec<&(&str, &Version, &Version)> = updates
+        .iter()
+        .filter(|(name, from, to)| !cache.contains_key(&ApiDiff::cache_key(name, from, to)))
+        .collect();
+    let fetched: Vec<Option<ApiDiff>> = stream::iter(missing)
+        .map(|(name, from, to)| client.api_diff(name, from, to))
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    // Only found estimates are kept: docs.rs may build the JSON later
+    let mut updated = false;
+    for diff in fetched.into_iter().flatten() {
+        cache.insert(ApiDiff::cache_key(&diff.name, &diff.from, &diff.to), diff);
+        updated = true;
+    }
+    if updated {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&path, serde_json::to_string(&cache)?)
+            .context(format!("Failed to write {}", path.display()))?;
+    }
+
+    Ok(updates
+        .iter()
+        .map(|(name, from, to)| cache.get(&ApiDiff::cache_key(name, from, to)).cloned())
+        .collect())
+}
+
+fn cache_path(root: &Path) -> PathBuf {
+    root.join(STATE_DIR).join(CACHE_FILE)
+}
+
+/// The crate's own items by kind and path below the crate root, which
+/// survives the crate being renamed
+fn public_items(json: &[u8]) -> Option<BTreeSet<String>> {
+    let rustdoc: RustdocJson = serde_json::from_slice(json).ok()?;
+    Some(
+        rustdoc
+            .paths
+            .into_values()
+            .filter(|item| item.crate_id == 0 && item.path.len() > 1)
+            .map(|item| format!("{} {}", item.kind, item.path[1..].join("::")))
+            .collect(),
+    )
+}
+
+/// Run `cargo semver-checks` on the two rustdoc builds in the scratch
+/// directory `dir`, returning how many breaking changes it reports, or
+/// `None` when it couldn't compare them
+fn semver_checks(cargo: &CargoOptions, dir: &Path, old: &[u8], new: &[u8]) -> Option<usize> {
+    fs::create_dir_all(dir).ok()?;
+    let baseline = dir.join("baseline.json");
+    let current = dir.join("current.json");
+    let result = fs::write(&baseline, old)
+        .and_then(|_| fs::write(&current, new))
+        .ok()
+        .and_then(|_| {
+            run_cargo_status(
+                &[
+                    OsStr::new("semver-checks"),
+                    OsStr::new("check-release"),
+                    OsStr::new("--baseline-rustdoc"),
+                    baseline.as_os_str(),
+                    OsStr::new("--current-rustdoc"),
+                    current.as_os_str(),
+                ],
+                dir,
+                cargo,
+            )
+            .ok()
+        });
+    let _ = fs::remove_dir_all(dir);
+
+    // It exits 1 when it finds breaking changes, so the output decides
+    let (_, output) = result?;
+    count_semver_failures(&format!("{}\n{}", output.stdout, output.stderr))
+}
+
+/// Count the places listed under each "Failed in:" of a cargo-semver-checks
+/// report. Output without its closing summary didn't come from a finished
+/// comparison.
+fn count_semver_failures(output: &str) -> Option<usize> {
+    if !output
+        .lines()
+        .any(|line| line.trim_start().starts_with("Summary"))
+    {
+        return None;
+    }
+    let mut failures = 0;
+    let mut listing = false;
+    for line in output.lines() {
+        if line.trim() == "Failed in:" {
+            listing = true;
+        } else if line.trim().is_empty() || !line.starts_with(char::is_whitespace) {
+            listing = false;
+        } else if listing {
+            failures += 1;
+        }
+    }
+    Some(failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Recorded from `cargo semver-checks check-release` on two releases
+    /// that removed a function and a variant
+    const SEMVER_CHECKS_OUTPUT: &str = "     Parsing demo v1.0.0 (current)
+      Parsed [   0.010s] (current)
+     Parsing demo v0.9.0 (baseline)
+      Parsed [   0.009s] (baseline)
+    Checking demo v0.9.0 -> v1.0.0 (major change)
+     Checked [   0.012s] 152 checks: 150 pass, 2 fail, 0 warn, 0 skip
+
+--- failure enum_variant_missing: pub enum variant removed or renamed ---
+
+Description:
+A publicly-visible enum has at least one variant that is no longer available under its prior name. It may have been renamed or removed entirely.
+        ref: https://doc.rust-lang.org/cargo/reference/semver.html#enum-variant-new
+       impl: https://github.com/obi1kenobi/cargo-semver-checks/tree/v0.41.0/src/lints/enum_variant_missing.ron
+
+Failed in:
+  variant Mode::Legacy, previously in file src/lib.rs:12
+
+--- failure function_missing: pub fn removed or renamed ---
+
+Description:
+A publicly-visible function cannot be imported by its prior path. A `pub use` may have been removed, or the function itself may have been renamed or removed entirely.
+        ref: https://doc.rust-lang.org/cargo/reference/semver.html#item-remove
+       impl: https://github.com/obi1kenobi/cargo-semver-checks/tree/v0.41.0/src/lints/function_missing.ron
+
+Failed in:
+  function demo::parse_legacy, previously in file src/lib.rs:30
+  function demo::util::parse_legacy, previously in file src/util.rs:4
+
+     Summary semver requires new major version: 2 major and 0 minor checks failed
+";
+
+    #[test]
+    fn test_count_semver_failures() {
+        assert_eq!(count_semver_failures(SEMVER_CHECKS_OUTPUT), Some(3));
+        assert_eq!(
+            count_semver_failures("     Summary no semver update required\n"),
+            Some(0)
+        );
+        // Gave up before comparing anything
+        assert_eq!(
+            count_semver_failures("error: rustdoc format version 39 is not supported\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_public_items() {
+        let old = br#"{"format_version": 39, "paths": {
+            "0": {"crate_id": 0, "path": ["demo"], "kind": "module"},
+            "1": {"crate_id": 0, "path": ["demo", "parse"], "kind": "function"},
+            "2": {"crate_id": 0, "path": ["demo", "parse_legacy"], "kind": "function"},
+            "3": {"crate_id": 0, "path": ["demo", "Mode"], "kind": "enum"},
+            "4": {"crate_id": 1, "path": ["std", "string", "String"], "kind": "struct"}
+        }}"#;
+        let new = br#"{"format_version": 39, "paths": {
+            "0": {"crate_id": 0, "path": ["demo_next"], "kind": "module"},
+            "1": {"crate_id": 0, "path": ["demo_next", "parse"], "kind": "function"},
+            "2": {"crate_id": 0, "path": ["demo_next", "Mode"], "kind": "struct"}
+        }}"#;
+        let old = public_items(old).unwrap();
+        let new = public_items(new).unwrap();
+        let removed: Vec<&String> = old.difference(&new).collect();
+        assert_eq!(removed, vec!["enum Mode", "function parse_legacy"]);
+    }
+
+    #[test]
+    fn test_describe() {
+        let diff = ApiDiff {
+            name: "demo".to_string(),
+            from: Version::new(0, 9, 0),
+            to: Version::new(1, 0, 0),
+            source: ApiDiffSource::DocsRs,
+            breaking: 47,
+        };
+        assert_eq!(
+            diff.describe(),
+            "47 items removed/renamed according to docs.rs"
+        );
+        assert_eq!(diff.link(), "https://diff.rs/demo/0.9.0/1.0.0");
+    }
+
+    #[test]
+    fn test_cached_estimates_are_used_and_missing_ones_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let cached = ApiDiff {
+            name: "demo".to_string(),
+            from: Version::new(0, 9, 0),
+            to: Version::new(1, 0, 0),
+            source: ApiDiffSource::SemverChecks,
+            breaking: 3,
+        };
+        let path = cache_path(dir.path());
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let cache = BTreeMap::from([(
+            ApiDiff::cache_key("demo", &cached.from, &cached.to),
+            cached.clone(),
+        )]);
+        fs::write(&path, serde_json::to_string(&cache).unwrap()).unwrap();
+
+        // Nothing listens here, so anything not cached has no estimate
+        let client = ApiDiffClient::with_base_url("http://127.0.0.1:9", None).unwrap();
+        let (old, new) = (Version::new(1, 0, 0), Version::new(2, 0, 0));
+        let updates = [("demo", &cached.from, &cached.to), ("other", &old, &new)];
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let diffs = runtime
+            .block_on(api_diffs(&client, &updates, dir.path(), 4))
+            .unwrap();
+        assert_eq!(diffs, vec![Some(cached), None]);
+    }
+}
@@ -0,0 +1,240 @@
+//! Append-only log of the changes cargo-sane makes, under `.cargo-sane/`
+//!
+//! Every command that edits Cargo.toml or Cargo.lock appends one JSON line
+//! to `audit.log`: when, who, the command line, and each change. A line is
+//! written with a single append, so runs finishing at the same time don't
+//! interleave. `disable_audit_log` in the config turns the log off.
+
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::Manifest;
+use crate::utils::cache::{unix_now, STATE_DIR};
+use crate::utils::formatting::{format_timestamp, parse_timestamp};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const AUDIT_FILE: &str = "audit.log";
+
+/// One run of a command that changed something
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// RFC 3339, UTC
+    pub timestamp: String,
+    pub user: Option<String>,
+    /// The subcommand, e.g. "update"
+    pub command: String,
+    /// The arguments after the subcommand, as given
+    pub args: Vec<String>,
+    pub manifest: PathBuf,
+    pub changes: Vec<AuditChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<PathBuf>,
+    /// Whether Cargo.toml and Cargo.lock still parsed after the change
+    pub verified: bool,
+}
+
+/// One changed requirement or locked version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditChange {
+    #[serde(rename = "crate")]
+    pub name: String,
+    /// None when the change added the crate
+    pub old: Option<String>,
+    /// None when the change removed the crate
+    pub new: Option<String>,
+    /// The manifest table, e.g. "dependencies", or "Cargo.lock"
+    pub section: String,
+}
+
+impl AuditEntry {
+    /// An entry for `command` editing `manifest` now, with the arguments of
+    /// this process after the subcommand
+    pub fn new(command: &str, manifest: &Path) -> Self {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let args = match args.iter().position(|a| a == command) {
+            Some(at) => args[at + 1..].to_vec(),
+            None => args,
+        };
+        Self {
+            timestamp: format_timestamp(unix_now()),
+            user: std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .ok(),
+            command: command.to_string(),
+            args,
+            manifest: manifest.to_path_buf(),
+            changes: Vec::new(),
+            backup: None,
+            verified: false,
+        }
+    }
+
+    pub fn with_changes(mut self, changes: Vec<AuditChange>) -> Self {
+        self.changes = changes;
+        self
+    }
+
+    pub fn with_backup(mut self, backup: Option<PathBuf>) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    /// Re-read the manifest and its lockfile and record whether both parse
+    pub fn verified(mut self) -> Self {
+        self.verified = Manifest::from_path(&self.manifest)
+            .and_then(|manifest| Lockfile::for_manifest(&manifest))
+            .is_ok();
+        self
+    }
+}
+
+/// Which entries `cargo sane audit` shows
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    /// Unix time of the earliest entry
+    pub since: Option<u64>,
+    /// Only entries changing this crate, and only those changes
+    pub name: Option<String>,
+}
+
+impl AuditFilter {
+    /// `entry` as the filter shows it, or `None` when it doesn't match
+    pub fn apply(&self, entry: &AuditEntry) -> Option<AuditEntry> {
+        if let Some(since) = self.since {
+            if parse_timestamp(&entry.timestamp).is_none_or(|at| at < since) {
+                return None;
+            }
+        }
+        let Some(name) = &self.name else {
+            return Some(entry.clone());
+        };
+        let changes: Vec<AuditChange> = entry
+            .changes
+            .iter()
+            .filter(|c| &c.name == name)
+            .cloned()
+            .collect();
+        (!changes.is_empty()).then(|| AuditEntry {
+            changes,
+            ..entry.clone()
+        })
+    }
+}
+
+/// The audit log of one project
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    /// The log of the project owning the manifest at `manifest_path`
+    pub fn for_manifest(manifest_path: &Path) -> Self {
+        let root = manifest_path.parent().unwrap_or(Path::new("."));
+        Self {
+            path: root.join(STATE_DIR).join(AUDIT_FILE),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `entry` as one line
+    pub fn append(&self, entry: &AuditEntry) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .context(format!("Failed to append to {}", self.path.display()))
+    }
+
+    /// Every entry, oldest first, and the number of lines that couldn't be
+    /// parsed. A missing log has no entries.
+    pub fn read(&self) -> Result<(Vec<AuditEntry>, usize)> {
+        if !self.path.exists() {
+            return Ok((Vec::new(), 0));
+        }
+        let content = fs::read_to_string(&self.path)
+            .context(format!("Failed to read {}", self.path.display()))?;
+        let mut entries = Vec::new();
+        let mut malformed = 0;
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) => malformed += 1,
+            }
+        }
+        Ok((entries, malformed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: &str, names: &[&str]) -> AuditEntry {
+        AuditEntry {
+            timestamp: timestamp.to_string(),
+            user: Some("alice".to_string()),
+            command: "update".to_string(),
+            args: vec!["--all".to_string()],
+            manifest: PathBuf::from("Cargo.toml"),
+            changes: names
+                .iter()
+                .map(|name| AuditChange {
+                    name: name.to_string(),
+                    old: Some("1.0".to_string()),
+                    new: Some("1.1".to_string()),
+                    section: "dependencies".to_string(),
+                })
+                .collect(),
+            backup: Some(PathBuf::from("Cargo.toml.backup")),
+            verified: true,
+        }
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::for_manifest(&dir.path().join("Cargo.toml"));
+        let first = entry("2024-05-01T10:00:00Z", &["serde"]);
+        let second = entry("2024-05-02T10:00:00Z", &["tokio"]);
+        log.append(&first).unwrap();
+        log.append(&second).unwrap();
+
+        let content = fs::read_to_string(log.path()).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.contains(r#""crate":"serde""#));
+
+        fs::write(log.path(), format!("{}not json\n", content)).unwrap();
+        assert_eq!(log.read().unwrap(), (vec![first, second], 1));
+    }
+
+    #[test]
+    fn test_filter() {
+        let entry = entry("2024-05-02T10:00:00Z", &["serde", "tokio"]);
+        let since = |date: &str| AuditFilter {
+            since: crate::utils::formatting::parse_date(date),
+            name: None,
+        };
+        assert!(since("2024-05-02").apply(&entry).is_some());
+        assert!(since("2024-05-03").apply(&entry).is_none());
+
+        let by_name = |name: &str| AuditFilter {
+            since: None,
+            name: Some(name.to_string()),
+        };
+        let filtered = by_name("tokio").apply(&entry).unwrap();
+        assert_eq!(filtered.changes.len(), 1);
+        assert_eq!(filtered.changes[0].name, "tokio");
+        assert!(by_name("rand").apply(&entry).is_none());
+    }
+}
@@ -0,0 +1,325 @@
+//! On-disk caching of analysis reports
+
+use crate::core::lockfile::Lockfile;
+use crate::core::manifest::{DependencySpec, Manifest};
+use crate::utils::advisory_db::database_path;
+use crate::utils::test_mode::Scenario;
+use crate::utils::timings;
+use anyhow::{Context, Result};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Directory, next to Cargo.toml, holding cargo-sane's per-project state
+pub const STATE_DIR: &str = ".cargo-sane";
+
+/// Suffix of the corrupt cache files set aside by [`quarantine`]
+pub const QUARANTINE_SUFFIX: &str = ".corrupt";
+
+/// A report stored together with what it was computed from
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry<T> {
+    key: String,
+    created_at: u64,
+    report: T,
+}
+
+/// A single cached report file, valid for a limited time
+pub struct ReportCache {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+impl ReportCache {
+    /// The check report cache of the project owning `manifest`
+    pub fn for_manifest(manifest: &Manifest, ttl_minutes: u64) -> Self {
+        let root = manifest.path.parent().unwrap_or(Path::new("."));
+        Self::new(root.join(STATE_DIR).join("cache.json"), ttl_minutes)
+    }
+
+    pub fn new(path: PathBuf, ttl_minutes: u64) -> Self {
+        Self {
+            path,
+            ttl: Duration::from_secs(ttl_minutes * 60),
+        }
+    }
+
+    /// A zero TTL disables the cache entirely, and so does `--test-mode`,
+    /// whose runs must not depend on earlier ones
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero() && Scenario::active().is_none()
+    }
+
+    /// Load the cached report if it was computed for `key` and is still fresh,
+    /// returning it along with its age. Unreadable or stale entries are ignored.
+    pub fn load<T: DeserializeOwned>(&self, key: &str) -> Option<(T, Duration)> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let found = self.read(key);
+        timings::count(
+            if found.is_some() {
+                "cache hits"
+            } else {
+                "cache misses"
+            },
+            1,
+        );
+        found
+    }
+
+    fn read<T: DeserializeOwned>(&self, key: &str) -> Option<(T, Duration)> {
+        let entry: CacheEntry<T> = read_json(&self.path)?;
+        if entry.key != key {
+            return None;
+        }
+
+        let age = Duration::from_secs(unix_now().saturating_sub(entry.created_at));
+        (age < self.ttl).then_some((entry.report, age))
+    }
+
+    /// Persist `report` as the cached result for `key`
+    pub fn store<T: Serialize>(&self, key: &str, report: &T) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+
+        let entry = CacheEntry {
+            key: key.to_string(),
+            created_at: unix_now(),
+            report,
+        };
+        fs::write(&self.path, serde_json::to_string(&entry)?)
+            .context(format!("Failed to write {}", self.path.display()))
+    }
+}
+
+/// Read the cache file at `path`, or `None` when it's missing or laid out
+/// for another version of cargo-sane. A file that isn't JSON at all, as a
+/// write cut short leaves it, is set aside rather than read again and again.
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let content = fs::read(path).ok()?;
+    match serde_json::from_slice(&content) {
+        Ok(value) => Some(value),
+        Err(_) => {
+            if serde_json::from_slice::<IgnoredAny>(&content).is_err() {
+                quarantine(path);
+            }
+            None
+        }
+    }
+}
+
+/// Where a corrupt cache file at `path` is set aside
+pub fn quarantine_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(QUARANTINE_SUFFIX);
+    path.with_file_name(name)
+}
+
+/// Rename the corrupt cache file at `path` aside, saying so on stderr so
+/// JSON output stays clean
+pub fn quarantine(path: &Path) {
+    let aside = quarantine_path(path);
+    match fs::rename(path, &aside) {
+        Ok(()) => eprintln!(
+            "Warning: Set aside the corrupt cache file {} as {}; `cargo sane cache clear` removes it",
+            path.display(),
+            aside.display()
+        ),
+        Err(e) => eprintln!(
+            "Warning: Ignoring the corrupt cache file {} (could not set it aside: {}); \
+             `cargo sane cache clear` removes it",
+            path.display(),
+            e
+        ),
+    }
+}
+
+/// Cache key covering every dependency declaration in the manifest (and the
+/// tool version), so any change to a dependency line invalidates the cache
+/// while edits elsewhere in Cargo.toml do not. `workspace = true`
+/// declarations count as what they inherit, so bumping the root's entry
+/// invalidates its members' caches too.
+pub fn manifest_key(manifest: &Manifest) -> String {
+    // serde_json::Value keeps object keys sorted, so this is canonical
+    let canonical = |spec: &DependencySpec| {
+        serde_json::to_value(spec)
+            .map(|v| v.to_string())
+            .unwrap_or_default()
+    };
+    let mut data = String::from(env!("CARGO_PKG_VERSION"));
+    for (section, name, spec) in manifest.declarations() {
+        let spec = manifest.resolve_inherited(&name, &spec).unwrap_or(spec);
+        data.push_str(&format!("\n[{}] {} = {}", section, name, canonical(&spec)));
+    }
+    let mut entries: Vec<_> = manifest
+        .content
+        .workspace
+        .iter()
+        .flat_map(|workspace| &workspace.dependencies)
+        .collect();
+    entries.sort_by_key(|(name, _)| *name);
+    for (name, spec) in entries {
+        data.push_str(&format!(
+            "\n[workspace.dependencies] {} = {}",
+            name,
+            canonical(spec)
+        ));
+    }
+    fingerprint(&data)
+}
+
+/// [`manifest_key`] extended with the other files a check reads, the
+/// advisory database and Cargo.lock, so a refreshed database or a
+/// re-resolved lockfile invalidates the cached report too
+pub fn check_key(manifest: &Manifest) -> String {
+    let mut data = manifest_key(manifest);
+    let inputs = [
+        ("advisories", database_path(manifest)),
+        ("lock", Lockfile::path_for(manifest)),
+    ];
+    for (input, path) in inputs {
+        // A missing file adds nothing, which no contents hash to
+        if let Ok(bytes) = fs::read(&path) {
+            data.push_str(&format!("\n{} {}", input, fingerprint_bytes(&bytes)));
+        }
+    }
+    fingerprint(&data)
+}
+
+/// Stable 64-bit FNV-1a hash, hex encoded. std's hasher is deliberately not
+/// stable across releases, which would silently invalidate every cache.
+pub fn fingerprint(data: &str) -> String {
+    fingerprint_bytes(data.as_bytes())
+}
+
+/// [`fingerprint`] of data that may not be UTF-8
+pub fn fingerprint_bytes(data: &[u8]) -> String {
+    let hash = data.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(text: &str) -> Manifest {
+        Manifest::parse(PathBuf::from("Cargo.toml"), text).unwrap()
+    }
+
+    #[test]
+    fn test_manifest_key_tracks_dependency_lines_only() {
+        let base = manifest(
+            "[package]\nname = \"a\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1.0\"\n",
+        );
+        let renamed = manifest(
+            "[package]\nname = \"b\"\nversion = \"0.2.0\"\n\n[dependencies]\nserde = \"1.0\"\n",
+        );
+        let bumped = manifest(
+            "[package]\nname = \"a\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1.1\"\n",
+        );
+        let featured = manifest(
+            "[package]\nname = \"a\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = { version = \"1.0\", features = [\"derive\"] }\n",
+        );
+
+        assert_eq!(manifest_key(&base), manifest_key(&renamed));
+        assert_ne!(manifest_key(&base), manifest_key(&bumped));
+        assert_ne!(manifest_key(&base), manifest_key(&featured));
+    }
+
+    #[test]
+    fn test_manifest_key_tracks_inherited_requirements() {
+        let root = |tokio: &str| {
+            manifest(&format!(
+                "[workspace]\nmembers = [\"a\"]\n\n[workspace.dependencies]\ntokio = \"{}\"\n",
+                tokio
+            ))
+        };
+        let member = |root: &Manifest| {
+            Manifest::parse(
+                PathBuf::from("a/Cargo.toml"),
+                "[package]\nname = \"a\"\nversion = \"0.1.0\"\n\n[dependencies]\ntokio = { workspace = true }\n",
+            )
+            .unwrap()
+            .with_workspace_root(root)
+        };
+        let (old, new) = (root("1"), root("2"));
+
+        assert_ne!(manifest_key(&member(&old)), manifest_key(&member(&new)));
+        assert_eq!(
+            manifest_key(&member(&old)),
+            manifest_key(&member(&root("1")))
+        );
+        assert_ne!(manifest_key(&old), manifest_key(&new));
+    }
+
+    #[test]
+    fn test_round_trip_and_invalidation() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ReportCache::new(dir.path().join(STATE_DIR).join("cache.json"), 10);
+
+        cache.store("abc", &vec![1, 2, 3]).unwrap();
+
+        let (report, age): (Vec<u32>, _) = cache.load("abc").unwrap();
+        assert_eq!(report, vec![1, 2, 3]);
+        assert!(age < Duration::from_secs(60));
+        assert!(cache.load::<Vec<u32>>("other-key").is_none());
+    }
+
+    #[test]
+    fn test_expired_and_disabled_entries_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+        let stale = CacheEntry {
+            key: "abc".to_string(),
+            created_at: unix_now() - 3600,
+            report: 1,
+        };
+        fs::write(&path, serde_json::to_string(&stale).unwrap()).unwrap();
+
+        assert!(ReportCache::new(path.clone(), 30)
+            .load::<u32>("abc")
+            .is_none());
+        assert!(ReportCache::new(path.clone(), 120)
+            .load::<u32>("abc")
+            .is_some());
+        assert!(ReportCache::new(path, 0).load::<u32>("abc").is_none());
+    }
+
+    #[test]
+    fn test_corrupt_files_are_set_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+
+        // Another version's layout is only ignored
+        fs::write(&path, r#"{"key":"abc"}"#).unwrap();
+        assert!(ReportCache::new(path.clone(), 10)
+            .load::<u32>("abc")
+            .is_none());
+        assert!(path.exists());
+
+        fs::write(&path, r#"{"key":"abc","created_at":17"#).unwrap();
+        assert!(ReportCache::new(path.clone(), 10)
+            .load::<u32>("abc")
+            .is_none());
+        assert!(!path.exists());
+        assert!(dir.path().join("cache.json.corrupt").exists());
+    }
+}
@@ -0,0 +1,410 @@
+//! The cache files under `.cargo-sane/`, for `cargo sane cache`
+//!
+//! Every cache is a JSON file that commands rebuild when it's missing, so
+//! any of them may be deleted at will. The rest of the directory (snapshots,
+//! accepted risks, snoozes, owning teams, the audit log) is the project's
+//! own record and is never listed or touched here.
+
+use crate::analyzer::history;
+use crate::analyzer::usage_cache::USAGE_DIR;
+use crate::core::manifest::Manifest;
+use crate::utils::cache::{quarantine_path, read_json, QUARANTINE_SUFFIX, STATE_DIR};
+use crate::utils::{owners, versions_file};
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Directory of the per-crate release lists of `check <crate>`
+const CRATES_DIR: &str = "crates";
+
+/// What a cache holds, which is also what `cache clear` selects by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheKind {
+    /// Answers from crates.io, its index, docs.rs and a remote versions file
+    Registry,
+    /// The local advisory database
+    Advisories,
+    /// What was worked out from the project itself, such as git history
+    Metadata,
+}
+
+impl CacheKind {
+    pub const ALL: [CacheKind; 3] = [
+        CacheKind::Registry,
+        CacheKind::Advisories,
+        CacheKind::Metadata,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            CacheKind::Registry => "Registry responses",
+            CacheKind::Advisories => "Advisory database",
+            CacheKind::Metadata => "Project metadata",
+        }
+    }
+}
+
+/// How long a cache file's content is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lifetime {
+    /// The config's `cache_ttl_minutes`
+    Configured,
+    Fixed(Duration),
+    /// Until replaced: published releases don't change, and a stale
+    /// advisory database still serves `health --offline`
+    Permanent,
+}
+
+impl Lifetime {
+    /// In seconds, where `ttl_minutes` is the config's `cache_ttl_minutes`;
+    /// none for a permanent cache
+    fn seconds(self, ttl_minutes: u64) -> Option<u64> {
+        match self {
+            Lifetime::Configured => Some(ttl_minutes * 60),
+            Lifetime::Fixed(lifetime) => Some(lifetime.as_secs()),
+            Lifetime::Permanent => None,
+        }
+    }
+}
+
+/// Where a cache file keeps its entries, for counting them
+#[derive(Debug, Clone, Copy)]
+enum Layout {
+    /// The whole file is one cached answer
+    Single,
+    /// One entry per key of the top-level object
+    Map,
+    /// One entry per key of the object under this key
+    Nested(&'static str),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Spec {
+    kind: CacheKind,
+    layout: Layout,
+    lifetime: Lifetime,
+}
+
+/// The per-crate release lists under [`CRATES_DIR`]
+const PER_CRATE: Spec = Spec {
+    kind: CacheKind::Registry,
+    layout: Layout::Single,
+    lifetime: Lifetime::Configured,
+};
+
+/// The file scans under [`USAGE_DIR`], whose entries stay valid for as long
+/// as their files are unchanged
+const SCANS: Spec = Spec {
+    kind: CacheKind::Metadata,
+    layout: Layout::Nested("files"),
+    lifetime: Lifetime::Permanent,
+};
+
+/// Names of the cache files at the top of `.cargo-sane/`, as [`known`]
+/// knows them
+const FILES: [&str; 7] = [
+    "cache.json",
+    "owners.json",
+    "checksums.json",
+    "api-diff.json",
+    "versions-file.json",
+    "advisory-db.json",
+    "history.json",
+];
+
+/// A cache a project may have, and how long its content is used
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct CacheDefault {
+    pub kind: CacheKind,
+    /// Relative to `.cargo-sane/`; directories of one file per entry end
+    /// in `/`
+    pub path: String,
+    /// Seconds; none for caches kept until replaced, 0 for one turned off
+    pub ttl_secs: Option<u64>,
+}
+
+/// Every cache a project may have, where `ttl_minutes` is the config's
+/// `cache_ttl_minutes`
+pub fn catalog(ttl_minutes: u64) -> Vec<CacheDefault> {
+    let files = FILES
+        .iter()
+        .filter_map(|name| Some((name.to_string(), known(name)?)));
+    let dirs = [(CRATES_DIR, PER_CRATE), (USAGE_DIR, SCANS)]
+        .into_iter()
+        .map(|(dir, spec)| (format!("{}/", dir), spec));
+    let mut caches: Vec<CacheDefault> = files
+        .chain(dirs)
+        .map(|(path, spec)| CacheDefault {
+            kind: spec.kind,
+            path,
+            ttl_secs: spec.lifetime.seconds(ttl_minutes),
+        })
+        .collect();
+    caches.sort_by(|a, b| (a.kind, &a.path).cmp(&(b.kind, &b.path)));
+    caches
+}
+
+/// The cache files at the top of `.cargo-sane/`
+fn known(name: &str) -> Option<Spec> {
+    let (kind, layout, lifetime) = match name {
+        "cache.json" => (CacheKind::Registry, Layout::Single, Lifetime::Configured),
+        "owners.json" => (
+            CacheKind::Registry,
+            Layout::Nested("crates"),
+            Lifetime::Fixed(owners::MAX_AGE),
+        ),
+        "checksums.json" | "api-diff.json" => {
+            (CacheKind::Registry, Layout::Map, Lifetime::Permanent)
+        }
+        "versions-file.json" => (
+            CacheKind::Registry,
+            Layout::Single,
+            Lifetime::Fixed(versions_file::MAX_AGE),
+        ),
+        "advisory-db.json" => (
+            CacheKind::Advisories,
+            Layout::Nested("entries"),
+            Lifetime::Permanent,
+        ),
+        "history.json" => (
+            CacheKind::Metadata,
+            Layout::Single,
+            Lifetime::Fixed(Duration::from_secs(history::CACHE_TTL_MINUTES * 60)),
+        ),
+        _ => return None,
+    };
+    Some(Spec {
+        kind,
+        layout,
+        lifetime,
+    })
+}
+
+/// A cache file as found on disk
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheFile {
+    pub kind: CacheKind,
+    /// Relative to `.cargo-sane/`, with forward slashes
+    pub path: String,
+    /// In bytes
+    pub size: u64,
+    /// Cached answers it holds; unknown for a corrupt file
+    pub entries: Option<usize>,
+    /// Unix timestamp of its last write
+    pub modified_at: u64,
+    /// Set aside as corrupt, and no longer read
+    pub corrupt: bool,
+    #[serde(skip)]
+    lifetime: Lifetime,
+}
+
+/// What `cache gc` removed, or would remove
+#[derive(Debug, Default, Serialize)]
+pub struct GcReport {
+    /// Corrupt files set aside earlier
+    pub corrupt: Vec<CacheFile>,
+    /// Files older than their cache's lifetime
+    pub expired: Vec<CacheFile>,
+    /// The oldest of the rest, until the others fit the size budget
+    pub over_budget: Vec<CacheFile>,
+    /// Bytes of cache left afterwards
+    pub remaining_size: u64,
+}
+
+impl GcReport {
+    pub fn removed(&self) -> impl Iterator<Item = &CacheFile> {
+        self.corrupt
+            .iter()
+            .chain(&self.expired)
+            .chain(&self.over_budget)
+    }
+}
+
+/// The caches of one project
+pub struct CacheStore {
+    dir: PathBuf,
+}
+
+impl CacheStore {
+    /// The caches of the project owning `manifest`
+    pub fn for_manifest(manifest: &Manifest) -> Self {
+        let root = manifest.path.parent().unwrap_or(Path::new("."));
+        Self::new(root.join(STATE_DIR))
+    }
+
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Every cache file, corrupt ones included, by kind and path. Counting
+    /// the entries reads each file, which sets aside any newly found to be
+    /// corrupt.
+    pub fn files(&self) -> Result<Vec<CacheFile>> {
+        let mut files = Vec::new();
+        for (path, spec, corrupt) in self.scan()? {
+            let entries = if corrupt {
+                None
+            } else {
+                read_json::<Value>(&self.dir.join(&path)).map(|value| count(&value, spec.layout))
+            };
+            // A file set aside just now is listed under its new name
+            let (path, corrupt) = if !corrupt && !self.dir.join(&path).exists() {
+                (quarantine_path(Path::new(&path)), true)
+            } else {
+                (PathBuf::from(path), corrupt)
+            };
+            let Ok(metadata) = fs::metadata(self.dir.join(&path)) else {
+                continue;
+            };
+            files.push(CacheFile {
+                kind: spec.kind,
+                path: path.to_string_lossy().replace('\\', "/"),
+                size: metadata.len(),
+                entries,
+                modified_at: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+                    .map(|at| at.as_secs())
+                    .unwrap_or(0),
+                corrupt,
+                lifetime: spec.lifetime,
+            });
+        }
+        files.sort_by(|a, b| (a.kind, &a.path).cmp(&(b.kind, &b.path)));
+        Ok(files)
+    }
+
+    /// Delete the cache files of `kinds`, corrupt ones included, returning
+    /// what was deleted
+    pub fn clear(&self, kinds: &[CacheKind]) -> Result<Vec<CacheFile>> {
+        let removed: Vec<CacheFile> = self
+            .files()?
+            .into_iter()
+            .filter(|file| kinds.contains(&file.kind))
+            .collect();
+        self.remove(&removed)?;
+        Ok(removed)
+    }
+
+    /// Delete corrupt files and those past their lifetime, where
+    /// `ttl_minutes` is the config's `cache_ttl_minutes`, then the oldest
+    /// files until the rest fit in `max_size` bytes. With `dry_run`, only
+    /// report what would go.
+    pub fn gc(
+        &self,
+        ttl_minutes: u64,
+        max_size: Option<u64>,
+        now: u64,
+        dry_run: bool,
+    ) -> Result<GcReport> {
+        let mut report = GcReport::default();
+        let mut kept = Vec::new();
+        for file in self.files()? {
+            if file.corrupt {
+                report.corrupt.push(file);
+            } else if file.is_expired(ttl_minutes, now) {
+                report.expired.push(file);
+            } else {
+                kept.push(file);
+            }
+        }
+
+        kept.sort_by_key(|file| file.modified_at);
+        let mut size: u64 = kept.iter().map(|file| file.size).sum();
+        if let Some(max_size) = max_size {
+            let mut kept_files = kept.into_iter();
+            while size > max_size {
+                let Some(file) = kept_files.next() else {
+                    break;
+                };
+                size -= file.size;
+                report.over_budget.push(file);
+            }
+        }
+        report.remaining_size = size;
+
+        if !dry_run {
+            let removed: Vec<CacheFile> = report.removed().cloned().collect();
+            self.remove(&removed)?;
+        }
+        Ok(report)
+    }
+
+    fn remove(&self, files: &[CacheFile]) -> Result<()> {
+        for file in files {
+            let path = self.dir.join(&file.path);
+            fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Cache files by path relative to the directory, with whether they
+    /// were set aside as corrupt
+    fn scan(&self) -> Result<Vec<(String, Spec, bool)>> {
+        let mut found = Vec::new();
+        for name in file_names(&self.dir)? {
+            let (base, corrupt) = match name.strip_suffix(QUARANTINE_SUFFIX) {
+                Some(base) => (base, true),
+                None => (name.as_str(), false),
+            };
+            if let Some(spec) = known(base) {
+                found.push((name.clone(), spec, corrupt));
+            }
+        }
+
+        for (dir, spec) in [(CRATES_DIR, PER_CRATE), (USAGE_DIR, SCANS)] {
+            for name in file_names(&self.dir.join(dir))? {
+                let corrupt = name.ends_with(QUARANTINE_SUFFIX);
+                if corrupt || name.ends_with(".json") {
+                    found.push((format!("{}/{}", dir, name), spec, corrupt));
+                }
+            }
+        }
+        Ok(found)
+    }
+}
+
+impl CacheFile {
+    fn is_expired(&self, ttl_minutes: u64, now: u64) -> bool {
+        // A zero TTL turns the cache off, so nothing reads these
+        match self.lifetime.seconds(ttl_minutes) {
+            Some(lifetime) => now.saturating_sub(self.modified_at) >= lifetime,
+            None => false,
+        }
+    }
+}
+
+/// The names of the files directly in `dir`; none when it doesn't exist
+fn file_names(dir: &Path) -> Result<Vec<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context(format!("Failed to read {}", dir.display())),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.context(format!("Failed to read {}", dir.display()))?;
+        if entry.file_type().is_ok_and(|kind| kind.is_file()) {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    Ok(names)
+}
+
+fn count(value: &Value, layout: Layout) -> usize {
+    match layout {
+        Layout::Single => 1,
+        Layout::Map => value.as_object().map_or(0, |map| map.len()),
+        Layout::Nested(key) => value[key].as_object().map_or(0, |map| map.len()),
+    }
+}
@@ -0,0 +1,134 @@
+//! Stopping network lookups early, for `--timeout`
+//!
+//! A [`Cancellation`] is handed to the clients that talk to the network,
+//! which check it before every request and refuse with [`Cancelled`] once
+//! it's cancelled or its deadline has passed. Requests already in flight
+//! finish, so a run that stops early still has every answer it waited for,
+//! and the analyses count the refused lookups as unchecked rather than
+//! failing. Clients built with `new()` share the process-wide cancellation
+//! that `--timeout` sets up.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+/// The cancellation `--timeout` set up, if it was given
+static GLOBAL: OnceLock<Cancellation> = OnceLock::new();
+
+/// A lookup refused because the run was cancelled or ran out of time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not looked up: the run stopped early")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Whether `error` is a lookup refused by a [`Cancellation`]
+pub fn is_cancelled(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<Cancelled>().is_some()
+}
+
+#[derive(Debug, Default)]
+struct State {
+    deadline: Option<Instant>,
+    cancelled: AtomicBool,
+    /// Whether a lookup was refused, so results are missing something
+    refused: AtomicBool,
+}
+
+/// A shared stop signal; clones observe the same one
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    state: Arc<State>,
+}
+
+impl Cancellation {
+    /// One that stops nothing until cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// One that stops lookups once `budget` has passed from now
+    pub fn with_deadline(budget: Duration) -> Self {
+        Self {
+            state: Arc::new(State {
+                deadline: Some(Instant::now() + budget),
+                ..State::default()
+            }),
+        }
+    }
+
+    /// Make `cancellation` the one clients built with `new()` use. Only the
+    /// first call has an effect.
+    pub fn set_global(cancellation: Cancellation) {
+        let _ = GLOBAL.set(cancellation);
+    }
+
+    /// The process-wide cancellation, which never stops anything unless
+    /// `--timeout` set one up
+    pub fn global() -> Cancellation {
+        GLOBAL.get_or_init(Cancellation::new).clone()
+    }
+
+    /// Stop every lookup not yet started
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+            || self
+                .state
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Err with [`Cancelled`] when no more lookups should start, recording
+    /// that one was refused
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            self.state.refused.store(true, Ordering::Relaxed);
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Whether a lookup was refused, leaving the results partial
+    pub fn cut_short(&self) -> bool {
+        self.state.refused.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline_refuses_later_lookups() {
+        let cancellation = Cancellation::with_deadline(Duration::from_millis(50));
+        assert!(cancellation.check().is_ok());
+        assert!(!cancellation.cut_short());
+
+        std::thread::sleep(Duration::from_millis(60));
+        let clone = cancellation.clone();
+        assert_eq!(clone.check(), Err(Cancelled));
+        assert!(cancellation.cut_short());
+
+        let error = anyhow::Error::new(Cancelled).context("Failed to fetch serde");
+        assert!(is_cancelled(&error));
+        assert!(!is_cancelled(&anyhow::anyhow!("timed out")));
+    }
+
+    #[test]
+    fn test_cancel() {
+        let cancellation = Cancellation::new();
+        assert!(!cancellation.is_cancelled());
+        cancellation.cancel();
+        assert_eq!(cancellation.check(), Err(Cancelled));
+    }
+}
@@ -1 +1,1088 @@
 //! Cargo command execution
+//!
+//! Every cargo subprocess goes through [`run_cargo`], which picks the binary
+//! (`cargo_command` config, then `$CARGO`, then `cargo` on the PATH), adds a
+//! `+toolchain` when asked, runs with a controlled environment, and gives up
+//! after a timeout. Cargo's warnings are passed on even when it succeeds,
+//! and a failure carries the end of its stderr plus, for the failures with
+//! a known way out, what to do next.
+//!
+//! Under `--no-workspace-discovery`, cargo is not run on a package it would
+//! resolve inside an enclosing workspace: cargo has no flag to ignore one,
+//! so its answers would be about a different dependency set than ours.
+//!
+//! Where cargo may not be run at all, `--metadata-file` supplies the output
+//! of `cargo metadata --format-version 1` instead. It goes through the same
+//! parsing as a live run. Under `--test-mode`, the scenario's stubs answer
+//! every invocation.
+
+use crate::core::config::Config;
+use crate::core::manifest::Manifest;
+use crate::core::workspace::Workspace;
+use crate::utils::test_mode::Scenario;
+use crate::utils::timings;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use semver::Version;
+use serde::Deserialize;
+use std::ffi::OsStr;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a cargo invocation may run before it is killed
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How many lines of stderr a failure message keeps, from the end
+const ERROR_TAIL_LINES: usize = 12;
+
+/// The `cargo metadata` output format cargo-sane reads
+const METADATA_FORMAT_VERSION: u32 = 1;
+
+static METADATA_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Environment variables passed through to cargo's read-only metadata and
+/// update calls. Everything else, notably RUSTFLAGS and CARGO_BUILD_*, is
+/// dropped so the caller's build settings don't change how dependencies
+/// resolve. Windows names are matched whatever their case, as Windows
+/// reports `Path` and `SystemRoot`.
+const ENV_ALLOWLIST: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "USERPROFILE",
+    "SYSTEMROOT",
+    "WINDIR",
+    "PATHEXT",
+    "COMSPEC",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "TMPDIR",
+    "TMP",
+    "TEMP",
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+    "RUSTUP_TOOLCHAIN",
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "NO_PROXY",
+    "http_proxy",
+    "https_proxy",
+    "no_proxy",
+    "SSL_CERT_FILE",
+    "SSL_CERT_DIR",
+    // Fetching private git dependencies over SSH
+    "SSH_AUTH_SOCK",
+];
+
+/// Prefixes of passed-through variables: registry, network, HTTP and git
+/// settings, which cargo reads for authentication and fetching (git's
+/// include `GIT_SSH_COMMAND`)
+const ENV_PREFIX_ALLOWLIST: &[&str] = &[
+    "CARGO_REGISTRIES_",
+    "CARGO_REGISTRY_",
+    "CARGO_NET_",
+    "CARGO_HTTP_",
+    "GIT_",
+];
+
+/// Subcommands that compile, and so get the whole environment: build
+/// scripts find system libraries and toolchains through variables like
+/// PKG_CONFIG_PATH, OPENSSL_DIR, LIBCLANG_PATH and CC, and a check without
+/// the caller's RUSTFLAGS isn't the build they run
+const COMPILING_SUBCOMMANDS: &[&str] = &["check", "build"];
+
+/// How to invoke cargo
+#[derive(Debug, Clone)]
+pub struct CargoOptions {
+    program: PathBuf,
+    /// Arguments of a configured `cargo_command` that come before ours
+    prefix_args: Vec<String>,
+    /// The program is `$CARGO`
+    from_env: bool,
+    toolchain: Option<String>,
+    timeout: Duration,
+}
+
+/// Captured output of a successful cargo run
+#[derive(Debug, Clone)]
+pub struct CargoOutput {
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Failures of a cargo run with a known way out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CargoFailure {
+    /// The package, or the locked version of it, isn't there
+    NoMatchingPackage,
+    /// Cargo.lock is missing or stale, and cargo may not write it
+    LockfileRequired,
+    /// The package sits below a workspace that doesn't list it
+    OutsideWorkspace,
+}
+
+impl CargoOutput {
+    /// The `warning:` lines cargo printed, each once, in order
+    pub fn warnings(&self) -> Vec<&str> {
+        let mut warnings: Vec<&str> = Vec::new();
+        for line in self.stderr.lines().map(str::trim) {
+            if line.starts_with("warning:") && !warnings.contains(&line) {
+                warnings.push(line);
+            }
+        }
+        warnings
+    }
+}
+
+impl CargoFailure {
+    /// Recognize the failure from cargo's stderr
+    pub fn classify(stderr: &str) -> Option<Self> {
+        let stderr = stderr.to_lowercase();
+        if stderr.contains("no matching package") || stderr.contains("did not match any packages") {
+            Some(CargoFailure::NoMatchingPackage)
+        } else if (stderr.contains("lock file") && stderr.contains("needs to be updated"))
+            || stderr.contains("requires a lock file")
+            || stderr.contains("requires the lock file")
+        {
+            Some(CargoFailure::LockfileRequired)
+        } else if stderr.contains("believes it's in a workspace when it's not") {
+            Some(CargoFailure::OutsideWorkspace)
+        } else {
+            None
+        }
+    }
+
+    /// What to do about it
+    pub fn hint(self) -> &'static str {
+        match self {
+            CargoFailure::NoMatchingPackage => {
+                "The package or version isn't in Cargo.lock or the registry index; check the \
+                 name, and if Cargo.lock changed since the check, re-run with --refresh"
+            }
+            CargoFailure::LockfileRequired => {
+                "Cargo.lock is missing or out of date and cargo may not write it; run \
+                 `cargo generate-lockfile` or drop --locked/--frozen from cargo_command"
+            }
+            CargoFailure::OutsideWorkspace => {
+                "Add the package to the enclosing workspace's `members` or `exclude`, or give \
+                 its manifest an empty [workspace] table to make it a root of its own"
+            }
+        }
+    }
+}
+
+impl Default for CargoOptions {
+    /// `$CARGO` when set (as it is under `cargo sane`), otherwise `cargo`
+    fn default() -> Self {
+        let (program, from_env) = match std::env::var_os("CARGO") {
+            Some(cargo) if !cargo.is_empty() => (PathBuf::from(cargo), true),
+            _ => (PathBuf::from("cargo"), false),
+        };
+        Self {
+            program,
+            prefix_args: Vec::new(),
+            from_env,
+            toolchain: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl CargoOptions {
+    /// Options for the project at `root`, honoring its `cargo_command`. A
+    /// command path with a directory in it is relative to `root`.
+    pub fn for_project(config: &Config, root: &Path) -> Result<Self> {
+        let Some(command) = &config.cargo_command else {
+            return Ok(Self::default());
+        };
+        let mut words = command.split_whitespace().map(str::to_string);
+        let program = words
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("cargo_command in the config is empty"))?;
+        let program = if Path::new(&program).components().count() > 1 {
+            root.join(program)
+        } else {
+            PathBuf::from(program)
+        };
+        Ok(Self {
+            program,
+            prefix_args: words.collect(),
+            from_env: false,
+            ..Self::default()
+        })
+    }
+
+    /// Run cargo under a rustup toolchain, as `cargo +<toolchain>`. Without
+    /// a configured `cargo_command` this goes through the rustup proxy on the
+    /// PATH, since `$CARGO` points at one toolchain's binary, which doesn't
+    /// understand `+toolchain`.
+    pub fn with_toolchain(mut self, toolchain: Option<String>) -> Result<Self> {
+        let Some(toolchain) = toolchain else {
+            return Ok(self);
+        };
+        let toolchain = toolchain.trim_start_matches('+');
+        if toolchain.is_empty() || toolchain.contains(char::is_whitespace) {
+            anyhow::bail!("Invalid toolchain name '{}'", toolchain);
+        }
+        if self.from_env {
+            self.program = PathBuf::from("cargo");
+            self.from_env = false;
+        }
+        self.toolchain = Some(toolchain.to_string());
+        Ok(self)
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The command line prefix, e.g. "cargo +nightly", for messages
+    pub fn describe(&self) -> String {
+        let mut words = vec![self.program.display().to_string()];
+        words.extend(self.prefix_args.iter().cloned());
+        words.extend(self.toolchain.iter().map(|t| format!("+{}", t)));
+        words.join(" ")
+    }
+}
+
+/// Run cargo with `args` in `dir`, capturing its output. Fails when cargo
+/// can't be started, exits unsuccessfully, or outlives the timeout.
+pub fn run_cargo<S: AsRef<OsStr>>(
+    args: &[S],
+    dir: &Path,
+    options: &CargoOptions,
+) -> Result<CargoOutput> {
+    let (success, output) = run_cargo_status(args, dir, options)?;
+    let subcommand = subcommand_of(args);
+    if !success {
+        anyhow::bail!(failure_message(&subcommand, &output.stderr));
+    }
+    for warning in output.warnings() {
+        eprintln!(
+            "{}",
+            format!("  cargo {}: {}", subcommand, warning).dimmed()
+        );
+    }
+    Ok(output)
+}
+
+/// "cargo <subcommand> failed: " with the last lines of stderr, and the way
+/// out of a recognized failure
+fn failure_message(subcommand: &str, stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.lines().filter(|l| !l.trim().is_empty()).collect();
+    let tail = &lines[lines.len().saturating_sub(ERROR_TAIL_LINES)..];
+    let mut message = if tail.is_empty() {
+        format!("cargo {} failed without printing why", subcommand)
+    } else {
+        format!("cargo {} failed: {}", subcommand, tail.join("\n"))
+    };
+    if let Some(failure) = CargoFailure::classify(stderr) {
+        message.push_str("\nhint: ");
+        message.push_str(failure.hint());
+    }
+    message
+}
+
+/// [`run_cargo`] for subcommands whose exit status is an answer, like a
+/// checker exiting 1 on findings: the output comes back along with whether
+/// cargo succeeded
+pub fn run_cargo_status<S: AsRef<OsStr>>(
+    args: &[S],
+    dir: &Path,
+    options: &CargoOptions,
+) -> Result<(bool, CargoOutput)> {
+    let subcommand = subcommand_of(args);
+    let _span = timings::span(&format!("cargo {}", subcommand));
+    if let Some(scenario) = Scenario::active() {
+        let args: Vec<String> = args
+            .iter()
+            .map(|a| a.as_ref().to_string_lossy().to_string())
+            .collect();
+        return scenario.run_cargo(&args, dir);
+    }
+    let mut command = Command::new(&options.program);
+    command
+        .args(&options.prefix_args)
+        .args(options.toolchain.iter().map(|t| format!("+{}", t)))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if !COMPILING_SUBCOMMANDS.contains(&subcommand.as_str()) {
+        command.env_clear().envs(child_env(std::env::vars_os()));
+    }
+
+    let mut child = command.spawn().context(format!(
+        "Failed to run {} {}",
+        options.describe(),
+        subcommand
+    ))?;
+    let stdout = capture(child.stdout.take());
+    let stderr = capture(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= options.timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!(
+                "cargo {} timed out after {}s",
+                subcommand,
+                options.timeout.as_secs_f64()
+            );
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    let output = CargoOutput {
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    };
+    Ok((status.success(), output))
+}
+
+fn subcommand_of<S: AsRef<OsStr>>(args: &[S]) -> String {
+    args.first()
+        .map(|a| a.as_ref().to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// The allowlisted subset of `vars`
+fn child_env<K: AsRef<OsStr>, V>(vars: impl Iterator<Item = (K, V)>) -> Vec<(K, V)> {
+    vars.filter(|(key, _)| allowed_in_child(&key.as_ref().to_string_lossy(), cfg!(windows)))
+        .collect()
+}
+
+/// Whether the variable `name` is passed through to cargo
+fn allowed_in_child(name: &str, ignore_case: bool) -> bool {
+    let same = |listed: &str, found: &str| {
+        if ignore_case {
+            listed.eq_ignore_ascii_case(found)
+        } else {
+            listed == found
+        }
+    };
+    ENV_ALLOWLIST.iter().any(|listed| same(listed, name))
+        || ENV_PREFIX_ALLOWLIST.iter().any(|prefix| {
+            name.get(..prefix.len())
+                .is_some_and(|start| same(prefix, start))
+        })
+}
+
+/// Read a pipe to the end on a separate thread, so a chatty child can't
+/// block on a full pipe while we wait for it
+fn capture(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        String::from_utf8_lossy(&bytes).to_string()
+    })
+}
+
+/// The subset of `cargo metadata` output cargo-sane relies on
+#[derive(Debug, Clone, Deserialize)]
+pub struct Metadata {
+    /// The output format, as asked for with `--format-version`
+    pub version: u32,
+    pub packages: Vec<MetadataPackage>,
+    pub resolve: Option<Resolve>,
+    #[serde(default)]
+    pub workspace_members: Vec<String>,
+    pub workspace_root: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MetadataPackage {
+    pub id: String,
+    pub name: String,
+    pub version: Version,
+    pub source: Option<String>,
+    pub manifest_path: PathBuf,
+    #[serde(default)]
+    pub targets: Vec<MetadataTarget>,
+    /// The native library the package links, from its `links` key
+    #[serde(default)]
+    pub links: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MetadataTarget {
+    pub name: String,
+    /// e.g. "lib", "bin", "proc-macro", "custom-build"
+    #[serde(default)]
+    pub kind: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Resolve {
+    pub nodes: Vec<ResolveNode>,
+    pub root: Option<String>,
+}
+
+/// A package in the resolved graph, with the features cargo unified for it
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResolveNode {
+    pub id: String,
+    #[serde(default)]
+    pub deps: Vec<NodeDep>,
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NodeDep {
+    pub name: String,
+    pub pkg: String,
+    #[serde(default)]
+    pub dep_kinds: Vec<DepKindInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DepKindInfo {
+    /// `None` for normal dependencies, else "dev" or "build"
+    pub kind: Option<String>,
+    pub target: Option<String>,
+}
+
+impl NodeDep {
+    /// Whether the edge is only used for tests, examples and benches
+    pub fn is_dev_only(&self) -> bool {
+        !self.dep_kinds.is_empty()
+            && self
+                .dep_kinds
+                .iter()
+                .all(|k| k.kind.as_deref() == Some("dev"))
+    }
+}
+
+impl MetadataPackage {
+    pub fn has_build_script(&self) -> bool {
+        self.targets
+            .iter()
+            .any(|t| t.kind.iter().any(|k| k == "custom-build"))
+    }
+
+    pub fn is_proc_macro(&self) -> bool {
+        self.targets
+            .iter()
+            .any(|t| t.kind.iter().any(|k| k == "proc-macro"))
+    }
+}
+
+/// The output of `cargo build --unit-graph`: every compilation cargo would
+/// run, with the units each one needs
+#[derive(Debug, Clone, Deserialize)]
+pub struct UnitGraph {
+    pub units: Vec<Unit>,
+    /// Indices of the units the build was asked for
+    #[serde(default)]
+    pub roots: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Unit {
+    pub pkg_id: String,
+    pub target: MetadataTarget,
+    /// The compile settings, compared whole
+    #[serde(default)]
+    pub profile: serde_json::Value,
+    /// The target triple, `None` for the host or when not cross-compiling
+    #[serde(default)]
+    pub platform: Option<String>,
+    /// e.g. "build", "check" or "run-custom-build"
+    pub mode: String,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub dependencies: Vec<UnitDep>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UnitDep {
+    pub index: usize,
+}
+
+impl Unit {
+    /// Compiling a build script, as opposed to running one or building a
+    /// library
+    pub fn is_build_script(&self) -> bool {
+        self.target.kind.iter().any(|k| k == "custom-build")
+    }
+
+    pub fn is_proc_macro(&self) -> bool {
+        self.target.kind.iter().any(|k| k == "proc-macro")
+    }
+}
+
+impl UnitGraph {
+    pub fn parse(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("Failed to parse the cargo unit graph")
+    }
+}
+
+/// The field that tells the format apart, read before the rest
+#[derive(Deserialize)]
+struct MetadataHeader {
+    version: Option<u32>,
+}
+
+impl Metadata {
+    /// Read the metadata from `path` for the rest of the process instead of
+    /// running `cargo metadata`
+    pub fn set_file(path: PathBuf) {
+        let _ = METADATA_FILE.set(path);
+    }
+
+    /// The file given with `--metadata-file`, if any
+    pub fn file() -> Option<&'static Path> {
+        METADATA_FILE.get().map(PathBuf::as_path)
+    }
+
+    /// Parse the JSON printed by `cargo metadata --format-version 1`. Another
+    /// format version is refused rather than half understood.
+    pub fn parse(json: &str) -> Result<Self> {
+        let header: MetadataHeader =
+            serde_json::from_str(json).context("Failed to parse cargo metadata output")?;
+        match header.version {
+            Some(METADATA_FORMAT_VERSION) => {}
+            Some(version) => anyhow::bail!(
+                "cargo metadata format version {} isn't supported; generate it with \
+                 `cargo metadata --format-version {}`",
+                version,
+                METADATA_FORMAT_VERSION
+            ),
+            None => anyhow::bail!("Not cargo metadata output: it has no format version"),
+        }
+        serde_json::from_str(json).context("Failed to parse cargo metadata output")
+    }
+
+    /// Load metadata saved from `cargo metadata --format-version 1`
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path).context(format!(
+            "Failed to read cargo metadata from {}",
+            path.display()
+        ))?;
+        Self::parse(&json).context(format!("Failed to load {}", path.display()))
+    }
+
+    pub fn package(&self, id: &str) -> Option<&MetadataPackage> {
+        self.packages.iter().find(|p| p.id == id)
+    }
+
+    pub fn node(&self, id: &str) -> Option<&ResolveNode> {
+        self.resolve.as_ref()?.nodes.iter().find(|n| n.id == id)
+    }
+
+    /// The package whose manifest is `manifest_path`
+    pub fn package_for_manifest(&self, manifest_path: &Path) -> Option<&MetadataPackage> {
+        let wanted = manifest_path.canonicalize().ok()?;
+        self.packages
+            .iter()
+            .find(|p| p.manifest_path.canonicalize().ok().as_ref() == Some(&wanted))
+    }
+
+    /// The features cargo resolved for dependency `name` of package `parent`.
+    /// `name` is the key used in Cargo.toml, so renamed dependencies match too.
+    pub fn resolved_features(&self, parent: &str, name: &str) -> Option<Vec<String>> {
+        let extern_name = name.replace('-', "_");
+        let dep = self.node(parent)?.deps.iter().find(|d| {
+            d.name == extern_name || self.package(&d.pkg).is_some_and(|p| p.name == name)
+        })?;
+
+        let mut features = self.node(&dep.pkg)?.features.clone();
+        features.sort();
+        Some(features)
+    }
+}
+
+/// The root manifest of the workspace cargo resolves `manifest_path` in,
+/// which is `manifest_path` itself for a package on its own
+pub fn workspace_root(manifest_path: &Path, options: &CargoOptions) -> Result<PathBuf> {
+    let output = run_cargo(
+        &[
+            OsStr::new("locate-project"),
+            OsStr::new("--workspace"),
+            OsStr::new("--message-format"),
+            OsStr::new("plain"),
+            OsStr::new("--manifest-path"),
+            manifest_path.as_os_str(),
+        ],
+        project_dir(manifest_path),
+        options,
+    )?;
+    Ok(PathBuf::from(output.stdout.trim()))
+}
+
+/// The root manifest of the workspace cargo resolves `manifest_path` in,
+/// when that's some other manifest
+pub fn enclosing_workspace(
+    manifest_path: &Path,
+    options: &CargoOptions,
+) -> Result<Option<PathBuf>> {
+    let root = workspace_root(manifest_path, options)?;
+    let own = std::fs::canonicalize(manifest_path)
+        .context(format!("Failed to resolve {}", manifest_path.display()))?;
+    let root = std::fs::canonicalize(&root).unwrap_or(root);
+    Ok((root != own).then_some(root))
+}
+
+/// Fail unless the package of `manifest_path` is a workspace member in
+/// `metadata`, read from `path`. Paths may differ on the machine that
+/// generated it, so packages are matched by name.
+fn ensure_covers(metadata: &Metadata, path: &Path, manifest_path: &Path) -> Result<()> {
+    let manifest = Manifest::from_path(manifest_path)?;
+    let Some(name) = manifest.package_name() else {
+        return Ok(());
+    };
+    let member = metadata
+        .workspace_members
+        .iter()
+        .filter_map(|id| metadata.package(id))
+        .any(|package| package.name == name);
+    if !member {
+        anyhow::bail!(
+            "{} is not the cargo metadata of {}: {} isn't one of its workspace members",
+            path.display(),
+            manifest_path.display(),
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Under `--no-workspace-discovery`, fail rather than let cargo resolve
+/// `manifest_path` inside an enclosing workspace
+fn ensure_standalone(manifest_path: &Path, options: &CargoOptions) -> Result<()> {
+    if !Workspace::is_standalone() {
+        return Ok(());
+    }
+    if let Some(root) = enclosing_workspace(manifest_path, options)? {
+        anyhow::bail!(
+            "--no-workspace-discovery: cargo resolves {} in the workspace at {} and has no flag \
+             to ignore it; give the package an empty [workspace] table to make it a root of its own",
+            manifest_path.display(),
+            root.display()
+        );
+    }
+    Ok(())
+}
+
+/// Run `cargo metadata` for the project owning `manifest_path`, or read the
+/// file given with `--metadata-file`, which must cover that project
+pub fn metadata(manifest_path: &Path, options: &CargoOptions) -> Result<Metadata> {
+    if let Some(path) = Metadata::file() {
+        let metadata = Metadata::from_file(path)?;
+        ensure_covers(&metadata, path, manifest_path)?;
+        return Ok(metadata);
+    }
+    ensure_standalone(manifest_path, options)?;
+    let output = run_cargo(
+        &[
+            OsStr::new("metadata"),
+            OsStr::new("--format-version"),
+            OsStr::new("1"),
+            OsStr::new("--manifest-path"),
+            manifest_path.as_os_str(),
+        ],
+        project_dir(manifest_path),
+        options,
+    )?;
+    Metadata::parse(&output.stdout)
+}
+
+/// Run `cargo tree --duplicates` for the project owning `manifest_path`,
+/// printing depth prefixes instead of tree art so the output is easy to parse
+pub fn tree_duplicates(manifest_path: &Path, options: &CargoOptions) -> Result<String> {
+    ensure_standalone(manifest_path, options)?;
+    let output = run_cargo(
+        &[
+            OsStr::new("tree"),
+            OsStr::new("--duplicates"),
+            OsStr::new("--prefix"),
+            OsStr::new("depth"),
+            OsStr::new("--manifest-path"),
+            manifest_path.as_os_str(),
+        ],
+        project_dir(manifest_path),
+        options,
+    )?;
+    Ok(output.stdout)
+}
+
+/// The units `cargo build` would compile for the project owning
+/// `manifest_path`, without compiling them. The unit graph is unstable, so
+/// this fails on a stable toolchain.
+pub fn unit_graph(manifest_path: &Path, options: &CargoOptions) -> Result<UnitGraph> {
+    ensure_standalone(manifest_path, options)?;
+    let output = run_cargo(
+        &[
+            OsStr::new("build"),
+            OsStr::new("--unit-graph"),
+            OsStr::new("-Z"),
+            OsStr::new("unstable-options"),
+            OsStr::new("--manifest-path"),
+            manifest_path.as_os_str(),
+        ],
+        project_dir(manifest_path),
+        options,
+    )?;
+    UnitGraph::parse(&output.stdout)
+}
+
+/// Move the locked `name@from` to exactly `to` with `cargo update --precise`
+pub fn update_precise(
+    manifest_path: &Path,
+    name: &str,
+    from: &Version,
+    to: &Version,
+    options: &CargoOptions,
+) -> Result<()> {
+    ensure_standalone(manifest_path, options)?;
+    let package = format!("{}@{}", name, from);
+    let precise = to.to_string();
+    run_cargo(
+        &[
+            OsStr::new("update"),
+            OsStr::new("--package"),
+            OsStr::new(&package),
+            OsStr::new("--precise"),
+            OsStr::new(&precise),
+            OsStr::new("--manifest-path"),
+            manifest_path.as_os_str(),
+        ],
+        project_dir(manifest_path),
+        options,
+    )?;
+    Ok(())
+}
+
+/// Check that the package of `manifest_path` still compiles with `cargo check`
+pub fn check(manifest_path: &Path, options: &CargoOptions) -> Result<()> {
+    ensure_standalone(manifest_path, options)?;
+    run_cargo(
+        &[
+            OsStr::new("check"),
+            OsStr::new("--manifest-path"),
+            manifest_path.as_os_str(),
+        ],
+        project_dir(manifest_path),
+        options,
+    )?;
+    Ok(())
+}
+
+/// Let `cargo update --package` move `name` as far as the manifest allows
+pub fn update_package(manifest_path: &Path, name: &str, options: &CargoOptions) -> Result<()> {
+    ensure_standalone(manifest_path, options)?;
+    run_cargo(
+        &[
+            OsStr::new("update"),
+            OsStr::new("--package"),
+            OsStr::new(name),
+            OsStr::new("--manifest-path"),
+            manifest_path.as_os_str(),
+        ],
+        project_dir(manifest_path),
+        options,
+    )?;
+    Ok(())
+}
+
+/// The directory cargo runs in, so the project's rust-toolchain file and
+/// .cargo/config apply
+fn project_dir(manifest_path: &Path) -> &Path {
+    match manifest_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolved_features() {
+        let metadata = Metadata::parse(
+            r#"{
+                "packages": [
+                    {"id": "app 0.1.0", "name": "app", "version": "0.1.0", "source": null, "manifest_path": "/app/Cargo.toml"},
+                    {"id": "tokio 1.0.0", "name": "tokio", "version": "1.0.0", "source": "registry+https://github.com/rust-lang/crates.io-index", "manifest_path": "/reg/tokio/Cargo.toml"}
+                ],
+                "resolve": {
+                    "nodes": [
+                        {"id": "app 0.1.0", "deps": [{"name": "tokio", "pkg": "tokio 1.0.0"}], "features": []},
+                        {"id": "tokio 1.0.0", "deps": [], "features": ["rt", "macros", "default"]}
+                    ],
+                    "root": "app 0.1.0"
+                },
+                "workspace_members": ["app 0.1.0"],
+                "workspace_root": "/app",
+                "version": 1
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            metadata.resolved_features("app 0.1.0", "tokio"),
+            Some(vec![
+                "default".to_string(),
+                "macros".to_string(),
+                "rt".to_string()
+            ])
+        );
+        assert_eq!(metadata.resolved_features("app 0.1.0", "serde"), None);
+    }
+
+    #[test]
+    fn test_metadata_format_version_is_checked() {
+        let json = |version: &str| {
+            format!(
+                r#"{{"packages": [], "resolve": null, "workspace_root": "/app"{}}}"#,
+                version
+            )
+        };
+        assert!(Metadata::parse(&json(r#", "version": 1"#)).is_ok());
+
+        let error = Metadata::parse(&json(r#", "version": 2"#)).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("format version 2 isn't supported"),
+            "{}",
+            error
+        );
+        let error = Metadata::parse(&json("")).unwrap_err();
+        assert!(error.to_string().contains("no format version"), "{}", error);
+    }
+
+    #[test]
+    fn test_child_env_drops_build_settings() {
+        let vars = [
+            ("PATH", "/usr/bin"),
+            ("RUSTFLAGS", "-C target-cpu=native"),
+            ("CARGO_BUILD_TARGET", "wasm32-unknown-unknown"),
+            ("CARGO_REGISTRIES_CORP_TOKEN", "secret"),
+            ("CARGO_NET_OFFLINE", "true"),
+        ];
+        let kept: Vec<&str> = child_env(vars.into_iter())
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(
+            kept,
+            vec!["PATH", "CARGO_REGISTRIES_CORP_TOKEN", "CARGO_NET_OFFLINE"]
+        );
+    }
+
+    #[test]
+    fn test_child_env_keeps_git_and_ssh_settings() {
+        for name in [
+            "SSH_AUTH_SOCK",
+            "GIT_SSH_COMMAND",
+            "GIT_SSH",
+            "GIT_CONFIG_GLOBAL",
+        ] {
+            assert!(allowed_in_child(name, false), "{}", name);
+        }
+        assert!(!allowed_in_child("SSH_CONNECTION", false));
+    }
+
+    #[test]
+    fn test_child_env_matches_windows_names_whatever_their_case() {
+        for name in [
+            "Path",
+            "SystemRoot",
+            "windir",
+            "PATHEXT",
+            "ComSpec",
+            "git_ssh_command",
+        ] {
+            assert!(allowed_in_child(name, true), "{}", name);
+        }
+        assert!(!allowed_in_child("RustFlags", true));
+        // Elsewhere case matters, as it does to the environment
+        assert!(!allowed_in_child("Path", false));
+    }
+
+    #[cfg(unix)]
+    fn fake_cargo(dir: &Path, body: &str) -> Config {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("fake-cargo");
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        Config {
+            cargo_command: Some(format!("{} --wrapped", path.display())),
+            ..Config::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_cargo_passes_toolchain_and_captures_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = fake_cargo(
+            dir.path(),
+            "echo \"$@\"; echo \"rustflags=${RUSTFLAGS:-unset}\"; pwd; echo warned >&2",
+        );
+        let options = CargoOptions::for_project(&config, dir.path())
+            .unwrap()
+            .with_toolchain(Some("+nightly".to_string()))
+            .unwrap();
+
+        let output = run_cargo(&["metadata", "--offline"], dir.path(), &options).unwrap();
+        let lines: Vec<&str> = output.stdout.lines().collect();
+        assert_eq!(lines[0], "--wrapped +nightly metadata --offline");
+        assert_eq!(lines[1], "rustflags=unset");
+        assert_eq!(
+            Path::new(lines[2]).canonicalize().unwrap(),
+            dir.path().canonicalize().unwrap()
+        );
+        assert_eq!(output.stderr.trim(), "warned");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compiling_subcommands_keep_the_environment() {
+        // cargo test sets CARGO_MANIFEST_DIR, which isn't allowlisted
+        let dir = tempfile::tempdir().unwrap();
+        let config = fake_cargo(dir.path(), "echo \"${CARGO_MANIFEST_DIR:-unset}\"");
+        let options = CargoOptions::for_project(&config, dir.path()).unwrap();
+        let (_, check) = run_cargo_status(&["check"], dir.path(), &options).unwrap();
+        assert_eq!(check.stdout.trim(), env!("CARGO_MANIFEST_DIR"));
+        let (_, metadata) = run_cargo_status(&["metadata"], dir.path(), &options).unwrap();
+        assert_eq!(metadata.stdout.trim(), "unset");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_cargo_reports_failure_and_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = fake_cargo(
+            dir.path(),
+            "echo 'error: no matching package' >&2; exit 101",
+        );
+        let options = CargoOptions::for_project(&config, dir.path()).unwrap();
+        let error = run_cargo(&["update"], dir.path(), &options).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "cargo update failed: error: no matching package\nhint: {}",
+                CargoFailure::NoMatchingPackage.hint()
+            )
+        );
+
+        let config = fake_cargo(dir.path(), "exec sleep 5");
+        let options = CargoOptions::for_project(&config, dir.path())
+            .unwrap()
+            .with_timeout(Duration::from_millis(100));
+        let started = Instant::now();
+        let error = run_cargo(&["tree"], dir.path(), &options).unwrap_err();
+        assert!(error.to_string().contains("timed out"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cargo_stderr_is_classified() {
+        let dir = tempfile::tempdir().unwrap();
+        let run = |body: &str| {
+            let config = fake_cargo(dir.path(), body);
+            let options = CargoOptions::for_project(&config, dir.path()).unwrap();
+            run_cargo(&["update"], dir.path(), &options)
+        };
+
+        // Warnings on success are kept once each; progress lines aren't warnings
+        let output = run(concat!(
+            "echo '    Updating crates.io index' >&2; ",
+            "echo 'warning: spurious network error (2 tries remaining)' >&2; ",
+            "echo 'warning: spurious network error (2 tries remaining)' >&2; ",
+            "echo 'warning: the following packages contain code that will be rejected by a future version of Rust: old v0.1.0' >&2; ",
+            "echo '    Locking 1 package' >&2",
+        ))
+        .unwrap();
+        assert_eq!(
+            output.warnings(),
+            [
+                "warning: spurious network error (2 tries remaining)",
+                "warning: the following packages contain code that will be rejected by a future version of Rust: old v0.1.0",
+            ]
+        );
+
+        // A failure keeps the end of a long stderr, warnings included
+        let error = run("for i in $(seq 1 30); do echo \"line $i\" >&2; done; exit 101")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("cargo update failed: line 19\n"),
+            "{}",
+            error
+        );
+        assert!(error.ends_with("line 30"), "{}", error);
+        assert!(!error.contains("hint:"), "{}", error);
+
+        let error = run(concat!(
+            "echo 'warning: offline mode' >&2; ",
+            "echo 'error: package ID specification `serde@1.0.100` did not match any packages' >&2; ",
+            "exit 101",
+        ))
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error.contains("warning: offline mode\nerror: package ID"),
+            "{}",
+            error
+        );
+        assert!(
+            error.ends_with(CargoFailure::NoMatchingPackage.hint()),
+            "{}",
+            error
+        );
+
+        let error = run(concat!(
+            "echo 'error: the lock file /p/Cargo.lock needs to be updated but --locked was passed to prevent this' >&2; ",
+            "exit 101",
+        ))
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error.ends_with(CargoFailure::LockfileRequired.hint()),
+            "{}",
+            error
+        );
+
+        assert_eq!(
+            CargoFailure::classify(
+                "error: current package believes it's in a workspace when it's not:\n\
+                 current:   /w/vendor/lib/Cargo.toml\nworkspace: /w/Cargo.toml"
+            ),
+            Some(CargoFailure::OutsideWorkspace)
+        );
+
+        let error = run("exit 1").unwrap_err().to_string();
+        assert_eq!(error, "cargo update failed without printing why");
+    }
+
+    #[test]
+    fn test_toolchain_names_are_validated() {
+        assert!(CargoOptions::default()
+            .with_toolchain(Some("+".to_string()))
+            .is_err());
+        let options = CargoOptions::default()
+            .with_toolchain(Some("nightly-2024-05-01".to_string()))
+            .unwrap();
+        assert!(options.describe().ends_with(" +nightly-2024-05-01"));
+    }
+}
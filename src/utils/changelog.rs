@@ -0,0 +1,578 @@
+//! Release notes of updated dependencies, gathered into one document
+//!
+//! For each update, the entries between the old and the new version come
+//! from the repository's GitHub releases when crates.io names a GitHub
+//! repository, else from its CHANGELOG.md on the default branch. When
+//! neither has anything, or anything fails, the crate gets a link instead:
+//! notes are a convenience and never stop an update.
+
+use crate::utils::crates_io::{CratesIoClient, USER_AGENT};
+use anyhow::{Context, Result};
+use futures::stream::{self, StreamExt};
+use semver::Version;
+use serde::Deserialize;
+use std::time::Duration;
+
+const GITHUB_API: &str = "https://api.github.com";
+const GITHUB_RAW: &str = "https://raw.githubusercontent.com";
+
+/// Environment variable with a GitHub token, which raises the API rate limit
+pub const GITHUB_TOKEN_ENV: &str = "GITHUB_TOKEN";
+
+/// Entries kept per crate; the rest are counted and linked
+pub const MAX_ENTRIES: usize = 15;
+
+/// Characters kept per entry
+const MAX_ENTRY_CHARS: usize = 200;
+
+/// One dependency moving from `from` to `to`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangelogUpdate {
+    pub name: String,
+    pub from: Version,
+    pub to: Version,
+}
+
+/// Where a crate's entries came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangelogSource {
+    Releases,
+    ChangelogFile,
+    /// Nothing was found; only the link is given
+    Link,
+}
+
+/// The notes of one update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateChangelog {
+    pub name: String,
+    pub from: Version,
+    pub to: Version,
+    pub source: ChangelogSource,
+    pub entries: Vec<String>,
+    /// Entries left out past `MAX_ENTRIES`
+    pub omitted: usize,
+    /// The releases page, the changelog file, or wherever else to look
+    pub link: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    draft: bool,
+}
+
+/// A GitHub repository, and the directory of the crate in it for
+/// repositories holding several crates
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GitHubRepo {
+    owner: String,
+    repo: String,
+    path: Option<String>,
+}
+
+impl GitHubRepo {
+    /// Parse `https://github.com/<owner>/<repo>[.git][/tree/<branch>/<path>]`
+    fn parse(url: &str) -> Option<Self> {
+        let url = url.trim().trim_end_matches('/');
+        let rest = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))?;
+        let rest = rest.strip_prefix("www.").unwrap_or(rest);
+        let mut parts = rest.strip_prefix("github.com/")?.split('/');
+        let owner = parts.next().filter(|s| !s.is_empty())?;
+        let repo = parts.next().filter(|s| !s.is_empty())?;
+        let repo = repo.strip_suffix(".git").unwrap_or(repo);
+        // /tree/<branch>/<path> or /blob/<branch>/<path>
+        let path = match (parts.next(), parts.next()) {
+            (Some("tree" | "blob"), Some(_)) => {
+                let path: Vec<&str> = parts.collect();
+                (!path.is_empty()).then(|| path.join("/"))
+            }
+            _ => None,
+        };
+        Some(Self {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            path,
+        })
+    }
+
+    fn url(&self) -> String {
+        format!("https://github.com/{}/{}", self.owner, self.repo)
+    }
+
+    /// CHANGELOG.md beside the crate, relative to the repository root
+    fn changelog_file(&self) -> String {
+        match &self.path {
+            Some(path) => format!("{}/CHANGELOG.md", path),
+            None => "CHANGELOG.md".to_string(),
+        }
+    }
+}
+
+pub struct ChangelogClient {
+    client: reqwest::Client,
+    crates_io: CratesIoClient,
+    github_api: String,
+    github_raw: String,
+    token: Option<String>,
+}
+
+impl ChangelogClient {
+    pub fn new() -> Result<Self> {
+        Self::with_base_urls(CratesIoClient::new()?, GITHUB_API, GITHUB_RAW)
+    }
+
+    /// Create a client using `crates_io` for repository URLs and
+    /// GitHub-compatible API and raw-content hosts
+    pub fn with_base_urls(
+        crates_io: CratesIoClient,
+        github_api: &str,
+        github_raw: &str,
+    ) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self {
+            client,
+            crates_io,
+            github_api: github_api.trim_end_matches('/').to_string(),
+            github_raw: github_raw.trim_end_matches('/').to_string(),
+            token: std::env::var(GITHUB_TOKEN_ENV)
+                .ok()
+                .filter(|t| !t.is_empty()),
+        })
+    }
+
+    /// The notes of `update`, falling back to a link on any failure
+    pub async fn changelog(&self, update: &ChangelogUpdate) -> CrateChangelog {
+        let mut changelog = CrateChangelog {
+            name: update.name.clone(),
+            from: update.from.clone(),
+            to: update.to.clone(),
+            source: ChangelogSource::Link,
+            entries: Vec::new(),
+            omitted: 0,
+            link: format!("https://crates.io/crates/{}/{}", update.name, update.to),
+        };
+
+        let repository = match self.crates_io.get_crate(&update.name).await {
+            Ok(krate) => krate.repository,
+            Err(_) => return changelog,
+        };
+        let Some(repository) = repository else {
+            return changelog;
+        };
+        let Some(repo) = GitHubRepo::parse(&repository) else {
+            changelog.link = repository;
+            return changelog;
+        };
+        changelog.link = repo.url();
+
+        let found = match self.release_entries(&repo, update).await {
+            Some(entries) if !entries.is_empty() => Some((
+                ChangelogSource::Releases,
+                entries,
+                format!("{}/releases", repo.url()),
+            )),
+            _ => self.file_entries(&repo, update).await.map(|entries| {
+                (
+                    ChangelogSource::ChangelogFile,
+                    entries,
+                    format!("{}/blob/HEAD/{}", repo.url(), repo.changelog_file()),
+                )
+            }),
+        };
+        if let Some((source, entries, link)) = found.filter(|(_, entries, _)| !entries.is_empty()) {
+            changelog.omitted = entries.len().saturating_sub(MAX_ENTRIES);
+            changelog.entries = entries.into_iter().take(MAX_ENTRIES).collect();
+            changelog.source = source;
+            changelog.link = link;
+        }
+        changelog
+    }
+
+    /// Entries of the releases in the update's range, newest first
+    async fn release_entries(
+        &self,
+        repo: &GitHubRepo,
+        update: &ChangelogUpdate,
+    ) -> Option<Vec<String>> {
+        let url = format!(
+            "{}/repos/{}/{}/releases?per_page=100",
+            self.github_api, repo.owner, repo.repo
+        );
+        let mut request = self
+            .client
+            .get(&url)
+            .header("Accept", "application/vnd.github+json");
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await.ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        let releases: Vec<Release> = response.json().await.ok()?;
+
+        let mut in_range: Vec<(Version, &str)> = releases
+            .iter()
+            .filter(|r| !r.draft)
+            .filter_map(|r| {
+                let version = tag_version(&r.tag_name, &update.name)?;
+                in_range(&version, &update.from, &update.to)
+                    .then(|| (version, r.body.as_deref().unwrap_or("")))
+            })
+            .collect();
+        in_range.sort_by(|a, b| b.0.cmp(&a.0));
+        Some(
+            in_range
+                .into_iter()
+                .flat_map(|(_, body)| bullet_entries(body))
+                .collect(),
+        )
+    }
+
+    /// Entries of CHANGELOG.md on the default branch
+    async fn file_entries(
+        &self,
+        repo: &GitHubRepo,
+        update: &ChangelogUpdate,
+    ) -> Option<Vec<String>> {
+        let url = format!(
+            "{}/{}/{}/HEAD/{}",
+            self.github_raw,
+            repo.owner,
+            repo.repo,
+            repo.changelog_file()
+        );
+        let response = self.client.get(&url).send().await.ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        let text = response.text().await.ok()?;
+        Some(changelog_entries(&text, &update.from, &update.to))
+    }
+}
+
+/// The notes of every update, in the order given. Never fails: crates
+/// whose notes can't be fetched get a link.
+pub async fn fetch_changelogs(
+    client: &ChangelogClient,
+    updates: &[ChangelogUpdate],
+    concurrency: usize,
+) -> Vec<CrateChangelog> {
+    stream::iter(updates)
+        .map(|update| client.changelog(update))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// Render `changelogs` as one Markdown document
+pub fn render_changelog(changelogs: &[CrateChangelog]) -> String {
+    let mut out = String::from("# Dependency changelog\n");
+    for changelog in changelogs {
+        out.push_str(&format!(
+            "\n## {} {} → {}\n\n",
+            changelog.name, changelog.from, changelog.to
+        ));
+        match changelog.source {
+            ChangelogSource::Link => {
+                out.push_str(&format!(
+                    "No changelog entries found; see {}\n",
+                    changelog.link
+                ));
+            }
+            ChangelogSource::Releases | ChangelogSource::ChangelogFile => {
+                for entry in &changelog.entries {
+                    out.push_str(&format!("- {}\n", entry));
+                }
+                if changelog.omitted > 0 {
+                    out.push_str(&format!("- … and {} more\n", changelog.omitted));
+                }
+                let label = if changelog.source == ChangelogSource::Releases {
+                    "GitHub releases"
+                } else {
+                    "CHANGELOG.md"
+                };
+                out.push_str(&format!("\nSource: [{}]({})\n", label, changelog.link));
+            }
+        }
+    }
+    out
+}
+
+/// Bullet entries of the sections of a changelog after `from`, up to and
+/// including `to`. Version headings may be ATX (`## [1.2.0] - date`),
+/// setext (underlined) or bare lines starting with a version; headings
+/// without a version (`### Fixed`) belong to the section they're in.
+pub fn changelog_entries(text: &str, from: &Version, to: &Version) -> Vec<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut section = String::new();
+    let mut selected = false;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let underlined = lines.get(i + 1).is_some_and(|next| is_underline(next));
+        let heading = if let Some(text) = line.trim_start().strip_prefix('#') {
+            Some(text.trim_start_matches('#'))
+        } else if underlined && !line.trim().is_empty() && !is_bullet(line) {
+            i += 1;
+            Some(line)
+        } else if bare_heading(line) {
+            Some(line)
+        } else {
+            None
+        };
+
+        match heading {
+            Some(heading) => {
+                if let Some(version) = heading_version(heading) {
+                    selected = in_range(&version, from, to);
+                } else if heading.trim().to_lowercase().contains("unreleased") {
+                    selected = false;
+                }
+            }
+            None if selected => {
+                section.push_str(line);
+                section.push('\n');
+            }
+            None => {}
+        }
+        i += 1;
+    }
+    bullet_entries(&section)
+}
+
+/// Top-level bullets of `text`, with wrapped lines joined; nested bullets
+/// are left out
+fn bullet_entries(text: &str) -> Vec<String> {
+    let mut entries: Vec<String> = Vec::new();
+    let mut open = false;
+    for line in text.lines() {
+        let indent = line.len() - line.trim_start().len();
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            open = false;
+        } else if indent <= 1 && is_bullet(trimmed) {
+            entries.push(trimmed[2..].trim().to_string());
+            open = true;
+        } else if open && indent > 1 && !is_bullet(trimmed) {
+            if let Some(last) = entries.last_mut() {
+                last.push(' ');
+                last.push_str(trimmed);
+            }
+        } else {
+            open = false;
+        }
+    }
+    entries
+        .into_iter()
+        .filter(|e| !e.is_empty())
+        .map(|e| shorten(&e))
+        .collect()
+}
+
+fn is_bullet(line: &str) -> bool {
+    let line = line.trim_start();
+    ["- ", "* ", "+ "]
+        .iter()
+        .any(|marker| line.starts_with(marker))
+}
+
+fn is_underline(line: &str) -> bool {
+    let line = line.trim();
+    line.len() >= 3 && (line.chars().all(|c| c == '=') || line.chars().all(|c| c == '-'))
+}
+
+fn shorten(entry: &str) -> String {
+    if entry.chars().count() <= MAX_ENTRY_CHARS {
+        return entry.to_string();
+    }
+    let cut: String = entry.chars().take(MAX_ENTRY_CHARS - 1).collect();
+    format!("{}…", cut.trim_end())
+}
+
+fn in_range(version: &Version, from: &Version, to: &Version) -> bool {
+    version > from && version <= to && (version.pre.is_empty() || version == to)
+}
+
+/// An unmarked line starting with a version: "1.2.0 (2024-05-01)",
+/// "v1.2.0" or "Version 1.2.0"
+fn bare_heading(line: &str) -> bool {
+    if line.starts_with(char::is_whitespace) {
+        return false;
+    }
+    let mut words = line.split_whitespace();
+    match words.next() {
+        Some(word)
+            if word.eq_ignore_ascii_case("version") || word.eq_ignore_ascii_case("release") =>
+        {
+            words.next().and_then(parse_version).is_some()
+        }
+        Some(word) => parse_version(word.trim_end_matches(':')).is_some(),
+        None => false,
+    }
+}
+
+/// The first version in a heading, e.g. `[1.2.0] - 2024-05-01`,
+/// `v1.2.0`, `Version 1.2 (May 2024)`
+fn heading_version(heading: &str) -> Option<Version> {
+    heading
+        .split(|c: char| c.is_whitespace() || "[](){}:,*`_|".contains(c))
+        .find_map(parse_version)
+}
+
+/// The version of a release tag: `v1.2.0`, `1.2.0`, `name-v1.2.0`,
+/// `name@1.2.0`. Tags naming another crate of the repository are skipped.
+fn tag_version(tag: &str, name: &str) -> Option<Version> {
+    let version = match tag
+        .rsplit_once(['@', '/'])
+        .or_else(|| tag.rsplit_once("-v"))
+    {
+        Some((prefix, version)) => {
+            let prefix = prefix.trim_end_matches('-');
+            if prefix != name && !prefix.is_empty() {
+                return None;
+            }
+            version
+        }
+        None => tag,
+    };
+    parse_version(version)
+}
+
+/// A version token, with an optional `v` and an optional missing patch
+fn parse_version(token: &str) -> Option<Version> {
+    let token = token.strip_prefix(['v', 'V']).unwrap_or(token);
+    if !token.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    Version::parse(token).ok().or_else(|| {
+        let parts: Vec<&str> = token.split('.').collect();
+        match parts[..] {
+            [major, minor] => Some(Version::new(major.parse().ok()?, minor.parse().ok()?, 0)),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        Version::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_changelog_entries_between_versions() {
+        let text = "\
+# Changelog
+
+## [Unreleased]
+- Not released yet
+
+## [1.3.0] - 2024-05-01
+### Added
+- New API
+  that wraps
+  * nested detail
+### Fixed
+* A crash
+
+## v1.2.1 (2024-04-01)
+
++ Patch fix
+
+1.2.0
+=====
+
+- Already in use
+
+Version 1.1
+- Older
+";
+        assert_eq!(
+            changelog_entries(text, &v("1.2.0"), &v("1.3.0")),
+            vec!["New API that wraps", "A crash", "Patch fix"]
+        );
+        assert_eq!(
+            changelog_entries(text, &v("1.0.0"), &v("1.2.0")),
+            vec!["Already in use", "Older"]
+        );
+        assert!(changelog_entries(text, &v("1.3.0"), &v("1.4.0")).is_empty());
+    }
+
+    #[test]
+    fn test_tag_versions() {
+        assert_eq!(tag_version("v1.2.0", "foo"), Some(v("1.2.0")));
+        assert_eq!(tag_version("1.2.0", "foo"), Some(v("1.2.0")));
+        assert_eq!(tag_version("foo-v1.2.0", "foo"), Some(v("1.2.0")));
+        assert_eq!(tag_version("foo@1.2.0", "foo"), Some(v("1.2.0")));
+        assert_eq!(tag_version("foo-macros-v1.2.0", "foo"), None);
+        assert_eq!(tag_version("nightly", "foo"), None);
+    }
+
+    #[test]
+    fn test_github_repo_urls() {
+        assert_eq!(
+            GitHubRepo::parse("https://github.com/serde-rs/serde.git"),
+            Some(GitHubRepo {
+                owner: "serde-rs".to_string(),
+                repo: "serde".to_string(),
+                path: None,
+            })
+        );
+        let repo =
+            GitHubRepo::parse("https://github.com/tokio-rs/tokio/tree/master/tokio/").unwrap();
+        assert_eq!(repo.changelog_file(), "tokio/CHANGELOG.md");
+        assert_eq!(GitHubRepo::parse("https://gitlab.com/foo/bar"), None);
+    }
+
+    #[test]
+    fn test_entries_are_capped() {
+        let long = "x".repeat(500);
+        assert_eq!(shorten(&long).chars().count(), MAX_ENTRY_CHARS);
+        let changelog = CrateChangelog {
+            name: "foo".to_string(),
+            from: v("1.0.0"),
+            to: v("2.0.0"),
+            source: ChangelogSource::Releases,
+            entries: vec!["Breaking change".to_string()],
+            omitted: 3,
+            link: "https://github.com/o/foo/releases".to_string(),
+        };
+        assert_eq!(
+            render_changelog(&[changelog]),
+            "# Dependency changelog\n\n## foo 1.0.0 → 2.0.0\n\n- Breaking change\n- … and 3 more\n\nSource: [GitHub releases](https://github.com/o/foo/releases)\n"
+        );
+    }
+
+    #[test]
+    fn test_unreachable_hosts_fall_back_to_a_link() {
+        // Nothing listens here
+        let crates_io = CratesIoClient::with_base_url("http://127.0.0.1:9").unwrap();
+        let client =
+            ChangelogClient::with_base_urls(crates_io, "http://127.0.0.1:9", "http://127.0.0.1:9")
+                .unwrap();
+        let update = ChangelogUpdate {
+            name: "serde".to_string(),
+            from: v("1.0.0"),
+            to: v("1.0.200"),
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let changelogs = runtime.block_on(fetch_changelogs(&client, &[update], 4));
+        assert_eq!(changelogs[0].source, ChangelogSource::Link);
+        assert_eq!(changelogs[0].link, "https://crates.io/crates/serde/1.0.200");
+    }
+}
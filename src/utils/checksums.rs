@@ -0,0 +1,107 @@
+//! Published .crate checksums from the crates.io sparse index, cached
+//! under `.cargo-sane/`
+//!
+//! A release can't be republished, so its checksum never changes and a
+//! cached one is used for good. Only releases missing from the cache are
+//! looked up, with one index request per crate however many of its
+//! versions are wanted; a release the index has no checksum for is cached
+//! as an empty one, so a warm cache makes no requests at all. Under
+//! `--test-mode` nothing is cached.
+
+use crate::utils::cache::{read_json, STATE_DIR};
+use crate::utils::sparse_index::SparseIndexClient;
+use crate::utils::test_mode::Scenario;
+use anyhow::{Context, Result};
+use futures::stream::{self, StreamExt};
+use semver::Version;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CACHE_FILE: &str = "checksums.json";
+
+/// What the index says about the wanted releases
+#[derive(Debug, Default)]
+pub struct PublishedChecksums {
+    /// By crate and version, for the releases the index lists a checksum of
+    pub checksums: BTreeMap<(String, Version), String>,
+    /// Crates whose index file couldn't be fetched, with the error
+    pub failed: BTreeMap<String, String>,
+}
+
+/// The published checksums of `wanted`, `(crate, version)` pairs, from the
+/// cache in the project at `root` where present and the index otherwise
+pub async fn published_checksums(
+    index: &SparseIndexClient,
+    wanted: &[(String, Version)],
+    root: &Path,
+    concurrency: usize,
+) -> Result<PublishedChecksums> {
+    let caching = Scenario::active().is_none();
+    let path = cache_path(root);
+    let mut cache: BTreeMap<String, String> = if caching {
+        read_json(&path).unwrap_or_default()
+    } else {
+        BTreeMap::new()
+    };
+
+    let mut missing: Vec<&str> = wanted
+        .iter()
+        .filter(|(name, version)| !cache.contains_key(&cache_key(name, version)))
+        .map(|(name, _)| name.as_str())
+        .collect();
+    missing.sort_unstable();
+    missing.dedup();
+    let fetched: Vec<_> = stream::iter(missing)
+        .map(|name| async move { (name, index.entries(name).await) })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut published = PublishedChecksums::default();
+    let mut updated = false;
+    for (name, entries) in fetched {
+        match entries {
+            Ok(entries) => {
+                for entry in entries {
+                    if let Some(cksum) = entry.cksum {
+                        cache.insert(cache_key(name, &entry.vers), cksum);
+                    }
+                }
+                // Remember the wanted releases the index has no checksum
+                // for too, so they aren't looked up again on every run
+                for (_, version) in wanted.iter().filter(|(wanted, _)| wanted == name) {
+                    cache.entry(cache_key(name, version)).or_default();
+                }
+                updated = true;
+            }
+            Err(e) => {
+                published.failed.insert(name.to_string(), e.to_string());
+            }
+        }
+    }
+    if caching && updated {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&path, serde_json::to_string(&cache)?)
+            .context(format!("Failed to write {}", path.display()))?;
+    }
+
+    published.checksums = wanted
+        .iter()
+        .filter_map(|(name, version)| {
+            let cksum = cache.get(&cache_key(name, version))?;
+            (!cksum.is_empty()).then(|| ((name.clone(), version.clone()), cksum.clone()))
+        })
+        .collect();
+    Ok(published)
+}
+
+fn cache_key(name: &str, version: &Version) -> String {
+    format!("{}@{}", name, version)
+}
+
+fn cache_path(root: &Path) -> PathBuf {
+    root.join(STATE_DIR).join(CACHE_FILE)
+}
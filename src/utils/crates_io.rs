@@ -1,12 +1,26 @@
 //! Crates.io API client
 
+use crate::core::version::PublishedVersion;
+use crate::utils::cancel::Cancellation;
+use crate::utils::formatting::parse_timestamp;
+use crate::utils::registry::{NotFound, RegistryProvider};
+use crate::utils::test_mode::Scenario;
+use crate::utils::timings;
 use anyhow::{Context, Result};
+use reqwest::StatusCode;
 use semver::Version;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::time::Duration;
 
-const CRATES_IO_API: &str = "https://crates.io/api/v1";
-const USER_AGENT: &str = "cargo-sane (https://github.com/yourusername/cargo-sane)";
+pub const CRATES_IO_API: &str = "https://crates.io/api/v1";
+/// Crates per download count request, the most crates.io lists on a page
+const DOWNLOADS_PAGE: usize = 100;
+
+/// Cargo's proxy setting, which lookups go through as cargo's own do
+const PROXY_ENV: &str = "CARGO_HTTP_PROXY";
+
+pub(crate) const USER_AGENT: &str = "cargo-sane (https://github.com/yourusername/cargo-sane)";
 
 #[derive(Debug, Deserialize)]
 pub struct CrateResponse {
@@ -18,8 +32,24 @@ pub struct CrateResponse {
 pub struct CrateInfo {
     pub name: String,
     pub newest_version: String,
+    /// `None` when every release is a prerelease
+    #[serde(default)]
+    pub max_stable_version: Option<String>,
     pub description: Option<String>,
     pub updated_at: String,
+    #[serde(default)]
+    pub repository: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CratesResponse {
+    pub crates: Vec<CrateDownloads>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CrateDownloads {
+    pub name: String,
+    pub downloads: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -31,31 +61,96 @@ pub struct VersionsResponse {
 pub struct VersionInfo {
     pub num: String,
     pub yanked: bool,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    /// Missing for releases published before crates.io recorded it
+    #[serde(default)]
+    pub published_by: Option<PublisherInfo>,
+    /// Missing for releases with only a license file
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Size in bytes of the .crate file
+    #[serde(default)]
+    pub crate_size: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PublisherInfo {
+    pub login: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OwnersResponse {
+    pub users: Vec<OwnerInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OwnerInfo {
+    /// A username, or `github:<org>:<team>` for teams
+    pub login: String,
 }
 
 pub struct CratesIoClient {
-    client: reqwest::blocking::Client,
+    client: reqwest::Client,
+    base_url: String,
+    /// Answers lookups instead of the API under `--test-mode`
+    scenario: Option<&'static Scenario>,
+    cancellation: Cancellation,
 }
 
 impl CratesIoClient {
     pub fn new() -> Result<Self> {
-        let client = reqwest::blocking::Client::builder()
+        Ok(Self {
+            scenario: Scenario::active(),
+            cancellation: Cancellation::global(),
+            ..Self::with_base_url(CRATES_IO_API)?
+        })
+    }
+
+    /// Create a client talking to a crates.io-compatible API at `base_url`
+    pub fn with_base_url(base_url: &str) -> Result<Self> {
+        reqwest::Url::parse(base_url)
+            .with_context(|| format!("Invalid crates.io API URL '{}'", base_url))?;
+        let mut builder = reqwest::Client::builder()
             .user_agent(USER_AGENT)
-            .timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(10));
+        if let Some(proxy) = std::env::var(PROXY_ENV).ok().filter(|p| !p.is_empty()) {
+            let proxy = reqwest::Proxy::all(&proxy)
+                .with_context(|| format!("Invalid proxy '{}' in {}", proxy, PROXY_ENV))?;
+            builder = builder.proxy(proxy);
+        }
+        let client = builder
             .build()
-            .context("Failed to create HTTP client")?;
+            .context("Failed to create the HTTP client for crates.io")?;
 
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            scenario: None,
+            cancellation: Cancellation::new(),
+        })
     }
 
-    /// Get the latest version of a crate
-    pub fn get_latest_version(&self, crate_name: &str) -> Result<Version> {
-        let url = format!("{}/crates/{}", CRATES_IO_API, crate_name);
+    /// Refuse lookups once `cancellation` stops them
+    pub fn with_cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Crate-level metadata: newest version, description, repository
+    pub async fn get_crate(&self, crate_name: &str) -> Result<CrateInfo> {
+        self.cancellation.check()?;
+        if let Some(scenario) = self.scenario {
+            return scenario.crate_info(crate_name);
+        }
+        let url = format!("{}/crates/{}", self.base_url, crate_name);
+        timings::count("registry requests", 1);
 
         let response = self
             .client
             .get(&url)
             .send()
+            .await
             .context(format!("Failed to fetch info for crate: {}", crate_name))?;
 
         if !response.status().is_success() {
@@ -66,28 +161,133 @@ impl CratesIoClient {
             );
         }
 
-        let crate_response: CrateResponse = response.json().context(format!(
+        let crate_response: CrateResponse = response.json().await.context(format!(
             "Failed to parse response for crate: {}",
             crate_name
         ))?;
+        Ok(crate_response.krate)
+    }
+
+    /// All-time download counts of `names`, fetched a page of crates at a
+    /// time. Crates the registry doesn't know are left out.
+    pub async fn get_downloads(&self, names: &[&str]) -> Result<HashMap<String, u64>> {
+        self.cancellation.check()?;
+        if let Some(scenario) = self.scenario {
+            return scenario.downloads(names);
+        }
+        let mut downloads = HashMap::new();
+        for page in names.chunks(DOWNLOADS_PAGE) {
+            let ids: Vec<String> = page.iter().map(|name| format!("ids[]={}", name)).collect();
+            let url = format!(
+                "{}/crates?per_page={}&{}",
+                self.base_url,
+                DOWNLOADS_PAGE,
+                ids.join("&")
+            );
+            self.cancellation.check()?;
+            timings::count("registry requests", 1);
+
+            let response = self
+                .client
+                .get(&url)
+                .send()
+                .await
+                .context("Failed to fetch download counts")?;
+            if !response.status().is_success() {
+                anyhow::bail!(
+                    "Crates.io API returned error for download counts: {}",
+                    response.status()
+                );
+            }
+            let crates: CratesResponse = response
+                .json()
+                .await
+                .context("Failed to parse download counts")?;
+            downloads.extend(crates.crates.into_iter().map(|c| (c.name, c.downloads)));
+        }
+        Ok(downloads)
+    }
+
+    /// Logins of the users and teams that own a crate
+    pub async fn get_owners(&self, crate_name: &str) -> Result<Vec<String>> {
+        self.cancellation.check()?;
+        if let Some(scenario) = self.scenario {
+            return scenario.owners(crate_name);
+        }
+        let url = format!("{}/crates/{}/owners", self.base_url, crate_name);
+        timings::count("registry requests", 1);
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context(format!("Failed to fetch owners for crate: {}", crate_name))?;
+
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Crates.io API returned error for {}: {}",
+                crate_name,
+                response.status()
+            );
+        }
+
+        let owners: OwnersResponse = response
+            .json()
+            .await
+            .context(format!("Failed to parse owners for crate: {}", crate_name))?;
+        Ok(owners.users.into_iter().map(|o| o.login).collect())
+    }
+}
 
-        let version = Version::parse(&crate_response.krate.newest_version).context(format!(
+impl RegistryProvider for CratesIoClient {
+    /// Get the latest version of a crate: the newest stable release, or the
+    /// newest prerelease when there is no stable one
+    async fn get_latest_version(&self, crate_name: &str) -> Result<Version> {
+        let krate = self.get_crate(crate_name).await?;
+        let newest = krate
+            .max_stable_version
+            .as_deref()
+            .unwrap_or(&krate.newest_version);
+
+        let version = Version::parse(newest).context(format!(
             "Failed to parse version {} for crate {}",
-            crate_response.krate.newest_version, crate_name
+            newest, crate_name
         ))?;
 
         Ok(version)
     }
 
     /// Get all versions of a crate (non-yanked only)
-    pub fn get_versions(&self, crate_name: &str) -> Result<Vec<Version>> {
-        let url = format!("{}/crates/{}/versions", CRATES_IO_API, crate_name);
+    async fn get_versions(&self, crate_name: &str) -> Result<Vec<Version>> {
+        Ok(self
+            .get_published_versions(crate_name)
+            .await?
+            .into_iter()
+            .filter(|v| !v.yanked)
+            .map(|v| v.version)
+            .collect())
+    }
 
-        let response = self.client.get(&url).send().context(format!(
+    async fn get_published_versions(&self, crate_name: &str) -> Result<Vec<PublishedVersion>> {
+        self.cancellation.check()?;
+        if let Some(scenario) = self.scenario {
+            return scenario.published_versions(crate_name);
+        }
+        let url = format!("{}/crates/{}/versions", self.base_url, crate_name);
+        timings::count("registry requests", 1);
+
+        let response = self.client.get(&url).send().await.context(format!(
             "Failed to fetch versions for crate: {}",
             crate_name
         ))?;
 
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(NotFound {
+                crate_name: crate_name.to_string(),
+            }
+            .into());
+        }
         if !response.status().is_success() {
             anyhow::bail!(
                 "Crates.io API returned error for {}: {}",
@@ -96,24 +296,24 @@ impl CratesIoClient {
             );
         }
 
-        let versions_response: VersionsResponse = response.json().context(format!(
+        let versions_response: VersionsResponse = response.json().await.context(format!(
             "Failed to parse versions for crate: {}",
             crate_name
         ))?;
 
-        let versions: Vec<Version> = versions_response
+        Ok(versions_response
             .versions
             .iter()
-            .filter(|v| !v.yanked)
-            .filter_map(|v| Version::parse(&v.num).ok())
-            .collect();
-
-        Ok(versions)
-    }
-}
-
-impl Default for CratesIoClient {
-    fn default() -> Self {
-        Self::new().expect("Failed to create CratesIoClient")
+            .filter_map(|v| {
+                Some(PublishedVersion {
+                    version: Version::parse(&v.num).ok()?,
+                    yanked: v.yanked,
+                    created_at: v.created_at.as_deref().and_then(parse_timestamp),
+                    published_by: v.published_by.as_ref().map(|p| p.login.clone()),
+                    license: v.license.clone(),
+                    crate_size: v.crate_size,
+                })
+            })
+            .collect())
     }
 }
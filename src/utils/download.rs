@@ -0,0 +1,209 @@
+//! Resumable downloads of large files into the cache
+//!
+//! A download is streamed into `<file>.partial` next to its destination and
+//! only renamed over it once the whole file is there and passes a check, so
+//! an interrupted or corrupt download never replaces a usable copy. The
+//! next attempt picks up where the partial file ends with an HTTP range
+//! request, and starts over when the server ignores the range or the
+//! resumed file turns out unreadable.
+
+use crate::utils::cancel::Cancellation;
+use crate::utils::progress::Progress;
+use anyhow::{Context, Result};
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// A file fetched into place
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Downloaded {
+    pub path: PathBuf,
+    /// Size of the whole file
+    pub bytes: u64,
+    /// Bytes an earlier, interrupted attempt had already fetched
+    pub resumed_from: u64,
+}
+
+/// Where the download of `dest` collects until it's complete
+pub fn partial_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    dest.with_file_name(name)
+}
+
+/// Check that `path` is a whole, readable gzip stream: decompressing it to
+/// the end verifies every member's CRC and length
+pub fn verify_gzip(path: &Path) -> Result<()> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut decoder = flate2::read::MultiGzDecoder::new(io::BufReader::new(file));
+    let unpacked = io::copy(&mut decoder, &mut io::sink())
+        .context(format!("{} is not a readable gzip archive", path.display()))?;
+    if unpacked == 0 {
+        anyhow::bail!("{} is an empty archive", path.display());
+    }
+    Ok(())
+}
+
+/// Fetch `url` to `dest` through `client`, resuming an earlier attempt's
+/// partial file, and move it into place once `verify` accepts it. `dest` is
+/// left as it was whenever this fails.
+pub async fn download(
+    client: &reqwest::Client,
+    url: &str,
+    dest: &Path,
+    message: &str,
+    progress: &dyn Progress,
+    verify: impl Fn(&Path) -> Result<()>,
+) -> Result<Downloaded> {
+    let partial = partial_path(dest);
+    if let Some(dir) = partial.parent() {
+        fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+    }
+
+    loop {
+        let on_disk = fs::metadata(&partial).map(|m| m.len()).unwrap_or(0);
+        let (bytes, resumed_from) =
+            fetch(client, url, &partial, on_disk, message, progress).await?;
+        match verify(&partial) {
+            Ok(()) => {
+                // A rename within a directory replaces `dest` in one step
+                fs::rename(&partial, dest)
+                    .context(format!("Failed to move {} into place", dest.display()))?;
+                return Ok(Downloaded {
+                    path: dest.to_path_buf(),
+                    bytes,
+                    resumed_from,
+                });
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&partial);
+                // What an earlier attempt left may be what's broken
+                if resumed_from == 0 {
+                    return Err(e.context(format!("Downloaded {} is corrupt", url)));
+                }
+                progress.warn(&format!(
+                    "The resumed download of {} is corrupt; starting over",
+                    url
+                ));
+            }
+        }
+    }
+}
+
+/// Stream `url` into `partial`, after the `on_disk` bytes already there
+/// when the server can send just the rest. Returns the file's size and the
+/// bytes kept from before.
+async fn fetch(
+    client: &reqwest::Client,
+    url: &str,
+    partial: &Path,
+    on_disk: u64,
+    message: &str,
+    progress: &dyn Progress,
+) -> Result<(u64, u64)> {
+    let cancellation = Cancellation::global();
+    cancellation.check()?;
+
+    let mut request = client.get(url);
+    if on_disk > 0 {
+        request = request.header(RANGE, format!("bytes={}-", on_disk));
+    }
+    let mut response = request
+        .send()
+        .await
+        .context(format!("Failed to download {}", url))?;
+
+    let status = response.status();
+    let resumed_from = match status {
+        StatusCode::PARTIAL_CONTENT if on_disk > 0 => {
+            let start = response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(range_start);
+            if start != Some(on_disk) {
+                anyhow::bail!("{} answered a different range than asked for", url);
+            }
+            on_disk
+        }
+        // Everything was there already; let the check decide
+        StatusCode::RANGE_NOT_SATISFIABLE if on_disk > 0 => {
+            return Ok((on_disk, on_disk));
+        }
+        // The server ignored the range, or there was nothing to resume
+        status if status.is_success() => 0,
+        status => anyhow::bail!("Failed to download {}: HTTP {}", url, status),
+    };
+
+    let expected = response.content_length().map(|rest| resumed_from + rest);
+    let mut file = if resumed_from > 0 {
+        OpenOptions::new().append(true).open(partial)
+    } else {
+        File::create(partial)
+    }
+    .context(format!("Failed to write {}", partial.display()))?;
+
+    progress.start_transfer(expected.unwrap_or(0), resumed_from, message);
+    let mut written = resumed_from;
+    let streamed = async {
+        while let Some(chunk) = response.chunk().await? {
+            cancellation.check()?;
+            file.write_all(&chunk)?;
+            written += chunk.len() as u64;
+            progress.inc(chunk.len() as u64);
+        }
+        file.sync_all()?;
+        anyhow::Ok(())
+    }
+    .await;
+    progress.finish();
+    streamed.context(format!(
+        "The download of {} was interrupted; run again to resume it",
+        url
+    ))?;
+
+    if let Some(expected) = expected.filter(|&expected| expected != written) {
+        anyhow::bail!(
+            "The download of {} ended at {} of {} bytes; run again to resume it",
+            url,
+            written,
+            expected
+        );
+    }
+    Ok((written, resumed_from))
+}
+
+/// The first byte of a `Content-Range: bytes <start>-<end>/<total>`
+fn range_start(value: &str) -> Option<u64> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    range.split_once('-')?.0.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_range_start() {
+        assert_eq!(range_start("bytes 4096-65535/65536"), Some(4096));
+        assert_eq!(range_start("bytes */65536"), None);
+        assert_eq!(range_start("items 0-1/2"), None);
+    }
+
+    #[test]
+    fn test_truncated_gzip_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.tar.gz");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(&[7u8; 4096]).unwrap();
+        let archive = encoder.finish().unwrap();
+
+        fs::write(&path, &archive).unwrap();
+        verify_gzip(&path).unwrap();
+        fs::write(&path, &archive[..archive.len() - 6]).unwrap();
+        assert!(verify_gzip(&path).is_err());
+        assert_eq!(partial_path(&path), dir.path().join("db.tar.gz.partial"));
+    }
+}
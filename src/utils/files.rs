@@ -0,0 +1,152 @@
+//! Source tree walking
+
+use crate::core::config::Config;
+use crate::utils::timings;
+use anyhow::Result;
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+
+/// Controls which parts of a source tree are scanned
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    pub include_hidden: bool,
+    pub include_ignored: bool,
+    pub include_target: bool,
+    pub follow_symlinks: bool,
+}
+
+impl WalkOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            include_hidden: config.scan_hidden,
+            include_ignored: config.scan_ignored,
+            include_target: config.scan_target,
+            follow_symlinks: config.follow_symlinks,
+        }
+    }
+}
+
+/// Collect every `.rs` file below `root`, sorted by path.
+///
+/// By default hidden directories, `target/` and anything excluded by
+/// `.gitignore`/`.ignore` files are skipped and symlinks are not followed.
+/// When following symlinks is enabled, directory cycles are detected and
+/// skipped instead of being walked forever.
+pub fn collect_rust_files(root: &Path, options: &WalkOptions) -> Result<Vec<PathBuf>> {
+    collect_files(root, options, |path| {
+        path.extension().is_some_and(|ext| ext == "rs")
+    })
+}
+
+/// Collect the files below `root` that `wanted` accepts, sorted by path,
+/// walking as [`collect_rust_files`] does
+pub fn collect_files(
+    root: &Path,
+    options: &WalkOptions,
+    wanted: impl Fn(&Path) -> bool,
+) -> Result<Vec<PathBuf>> {
+    let _span = timings::span("file scan");
+    let include_target = options.include_target;
+
+    let walker = WalkBuilder::new(root)
+        .hidden(!options.include_hidden)
+        .ignore(!options.include_ignored)
+        .git_ignore(!options.include_ignored)
+        .git_global(!options.include_ignored)
+        .git_exclude(!options.include_ignored)
+        .parents(!options.include_ignored)
+        .require_git(false)
+        .follow_links(options.follow_symlinks)
+        .filter_entry(move |entry| {
+            include_target
+                || entry.depth() == 0
+                || !(entry.file_name() == "target" && entry.file_type().is_some_and(|t| t.is_dir()))
+        })
+        .build();
+
+    let mut files = Vec::new();
+    for entry in walker {
+        // Unreadable entries and symlink loops are reported as errors by the
+        // walker; neither should stop the scan
+        let Ok(entry) = entry else {
+            continue;
+        };
+
+        let path = entry.path();
+        let is_file = entry.file_type().is_some_and(|t| t.is_file());
+        if is_file && wanted(path) {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn names(root: &Path, files: &[PathBuf]) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| f.strip_prefix(root).unwrap().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_default_exclusions() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for sub in ["src", "target/debug", ".hidden", "generated"] {
+            fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("build.rs"), "").unwrap();
+        fs::write(root.join("target/debug/out.rs"), "").unwrap();
+        fs::write(root.join(".hidden/secret.rs"), "").unwrap();
+        fs::write(root.join("generated/bindings.rs"), "").unwrap();
+        fs::write(root.join("src/notes.txt"), "").unwrap();
+        fs::write(root.join(".gitignore"), "generated/\n").unwrap();
+
+        let files = collect_rust_files(root, &WalkOptions::default()).unwrap();
+        assert_eq!(names(root, &files), vec!["build.rs", "src/main.rs"]);
+
+        let everything = WalkOptions {
+            include_hidden: true,
+            include_ignored: true,
+            include_target: true,
+            follow_symlinks: false,
+        };
+        let files = collect_rust_files(root, &everything).unwrap();
+        assert_eq!(files.len(), 5);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_self_referential_symlink_terminates() {
+        use std::time::{Duration, Instant};
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("vendor")).unwrap();
+        fs::write(root.join("vendor/lib.rs"), "").unwrap();
+        std::os::unix::fs::symlink(root, root.join("vendor/loop")).unwrap();
+
+        let started = Instant::now();
+        let skipped = collect_rust_files(root, &WalkOptions::default()).unwrap();
+        let followed = collect_rust_files(
+            root,
+            &WalkOptions {
+                follow_symlinks: true,
+                ..WalkOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(names(root, &skipped), vec!["vendor/lib.rs"]);
+        assert_eq!(names(root, &followed), vec!["vendor/lib.rs"]);
+    }
+}
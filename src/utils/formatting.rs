@@ -1 +1,323 @@
 //! Output formatting utilities
+//!
+//! Every date, duration and count a report prints goes through here, so the
+//! formats stay identical across commands. `tests/golden/` locks them down.
+
+use crate::utils::timings::Timings;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Calendar approximations for durations; exact enough for "how old"
+const SECONDS_PER_MONTH: u64 = 30 * SECONDS_PER_DAY;
+const SECONDS_PER_YEAR: u64 = 365 * SECONDS_PER_DAY;
+
+/// Version of the JSON documents' shape. Bumped whenever a field is
+/// removed, renamed or changes type; added fields leave it alone.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A JSON report stamped with when and by which version it was produced
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Stamped<'a, T> {
+    /// See [`SCHEMA_VERSION`]
+    pub schema_version: u32,
+    /// RFC 3339, UTC
+    pub generated_at: String,
+    pub tool_version: &'static str,
+    /// Where the time went, when `-v` recorded it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<Timings>,
+    #[serde(flatten)]
+    pub report: &'a T,
+}
+
+impl<'a, T: Serialize> Stamped<'a, T> {
+    /// Stamp `report` as generated at the Unix time `generated_at`. The report
+    /// must serialize as a map.
+    pub fn new(report: &'a T, generated_at: u64) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            generated_at: format_timestamp(generated_at),
+            tool_version: env!("CARGO_PKG_VERSION"),
+            timings: None,
+            report,
+        }
+    }
+}
+
+/// Render a Unix timestamp as RFC 3339 in UTC, e.g. "2024-03-01T12:30:00Z"
+pub fn format_timestamp(unix: u64) -> String {
+    let (year, month, day) = civil_from_days(unix / SECONDS_PER_DAY);
+    let seconds = unix % SECONDS_PER_DAY;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Render the UTC calendar date of a Unix timestamp, e.g. "2024-03-01"
+pub fn format_date(unix: u64) -> String {
+    let (year, month, day) = civil_from_days(unix / SECONDS_PER_DAY);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// A timestamp followed by how long before `now` it was, e.g.
+/// "2024-03-01T12:30:00Z (3 days ago)"
+pub fn format_since(unix: u64, now: u64) -> String {
+    format!(
+        "{} ({} ago)",
+        format_timestamp(unix),
+        format_duration(Duration::from_secs(now.saturating_sub(unix)))
+    )
+}
+
+/// Render a duration in its two largest units, e.g. "1 year, 3 months",
+/// "5 days, 2 hours" or "45 seconds"
+pub fn format_duration(duration: Duration) -> String {
+    let mut remaining = duration.as_secs();
+    let units = [
+        (SECONDS_PER_YEAR, "year"),
+        (SECONDS_PER_MONTH, "month"),
+        (SECONDS_PER_DAY, "day"),
+        (3600, "hour"),
+        (60, "minute"),
+        (1, "second"),
+    ];
+
+    let mut parts = Vec::new();
+    for (size, unit) in units {
+        let count = remaining / size;
+        if count > 0 || (parts.is_empty() && size == 1) {
+            parts.push(plural(count, unit));
+            remaining %= size;
+        } else if !parts.is_empty() {
+            // Two adjacent units at most; "1 year, 4 hours" reads oddly
+            break;
+        }
+        if parts.len() == 2 {
+            break;
+        }
+    }
+    parts.join(", ")
+}
+
+/// Render fractional seconds, rounded to whole seconds, e.g. "2 minutes, 5 seconds"
+pub fn format_seconds(seconds: f64) -> String {
+    format_duration(Duration::from_secs(seconds.max(0.0).round() as u64))
+}
+
+/// Group digits in thousands, e.g. "12,345"
+pub fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// A byte count in decimal units, as crates.io shows them, e.g. "2.1 MB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["kB", "MB", "GB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 999.95 && unit + 1 < UNITS.len() {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// "1 crate", "3 crates", "2 dependencies"
+pub fn plural(count: u64, noun: &str) -> String {
+    if count == 1 {
+        return format!("1 {}", noun);
+    }
+    let count = format_count(count as usize);
+    match noun.strip_suffix('y') {
+        Some(stem) if !stem.ends_with(['a', 'e', 'o', 'u']) => format!("{} {}ies", count, stem),
+        _ => format!("{} {}s", count, noun),
+    }
+}
+
+/// A path as users of the platform expect to read it: with backslashes and
+/// without the `\\?\` prefix of canonicalized paths on Windows, as is
+/// elsewhere
+pub fn display_path(path: &Path) -> String {
+    let text = path.display().to_string();
+    if cfg!(windows) {
+        let text = text.strip_prefix(r"\\?\").unwrap_or(&text);
+        text.replace('/', "\\")
+    } else {
+        text
+    }
+}
+
+/// Parse a `YYYY-MM-DD` date into the Unix timestamp of its UTC midnight
+pub fn parse_date(text: &str) -> Option<u64> {
+    let mut parts = text.trim().splitn(3, '-');
+    let year: u64 = parts.next()?.parse().ok()?;
+    let month: u64 = parts.next()?.parse().ok()?;
+    let day: u64 = parts.next()?.parse().ok()?;
+    if year < 1970 || !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * SECONDS_PER_DAY)
+}
+
+/// Parse an RFC 3339 timestamp such as "2024-02-19T17:41:13.580542+00:00"
+/// into Unix seconds, dropping fractions of a second
+pub fn parse_timestamp(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let date = parse_date(text.get(..10)?)?;
+    let rest = text.get(10..)?.strip_prefix(['T', 't', ' '])?;
+
+    let time: Vec<u64> = rest
+        .get(..8)?
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let [hours, minutes, seconds] = time[..] else {
+        return None;
+    };
+    if hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    let zone = rest[8..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    let offset: i64 = match zone {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match zone.get(..1)? {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            let hours: i64 = zone.get(1..3)?.parse().ok()?;
+            let minutes: i64 = zone.get(4..6)?.parse().ok()?;
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+
+    let local = date + hours * 3600 + minutes * 60 + seconds;
+    u64::try_from(local as i64 - offset).ok()
+}
+
+fn is_leap_year(year: u64) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 to a (year, month, day) date, using Howard
+/// Hinnant's civil calendar algorithm restricted to dates after the epoch
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Inverse of `civil_from_days`
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year % 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_709_296_245), "2024-03-01T12:30:45Z");
+        assert_eq!(format_date(1_709_296_245), "2024-03-01");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        let d = Duration::from_secs;
+        assert_eq!(format_duration(d(0)), "0 seconds");
+        assert_eq!(format_duration(d(42)), "42 seconds");
+        assert_eq!(format_duration(d(75 * 60)), "1 hour, 15 minutes");
+        assert_eq!(format_duration(d(SECONDS_PER_DAY + 30)), "1 day");
+        assert_eq!(
+            format_duration(d(SECONDS_PER_YEAR + 3 * SECONDS_PER_MONTH + 5)),
+            "1 year, 3 months"
+        );
+        assert_eq!(format_count(1_234_567), "1,234,567");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(48_300), "48.3 kB");
+        assert_eq!(format_bytes(2_100_000), "2.1 MB");
+        assert_eq!(format_bytes(999_990), "1.0 MB");
+    }
+
+    #[test]
+    fn test_plural() {
+        assert_eq!(plural(1, "dependency"), "1 dependency");
+        assert_eq!(plural(2, "dependency"), "2 dependencies");
+        assert_eq!(plural(3, "key"), "3 keys");
+        assert_eq!(plural(1_200, "crate"), "1,200 crates");
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2024-03-01"), Some(1_709_251_200));
+        assert_eq!(parse_date("2000-02-29"), Some(951_782_400));
+        assert_eq!(parse_date("2023-02-29"), None);
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("v1.2"), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("2024-03-01T12:30:45Z"), Some(1_709_296_245));
+        assert_eq!(
+            parse_timestamp("2024-03-01T12:30:45.580542+00:00"),
+            Some(1_709_296_245)
+        );
+        assert_eq!(
+            parse_timestamp("2024-03-01T14:30:45+02:00"),
+            Some(1_709_296_245)
+        );
+        assert_eq!(parse_timestamp("2024-03-01"), None);
+        assert_eq!(parse_timestamp("2024-03-01T25:00:00Z"), None);
+        assert_eq!(parse_timestamp("2024-03-01T12:30:45"), None);
+    }
+}
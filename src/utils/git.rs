@@ -0,0 +1,510 @@
+//! Git working tree status of the files cargo-sane edits, and the history
+//! of their lines
+//!
+//! Edits on top of uncommitted changes produce a diff that mixes both, so
+//! commands that write check first. Outside a git repository, or without
+//! git installed, there is nothing to check and [`GitRepo::discover`]
+//! returns `None`.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A git working tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRepo {
+    root: PathBuf,
+}
+
+/// A commit's id, author and author time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commit {
+    pub id: String,
+    pub author: String,
+    pub email: String,
+    /// Unix timestamp
+    pub time: u64,
+}
+
+/// Where `git blame` found a line: the commit that last changed it, and
+/// the line's number and file in that commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Blamed {
+    commit: String,
+    line: usize,
+    path: String,
+}
+
+impl GitRepo {
+    /// The working tree containing `dir`, if any
+    pub fn discover(dir: &Path) -> Option<Self> {
+        let output = git(dir, &["rev-parse", "--show-toplevel"]).ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let root = String::from_utf8(output.stdout).ok()?;
+        Some(Self {
+            root: PathBuf::from(root.trim_end_matches(['\n', '\r'])),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Clone the default branch of `url` into `into`, without its history
+    pub fn clone_shallow(url: &str, into: &Path) -> Result<Self> {
+        let parent = into.parent().unwrap_or(Path::new("."));
+        let output = git(
+            parent,
+            &[
+                "clone",
+                "-q",
+                "--depth",
+                "1",
+                "--",
+                url,
+                &into.to_string_lossy(),
+            ],
+        )?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git clone of {} failed: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(Self {
+            root: into.to_path_buf(),
+        })
+    }
+
+    /// Those of `files` with uncommitted changes, staged or not, including
+    /// untracked ones, as paths relative to the repository root. Ignored
+    /// and missing files are never dirty.
+    pub fn dirty(&self, files: &[PathBuf]) -> Result<Vec<String>> {
+        let mut args = vec![
+            "status".to_string(),
+            "--porcelain".to_string(),
+            "-z".to_string(),
+            "--untracked-files=all".to_string(),
+            "--".to_string(),
+        ];
+        // git resolves the root through symlinks; resolve the files the
+        // same way so they're recognised as inside it
+        args.extend(files.iter().filter_map(|file| {
+            let dir = match file.parent()? {
+                dir if dir.as_os_str().is_empty() => Path::new("."),
+                dir => dir,
+            };
+            let dir = dir.canonicalize().ok()?;
+            Some(dir.join(file.file_name()?).to_string_lossy().into_owned())
+        }));
+        if args.last().is_some_and(|a| a == "--") {
+            return Ok(Vec::new());
+        }
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+        let output = git(&self.root, &args)?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git status failed in {}: {}",
+                self.root.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(parse_porcelain(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// The commit HEAD points to, if there is one
+    pub fn head(&self) -> Option<String> {
+        let output = git(&self.root, &["rev-parse", "--verify", "-q", "HEAD"]).ok()?;
+        let head = String::from_utf8(output.stdout).ok()?;
+        output.status.success().then(|| head.trim().to_string())
+    }
+
+    /// The content of `file` at revision `rev`, or `None` when the file
+    /// didn't exist there. A revision git doesn't know is an error.
+    pub fn file_at(&self, rev: &str, file: &Path) -> Result<Option<String>> {
+        let commit = format!("{}^{{commit}}", rev);
+        let output = git(&self.root, &["rev-parse", "--verify", "-q", &commit])?;
+        if !output.status.success() {
+            anyhow::bail!("Unknown git revision '{}' in {}", rev, self.root.display());
+        }
+
+        let relative = self.relative(file)?;
+        let output = git(&self.root, &["show", &format!("{}:{}", rev, relative)])?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        String::from_utf8(output.stdout)
+            .map(Some)
+            .with_context(|| format!("{} at {} isn't UTF-8", relative, rev))
+    }
+
+    /// Whether the history is cut off, as in a `--depth` clone, so the
+    /// oldest commit reachable isn't where anything began
+    pub fn is_shallow(&self) -> bool {
+        git(&self.root, &["rev-parse", "--is-shallow-repository"])
+            .is_ok_and(|output| output.stdout.starts_with(b"true"))
+    }
+
+    /// The commit that first put `word` on each of `lines` (1-based) of
+    /// `file`, given as `(line, word)`.
+    ///
+    /// `git blame` finds the commit that last changed each line as it is in
+    /// the working tree, and `git log -L` follows the line back from there
+    /// through every edit. A line is followed by position, so the edit that
+    /// rewrote another line into it ends the search: the origin is the
+    /// oldest edit whose new line has `word` and whose old line, if any,
+    /// doesn't. Both commands follow the file across renames and moves on
+    /// their own (git refuses `--follow` with `-L`). Lines that aren't
+    /// committed yet are left out.
+    pub fn line_origins(
+        &self,
+        file: &Path,
+        lines: &[(usize, &str)],
+    ) -> Result<HashMap<usize, Commit>> {
+        let mut origins = HashMap::new();
+        if lines.is_empty() {
+            return Ok(origins);
+        }
+        let words: HashMap<usize, &str> = lines.iter().copied().collect();
+        let relative = self.relative(file)?;
+        let ranges: Vec<String> = lines
+            .iter()
+            .map(|(line, _)| format!("-L{},{}", line, line))
+            .collect();
+        let mut args = vec!["blame", "--line-porcelain"];
+        args.extend(ranges.iter().map(String::as_str));
+        args.extend(["--", relative.as_str()]);
+        let output = git(&self.root, &args)?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git blame failed on {}: {}",
+                relative,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        for (line, blamed) in parse_blame(&String::from_utf8_lossy(&output.stdout)) {
+            if blamed.commit.bytes().all(|b| b == b'0') {
+                continue;
+            }
+            let range = format!("-L{},{}:{}", blamed.line, blamed.line, blamed.path);
+            let output = git(
+                &self.root,
+                &[
+                    "log",
+                    "--format=%x01%H%x00%an%x00%ae%x00%at",
+                    &range,
+                    &blamed.commit,
+                ],
+            )?;
+            if !output.status.success() {
+                anyhow::bail!(
+                    "git log failed on {}: {}",
+                    range,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            let log = String::from_utf8_lossy(&output.stdout);
+            if let Some(commit) = origin_of(&log, words[&line]) {
+                origins.insert(line, commit);
+            }
+        }
+        Ok(origins)
+    }
+
+    /// `file` relative to the root, resolved through symlinks the way git
+    /// resolves the root
+    fn relative(&self, file: &Path) -> Result<String> {
+        let file = file
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve {}", file.display()))?;
+        let root = self
+            .root
+            .canonicalize()
+            .unwrap_or_else(|_| self.root.clone());
+        let relative = file
+            .strip_prefix(&root)
+            .with_context(|| format!("{} is outside {}", file.display(), root.display()))?;
+        Ok(relative.to_string_lossy().replace('\\', "/"))
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<Output> {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git")
+}
+
+/// Paths of `git status --porcelain -z` output. Entries are `XY path`;
+/// renames and copies are followed by their original path, which is
+/// skipped.
+fn parse_porcelain(output: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut entries = output.split('\0').filter(|e| !e.is_empty());
+    while let Some(entry) = entries.next() {
+        let Some(path) = entry.get(3..) else {
+            continue;
+        };
+        if entry.starts_with(['R', 'C']) {
+            entries.next();
+        }
+        paths.push(path.to_string());
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// The lines of `git blame --line-porcelain` output, by their number in
+/// the working tree. Each line starts with a `<commit> <original line>
+/// <final line>` header, and every header is followed by a `filename`.
+fn parse_blame(output: &str) -> Vec<(usize, Blamed)> {
+    let mut blamed = Vec::new();
+    let mut header = None;
+    for line in output.lines() {
+        if let Some(path) = line.strip_prefix("filename ") {
+            if let Some((commit, original, final_line)) = header.take() {
+                blamed.push((
+                    final_line,
+                    Blamed {
+                        commit,
+                        line: original,
+                        path: path.to_string(),
+                    },
+                ));
+            }
+            continue;
+        }
+        let mut fields = line.split(' ');
+        if let (Some(commit), Some(original), Some(final_line)) =
+            (fields.next(), fields.next(), fields.next())
+        {
+            if commit.len() == 40 && commit.bytes().all(|b| b.is_ascii_hexdigit()) {
+                if let (Ok(original), Ok(final_line)) = (original.parse(), final_line.parse()) {
+                    header = Some((commit.to_string(), original, final_line));
+                }
+            }
+        }
+    }
+    blamed
+}
+
+/// The commit that put `word` on the line a `git log -L` trace follows.
+/// Entries are newest first, each a `%x01`-prefixed header and the line's
+/// patch; the search ends at an edit whose old line lacks the word.
+fn origin_of(log: &str, word: &str) -> Option<Commit> {
+    let mut origin = None;
+    for entry in log.split('\x01').filter(|e| !e.is_empty()) {
+        let (header, patch) = entry.split_once('\n').unwrap_or((entry, ""));
+        let Some(commit) = parse_commit(header) else {
+            break;
+        };
+        let (mut before, mut after) = (false, false);
+        let hunks = patch.lines().skip_while(|line| !line.starts_with("@@"));
+        for line in hunks {
+            if let Some(old) = line.strip_prefix('-') {
+                before |= mentions(old, word);
+            } else if let Some(new) = line.strip_prefix('+') {
+                after |= mentions(new, word);
+            }
+        }
+        if !after {
+            break;
+        }
+        origin = Some(commit);
+        if !before {
+            break;
+        }
+    }
+    origin
+}
+
+/// Whether `word` appears in `line` as a whole name, so `serde` isn't
+/// found in `serde_json`
+fn mentions(line: &str, word: &str) -> bool {
+    let part_of_name = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    line.match_indices(word).any(|(at, _)| {
+        !line[..at].ends_with(part_of_name) && !line[at + word.len()..].starts_with(part_of_name)
+    })
+}
+
+/// A `%H%x00%an%x00%ae%x00%at` header of `git log`
+fn parse_commit(line: &str) -> Option<Commit> {
+    let mut fields = line.split('\0');
+    let id = fields.next()?;
+    if id.len() != 40 {
+        return None;
+    }
+    Some(Commit {
+        id: id.to_string(),
+        author: fields.next()?.to_string(),
+        email: fields.next()?.to_string(),
+        time: fields.next()?.trim().parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn run(dir: &Path, args: &[&str]) {
+        let output = git(dir, args).unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+    }
+
+    /// A repository with Cargo.toml and Cargo.lock committed
+    fn repo() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        run(dir.path(), &["init", "-q"]);
+        run(dir.path(), &["config", "user.email", "test@example.com"]);
+        run(dir.path(), &["config", "user.name", "Test"]);
+        fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"a\"\n").unwrap();
+        fs::write(dir.path().join("Cargo.lock"), "version = 3\n").unwrap();
+        run(dir.path(), &["add", "."]);
+        run(
+            dir.path(),
+            &["-c", "commit.gpgsign=false", "commit", "-q", "-m", "init"],
+        );
+        dir
+    }
+
+    fn files(dir: &Path) -> Vec<PathBuf> {
+        vec![dir.join("Cargo.toml"), dir.join("Cargo.lock")]
+    }
+
+    #[test]
+    fn test_clean_worktree() {
+        let dir = repo();
+        fs::write(dir.path().join("notes.txt"), "unrelated").unwrap();
+        let repo = GitRepo::discover(dir.path()).unwrap();
+        assert!(repo.dirty(&files(dir.path())).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dirty_worktree() {
+        let dir = repo();
+        fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"b\"\n").unwrap();
+        let repo = GitRepo::discover(dir.path()).unwrap();
+        assert_eq!(repo.dirty(&files(dir.path())).unwrap(), vec!["Cargo.toml"]);
+
+        // Staged changes count too
+        run(dir.path(), &["add", "Cargo.toml"]);
+        fs::write(dir.path().join("Cargo.lock"), "version = 4\n").unwrap();
+        assert_eq!(
+            repo.dirty(&files(dir.path())).unwrap(),
+            vec!["Cargo.lock", "Cargo.toml"]
+        );
+    }
+
+    #[test]
+    fn test_outside_a_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        // A temp dir inside someone's checkout would be found; only assert
+        // when git agrees there is no repository
+        if git(dir.path(), &["rev-parse"])
+            .map(|o| !o.status.success())
+            .unwrap_or(true)
+        {
+            assert_eq!(GitRepo::discover(dir.path()), None);
+        }
+    }
+
+    /// Commit everything as `author` at `time`
+    fn commit(dir: &Path, author: &str, time: u64) {
+        run(dir, &["add", "-A"]);
+        let date = format!("@{} +0000", time);
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_COMMITTER_DATE", &date)
+            .args(["-c", "commit.gpgsign=false", "-c"])
+            .arg(format!("user.name={}", author))
+            .args(["commit", "-q", "-m", "change"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+    }
+
+    #[test]
+    fn test_line_origins_follow_edits_and_moves() {
+        let dir = repo();
+        let manifest = dir.path().join("Cargo.toml");
+        fs::write(
+            &manifest,
+            "[package]\nname = \"a\"\n\n[dependencies]\nserde = \"1\"\n",
+        )
+        .unwrap();
+        commit(dir.path(), "Alice", 1_615_000_000);
+        fs::write(
+            &manifest,
+            "[package]\nname = \"a\"\n\n[dependencies]\nanyhow = \"1\"\nserde = \"1.0.100\"\n",
+        )
+        .unwrap();
+        commit(dir.path(), "Bob", 1_650_000_000);
+        fs::create_dir_all(dir.path().join("crates/app")).unwrap();
+        run(dir.path(), &["mv", "Cargo.toml", "crates/app/Cargo.toml"]);
+        commit(dir.path(), "Carol", 1_700_000_000);
+        let manifest = dir.path().join("crates/app/Cargo.toml");
+        let mut text = fs::read_to_string(&manifest).unwrap();
+        text.push_str("tokio = \"1\"\n");
+        fs::write(&manifest, text).unwrap();
+
+        let repo = GitRepo::discover(dir.path()).unwrap();
+        assert!(!repo.is_shallow());
+        let origins = repo
+            .line_origins(&manifest, &[(5, "anyhow"), (6, "serde"), (7, "tokio")])
+            .unwrap();
+        // anyhow came with Bob's edit; serde was edited then but Alice added
+        // it, before the move; tokio isn't committed
+        assert_eq!(origins[&5].author, "Bob");
+        assert_eq!(origins[&5].time, 1_650_000_000);
+        assert_eq!(origins[&6].author, "Alice");
+        assert_eq!(origins[&6].time, 1_615_000_000);
+        assert_eq!(origins[&6].email, "test@example.com");
+        assert_eq!(origins.len(), 2);
+    }
+
+    #[test]
+    fn test_shallow_clone() {
+        let dir = repo();
+        fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"b\"\n").unwrap();
+        commit(dir.path(), "Alice", 1_615_000_000);
+        let clone = tempfile::tempdir().unwrap();
+        let url = format!("file://{}", dir.path().display());
+        let output = Command::new("git")
+            .args(["clone", "-q", "--depth", "1", &url])
+            .arg(clone.path().join("clone"))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let shallow = GitRepo::discover(&clone.path().join("clone")).unwrap();
+        assert!(shallow.is_shallow());
+        assert!(shallow.head().is_some());
+    }
+
+    #[test]
+    fn test_mentions() {
+        assert!(mentions("serde = \"1\"", "serde"));
+        assert!(mentions("[dependencies.serde]", "serde"));
+        assert!(!mentions("serde_json = \"1\"", "serde"));
+        assert!(!mentions("tokio-serde = \"1\"", "serde"));
+    }
+
+    #[test]
+    fn test_parse_porcelain() {
+        assert_eq!(
+            parse_porcelain(" M Cargo.toml\0R  new.toml\0old.toml\0?? sub/Cargo.lock\0"),
+            vec!["Cargo.toml", "new.toml", "sub/Cargo.lock"]
+        );
+    }
+}
@@ -1,5 +1,25 @@
 //! Utility functions
 
+pub mod advisories;
+pub mod advisory_db;
+pub mod api_diff;
+pub mod audit;
+pub mod cache;
+pub mod cache_store;
+pub mod cancel;
 pub mod cargo;
+pub mod changelog;
+pub mod checksums;
 pub mod crates_io;
+pub mod download;
+pub mod files;
 pub mod formatting;
+pub mod git;
+pub mod owners;
+pub mod progress;
+pub mod registry;
+pub mod snapshots;
+pub mod sparse_index;
+pub mod test_mode;
+pub mod timings;
+pub mod versions_file;
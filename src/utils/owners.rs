@@ -0,0 +1,130 @@
+//! Crate owner lists from crates.io, cached under `.cargo-sane/`
+//!
+//! Owners change rarely and the endpoint costs one request per crate, so
+//! lists are kept for a day. Crates whose owners can't be fetched are left
+//! out rather than failing the run.
+
+use crate::utils::cache::{read_json, unix_now, STATE_DIR};
+use crate::utils::crates_io::CratesIoClient;
+use anyhow::{Context, Result};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long a fetched owner list is used before fetching it again
+pub const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+const CACHE_FILE: &str = "owners.json";
+
+/// Owner logins by crate name
+pub type Owners = BTreeMap<String, Vec<String>>;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OwnersCache {
+    crates: BTreeMap<String, CachedOwners>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedOwners {
+    fetched_at: u64,
+    owners: Vec<String>,
+}
+
+/// The sorted owner logins of each crate in `names`, from the cache in the
+/// project at `root` where fresh and from crates.io otherwise
+pub async fn crate_owners(
+    client: &CratesIoClient,
+    names: &[String],
+    root: &Path,
+    concurrency: usize,
+) -> Result<Owners> {
+    let path = cache_path(root);
+    let mut cache: OwnersCache = read_json(&path).unwrap_or_default();
+
+    let now = unix_now();
+    let stale: Vec<&String> = names
+        .iter()
+        .filter(|name| {
+            cache
+                .crates
+                .get(*name)
+                .is_none_or(|cached| now.saturating_sub(cached.fetched_at) >= MAX_AGE.as_secs())
+        })
+        .collect();
+    let fetched: Vec<(&String, Result<Vec<String>>)> = stream::iter(stale)
+        .map(|name| async move { (name, client.get_owners(name).await) })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut updated = false;
+    for (name, owners) in fetched {
+        if let Ok(mut owners) = owners {
+            owners.sort();
+            owners.dedup();
+            cache.crates.insert(
+                name.clone(),
+                CachedOwners {
+                    fetched_at: now,
+                    owners,
+                },
+            );
+            updated = true;
+        }
+    }
+    if updated {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        fs::write(&path, serde_json::to_string(&cache)?)
+            .context(format!("Failed to write {}", path.display()))?;
+    }
+
+    Ok(names
+        .iter()
+        .filter_map(|name| {
+            let cached = cache.crates.get(name)?;
+            Some((name.clone(), cached.owners.clone()))
+        })
+        .collect())
+}
+
+fn cache_path(root: &Path) -> PathBuf {
+    root.join(STATE_DIR).join(CACHE_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fresh_entries_are_served_from_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = cache_path(dir.path());
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut cache = OwnersCache::default();
+        cache.crates.insert(
+            "serde".to_string(),
+            CachedOwners {
+                fetched_at: unix_now(),
+                owners: vec!["dtolnay".to_string()],
+            },
+        );
+        fs::write(&path, serde_json::to_string(&cache).unwrap()).unwrap();
+
+        // Nothing listens here, so anything not cached is left out
+        let client = CratesIoClient::with_base_url("http://127.0.0.1:9").unwrap();
+        let names = vec!["serde".to_string(), "tokio".to_string()];
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let owners = runtime
+            .block_on(crate_owners(&client, &names, dir.path(), 4))
+            .unwrap();
+        assert_eq!(
+            owners,
+            Owners::from([("serde".to_string(), vec!["dtolnay".to_string()])])
+        );
+    }
+}
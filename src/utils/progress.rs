@@ -0,0 +1,478 @@
+//! Progress reporting for long-running lookups
+//!
+//! Analyzers report through the `Progress` trait and never draw anything
+//! themselves. The command layer picks the implementation: a bar on an
+//! interactive terminal, plain lines in CI logs and pipes, nothing for JSON.
+//! `--progress` overrides the choice for every command.
+
+use crate::utils::formatting::{format_bytes, format_duration};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Items between two lines of plain progress
+const PLAIN_EVERY: u64 = 10;
+
+/// Lines of plain progress over a whole download, one per tenth
+const PLAIN_TRANSFER_STEPS: u64 = 10;
+
+/// The mode given with `--progress`, if any
+static PREFERENCE: OnceLock<ProgressMode> = OnceLock::new();
+
+pub trait Progress: Send + Sync {
+    /// Begin tracking `len` items
+    fn start(&self, len: u64, message: &str);
+
+    /// Begin tracking a download of `len` bytes, `done` of which an earlier
+    /// attempt already fetched; `inc` then counts bytes
+    fn start_transfer(&self, len: u64, done: u64, message: &str) {
+        self.start(len, message);
+        self.inc(done);
+    }
+
+    fn set_message(&self, message: &str);
+
+    fn inc(&self, delta: u64);
+
+    /// Mark one named item as done after `elapsed`
+    fn item_done(&self, name: &str, _elapsed: Duration) {
+        self.set_message(&format!("Checked {}", name));
+        self.inc(1);
+    }
+
+    /// Report a problem without tearing the progress display
+    fn warn(&self, message: &str);
+
+    fn finish(&self);
+}
+
+/// How progress should be shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProgressMode {
+    /// One line every few items, without carriage returns
+    Plain,
+    /// An in-place progress bar
+    Bar,
+    /// No progress at all
+    #[value(name = "none")]
+    Hidden,
+}
+
+impl ProgressMode {
+    /// Nothing while stdout carries JSON, plain lines in CI or when stdout
+    /// isn't a terminal, otherwise a bar
+    pub fn select(json: bool, interactive: bool, ci: bool) -> Self {
+        if json {
+            ProgressMode::Hidden
+        } else if ci || !interactive {
+            ProgressMode::Plain
+        } else {
+            ProgressMode::Bar
+        }
+    }
+
+    /// Use `mode` for the rest of the process, whatever `detect` would pick
+    pub fn set_preference(mode: ProgressMode) {
+        let _ = PREFERENCE.set(mode);
+    }
+
+    /// Select the mode for the current process
+    pub fn detect(json: bool) -> Self {
+        if let Some(mode) = PREFERENCE.get() {
+            return *mode;
+        }
+        let ci = std::env::var("CI").is_ok_and(|v| v == "true" || v == "1");
+        Self::select(json, std::io::stdout().is_terminal(), ci)
+    }
+
+    /// Create the reporter; `verbose` reporters also list each item with its
+    /// timing
+    pub fn build(self, verbose: bool) -> Arc<dyn Progress> {
+        match self {
+            ProgressMode::Plain => Arc::new(PlainProgress::new(verbose)),
+            ProgressMode::Bar => Arc::new(BarProgress::new(verbose)),
+            ProgressMode::Hidden => Arc::new(HiddenProgress),
+        }
+    }
+}
+
+/// An indicatif progress bar on stderr
+pub struct BarProgress {
+    bar: ProgressBar,
+    verbose: bool,
+}
+
+impl BarProgress {
+    pub fn new(verbose: bool) -> Self {
+        let bar = ProgressBar::hidden();
+        if let Ok(style) = ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+        {
+            bar.set_style(style.progress_chars("#>-"));
+        }
+        Self { bar, verbose }
+    }
+}
+
+impl Progress for BarProgress {
+    fn start(&self, len: u64, message: &str) {
+        self.bar
+            .set_draw_target(indicatif::ProgressDrawTarget::stderr());
+        self.bar.set_length(len);
+        self.bar.set_position(0);
+        self.bar.set_message(message.to_string());
+    }
+
+    fn start_transfer(&self, len: u64, done: u64, message: &str) {
+        if let Ok(style) = ProgressStyle::default_bar().template(
+            "{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec}, {eta} left {msg}",
+        ) {
+            self.bar.set_style(style.progress_chars("#>-"));
+        }
+        self.start(len, message);
+        // The speed and time left are the resumed attempt's alone
+        self.bar.set_position(done);
+        self.bar.reset_eta();
+    }
+
+    fn set_message(&self, message: &str) {
+        self.bar.set_message(message.to_string());
+    }
+
+    fn inc(&self, delta: u64) {
+        self.bar.inc(delta);
+    }
+
+    fn item_done(&self, name: &str, elapsed: Duration) {
+        if self.verbose {
+            self.bar
+                .println(format!("  {} ({} ms)", name, elapsed.as_millis()));
+        }
+        self.set_message(&format!("Checked {}", name));
+        self.inc(1);
+    }
+
+    fn warn(&self, message: &str) {
+        self.bar.suspend(|| eprintln!("Warning: {}", message));
+    }
+
+    fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+/// Whole lines on stderr, one every `every` items, for logs that can't
+/// render in-place updates
+pub struct PlainProgress {
+    out: Mutex<Box<dyn Write + Send>>,
+    verbose: bool,
+    every: u64,
+    len: AtomicU64,
+    done: AtomicU64,
+    message: Mutex<String>,
+    transfer: Mutex<Option<Transfer>>,
+}
+
+/// A download being reported in plain lines
+struct Transfer {
+    started: Instant,
+    /// Bytes an earlier attempt fetched
+    resumed_at: u64,
+}
+
+impl PlainProgress {
+    pub fn new(verbose: bool) -> Self {
+        Self::with_writer(Box::new(std::io::stderr()), verbose, PLAIN_EVERY)
+    }
+
+    /// Write to `out`, a line every `every` items (and after the last)
+    pub fn with_writer(out: Box<dyn Write + Send>, verbose: bool, every: u64) -> Self {
+        Self {
+            out: Mutex::new(out),
+            verbose,
+            every: every.max(1),
+            len: AtomicU64::new(0),
+            done: AtomicU64::new(0),
+            message: Mutex::new(String::new()),
+            transfer: Mutex::new(None),
+        }
+    }
+
+    /// "4.0 MB of 40.0 MB (10%), 1.2 MB/s, about 30 seconds left", the
+    /// speed and time left once there's a rate to go by
+    fn transfer_line(transfer: &Transfer, done: u64, len: u64) -> String {
+        let percent = (done * 100).checked_div(len).unwrap_or(100);
+        let mut line = format!(
+            "{} of {} ({}%)",
+            format_bytes(done),
+            format_bytes(len),
+            percent
+        );
+        let fetched = done.saturating_sub(transfer.resumed_at);
+        let elapsed = transfer.started.elapsed().as_secs_f64();
+        if fetched > 0 && elapsed >= 0.001 {
+            let rate = fetched as f64 / elapsed;
+            line.push_str(&format!(", {}/s", format_bytes(rate as u64)));
+            if done < len {
+                let left = Duration::from_secs_f64(len.saturating_sub(done) as f64 / rate);
+                line.push_str(&format!(", about {} left", format_duration(left)));
+            }
+        }
+        line
+    }
+
+    fn line(&self, line: &str) {
+        if let Ok(mut out) = self.out.lock() {
+            let _ = writeln!(out, "{}", line);
+        }
+    }
+}
+
+impl Progress for PlainProgress {
+    fn start(&self, len: u64, message: &str) {
+        self.len.store(len, Ordering::Relaxed);
+        self.done.store(0, Ordering::Relaxed);
+        if let Ok(mut current) = self.message.lock() {
+            *current = message.to_string();
+        }
+        if let Ok(mut transfer) = self.transfer.lock() {
+            *transfer = None;
+        }
+        self.line(&format!("{}: {} items", message, len));
+    }
+
+    fn start_transfer(&self, len: u64, done: u64, message: &str) {
+        self.len.store(len, Ordering::Relaxed);
+        self.done.store(done, Ordering::Relaxed);
+        if let Ok(mut current) = self.message.lock() {
+            *current = message.to_string();
+        }
+        if let Ok(mut transfer) = self.transfer.lock() {
+            *transfer = Some(Transfer {
+                started: Instant::now(),
+                resumed_at: done,
+            });
+        }
+        if done > 0 {
+            self.line(&format!(
+                "{}: {}, resuming at {}",
+                message,
+                format_bytes(len),
+                format_bytes(done)
+            ));
+        } else {
+            self.line(&format!("{}: {}", message, format_bytes(len)));
+        }
+    }
+
+    // Per-item messages would defeat the point of throttled output
+    fn set_message(&self, _message: &str) {}
+
+    fn inc(&self, delta: u64) {
+        let done = self.done.fetch_add(delta, Ordering::Relaxed) + delta;
+        let len = self.len.load(Ordering::Relaxed);
+        if let Some(transfer) = self
+            .transfer
+            .lock()
+            .ok()
+            .as_deref()
+            .and_then(Option::as_ref)
+        {
+            let step = (len / PLAIN_TRANSFER_STEPS).max(1);
+            if done / step != (done - delta) / step || done == len {
+                let message = self.message.lock().map(|m| m.clone()).unwrap_or_default();
+                self.line(&format!(
+                    "{}: {}",
+                    message,
+                    Self::transfer_line(transfer, done, len)
+                ));
+            }
+            return;
+        }
+        let crossed = done / self.every != (done - delta) / self.every;
+        if crossed || done == len {
+            let message = self.message.lock().map(|m| m.clone()).unwrap_or_default();
+            self.line(&format!("{}: checked {}/{}…", message, done, len));
+        }
+    }
+
+    fn item_done(&self, name: &str, elapsed: Duration) {
+        if self.verbose {
+            self.line(&format!("  {} ({} ms)", name, elapsed.as_millis()));
+        }
+        self.inc(1);
+    }
+
+    fn warn(&self, message: &str) {
+        self.line(&format!("Warning: {}", message));
+    }
+
+    fn finish(&self) {}
+}
+
+/// Draws nothing; warnings still go to stderr
+pub struct HiddenProgress;
+
+impl Progress for HiddenProgress {
+    fn start(&self, _len: u64, _message: &str) {}
+
+    fn set_message(&self, _message: &str) {}
+
+    fn inc(&self, _delta: u64) {}
+
+    fn warn(&self, message: &str) {
+        eprintln!("Warning: {}", message);
+    }
+
+    fn finish(&self) {}
+}
+
+/// Records every call, for asserting on progress in tests
+#[derive(Default)]
+pub struct CapturedProgress {
+    events: Mutex<Vec<String>>,
+}
+
+impl CapturedProgress {
+    pub fn events(&self) -> Vec<String> {
+        self.events.lock().map(|e| e.clone()).unwrap_or_default()
+    }
+
+    fn record(&self, event: String) {
+        if let Ok(mut events) = self.events.lock() {
+            events.push(event);
+        }
+    }
+}
+
+impl Progress for CapturedProgress {
+    fn start(&self, len: u64, message: &str) {
+        self.record(format!("start {} {}", len, message));
+    }
+
+    fn set_message(&self, message: &str) {
+        self.record(format!("message {}", message));
+    }
+
+    fn inc(&self, delta: u64) {
+        self.record(format!("inc {}", delta));
+    }
+
+    fn warn(&self, message: &str) {
+        self.record(format!("warn {}", message));
+    }
+
+    fn finish(&self) {
+        self.record("finish".to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A writer whose output can be read back after the reporter is done
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mode_selection() {
+        assert_eq!(
+            ProgressMode::select(true, true, false),
+            ProgressMode::Hidden
+        );
+        assert_eq!(
+            ProgressMode::select(true, false, true),
+            ProgressMode::Hidden
+        );
+        assert_eq!(
+            ProgressMode::select(false, false, false),
+            ProgressMode::Plain
+        );
+        assert_eq!(ProgressMode::select(false, true, true), ProgressMode::Plain);
+        assert_eq!(ProgressMode::select(false, true, false), ProgressMode::Bar);
+    }
+
+    #[test]
+    fn test_plain_progress_prints_whole_lines() {
+        let out = Shared::default();
+        let progress = PlainProgress::with_writer(Box::new(out.clone()), false, 10);
+        progress.start(25, "Checking crates.io");
+        for i in 0..25 {
+            progress.item_done(&format!("crate{}", i), Duration::from_millis(1));
+        }
+        progress.warn("crate7 timed out");
+        progress.finish();
+
+        let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        assert!(!text.contains('\r'), "{:?}", text);
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            vec![
+                "Checking crates.io: 25 items",
+                "Checking crates.io: checked 10/25…",
+                "Checking crates.io: checked 20/25…",
+                "Checking crates.io: checked 25/25…",
+                "Warning: crate7 timed out",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plain_transfer_prints_every_tenth() {
+        let out = Shared::default();
+        let progress = PlainProgress::with_writer(Box::new(out.clone()), false, 10);
+        progress.start_transfer(40_000_000, 8_000_000, "Downloading advisory database");
+        // Long enough to have a rate to go by
+        std::thread::sleep(Duration::from_millis(5));
+        for _ in 0..32 {
+            progress.inc(1_000_000);
+        }
+        progress.finish();
+
+        let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Downloading advisory database: 40.0 MB, resuming at 8.0 MB"
+        );
+        // 8 MB was there already: one line per 4 MB after it
+        assert_eq!(lines.len(), 9, "{:?}", lines);
+        assert!(lines[1].starts_with("Downloading advisory database: 12.0 MB of 40.0 MB (30%), "));
+        assert!(lines[1].contains(" MB/s, about "), "{}", lines[1]);
+        assert!(lines[8].starts_with("Downloading advisory database: 40.0 MB of 40.0 MB (100%), "));
+        assert!(!lines[8].contains("left"), "{}", lines[8]);
+    }
+
+    #[test]
+    fn test_captured_progress() {
+        let progress = CapturedProgress::default();
+        progress.start(2, "Checking");
+        progress.item_done("serde", Duration::from_millis(5));
+        progress.warn("oops");
+        progress.finish();
+        assert_eq!(
+            progress.events(),
+            vec![
+                "start 2 Checking",
+                "message Checked serde",
+                "inc 1",
+                "warn oops",
+                "finish"
+            ]
+        );
+    }
+}
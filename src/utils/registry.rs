@@ -0,0 +1,42 @@
+//! Registry access abstraction
+
+use crate::core::config::Settings;
+use crate::core::version::PublishedVersion;
+use anyhow::Result;
+use semver::Version;
+use std::fmt;
+use std::future::Future;
+
+/// Number of registry requests kept in flight when the config doesn't say
+pub const DEFAULT_CONCURRENCY: usize = Settings::DEFAULT.concurrency;
+
+/// The registry doesn't know the crate: it was never published there.
+/// Lookups fail with this, rather than a generic error, so callers can tell
+/// a missing crate from an unreachable registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotFound {
+    pub crate_name: String,
+}
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not published on the registry", self.crate_name)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+/// A source of published crate versions, such as crates.io
+pub trait RegistryProvider {
+    /// The newest published version of a crate
+    fn get_latest_version(&self, crate_name: &str) -> impl Future<Output = Result<Version>> + Send;
+
+    /// All non-yanked versions of a crate
+    fn get_versions(&self, crate_name: &str) -> impl Future<Output = Result<Vec<Version>>> + Send;
+
+    /// Every published version of a crate, yanked ones included and marked
+    fn get_published_versions(
+        &self,
+        crate_name: &str,
+    ) -> impl Future<Output = Result<Vec<PublishedVersion>>> + Send;
+}
@@ -0,0 +1,252 @@
+//! On-disk storage of dependency snapshots
+
+use crate::analyzer::snapshot::Snapshot;
+use crate::core::manifest::Manifest;
+use crate::utils::cache::STATE_DIR;
+use crate::utils::formatting::{format_timestamp, parse_date};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Snapshots of one project, one compact JSON file each
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+/// A stored snapshot, identified without loading its reports
+#[derive(Debug, Clone)]
+pub struct SnapshotEntry {
+    pub path: PathBuf,
+    pub tag: Option<String>,
+    pub created_at: u64,
+}
+
+/// The leading fields of a snapshot file
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    tag: Option<String>,
+    created_at: u64,
+}
+
+impl SnapshotEntry {
+    /// The tag, or the timestamp for untagged snapshots
+    pub fn label(&self) -> String {
+        self.tag
+            .clone()
+            .unwrap_or_else(|| format_timestamp(self.created_at))
+    }
+}
+
+impl SnapshotStore {
+    /// The snapshot directory of the project owning `manifest`
+    pub fn for_manifest(manifest: &Manifest) -> Self {
+        let root = manifest.path.parent().unwrap_or(Path::new("."));
+        Self::new(root.join(STATE_DIR).join("snapshots"))
+    }
+
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Write `snapshot`, replacing an older snapshot with the same tag
+    pub fn save(&self, snapshot: &Snapshot) -> Result<PathBuf> {
+        if let Some(tag) = &snapshot.tag {
+            validate_tag(tag)?;
+        }
+        fs::create_dir_all(&self.dir)
+            .context(format!("Failed to create {}", self.dir.display()))?;
+
+        if snapshot.tag.is_some() {
+            for entry in self.list()? {
+                if entry.tag == snapshot.tag {
+                    fs::remove_file(&entry.path)
+                        .context(format!("Failed to remove {}", entry.path.display()))?;
+                }
+            }
+        }
+
+        // Colons aren't allowed in Windows file names
+        let mut name = format_timestamp(snapshot.created_at).replace([':', '-'], "");
+        if let Some(tag) = &snapshot.tag {
+            name.push('-');
+            name.push_str(tag);
+        }
+        let path = self.dir.join(format!("{}.json", name));
+        fs::write(&path, serde_json::to_string(snapshot)?)
+            .context(format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Every readable snapshot, oldest first
+    pub fn list(&self) -> Result<Vec<SnapshotEntry>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for file in fs::read_dir(&self.dir)
+            .context(format!("Failed to read {}", self.dir.display()))?
+            .flatten()
+        {
+            let path = file.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let Ok(header) = serde_json::from_str::<SnapshotHeader>(&content) else {
+                continue;
+            };
+            entries.push(SnapshotEntry {
+                path,
+                tag: header.tag,
+                created_at: header.created_at,
+            });
+        }
+
+        entries.sort_by(|a, b| (a.created_at, &a.path).cmp(&(b.created_at, &b.path)));
+        Ok(entries)
+    }
+
+    pub fn load(&self, entry: &SnapshotEntry) -> Result<Snapshot> {
+        let content = fs::read_to_string(&entry.path)
+            .context(format!("Failed to read {}", entry.path.display()))?;
+        serde_json::from_str(&content).context(format!("Failed to parse {}", entry.path.display()))
+    }
+
+    /// Resolve a tag, or a `YYYY-MM-DD` date meaning the last snapshot taken
+    /// on or before that day
+    pub fn find(&self, reference: &str) -> Result<Option<SnapshotEntry>> {
+        let entries = self.list()?;
+        if let Some(entry) = entries
+            .iter()
+            .rev()
+            .find(|e| e.tag.as_deref() == Some(reference))
+        {
+            return Ok(Some(entry.clone()));
+        }
+
+        let Some(day) = parse_date(reference) else {
+            return Ok(None);
+        };
+        let end_of_day = day + 86_400;
+        Ok(entries
+            .into_iter()
+            .rev()
+            .find(|e| e.created_at < end_of_day))
+    }
+
+    /// Delete all but the newest `keep` untagged snapshots, returning the
+    /// removed paths. Tagged snapshots are never pruned; 0 keeps everything.
+    pub fn prune(&self, keep: usize) -> Result<Vec<PathBuf>> {
+        if keep == 0 {
+            return Ok(Vec::new());
+        }
+
+        let untagged: Vec<SnapshotEntry> = self
+            .list()?
+            .into_iter()
+            .filter(|e| e.tag.is_none())
+            .collect();
+        let excess = untagged.len().saturating_sub(keep);
+
+        let mut removed = Vec::new();
+        for entry in untagged.into_iter().take(excess) {
+            fs::remove_file(&entry.path)
+                .context(format!("Failed to remove {}", entry.path.display()))?;
+            removed.push(entry.path);
+        }
+        Ok(removed)
+    }
+}
+
+/// Tags become part of a file name, so keep them to a safe character set
+fn validate_tag(tag: &str) -> Result<()> {
+    let valid = !tag.is_empty()
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid {
+        anyhow::bail!(
+            "Invalid snapshot tag '{}': use letters, digits, '.', '_' and '-'",
+            tag
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::checker::CheckReport;
+
+    fn snapshot(created_at: u64, tag: Option<&str>) -> Snapshot {
+        let check = CheckReport {
+            package: None,
+            manifest: PathBuf::from("Cargo.toml"),
+            dependencies: Vec::new(),
+            git_dependencies: Vec::new(),
+            forks: Vec::new(),
+            path_dependencies: Vec::new(),
+            declaration_conflicts: Vec::new(),
+            features: Vec::new(),
+            redundancies: Vec::new(),
+            stats: None,
+            ownership_changes: Vec::new(),
+            freshness: Vec::new(),
+            internal: Vec::new(),
+            skipped: Vec::new(),
+            lock_mismatches: Vec::new(),
+            workflow_pins: Vec::new(),
+            partial: false,
+            unchecked: 0,
+        };
+        Snapshot::new(created_at, check, None, None).with_tag(tag.map(str::to_string))
+    }
+
+    #[test]
+    fn test_save_find_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().join("snapshots"));
+
+        // 2024-03-01 and 2024-03-02, noon UTC
+        let (day1, day2) = (1_709_294_400, 1_709_380_800);
+        store.save(&snapshot(day1, None)).unwrap();
+        store.save(&snapshot(day1 + 60, Some("v1.0"))).unwrap();
+        store.save(&snapshot(day2, None)).unwrap();
+        store.save(&snapshot(day2 + 60, None)).unwrap();
+
+        assert_eq!(store.list().unwrap().len(), 4);
+        assert_eq!(store.find("v1.0").unwrap().unwrap().created_at, day1 + 60);
+        assert_eq!(
+            store.find("2024-03-01").unwrap().unwrap().created_at,
+            day1 + 60
+        );
+        assert_eq!(
+            store.find("2024-03-05").unwrap().unwrap().created_at,
+            day2 + 60
+        );
+        assert!(store.find("2024-02-01").unwrap().is_none());
+        assert!(store.find("v2.0").unwrap().is_none());
+
+        let removed = store.prune(1).unwrap();
+        assert_eq!(removed.len(), 2);
+        let left: Vec<String> = store.list().unwrap().iter().map(|e| e.label()).collect();
+        assert_eq!(left, vec!["v1.0", "2024-03-02T12:01:00Z"]);
+    }
+
+    #[test]
+    fn test_retagging_replaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().to_path_buf());
+        store.save(&snapshot(100, Some("base"))).unwrap();
+        store.save(&snapshot(200, Some("base"))).unwrap();
+
+        let entries = store.list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].created_at, 200);
+        assert!(store.save(&snapshot(300, Some("../x"))).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+//! crates.io sparse index client
+//!
+//! The index records, for every published version, the dependencies it
+//! declares. That is information the web API only exposes one version at a
+//! time, while the index returns all versions of a crate in one response.
+
+use crate::utils::cancel::Cancellation;
+use crate::utils::test_mode::Scenario;
+use crate::utils::timings;
+use anyhow::{Context, Result};
+use semver::Version;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+pub const CRATES_IO_INDEX: &str = "https://index.crates.io";
+const USER_AGENT: &str = "cargo-sane (https://github.com/chronocoders/cargo-sane)";
+
+/// One published version of a crate, as recorded in the index
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    pub vers: Version,
+    #[serde(default)]
+    pub deps: Vec<IndexDependency>,
+    /// Feature name to what it enables
+    #[serde(default)]
+    pub features: BTreeMap<String, Vec<String>>,
+    /// Features using `dep:` or `?` syntax, kept apart so older cargo
+    /// versions can still read `features`
+    #[serde(default)]
+    pub features2: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub yanked: bool,
+    /// The `rust-version` the release declares, as written
+    #[serde(default)]
+    pub rust_version: Option<String>,
+    /// SHA-256 of the .crate file, as Cargo.lock records it
+    #[serde(default)]
+    pub cksum: Option<String>,
+}
+
+impl IndexEntry {
+    /// Every feature a dependent can enable: the declared ones, plus the
+    /// implicit feature of each optional dependency that no `dep:` entry
+    /// hides
+    pub fn available_features(&self) -> BTreeSet<String> {
+        let declared = self.features.iter().chain(&self.features2);
+        let hidden: BTreeSet<&str> = declared
+            .clone()
+            .flat_map(|(_, enables)| enables)
+            .filter_map(|e| e.strip_prefix("dep:"))
+            .collect();
+        let mut available: BTreeSet<String> = declared.map(|(name, _)| name.clone()).collect();
+        available.extend(
+            self.deps
+                .iter()
+                .filter(|d| d.optional && !hidden.contains(d.name.as_str()))
+                .map(|d| d.name.clone()),
+        );
+        available
+    }
+
+    /// The features `default` turns on, directly or through other features
+    pub fn default_features(&self) -> BTreeSet<String> {
+        let mut enabled = BTreeSet::new();
+        let mut pending = vec!["default".to_string()];
+        while let Some(feature) = pending.pop() {
+            let enables = self.features.get(&feature).or(self.features2.get(&feature));
+            for next in enables.into_iter().flatten() {
+                // `dep:x` and `x/y` reach into dependencies, not features
+                if !next.contains([':', '/']) && enabled.insert(next.clone()) {
+                    pending.push(next.clone());
+                }
+            }
+        }
+        enabled
+    }
+}
+
+/// A dependency declared by a published version
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexDependency {
+    /// The name the dependency is declared under (may be a rename)
+    pub name: String,
+    pub req: String,
+    #[serde(default)]
+    pub optional: bool,
+    /// "normal", "build" or "dev"; older entries omit it for normal deps
+    pub kind: Option<String>,
+    pub target: Option<String>,
+    /// The real crate name when the dependency is renamed
+    pub package: Option<String>,
+}
+
+impl IndexDependency {
+    /// The name of the crate this dependency resolves to
+    pub fn crate_name(&self) -> &str {
+        self.package.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Whether the dependency is built for consumers of the crate, i.e. it
+    /// is a normal or build dependency rather than a dev-dependency
+    pub fn is_built(&self) -> bool {
+        self.kind.as_deref() != Some("dev")
+    }
+}
+
+pub struct SparseIndexClient {
+    client: reqwest::Client,
+    base_url: String,
+    /// Answers lookups instead of the index under `--test-mode`
+    scenario: Option<&'static Scenario>,
+    cancellation: Cancellation,
+}
+
+impl SparseIndexClient {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            scenario: Scenario::active(),
+            cancellation: Cancellation::global(),
+            ..Self::with_base_url(CRATES_IO_INDEX)?
+        })
+    }
+
+    /// Create a client reading a sparse index rooted at `base_url`
+    pub fn with_base_url(base_url: &str) -> Result<Self> {
+        reqwest::Url::parse(base_url)
+            .with_context(|| format!("Invalid sparse index URL '{}'", base_url))?;
+        let client = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create the HTTP client for the crates.io index")?;
+
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            scenario: None,
+            cancellation: Cancellation::new(),
+        })
+    }
+
+    /// Refuse lookups once `cancellation` stops them
+    pub fn with_cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Every published version of a crate, in index order (oldest first)
+    pub async fn entries(&self, crate_name: &str) -> Result<Vec<IndexEntry>> {
+        self.cancellation.check()?;
+        if let Some(scenario) = self.scenario {
+            return scenario.index_entries(crate_name);
+        }
+        let url = format!("{}/{}", self.base_url, index_path(crate_name));
+        timings::count("registry requests", 1);
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context(format!("Failed to fetch index entry for {}", crate_name))?;
+
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Sparse index returned error for {}: {}",
+                crate_name,
+                response.status()
+            );
+        }
+
+        let body = response.text().await?;
+        parse_entries(&body).context(format!("Failed to parse index entry for {}", crate_name))
+    }
+}
+
+/// Parse an index file: one JSON object per line
+pub fn parse_entries(body: &str) -> Result<Vec<IndexEntry>> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(Into::into))
+        .collect()
+}
+
+/// The path of a crate's file within the index, following cargo's layout
+pub fn index_path(crate_name: &str) -> String {
+    let name = crate_name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_path() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("cc"), "2/cc");
+        assert_eq!(index_path("syn"), "3/s/syn");
+        assert_eq!(index_path("Serde_JSON"), "se/rd/serde_json");
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let entries = parse_entries(
+            r#"{"name":"demo","vers":"1.0.0","deps":[{"name":"log","req":"^0.4","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal"}],"cksum":"00","features":{},"yanked":false}
+{"name":"demo","vers":"1.1.0","deps":[{"name":"rand2","package":"rand","req":"^0.8","features":[],"optional":false,"default_features":true,"target":null,"kind":"dev"}],"cksum":"00","features":{},"yanked":true}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].deps[0].crate_name(), "log");
+        assert!(entries[0].deps[0].is_built());
+        assert!(entries[1].yanked);
+        assert_eq!(entries[1].cksum.as_deref(), Some("00"));
+        assert_eq!(entries[1].deps[0].crate_name(), "rand");
+        assert!(!entries[1].deps[0].is_built());
+    }
+
+    #[test]
+    fn test_features() {
+        let entries = parse_entries(
+            r#"{"name":"demo","vers":"1.0.0","deps":[{"name":"serde","req":"^1","optional":true},{"name":"log","req":"^0.4","optional":true}],"features":{"default":["std"],"std":["alloc"],"alloc":[]},"features2":{"serde":["dep:serde","log?/std"]},"yanked":false}
+"#,
+        )
+        .unwrap();
+
+        let available: Vec<String> = entries[0].available_features().into_iter().collect();
+        assert_eq!(available, vec!["alloc", "default", "log", "serde", "std"]);
+        let defaults: Vec<String> = entries[0].default_features().into_iter().collect();
+        assert_eq!(defaults, vec!["alloc", "std"]);
+    }
+}
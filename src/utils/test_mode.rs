@@ -0,0 +1,539 @@
+//! Scripted runs for end-to-end testing
+//!
+//! `--test-mode <scenario.toml>` answers everything a run can't control from
+//! a scenario file: registry lookups, advisories, cargo subprocesses and
+//! prompts. A scripted run touches no network and starts no cargo, so the
+//! same scenario always takes the same path through a command. Report
+//! caches are bypassed for the same reason.
+//!
+//! A scenario can inject failures: every registry and advisory lookup fails
+//! with probability `failure_rate`, decided from the seed and the lookup
+//! alone, so the same lookups fail whatever order they run in. `--seed`
+//! replaces the scenario's seed.
+//!
+//! ```toml
+//! seed = 7
+//! failure_rate = 0.1
+//! # Whether prompts may be shown (default true); `answers` are used in order
+//! interactive = true
+//! answers = [["serde"], "Skip", true]
+//!
+//! [[crates]]
+//! name = "serde"
+//! versions = ["1.0.200", "1.0.199", "1.0.100"]  # newest first
+//! yanked = ["1.0.199"]
+//! rust_versions = { "1.0.200" = "1.61" }
+//!
+//! [[advisories]]
+//! id = "RUSTSEC-2024-0001"
+//! package = "serde"
+//! title = "Stack overflow on deeply nested input"
+//! severity = "high"
+//! patched = [">=1.0.200"]
+//!
+//! # Matches cargo invocations whose arguments start with `args`; a cargo
+//! # run no stub matches fails
+//! [[cargo]]
+//! args = ["check"]
+//! status = 101
+//! stderr = "error[E0308]: mismatched types"
+//!
+//! # Files the stub writes, relative to the directory cargo runs in
+//! [cargo.writes]
+//! "Cargo.lock" = "..."
+//! ```
+
+use crate::core::advisory::{Advisory, Severity};
+use crate::core::version::PublishedVersion;
+use crate::utils::cache::fingerprint;
+use crate::utils::cargo::CargoOutput;
+use crate::utils::crates_io::CrateInfo;
+use crate::utils::registry::NotFound;
+use crate::utils::sparse_index::IndexEntry;
+use anyhow::{Context, Result};
+use semver::Version;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+static SCENARIO: OnceLock<Scenario> = OnceLock::new();
+
+/// What a scripted run sees instead of the network, cargo and the user
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub seed: u64,
+    /// Share of registry and advisory lookups that fail, from 0 to 1
+    #[serde(default)]
+    pub failure_rate: f64,
+    /// Whether prompts may be shown, as on a terminal
+    #[serde(default = "default_interactive")]
+    pub interactive: bool,
+    /// Answers to the prompts, in the order they come up
+    #[serde(default)]
+    pub answers: Vec<Answer>,
+    #[serde(default)]
+    pub crates: Vec<ScenarioCrate>,
+    #[serde(default)]
+    pub advisories: Vec<ScenarioAdvisory>,
+    #[serde(default)]
+    pub cargo: Vec<CargoStub>,
+    /// How many answers were used so far
+    #[serde(skip)]
+    answered: AtomicUsize,
+    #[serde(skip)]
+    fingerprint: String,
+}
+
+/// A pre-answered prompt: yes or no, an item by index or label, several
+/// items, or text
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Answer {
+    Bool(bool),
+    Index(usize),
+    Text(String),
+    List(Vec<Answer>),
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Answer::Bool(yes) => write!(f, "{}", yes),
+            Answer::Index(index) => write!(f, "{}", index),
+            Answer::Text(text) => write!(f, "\"{}\"", text),
+            Answer::List(answers) => {
+                let answers: Vec<String> = answers.iter().map(Answer::to_string).collect();
+                write!(f, "[{}]", answers.join(", "))
+            }
+        }
+    }
+}
+
+/// A crate as the registry knows it
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioCrate {
+    pub name: String,
+    /// Every release, newest first
+    pub versions: Vec<Version>,
+    /// The releases among `versions` that are yanked
+    #[serde(default)]
+    pub yanked: Vec<Version>,
+    #[serde(default)]
+    pub license: Option<String>,
+    #[serde(default)]
+    pub repository: Option<String>,
+    #[serde(default)]
+    pub owners: Vec<String>,
+    #[serde(default)]
+    pub downloads: u64,
+    /// Features of every release, as the index records them
+    #[serde(default)]
+    pub features: BTreeMap<String, Vec<String>>,
+    /// The `rust-version` of the releases that declare one, by release
+    #[serde(default)]
+    pub rust_versions: BTreeMap<Version, String>,
+    /// The .crate file size in bytes of the releases that have one, by
+    /// release
+    #[serde(default)]
+    pub sizes: BTreeMap<Version, u64>,
+    /// The index checksum of the releases that have one, by release
+    #[serde(default)]
+    pub checksums: BTreeMap<Version, String>,
+    /// Every lookup of the crate fails
+    #[serde(default)]
+    pub fail: bool,
+}
+
+/// An advisory against every version of `package` outside `patched`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioAdvisory {
+    pub id: String,
+    pub package: String,
+    pub title: String,
+    #[serde(default)]
+    pub severity: Option<Severity>,
+    #[serde(default)]
+    pub patched: Vec<String>,
+    #[serde(default)]
+    pub informational: Option<String>,
+}
+
+/// The result of the cargo invocations starting with `args`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CargoStub {
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub status: i32,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+    #[serde(default)]
+    pub writes: BTreeMap<String, String>,
+}
+
+fn default_interactive() -> bool {
+    true
+}
+
+impl Scenario {
+    /// Script the rest of the process with the scenario at `path`, its seed
+    /// replaced by `seed` when given
+    pub fn activate(path: &Path, seed: Option<u64>) -> Result<()> {
+        if !cfg!(feature = "test-mode") {
+            anyhow::bail!(
+                "This build of cargo-sane has no test mode (the test-mode feature is off)"
+            );
+        }
+        let mut scenario = Self::load(path)?;
+        if let Some(seed) = seed {
+            scenario.seed = seed;
+        }
+        scenario.fingerprint = fingerprint(&format!("{}\n{}", scenario.fingerprint, scenario.seed));
+        let _ = SCENARIO.set(scenario);
+        Ok(())
+    }
+
+    /// The scenario given with `--test-mode`, if any
+    pub fn active() -> Option<&'static Scenario> {
+        SCENARIO.get()
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        let mut scenario: Scenario =
+            toml::from_str(&content).context(format!("Failed to parse {}", path.display()))?;
+        if !(0.0..=1.0).contains(&scenario.failure_rate) {
+            anyhow::bail!("{}: failure_rate must be between 0 and 1", path.display());
+        }
+        scenario.fingerprint = fingerprint(&content);
+        Ok(scenario)
+    }
+
+    /// Identifies the scenario's contents and seed
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// The answer to the next prompt, if any are left
+    pub fn next_answer(&self) -> Option<&Answer> {
+        self.answers
+            .get(self.answered.fetch_add(1, Ordering::SeqCst))
+    }
+
+    /// Whether the lookup described by `key` fails, from the seed and the
+    /// key alone
+    fn fails(&self, key: &str) -> bool {
+        if self.failure_rate <= 0.0 {
+            return false;
+        }
+        // SplitMix64's finalizer spreads the FNV-1a hash of seed and key
+        let mut z = key
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64 ^ self.seed, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        ((z >> 11) as f64 / (1_u64 << 53) as f64) < self.failure_rate
+    }
+
+    fn inject(&self, key: &str) -> Result<()> {
+        if self.fails(key) {
+            anyhow::bail!("test mode: injected failure looking up {}", key);
+        }
+        Ok(())
+    }
+
+    fn registry_crate(&self, name: &str) -> Result<&ScenarioCrate> {
+        self.inject(name)?;
+        let krate = self
+            .crates
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| NotFound {
+                crate_name: name.to_string(),
+            })?;
+        if krate.fail {
+            anyhow::bail!("test mode: the registry fails every lookup of {}", name);
+        }
+        Ok(krate)
+    }
+
+    pub fn crate_info(&self, name: &str) -> Result<CrateInfo> {
+        let krate = self.registry_crate(name)?;
+        let released = krate.versions.iter().filter(|v| !krate.yanked.contains(v));
+        let newest = released.clone().next().or(krate.versions.first());
+        Ok(CrateInfo {
+            name: krate.name.clone(),
+            newest_version: newest.map(Version::to_string).unwrap_or_default(),
+            max_stable_version: released
+                .clone()
+                .find(|v| v.pre.is_empty())
+                .map(Version::to_string),
+            description: None,
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+            repository: krate.repository.clone(),
+        })
+    }
+
+    pub fn published_versions(&self, name: &str) -> Result<Vec<PublishedVersion>> {
+        let krate = self.registry_crate(name)?;
+        Ok(krate
+            .versions
+            .iter()
+            .map(|version| PublishedVersion {
+                version: version.clone(),
+                yanked: krate.yanked.contains(version),
+                created_at: None,
+                published_by: None,
+                license: krate.license.clone(),
+                crate_size: krate.sizes.get(version).copied(),
+            })
+            .collect())
+    }
+
+    pub fn downloads(&self, names: &[&str]) -> Result<HashMap<String, u64>> {
+        self.inject("download counts")?;
+        Ok(self
+            .crates
+            .iter()
+            .filter(|c| names.contains(&c.name.as_str()))
+            .map(|c| (c.name.clone(), c.downloads))
+            .collect())
+    }
+
+    pub fn owners(&self, name: &str) -> Result<Vec<String>> {
+        Ok(self.registry_crate(name)?.owners.clone())
+    }
+
+    /// The crate's index entries, oldest first as in the index
+    pub fn index_entries(&self, name: &str) -> Result<Vec<IndexEntry>> {
+        let krate = self.registry_crate(name)?;
+        Ok(krate
+            .versions
+            .iter()
+            .rev()
+            .map(|version| IndexEntry {
+                name: krate.name.clone(),
+                vers: version.clone(),
+                deps: Vec::new(),
+                features: krate.features.clone(),
+                features2: BTreeMap::new(),
+                yanked: krate.yanked.contains(version),
+                rust_version: krate.rust_versions.get(version).cloned(),
+                cksum: krate.checksums.get(version).cloned(),
+            })
+            .collect())
+    }
+
+    pub fn advisories_for(&self, name: &str, version: &Version) -> Result<Vec<Advisory>> {
+        self.inject(&format!("advisories for {}@{}", name, version))?;
+        Ok(self
+            .advisories_on_record(name)
+            .filter(|a| a.affects(version))
+            .collect())
+    }
+
+    /// Every advisory against `name`, whichever versions it affects
+    pub fn advisories_of(&self, name: &str) -> Result<Vec<Advisory>> {
+        self.inject(&format!("advisories for {}", name))?;
+        Ok(self.advisories_on_record(name).collect())
+    }
+
+    fn advisories_on_record<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Advisory> + 'a {
+        self.advisories
+            .iter()
+            .filter(move |a| a.package == name)
+            .map(|a| Advisory {
+                id: a.id.clone(),
+                package: a.package.clone(),
+                title: a.title.clone(),
+                severity: a.severity,
+                cvss: None,
+                aliases: Vec::new(),
+                patched_versions: a.patched.clone(),
+                informational: a.informational.clone(),
+                url: format!("https://rustsec.org/advisories/{}", a.id),
+            })
+    }
+
+    /// Play the first stub matching `args` in `dir`: write its files and
+    /// return its exit status and output
+    pub fn run_cargo(&self, args: &[String], dir: &Path) -> Result<(bool, CargoOutput)> {
+        let stub = self
+            .cargo
+            .iter()
+            .find(|stub| args.starts_with(&stub.args))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "test mode: the scenario has no stub for `cargo {}`",
+                    args.join(" ")
+                )
+            })?;
+        for (file, content) in &stub.writes {
+            let path = dir.join(file);
+            fs::write(&path, content).context(format!("Failed to write {}", path.display()))?;
+        }
+        let output = CargoOutput {
+            stdout: stub.stdout.clone(),
+            stderr: stub.stderr.clone(),
+        };
+        Ok((stub.status == 0, output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(toml: &str) -> Scenario {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scenario.toml");
+        fs::write(&path, toml).unwrap();
+        Scenario::load(&path).unwrap()
+    }
+
+    #[test]
+    fn test_registry_and_advisories() {
+        let scenario = scenario(
+            r#"
+            [[crates]]
+            name = "serde"
+            versions = ["1.1.0-rc.1", "1.0.200", "1.0.199", "1.0.100"]
+            yanked = ["1.0.199"]
+            license = "MIT"
+
+            [[advisories]]
+            id = "RUSTSEC-2024-0001"
+            package = "serde"
+            title = "Flaw"
+            severity = "high"
+            patched = [">=1.0.200"]
+            "#,
+        );
+
+        let info = scenario.crate_info("serde").unwrap();
+        assert_eq!(info.newest_version, "1.1.0-rc.1");
+        assert_eq!(info.max_stable_version.as_deref(), Some("1.0.200"));
+        let versions = scenario.published_versions("serde").unwrap();
+        assert_eq!(versions.len(), 4);
+        assert!(versions[2].yanked);
+        assert_eq!(
+            scenario.index_entries("serde").unwrap()[0].vers.to_string(),
+            "1.0.100"
+        );
+
+        let error = scenario.crate_info("missing").unwrap_err();
+        assert!(error.downcast_ref::<NotFound>().is_some());
+
+        let affected = |v: &str| {
+            scenario
+                .advisories_for("serde", &Version::parse(v).unwrap())
+                .unwrap()
+                .len()
+        };
+        assert_eq!((affected("1.0.100"), affected("1.0.200")), (1, 0));
+    }
+
+    #[test]
+    fn test_injected_failures_follow_the_seed() {
+        let names: Vec<String> = (0..1000).map(|i| format!("crate-{}", i)).collect();
+        let failing = |scenario: &Scenario| -> Vec<bool> {
+            names.iter().map(|name| scenario.fails(name)).collect()
+        };
+        let mut scenario = scenario("failure_rate = 0.2\nseed = 1\n");
+        let first = failing(&scenario);
+        assert_eq!(first, failing(&scenario));
+        let count = first.iter().filter(|f| **f).count();
+        assert!((150..250).contains(&count), "{} of 1000 failed", count);
+
+        scenario.seed = 2;
+        assert_ne!(first, failing(&scenario));
+        scenario.failure_rate = 0.0;
+        assert!(!failing(&scenario).contains(&true));
+    }
+
+    #[test]
+    fn test_cargo_stubs_and_answers() {
+        let scenario = scenario(
+            r#"
+            answers = [true, 2, "Skip", ["serde", 0]]
+
+            [[cargo]]
+            args = ["update", "--package", "syn@1.0.0"]
+            stdout = "updated"
+
+            [cargo.writes]
+            "Cargo.lock" = "version = 3\n"
+
+            [[cargo]]
+            args = ["check"]
+            status = 101
+            stderr = "error: mismatched types"
+            "#,
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let args = |line: &str| line.split(' ').map(str::to_string).collect::<Vec<_>>();
+
+        let (success, output) = scenario
+            .run_cargo(
+                &args("update --package syn@1.0.0 --precise 2.0.0"),
+                dir.path(),
+            )
+            .unwrap();
+        assert!(success);
+        assert_eq!(output.stdout, "updated");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("Cargo.lock")).unwrap(),
+            "version = 3\n"
+        );
+        let (success, output) = scenario
+            .run_cargo(&args("check --manifest-path x"), dir.path())
+            .unwrap();
+        assert!(!success);
+        assert!(output.stderr.contains("mismatched"));
+        let error = scenario.run_cargo(&args("tree"), dir.path()).unwrap_err();
+        assert!(error.to_string().contains("no stub for `cargo tree`"));
+
+        assert_eq!(scenario.next_answer(), Some(&Answer::Bool(true)));
+        assert_eq!(scenario.next_answer(), Some(&Answer::Index(2)));
+        assert_eq!(
+            scenario.next_answer(),
+            Some(&Answer::Text("Skip".to_string()))
+        );
+        assert_eq!(
+            scenario.next_answer(),
+            Some(&Answer::List(vec![
+                Answer::Text("serde".to_string()),
+                Answer::Index(0)
+            ]))
+        );
+        assert_eq!(scenario.next_answer(), None);
+    }
+
+    #[test]
+    fn test_unknown_keys_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scenario.toml");
+        fs::write(
+            &path,
+            "[[crates]]\nname = \"a\"\nversions = []\nyank = []\n",
+        )
+        .unwrap();
+        assert!(Scenario::load(&path).is_err());
+        fs::write(&path, "failure_rate = 2.0\n").unwrap();
+        assert!(Scenario::load(&path).is_err());
+    }
+}
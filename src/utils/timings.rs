@@ -0,0 +1,256 @@
+//! Where the time goes, for `-v`
+//!
+//! Phases (registry lookups, cargo subprocesses, file scans, ...) are timed
+//! with [`span`] guards and events tallied with [`count`]. Nothing is
+//! recorded until [`enable`] is called, so the instrumentation costs a
+//! single check per call otherwise. Time spent on the network is the
+//! exception: it's always added up, for [`network_time`].
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+static RECORDER: OnceLock<Mutex<Recorder>> = OnceLock::new();
+
+/// The phases that wait on the network
+const NETWORK_PHASES: [&str; 2] = ["registry", "advisories"];
+
+/// Microseconds spent in network phases, recording or not
+static NETWORK_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Start recording, from now
+pub fn enable() {
+    RECORDER.get_or_init(|| Mutex::new(Recorder::new(Instant::now())));
+}
+
+pub fn is_enabled() -> bool {
+    RECORDER.get().is_some()
+}
+
+/// Time `phase` until the returned guard is dropped
+pub fn span(phase: &str) -> Span {
+    let now = Instant::now();
+    Span {
+        phase: is_enabled().then(|| (phase.to_string(), now)),
+        network: NETWORK_PHASES.contains(&phase).then_some(now),
+    }
+}
+
+/// Time spent in network phases so far, whether or not recording is on.
+/// Phases running side by side each count in full.
+pub fn network_time() -> Duration {
+    Duration::from_micros(NETWORK_MICROS.load(Ordering::Relaxed))
+}
+
+/// Add `n` to `counter`
+pub fn count(counter: &str, n: u64) {
+    if let Some(recorder) = RECORDER.get() {
+        if let Ok(mut recorder) = recorder.lock() {
+            recorder.count(counter, n);
+        }
+    }
+}
+
+/// Everything recorded so far, or None when recording is off
+pub fn snapshot() -> Option<Timings> {
+    let recorder = RECORDER.get()?.lock().ok()?;
+    Some(recorder.timings(Instant::now()))
+}
+
+/// Records its phase's duration when dropped
+#[must_use]
+pub struct Span {
+    phase: Option<(String, Instant)>,
+    network: Option<Instant>,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(started) = self.network.take() {
+            let micros = u64::try_from(started.elapsed().as_micros()).unwrap_or(u64::MAX);
+            NETWORK_MICROS.fetch_add(micros, Ordering::Relaxed);
+        }
+        let Some((phase, started)) = self.phase.take() else {
+            return;
+        };
+        if let Some(Ok(mut recorder)) = RECORDER.get().map(Mutex::lock) {
+            recorder.record(&phase, started.elapsed());
+        }
+    }
+}
+
+/// Phase durations and counters of one run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, JsonSchema)]
+pub struct Timings {
+    /// Since recording started
+    pub total_ms: f64,
+    /// In the order each phase first finished
+    pub phases: Vec<PhaseTiming>,
+    pub counters: Vec<Counter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct PhaseTiming {
+    pub name: String,
+    pub calls: u64,
+    pub total_ms: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct Counter {
+    pub name: String,
+    pub count: u64,
+}
+
+impl Timings {
+    /// One line, e.g. "manifest 2ms, registry 840ms (24 requests), total 1.10s"
+    pub fn summary(&self) -> String {
+        let counter = |name: &str| {
+            self.counters
+                .iter()
+                .find(|c| c.name == name)
+                .map_or(0, |c| c.count)
+        };
+        let mut parts: Vec<String> = self
+            .phases
+            .iter()
+            .map(|phase| {
+                let mut part = format!("{} {}", phase.name, format_ms(phase.total_ms));
+                if phase.name == "registry" {
+                    part.push_str(&format!(" ({} requests)", counter("registry requests")));
+                } else if phase.calls > 1 {
+                    part.push_str(&format!(" ({} calls)", phase.calls));
+                }
+                part
+            })
+            .collect();
+
+        let (hits, misses) = (counter("cache hits"), counter("cache misses"));
+        if let Some(rate) = (hits * 100).checked_div(hits + misses) {
+            parts.push(format!(
+                "cache {}% hit ({} of {})",
+                rate,
+                hits,
+                hits + misses
+            ));
+        }
+        parts.push(format!("total {}", format_ms(self.total_ms)));
+        parts.join(", ")
+    }
+}
+
+fn format_ms(ms: f64) -> String {
+    if ms < 1000.0 {
+        format!("{:.0}ms", ms)
+    } else {
+        format!("{:.2}s", ms / 1000.0)
+    }
+}
+
+struct Recorder {
+    started: Instant,
+    phases: Vec<(String, u64, Duration)>,
+    counters: Vec<(String, u64)>,
+}
+
+impl Recorder {
+    fn new(started: Instant) -> Self {
+        Self {
+            started,
+            phases: Vec::new(),
+            counters: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, phase: &str, elapsed: Duration) {
+        match self.phases.iter_mut().find(|(name, _, _)| name == phase) {
+            Some((_, calls, total)) => {
+                *calls += 1;
+                *total += elapsed;
+            }
+            None => self.phases.push((phase.to_string(), 1, elapsed)),
+        }
+    }
+
+    fn count(&mut self, counter: &str, n: u64) {
+        match self.counters.iter_mut().find(|(name, _)| name == counter) {
+            Some((_, count)) => *count += n,
+            None => self.counters.push((counter.to_string(), n)),
+        }
+    }
+
+    fn timings(&self, now: Instant) -> Timings {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        Timings {
+            total_ms: ms(now.duration_since(self.started)),
+            phases: self
+                .phases
+                .iter()
+                .map(|(name, calls, total)| PhaseTiming {
+                    name: name.clone(),
+                    calls: *calls,
+                    total_ms: ms(*total),
+                })
+                .collect(),
+            counters: self
+                .counters
+                .iter()
+                .map(|(name, count)| Counter {
+                    name: name.clone(),
+                    count: *count,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorder() {
+        let started = Instant::now();
+        let mut recorder = Recorder::new(started);
+        recorder.record("manifest", Duration::from_millis(2));
+        recorder.record("registry", Duration::from_millis(840));
+        recorder.record("cargo metadata", Duration::from_millis(300));
+        recorder.record("cargo metadata", Duration::from_millis(200));
+        recorder.count("registry requests", 20);
+        recorder.count("registry requests", 4);
+        recorder.count("cache misses", 1);
+        recorder.count("cache hits", 3);
+
+        let timings = recorder.timings(started + Duration::from_millis(1500));
+        assert_eq!(timings.phases[2].calls, 2);
+        assert_eq!(timings.phases[2].total_ms, 500.0);
+        assert_eq!(
+            timings.summary(),
+            "manifest 2ms, registry 840ms (24 requests), cargo metadata 500ms (2 calls), \
+             cache 75% hit (3 of 4), total 1.50s"
+        );
+    }
+
+    #[test]
+    fn test_disabled_spans_record_nothing() {
+        // Other tests never enable recording, so this one can't either
+        if !is_enabled() {
+            let span = span("registry");
+            assert!(span.phase.is_none());
+            assert_eq!(snapshot(), None);
+        }
+    }
+
+    #[test]
+    fn test_network_time_is_always_added_up() {
+        let before = network_time();
+        {
+            let _registry = span("registry");
+            let _manifest = span("manifest");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(network_time() >= before + Duration::from_millis(5));
+    }
+}
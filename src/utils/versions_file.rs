@@ -0,0 +1,142 @@
+//! Loading the organization's versions file from disk or over HTTPS
+//!
+//! Remote files are cached under `.cargo-sane/` and refetched once a day. If
+//! a refetch fails, the cached copy is used with a warning so a flaky policy
+//! server doesn't block every check.
+
+use crate::core::policy::VersionPolicy;
+use crate::utils::cache::{read_json, unix_now, STATE_DIR};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+const USER_AGENT: &str = "cargo-sane (https://github.com/chronocoders/cargo-sane)";
+
+/// How long a fetched versions file is used before fetching it again
+pub const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A remote versions file as last fetched
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedFile {
+    url: String,
+    fetched_at: u64,
+    content: String,
+}
+
+/// Load the versions file named by the `versions_file` option: an HTTPS URL,
+/// or a path relative to the project at `root`
+pub async fn load_versions_file(source: &str, root: &Path) -> Result<VersionPolicy> {
+    let content = if source.starts_with("https://") {
+        fetch_cached(source, &root.join(STATE_DIR).join("versions-file.json")).await?
+    } else if source.contains("://") {
+        anyhow::bail!(
+            "versions_file must be a local path or an https:// URL, not {}",
+            source
+        );
+    } else {
+        let path = root.join(source);
+        fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?
+    };
+
+    let json = source.ends_with(".json") || content.trim_start().starts_with('{');
+    VersionPolicy::parse(&content, json).context(format!("Invalid versions file {}", source))
+}
+
+async fn fetch_cached(url: &str, cache_path: &Path) -> Result<String> {
+    let cached: Option<CachedFile> =
+        read_json(cache_path).filter(|cached: &CachedFile| cached.url == url);
+    if let Some(cached) = &cached {
+        if unix_now().saturating_sub(cached.fetched_at) < MAX_AGE.as_secs() {
+            return Ok(cached.content.clone());
+        }
+    }
+
+    match fetch(url).await {
+        Ok(content) => {
+            let entry = CachedFile {
+                url: url.to_string(),
+                fetched_at: unix_now(),
+                content,
+            };
+            if let Some(dir) = cache_path.parent() {
+                fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+            }
+            fs::write(cache_path, serde_json::to_string(&entry)?)
+                .context(format!("Failed to write {}", cache_path.display()))?;
+            Ok(entry.content)
+        }
+        Err(e) => match cached {
+            Some(cached) => {
+                eprintln!(
+                    "Warning: Could not refresh versions file, using the cached copy: {:#}",
+                    e
+                );
+                Ok(cached.content)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+async fn fetch(url: &str) -> Result<String> {
+    let client = reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(Duration::from_secs(10))
+        .build()
+        .context("Failed to create HTTP client")?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .context(format!("Failed to fetch versions file {}", url))?;
+    if !response.status().is_success() {
+        anyhow::bail!(
+            "Fetching versions file {} failed: {}",
+            url,
+            response.status()
+        );
+    }
+    response
+        .text()
+        .await
+        .context(format!("Failed to read versions file {}", url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_loads_local_toml_and_json() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("blessed.toml"), "serde = \"=1.0.197\"\n").unwrap();
+        fs::write(dir.path().join("blessed.json"), r#"{"serde": "=1.0.197"}"#).unwrap();
+
+        let toml = block_on(load_versions_file("blessed.toml", dir.path())).unwrap();
+        let json = block_on(load_versions_file("blessed.json", dir.path())).unwrap();
+        assert_eq!(toml.to_string(), "serde = \"=1.0.197\"\n");
+        assert_eq!(json.to_string(), toml.to_string());
+        assert!(block_on(load_versions_file("missing.toml", dir.path())).is_err());
+        assert!(block_on(load_versions_file("http://example.com/v.toml", dir.path())).is_err());
+    }
+
+    #[test]
+    fn test_fresh_cache_is_used_without_fetching() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = "https://policy.invalid/blessed.toml";
+        let cache_path = dir.path().join(STATE_DIR).join("versions-file.json");
+        fs::create_dir_all(cache_path.parent().unwrap()).unwrap();
+        let entry = CachedFile {
+            url: url.to_string(),
+            fetched_at: unix_now(),
+            content: "tokio = \"~1.36\"\n".to_string(),
+        };
+        fs::write(&cache_path, serde_json::to_string(&entry).unwrap()).unwrap();
+
+        let policy = block_on(load_versions_file(url, dir.path())).unwrap();
+        assert_eq!(policy.requirement("tokio").unwrap().to_string(), "~1.36");
+    }
+}
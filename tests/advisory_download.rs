@@ -0,0 +1,198 @@
+mod common;
+
+use cargo_sane::utils::advisory_db::fetch_archive;
+use cargo_sane::utils::download::{partial_path, Downloaded};
+use cargo_sane::utils::progress::CapturedProgress;
+use common::ArchiveServer;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// A gzip archive of 64 kB that doesn't compress, so it arrives in many
+/// chunks
+fn archive() -> Vec<u8> {
+    let mut state: u32 = 0x2545_f491;
+    let contents: Vec<u8> = (0..65_536)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&contents).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn fetch(
+    server: &ArchiveServer,
+    path: &Path,
+    progress: &CapturedProgress,
+) -> anyhow::Result<Downloaded> {
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(fetch_archive(&server.url, path, progress))
+}
+
+#[test]
+fn test_first_download_reports_bytes() {
+    let body = archive();
+    let server = ArchiveServer::start("advisory-db.tar.gz", body.clone());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(".cargo-sane/advisory-db.tar.gz");
+
+    let progress = CapturedProgress::default();
+    let downloaded = fetch(&server, &path, &progress).unwrap();
+    assert_eq!(downloaded.bytes, body.len() as u64);
+    assert_eq!(downloaded.resumed_from, 0);
+    assert_eq!(fs::read(&path).unwrap(), body);
+    assert!(!partial_path(&path).exists());
+
+    let events = progress.events();
+    assert_eq!(
+        events[0],
+        format!("start {} Downloading advisory database", body.len())
+    );
+    let received: u64 = events
+        .iter()
+        .filter_map(|e| e.strip_prefix("inc "))
+        .map(|n| n.parse::<u64>().unwrap())
+        .sum();
+    assert_eq!(received, body.len() as u64);
+    assert_eq!(events.last().map(String::as_str), Some("finish"));
+}
+
+#[test]
+fn test_interrupted_download_keeps_the_old_archive_and_resumes() {
+    let body = archive();
+    let server = ArchiveServer::start("advisory-db.tar.gz", body.clone());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("advisory-db.tar.gz");
+    fs::write(&path, b"the previous archive").unwrap();
+
+    server.cut_next_after(20_000);
+    let interrupted = fetch(&server, &path, &CapturedProgress::default());
+    let message = format!("{:#}", interrupted.unwrap_err());
+    assert!(message.contains("run again to resume it"), "{}", message);
+    assert_eq!(fs::read(&path).unwrap(), b"the previous archive");
+    assert_eq!(fs::metadata(partial_path(&path)).unwrap().len(), 20_000);
+
+    let progress = CapturedProgress::default();
+    let resumed = fetch(&server, &path, &progress).unwrap();
+    assert_eq!(resumed.resumed_from, 20_000);
+    assert_eq!(fs::read(&path).unwrap(), body);
+    assert_eq!(server.ranges(), [None, Some("bytes=20000-".to_string())]);
+    // The bar starts where the last attempt stopped
+    assert_eq!(progress.events()[1], "inc 20000");
+}
+
+#[test]
+fn test_corrupt_partial_file_starts_over() {
+    let body = archive();
+    let server = ArchiveServer::start("advisory-db.tar.gz", body.clone());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("advisory-db.tar.gz");
+    fs::write(partial_path(&path), vec![0u8; 500]).unwrap();
+
+    let progress = CapturedProgress::default();
+    let downloaded = fetch(&server, &path, &progress).unwrap();
+    assert_eq!(downloaded.resumed_from, 0);
+    assert_eq!(fs::read(&path).unwrap(), body);
+    assert_eq!(server.ranges(), [Some("bytes=500-".to_string()), None]);
+    assert!(progress
+        .events()
+        .iter()
+        .any(|e| e.starts_with("warn ") && e.contains("starting over")));
+}
+
+#[test]
+fn test_unreadable_archive_never_replaces_the_old_one() {
+    let server = ArchiveServer::start("advisory-db.tar.gz", b"<html>maintenance</html>".to_vec());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("advisory-db.tar.gz");
+    fs::write(&path, b"the previous archive").unwrap();
+
+    let error = fetch(&server, &path, &CapturedProgress::default()).unwrap_err();
+    assert!(format!("{:#}", error).contains("corrupt"), "{:#}", error);
+    assert_eq!(fs::read(&path).unwrap(), b"the previous archive");
+    assert!(!partial_path(&path).exists());
+}
+
+/// Run `health` on a project whose config points at `server`'s archive
+#[cfg(feature = "test-mode")]
+fn health(dir: &Path, args: &[&str]) -> std::process::Output {
+    common::cargo_sane_scripted(dir, &[&["health"], args].concat())
+        .env_remove("CI")
+        .output()
+        .unwrap()
+}
+
+#[cfg(feature = "test-mode")]
+#[test]
+fn test_health_refreshes_the_archive_the_config_names() {
+    let body = archive();
+    let server = ArchiveServer::start("advisory-db.tar.gz", body.clone());
+    let dir = common::project("");
+    fs::write(dir.path().join("scenario.toml"), "").unwrap();
+    fs::write(
+        dir.path().join(".cargo-sane.toml"),
+        format!("advisory_db_archive = \"{}\"\n", server.url),
+    )
+    .unwrap();
+    let path = dir.path().join(".cargo-sane/advisory-db.tar.gz");
+
+    // Not a terminal: plain progress lines on stderr
+    let output = health(dir.path(), &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let err = String::from_utf8_lossy(&output.stderr);
+    assert!(err.contains("Downloading advisory database"), "{}", err);
+    assert_eq!(fs::read(&path).unwrap(), body);
+
+    // Fresh enough: the next run leaves it alone
+    let output = health(dir.path(), &[]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(server.ranges().len(), 1);
+
+    // --update-db fetches it again, and --json keeps the progress quiet
+    let output = health(dir.path(), &["--update-db", "--json"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(server.ranges().len(), 2);
+    let err = String::from_utf8_lossy(&output.stderr);
+    assert!(!err.contains("Downloading advisory database"), "{}", err);
+
+    // Offline never touches it
+    let output = health(dir.path(), &["--update-db", "--offline"]);
+    assert_eq!(server.ranges().len(), 2, "{:?}", output);
+}
+
+#[cfg(feature = "test-mode")]
+#[test]
+fn test_health_keeps_the_old_archive_when_the_refresh_fails() {
+    let server = ArchiveServer::start("advisory-db.tar.gz", b"<html>maintenance</html>".to_vec());
+    let dir = common::project("");
+    fs::write(dir.path().join("scenario.toml"), "").unwrap();
+    fs::write(
+        dir.path().join(".cargo-sane.toml"),
+        format!("advisory_db_archive = \"{}\"\n", server.url),
+    )
+    .unwrap();
+    let path = dir.path().join(".cargo-sane/advisory-db.tar.gz");
+
+    // A scheduled first download only warns
+    let output = health(dir.path(), &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let out = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        out.contains("Could not refresh the advisory database archive"),
+        "{}",
+        out
+    );
+    assert!(!path.exists());
+
+    // A demanded refresh fails the run, leaving the old archive
+    fs::write(&path, b"the previous archive").unwrap();
+    let output = health(dir.path(), &["--update-db"]);
+    assert!(!output.status.success(), "{:?}", output);
+    assert_eq!(fs::read(&path).unwrap(), b"the previous archive");
+}
@@ -0,0 +1,250 @@
+mod common;
+
+use cargo_sane::cli::commands;
+use cargo_sane::cli::output::OutputFormat;
+use cargo_sane::core::dependency::DependencyKind;
+use cargo_sane::core::manifest::{DependencySection, Manifest};
+use cargo_sane::updater::plan::{Plan, PlannedAction};
+use cargo_sane::utils::audit::{AuditEntry, AuditLog};
+use common::manifest_arg;
+use std::fs;
+use std::path::Path;
+
+const LOCKFILE: &str = r#"version = 3
+
+[[package]]
+name = "fixture"
+version = "0.1.0"
+dependencies = ["serde"]
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+fn entries(dir: &Path) -> Vec<AuditEntry> {
+    AuditLog::for_manifest(&dir.join("Cargo.toml"))
+        .read()
+        .unwrap()
+        .0
+}
+
+/// Save a plan raising serde's requirement, as `--dry-run --json` would
+fn save_plan(dir: &Path, from: &str, to: &str) -> String {
+    let manifest = Manifest::from_path(&dir.join("Cargo.toml")).unwrap();
+    let action = PlannedAction::manifest_edit(
+        &DependencySection::new(DependencyKind::Normal),
+        "serde",
+        from,
+        to,
+        "RUSTSEC-0000-0000".to_string(),
+    );
+    let plan = Plan::new(&manifest, vec![action]).unwrap();
+    let path = dir.join("plan.json");
+    fs::write(&path, serde_json::to_string(&plan).unwrap()).unwrap();
+    path.display().to_string()
+}
+
+#[test]
+fn test_lint_fix_is_recorded() {
+    let dir = common::project("serde = \"*\"\n");
+    fs::write(dir.path().join("Cargo.lock"), LOCKFILE).unwrap();
+
+    commands::lint_command(manifest_arg(dir.path()), false, false, true).unwrap();
+    assert!(entries(dir.path()).is_empty());
+
+    commands::lint_command(manifest_arg(dir.path()), true, false, true).unwrap();
+    let entries = entries(dir.path());
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!(entry.command, "lint");
+    assert!(entry.verified);
+    assert_eq!(
+        entry.backup.as_deref(),
+        Some(dir.path().join("Cargo.toml.backup").as_path())
+    );
+    assert_eq!(entry.changes.len(), 1);
+    let change = &entry.changes[0];
+    assert_eq!(
+        (
+            change.name.as_str(),
+            change.old.as_deref(),
+            change.new.as_deref(),
+            change.section.as_str()
+        ),
+        ("serde", Some("*"), Some("1.0.200"), "dependencies")
+    );
+}
+
+#[test]
+fn test_lint_fix_pins_workspace_entries_at_the_root() {
+    let dir = common::workspace(&[("a", "serde = { workspace = true }\n")]);
+    let root = dir.path().join("Cargo.toml");
+    let mut manifest = fs::read_to_string(&root).unwrap();
+    manifest.push_str("\n[workspace.dependencies]\nserde = \"*\"\n");
+    fs::write(&root, manifest).unwrap();
+    fs::write(dir.path().join("Cargo.lock"), LOCKFILE).unwrap();
+
+    // The member can't pin what it inherits
+    let member = dir.path().join("crates/a/Cargo.toml");
+    let member_arg = Some(member.display().to_string());
+    let before = fs::read_to_string(&member).unwrap();
+    assert!(!commands::lint_command(member_arg, true, false, true).unwrap());
+    assert_eq!(fs::read_to_string(&member).unwrap(), before);
+    assert!(entries(member.parent().unwrap()).is_empty());
+
+    assert!(commands::lint_command(manifest_arg(dir.path()), true, false, true).unwrap());
+    assert!(fs::read_to_string(&root)
+        .unwrap()
+        .contains("[workspace.dependencies]\nserde = \"1.0.200\"\n"));
+    let entries = entries(dir.path());
+    assert_eq!(entries.len(), 1);
+    let change = &entries[0].changes[0];
+    assert_eq!(
+        (
+            change.old.as_deref(),
+            change.new.as_deref(),
+            change.section.as_str()
+        ),
+        (Some("*"), Some("1.0.200"), "workspace.dependencies")
+    );
+}
+
+#[test]
+fn test_fmt_deps_is_recorded() {
+    let dir = common::project("serde = { version = \"1\" }\nanyhow = \"1\"\n");
+
+    assert!(commands::fmt_deps_command(manifest_arg(dir.path()), false, true).unwrap());
+    assert!(entries(dir.path()).is_empty());
+
+    assert!(commands::fmt_deps_command(manifest_arg(dir.path()), false, false).unwrap());
+    let entries = entries(dir.path());
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!(entry.command, "fmt-deps");
+    assert_eq!(
+        entry.backup.as_deref(),
+        Some(dir.path().join("Cargo.toml.backup").as_path())
+    );
+    let changes: Vec<(&str, Option<&str>, Option<&str>)> = entry
+        .changes
+        .iter()
+        .map(|c| (c.name.as_str(), c.old.as_deref(), c.new.as_deref()))
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            ("anyhow", Some("anyhow = \"1\""), Some("anyhow = \"1\"")),
+            (
+                "serde",
+                Some("serde = { version = \"1\" }"),
+                Some("serde = \"1\"")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_applied_plans_are_recorded() {
+    let dir = common::project("serde = \"1.0.100\"\n");
+    let plan = save_plan(dir.path(), "1.0.100", "1.0.200");
+    commands::fix_command(
+        manifest_arg(dir.path()),
+        false,
+        false,
+        false,
+        Some(plan),
+        None,
+        false,
+        false,
+    )
+    .unwrap();
+
+    let plan = save_plan(dir.path(), "1.0.200", "1.0.210");
+    commands::health_command(
+        manifest_arg(dir.path()),
+        OutputFormat::Text,
+        None,
+        None,
+        false,
+        false,
+        false,
+        false,
+        Some(plan),
+        0,
+        false,
+        false,
+        false,
+        None,
+        false,
+        false,
+        false,
+        Vec::new(),
+    )
+    .unwrap();
+
+    let entries = entries(dir.path());
+    let summary: Vec<(&str, Option<&str>, Option<&str>)> = entries
+        .iter()
+        .map(|e| {
+            (
+                e.command.as_str(),
+                e.changes[0].old.as_deref(),
+                e.changes[0].new.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("fix", Some("1.0.100"), Some("1.0.200")),
+            ("health", Some("1.0.200"), Some("1.0.210")),
+        ]
+    );
+    assert!(entries.iter().all(|e| e.verified));
+}
+
+#[test]
+fn test_dry_runs_are_not_recorded() {
+    let dir = common::project("");
+    commands::fix_command(
+        manifest_arg(dir.path()),
+        false,
+        true,
+        true,
+        None,
+        None,
+        false,
+        false,
+    )
+    .unwrap();
+    assert!(entries(dir.path()).is_empty());
+    assert!(!dir.path().join(".cargo-sane/audit.log").exists());
+}
+
+#[test]
+fn test_nothing_is_recorded_when_disabled() {
+    let dir = common::project("serde = \"1.0.100\"\n");
+    fs::write(
+        dir.path().join(".cargo-sane.toml"),
+        "disable_audit_log = true\n",
+    )
+    .unwrap();
+    let plan = save_plan(dir.path(), "1.0.100", "1.0.200");
+    commands::fix_command(
+        manifest_arg(dir.path()),
+        false,
+        false,
+        false,
+        Some(plan),
+        None,
+        false,
+        false,
+    )
+    .unwrap();
+    assert!(fs::read_to_string(dir.path().join("Cargo.toml"))
+        .unwrap()
+        .contains("1.0.200"));
+    assert!(entries(dir.path()).is_empty());
+}
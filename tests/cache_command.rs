@@ -0,0 +1,215 @@
+mod common;
+
+use cargo_sane::analyzer::checker::DependencyChecker;
+use cargo_sane::cli::commands;
+use cargo_sane::core::advisory::Advisory;
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::utils::advisories::AdvisorySource;
+use cargo_sane::utils::advisory_db::{database_path, AdvisoryDb, DbMode, DbOptions};
+use cargo_sane::utils::cache::{self, ReportCache, STATE_DIR};
+use cargo_sane::utils::cache_store::{CacheKind, CacheStore};
+use cargo_sane::utils::crates_io::CratesIoClient;
+use common::{block_on, manifest_arg, MockRegistry, NoAdvisories};
+use semver::Version;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HOUR: u64 = 60 * 60;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Knows of one advisory, against serde
+struct SerdeAdvisory;
+
+impl AdvisorySource for SerdeAdvisory {
+    async fn advisories_for(&self, name: &str, _: &Version) -> anyhow::Result<Vec<Advisory>> {
+        if name != "serde" {
+            return Ok(Vec::new());
+        }
+        Ok(vec![Advisory {
+            id: "RUSTSEC-2099-0001".to_string(),
+            package: "serde".to_string(),
+            title: "Something bad".to_string(),
+            severity: None,
+            cvss: None,
+            aliases: Vec::new(),
+            patched_versions: vec![">=1.0.201".to_string()],
+            informational: None,
+            url: String::new(),
+        }])
+    }
+}
+
+/// A project whose check cache and advisory database were filled from the
+/// mock registry and a stand-in advisory source
+fn populated() -> (tempfile::TempDir, Manifest) {
+    let registry = MockRegistry::start(
+        &[("serde", "1.0.200"), ("anyhow", "1.0.80")],
+        Duration::from_millis(0),
+    );
+    let dir = common::project("serde = \"1.0\"\nanyhow = \"1.0\"\n");
+    let manifest = Manifest::from_path(&dir.path().join("Cargo.toml")).unwrap();
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    );
+    let dependencies = block_on(checker.check_dependencies(&manifest)).unwrap();
+    ReportCache::for_manifest(&manifest, 60)
+        .store(&cache::manifest_key(&manifest), &dependencies)
+        .unwrap();
+
+    let options = DbOptions {
+        mode: DbMode::Update,
+        max_age_days: 7,
+        strict: false,
+    };
+    let db = AdvisoryDb::open(NoAdvisories, "test", database_path(&manifest), options).unwrap();
+    for dependency in &dependencies {
+        block_on(db.advisories_for(&dependency.name, &Version::new(1, 0, 0))).unwrap();
+    }
+    db.save().unwrap();
+    (dir, manifest)
+}
+
+fn status(dir: &Path) -> Value {
+    let output = common::cargo_sane(dir, &["cache", "status", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn test_status_lists_populated_caches() {
+    let (dir, _) = populated();
+    // Project records aren't caches
+    fs::write(dir.path().join(STATE_DIR).join("snoozed.toml"), "").unwrap();
+
+    let status = status(dir.path());
+    let caches: Vec<(&str, &str, u64)> = status["caches"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| {
+            (
+                c["kind"].as_str().unwrap(),
+                c["path"].as_str().unwrap(),
+                c["entries"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        caches,
+        [
+            ("registry", "cache.json", 1),
+            ("advisories", "advisory-db.json", 2)
+        ]
+    );
+    assert!(status["total_size"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn test_clear_selects_by_kind() {
+    let (dir, manifest) = populated();
+    let state = dir.path().join(STATE_DIR);
+    fs::write(state.join("snoozed.toml"), "").unwrap();
+
+    commands::cache_clear_command(manifest_arg(dir.path()), vec![CacheKind::Advisories]).unwrap();
+    assert!(!database_path(&manifest).exists());
+    assert!(state.join("cache.json").exists());
+
+    commands::cache_clear_command(manifest_arg(dir.path()), CacheKind::ALL.to_vec()).unwrap();
+    assert!(CacheStore::for_manifest(&manifest)
+        .files()
+        .unwrap()
+        .is_empty());
+    assert!(state.join("snoozed.toml").exists());
+}
+
+#[test]
+fn test_gc_drops_expired_then_oldest_over_budget() {
+    let (_dir, manifest) = populated();
+    let store = CacheStore::for_manifest(&manifest);
+    let now = now();
+
+    // The check cache lives for the configured TTL; the advisory database
+    // stays until replaced
+    let report = store.gc(60, None, now + 2 * HOUR, true).unwrap();
+    let expired: Vec<&str> = report.expired.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(expired, ["cache.json"]);
+    assert!(report.over_budget.is_empty());
+    assert_eq!(store.files().unwrap().len(), 2, "dry run removed files");
+
+    // Within the TTL, only the budget prunes
+    let report = store.gc(60, Some(1), now, false).unwrap();
+    assert!(report.expired.is_empty());
+    assert_eq!(report.over_budget.len(), 2);
+    assert_eq!(report.remaining_size, 0);
+    assert!(store.files().unwrap().is_empty());
+}
+
+#[test]
+fn test_corrupt_cache_is_quarantined() {
+    let (dir, manifest) = populated();
+    let state = dir.path().join(STATE_DIR);
+    fs::write(state.join("cache.json"), "{\"key\": \"trunc").unwrap();
+
+    // A command reading it carries on without the cache
+    let cache = ReportCache::for_manifest(&manifest, 60);
+    assert!(cache
+        .load::<Value>(&cache::manifest_key(&manifest))
+        .is_none());
+    assert!(!state.join("cache.json").exists());
+    assert!(state.join("cache.json.corrupt").exists());
+
+    // Status finds corrupt files of its own accord too
+    fs::write(database_path(&manifest), [0xff, 0xfe, 0x00]).unwrap();
+    let status = status(dir.path());
+    let corrupt: Vec<&str> = status["caches"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|c| c["corrupt"] == true)
+        .map(|c| c["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(corrupt, ["cache.json.corrupt", "advisory-db.json.corrupt"]);
+
+    // gc always drops them, and the cache is rebuilt as usual
+    let report = CacheStore::for_manifest(&manifest)
+        .gc(60, None, now(), false)
+        .unwrap();
+    assert_eq!(report.corrupt.len(), 2);
+    cache.store(&cache::manifest_key(&manifest), &1).unwrap();
+    assert!(cache.load::<u32>(&cache::manifest_key(&manifest)).is_some());
+}
+
+#[test]
+fn test_check_cache_is_invalidated_when_its_inputs_change() {
+    let (dir, manifest) = populated();
+    let cache = ReportCache::for_manifest(&manifest, 60);
+    cache.store(&cache::check_key(&manifest), &1).unwrap();
+    assert!(cache.load::<u32>(&cache::check_key(&manifest)).is_some());
+
+    // The database gains an advisory against a cached dependency
+    let options = DbOptions {
+        mode: DbMode::Update,
+        max_age_days: 7,
+        strict: false,
+    };
+    let db = AdvisoryDb::open(SerdeAdvisory, "test", database_path(&manifest), options).unwrap();
+    block_on(db.advisories_for("serde", &Version::new(1, 0, 0))).unwrap();
+    db.save().unwrap();
+    assert!(cache.load::<u32>(&cache::check_key(&manifest)).is_none());
+
+    // So does a changed Cargo.lock
+    cache.store(&cache::check_key(&manifest), &1).unwrap();
+    fs::write(dir.path().join("Cargo.lock"), "version = 4\n").unwrap();
+    assert!(cache.load::<u32>(&cache::check_key(&manifest)).is_none());
+}
@@ -1 +1,770 @@
+mod common;
 
+use cargo_sane::analyzer::checker::{git_dependencies, DependencyChecker};
+use cargo_sane::analyzer::lock_mismatch::find_lock_mismatches;
+use cargo_sane::analyzer::workflows::{check_pins, find_workflow_pins};
+use cargo_sane::cli::commands::{self, CheckOptions};
+use cargo_sane::cli::output::OutputFormat;
+use cargo_sane::core::deny::{DenyPolicy, DenyViolation};
+use cargo_sane::core::dependency::SkipCause;
+use cargo_sane::core::license::{LicenseChange, LicensePolicy};
+use cargo_sane::core::lockfile::Lockfile;
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::core::policy::{PolicyStatus, VersionPolicy};
+use cargo_sane::utils::advisories::OsvClient;
+use cargo_sane::utils::advisory_db::AdvisoryIndex;
+use cargo_sane::utils::cancel::Cancellation;
+use cargo_sane::utils::crates_io::CratesIoClient;
+use cargo_sane::utils::progress::CapturedProgress;
+use common::{block_on, MockRegistry};
+use semver::Version;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn test_concurrent_checks_respect_limit() {
+    let crates: Vec<(String, String)> = (0..12)
+        .map(|i| (format!("crate{:02}", i), "2.0.0".to_string()))
+        .collect();
+    let latest: Vec<(&str, &str)> = crates
+        .iter()
+        .map(|(n, v)| (n.as_str(), v.as_str()))
+        .collect();
+    let registry = MockRegistry::start(&latest, Duration::from_millis(100));
+
+    let lines: String = crates
+        .iter()
+        .map(|(name, _)| format!("{} = \"1.0\"\n", name))
+        .collect();
+    let project = common::project(&lines);
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    )
+    .with_concurrency(3);
+    let deps = block_on(checker.check_dependencies(&manifest)).unwrap();
+
+    assert_eq!(registry.requests(), 12);
+    assert!(registry.max_in_flight() <= 3, "limit exceeded");
+    assert!(registry.max_in_flight() > 1, "requests were not concurrent");
+    assert_eq!(deps.len(), 12);
+}
+
+#[test]
+fn test_results_keep_deterministic_order() {
+    let registry = MockRegistry::start(
+        &[("zeta", "1.2.0"), ("alpha", "0.3.1"), ("mid", "5.0.0")],
+        Duration::from_millis(10),
+    );
+    let project =
+        common::project("zeta = \"1.0\"\nmid = \"5\"\nalpha = \"0.3\"\nmissing = \"1\"\n");
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+
+    let progress = Arc::new(CapturedProgress::default());
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    )
+    .with_concurrency(4)
+    .with_progress(progress.clone());
+    let deps = block_on(checker.check_dependencies(&manifest)).unwrap();
+
+    let events = progress.events();
+    assert_eq!(
+        events.first().map(String::as_str),
+        Some("start 4 Checking crates.io")
+    );
+    assert_eq!(events.iter().filter(|e| *e == "inc 1").count(), 4);
+    assert_eq!(events.iter().filter(|e| e.starts_with("warn ")).count(), 1);
+    assert_eq!(events.last().map(String::as_str), Some("finish"));
+
+    let names: Vec<&str> = deps.iter().map(|d| d.name.as_str()).collect();
+    assert_eq!(names, vec!["alpha", "mid", "missing", "zeta"]);
+    assert_eq!(deps[0].latest_version, Some(Version::new(0, 3, 1)));
+    assert_eq!(deps[1].latest_version, Some(Version::new(5, 0, 0)));
+    assert_eq!(deps[2].latest_version, None);
+    assert_eq!(deps[3].latest_version, Some(Version::new(1, 2, 0)));
+}
+
+#[test]
+fn test_yanked_releases_are_not_suggested() {
+    let registry = MockRegistry::with_releases(
+        &[(
+            "retracted",
+            vec![("1.4.2", true), ("1.4.1", false), ("1.0.0", false)],
+        )],
+        Duration::from_millis(0),
+    );
+    let project = common::project("retracted = \"1.0\"\n");
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    );
+    let deps = block_on(checker.check_dependencies(&manifest)).unwrap();
+
+    assert_eq!(deps[0].latest_version, Some(Version::new(1, 4, 1)));
+    assert_eq!(
+        deps[0].skip_note().as_deref(),
+        Some("1.4.2 skipped: yanked; suggesting 1.4.1")
+    );
+}
+
+#[test]
+fn test_versions_file_overrides_update_targets() {
+    let registry = MockRegistry::with_releases(
+        &[
+            (
+                "tokio",
+                vec![("1.40.0", false), ("1.36.3", false), ("1.30.0", false)],
+            ),
+            ("serde", vec![("1.0.210", false), ("1.0.197", false)]),
+            ("rand", vec![("0.9.0", false), ("0.8.5", false)]),
+        ],
+        Duration::from_millis(0),
+    );
+    let project = common::project("tokio = \"1.30\"\nserde = \"1.0.210\"\nrand = \"0.8.5\"\n");
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+    let policy = VersionPolicy::parse("tokio = \"~1.36\"\nserde = \"=1.0.197\"\n", false).unwrap();
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    )
+    .with_policy(policy);
+    let deps = block_on(checker.check_dependencies(&manifest)).unwrap();
+    let dep = |name: &str| deps.iter().find(|d| d.name == name).unwrap();
+
+    let tokio = dep("tokio");
+    assert_eq!(tokio.latest_version, Some(Version::new(1, 36, 3)));
+    assert_eq!(
+        tokio.policy.as_ref().unwrap().status,
+        PolicyStatus::OffPolicy
+    );
+
+    let serde = dep("serde");
+    assert!(serde.is_off_policy());
+    assert!(matches!(
+        serde.policy.as_ref().unwrap().status,
+        PolicyStatus::Conflict(_)
+    ));
+    assert!(!serde.has_update());
+
+    let rand = dep("rand");
+    assert!(rand.policy.is_none());
+    assert_eq!(rand.latest_version, Some(Version::new(0, 9, 0)));
+}
+
+#[test]
+fn test_path_dependencies_compared_with_registry() {
+    let registry = MockRegistry::start(&[("internal-core", "0.5.0")], Duration::from_millis(0));
+    let project = common::project(
+        "core = { package = \"internal-core\", path = \"crates/core\" }\nscratch = { path = \"crates/scratch\" }\n",
+    );
+    for (dir, name, version) in [
+        ("crates/core", "internal-core", "0.4.2"),
+        ("crates/scratch", "scratch", "0.1.0"),
+    ] {
+        let dir = project.path().join(dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"{}\"\n",
+                name, version
+            ),
+        )
+        .unwrap();
+    }
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    );
+    let found = block_on(checker.check_path_dependencies(&manifest));
+
+    assert_eq!(found.len(), 1, "unpublished path crates are skipped");
+    assert_eq!(found[0].name, "core");
+    assert_eq!(found[0].package, "internal-core");
+    assert_eq!(found[0].path, std::path::PathBuf::from("crates/core"));
+    assert_eq!(found[0].local_version, Version::new(0, 4, 2));
+    assert_eq!(found[0].published_version, Version::new(0, 5, 0));
+    assert!(found[0].is_stale());
+}
+
+#[test]
+fn test_git_forks_trailing_crates_io_are_flagged() {
+    let registry = MockRegistry::start(
+        &[
+            ("serde", "1.0.200"),
+            ("serde_json", "1.0.100"),
+            ("ours", "2.0.0"),
+        ],
+        Duration::from_millis(0),
+    );
+    let project = common::project(
+        "serde = { git = \"https://github.com/me/serde\", branch = \"main\" }\n\
+         json = { package = \"serde_json\", git = \"https://github.com/me/json\" }\n\
+         ours = { git = \"https://github.com/me/ours\" }\n",
+    );
+    std::fs::write(
+        project.path().join("Cargo.lock"),
+        "version = 3\n\n\
+         [[package]]\nname = \"serde\"\nversion = \"1.0.100\"\nsource = \"git+https://github.com/me/serde?branch=main#0123abcd\"\n\n\
+         [[package]]\nname = \"serde_json\"\nversion = \"1.0.100\"\nsource = \"git+https://github.com/me/json#4567ef01\"\n\n\
+         [[package]]\nname = \"ours\"\nversion = \"1.0.0\"\nsource = \"git+https://github.com/me/ours#89abcdef\"\n",
+    )
+    .unwrap();
+    let database = project.path().join("advisory-db.json");
+    let advisory = |id: &str, patched: &str| {
+        serde_json::json!({
+            "id": id,
+            "package": "serde",
+            "title": "Flaw",
+            "severity": "high",
+            "cvss": null,
+            "patched_versions": [patched],
+            "informational": null,
+            "url": "",
+        })
+    };
+    std::fs::write(
+        &database,
+        serde_json::json!({
+            "source": "test",
+            "fetched_at": null,
+            "entries": {
+                "serde@1.0.100": [
+                    advisory("RUSTSEC-0000-0001", ">=1.0.150"),
+                    advisory("RUSTSEC-0000-0002", ">=9.0.0"),
+                ],
+            },
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+    let lockfile = Lockfile::for_manifest(&manifest).unwrap();
+    let git = git_dependencies(&manifest, lockfile.as_ref());
+    let json = git.iter().find(|d| d.name == "json").unwrap();
+    assert_eq!(json.package.as_deref(), Some("serde_json"));
+    assert_eq!(json.locked_version, Some(Version::new(1, 0, 100)));
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    )
+    .with_advisories(AdvisoryIndex::load(&database))
+    .with_intentional_forks(vec!["ours".to_string()]);
+    let forks = block_on(checker.check_forks(&git));
+
+    // The renamed fork is current and `ours` is marked intentional
+    assert_eq!(forks.len(), 1);
+    let serde = &forks[0];
+    assert_eq!(serde.name, "serde");
+    assert_eq!(serde.based_on, Version::new(1, 0, 100));
+    assert_eq!(serde.upstream, Version::new(1, 0, 200));
+    // Only the advisory the release is patched for counts as fixed upstream
+    assert_eq!(serde.fixed_upstream, ["RUSTSEC-0000-0001"]);
+    assert_eq!(
+        serde.summary(),
+        "fork is based on 1.0.100; upstream is at 1.0.200 (includes RUSTSEC-0000-0001 fix)"
+    );
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    );
+    let names: Vec<String> = block_on(checker.check_forks(&git))
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    assert_eq!(names, ["ours", "serde"]);
+}
+
+#[test]
+fn test_prerelease_and_build_metadata_targets() {
+    let registry = MockRegistry::with_releases(
+        &[
+            (
+                "beta",
+                vec![
+                    ("0.13.0-alpha.1", false),
+                    ("0.12.0-rc.1", false),
+                    ("0.12.0-beta.1", false),
+                    ("0.11.0", false),
+                ],
+            ),
+            ("stable", vec![("1.1.0-beta.1", false), ("1.0.0", false)]),
+            (
+                "sys",
+                vec![("0.16.2+1.7.2", false), ("0.16.1+1.7.1", false)],
+            ),
+        ],
+        Duration::ZERO,
+    );
+    let project =
+        common::project("beta = \"=0.12.0-beta.1\"\nstable = \"1.0\"\nsys = \"0.16.2+1.7.1\"\n");
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+    let check = |pre: bool| {
+        let checker = DependencyChecker::with_provider(
+            CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+        )
+        .with_prereleases(pre);
+        block_on(checker.check_dependencies(&manifest))
+            .unwrap()
+            .into_iter()
+            .map(|d| {
+                let update = d
+                    .has_update()
+                    .then(|| d.latest_version.unwrap().to_string());
+                (d.name, d.current_version.to_string(), update)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let some = |v: &str| Some(v.to_string());
+    assert_eq!(
+        check(false),
+        vec![
+            // Along its own prerelease, not onto the next version's alpha
+            (
+                "beta".to_string(),
+                "0.12.0-beta.1".to_string(),
+                some("0.12.0-rc.1")
+            ),
+            // Stable stays stable without --pre
+            ("stable".to_string(), "1.0.0".to_string(), None),
+            // Only the build metadata differs: the same release
+            ("sys".to_string(), "0.16.2".to_string(), None),
+        ]
+    );
+    assert_eq!(
+        check(true)
+            .into_iter()
+            .map(|(_, _, update)| update)
+            .collect::<Vec<_>>(),
+        vec![some("0.13.0-alpha.1"), some("1.1.0-beta.1"), None]
+    );
+}
+
+#[test]
+fn test_single_crate_check() {
+    let project = common::project("serde = \"1\"\nlocal = { path = \"local\" }\n");
+    let check = |name: &str| {
+        commands::check_command(
+            Some(project.path().join("Cargo.toml").display().to_string()),
+            CheckOptions {
+                format: OutputFormat::Json,
+                crate_name: Some(name.to_string()),
+                ..CheckOptions::default()
+            },
+        )
+    };
+
+    // Path dependencies need no registry lookup
+    assert!(check("local").unwrap());
+
+    let error = check("serd").unwrap_err().to_string();
+    assert!(
+        error.starts_with("serd is not a dependency in"),
+        "{}",
+        error
+    );
+    assert!(error.ends_with("did you mean serde?"), "{}", error);
+}
+
+#[test]
+fn test_skipped_dependencies_are_explained() {
+    let registry = MockRegistry::with_releases(
+        &[
+            ("serde", vec![("1.0.210", false)]),
+            ("tokio", vec![("1.40.0", false), ("1.36.3", false)]),
+            ("noisy", vec![("2.0.0", false)]),
+        ],
+        Duration::from_millis(0),
+    );
+    let project = common::project(
+        "serde = \"1.0\"\ntokio = \"1.36\"\nnoisy = \"1\"\nmissing = \"1\"\nwild = \"*\"\n\
+         inherited = { workspace = true }\nforked = { git = \"https://github.com/ourorg/forked\" }\n\
+         local = { path = \"local\" }\n",
+    );
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+    let policy = VersionPolicy::parse("tokio = \"~1.36\"\n", false).unwrap();
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    )
+    .with_policy(policy)
+    .with_ignored(vec!["noisy".to_string()]);
+    let report = block_on(checker.check(&manifest)).unwrap();
+
+    let skipped: Vec<(&str, &str)> = report
+        .skipped
+        .iter()
+        .map(|s| (s.name.as_str(), s.reason.code()))
+        .collect();
+    assert_eq!(
+        skipped,
+        vec![
+            ("forked", "git"),
+            ("inherited", "parse-failure"),
+            ("local", "path"),
+            ("missing", "registry-error"),
+            ("noisy", "ignored"),
+            ("tokio", "policy-held"),
+            ("wild", "parse-failure"),
+        ]
+    );
+    let tokio = report.skipped.iter().find(|s| s.name == "tokio").unwrap();
+    assert_eq!(
+        tokio.detail.as_deref(),
+        Some("held at 1.36.3; 1.40.0 is the newest release")
+    );
+
+    // Failed lookups keep their row; ignored crates have none
+    let rows: Vec<&str> = report
+        .dependencies
+        .iter()
+        .map(|d| d.name.as_str())
+        .collect();
+    assert_eq!(rows, vec!["missing", "serde", "tokio"]);
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["skipped"][3]["reason"], "registry-error");
+}
+
+#[test]
+fn test_lockfile_behind_the_manifest_is_flagged() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/lock-mismatch/Cargo.toml");
+    let manifest = Manifest::from_path(&path).unwrap();
+    let lockfile = Lockfile::for_manifest(&manifest).unwrap().unwrap();
+
+    let mismatches = find_lock_mismatches(&manifest, &lockfile);
+    assert_eq!(mismatches.len(), 1);
+    let tokio = &mismatches[0];
+    assert_eq!(tokio.name, "tokio");
+    assert_eq!(tokio.requirement, "2");
+    assert_eq!(tokio.locked, vec![Version::new(1, 38, 0)]);
+    assert_eq!(tokio.command, "cargo update -p tokio");
+    assert_eq!(tokio.line, Some(8));
+}
+
+#[test]
+fn test_workflow_pins_are_checked_from_a_member() {
+    let registry = MockRegistry::start(
+        &[("cargo-deny", "0.16.1"), ("cargo-nextest", "0.9.70")],
+        Duration::ZERO,
+    );
+    let workspace = common::workspace(&[("app", "")]);
+    let workflows = workspace.path().join(".github/workflows");
+    std::fs::create_dir_all(&workflows).unwrap();
+    std::fs::write(
+        workflows.join("ci.yml"),
+        "jobs:\n  lint:\n    steps:\n      - run: cargo install cargo-deny --version 0.14.0\n      - run: cargo install cargo-nextest --version 0.9.70 --locked\n",
+    )
+    .unwrap();
+    std::fs::write(
+        workflows.join("release.yaml"),
+        "jobs:\n  dist:\n    steps:\n      - run: cargo install cargo-deny --version=0.14.0\n",
+    )
+    .unwrap();
+
+    let mut pins = find_workflow_pins(&workspace.path().join("app"));
+    let client = CratesIoClient::with_base_url(&registry.base_url).unwrap();
+    block_on(check_pins(&client, &mut pins, 4));
+
+    let outdated: Vec<(&str, String, usize)> = pins
+        .iter()
+        .filter(|pin| pin.is_outdated())
+        .map(|pin| (pin.name.as_str(), pin.file.display().to_string(), pin.line))
+        .collect();
+    assert_eq!(
+        outdated,
+        [
+            ("cargo-deny", ".github/workflows/ci.yml".to_string(), 4),
+            (
+                "cargo-deny",
+                ".github/workflows/release.yaml".to_string(),
+                4
+            ),
+        ]
+    );
+    assert_eq!(pins.len(), 3);
+    // Each crate is looked up once however often it's pinned
+    assert_eq!(registry.requests_for("cargo-deny"), 1);
+}
+
+#[test]
+fn test_relicensed_updates_are_flagged() {
+    let registry = MockRegistry::with_licenses(
+        &[
+            ("vault", vec![("1.5.0", "BUSL-1.1"), ("1.4.0", "MIT")]),
+            (
+                "dual",
+                vec![("2.1.0", "Apache-2.0 OR MIT"), ("2.0.0", "MIT/Apache-2.0")],
+            ),
+            ("gpl", vec![("0.3.0", "GPL-3.0-only"), ("0.2.0", "MIT")]),
+        ],
+        Duration::ZERO,
+    );
+    let project = common::project("vault = \"1.4\"\ndual = \"2.0\"\ngpl = \"0.2\"\n");
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+    let licenses = LicensePolicy {
+        allow: Vec::new(),
+        deny: vec!["BUSL-1.1".to_string()],
+    };
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    )
+    .with_licenses(licenses);
+    let report = block_on(checker.check(&manifest)).unwrap();
+
+    let changes: Vec<(&str, Option<&LicenseChange>)> = report
+        .dependencies
+        .iter()
+        .map(|d| (d.name.as_str(), d.license_change.as_ref()))
+        .collect();
+    let change = |from: &str, to: &str, denied| LicenseChange {
+        from: from.to_string(),
+        to: to.to_string(),
+        denied,
+    };
+    assert_eq!(
+        changes,
+        [
+            ("dual", None),
+            ("gpl", Some(&change("MIT", "GPL-3.0-only", false))),
+            ("vault", Some(&change("MIT", "BUSL-1.1", true))),
+        ]
+    );
+
+    let json = serde_json::to_value(&report).unwrap();
+    let vault = &json["dependencies"][2]["license_change"];
+    assert_eq!(vault["from"], "MIT");
+    assert_eq!(vault["to"], "BUSL-1.1");
+    assert_eq!(vault["denied"], true);
+}
+
+#[test]
+fn test_updates_deny_toml_refuses_are_flagged() {
+    let registry = MockRegistry::with_licenses(
+        &[
+            ("vault", vec![("1.5.0", "MIT"), ("1.4.0", "MIT")]),
+            ("legacy", vec![("1.2.0", "MIT"), ("1.0.0", "MIT")]),
+            ("gpl", vec![("0.3.0", "GPL-3.0-only"), ("0.2.0", "MIT")]),
+            (
+                "dual",
+                vec![("2.1.0", "Apache-2.0 OR MIT"), ("2.0.0", "MIT/Apache-2.0")],
+            ),
+        ],
+        Duration::ZERO,
+    );
+    let project =
+        common::project("vault = \"1.4\"\nlegacy = \"1.0\"\ngpl = \"0.2\"\ndual = \"2.0\"\n");
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+    let fixture = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/deny");
+    let deny = DenyPolicy::load(&fixture).unwrap().unwrap();
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    )
+    .with_deny(deny);
+    let report = block_on(checker.check(&manifest)).unwrap();
+
+    let violations: Vec<(&str, Option<&DenyViolation>)> = report
+        .dependencies
+        .iter()
+        .map(|d| (d.name.as_str(), d.deny.as_ref()))
+        .collect();
+    let banned = |ban: &str, reason: Option<&str>| DenyViolation::Banned {
+        ban: ban.to_string(),
+        reason: reason.map(str::to_string),
+    };
+    assert_eq!(
+        violations,
+        [
+            ("dual", None),
+            (
+                "gpl",
+                Some(&DenyViolation::License {
+                    license: "GPL-3.0-only".to_string()
+                })
+            ),
+            (
+                "legacy",
+                Some(&banned("legacy@<2", Some("use the 2.x rewrite")))
+            ),
+            ("vault", Some(&banned("vault@>=1.5", None))),
+        ]
+    );
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["dependencies"][1]["deny"]["kind"], "license");
+    assert_eq!(json["dependencies"][3]["deny"]["ban"], "vault@>=1.5");
+    assert!(json["dependencies"][0].get("deny").is_none());
+}
+
+#[test]
+fn test_client_setup_failure_is_an_error() {
+    // A misconfigured client fails where it's built, with what went wrong,
+    // rather than panicking inside the checkers
+    let error = CratesIoClient::with_base_url("crates.io/api/v1")
+        .err()
+        .expect("a URL without a scheme is rejected");
+    assert_eq!(
+        format!("{:#}", error),
+        "Invalid crates.io API URL 'crates.io/api/v1': relative URL without a base"
+    );
+    let error = OsvClient::with_base_url("").err().unwrap();
+    assert!(
+        error.to_string().starts_with("Invalid advisory API URL"),
+        "{}",
+        error
+    );
+
+    // A proxy cargo couldn't use either fails the command that needs the
+    // registry, saying what was being set up
+    let project = common::project("serde = \"1.0\"\n");
+    let output = assert_cmd::Command::cargo_bin("cargo-sane")
+        .unwrap()
+        .args(["sane", "check", "--manifest-path"])
+        .arg(project.path().join("Cargo.toml"))
+        .env("CARGO_HTTP_PROXY", "http://[::1")
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("building the crates.io client")
+            && stderr.contains("Invalid proxy 'http://[::1' in CARGO_HTTP_PROXY"),
+        "{}",
+        stderr
+    );
+
+    // Given a working provider, construction itself can't fail
+    let registry = MockRegistry::start(&[("serde", "1.0.200")], Duration::from_millis(0));
+    let project = common::project("serde = \"1.0\"\n");
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    );
+    assert_eq!(
+        block_on(checker.check_dependencies(&manifest))
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn test_timeout_leaves_partial_results() {
+    let crates: Vec<(String, String)> = (0..6)
+        .map(|i| (format!("crate{}", i), "2.0.0".to_string()))
+        .collect();
+    let latest: Vec<(&str, &str)> = crates
+        .iter()
+        .map(|(n, v)| (n.as_str(), v.as_str()))
+        .collect();
+    let registry = MockRegistry::start(&latest, Duration::from_millis(150));
+    let lines: String = crates
+        .iter()
+        .map(|(name, _)| format!("{} = \"1.0\"\n", name))
+        .collect();
+    let project = common::project(&lines);
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+
+    // One lookup at a time, so the deadline falls between them
+    let cancellation = Cancellation::with_deadline(Duration::from_millis(250));
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url)
+            .unwrap()
+            .with_cancellation(cancellation.clone()),
+    )
+    .with_concurrency(1);
+    let report = block_on(checker.check(&manifest)).unwrap();
+
+    assert!(cancellation.cut_short());
+    assert!(report.partial);
+    assert!(
+        report.unchecked > 0 && report.unchecked < 6,
+        "{}",
+        report.unchecked
+    );
+    // Lookups in flight at the deadline still finish and count
+    assert_eq!(registry.requests(), 6 - report.unchecked);
+    let checked = report
+        .dependencies
+        .iter()
+        .filter(|dep| dep.latest_version.is_some())
+        .count();
+    assert_eq!(checked, registry.requests());
+    // Every dependency keeps its row, the unchecked ones saying why
+    assert_eq!(report.dependencies.len(), 6);
+    let timed_out = report
+        .skipped
+        .iter()
+        .filter(|s| s.reason == SkipCause::TimedOut)
+        .count();
+    assert_eq!(timed_out, report.unchecked);
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["partial"], true);
+    assert_eq!(json["unchecked"], report.unchecked);
+}
+
+#[test]
+fn test_advisory_without_a_patched_release_leaves_the_crate_vulnerable() {
+    let registry = MockRegistry::with_releases(
+        &[
+            ("abandoned", vec![("1.2.0", false), ("1.1.0", false)]),
+            ("fixed", vec![("2.0.1", false), ("2.0.0", false)]),
+        ],
+        Duration::ZERO,
+    );
+    let project = common::project("abandoned = \"1.2.0\"\nfixed = \"2.0.0\"\n");
+    let database = project.path().join("advisory-db.json");
+    let advisory = |id: &str, package: &str, patched: &[&str]| {
+        serde_json::json!({
+            "id": id,
+            "package": package,
+            "title": "Flaw",
+            "severity": "high",
+            "cvss": null,
+            "patched_versions": patched,
+            "informational": null,
+            "url": "",
+        })
+    };
+    std::fs::write(
+        &database,
+        serde_json::json!({
+            "source": "test",
+            "fetched_at": null,
+            "entries": {
+                "abandoned@1.2.0": [advisory("RUSTSEC-0000-0003", "abandoned", &[])],
+                "fixed@2.0.0": [advisory("RUSTSEC-0000-0004", "fixed", &[">=2.0.1"])],
+            },
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    )
+    .with_advisories(AdvisoryIndex::load(&database));
+    let deps = block_on(checker.check_dependencies(&manifest)).unwrap();
+
+    // Every release is affected: no target, and not up to date either
+    let abandoned = deps.iter().find(|d| d.name == "abandoned").unwrap();
+    assert_eq!(abandoned.latest_version, None);
+    assert!(abandoned.is_unpatched());
+    assert_eq!(abandoned.unpatched_advisories, ["RUSTSEC-0000-0003"]);
+
+    // A patched release is an update, not a dead end
+    let fixed = deps.iter().find(|d| d.name == "fixed").unwrap();
+    assert_eq!(fixed.latest_version, Some(Version::new(2, 0, 1)));
+    assert!(!fixed.is_unpatched());
+}
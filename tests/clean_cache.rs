@@ -0,0 +1,159 @@
+mod common;
+
+use cargo_sane::analyzer::usage::{find_unused_dependencies, workspace_usage};
+use cargo_sane::analyzer::usage_cache::{ScanStats, UsageCache, USAGE_DIR};
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::core::workspace::Workspace;
+use cargo_sane::utils::cache::{quarantine_path, STATE_DIR};
+use cargo_sane::utils::files::{collect_rust_files, WalkOptions};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// A package using serde and regex across a few files, with anyhow unused
+fn package() -> tempfile::TempDir {
+    let dir = common::project("serde = \"1\"\nregex = \"1\"\nanyhow = \"1\"\n");
+    let write = |path: &str, content: &str| {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    };
+    write("src/lib.rs", "mod parse;\nmod model;\n");
+    write("src/parse.rs", "use regex::Regex;\n");
+    write(
+        "src/model.rs",
+        "#[derive(serde::Serialize)]\npub struct M;\n",
+    );
+    write("tests/it.rs", "#[test]\nfn t() {}\n");
+    dir
+}
+
+/// The names `clean` would remove, and how the files were scanned
+fn unused(dir: &Path, cached: bool) -> (Vec<String>, ScanStats) {
+    let manifest = Manifest::from_path(&dir.join("Cargo.toml")).unwrap();
+    let files = collect_rust_files(dir, &WalkOptions::default()).unwrap();
+    let cache = UsageCache::for_manifest(&manifest, cached);
+    let unused = find_unused_dependencies(&manifest, &files, &cache).unwrap();
+    cache.save().unwrap();
+    (unused.into_iter().map(|u| u.name).collect(), cache.stats())
+}
+
+fn scanned(parsed: usize, reused: usize) -> ScanStats {
+    ScanStats { parsed, reused }
+}
+
+/// The `clean --json` report, without its timestamp
+fn clean_json(dir: &Path, extra: &[&str]) -> Value {
+    let output = common::cargo_sane(dir, &["clean", "--dry-run", "--json"])
+        .args(extra)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stderr.is_empty(), "{:?}", output);
+    let mut report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    report.as_object_mut().unwrap().remove("generated_at");
+    report
+}
+
+#[test]
+fn test_only_changed_files_are_scanned_again() {
+    let dir = package();
+    let (first, stats) = unused(dir.path(), true);
+    assert_eq!(first, ["anyhow"]);
+    assert_eq!(stats, scanned(4, 0));
+
+    let (second, stats) = unused(dir.path(), true);
+    assert_eq!(second, first);
+    assert_eq!(stats, scanned(0, 4));
+
+    fs::write(
+        dir.path().join("tests/it.rs"),
+        "#[test]\nfn t() -> anyhow::Result<()> { Ok(()) }\n",
+    )
+    .unwrap();
+    let (warm, stats) = unused(dir.path(), true);
+    assert_eq!(stats, scanned(1, 3));
+    let (cold, stats) = unused(dir.path(), false);
+    assert_eq!(stats, scanned(4, 0));
+    assert!(warm.is_empty());
+    assert_eq!(warm, cold);
+
+    // Dropping a dependency from the manifest needs no new scan
+    let manifest = dir.path().join("Cargo.toml");
+    let text = fs::read_to_string(&manifest).unwrap();
+    fs::write(&manifest, text.replace("regex = \"1\"\n", "")).unwrap();
+    let (_, stats) = unused(dir.path(), true);
+    assert_eq!(stats.parsed, 0);
+}
+
+#[test]
+fn test_cached_and_full_scans_print_the_same_report() {
+    let dir = package();
+    let cold = clean_json(dir.path(), &["--no-cache"]);
+    assert!(!dir.path().join(STATE_DIR).join(USAGE_DIR).exists());
+
+    clean_json(dir.path(), &[]);
+    fs::write(dir.path().join("src/parse.rs"), "pub fn parse() {}\n").unwrap();
+    let warm = clean_json(dir.path(), &[]);
+    assert_ne!(warm, cold);
+    assert_eq!(warm, clean_json(dir.path(), &["--no-cache"]));
+}
+
+#[test]
+fn test_corrupt_cache_is_set_aside_and_rebuilt() {
+    let dir = package();
+    let cold = clean_json(dir.path(), &[]);
+    let cache_file = dir
+        .path()
+        .join(STATE_DIR)
+        .join(USAGE_DIR)
+        .join("files.json");
+    fs::write(&cache_file, "{\"version\": \"0.0.0\", \"files\": ").unwrap();
+
+    let output = common::cargo_sane(dir.path(), &["clean", "--dry-run", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(common::stderr(&output).contains("Set aside the corrupt cache file"));
+    let mut report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    report.as_object_mut().unwrap().remove("generated_at");
+    assert_eq!(report, cold);
+    assert!(quarantine_path(&cache_file).exists());
+
+    let (_, stats) = unused(dir.path(), true);
+    assert_eq!(stats, scanned(0, 4));
+}
+
+#[test]
+fn test_workspace_members_share_the_cache() {
+    let dir = common::workspace(&[("a", "serde = \"1\"\n"), ("b", "log = \"0.4\"\n")]);
+    for (member, source) in [("a", "use serde::Serialize;\n"), ("b", "fn f() {}\n")] {
+        let src = dir.path().join("crates").join(member).join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("lib.rs"), source).unwrap();
+    }
+
+    let scan = || {
+        let manifest = Manifest::from_path(&dir.path().join("Cargo.toml")).unwrap();
+        let cache = UsageCache::for_manifest(&manifest, true);
+        let workspace = Workspace::load(manifest).unwrap().unwrap();
+        let members: Vec<_> = workspace
+            .members
+            .iter()
+            .map(|member| {
+                let root = member.path.parent().unwrap();
+                let files = collect_rust_files(root, &WalkOptions::default()).unwrap();
+                (Workspace::member_name(member), member, files)
+            })
+            .collect();
+        let usage = workspace_usage(&members, &cache).unwrap();
+        cache.save().unwrap();
+        let unused: Vec<String> = usage.crates.into_iter().map(|c| c.name).collect();
+        (unused, cache.stats())
+    };
+
+    let (cold, stats) = scan();
+    assert_eq!(cold, ["log"]);
+    assert_eq!(stats, scanned(2, 0));
+    assert_eq!(scan(), (cold, scanned(0, 2)));
+}
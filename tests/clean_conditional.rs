@@ -0,0 +1,103 @@
+mod common;
+
+use cargo_sane::analyzer::cfg_usage::{find_conditionally_used, Gating};
+use cargo_sane::analyzer::usage_cache::UsageCache;
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::utils::files::{collect_rust_files, WalkOptions};
+use common::copy_fixture;
+use std::fs;
+use std::path::Path;
+
+/// `(name, gating, conditions)` of each conditionally used dependency
+fn conditional(dir: &Path) -> Vec<(String, Gating, Vec<String>)> {
+    let manifest = Manifest::from_path(&dir.join("Cargo.toml")).unwrap();
+    let files = collect_rust_files(dir, &WalkOptions::default()).unwrap();
+    let mut found: Vec<_> = find_conditionally_used(&manifest, &files, &UsageCache::disabled(dir))
+        .unwrap()
+        .into_iter()
+        .map(|c| (c.name, c.gating, c.conditions))
+        .collect();
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    found
+}
+
+fn clean(dir: &Path, json: bool) -> std::process::Output {
+    let mut command = common::cargo_sane(dir, &["clean", "--dry-run", "--no-cache"]);
+    if json {
+        command.arg("--json");
+    }
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    output
+}
+
+#[test]
+fn test_dependencies_behind_non_default_features() {
+    let dir = copy_fixture("cfg-non-default");
+    let metrics = vec!["feature = \"metrics\"".to_string()];
+    // regex is behind the default `std` feature and libc behind `unix`,
+    // which may well hold
+    assert_eq!(
+        conditional(dir.path()),
+        [
+            (
+                "hdrhistogram".to_string(),
+                Gating::NonDefault,
+                metrics.clone()
+            ),
+            ("prometheus".to_string(), Gating::NonDefault, metrics),
+        ]
+    );
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&clean(dir.path(), true).stdout).unwrap();
+    assert_eq!(report["unused"], serde_json::json!([]), "{}", report);
+    assert_eq!(report["conditionally_used"][1]["name"], "prometheus");
+    assert_eq!(report["conditionally_used"][1]["gating"], "non-default");
+    assert_eq!(report["conditionally_used"][1]["line"], 13);
+
+    let output = String::from_utf8(clean(dir.path(), false).stdout).unwrap();
+    assert!(output.contains("🔀 Conditionally used:"), "{}", output);
+    assert!(
+        output.contains("prometheus [dependencies] (line 13) — off with the default features\n      cfg(feature = \"metrics\")"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("No unused dependencies found!"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_dependencies_behind_features_that_dont_exist() {
+    let dir = copy_fixture("cfg-unreachable");
+    // tracing is optional, so the default build leaves it out along with
+    // its code
+    assert_eq!(
+        conditional(dir.path()),
+        [(
+            "opentelemetry".to_string(),
+            Gating::Unreachable,
+            vec!["feature = \"otel\"".to_string()]
+        )]
+    );
+
+    let output = String::from_utf8(clean(dir.path(), false).stdout).unwrap();
+    assert!(
+        output.contains("opentelemetry [dependencies] (line 12) — no feature enables any of these"),
+        "{}",
+        output
+    );
+
+    // Once the feature exists again, it's only off by default
+    let manifest = dir.path().join("Cargo.toml");
+    let text = fs::read_to_string(&manifest).unwrap();
+    fs::write(
+        &manifest,
+        text.replace("default = []", "default = []\notel = []"),
+    )
+    .unwrap();
+    assert_eq!(conditional(dir.path())[0].1, Gating::NonDefault);
+}
@@ -1 +1,478 @@
+//! Shared helpers for integration tests
 
+#![allow(dead_code)]
+
+use assert_cmd::Command;
+use cargo_sane::analyzer::checker::CheckReport;
+use cargo_sane::core::advisory::Advisory;
+use cargo_sane::core::dependency::Dependency;
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::utils::advisories::AdvisorySource;
+use semver::Version;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// 2024-03-01T12:30:45Z, the "now" golden reports are rendered at
+pub const NOW: u64 = 1_709_296_245;
+
+pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+/// The `--manifest-path` the command functions take for the project in `dir`
+pub fn manifest_arg(dir: &Path) -> Option<String> {
+    Some(dir.join("Cargo.toml").display().to_string())
+}
+
+/// `cargo sane <args> --manifest-path <dir>/Cargo.toml`, without color or
+/// backtraces
+pub fn cargo_sane(dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::cargo_bin("cargo-sane").unwrap();
+    command
+        .args(args)
+        .arg("--manifest-path")
+        .arg(dir.join("Cargo.toml"))
+        .env("NO_COLOR", "1")
+        .env("RUST_BACKTRACE", "0");
+    command
+}
+
+/// [`cargo_sane`] under `--test-mode`, scripted by `<dir>/scenario.toml`
+pub fn cargo_sane_scripted(dir: &Path, args: &[&str]) -> Command {
+    let mut command = cargo_sane(dir, args);
+    command.arg("--test-mode").arg(dir.join("scenario.toml"));
+    command
+}
+
+pub fn stdout(output: &std::process::Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+pub fn stderr(output: &std::process::Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+/// Compare `actual` with `tests/golden/<name>`, or rewrite the file when
+/// `UPDATE_GOLDEN` is set
+pub fn assert_golden(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1)", path.display(), e));
+    assert_eq!(actual, expected, "{} changed", name);
+}
+
+/// `tests/fixtures/<name>`
+pub fn fixture_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// The Cargo.toml of the fixture `name`, read in place
+pub fn fixture_manifest(name: &str) -> Manifest {
+    Manifest::from_path(&fixture_path(name).join("Cargo.toml")).unwrap()
+}
+
+/// A temp copy of the fixture `name`, subdirectories and all
+pub fn copy_fixture(name: &str) -> tempfile::TempDir {
+    fn copy_dir(from: &Path, to: &Path) {
+        fs::create_dir_all(to).unwrap();
+        for entry in fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            let target = to.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &target);
+            } else {
+                fs::copy(entry.path(), target).unwrap();
+            }
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    copy_dir(&fixture_path(name), dir.path());
+    dir
+}
+
+/// Write a package called `name` with an empty library to `dir`
+pub fn write_package(dir: &Path, name: &str) {
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/lib.rs"), "").unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+            name
+        ),
+    )
+    .unwrap();
+}
+
+/// Run git in `dir`, failing the test if it fails
+pub fn git(dir: &Path, args: &[&str]) {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "commit.gpgsign=false"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?} failed", args);
+}
+
+/// An advisory source that knows of no advisories
+pub struct NoAdvisories;
+
+impl AdvisorySource for NoAdvisories {
+    async fn advisories_for(&self, _: &str, _: &Version) -> anyhow::Result<Vec<Advisory>> {
+        Ok(Vec::new())
+    }
+}
+
+/// A registry dependency on `current` whose newest release is `latest`; the
+/// library's unit tests share the same factory in `dependency::test_support`
+pub fn dep(name: &str, current: &str, latest: &str) -> Dependency {
+    Dependency::new(name.to_string(), Version::parse(current).unwrap(), true)
+        .with_latest(Version::parse(latest).unwrap())
+}
+
+/// A report for the package `demo` listing only `dependencies`
+pub fn check_report(dependencies: Vec<Dependency>) -> CheckReport {
+    serde_json::from_value(serde_json::json!({
+        "package": "demo",
+        "manifest": "Cargo.toml",
+        "dependencies": dependencies,
+        "declaration_conflicts": [],
+    }))
+    .unwrap()
+}
+
+/// A minimal stand-in for the crates.io API, serving `/crates/<name>` and
+/// `/crates/<name>/versions` from a fixed release list per crate, with an
+/// artificial per-request delay
+pub struct MockRegistry {
+    pub base_url: String,
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: Arc<AtomicUsize>,
+    requests: Arc<AtomicUsize>,
+    crates_requested: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockRegistry {
+    /// Serve a single, non-yanked release per crate
+    pub fn start(latest: &[(&str, &str)], delay: Duration) -> Self {
+        let releases: Vec<(&str, Vec<(&str, bool)>)> = latest
+            .iter()
+            .map(|(name, version)| (*name, vec![(*version, false)]))
+            .collect();
+        Self::with_releases(&releases, delay)
+    }
+
+    /// Serve `(version, yanked)` releases per crate, newest first
+    pub fn with_releases(releases: &[(&str, Vec<(&str, bool)>)], delay: Duration) -> Self {
+        Self::serve_releases(
+            releases
+                .iter()
+                .map(|(name, versions)| {
+                    let versions = versions
+                        .iter()
+                        .map(|(version, yanked)| (version.to_string(), *yanked, None))
+                        .collect();
+                    (name.to_string(), versions)
+                })
+                .collect(),
+            delay,
+        )
+    }
+
+    /// Serve `(version, license)` releases per crate, newest first
+    pub fn with_licenses(releases: &[(&str, Vec<(&str, &str)>)], delay: Duration) -> Self {
+        Self::serve_releases(
+            releases
+                .iter()
+                .map(|(name, versions)| {
+                    let versions = versions
+                        .iter()
+                        .map(|(version, license)| {
+                            (version.to_string(), false, Some(license.to_string()))
+                        })
+                        .collect();
+                    (name.to_string(), versions)
+                })
+                .collect(),
+            delay,
+        )
+    }
+
+    fn serve_releases(releases: Releases, delay: Duration) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock registry");
+        let base_url = format!("http://{}/api/v1", listener.local_addr().unwrap());
+
+        let releases = Arc::new(releases);
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let requests = Arc::new(AtomicUsize::new(0));
+        let crates_requested = Arc::new(Mutex::new(Vec::new()));
+
+        let counters = (in_flight.clone(), max_in_flight.clone(), requests.clone());
+        let log = crates_requested.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let releases = releases.clone();
+                let (in_flight, max_in_flight, requests) =
+                    (counters.0.clone(), counters.1.clone(), counters.2.clone());
+                let log = log.clone();
+                thread::spawn(move || {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(delay);
+                    serve(stream, &releases, &log);
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        Self {
+            base_url,
+            in_flight,
+            max_in_flight,
+            requests,
+            crates_requested,
+        }
+    }
+
+    /// Highest number of requests that were being served at the same time
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Total number of requests received
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    /// Number of requests served for the crate called `name`
+    pub fn requests_for(&self, name: &str) -> usize {
+        let requested = self.crates_requested.lock().unwrap();
+        requested.iter().filter(|n| *n == name).count()
+    }
+}
+
+/// `(version, yanked, license)` releases by crate name
+type Releases = HashMap<String, Vec<(String, bool, Option<String>)>>;
+
+/// Answer one request, logging the crate it was about
+fn serve(mut stream: TcpStream, releases: &Releases, log: &Mutex<Vec<String>>) {
+    let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // Drain headers
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+        line.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+    let (path, list_versions) = match path.strip_suffix("/versions") {
+        Some(path) => (path, true),
+        None => (path, false),
+    };
+    let name = path.rsplit('/').next().unwrap_or("");
+    log.lock().unwrap().push(name.to_string());
+    let (status, body) = match releases.get(name) {
+        Some(versions) if list_versions => {
+            let versions: Vec<String> = versions
+                .iter()
+                .map(|(version, yanked, license)| match license {
+                    Some(license) => format!(
+                        r#"{{"num":"{}","yanked":{},"license":"{}"}}"#,
+                        version, yanked, license
+                    ),
+                    None => format!(r#"{{"num":"{}","yanked":{}}}"#, version, yanked),
+                })
+                .collect();
+            (
+                "200 OK",
+                format!(r#"{{"versions":[{}]}}"#, versions.join(",")),
+            )
+        }
+        Some(versions) => {
+            let newest = versions
+                .iter()
+                .find(|(_, yanked, _)| !yanked)
+                .map(|(version, _, _)| version.as_str())
+                .unwrap_or("");
+            (
+                "200 OK",
+                format!(
+                    r#"{{"crate":{{"name":"{}","newest_version":"{}","description":null,"updated_at":"2024-01-01T00:00:00Z"}}}}"#,
+                    name, newest
+                ),
+            )
+        }
+        None => ("404 Not Found", r#"{"errors":[]}"#.to_string()),
+    };
+
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+}
+
+/// Serves one file at `/<name>`, honouring `Range: bytes=<start>-`, and
+/// can drop the connection partway through a response
+pub struct ArchiveServer {
+    pub url: String,
+    /// The `Range` header of each request, if it had one
+    ranges: Arc<Mutex<Vec<Option<String>>>>,
+    /// Bytes to send of the next response before hanging up
+    cut_after: Arc<Mutex<Option<usize>>>,
+}
+
+impl ArchiveServer {
+    pub fn start(name: &str, body: Vec<u8>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind archive server");
+        let url = format!("http://{}/{}", listener.local_addr().unwrap(), name);
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let cut_after = Arc::new(Mutex::new(None));
+
+        let (log, cut) = (ranges.clone(), cut_after.clone());
+        let body = Arc::new(body);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (log, cut, body) = (log.clone(), cut.clone(), body.clone());
+                thread::spawn(move || serve_range(stream, &body, &log, &cut));
+            }
+        });
+        Self {
+            url,
+            ranges,
+            cut_after,
+        }
+    }
+
+    /// Hang up the next response after `bytes` of its body
+    pub fn cut_next_after(&self, bytes: usize) {
+        *self.cut_after.lock().unwrap() = Some(bytes);
+    }
+
+    pub fn ranges(&self) -> Vec<Option<String>> {
+        self.ranges.lock().unwrap().clone()
+    }
+}
+
+fn serve_range(
+    mut stream: TcpStream,
+    body: &[u8],
+    log: &Mutex<Vec<Option<String>>>,
+    cut: &Mutex<Option<usize>>,
+) {
+    let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+    let mut range = None;
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("range") {
+                range = Some(value.trim().to_string());
+            }
+        }
+        line.clear();
+    }
+    log.lock().unwrap().push(range.clone());
+
+    let start = range
+        .as_deref()
+        .and_then(|r| r.strip_prefix("bytes="))
+        .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok());
+    let head = match start {
+        Some(start) if start >= body.len() => {
+            let _ = write!(
+                stream,
+                "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            return;
+        }
+        Some(start) => format!(
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\n",
+            start,
+            body.len() - 1,
+            body.len()
+        ),
+        None => "HTTP/1.1 200 OK\r\n".to_string(),
+    };
+    let rest = &body[start.unwrap_or(0)..];
+    let _ = write!(
+        stream,
+        "{}Content-Type: application/gzip\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        head,
+        rest.len()
+    );
+    let sent = match cut.lock().unwrap().take() {
+        Some(bytes) => &rest[..bytes.min(rest.len())],
+        None => rest,
+    };
+    let _ = stream.write_all(sent);
+    let _ = stream.flush();
+}
+
+/// Write a Cargo.toml with the given `[dependencies]` lines into a temp dir
+pub fn project(dependencies: &str) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().expect("create temp project");
+    fs::write(
+        dir.path().join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"fixture\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n{}",
+            dependencies
+        ),
+    )
+    .expect("write Cargo.toml");
+    dir
+}
+
+/// Write a workspace with one member per `(name, dependency lines)` under
+/// `crates/<name>`
+pub fn workspace(members: &[(&str, &str)]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().expect("create temp workspace");
+    fs::write(
+        dir.path().join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/*\"]\nresolver = \"2\"\n",
+    )
+    .expect("write workspace Cargo.toml");
+
+    for (name, dependencies) in members {
+        let member = dir.path().join("crates").join(name);
+        fs::create_dir_all(&member).expect("create member dir");
+        fs::write(
+            member.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n{}",
+                name, dependencies
+            ),
+        )
+        .expect("write member Cargo.toml");
+    }
+    dir
+}
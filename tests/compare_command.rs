@@ -0,0 +1,165 @@
+mod common;
+
+use assert_cmd::Command;
+use cargo_sane::analyzer::compare::{compare, suggest_targets, ConvergenceBasis};
+use cargo_sane::core::lockfile::Lockfile;
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::utils::crates_io::CratesIoClient;
+use common::{fixture_path, git, MockRegistry};
+use semver::Version;
+use serde_json::Value;
+use std::fs;
+use std::time::Duration;
+
+fn load(name: &str) -> (Manifest, Option<Lockfile>) {
+    let manifest = Manifest::from_path(&fixture_path(name).join("Cargo.toml")).unwrap();
+    let lockfile = Lockfile::resolve(&manifest, None).unwrap();
+    (manifest, lockfile)
+}
+
+fn compare_args(with: &str, args: &[&str]) -> std::process::Output {
+    Command::cargo_bin("cargo-sane")
+        .unwrap()
+        .arg("compare")
+        .args(["--with", with])
+        .args(args)
+        .arg("--manifest-path")
+        .arg(fixture_path("compare-api").join("Cargo.toml"))
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_compare_suggests_releases_both_requirements_allow() {
+    let registry = MockRegistry::with_releases(
+        &[
+            (
+                "serde",
+                vec![
+                    ("1.0.190", false),
+                    ("1.0.193", false),
+                    ("1.0.200", false),
+                    ("1.0.209", false),
+                    ("1.0.210", true),
+                ],
+            ),
+            ("anyhow", vec![("1.0.79", false), ("1.0.80", false)]),
+        ],
+        Duration::from_millis(0),
+    );
+    let (api, api_lock) = load("compare-api");
+    let (worker, worker_lock) = load("compare-worker");
+
+    let mut comparison = compare((&api, api_lock.as_ref()), (&worker, worker_lock.as_ref()));
+    assert_eq!(comparison.shared, 3);
+    assert_eq!(comparison.only_ours, ["axum"]);
+    assert_eq!(comparison.only_theirs, ["lapin"]);
+    // The renamed tokio is aligned with ours, and locked at the same version
+    assert_eq!(comparison.features.len(), 1);
+    assert_eq!(comparison.features[0].name, "tokio");
+    assert_eq!(comparison.features[0].only_theirs, ["default", "macros"]);
+
+    let client = CratesIoClient::with_base_url(&registry.base_url).unwrap();
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(suggest_targets(&mut comparison, &client, 2));
+    let targets: Vec<(&str, String, ConvergenceBasis)> = comparison
+        .versions
+        .iter()
+        .map(|d| {
+            let target = d.target.as_ref().unwrap();
+            (d.name.as_str(), target.version.to_string(), target.basis)
+        })
+        .collect();
+    assert_eq!(
+        targets,
+        [
+            (
+                "anyhow",
+                "1.0.80".to_string(),
+                ConvergenceBasis::BothRequirements
+            ),
+            // 1.0.210 is yanked
+            (
+                "serde",
+                "1.0.209".to_string(),
+                ConvergenceBasis::BothRequirements
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_compare_offline_renders_markdown_and_json() {
+    let worker = fixture_path("compare-worker").display().to_string();
+    let output = compare_args(&worker, &["--offline", "--markdown"]);
+    assert!(output.status.success());
+    let markdown = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        markdown.starts_with(
+            "# Dependencies of `api` and `worker`\n\n\
+             3 dependencies shared, 2 at different versions.\n\n\
+             ## Versions\n\n\
+             | Crate | `api` | `worker` | Converge on |\n|---|---|---|---|\n\
+             | `anyhow` | 1.0.80 | 1.0.79 | 1.0.80 (the newer one) |\n\
+             | `serde` | 1.0.193 | 1.0.200 | 1.0.200 (the newer one) |\n"
+        ),
+        "{}",
+        markdown
+    );
+    assert!(
+        markdown.contains("- `tokio`: only `worker` enables default, macros\n"),
+        "{}",
+        markdown
+    );
+    assert!(
+        markdown.contains("## Only in `api`\n\n`axum`\n"),
+        "{}",
+        markdown
+    );
+
+    let output = compare_args(&worker, &["--offline", "--json"]);
+    assert!(output.status.success());
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["versions"][1]["name"], "serde");
+    assert_eq!(json["versions"][1]["theirs"]["requirement"], "1.0.200");
+    assert_eq!(json["versions"][1]["target"]["basis"], "newest");
+    assert_eq!(json["only_theirs"][0], "lapin");
+}
+
+#[test]
+fn test_compare_with_a_git_url() {
+    let repo = tempfile::tempdir().unwrap();
+    for file in ["Cargo.toml", "Cargo.lock"] {
+        fs::copy(
+            fixture_path("compare-worker").join(file),
+            repo.path().join(file),
+        )
+        .unwrap();
+    }
+    git(repo.path(), &["init", "-q"]);
+    git(repo.path(), &["config", "user.name", "Alice"]);
+    git(repo.path(), &["config", "user.email", "alice@example.com"]);
+    git(repo.path(), &["add", "."]);
+    git(repo.path(), &["commit", "-q", "-m", "init"]);
+
+    let url = format!("file://{}", repo.path().display());
+    let output = compare_args(&url, &["--offline", "--json"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["theirs"], "worker");
+    assert_eq!(
+        Version::parse(json["versions"][0]["theirs"]["version"].as_str().unwrap()).unwrap(),
+        Version::new(1, 0, 79)
+    );
+
+    let output = compare_args("file:///nonexistent/repo", &["--offline"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("git clone of file:///nonexistent/repo failed"));
+}
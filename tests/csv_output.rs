@@ -0,0 +1,102 @@
+mod common;
+
+use cargo_sane::analyzer::checker::DependencyChecker;
+use cargo_sane::analyzer::health::HealthChecker;
+use cargo_sane::cli::csv::{check_csv, health_csv, CHECK_HEADER, HEALTH_HEADER};
+use cargo_sane::core::advisory::{Advisory, Severity};
+use cargo_sane::utils::advisories::AdvisorySource;
+use cargo_sane::utils::crates_io::CratesIoClient;
+use common::{block_on, fixture_manifest, MockRegistry};
+use semver::Version;
+use std::time::Duration;
+
+/// Parse CSV back into its header and records
+fn parse(csv: &str) -> (Vec<String>, Vec<Vec<String>>) {
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    let header = reader.headers().unwrap().iter().map(String::from).collect();
+    let records = reader
+        .records()
+        .map(|r| r.unwrap().iter().map(String::from).collect())
+        .collect();
+    (header, records)
+}
+
+struct FixtureAdvisories;
+
+impl AdvisorySource for FixtureAdvisories {
+    async fn advisories_for(
+        &self,
+        crate_name: &str,
+        _version: &Version,
+    ) -> anyhow::Result<Vec<Advisory>> {
+        let advisory = |id: &str, patched: &[&str]| Advisory {
+            id: id.to_string(),
+            package: crate_name.to_string(),
+            title: "Potential segfault, \"unsound\" API".to_string(),
+            severity: Some(Severity::Medium),
+            cvss: None,
+            aliases: Vec::new(),
+            patched_versions: patched.iter().map(|p| p.to_string()).collect(),
+            informational: None,
+            url: format!("https://rustsec.org/advisories/{}.html", id),
+        };
+        Ok(match crate_name {
+            "time" => vec![
+                advisory("RUSTSEC-2020-0071", &[">=0.2.23"]),
+                advisory("RUSTSEC-2020-0159", &[">=0.1.43, <0.2", ">=0.2.1"]),
+            ],
+            "tokio" => vec![advisory("RUSTSEC-2023-0001", &[">=1.20.4"])],
+            _ => Vec::new(),
+        })
+    }
+}
+
+#[test]
+fn test_check_csv_has_one_row_per_dependency() {
+    let registry = MockRegistry::start(
+        &[
+            ("serde", "1.0.200"),
+            ("time", "0.3.36"),
+            ("tokio", "1.37.0"),
+            ("uuid", "1.8.0"),
+        ],
+        Duration::ZERO,
+    );
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    );
+    let report = block_on(checker.check(&fixture_manifest("csv"))).unwrap();
+
+    let (header, records) = parse(&check_csv(&report, 1_700_000_000));
+    assert_eq!(header, CHECK_HEADER);
+    assert_eq!(records.len(), 4);
+    let time = records.iter().find(|r| r[0] == "time").unwrap();
+    assert_eq!(
+        time,
+        &["time", "normal", "0.1.40", "0.3.36", "minor", "", "false"]
+    );
+    let uuid = records.iter().find(|r| r[0] == "uuid").unwrap();
+    assert_eq!(uuid[4], "up-to-date");
+}
+
+#[test]
+fn test_health_csv_has_one_row_per_advisory() {
+    let report = block_on(
+        HealthChecker::with_source(FixtureAdvisories).check(&fixture_manifest("csv"), None),
+    )
+    .unwrap();
+
+    let csv = health_csv(&report);
+    let (header, records) = parse(&csv);
+    assert_eq!(header, HEALTH_HEADER);
+    assert_eq!(records.len(), 3);
+    assert_eq!(records.iter().filter(|r| r[0] == "time").count(), 2);
+
+    let quoted = records
+        .iter()
+        .find(|r| r[2] == "RUSTSEC-2020-0159")
+        .unwrap();
+    assert_eq!(quoted[3], "medium");
+    assert_eq!(quoted[4], ">=0.1.43, <0.2 || >=0.2.1");
+    assert!(csv.contains("\">=0.1.43, <0.2 || >=0.2.1\""));
+}
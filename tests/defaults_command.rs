@@ -0,0 +1,65 @@
+//! `cargo sane defaults --json`, which CI pins to notice when a release
+//! changes a default or a contract. Run with `UPDATE_GOLDEN=1` to rewrite
+//! the golden file after an intended change, and bump the contract version
+//! that covers it.
+
+mod common;
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The `defaults --json` document, without what changes from run to run
+/// or machine to machine
+fn defaults_json(dir: &Path) -> Value {
+    let output = common::cargo_sane(dir, &["defaults", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let mut report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let object = report.as_object_mut().unwrap();
+    object.remove("generated_at");
+    object.insert("tool_version".to_string(), "<version>".into());
+    let state_dir = object["state_dir"].as_str().unwrap().to_string();
+    assert_eq!(Path::new(&state_dir), dir.join(".cargo-sane"));
+    object.insert("state_dir".to_string(), "<project>/.cargo-sane".into());
+    report
+}
+
+#[test]
+fn test_defaults_are_stable() {
+    let dir = common::project("");
+    let json = serde_json::to_string_pretty(&defaults_json(dir.path())).unwrap() + "\n";
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden/defaults.json");
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &json).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1)", path.display(), e));
+    assert_eq!(json, expected, "the defaults changed");
+}
+
+#[test]
+fn test_config_file_settings_are_reported() {
+    let dir = common::project("");
+    let config = dir.path().join(".cargo-sane.toml");
+    fs::write(
+        &config,
+        "concurrency = 4\ncache_ttl_minutes = 60\nenrich_limit = 0\n",
+    )
+    .unwrap();
+
+    let json = defaults_json(dir.path());
+    assert_eq!(Path::new(json["config_file"].as_str().unwrap()), config);
+    assert_eq!(json["settings"]["concurrency"], 4);
+    assert_eq!(json["settings"]["enrich_limit"], 50);
+    let check_cache = json["caches"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|cache| cache["path"] == "cache.json")
+        .unwrap();
+    assert_eq!(check_cache["ttl_secs"], 3600);
+}
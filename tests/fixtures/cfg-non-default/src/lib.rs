@@ -0,0 +1,27 @@
+use serde::Serialize;
+
+#[cfg(feature = "metrics")]
+use prometheus::Registry;
+
+#[cfg(feature = "metrics")]
+mod stats;
+
+#[cfg(feature = "std")]
+pub fn words(text: &str) -> usize {
+    regex::Regex::new(r"\w+").unwrap().find_iter(text).count()
+}
+
+#[cfg(unix)]
+pub fn pid() -> i32 {
+    unsafe { libc::getpid() }
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub requests: u64,
+}
+
+#[cfg(feature = "metrics")]
+pub fn registry() -> Registry {
+    Registry::new()
+}
@@ -0,0 +1,5 @@
+use hdrhistogram::Histogram;
+
+pub fn latencies() -> Histogram<u64> {
+    Histogram::new(3).unwrap()
+}
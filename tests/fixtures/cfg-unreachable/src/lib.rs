@@ -0,0 +1,14 @@
+// `otel` was dropped from [features], but the code still refers to it
+#[cfg(feature = "otel")]
+pub fn exporter() -> opentelemetry::global::BoxedTracer {
+    opentelemetry::global::tracer("cfg-unreachable")
+}
+
+#[cfg(feature = "trace")]
+pub fn traced() {
+    tracing::info!("traced");
+}
+
+pub fn run() -> anyhow::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! Golden-file tests locking the formats reports print and emit.
+//! Run with `UPDATE_GOLDEN=1` to rewrite the files after an intended change.
+
+mod common;
+
+use cargo_sane::utils::formatting::{
+    format_count, format_date, format_duration, format_seconds, format_since, format_timestamp,
+    Stamped,
+};
+use common::{assert_golden, NOW};
+use serde::Serialize;
+use std::time::Duration;
+
+const DAY: u64 = 86_400;
+
+#[test]
+fn test_text_formats() {
+    let mut out = String::new();
+
+    for unix in [0, 951_782_400, NOW] {
+        out.push_str(&format!(
+            "timestamp {} = {} / {}\n",
+            unix,
+            format_timestamp(unix),
+            format_date(unix)
+        ));
+    }
+    for seconds in [
+        0,
+        1,
+        59,
+        60,
+        61,
+        3_599,
+        3_600,
+        5_400,
+        DAY,
+        DAY + 3_600,
+        45 * DAY,
+        400 * DAY,
+        800 * DAY + 7 * DAY,
+    ] {
+        out.push_str(&format!(
+            "duration {} = {}\n",
+            seconds,
+            format_duration(Duration::from_secs(seconds))
+        ));
+    }
+    for seconds in [0.4, 12.6, 95.0] {
+        out.push_str(&format!(
+            "seconds {} = {}\n",
+            seconds,
+            format_seconds(seconds)
+        ));
+    }
+    for count in [0, 7, 999, 1_000, 12_345, 1_234_567] {
+        out.push_str(&format!("count {} = {}\n", count, format_count(count)));
+    }
+    out.push_str(&format!(
+        "since = {}\n",
+        format_since(NOW - 3 * DAY - 7_200, NOW)
+    ));
+
+    assert_golden("formats.txt", &out);
+}
+
+#[test]
+fn test_json_stamp() {
+    #[derive(Serialize)]
+    struct Report {
+        package: &'static str,
+        scanned: usize,
+    }
+
+    let report = Report {
+        package: "demo",
+        scanned: 3,
+    };
+    let json = serde_json::to_string_pretty(&Stamped::new(&report, NOW)).unwrap();
+    // The tool version changes every release; the shape must not
+    let json = json.replace(env!("CARGO_PKG_VERSION"), "<version>");
+    assert_golden("stamped.json", &format!("{}\n", json));
+}
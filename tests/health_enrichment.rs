@@ -0,0 +1,82 @@
+mod common;
+
+use cargo_sane::analyzer::enrichment::{enrich, untrusted_input_parsers, Candidate, Exposure};
+use cargo_sane::analyzer::health::HealthChecker;
+use cargo_sane::core::lockfile::Lockfile;
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::utils::crates_io::CratesIoClient;
+use common::{block_on, MockRegistry, NoAdvisories};
+use std::time::Duration;
+
+/// `fixture` depends on `web`, which brings in a parser and two other crates
+fn project_with_transitive_packages() -> tempfile::TempDir {
+    let project = common::project("web = \"1.0\"\n");
+    std::fs::write(
+        project.path().join("Cargo.lock"),
+        "version = 3\n\n\
+         [[package]]\nname = \"fixture\"\nversion = \"0.1.0\"\ndependencies = [\"web\"]\n\n\
+         [[package]]\nname = \"web\"\nversion = \"1.0.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\ndependencies = [\"httparse\", \"obscure\", \"tinyvec\"]\n\n\
+         [[package]]\nname = \"httparse\"\nversion = \"1.8.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+         [[package]]\nname = \"obscure\"\nversion = \"0.1.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+         [[package]]\nname = \"tinyvec\"\nversion = \"1.6.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+    )
+    .unwrap();
+    project
+}
+
+fn candidates(project: &tempfile::TempDir) -> Vec<Candidate> {
+    let manifest = Manifest::from_path(&project.path().join("Cargo.toml")).unwrap();
+    let lockfile = Lockfile::for_manifest(&manifest).unwrap();
+    let parsers = untrusted_input_parsers().unwrap();
+    HealthChecker::with_source(NoAdvisories)
+        .with_transitive(true)
+        .scanned_packages(&manifest, lockfile.as_ref())
+        .into_iter()
+        .map(|(name, version, direct)| Candidate::new(name, version, direct, &parsers))
+        .collect()
+}
+
+#[test]
+fn test_enrichment_stays_within_budget() {
+    let registry = MockRegistry::with_releases(
+        &[
+            ("web", vec![("1.1.0", false), ("1.0.0", true)]),
+            ("httparse", vec![("1.8.0", false)]),
+            ("obscure", vec![("0.1.0", false)]),
+            ("tinyvec", vec![("1.6.0", false)]),
+        ],
+        Duration::ZERO,
+    );
+    let client = CratesIoClient::with_base_url(&registry.base_url).unwrap();
+    let project = project_with_transitive_packages();
+
+    let enrichment = block_on(enrich(&client, candidates(&project), 2, 4));
+    let enriched: Vec<(&str, Exposure)> = enrichment
+        .enriched
+        .iter()
+        .map(|p| (p.name.as_str(), p.exposure))
+        .collect();
+    assert_eq!(
+        enriched,
+        [
+            ("web", Exposure::Direct),
+            ("httparse", Exposure::UntrustedInput)
+        ]
+    );
+    assert_eq!(enrichment.advisory_only, ["obscure@0.1.0", "tinyvec@1.6.0"]);
+    assert!(enrichment.failed.is_empty());
+    // Packages past the budget cost no requests
+    assert_eq!(registry.requests_for("obscure"), 0);
+    assert_eq!(registry.requests_for("tinyvec"), 0);
+
+    let web = &enrichment.enriched[0];
+    assert!(web.yanked);
+    assert!(web.concerns(0).contains(&"1.0.0 is yanked".to_string()));
+
+    // Without download counts the rest goes by name, and 0 looks up all
+    let enrichment = block_on(enrich(&client, candidates(&project), 3, 4));
+    assert_eq!(enrichment.enriched[2].name, "obscure");
+    let enrichment = block_on(enrich(&client, candidates(&project), 0, 4));
+    assert_eq!(enrichment.enriched.len(), 4);
+    assert!(enrichment.advisory_only.is_empty());
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use cargo_sane::analyzer::checker::DependencyChecker;
+use cargo_sane::analyzer::health::HealthChecker;
+use cargo_sane::analyzer::internal::InternalCrates;
+use cargo_sane::core::advisory::Advisory;
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::core::workspace::Workspace;
+use cargo_sane::utils::advisories::AdvisorySource;
+use cargo_sane::utils::crates_io::CratesIoClient;
+use common::{block_on, MockRegistry};
+use semver::Version;
+use std::time::Duration;
+
+/// `app` declares `internal-util` by version, and the root patches it in
+/// from the unpublished member of that name
+fn workspace_with_unpublished_member() -> tempfile::TempDir {
+    let fixture = common::workspace(&[
+        ("app", "serde = \"1.0\"\ninternal-util = \"0.1\"\n"),
+        ("internal-util", ""),
+    ]);
+    std::fs::write(
+        fixture.path().join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/*\"]\nresolver = \"2\"\n\n\
+         [patch.crates-io]\ninternal-util = { path = \"crates/internal-util\" }\n",
+    )
+    .unwrap();
+    std::fs::write(
+        fixture.path().join("crates/internal-util/Cargo.toml"),
+        "[package]\nname = \"internal-util\"\nversion = \"0.1.0\"\nedition = \"2021\"\npublish = false\n",
+    )
+    .unwrap();
+    fixture
+}
+
+fn load(fixture: &tempfile::TempDir) -> Workspace {
+    let root = Manifest::from_path(&fixture.path().join("Cargo.toml")).unwrap();
+    Workspace::load(root).unwrap().unwrap()
+}
+
+#[test]
+fn test_unpublished_members_are_not_looked_up() {
+    let registry = MockRegistry::start(&[("serde", "1.0.200")], Duration::ZERO);
+    let fixture = workspace_with_unpublished_member();
+    let workspace = load(&fixture);
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    )
+    .with_internal(InternalCrates::discover(&workspace.root, None));
+    let report = block_on(checker.check_workspace(&workspace)).unwrap();
+
+    assert_eq!(registry.requests(), 1);
+    assert_eq!(report.internal, vec!["internal-util"]);
+    let app: Vec<&str> = report.members[0]
+        .dependencies
+        .iter()
+        .map(|d| d.name.as_str())
+        .collect();
+    assert_eq!(app, vec!["serde"]);
+}
+
+#[test]
+fn test_internal_crates_can_be_treated_as_external() {
+    let registry = MockRegistry::start(&[("serde", "1.0.200")], Duration::ZERO);
+    let fixture = workspace_with_unpublished_member();
+    let workspace = load(&fixture);
+
+    // What `treat_internal_as_external = true` gives: nothing set aside
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    );
+    let report = block_on(checker.check_workspace(&workspace)).unwrap();
+
+    assert_eq!(registry.requests(), 2);
+    assert!(report.internal.is_empty());
+    let internal_util = report.members[0]
+        .dependencies
+        .iter()
+        .find(|d| d.name == "internal-util")
+        .unwrap();
+    assert_eq!(internal_util.latest_version, None);
+}
+
+/// Knows one advisory, against a published crate that happens to be called
+/// `internal-util`
+struct NamesakeAdvisories;
+
+impl AdvisorySource for NamesakeAdvisories {
+    async fn advisories_for(
+        &self,
+        crate_name: &str,
+        _version: &Version,
+    ) -> anyhow::Result<Vec<Advisory>> {
+        if crate_name != "internal-util" {
+            return Ok(Vec::new());
+        }
+        Ok(vec![Advisory {
+            id: "RUSTSEC-2024-0001".to_string(),
+            package: crate_name.to_string(),
+            title: "Unsound namesake".to_string(),
+            severity: None,
+            cvss: None,
+            aliases: Vec::new(),
+            patched_versions: Vec::new(),
+            informational: None,
+            url: String::new(),
+        }])
+    }
+}
+
+#[test]
+fn test_advisories_against_internal_crates_are_kept_apart() {
+    let fixture = workspace_with_unpublished_member();
+    let workspace = load(&fixture);
+    let app = workspace.member("app").unwrap();
+
+    let checker = HealthChecker::with_source(NamesakeAdvisories)
+        .with_internal(InternalCrates::discover(&workspace.root, None));
+    let report = block_on(checker.check(app, None)).unwrap();
+    assert_eq!(report.scanned, 2);
+    assert!(report.vulnerable.is_empty());
+    assert_eq!(report.internal[0].name, "internal-util");
+
+    let strict = block_on(HealthChecker::with_source(NamesakeAdvisories).check(app, None)).unwrap();
+    assert_eq!(strict.vulnerable.len(), 1);
+    assert!(strict.internal.is_empty());
+}
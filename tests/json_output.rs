@@ -0,0 +1,211 @@
+//! The `--json` documents are an interface: each must validate against the
+//! schema `cargo sane schema` prints, and read back into its report type.
+
+mod common;
+
+use cargo_sane::analyzer::checker::{CheckReport, DependencyChecker};
+use cargo_sane::analyzer::conflicts::ConflictReport;
+use cargo_sane::analyzer::health::{HealthChecker, HealthReport};
+use cargo_sane::analyzer::lint::LintSeverity;
+use cargo_sane::analyzer::plugin::{parse_output, AnalyzerFindings, Finding, ProjectAnalysis};
+use cargo_sane::analyzer::snapshot::{ProjectReport, Snapshot};
+use cargo_sane::analyzer::stats::dependency_stats;
+use cargo_sane::analyzer::std_replacements::StdReplacement;
+use cargo_sane::analyzer::usage::{find_unused_dependencies, CleanReport};
+use cargo_sane::analyzer::usage_cache::UsageCache;
+use cargo_sane::cli::output::render_json;
+use cargo_sane::cli::schema::{schema, SchemaKind};
+use cargo_sane::core::advisory::{Advisory, Severity};
+use cargo_sane::core::dependency::DependencyKind;
+use cargo_sane::core::manifest::DependencySection;
+use cargo_sane::updater::plan::{Plan, PlannedAction};
+use cargo_sane::utils::advisories::AdvisorySource;
+use cargo_sane::utils::crates_io::CratesIoClient;
+use cargo_sane::utils::formatting::SCHEMA_VERSION;
+use common::{block_on, fixture_manifest, MockRegistry, NOW};
+use semver::Version;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+
+struct FixtureAdvisories;
+
+impl AdvisorySource for FixtureAdvisories {
+    async fn advisories_for(
+        &self,
+        crate_name: &str,
+        _version: &Version,
+    ) -> anyhow::Result<Vec<Advisory>> {
+        if crate_name != "time" {
+            return Ok(Vec::new());
+        }
+        Ok(vec![Advisory {
+            id: "RUSTSEC-2020-0071".to_string(),
+            package: crate_name.to_string(),
+            title: "Potential segfault in the time crate".to_string(),
+            severity: Some(Severity::Medium),
+            cvss: None,
+            aliases: vec!["CVE-2020-26235".to_string()],
+            patched_versions: vec![">=0.2.23".to_string()],
+            informational: None,
+            url: "https://rustsec.org/advisories/RUSTSEC-2020-0071.html".to_string(),
+        }])
+    }
+}
+
+fn check_report() -> CheckReport {
+    let registry = MockRegistry::start(
+        &[
+            ("serde", "1.0.200"),
+            ("time", "0.3.36"),
+            ("tokio", "1.37.0"),
+            ("uuid", "1.8.0"),
+        ],
+        Duration::ZERO,
+    );
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    );
+    block_on(checker.check(&fixture_manifest("csv"))).unwrap()
+}
+
+fn health_report() -> HealthReport {
+    block_on(HealthChecker::with_source(FixtureAdvisories).check(&fixture_manifest("csv"), None))
+        .unwrap()
+}
+
+/// Render `report` as `--json` does, validate it against `kind`'s schema,
+/// and read it back
+fn round_trip<T: Serialize + DeserializeOwned>(kind: SchemaKind, report: &T) -> T {
+    let document: Value = serde_json::from_str(&render_json(report).unwrap()).unwrap();
+    assert_eq!(document["schema_version"], SCHEMA_VERSION);
+
+    let validator = jsonschema::validator_for(&schema(kind).to_value()).unwrap();
+    let errors: Vec<String> = validator
+        .iter_errors(&document)
+        .map(|e| format!("{} at {}", e, e.instance_path))
+        .collect();
+    assert!(errors.is_empty(), "{:?}: {:#?}", kind, errors);
+
+    let read: T = serde_json::from_value(document).unwrap();
+    assert_eq!(
+        serde_json::to_value(&read).unwrap(),
+        serde_json::to_value(report).unwrap()
+    );
+    read
+}
+
+#[test]
+fn test_check_document() {
+    let report = round_trip(SchemaKind::Check, &check_report());
+    assert_eq!(report.dependencies.len(), 4);
+}
+
+#[test]
+fn test_health_document() {
+    let report = round_trip(SchemaKind::Health, &health_report());
+    assert_eq!(report.vulnerable.len(), 1);
+}
+
+#[test]
+fn test_fix_document() {
+    let manifest = fixture_manifest("csv");
+    let action = PlannedAction::manifest_edit(
+        &DependencySection::new(DependencyKind::Normal),
+        "time",
+        "0.1",
+        "0.3.36",
+        "RUSTSEC-2020-0071".to_string(),
+    );
+    let plan = round_trip(
+        SchemaKind::Fix,
+        &Plan::new(&manifest, vec![action]).unwrap(),
+    );
+    assert_eq!(plan.actions.len(), 1);
+}
+
+#[test]
+fn test_clean_document() {
+    let manifest = fixture_manifest("csv");
+    let unused =
+        find_unused_dependencies(&manifest, &[], &UsageCache::disabled(Path::new("."))).unwrap();
+    let std_replacements = vec![StdReplacement {
+        name: "atty".to_string(),
+        std: "std::io::IsTerminal".to_string(),
+        since: "1.70".to_string(),
+        note: "atty is unmaintained".to_string(),
+    }];
+    let report = round_trip(
+        SchemaKind::Clean,
+        &CleanReport {
+            unused,
+            std_replacements,
+            conditionally_used: Vec::new(),
+        },
+    );
+    assert_eq!(report.unused.len(), 4);
+    assert_eq!(report.std_replacements[0].std, "std::io::IsTerminal");
+}
+
+#[test]
+fn test_report_document() {
+    let check = check_report();
+    let conflicts = ConflictReport::from_tree(
+        "\
+0syn v1.0.109
+1serde_derive v1.0.100 (proc-macro)
+
+0syn v2.0.50
+1thiserror-impl v1.0.57 (proc-macro)
+",
+    );
+    let current = Snapshot::new(NOW, check.clone(), Some(health_report()), Some(conflicts));
+    let baseline = Snapshot::new(NOW - 86_400, check, None, None);
+    let report = ProjectReport {
+        stats: dependency_stats(&current.check.dependencies, NOW),
+        freshness: Vec::new(),
+        since: Some(baseline.diff(&current)),
+        check: current.check,
+        health: current.health,
+        conflicts: current.conflicts,
+        history: None,
+        analyzers: vec![AnalyzerFindings {
+            analyzer: "policy".to_string(),
+            program: Some("./policy".into()),
+            findings: vec![Finding {
+                code: "stale-internal-crate".to_string(),
+                severity: LintSeverity::Error,
+                message: "acme-auth is behind".to_string(),
+                dependency: Some("acme-auth".to_string()),
+            }],
+            error: None,
+        }],
+    };
+    let report = round_trip(SchemaKind::Report, &report);
+    assert_eq!(report.conflicts.unwrap().conflicts.len(), 1);
+    assert_eq!(report.analyzers[0].findings[0].code, "stale-internal-crate");
+}
+
+#[test]
+fn test_plugin_documents() {
+    let validate = |kind: SchemaKind, document: Value| {
+        let validator = jsonschema::validator_for(&schema(kind).to_value()).unwrap();
+        validator.iter_errors(&document).count() == 0
+    };
+    let snapshot = Snapshot::new(NOW, check_report(), Some(health_report()), None);
+    let input = serde_json::to_value(ProjectAnalysis::new(&snapshot)).unwrap();
+    assert!(validate(SchemaKind::PluginInput, input));
+
+    // The schema rejects what parse_output does
+    let output = serde_json::json!({"findings": [
+        {"code": "stale-internal-crate", "severity": "error", "message": "behind",
+         "dependency": "acme-auth"}
+    ]});
+    assert!(parse_output(output.to_string().as_bytes()).is_ok());
+    assert!(validate(SchemaKind::PluginOutput, output));
+    let extra = serde_json::json!({"findings": [], "summary": "ok"});
+    assert!(parse_output(extra.to_string().as_bytes()).is_err());
+    assert!(!validate(SchemaKind::PluginOutput, extra));
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use cargo_sane::analyzer::feature_table::{find_feature_issues, FeatureFinding, FeatureIssue};
+use cargo_sane::analyzer::lint::LintSeverity;
+use cargo_sane::cli::commands;
+use cargo_sane::core::manifest::Manifest;
+use common::copy_fixture;
+use std::fs;
+use std::path::Path;
+
+fn findings(dir: &Path) -> Vec<FeatureFinding> {
+    let path = dir.join("Cargo.toml");
+    let content = fs::read_to_string(&path).unwrap();
+    find_feature_issues(&Manifest::from_path(&path).unwrap(), &content)
+}
+
+/// `(feature, name, kind, line)` of each finding
+fn summary(findings: &[FeatureFinding]) -> Vec<(Option<&str>, &str, FeatureIssue, usize)> {
+    findings
+        .iter()
+        .map(|f| {
+            (
+                f.feature.as_deref(),
+                f.name.as_str(),
+                f.kind,
+                f.line.unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn lint(dir: &Path, fix: bool) -> bool {
+    commands::lint_command(
+        Some(dir.join("Cargo.toml").display().to_string()),
+        fix,
+        false,
+        true,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_dep_references_to_missing_dependencies_are_removed() {
+    let dir = copy_fixture("features-missing-dependency");
+    assert_eq!(
+        summary(&findings(dir.path())),
+        [
+            (
+                Some("native-tls"),
+                "native-tls",
+                FeatureIssue::MissingDependency,
+                13
+            ),
+            (
+                Some("tls"),
+                "native-tls",
+                FeatureIssue::MissingDependency,
+                15
+            ),
+        ]
+    );
+    assert!(!lint(dir.path(), false));
+
+    assert!(lint(dir.path(), true));
+    let fixed = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert!(
+        fixed.ends_with(
+            "# native-tls was dropped for rustls\n\
+             native-tls = []\n\
+             tls = [\n    \"dep:rustls\",\n]\n"
+        ),
+        "{}",
+        fixed
+    );
+    assert!(findings(dir.path()).is_empty());
+    assert!(dir.path().join("Cargo.toml.backup").exists());
+}
+
+#[test]
+fn test_features_of_missing_crates_are_removed() {
+    let dir = copy_fixture("features-missing-crate");
+    // A dev-dependency is never built by a feature
+    assert_eq!(
+        summary(&findings(dir.path())),
+        [
+            (
+                Some("std"),
+                "serde_json",
+                FeatureIssue::MissingDependency,
+                14
+            ),
+            (
+                Some("fuzz"),
+                "proptest",
+                FeatureIssue::MissingDependency,
+                15
+            ),
+        ]
+    );
+
+    assert!(lint(dir.path(), true));
+    let fixed = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert!(
+        fixed.contains("std = [\"serde?/std\"]\nfuzz = []\n"),
+        "{}",
+        fixed
+    );
+}
+
+#[test]
+fn test_required_dependencies_behind_features_need_a_hand() {
+    let dir = copy_fixture("features-not-optional");
+    let found = findings(dir.path());
+    assert_eq!(
+        summary(&found),
+        [
+            (Some("logging"), "log", FeatureIssue::NotOptional, 10),
+            (Some("kv"), "log", FeatureIssue::NotOptional, 11),
+        ]
+    );
+    assert!(found.iter().all(|f| !f.is_fixable()));
+    assert_eq!(
+        found[0].suggestion.as_deref(),
+        Some("add `optional = true` to log")
+    );
+
+    // Nothing is removable, so --fix leaves the manifest alone
+    let before = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert!(!lint(dir.path(), true));
+    let after = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert_eq!(before, after);
+}
+
+#[test]
+fn test_unknown_features_are_removed() {
+    let dir = copy_fixture("features-unknown-feature");
+    assert_eq!(
+        summary(&findings(dir.path())),
+        [(Some("legacy"), "openssl", FeatureIssue::UnknownFeature, 12)]
+    );
+
+    assert!(lint(dir.path(), true));
+    let fixed = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert!(fixed.ends_with("legacy = [\"std\"]\n"), "{}", fixed);
+}
+
+#[test]
+fn test_orphaned_optional_dependencies_are_noted_without_failing() {
+    let dir = copy_fixture("features-orphaned");
+    let found = findings(dir.path());
+    assert_eq!(
+        summary(&found),
+        [(None, "chrono", FeatureIssue::Orphaned, 7)]
+    );
+    assert_eq!(found[0].severity, LintSeverity::Info);
+    assert_eq!(
+        found[0].suggestion.as_deref(),
+        Some("chrono = [\"dep:chrono\"] under [features], or remove the dependency")
+    );
+    assert!(lint(dir.path(), false));
+}
@@ -0,0 +1,55 @@
+//! `--metadata-file` is process-wide, so this file keeps to one test
+
+mod common;
+
+use cargo_sane::analyzer::conflicts::find_conflicts;
+use cargo_sane::core::config::Config;
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::utils::cargo::{self, CargoOptions, Metadata};
+use std::fs;
+use std::path::Path;
+
+fn package(dir: &Path, name: &str) -> Manifest {
+    common::write_package(dir, name);
+    Manifest::from_path(&dir.join("Cargo.toml")).unwrap()
+}
+
+#[test]
+fn test_conflicts_come_from_the_metadata_file_without_cargo() {
+    let fixture = common::fixture_path("mirrored");
+    let dir = tempfile::tempdir().unwrap();
+    let demo = package(dir.path(), "demo");
+
+    // A cargo that can't be started: any attempt to run it fails the test
+    let config = Config {
+        cargo_command: Some("/nonexistent/cargo".to_string()),
+        ..Config::default()
+    };
+    let options = CargoOptions::for_project(&config, dir.path()).unwrap();
+    assert!(cargo::metadata(&demo.path, &options).is_err());
+
+    Metadata::set_file(fixture.join("metadata.json"));
+    let report = find_conflicts(&demo, &options).unwrap();
+    let names: Vec<&str> = report.conflicts.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["syn"]);
+    assert_eq!(report.source_splits.len(), 1);
+
+    // Metadata of some other project is refused
+    let other = tempfile::tempdir().unwrap();
+    let other = package(other.path(), "other");
+    let error = cargo::metadata(&other.path, &options).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("isn't one of its workspace members"),
+        "{}",
+        error
+    );
+
+    // And so is another format version, with the way to regenerate it
+    let newer = dir.path().join("metadata-v2.json");
+    let json = fs::read_to_string(fixture.join("metadata.json")).unwrap();
+    fs::write(&newer, json.replace("\"version\": 1", "\"version\": 2")).unwrap();
+    let error = format!("{:#}", Metadata::from_file(&newer).unwrap_err());
+    assert!(error.contains("--format-version 1"), "{}", error);
+}
@@ -0,0 +1,329 @@
+//! Property tests for the parsers that read text we don't control: `cargo
+//! tree` output, whatever its version or locale, and arbitrary manifests.
+//! None of them may panic, and odd-but-valid input must parse as what it is.
+
+use cargo_sane::analyzer::conflicts::ConflictReport;
+use cargo_sane::core::dependency::DependencyKind;
+use cargo_sane::core::manifest::{DependencySection, Manifest};
+use cargo_sane::updater::DependencyUpdater;
+use proptest::prelude::*;
+use semver::Version;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+/// Crate names, including ones that look like a version or a tree prefix
+fn crate_name() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-z][a-z0-9_-]{0,8}",
+        Just("v1".to_string()),
+        Just("v".to_string()),
+        "v[0-9]{1,3}",
+        Just("feature".to_string()),
+    ]
+}
+
+fn version() -> impl Strategy<Value = Version> {
+    (
+        0u64..20,
+        0u64..20,
+        0u64..20,
+        proptest::option::of("[a-z][a-z0-9]{0,4}"),
+    )
+        .prop_map(|(major, minor, patch, pre)| {
+            let text = match pre {
+                Some(pre) => format!("{}.{}.{}-{}", major, minor, patch, pre),
+                None => format!("{}.{}.{}", major, minor, patch),
+            };
+            Version::parse(&text).unwrap()
+        })
+}
+
+/// What cargo may print after `name vX.Y.Z`
+fn annotations() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        Just(" (*)".to_string()),
+        Just(" (proc-macro)".to_string()),
+        Just(" (proc-macro) (*)".to_string()),
+        Just(" (registry `my-mirror`)".to_string()),
+        Just(" (https://github.com/o/r?branch=main#0a1b2c3d)".to_string()),
+        "[a-z/ v0-9.()]{0,24}".prop_map(|path| format!(" (/work/{})", path)),
+    ]
+}
+
+/// A tree line that isn't a package entry
+fn noise_line() -> impl Strategy<Value = String> {
+    prop_oneof![
+        (0usize..4, crate_name(), "[a-z0-9]{1,6}").prop_map(|(depth, name, feature)| format!(
+            "{}{} feature \"{}\"",
+            depth, name, feature
+        )),
+        (0usize..4).prop_map(|depth| format!("{}[build-dependencies]", depth)),
+        (0usize..4).prop_map(|depth| format!("{}[dev-dependencies]", depth)),
+        Just(String::new()),
+        Just("0".to_string()),
+        Just("99999999999999999999999999serde v1.0.0".to_string()),
+        Just("warning: Patch `foo v1.0.0` was not used in the crate graph".to_string()),
+        "\\PC{0,40}",
+    ]
+}
+
+/// A `cargo tree --duplicates --prefix depth` line: a package entry, with
+/// its depth, or noise
+#[derive(Debug, Clone)]
+enum TreeLine {
+    /// Depth, name, version, separator and annotations
+    Package(usize, String, Version, &'static str, String),
+    Noise(String),
+}
+
+impl TreeLine {
+    fn render(&self) -> String {
+        match self {
+            TreeLine::Package(depth, name, version, separator, annotations) => {
+                format!("{}{}{}v{}{}", depth, name, separator, version, annotations)
+            }
+            TreeLine::Noise(line) => line.clone(),
+        }
+    }
+}
+
+fn tree_line() -> impl Strategy<Value = TreeLine> {
+    prop_oneof![
+        3 => (
+            0usize..4,
+            crate_name(),
+            version(),
+            prop_oneof![Just(" "), Just("  "), Just("\t")],
+            annotations(),
+        )
+            .prop_map(|(depth, name, version, separator, annotations)| {
+                TreeLine::Package(depth, name, version, separator, annotations)
+            }),
+        1 => noise_line().prop_map(TreeLine::Noise),
+    ]
+}
+
+/// How a dependency is declared in a manifest
+#[derive(Debug, Clone, Copy)]
+enum Style {
+    Simple,
+    Inline,
+    InlineFeaturesFirst,
+    Dotted,
+    Table,
+}
+
+fn style() -> impl Strategy<Value = Style> {
+    prop_oneof![
+        Just(Style::Simple),
+        Just(Style::Inline),
+        Just(Style::InlineFeaturesFirst),
+        Just(Style::Dotted),
+        Just(Style::Table),
+    ]
+}
+
+fn section() -> impl Strategy<Value = DependencySection> {
+    prop_oneof![
+        Just(DependencySection::new(DependencyKind::Normal)),
+        Just(DependencySection::new(DependencyKind::Dev)),
+        Just(DependencySection::new(DependencyKind::Build)),
+        Just(DependencySection {
+            kind: DependencyKind::Normal,
+            target: Some("cfg(unix)".to_string()),
+        }),
+    ]
+}
+
+/// Dependencies per section, each with a declaration style and a comment
+/// flag; names are unique within a section
+type Declarations = BTreeMap<DependencySection, BTreeMap<String, (Style, bool)>>;
+
+fn declarations() -> impl Strategy<Value = Declarations> {
+    proptest::collection::vec((section(), crate_name(), style(), any::<bool>()), 1..8).prop_map(
+        |entries| {
+            let mut declarations = Declarations::new();
+            for (section, name, style, comment) in entries {
+                declarations
+                    .entry(section)
+                    .or_default()
+                    .insert(name, (style, comment));
+            }
+            declarations
+        },
+    )
+}
+
+/// Valid `[package]` keys holding text a line scanner could mistake for
+/// structure
+fn package_extra() -> impl Strategy<Value = &'static str> {
+    prop_oneof![
+        Just(""),
+        Just("description = 'Quotes like \"\"\" are fine'\n"),
+        Just("description = \"\"\"\n[dependencies]\nfake = \"1.0\"\n\"\"\"\n"),
+        Just("description = '''\n[dependencies]\nfake = \"1.0\"'''\n"),
+        Just("description = \"\"\"one-liner\"\"\"\n"),
+        Just("keywords = [\n  \"[dependencies]\",\n]\n"),
+    ]
+}
+
+/// Render the manifest, with every requirement at "1.0"
+fn render(declarations: &Declarations, extra: &str, newline: &str) -> String {
+    let mut text = format!("[package]\nname = \"fuzz\"\nversion = \"0.1.0\"\n{}", extra);
+    for (section, deps) in declarations {
+        text.push_str(&format!("\n[{}]\n", section));
+        for (name, (style, comment)) in deps {
+            if *comment {
+                text.push_str(&format!("# {} = \"0.1\"\n", name));
+            }
+            match style {
+                Style::Simple => text.push_str(&format!("{} = \"1.0\"\n", name)),
+                Style::Inline => text.push_str(&format!(
+                    "{} = {{ version = \"1.0\", default-features = false }}\n",
+                    name
+                )),
+                Style::InlineFeaturesFirst => text.push_str(&format!(
+                    "\"{}\" = {{ features = [\"v1\"], version = \"1.0\" }}\n",
+                    name
+                )),
+                Style::Dotted => text.push_str(&format!(
+                    "{0}.version = \"1.0\"\n{0}.optional = true\n",
+                    name
+                )),
+                Style::Table => {}
+            }
+        }
+        for (name, (style, _)) in deps {
+            if let Style::Table = style {
+                text.push_str(&format!(
+                    "\n[{}.{}]\nversion = \"1.0\"\nfeatures = [\"std\"]\n",
+                    section, name
+                ));
+            }
+        }
+    }
+    text.replace('\n', newline)
+}
+
+/// The requirement of every declaration, by section and name
+fn requirements(manifest: &Manifest) -> BTreeMap<(DependencySection, String), Option<String>> {
+    manifest
+        .declarations()
+        .into_iter()
+        .map(|(section, name, spec)| ((section, name), spec.version().map(str::to_string)))
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn tree_parser_never_panics(output in "\\PC*(\n\\PC*){0,8}") {
+        ConflictReport::from_tree(&output);
+    }
+
+    #[test]
+    fn tree_conflicts_come_from_package_entries_only(
+        lines in proptest::collection::vec(tree_line(), 0..40)
+    ) {
+        let output: Vec<String> = lines.iter().map(TreeLine::render).collect();
+        let report = ConflictReport::from_tree(&output.join("\n"));
+
+        // Versions by name, with the dependents listed under each: the depth
+        // 1 entries up to the next depth 0 line, package or not
+        let mut expected: BTreeMap<String, BTreeMap<Version, BTreeSet<String>>> = BTreeMap::new();
+        let mut current = None;
+        for line in &lines {
+            match line {
+                TreeLine::Package(0, name, version, _, _) => {
+                    expected.entry(name.clone()).or_default().entry(version.clone()).or_default();
+                    current = Some((name.clone(), version.clone()));
+                }
+                TreeLine::Package(1, name, version, _, _) => {
+                    if let Some((duplicate, at)) = &current {
+                        expected
+                            .get_mut(duplicate)
+                            .and_then(|versions| versions.get_mut(at))
+                            .unwrap()
+                            .insert(format!("{} v{}", name, version));
+                    }
+                }
+                TreeLine::Package(..) => {}
+                TreeLine::Noise(line) => {
+                    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+                    if line[..digits].parse::<usize>() == Ok(0) {
+                        current = None;
+                    }
+                }
+            }
+        }
+        expected.retain(|_, versions| versions.len() > 1);
+
+        let found: BTreeMap<String, BTreeMap<Version, BTreeSet<String>>> = report
+            .conflicts
+            .iter()
+            .map(|c| {
+                let versions = c
+                    .versions
+                    .iter()
+                    .map(|v| (v.version.clone(), v.dependents.iter().cloned().collect()))
+                    .collect();
+                (c.name.clone(), versions)
+            })
+            .collect();
+        prop_assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn manifest_parser_never_panics(text in "\\PC*(\n\\PC*){0,8}") {
+        if let Ok(manifest) = Manifest::parse(PathBuf::from("Cargo.toml"), &text) {
+            manifest.declarations();
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn every_declaration_style_can_be_updated(
+        declarations in declarations(),
+        extra in package_extra(),
+        crlf in any::<bool>(),
+    ) {
+        let text = render(&declarations, extra, if crlf { "\r\n" } else { "\n" });
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Cargo.toml");
+        fs::write(&path, &text).unwrap();
+        let before = requirements(&Manifest::from_path(&path).unwrap());
+
+        for (section, deps) in &declarations {
+            for name in deps.keys() {
+                let mut updater =
+                    DependencyUpdater::new(Manifest::from_path(&path).unwrap()).unwrap();
+                let edit = updater.update_declaration(section, name, "9.9.9");
+                prop_assert!(edit.is_ok(), "{}: {:?}\n{}", name, edit.err(), text);
+                prop_assert_eq!(edit.unwrap().old_requirement, "1.0");
+
+                let updated = Manifest::parse(path.clone(), updater.get_content()).unwrap();
+                let mut expected = before.clone();
+                expected.insert((section.clone(), name.clone()), Some("9.9.9".to_string()));
+                prop_assert_eq!(requirements(&updated), expected, "{}\n{}", name, text);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_pathological_tree_lines_are_skipped() {
+    let long = format!("0{} v1.0.0", "a".repeat(1 << 20));
+    let output = format!(
+        "{}\n0{} v2.0.0\n0syn v1.0.0\n0syn v2.0.0\n",
+        long,
+        "a".repeat(1 << 20)
+    );
+    let report = ConflictReport::from_tree(&output);
+
+    let names: Vec<&str> = report.conflicts.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["syn"]);
+}
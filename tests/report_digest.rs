@@ -0,0 +1,197 @@
+//! Golden-file tests of the Markdown digest `report --digest` writes.
+//! Run with `UPDATE_GOLDEN=1` to rewrite the files after an intended change.
+
+mod common;
+
+use cargo_sane::analyzer::attribution::Attribution;
+use cargo_sane::analyzer::checker::CheckReport;
+use cargo_sane::analyzer::conflicts::{Conflict, ConflictReport, ConflictVersion};
+use cargo_sane::analyzer::health::{AffectedPackage, HealthReport};
+use cargo_sane::analyzer::snapshot::Snapshot;
+use cargo_sane::cli::digest::render_digest;
+use cargo_sane::core::advisory::{Advisory, Severity};
+use cargo_sane::core::dependency::{Dependency, DependencySource};
+use common::{assert_golden, check_report, dep, NOW};
+use semver::Version;
+use std::path::PathBuf;
+
+const WEEK: u64 = 7 * 86_400;
+
+fn yanked(name: &str, current: &str) -> Dependency {
+    let mut dep = dep(name, current, current);
+    dep.yanked = true;
+    dep
+}
+
+/// `(package, version, advisory id, severity)`
+fn health(advisories: &[(&str, &str, &str, Option<Severity>)]) -> HealthReport {
+    HealthReport {
+        package: Some("demo".to_string()),
+        manifest: PathBuf::from("Cargo.toml"),
+        scanned: 40,
+        vulnerable: advisories
+            .iter()
+            .map(|(package, version, id, severity)| AffectedPackage {
+                name: package.to_string(),
+                version: Version::parse(version).unwrap(),
+                source: DependencySource::Registry,
+                attribution: Attribution::direct(package),
+                also_via: Vec::new(),
+                owner: None,
+                advisories: vec![Advisory {
+                    id: id.to_string(),
+                    package: package.to_string(),
+                    title: format!("Flaw in {}", package),
+                    severity: *severity,
+                    cvss: None,
+                    aliases: Vec::new(),
+                    patched_versions: Vec::new(),
+                    informational: None,
+                    url: String::new(),
+                }],
+            })
+            .collect(),
+        database: None,
+        system_libraries: Vec::new(),
+        forks: Vec::new(),
+        enrichment: None,
+        checksums: None,
+        embedded: Vec::new(),
+        build_time: None,
+        partial: false,
+        unchecked: 0,
+        ownership_changes: Vec::new(),
+        accepted: Vec::new(),
+        internal: Vec::new(),
+    }
+}
+
+fn conflicts(names: &[&str]) -> ConflictReport {
+    let version = |v: u64| ConflictVersion {
+        version: Version::new(v, 0, 0),
+        dependents: Vec::new(),
+        advisories: Vec::new(),
+        sources: Vec::new(),
+    };
+    ConflictReport {
+        conflicts: names
+            .iter()
+            .map(|name| Conflict {
+                name: name.to_string(),
+                versions: vec![version(1), version(2)],
+                security_relevant: false,
+            })
+            .collect(),
+        source_splits: Vec::new(),
+    }
+}
+
+/// A fixed tool version, so releases don't rewrite the golden files
+fn snapshot(
+    created_at: u64,
+    check: CheckReport,
+    health: Option<HealthReport>,
+    conflicts: Option<ConflictReport>,
+) -> Snapshot {
+    let mut snapshot = Snapshot::new(created_at, check, health, conflicts);
+    snapshot.tool_version = "1.2.3".to_string();
+    snapshot
+}
+
+fn last_week() -> Snapshot {
+    snapshot(
+        NOW - WEEK,
+        check_report(vec![
+            dep("serde", "1.0.100", "1.0.100"),
+            dep("clap", "4.0.0", "4.5.0"),
+            dep("tokio", "1.0.0", "1.38.0"),
+            dep("old", "0.1.0", "0.1.0"),
+            yanked("memchr", "2.0.0"),
+        ]),
+        Some(health(&[
+            (
+                "time",
+                "0.1.45",
+                "RUSTSEC-2020-0071",
+                Some(Severity::Medium),
+            ),
+            ("chrono", "0.4.19", "RUSTSEC-2020-0159", None),
+        ])),
+        Some(conflicts(&["syn", "bitflags"])),
+    )
+}
+
+fn this_week() -> Snapshot {
+    snapshot(
+        NOW,
+        check_report(vec![
+            dep("serde", "1.0.100", "1.0.200"),
+            dep("clap", "4.0.0", "4.5.0"),
+            dep("tokio", "1.38.0", "1.38.0"),
+            dep("anyhow", "1.0.0", "2.0.0"),
+            yanked("memchr", "2.0.0"),
+        ]),
+        Some(health(&[
+            (
+                "time",
+                "0.1.45",
+                "RUSTSEC-2020-0071",
+                Some(Severity::Medium),
+            ),
+            (
+                "anyhow",
+                "1.0.0",
+                "RUSTSEC-2024-0001",
+                Some(Severity::Critical),
+            ),
+        ])),
+        Some(conflicts(&["syn", "rand"])),
+    )
+}
+
+#[test]
+fn test_digest_of_changes() {
+    let (previous, current) = (last_week(), this_week());
+    let digest = render_digest(&current, Some(&previous), 15);
+    assert_eq!(digest, render_digest(&current, Some(&previous), 15));
+    assert_golden("digest.md", &digest);
+
+    // Nothing changed: only the still outstanding issues
+    let unchanged = render_digest(&current, Some(&current), 15);
+    assert!(
+        unchanged.contains("## New\n\nNothing new.\n"),
+        "{}",
+        unchanged
+    );
+    assert!(
+        unchanged.contains("## Resolved\n\nNothing resolved.\n"),
+        "{}",
+        unchanged
+    );
+    assert!(unchanged.contains("RUSTSEC-2024-0001"), "{}", unchanged);
+}
+
+#[test]
+fn test_first_digest_caps_outstanding() {
+    let mut current = this_week();
+    current.conflicts = None;
+    assert_golden("digest_first.md", &render_digest(&current, None, 3));
+}
+
+#[test]
+fn test_outstanding_by_team() {
+    let mut current = this_week();
+    current.conflicts = None;
+    for dep in &mut current.check.dependencies {
+        if dep.name == "clap" || dep.name == "anyhow" {
+            dep.owner = Some("cli".to_string());
+        }
+    }
+    for package in &mut current.health.as_mut().unwrap().vulnerable {
+        package.owner = Some(match package.name.as_str() {
+            "time" => "platform".to_string(),
+            _ => "cli".to_string(),
+        });
+    }
+    assert_golden("digest_teams.md", &render_digest(&current, None, 15));
+}
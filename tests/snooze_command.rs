@@ -0,0 +1,56 @@
+mod common;
+
+use cargo_sane::analyzer::snoozed::Snoozes;
+use cargo_sane::cli::commands;
+use common::manifest_arg;
+use semver::Version;
+
+#[test]
+fn test_snoozes_are_recorded_and_cleared() {
+    let dir = common::project("clap = \"4.5\"\nserde = \"1.0\"\n");
+    let snooze = |name: &str, version: &str, length: Option<&str>| {
+        commands::snooze_command(
+            manifest_arg(dir.path()),
+            Some(name.to_string()),
+            Some(version.to_string()),
+            length.map(str::to_string),
+            false,
+            false,
+        )
+    };
+
+    snooze("clap", "4.6.0", Some("30d")).unwrap();
+    snooze("serde", "1.0.300", None).unwrap();
+    assert!(snooze("serde", "latest", None).is_err());
+    assert!(snooze("serde", "1.0.300", Some("a month")).is_err());
+
+    let snoozes = Snoozes::load(dir.path()).unwrap();
+    let recorded: Vec<(&str, &Version, bool)> = snoozes
+        .snoozed
+        .iter()
+        .map(|s| (s.name.as_str(), &s.version, s.until.is_some()))
+        .collect();
+    assert_eq!(
+        recorded,
+        [
+            ("clap", &Version::new(4, 6, 0), true),
+            ("serde", &Version::new(1, 0, 300), false),
+        ]
+    );
+
+    let clear = |name: Option<&str>| {
+        commands::snooze_command(
+            manifest_arg(dir.path()),
+            name.map(str::to_string),
+            None,
+            None,
+            false,
+            true,
+        )
+    };
+    clear(Some("clap")).unwrap();
+    assert!(clear(Some("clap")).is_err());
+    assert_eq!(Snoozes::load(dir.path()).unwrap().snoozed.len(), 1);
+    clear(None).unwrap();
+    assert!(Snoozes::load(dir.path()).unwrap().is_empty());
+}
@@ -0,0 +1,132 @@
+//! Golden-file tests of how statuses read without color, by default and
+//! with `--symbols`, from runs under `--test-mode`.
+//! Run with `UPDATE_GOLDEN=1` to rewrite the files after an intended change.
+
+#![cfg(feature = "test-mode")]
+
+mod common;
+
+use common::assert_golden;
+use std::fs;
+use std::path::Path;
+
+const MANIFEST: &str = r#"[package]
+name = "fixture"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+bitflags = "1"
+nix = "0.20"
+serde = "1.0.200"
+syn = "2.0.10"
+"#;
+
+const LOCK: &str = r#"version = 3
+
+[[package]]
+name = "bitflags"
+version = "1.3.2"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "fixture"
+version = "0.1.0"
+dependencies = ["bitflags", "nix", "serde", "syn"]
+
+[[package]]
+name = "nix"
+version = "0.20.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "syn"
+version = "2.0.10"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+const SCENARIO: &str = r#"[[crates]]
+name = "bitflags"
+versions = ["2.4.0", "1.3.2"]
+
+[[crates]]
+name = "nix"
+versions = ["0.20.5", "0.20.0"]
+
+[[crates]]
+name = "serde"
+versions = ["1.0.200"]
+
+[[crates]]
+name = "syn"
+versions = ["2.1.0", "2.0.10"]
+
+[[advisories]]
+id = "RUSTSEC-2021-0119"
+package = "nix"
+title = "Out-of-bounds write in nix::unistd::getgrouplist"
+severity = "critical"
+patched = [">=0.20.2"]
+
+[[advisories]]
+id = "RUSTSEC-2023-0001"
+package = "syn"
+title = "Parser panics on malformed input"
+severity = "medium"
+patched = [">=2.0.11"]
+"#;
+
+/// The run's stdout, without the parts that differ between runs
+fn run(dir: &Path, args: &[&str]) -> String {
+    let output = common::cargo_sane_scripted(dir, args)
+        .args(["--progress", "none"])
+        .env("CARGO_SANE_ASCII", "0")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .filter(|line| !line.contains("Manifest:") && !line.contains("Advisory DB:"))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+fn project(config: Option<&str>) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("Cargo.toml"), MANIFEST).unwrap();
+    fs::write(dir.path().join("Cargo.lock"), LOCK).unwrap();
+    fs::write(dir.path().join("scenario.toml"), SCENARIO).unwrap();
+    if let Some(config) = config {
+        fs::write(dir.path().join(".cargo-sane.toml"), config).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_default_statuses() {
+    let dir = project(None);
+    assert_golden("check_default.txt", &run(dir.path(), &["check"]));
+    assert_golden("health_default.txt", &run(dir.path(), &["health"]));
+}
+
+#[test]
+fn test_symbol_statuses() {
+    let dir = project(None);
+    let check = run(dir.path(), &["check", "--symbols"]);
+    assert_golden("check_symbols.txt", &check);
+    assert_golden(
+        "health_symbols.txt",
+        &run(dir.path(), &["health", "--symbols"]),
+    );
+
+    // The config setting does what the flag does
+    let configured = project(Some(
+        "accessibility = \"symbols\"\npalette = \"colorblind\"\n",
+    ));
+    assert_eq!(run(configured.path(), &["check"]), check);
+}
@@ -0,0 +1,2002 @@
+//! End-to-end runs of the binary under `--test-mode`: the scenario stands
+//! in for the registry, advisories, cargo and the user, so these need
+//! neither the network nor a buildable project
+
+#![cfg(feature = "test-mode")]
+
+mod common;
+
+use common::{cargo_sane_scripted, git, stderr, stdout};
+use std::fs;
+use std::path::Path;
+
+const MANIFEST: &str = r#"[package]
+name = "fixture"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+bitflags = "2"
+nix = "0.20"
+syn = "2"
+"#;
+
+/// `(name, version, dependencies)` packages of a registry-only lockfile
+fn lockfile(packages: &[(&str, &str, &str)]) -> String {
+    let mut lock = String::from("version = 3\n");
+    for (name, version, dependencies) in packages {
+        lock.push_str(&format!(
+            "\n[[package]]\nname = \"{}\"\nversion = \"{}\"\n",
+            name, version
+        ));
+        if *name != "fixture" {
+            lock.push_str("source = \"registry+https://github.com/rust-lang/crates.io-index\"\n");
+        }
+        if !dependencies.is_empty() {
+            lock.push_str(&format!("dependencies = [{}]\n", dependencies));
+        }
+    }
+    lock
+}
+
+fn locked_duplicates() -> String {
+    lockfile(&[
+        ("bitflags", "1.3.2", ""),
+        ("bitflags", "2.4.0", ""),
+        (
+            "fixture",
+            "0.1.0",
+            r#""bitflags 2.4.0", "nix", "syn 2.0.48""#,
+        ),
+        ("nix", "0.20.0", r#""bitflags 1.3.2""#),
+        ("syn", "2.0.10", ""),
+        ("syn", "2.0.48", ""),
+    ])
+}
+
+fn project(manifest: &str, lock: &str, scenario: &str) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("Cargo.toml"), manifest).unwrap();
+    fs::write(dir.path().join("Cargo.lock"), lock).unwrap();
+    fs::write(dir.path().join("scenario.toml"), scenario).unwrap();
+    dir
+}
+
+fn audit_entries(dir: &Path) -> usize {
+    fs::read_to_string(dir.join(".cargo-sane/audit.log"))
+        .map(|log| log.lines().count())
+        .unwrap_or(0)
+}
+
+fn update_scenario(check_status: i32) -> String {
+    format!(
+        r##"answers = [["nix"]]
+
+[[crates]]
+name = "nix"
+versions = ["0.20.5", "0.20.0"]
+
+[[crates]]
+name = "syn"
+versions = ["2.0.48", "2.0.10"]
+
+[[crates]]
+name = "bitflags"
+versions = ["2.4.0", "1.3.2"]
+
+[[cargo]]
+args = ["check"]
+status = {}
+stderr = "error[E0308]: mismatched types"
+
+[cargo.writes]
+"Cargo.lock" = "# rewritten by cargo check\n"
+"##,
+        check_status
+    )
+}
+
+#[test]
+fn test_update_rolls_back_when_verification_fails() {
+    let lock = locked_duplicates();
+    let dir = project(MANIFEST, &lock, &update_scenario(101));
+
+    let output = cargo_sane_scripted(dir.path(), &["update", "--verify", "--yes"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{}", stdout(&output));
+    let (out, err) = (stdout(&output), stderr(&output));
+    assert!(
+        out.contains(
+            "Select dependencies to update (Space to select, Enter to confirm) · 🟢 PATCH nix"
+        ),
+        "{}",
+        out
+    );
+    assert!(out.contains("Apply these updates? · yes"), "{}", out);
+    assert!(out.contains("Updated nix"), "{}", out);
+    assert!(err.contains("mismatched types"), "{}", err);
+    assert!(err.contains("rolled back"), "{}", err);
+
+    // Both files are as they were, and nothing is on record
+    let manifest = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert_eq!(manifest, MANIFEST);
+    let locked = fs::read_to_string(dir.path().join("Cargo.lock")).unwrap();
+    assert_eq!(locked, lock);
+    assert_eq!(audit_entries(dir.path()), 0);
+}
+
+#[test]
+fn test_update_is_kept_when_verification_passes() {
+    let dir = project(MANIFEST, &locked_duplicates(), &update_scenario(0));
+
+    let output = cargo_sane_scripted(dir.path(), &["update", "--verify", "--yes"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("cargo check passed"));
+    let manifest = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert!(manifest.contains("nix = \"0.20.5\""), "{}", manifest);
+    assert_eq!(audit_entries(dir.path()), 1);
+
+    // --no declines the same updates without touching anything
+    let dir = project(MANIFEST, &locked_duplicates(), &update_scenario(0));
+    let output = cargo_sane_scripted(dir.path(), &["update", "--verify", "--no"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Update cancelled."));
+    let manifest = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert_eq!(manifest, MANIFEST);
+}
+
+#[test]
+fn test_fix_wizard_bumps_and_converges() {
+    // Bumping nix drops the old bitflags; converging syn leaves one of each
+    let bumped = lockfile(&[
+        ("bitflags", "2.4.0", ""),
+        ("fixture", "0.1.0", r#""bitflags", "nix", "syn 2.0.48""#),
+        ("nix", "0.27.1", r#""bitflags""#),
+        ("syn", "2.0.10", ""),
+        ("syn", "2.0.48", ""),
+    ]);
+    let converged = lockfile(&[
+        ("bitflags", "2.4.0", ""),
+        ("fixture", "0.1.0", r#""bitflags", "nix", "syn""#),
+        ("nix", "0.27.1", r#""bitflags""#),
+        ("syn", "2.0.48", ""),
+    ]);
+    let scenario = format!(
+        r#"answers = ["Bump", "nix", "Update the lockfile"]
+
+[[crates]]
+name = "nix"
+versions = ["0.27.1", "0.20.0"]
+
+[[cargo]]
+args = ["tree", "--duplicates"]
+stdout = """
+0bitflags v1.3.2
+1nix v0.20.0
+0bitflags v2.4.0
+1fixture v0.1.0 (/work/fixture)
+0syn v2.0.10
+1thiserror-impl v1.0.40
+0syn v2.0.48
+1fixture v0.1.0 (/work/fixture)
+"""
+
+[[cargo]]
+args = ["update", "--package", "nix"]
+writes = {{ "Cargo.lock" = '''{}''' }}
+
+[[cargo]]
+args = ["update", "--package", "syn@2.0.10", "--precise", "2.0.48"]
+writes = {{ "Cargo.lock" = '''{}''' }}
+"#,
+        bumped, converged
+    );
+    let dir = project(MANIFEST, &locked_duplicates(), &scenario);
+
+    let output = cargo_sane_scripted(dir.path(), &["fix"]).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("What should be done about bitflags? · Bump a direct dependency"),
+        "{}",
+        out
+    );
+    assert!(out.contains("Which dependency? · nix"), "{}", out);
+    assert!(out.contains("✓ syn v2.0.10 → v2.0.48"), "{}", out);
+    assert!(
+        out.contains("Every duplicated crate was handled."),
+        "{}",
+        out
+    );
+
+    let manifest = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert!(manifest.contains("nix = \"0.27.1\""), "{}", manifest);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("Cargo.lock")).unwrap(),
+        converged
+    );
+    // The bump and the lockfile update
+    assert_eq!(audit_entries(dir.path()), 2);
+}
+
+#[test]
+fn test_unanswered_prompts_and_unstubbed_cargo_fail() {
+    let scenario = r#"
+[[cargo]]
+args = ["tree", "--duplicates"]
+stdout = """
+0syn v2.0.10
+1thiserror-impl v1.0.40
+0syn v2.0.48
+1fixture v0.1.0 (/work/fixture)
+"""
+"#;
+    let dir = project(MANIFEST, &locked_duplicates(), scenario);
+    let output = cargo_sane_scripted(dir.path(), &["fix"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("no answer left for \"What should be done about syn?\""),
+        "{}",
+        stderr(&output)
+    );
+
+    // Not interactive: the duplicates join the plan, and applying it runs a
+    // cargo update the scenario has no stub for
+    let scenario = format!("interactive = false\n{}", scenario);
+    let dir = project(MANIFEST, &locked_duplicates(), &scenario);
+    let output = cargo_sane_scripted(dir.path(), &["fix", "--auto"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let err = stderr(&output);
+    assert!(
+        err.contains("no stub for `cargo update --package syn@2.0.10"),
+        "{}",
+        err
+    );
+    assert!(err.contains("1 of 1 planned change(s) failed"), "{}", err);
+}
+
+#[test]
+fn test_fix_auto_passes_when_only_incompatible_duplicates_remain() {
+    // syn converges; bitflags 1 and 2 need a new nix, which fix can't make
+    let converged = lockfile(&[
+        ("bitflags", "1.3.2", ""),
+        ("bitflags", "2.4.0", ""),
+        ("fixture", "0.1.0", r#""bitflags 2.4.0", "nix", "syn""#),
+        ("nix", "0.20.0", r#""bitflags 1.3.2""#),
+        ("syn", "2.0.48", ""),
+    ]);
+    let scenario = format!(
+        r#"interactive = false
+
+[[cargo]]
+args = ["tree", "--duplicates"]
+stdout = """
+0bitflags v1.3.2
+1nix v0.20.0
+0bitflags v2.4.0
+1fixture v0.1.0 (/work/fixture)
+0syn v2.0.10
+1thiserror-impl v1.0.40
+0syn v2.0.48
+1fixture v0.1.0 (/work/fixture)
+"""
+
+[[cargo]]
+args = ["update", "--package", "syn@2.0.10", "--precise", "2.0.48"]
+writes = {{ "Cargo.lock" = '''{}''' }}
+"#,
+        converged
+    );
+    let dir = project(MANIFEST, &locked_duplicates(), &scenario);
+
+    let output = cargo_sane_scripted(dir.path(), &["fix", "--auto"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(out.contains("✓ syn 2.0.10 → 2.0.48"), "{}", out);
+    assert!(
+        out.contains("Needs manual attention:\n  • bitflags 1.3.2"),
+        "{}",
+        out
+    );
+}
+
+#[test]
+fn test_saved_plan_fails_when_an_action_fails() {
+    let scenario = r#"interactive = false
+
+[[cargo]]
+args = ["tree", "--duplicates"]
+stdout = """
+0syn v2.0.10
+1thiserror-impl v1.0.40
+0syn v2.0.48
+1fixture v0.1.0 (/work/fixture)
+"""
+
+[[cargo]]
+args = ["update", "--package", "syn@2.0.10", "--precise", "2.0.48"]
+status = 101
+stderr = "error: failed to select a version for the requirement `syn = \"=2.0.10\"`"
+"#;
+    let dir = project(MANIFEST, &locked_duplicates(), scenario);
+    let output = cargo_sane_scripted(dir.path(), &["fix", "--dry-run", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let plan = dir.path().join("plan.json");
+    fs::write(&plan, &output.stdout).unwrap();
+
+    let output = cargo_sane_scripted(dir.path(), &["fix", &format!("--plan={}", plan.display())])
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{}", stdout(&output));
+    let err = stderr(&output);
+    assert!(err.contains("✗ Failed to update syn"), "{}", err);
+    assert!(err.contains("1 of 1 planned change(s) failed"), "{}", err);
+    assert!(!stdout(&output).contains("Done."), "{}", stdout(&output));
+
+    // Cargo.lock is part of what the plan was made for
+    fs::write(dir.path().join("Cargo.lock"), locked_duplicates() + "\n").unwrap();
+    let output = cargo_sane_scripted(dir.path(), &["fix", &format!("--plan={}", plan.display())])
+        .output()
+        .unwrap();
+    assert!(
+        stderr(&output).contains("or its Cargo.lock changed since the plan was made"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn test_fix_plans_lock_mismatches_and_runs_only_what_the_plan_holds() {
+    let manifest = MANIFEST.replace("nix = \"0.20\"", "nix = \"0.27\"");
+    let refreshed = lockfile(&[
+        ("bitflags", "1.3.2", ""),
+        ("bitflags", "2.4.0", ""),
+        (
+            "fixture",
+            "0.1.0",
+            r#""bitflags 2.4.0", "nix", "syn 2.0.48""#,
+        ),
+        ("nix", "0.27.1", r#""bitflags 1.3.2""#),
+        ("syn", "2.0.10", ""),
+        ("syn", "2.0.48", ""),
+    ]);
+    let scenario = format!(
+        r#"interactive = false
+
+[[cargo]]
+args = ["tree", "--duplicates"]
+stdout = """
+0syn v2.0.10
+1thiserror-impl v1.0.40
+0syn v2.0.48
+1fixture v0.1.0 (/work/fixture)
+"""
+
+[[cargo]]
+args = ["update", "--package", "nix@0.20.0"]
+writes = {{ "Cargo.lock" = '''{}''' }}
+"#,
+        refreshed
+    );
+    let dir = project(&manifest, &locked_duplicates(), &scenario);
+    let output = cargo_sane_scripted(dir.path(), &["fix", "--dry-run", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let mut plan: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let actions = plan["actions"].as_array().unwrap();
+    let nix = actions.iter().find(|a| a["crate"] == "nix").unwrap();
+    assert_eq!(nix["type"], "lockfile_update");
+    assert_eq!(nix["from_version"], "0.20.0");
+    assert!(nix["to_version"].is_null(), "{}", nix);
+    assert!(actions.iter().any(|a| a["crate"] == "syn"), "{}", plan);
+
+    // Taking syn out of the saved plan keeps it from being converged; the
+    // scenario has no stub for its update
+    plan["actions"]
+        .as_array_mut()
+        .unwrap()
+        .retain(|a| a["crate"] != "syn");
+    let path = dir.path().join("plan.json");
+    fs::write(&path, plan.to_string()).unwrap();
+    let output = cargo_sane_scripted(dir.path(), &["fix", &format!("--plan={}", path.display())])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(out.contains("✓ nix 0.20.0 → newest allowed"), "{}", out);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("Cargo.lock")).unwrap(),
+        refreshed
+    );
+    assert_eq!(audit_entries(dir.path()), 1);
+}
+
+#[test]
+fn test_seeded_failures_are_reproducible() {
+    let mut scenario = String::from("failure_rate = 0.5\n");
+    let names: Vec<String> = (0..12).map(|i| format!("crate{}", i)).collect();
+    let mut manifest = String::from(
+        "[package]\nname = \"fixture\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n",
+    );
+    let mut packages = vec![("fixture", "0.1.0", "")];
+    for name in &names {
+        manifest.push_str(&format!("{} = \"1.0\"\n", name));
+        scenario.push_str(&format!(
+            "\n[[crates]]\nname = \"{}\"\nversions = [\"1.1.0\", \"1.0.0\"]\n",
+            name
+        ));
+        packages.push((name, "1.0.0", ""));
+    }
+    let dir = project(&manifest, &lockfile(&packages), &scenario);
+
+    let checked = |seed: &str| {
+        let output = cargo_sane_scripted(dir.path(), &["check", "--json", "--seed", seed])
+            .output()
+            .unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let mut checked: Vec<String> = report["dependencies"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|d| d["latest_version"].is_string())
+            .map(|d| d["name"].as_str().unwrap().to_string())
+            .collect();
+        checked.sort();
+        checked
+    };
+    let first = checked("1");
+    assert!(
+        !first.is_empty() && first.len() < names.len(),
+        "{:?}",
+        first
+    );
+    assert_eq!(first, checked("1"));
+    assert_ne!(first, checked("2"));
+}
+
+/// A workspace whose `embedded` member inherits a rust-version older than
+/// the latest tokio supports
+fn msrv_workspace() -> tempfile::TempDir {
+    let dir = project(
+        "[workspace]\nmembers = [\"crates/*\"]\n\n[workspace.package]\nrust-version = \"1.65\"\n\n\
+         [workspace.dependencies]\ntokio = \"1.30\"\n",
+        "",
+        r#"[[crates]]
+name = "tokio"
+versions = ["1.38.0", "1.30.0"]
+rust_versions = { "1.38.0" = "1.70" }
+
+[[crates]]
+name = "serde"
+versions = ["1.0.200", "1.0.100"]
+"#,
+    );
+    for (member, manifest) in [
+        (
+            "embedded",
+            "[package]\nname = \"embedded\"\nversion = \"0.1.0\"\nrust-version.workspace = true\n\n\
+             [dependencies]\ntokio = { workspace = true }\n",
+        ),
+        (
+            "server",
+            "[package]\nname = \"server\"\nversion = \"0.1.0\"\nrust-version = \"1.75\"\n\n\
+             [dependencies]\ntokio = \"1.30\"\nserde = \"1.0.100\"\n",
+        ),
+    ] {
+        let path = dir.path().join("crates").join(member);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("Cargo.toml"), manifest).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_workspace_update_holds_back_releases_past_a_members_rust_version() {
+    let dir = msrv_workspace();
+    let server = dir.path().join("crates/server/Cargo.toml");
+
+    let output = cargo_sane_scripted(dir.path(), &["sane", "update", "--workspace", "--all"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("Not updating tokio: crates/embedded declares 1.65; tokio 1.38.0 needs 1.70"),
+        "{}",
+        out
+    );
+    assert!(out.contains("Held back tokio 1.38.0 for crates/embedded (1.65)"));
+    let manifest = fs::read_to_string(&server).unwrap();
+    assert!(manifest.contains("tokio = \"1.30\""));
+    assert!(manifest.contains("serde = \"1.0.200\""));
+
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &[
+            "sane",
+            "update",
+            "--workspace",
+            "--all",
+            "--ignore-rust-version",
+        ],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("crates/embedded (1.65) may not build with tokio 1.38.0"));
+    assert!(fs::read_to_string(&server)
+        .unwrap()
+        .contains("tokio = \"1.38.0\""));
+}
+
+#[test]
+fn test_fix_reports_duplicate_build_units() {
+    let metadata = r#"{"version": 1, "workspace_root": "/fixture",
+        "workspace_members": ["fixture 0.1.0"],
+        "packages": [
+            {"id": "fixture 0.1.0", "name": "fixture", "version": "0.1.0", "source": null,
+             "manifest_path": "/fixture/Cargo.toml", "targets": [{"name": "fixture", "kind": ["lib"]}]},
+            {"id": "syn 2.0.48", "name": "syn", "version": "2.0.48", "source": null,
+             "manifest_path": "/syn/Cargo.toml", "targets": [{"name": "syn", "kind": ["lib"]}]},
+            {"id": "derive 1.0.0", "name": "derive", "version": "1.0.0", "source": null,
+             "manifest_path": "/derive/Cargo.toml", "targets": [{"name": "derive", "kind": ["proc-macro"]}]}
+        ],
+        "resolve": {"root": "fixture 0.1.0", "nodes": [
+            {"id": "fixture 0.1.0", "deps": [
+                {"name": "syn", "pkg": "syn 2.0.48", "dep_kinds": [{"kind": null}]},
+                {"name": "derive", "pkg": "derive 1.0.0", "dep_kinds": [{"kind": null}]}]},
+            {"id": "syn 2.0.48", "deps": []},
+            {"id": "derive 1.0.0", "deps": [
+                {"name": "syn", "pkg": "syn 2.0.48", "dep_kinds": [{"kind": null}]}]}
+        ]}}"#;
+    let scenario = format!(
+        r#"[[cargo]]
+args = ["metadata"]
+stdout = '''{}'''
+
+[[cargo]]
+args = ["build", "--unit-graph"]
+status = 101
+stderr = "error: the `--unit-graph` flag is unstable"
+"#,
+        metadata
+    );
+    let dir = project(MANIFEST, &locked_duplicates(), &scenario);
+
+    let output = cargo_sane_scripted(dir.path(), &["fix", "--dry-run", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let plan: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let units = plan["duplicate_build_units"].as_array().unwrap();
+    assert_eq!(units.len(), 1, "{}", plan);
+    assert_eq!(units[0]["name"], "syn");
+    assert_eq!(units[0]["cause"], "proc_macro_host");
+    assert_eq!(units[0]["host_parents"][0], "derive");
+    assert_eq!(units[0]["confirmed"], false);
+
+    let output = cargo_sane_scripted(dir.path(), &["fix", "--dry-run"])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(out.contains("Duplicate build units:"), "{}", out);
+    assert!(
+        out.contains("syn v2.0.48 likely compiled twice (proc-macro host build for derive)"),
+        "{}",
+        out
+    );
+
+    // A metadata file that can't be used fails the run, even as JSON
+    let newer = dir.path().join("metadata.json");
+    fs::write(&newer, metadata.replace("\"version\": 1", "\"version\": 2")).unwrap();
+    let output = cargo_sane_scripted(dir.path(), &["fix", "--dry-run", "--json"])
+        .arg("--metadata-file")
+        .arg(&newer)
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).is_empty(), "{}", stdout(&output));
+    assert!(
+        stderr(&output).contains("--format-version 1"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn test_update_applies_exactly_the_answers_file() {
+    let dir = project(MANIFEST, &locked_duplicates(), &update_scenario(0));
+    let answers = dir.path().join("answers.toml");
+    let path = answers.to_str().unwrap();
+
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &["update", "--dry-run", "--write-answers", path],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let written = fs::read_to_string(&answers).unwrap();
+    assert!(written.contains("nix = \"0.20.5\""), "{}", written);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("Cargo.toml")).unwrap(),
+        MANIFEST
+    );
+
+    // The reviewer keeps nix and drops everything else
+    fs::write(&answers, "[updates]\nnix = \"0.20.5\"\n").unwrap();
+    let output = cargo_sane_scripted(dir.path(), &["update", "--answers", path])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(out.contains("Updated nix"), "{}", out);
+    assert!(!out.contains("Select dependencies"), "{}", out);
+    let manifest = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert!(manifest.contains("nix = \"0.20.5\""), "{}", manifest);
+    assert!(manifest.contains("bitflags = \"2\""), "{}", manifest);
+}
+
+#[test]
+fn test_update_refuses_answers_that_drifted() {
+    let dir = project(MANIFEST, &locked_duplicates(), &update_scenario(0));
+    let answers = dir.path().join("answers.toml");
+    fs::write(
+        &answers,
+        "[updates]\nnix = \"0.20.4\"\nrand = \"0.9.0\"\nsyn = \"skip\"\n",
+    )
+    .unwrap();
+
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &["update", "--answers", answers.to_str().unwrap()],
+    )
+    .output()
+    .unwrap();
+    assert!(!output.status.success());
+    let err = stderr(&output);
+    assert!(
+        err.contains("nix: approved at 0.20.4, but the latest is now 0.20.5"),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains("rand: approved at 0.9.0, but it has no update available"),
+        "{}",
+        err
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("Cargo.toml")).unwrap(),
+        MANIFEST
+    );
+    assert_eq!(audit_entries(dir.path()), 0);
+}
+
+#[test]
+fn test_download_sizes_of_updates() {
+    let scenario = update_scenario(0).replace(
+        "versions = [\"0.20.5\", \"0.20.0\"]\n",
+        "versions = [\"0.20.5\", \"0.20.0\"]\nsizes = { \"0.20.5\" = 281_600, \"0.20.0\" = 245_000 }\n",
+    );
+    let dir = project(MANIFEST, &locked_duplicates(), &scenario);
+
+    let output = cargo_sane_scripted(dir.path(), &["check", "--verbose"])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(out.contains("📦 245.0 kB → 281.6 kB"), "{}", out);
+
+    let output = cargo_sane_scripted(dir.path(), &["check", "--json"])
+        .output()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let nix = json["dependencies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["name"] == "nix")
+        .unwrap();
+    assert_eq!(nix["current_size"], 245_000);
+    assert_eq!(nix["latest_size"], 281_600);
+
+    let output = cargo_sane_scripted(dir.path(), &["update", "--all", "--dry-run"])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(
+        out.contains("Download size: +36.6 kB across 1 update"),
+        "{}",
+        out
+    );
+}
+
+#[test]
+fn test_update_all_leaves_out_what_deny_toml_bans() {
+    let dir = project(MANIFEST, &locked_duplicates(), &update_scenario(0));
+    fs::write(
+        dir.path().join("deny.toml"),
+        "[bans]\ndeny = [{ name = \"nix\", version = \">=0.20.5, <0.21\", reason = \"breaks musl\" }]\n",
+    )
+    .unwrap();
+
+    let output = cargo_sane_scripted(dir.path(), &["check"])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(
+        out.contains("⛔ banned by deny.toml (nix@>=0.20.5, <0.21: breaks musl)"),
+        "{}",
+        out
+    );
+
+    let output = cargo_sane_scripted(dir.path(), &["update", "--all", "--dry-run"])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(
+        out.contains("Not updating nix with --all: 0.20.5 is banned by deny.toml"),
+        "{}",
+        out
+    );
+    assert!(!out.contains("nix 0.20.0 → 0.20.5"), "{}", out);
+
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &["update", "--all", "--dry-run", "--ignore-deny"],
+    )
+    .output()
+    .unwrap();
+    let out = stdout(&output);
+    assert!(out.contains("nix 0.20.0 → 0.20.5"), "{}", out);
+}
+
+#[test]
+fn test_check_groups_updates_by_owning_team() {
+    let scenario = update_scenario(0).replace("2.0.48\", \"2.0.10", "2.1.0\", \"2.0.48");
+    let dir = project(MANIFEST, &locked_duplicates(), &scenario);
+    fs::create_dir(dir.path().join(".cargo-sane")).unwrap();
+    fs::write(
+        dir.path().join(".cargo-sane/owners.toml"),
+        "nix = \"platform\"\nsyn = \"macros\"\nrand = \"platform\"\n",
+    )
+    .unwrap();
+
+    let output = cargo_sane_scripted(dir.path(), &["check"])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(
+        out.contains("owners.toml assigns crates that aren't dependencies: rand"),
+        "{}",
+        out
+    );
+    let by_team = out.split("👥 Updates by team:").nth(1).unwrap();
+    let macros = by_team.find("macros (1)").unwrap();
+    let platform = by_team.find("platform (1)").unwrap();
+    assert!(macros < platform, "{}", out);
+    assert!(by_team.contains("MINOR syn 2.0.0 → 2.1.0"), "{}", out);
+    assert!(
+        out.contains("1 of 3 dependencies have no owning team"),
+        "{}",
+        out
+    );
+
+    let output = cargo_sane_scripted(dir.path(), &["check", "--json"])
+        .output()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let owners: Vec<(&str, Option<&str>)> = json["dependencies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| (d["name"].as_str().unwrap(), d["owner"].as_str()))
+        .collect();
+    assert!(owners.contains(&("nix", Some("platform"))), "{:?}", owners);
+    assert!(owners.contains(&("bitflags", None)), "{:?}", owners);
+}
+
+#[test]
+fn test_health_flags_lockfile_checksums_that_differ_from_the_index() {
+    const PUBLISHED: &str = "5b4a0c0d4f1e8e5b3b5c6a7e8f9d0a1b2c3d4e5f60718293a4b5c6d7e8f90a1b";
+    // One byte off from what crates.io publishes
+    const CORRUPTED: &str = "5b4a0c0d4f1e8e5b3b5c6a7e8f9d0a1b2c3d4e5f60718293a4b5c6d7e8f90a1c";
+    let lock = lockfile(&[
+        ("bitflags", "2.4.0", ""),
+        ("fixture", "0.1.0", r#""bitflags", "nix""#),
+        ("nix", "0.20.0", ""),
+    ])
+    .replace(
+        "name = \"bitflags\"\nversion = \"2.4.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        &format!(
+            "name = \"bitflags\"\nversion = \"2.4.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\nchecksum = \"{}\"\n",
+            PUBLISHED
+        ),
+    )
+    .replace(
+        "name = \"nix\"\nversion = \"0.20.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        &format!(
+            "name = \"nix\"\nversion = \"0.20.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\nchecksum = \"{}\"\n",
+            CORRUPTED
+        ),
+    );
+    let scenario = format!(
+        r#"[[crates]]
+name = "bitflags"
+versions = ["2.4.0"]
+checksums = {{ "2.4.0" = "{published}" }}
+
+[[crates]]
+name = "nix"
+versions = ["0.20.0"]
+checksums = {{ "0.20.0" = "{published}" }}
+"#,
+        published = PUBLISHED
+    );
+    let manifest = "[package]\nname = \"fixture\"\nversion = \"0.1.0\"\n\n[dependencies]\nbitflags = \"2\"\nnix = \"0.20\"\n";
+    let dir = project(manifest, &lock, &scenario);
+
+    let output = cargo_sane_scripted(dir.path(), &["health"])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(!output.status.success(), "{}", out);
+    assert!(
+        out.contains("🚨 Checksum mismatches with crates.io:"),
+        "{}",
+        out
+    );
+    assert!(out.contains("CRITICAL nix 0.20.0"), "{}", out);
+    assert!(
+        out.contains(&format!("Cargo.lock: {}", CORRUPTED)),
+        "{}",
+        out
+    );
+    assert!(out.contains("Checksums: 1 of 2 packages match"), "{}", out);
+
+    let output = cargo_sane_scripted(dir.path(), &["health", "--json"])
+        .output()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["checksums"]["verified"], 1);
+    assert_eq!(json["checksums"]["mismatches"][0]["name"], "nix");
+    assert_eq!(json["checksums"]["mismatches"][0]["published"], PUBLISHED);
+
+    // Offline, there's no index to compare with
+    let output = cargo_sane_scripted(dir.path(), &["health", "--offline", "--json"])
+        .output()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(json.get("checksums").is_none(), "{}", json);
+}
+
+/// A virtual workspace root whose only member, `fixture`, declares
+/// `dependencies`
+fn virtual_workspace(dependencies: &str, lock: &str, scenario: &str) -> tempfile::TempDir {
+    let dir = project("[workspace]\nmembers = [\"fixture\"]\n", lock, scenario);
+    let member = dir.path().join("fixture");
+    fs::create_dir_all(&member).unwrap();
+    fs::write(
+        member.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"fixture\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+            dependencies
+        ),
+    )
+    .unwrap();
+    dir
+}
+
+#[test]
+fn test_virtual_root_check_fails_on_a_members_lock_mismatch() {
+    let lock = lockfile(&[("fixture", "0.1.0", r#""serde""#), ("serde", "1.0.0", "")]);
+    let scenario = "[[crates]]\nname = \"serde\"\nversions = [\"2.0.0\", \"1.0.0\"]\n";
+    let dir = virtual_workspace("serde = \"2\"\n", &lock, scenario);
+
+    let output = cargo_sane_scripted(dir.path(), &["check"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{}", stdout(&output));
+    let out = stdout(&output);
+    assert!(out.contains("🔒 Cargo.lock out of date:"), "{}", out);
+    assert!(
+        out.contains("serde \"2\" [dependencies] (line 6), but Cargo.lock has 1.0.0"),
+        "{}",
+        out
+    );
+    assert!(
+        stderr(&output).contains("Cargo.lock doesn't satisfy 1 requirement"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = cargo_sane_scripted(dir.path(), &["check", "--json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let mismatches = &json["members"][0]["lock_mismatches"];
+    assert_eq!(mismatches[0]["name"], "serde", "{}", json);
+    assert_eq!(mismatches[0]["command"], "cargo update -p serde");
+}
+
+#[test]
+fn test_virtual_root_health_fails_on_a_checksum_mismatch() {
+    const PUBLISHED: &str = "5b4a0c0d4f1e8e5b3b5c6a7e8f9d0a1b2c3d4e5f60718293a4b5c6d7e8f90a1b";
+    const CORRUPTED: &str = "5b4a0c0d4f1e8e5b3b5c6a7e8f9d0a1b2c3d4e5f60718293a4b5c6d7e8f90a1c";
+    let lock = lockfile(&[("fixture", "0.1.0", r#""nix""#), ("nix", "0.20.0", "")]).replace(
+        "version = \"0.20.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        &format!(
+            "version = \"0.20.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\nchecksum = \"{}\"\n",
+            CORRUPTED
+        ),
+    );
+    let scenario = format!(
+        "[[crates]]\nname = \"nix\"\nversions = [\"0.20.0\"]\nchecksums = {{ \"0.20.0\" = \"{}\" }}\n",
+        PUBLISHED
+    );
+    let dir = virtual_workspace("nix = \"0.20\"\n", &lock, &scenario);
+
+    let output = cargo_sane_scripted(dir.path(), &["health"])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(!output.status.success(), "{}", out);
+    assert!(out.contains("cargo-sane health --workspace"), "{}", out);
+    assert!(out.contains("CRITICAL nix 0.20.0"), "{}", out);
+
+    let output = cargo_sane_scripted(dir.path(), &["health", "--json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        json["checksums"]["mismatches"][0]["name"], "nix",
+        "{}",
+        json
+    );
+}
+
+fn duplicates_scenario(interactive: bool, answers: &str) -> String {
+    format!(
+        r#"interactive = {}
+answers = [{}]
+
+[[crates]]
+name = "nix"
+versions = ["0.27.1", "0.20.0"]
+
+[[crates]]
+name = "syn"
+versions = ["2.0.48", "2.0.10"]
+
+[[crates]]
+name = "bitflags"
+versions = ["2.4.0", "1.3.2"]
+
+[[advisories]]
+id = "RUSTSEC-2021-0119"
+package = "nix"
+title = "Out-of-bounds write in nix::unistd::getgrouplist"
+severity = "high"
+patched = [">=0.20.2"]
+
+[[cargo]]
+args = ["tree", "--duplicates"]
+stdout = """
+0bitflags v1.3.2
+1nix v0.20.0
+0bitflags v2.4.0
+1fixture v0.1.0 (/work/fixture)
+0syn v2.0.10
+1thiserror-impl v1.0.40
+0syn v2.0.48
+1fixture v0.1.0 (/work/fixture)
+"""
+"#,
+        interactive, answers
+    )
+}
+
+#[test]
+fn test_tour_without_a_terminal_prints_every_step() {
+    let dir = project(
+        MANIFEST,
+        &locked_duplicates(),
+        &duplicates_scenario(false, ""),
+    );
+
+    let output = cargo_sane_scripted(dir.path(), &["tour"]).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    let steps: Vec<usize> = (1..=4)
+        .map(|n| {
+            out.find(&format!("Step {} of 4", n))
+                .unwrap_or_else(|| panic!("no step {}: {}", n, out))
+        })
+        .collect();
+    assert!(steps.windows(2).all(|w| w[0] < w[1]), "{}", out);
+    assert!(
+        out.contains("🟡 Minor (2): bitflags 2.0.0 → 2.4.0, nix 0.20.0 → 0.27.1"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("bitflags (1.3.2, 2.4.0), syn (2.0.10, 2.0.48)"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("nix 0.20.0: RUSTSEC-2021-0119 (high)"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("    ignore_conflicts = [\"bitflags\"]"),
+        "{}",
+        out
+    );
+    // Nothing to pause in or confirm with, and nothing written
+    assert!(!out.contains("Press enter"), "{}", out);
+    assert!(!dir.path().join(".cargo-sane.toml").exists());
+
+    // The groups are marked the way check marks them
+    let output = cargo_sane_scripted(dir.path(), &["tour", "--symbols"])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(out.contains("🟡 [MINOR] Minor (2): bitflags"), "{}", out);
+}
+
+#[test]
+fn test_tour_pauses_and_offers_to_write_the_settings() {
+    let dir = project(
+        MANIFEST,
+        &locked_duplicates(),
+        &duplicates_scenario(true, "true"),
+    );
+
+    let output = cargo_sane_scripted(dir.path(), &["tour"]).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert_eq!(out.matches("Press enter to continue").count(), 4, "{}", out);
+    assert!(
+        out.contains("Write these settings to .cargo-sane.toml? · yes"),
+        "{}",
+        out
+    );
+    let config = fs::read_to_string(dir.path().join(".cargo-sane.toml")).unwrap();
+    assert!(
+        config.contains("ignore_conflicts = [\"bitflags\"]"),
+        "{}",
+        config
+    );
+
+    // An existing config is left alone, without asking
+    let output = cargo_sane_scripted(dir.path(), &["tour"]).output().unwrap();
+    let out = stdout(&output);
+    assert!(out.contains("which the tour leaves as it is"), "{}", out);
+    assert!(!out.contains("Write these settings"), "{}", out);
+}
+
+#[test]
+fn test_output_files_are_written_alongside_the_console_view() {
+    let dir = project(
+        MANIFEST,
+        &locked_duplicates(),
+        &duplicates_scenario(false, ""),
+    );
+    let json = dir.path().join("check.json");
+    let markdown = dir.path().join("check.md");
+
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &[
+            "check",
+            "--output-file",
+            json.to_str().unwrap(),
+            "--output-file",
+            markdown.to_str().unwrap(),
+        ],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(out.contains("📊 Update Summary:"), "{}", out);
+    assert!(out.contains("JSON report written to"), "{}", out);
+    let document: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&json).unwrap()).unwrap();
+    assert_eq!(document["package"], "fixture");
+    assert_eq!(document["dependencies"].as_array().unwrap().len(), 3);
+    let markdown = fs::read_to_string(&markdown).unwrap();
+    assert!(
+        markdown.starts_with("# Dependency check: fixture\n"),
+        "{}",
+        markdown
+    );
+    assert!(
+        markdown.contains("- `nix` 0.20.0 → 0.27.1 (minor)\n"),
+        "{}",
+        markdown
+    );
+
+    // Formats given explicitly, in order, whatever the file names say
+    let csv = dir.path().join("health.out");
+    let markdown = dir.path().join("health.txt");
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &[
+            "health",
+            "--json",
+            "--output-file",
+            csv.to_str().unwrap(),
+            "--output-format",
+            "csv",
+            "--output-file",
+            markdown.to_str().unwrap(),
+            "--output-format",
+            "markdown",
+        ],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    // The console keeps the JSON document --json asked for, and nothing else
+    let console: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(console["vulnerable"][0]["name"], "nix");
+    let csv = fs::read_to_string(&csv).unwrap();
+    assert!(csv.contains("nix,0.20.0,RUSTSEC-2021-0119,high"), "{}", csv);
+    let markdown = fs::read_to_string(&markdown).unwrap();
+    assert!(
+        markdown.contains("- **HIGH** RUSTSEC-2021-0119 in `nix` 0.20.0"),
+        "{}",
+        markdown
+    );
+
+    // report writes JSON and a digest, but no CSV
+    let digest = dir.path().join("report.md");
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &["report", "--output-file", digest.to_str().unwrap()],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("📋 cargo-sane report"));
+    let digest = fs::read_to_string(&digest).unwrap();
+    assert!(
+        digest.starts_with("# Dependency digest: fixture\n"),
+        "{}",
+        digest
+    );
+    assert!(digest.contains("RUSTSEC-2021-0119"), "{}", digest);
+
+    let output = cargo_sane_scripted(dir.path(), &["report", "--output-file", "report.csv"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("report has no csv output (report.csv); it writes json, markdown"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn test_health_scan_embedded_finds_a_planted_crate() {
+    let scenario = r#"[[advisories]]
+id = "RUSTSEC-2019-0009"
+package = "smallvec"
+title = "Double-free and use-after-free in SmallVec::insert_many()"
+severity = "high"
+patched = [">=0.6.10"]
+"#;
+    let dir = project(MANIFEST, &locked_duplicates(), scenario);
+    let copy = dir.path().join("src/vendored/smallvec");
+    fs::create_dir_all(copy.join("src")).unwrap();
+    fs::write(
+        copy.join("Cargo.toml"),
+        "[package]\nname = \"smallvec\"\nversion = \"0.6.9\"\n",
+    )
+    .unwrap();
+    fs::write(copy.join("src/lib.rs"), "").unwrap();
+
+    // Only when asked for
+    let output = cargo_sane_scripted(dir.path(), &["health"])
+        .output()
+        .unwrap();
+    assert!(!stdout(&output).contains("embedded"), "{}", stdout(&output));
+
+    let output = cargo_sane_scripted(dir.path(), &["health", "--scan-embedded"])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(
+        out.contains("🧩 Likely embedded copies of crates (informational):"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("src/vendored/smallvec smallvec 0.6.9"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("Cargo.toml outside the workspace: src/vendored/smallvec/Cargo.toml"),
+        "{}",
+        out
+    );
+    assert!(out.contains("RUSTSEC-2019-0009"), "{}", out);
+
+    let output = cargo_sane_scripted(dir.path(), &["health", "--scan-embedded", "--json"])
+        .output()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let embedded = &json["embedded"][0];
+    assert_eq!(embedded["name"], "smallvec");
+    assert_eq!(embedded["version"], "0.6.9");
+    assert_eq!(embedded["markers"][0]["kind"], "nested_manifest");
+    assert_eq!(embedded["advisories"][0]["id"], "RUSTSEC-2019-0009");
+    // Informational: the embedded copy doesn't count as a vulnerable dependency
+    assert!(
+        json["vulnerable"].as_array().unwrap().is_empty(),
+        "{}",
+        json
+    );
+}
+
+#[test]
+fn test_intake_reviews_dependencies_added_since_a_revision() {
+    let before = format!("{}anyhow = \"1\"\n", MANIFEST);
+    let scenario = r#"[[crates]]
+name = "eyre"
+versions = ["0.6.12"]
+license = "MIT OR Apache-2.0"
+repository = "https://github.com/eyre-rs/eyre"
+owners = ["yaahc"]
+
+[[crates]]
+name = "time"
+versions = ["0.1.45"]
+license = "MIT"
+owners = ["jhpratt"]
+
+[[advisories]]
+id = "RUSTSEC-2020-0071"
+package = "time"
+title = "Potential segfault in the time crate"
+severity = "medium"
+patched = [">=0.2.23"]
+"#;
+    let lock = lockfile(&[
+        ("anyhow", "1.0.80", ""),
+        ("eyre", "0.6.12", r#""indenter", "once_cell""#),
+        (
+            "fixture",
+            "0.1.0",
+            r#""anyhow", "eyre", "once_cell", "time""#,
+        ),
+        ("indenter", "0.3.3", ""),
+        ("once_cell", "1.19.0", ""),
+        ("time", "0.1.45", ""),
+    ]);
+    let dir = project(&before, &lock, scenario);
+    git(dir.path(), &["init", "-q"]);
+    git(dir.path(), &["config", "user.name", "Alice"]);
+    git(dir.path(), &["config", "user.email", "alice@example.com"]);
+    git(dir.path(), &["add", "Cargo.toml"]);
+    git(dir.path(), &["commit", "-q", "-m", "init"]);
+
+    let output = cargo_sane_scripted(dir.path(), &["intake"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("No dependencies added."));
+
+    fs::write(
+        dir.path().join("Cargo.toml"),
+        format!(
+            "{}eyre = \"0.6\"\nonce_cell = \"1\"\ntime = \"0.1\"\n",
+            before
+        ),
+    )
+    .unwrap();
+    let output = cargo_sane_scripted(dir.path(), &["intake"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(out.contains("3 dependencies added."), "{}", out);
+    assert!(out.contains("## `eyre` 0.6.12"), "{}", out);
+    // once_cell is a direct dependency now, so only indenter comes with eyre
+    assert!(
+        out.contains("- [ ] Footprint: brings 1 other package into Cargo.lock: indenter 0.3.3"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("- [ ] Overlap: `anyhow` already covers error handling."),
+        "{}",
+        out
+    );
+    assert!(out.contains("- [ ] Owners: yaahc"), "{}", out);
+    assert!(
+        out.contains("- [ ] Advisories: 0.1.45 is affected by RUSTSEC-2020-0071"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("- [ ] Maintenance: no repository link"),
+        "{}",
+        out
+    );
+
+    let output = cargo_sane_scripted(dir.path(), &["intake", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["from"], "HEAD");
+    let names: Vec<&str> = json["added"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["eyre", "once_cell", "time"]);
+    assert_eq!(json["added"][2]["affecting"][0], "RUSTSEC-2020-0071");
+
+    let output = cargo_sane_scripted(dir.path(), &["intake", "--from", "no-such-rev"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("Unknown git revision"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn test_timeout_reports_partial_results_and_exits_by_policy() {
+    let dir = project(MANIFEST, &locked_duplicates(), &update_scenario(0));
+
+    // A timeout that has already passed refuses every lookup
+    let output = cargo_sane_scripted(dir.path(), &["--timeout", "0", "check", "--json"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(124), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["partial"], true);
+    assert_eq!(json["unchecked"], 3);
+    assert_eq!(json["dependencies"].as_array().unwrap().len(), 3);
+
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &["--timeout", "0", "--timeout-exit", "findings", "health"],
+    )
+    .output()
+    .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("Stopped at the --timeout: 3 packages not looked up"),
+        "{}",
+        out
+    );
+
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &["--timeout-exit", "error", "--timeout", "0", "check"],
+    )
+    .output()
+    .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stdout(&output).contains("3 dependencies not looked up"),
+        "{}",
+        stdout(&output)
+    );
+
+    // With time to spare nothing changes
+    let output = cargo_sane_scripted(dir.path(), &["--timeout", "60", "check", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(json.get("partial").is_none());
+}
+
+/// A scenario whose `cargo metadata` resolves `fixture` to `(name, target
+/// kinds, dependencies)` packages
+fn metadata_scenario(packages: &[(&str, &[&str], &[&str])]) -> String {
+    let package = |name: &str, kinds: &[&str]| {
+        serde_json::json!({
+            "id": name,
+            "name": name,
+            "version": "1.0.0",
+            "source": "registry+https://github.com/rust-lang/crates.io-index",
+            "manifest_path": format!("/registry/{}/Cargo.toml", name),
+            "targets": [{"name": name, "kind": kinds}],
+        })
+    };
+    let node = |name: &str, deps: &[&str]| {
+        let deps: Vec<_> = deps
+            .iter()
+            .map(|d| serde_json::json!({"name": d, "pkg": d}))
+            .collect();
+        serde_json::json!({"id": name, "deps": deps})
+    };
+    let mut packages_json = vec![package("fixture", &["lib"])];
+    let mut nodes = vec![node(
+        "fixture",
+        &packages
+            .iter()
+            .map(|(name, _, _)| *name)
+            .collect::<Vec<_>>(),
+    )];
+    for (name, kinds, deps) in packages {
+        packages_json.push(package(name, kinds));
+        nodes.push(node(name, deps));
+    }
+    let metadata = serde_json::json!({
+        "packages": packages_json,
+        "resolve": {"nodes": nodes, "root": "fixture"},
+        "workspace_members": ["fixture"],
+        "workspace_root": "/work",
+        "version": 1
+    });
+    format!(
+        "[[cargo]]\nargs = [\"metadata\"]\nstdout = '''{}'''\n",
+        metadata
+    )
+}
+
+#[test]
+fn test_new_build_time_code_fails_health_until_accepted() {
+    let before = metadata_scenario(&[
+        ("serde", &["lib", "custom-build"], &["serde_derive"]),
+        ("serde_derive", &["proc-macro"], &[]),
+        ("syn", &["lib"], &[]),
+    ]);
+    let dir = project(MANIFEST, &locked_duplicates(), &before);
+
+    let output = cargo_sane_scripted(dir.path(), &["health", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["build_time"]["total"], 3);
+    let listed: Vec<(&str, &str)> = json["build_time"]["packages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| (p["name"].as_str().unwrap(), p["code"][0].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        listed,
+        [("serde", "build_script"), ("serde_derive", "proc_macro")]
+    );
+
+    // Without a snapshot there's nothing to compare with
+    let output = cargo_sane_scripted(dir.path(), &["health", "--fail-on-new-build-scripts"])
+        .output()
+        .unwrap();
+    assert!(
+        stderr(&output).contains("No snapshot records build-time code"),
+        "{}",
+        stderr(&output)
+    );
+    let output = cargo_sane_scripted(dir.path(), &["snapshot", "save", "--tag", "reviewed"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // ring arrives with a build script of its own
+    let after = metadata_scenario(&[
+        ("serde", &["lib", "custom-build"], &["serde_derive"]),
+        ("serde_derive", &["proc-macro"], &[]),
+        ("syn", &["lib"], &["ring"]),
+        ("ring", &["lib", "custom-build"], &[]),
+    ]);
+    fs::write(dir.path().join("scenario.toml"), after).unwrap();
+    let output = cargo_sane_scripted(dir.path(), &["health", "--fail-on-new-build-scripts"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("Build-time code: 3 of 4 packages run build-time code"),
+        "{}",
+        out
+    );
+    assert!(out.contains("syn: ring (build script)"), "{}", out);
+    assert!(out.contains("New since snapshot reviewed: ring"), "{}", out);
+
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &["accept", "ring", "--build-script", "--reason", "audited"],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &["health", "--fail-on-new-build-scripts", "--json"],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        json["build_time"]["new_since_baseline"],
+        serde_json::json!([])
+    );
+}
+
+/// A virtual workspace of two members sharing tokio, with an advisory
+/// against each of its dependencies
+fn advised_workspace() -> tempfile::TempDir {
+    // The members are path packages, listed without a source
+    let members = "\n[[package]]\nname = \"embedded\"\nversion = \"0.1.0\"\n\
+                   dependencies = [\"tokio\"]\n\n[[package]]\nname = \"server\"\n\
+                   version = \"0.1.0\"\ndependencies = [\"serde\", \"tokio\"]\n";
+    let lock = lockfile(&[("serde", "1.0.100", ""), ("tokio", "1.30.0", "")]) + members;
+    let dir = project(
+        "[workspace]\nmembers = [\"crates/*\"]\n",
+        &lock,
+        r#"[[crates]]
+name = "tokio"
+versions = ["1.38.0", "1.30.0"]
+
+[[crates]]
+name = "serde"
+versions = ["1.0.200", "1.0.100"]
+
+[[advisories]]
+id = "RUSTSEC-2023-0001"
+package = "tokio"
+title = "reject_remote_clients configuration corruption"
+severity = "medium"
+patched = [">=1.38.0"]
+
+[[advisories]]
+id = "RUSTSEC-2023-0002"
+package = "serde"
+title = "Stack overflow on deeply nested input"
+severity = "high"
+patched = [">=1.0.200"]
+"#,
+    );
+    for (member, dependencies) in [
+        ("embedded", "tokio = \"1.30\"\n"),
+        ("server", "tokio = \"1.30\"\nserde = \"1.0.100\"\n"),
+    ] {
+        let path = dir.path().join("crates").join(member);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(
+            path.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+                member, dependencies
+            ),
+        )
+        .unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_virtual_manifest_is_analyzed_as_a_workspace() {
+    let dir = advised_workspace();
+
+    let output = cargo_sane_scripted(dir.path(), &["sane", "check", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let members: Vec<_> = json["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|member| member["name"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(members, ["embedded", "server"], "{}", json);
+
+    let output = cargo_sane_scripted(dir.path(), &["sane", "health", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let affected = |member: usize| -> Vec<String> {
+        json["members"][member]["vulnerable"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|package| package["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+    // tokio is looked up once for both members
+    assert_eq!(json["scanned"], 2, "{}", json);
+    assert_eq!(affected(0), ["tokio"], "{}", json);
+    assert_eq!(affected(1), ["serde", "tokio"], "{}", json);
+
+    let output = cargo_sane_scripted(dir.path(), &["sane", "health"])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(out.contains("📦 server (2 affected packages)"), "{}", out);
+
+    // Options for a single package need one
+    let output = cargo_sane_scripted(dir.path(), &["sane", "health", "--workspace", "--fix"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+/// A virtual workspace whose members inherit serde and tokio from
+/// `[workspace.dependencies]`, server adding a feature of its own
+fn inheriting_workspace() -> tempfile::TempDir {
+    let members = "\n[[package]]\nname = \"embedded\"\nversion = \"0.1.0\"\n\
+                   dependencies = [\"tokio\"]\n\n[[package]]\nname = \"server\"\n\
+                   version = \"0.1.0\"\ndependencies = [\"serde\", \"tokio\"]\n";
+    let lock = lockfile(&[("serde", "1.0.100", ""), ("tokio", "1.30.0", "")]) + members;
+    let dir = project(
+        "[workspace]\nmembers = [\"crates/*\"]\n\n\
+         [workspace.dependencies]\n\
+         serde = { version = \"1.0.100\", features = [\"std\"] }\n\n\
+         [workspace.dependencies.tokio]\nversion = \"1.30\"\n",
+        &lock,
+        r#"[[crates]]
+name = "tokio"
+versions = ["1.38.0", "1.30.0"]
+
+[[crates]]
+name = "serde"
+versions = ["1.0.200", "1.0.100"]
+"#,
+    );
+    for (member, dependencies) in [
+        (
+            "embedded",
+            "tokio = { workspace = true, optional = true }\n",
+        ),
+        (
+            "server",
+            "serde = { workspace = true, features = [\"derive\"] }\ntokio.workspace = true\n",
+        ),
+    ] {
+        let path = dir.path().join("crates").join(member);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(
+            path.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+                member, dependencies
+            ),
+        )
+        .unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_inherited_requirements_are_checked_and_updated_at_the_root() {
+    let dir = inheriting_workspace();
+    let server = dir.path().join("crates/server/Cargo.toml");
+    let server_before = fs::read_to_string(&server).unwrap();
+
+    let output = cargo_sane_scripted(dir.path(), &["sane", "check", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let server_deps = &json["members"][1]["dependencies"];
+    assert_eq!(server_deps[0]["name"], "serde", "{}", json);
+    assert_eq!(server_deps[0]["current_version"], "1.0.100", "{}", json);
+    assert_eq!(server_deps[0]["latest_version"], "1.0.200", "{}", json);
+    assert!(
+        server_deps[0]["inherited_from"]
+            .as_str()
+            .unwrap()
+            .ends_with("Cargo.toml"),
+        "{}",
+        json
+    );
+
+    // The member on its own still finds its workspace root
+    let output = assert_cmd::Command::cargo_bin("cargo-sane")
+        .unwrap()
+        .args(["sane", "check", "--manifest-path"])
+        .arg(&server)
+        .arg("--test-mode")
+        .arg(dir.path().join("scenario.toml"))
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("serde (workspace) 1.0.100 → 1.0.200"),
+        "{}",
+        out
+    );
+
+    let output = cargo_sane_scripted(dir.path(), &["sane", "update", "--all"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("([workspace.dependencies], line 5)"),
+        "{}",
+        out
+    );
+    // Both members inherit tokio, which is changed once
+    assert_eq!(out.matches("Updated tokio").count(), 1, "{}", out);
+
+    let root = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert!(
+        root.contains("serde = { version = \"1.0.200\", features = [\"std\"] }\n"),
+        "{}",
+        root
+    );
+    assert!(
+        root.contains("[workspace.dependencies.tokio]\nversion = \"1.38.0\"\n"),
+        "{}",
+        root
+    );
+    // The members' declarations, features and all, are left alone
+    assert_eq!(fs::read_to_string(&server).unwrap(), server_before);
+}
+
+#[test]
+fn test_target_specific_dependencies_are_checked_and_updated() {
+    let manifest = r#"[package]
+name = "fixture"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+bitflags = "2.4.0"
+
+[target.'cfg(windows)'.dependencies]
+winapi = { version = "0.3.5", features = ["winuser"] }
+
+[target.'cfg(unix)'.dependencies]
+nix = "0.27.0"
+
+[target.'cfg(unix)'.dev-dependencies]
+tempfile = "3.8.0"
+"#;
+    let dir = project(
+        manifest,
+        &lockfile(&[
+            ("bitflags", "2.4.0", ""),
+            ("nix", "0.27.0", ""),
+            ("tempfile", "3.8.0", ""),
+            ("winapi", "0.3.5", ""),
+        ]),
+        r#"[[crates]]
+name = "bitflags"
+versions = ["2.4.0"]
+
+[[crates]]
+name = "nix"
+versions = ["0.27.1", "0.27.0"]
+
+[[crates]]
+name = "tempfile"
+versions = ["3.10.1", "3.8.0"]
+
+[[crates]]
+name = "winapi"
+versions = ["0.3.9", "0.3.5"]
+"#,
+    );
+
+    let output = cargo_sane_scripted(dir.path(), &["sane", "check", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let deps = json["dependencies"].as_array().unwrap();
+    let dep = |name: &str| deps.iter().find(|d| d["name"] == name).unwrap().clone();
+    assert_eq!(dep("winapi")["target"], "cfg(windows)", "{}", json);
+    assert_eq!(dep("winapi")["latest_version"], "0.3.9", "{}", json);
+    assert_eq!(dep("winapi")["location"]["line"], 10, "{}", json);
+    assert_eq!(dep("nix")["target"], "cfg(unix)", "{}", json);
+    assert_eq!(dep("tempfile")["kind"], "dev", "{}", json);
+    assert_eq!(dep("tempfile")["target"], "cfg(unix)", "{}", json);
+    assert!(dep("bitflags").get("target").is_none(), "{}", json);
+
+    let output = cargo_sane_scripted(dir.path(), &["sane", "check"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("winapi (cfg(windows)) 0.3.5 → 0.3.9"),
+        "{}",
+        out
+    );
+    assert!(out.contains("nix (cfg(unix)) 0.27.0 → 0.27.1"), "{}", out);
+
+    let output = cargo_sane_scripted(dir.path(), &["sane", "update", "--all"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let updated = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert_eq!(
+        updated,
+        manifest
+            .replace("\"0.3.5\"", "\"0.3.9\"")
+            .replace("\"0.27.0\"", "\"0.27.1\"")
+            .replace("\"3.8.0\"", "\"3.10.1\"")
+    );
+}
+
+/// An executable script in `dir` standing in for a `report --plugin`
+/// program
+#[cfg(unix)]
+fn plugin(dir: &Path, name: &str, body: &str) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.join(name);
+    fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[cfg(unix)]
+#[test]
+fn test_report_merges_plugin_findings() {
+    let dir = project(MANIFEST, &locked_duplicates(), &update_scenario(0));
+    let input = dir.path().join("input.json");
+    let policy = plugin(
+        dir.path(),
+        "policy.sh",
+        &format!(
+            "cat > {}\necho '{{\"findings\": [{{\"code\": \"pinned-major\", \"severity\": \
+             \"warning\", \"message\": \"nix is two majors behind\", \"dependency\": \"nix\"}}]}}'",
+            input.display()
+        ),
+    );
+    let quiet = plugin(dir.path(), "quiet.sh", "echo '{\"findings\": []}'");
+
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &["report", "--json", "--plugin", &policy, "--plugin", &quiet],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let analyzers = json["analyzers"].as_array().unwrap();
+    assert_eq!(analyzers.len(), 2, "{}", json);
+    assert_eq!(analyzers[0]["analyzer"], "policy");
+    assert_eq!(analyzers[0]["program"], policy.as_str());
+    assert_eq!(analyzers[0]["findings"][0]["code"], "pinned-major");
+    assert_eq!(analyzers[0]["findings"][0]["dependency"], "nix");
+    assert_eq!(analyzers[1]["findings"], serde_json::json!([]));
+
+    // The plugin was fed the analysis on stdin
+    let sent: serde_json::Value = serde_json::from_slice(&fs::read(&input).unwrap()).unwrap();
+    assert_eq!(sent["protocol"], 1);
+    let names: Vec<&str> = sent["check"]["dependencies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|dep| dep["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["bitflags", "nix", "syn"]);
+
+    let output = cargo_sane_scripted(dir.path(), &["report", "--plugin", &policy])
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert!(out.contains("🧩 Analyzer findings:"), "{}", out);
+    assert!(
+        out.contains("warning: pinned-major nix: nix is two majors behind"),
+        "{}",
+        out
+    );
+
+    // Findings count toward --fail-on like any other
+    let fail_on = |threshold: &str| {
+        cargo_sane_scripted(
+            dir.path(),
+            &[
+                "report",
+                "--json",
+                "--plugin",
+                &policy,
+                "--fail-on",
+                threshold,
+            ],
+        )
+        .output()
+        .unwrap()
+        .status
+        .code()
+    };
+    assert_eq!(fail_on("warning"), Some(1));
+    assert_eq!(fail_on("error"), Some(0));
+
+    let markdown = dir.path().join("report.md");
+    let output = cargo_sane_scripted(
+        dir.path(),
+        &[
+            "report",
+            "--plugin",
+            &policy,
+            "--output-file",
+            markdown.to_str().unwrap(),
+        ],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let markdown = fs::read_to_string(markdown).unwrap();
+    assert!(
+        markdown.contains(
+            "## Analyzer findings\n\n### policy\n\n\
+             - **WARNING** `pinned-major` in `nix`: nix is two majors behind\n"
+        ),
+        "{}",
+        markdown
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_report_rejects_malformed_plugin_output() {
+    let dir = project(MANIFEST, &locked_duplicates(), &update_scenario(0));
+    let cases = [
+        ("garbled", "echo 'findings: none'", "doesn't match the plugin output schema"),
+        (
+            "unknown-severity",
+            "echo '{\"findings\": [{\"code\": \"x\", \"severity\": \"fatal\", \"message\": \"m\"}]}'",
+            "doesn't match the plugin output schema",
+        ),
+        (
+            "crashed",
+            "echo 'cannot reach the internal registry' >&2\nexit 3",
+            "cannot reach the internal registry",
+        ),
+    ];
+    for (name, body, expected) in cases {
+        let program = plugin(dir.path(), name, body);
+
+        let output = cargo_sane_scripted(dir.path(), &["report", "--json", "--plugin", &program])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", stderr(&output));
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let analyzer = &json["analyzers"][0];
+        assert_eq!(analyzer["findings"], serde_json::json!([]), "{}", json);
+        let error = analyzer["error"].as_str().unwrap();
+        assert!(error.contains(expected), "{}: {}", name, error);
+
+        // A failed check never passes a gate
+        let output = cargo_sane_scripted(
+            dir.path(),
+            &["report", "--plugin", &program, "--fail-on", "error"],
+        )
+        .output()
+        .unwrap();
+        assert_eq!(output.status.code(), Some(1), "{}", name);
+        assert!(stdout(&output).contains(&format!("{} failed:", name)));
+    }
+}
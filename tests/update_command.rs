@@ -1 +1,145 @@
+mod common;
 
+use cargo_sane::cli::commands;
+use cargo_sane::core::dependency::{Dependency, DependencyKind};
+use cargo_sane::core::manifest::{DependencySection, Manifest};
+use cargo_sane::updater::plan::Plan;
+use cargo_sane::updater::DependencyUpdater;
+use semver::Version;
+use std::fs;
+
+/// Copy a fixture into a scratch project, with its manifest's bytes
+fn fixture(name: &str) -> (tempfile::TempDir, Vec<u8>) {
+    let dir = common::copy_fixture(name);
+    let bytes = fs::read(dir.path().join("Cargo.toml")).unwrap();
+    (dir, bytes)
+}
+
+fn update(manifest: Manifest) {
+    let serde = Dependency::new("serde".to_string(), Version::new(1, 0, 100), true);
+    let mut updater = DependencyUpdater::new(manifest).unwrap();
+    updater.update_dependency(&serde, "1.0.200").unwrap();
+    updater
+        .update_declaration(
+            &DependencySection::new(DependencyKind::Normal),
+            "log",
+            "0.4.22",
+        )
+        .unwrap();
+    updater.save().unwrap();
+}
+
+fn expected(original: &[u8]) -> Vec<u8> {
+    String::from_utf8(original.to_vec())
+        .unwrap()
+        .replace("\"1.0.100\"", "\"1.0.200\"")
+        .replace("\"0.4.10\"", "\"0.4.22\"")
+        .into_bytes()
+}
+
+#[test]
+fn test_bom_manifest_parses_and_round_trips() {
+    let (dir, original) = fixture("bom");
+    let path = dir.path().join("Cargo.toml");
+
+    let manifest = Manifest::from_path(&path).unwrap();
+    assert_eq!(manifest.package_name(), Some("bom-fixture"));
+    assert_eq!(
+        manifest.location_of("serde", DependencyKind::Normal),
+        Some((7, 1))
+    );
+
+    update(manifest);
+    let saved = fs::read(&path).unwrap();
+    assert!(saved.starts_with(b"\xEF\xBB\xBF[package]\n"));
+    assert_eq!(saved, expected(&original));
+}
+
+#[test]
+fn test_crlf_manifest_parses_and_round_trips() {
+    let (dir, original) = fixture("crlf");
+    let path = dir.path().join("Cargo.toml");
+
+    let manifest = Manifest::from_path(&path).unwrap();
+    assert_eq!(manifest.package_name(), Some("crlf-fixture"));
+    assert_eq!(
+        manifest.location_of("log", DependencyKind::Normal),
+        Some((8, 1))
+    );
+
+    update(manifest);
+    let saved = fs::read(&path).unwrap();
+    assert_eq!(saved, expected(&original));
+
+    let mut updater = DependencyUpdater::new(Manifest::from_path(&path).unwrap()).unwrap();
+    updater
+        .remove_declaration(&DependencySection::new(DependencyKind::Dev), "tempfile")
+        .unwrap();
+    assert!(updater
+        .get_content()
+        .ends_with("features = [\"std\"] }\r\n\r\n[dev-dependencies]\r\n"));
+}
+
+#[test]
+fn test_invalid_utf8_manifest_reports_position() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("Cargo.toml");
+    fs::write(
+        &path,
+        b"[package]\nname = \"latin1\"\n# Auteur: Ren\xE9\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+
+    let error = format!("{:#}", Manifest::from_path(&path).unwrap_err());
+    assert!(error.contains("line 3, column 14"), "{}", error);
+    assert!(error.contains("must be UTF-8"), "{}", error);
+}
+
+#[test]
+fn test_format_dependencies_keeps_line_endings_and_backs_up() {
+    let (dir, original) = fixture("crlf");
+    let path = dir.path().join("Cargo.toml");
+
+    let mut updater = DependencyUpdater::new(Manifest::from_path(&path).unwrap()).unwrap();
+    assert!(updater.format_dependencies(4).unwrap());
+    let backup = updater.save().unwrap();
+
+    assert_eq!(fs::read(&backup).unwrap(), original);
+    let saved = String::from_utf8(fs::read(&path).unwrap()).unwrap();
+    assert!(saved.contains(
+        "[dependencies]\r\nlog = { version = \"0.4.10\", features = [\"std\"] }\r\nserde = \"1.0.100\"\r\n"
+    ));
+    assert!(!saved.replace("\r\n", "").contains('\n'));
+
+    // Formatting again finds nothing to do
+    let mut updater = DependencyUpdater::new(Manifest::from_path(&path).unwrap()).unwrap();
+    assert!(!updater.format_dependencies(4).unwrap());
+}
+
+#[test]
+fn test_update_plan_applies_verbatim_and_detects_drift() {
+    let (dir, original) = fixture("crlf");
+    let path = dir.path().join("Cargo.toml");
+    let plan_path = dir.path().join("plan.json");
+
+    let serde = Dependency::new("serde".to_string(), Version::new(1, 0, 100), true)
+        .with_latest(Version::new(1, 0, 200));
+    let log = Dependency::new("log".to_string(), Version::new(0, 4, 10), true)
+        .with_latest(Version::new(0, 4, 22));
+    let plan = Plan::for_updates(&Manifest::from_path(&path).unwrap(), &[&serde, &log]).unwrap();
+    fs::write(&plan_path, serde_json::to_string_pretty(&plan).unwrap()).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), original);
+
+    let saved = Plan::load(&plan_path).unwrap();
+    assert_eq!(saved.actions, plan.actions);
+    assert_eq!(saved.actions[0].name, "log");
+    assert_eq!(saved.actions[0].reason, "latest");
+
+    commands::apply_command(plan_path.clone(), None).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), expected(&original));
+
+    // Cargo.toml no longer matches, so the same plan is refused
+    let err = commands::apply_command(plan_path, None).unwrap_err();
+    assert!(err.to_string().contains("changed since the plan was made"));
+    assert_eq!(fs::read(&path).unwrap(), expected(&original));
+}
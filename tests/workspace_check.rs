@@ -0,0 +1,219 @@
+mod common;
+
+use cargo_sane::analyzer::checker::DependencyChecker;
+use cargo_sane::cli::commands;
+use cargo_sane::core::dependency::UpdateType;
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::core::workspace::Workspace;
+use cargo_sane::utils::crates_io::CratesIoClient;
+use cargo_sane::utils::progress::CapturedProgress;
+use common::{block_on, MockRegistry};
+use semver::Version;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn three_member_workspace() -> tempfile::TempDir {
+    common::workspace(&[
+        (
+            "app",
+            "serde = \"1.0\"\nanyhow = \"1.0.80\"\nclap = \"3\"\n",
+        ),
+        ("core", "serde = \"1.0.150\"\nlog = \"0.4\"\n"),
+        (
+            "web",
+            "serde = \"1.0\"\nlog = \"0.4\"\nanyhow = \"1.0.80\"\n",
+        ),
+    ])
+}
+
+#[test]
+fn test_workspace_rollup() {
+    let registry = MockRegistry::start(
+        &[
+            ("serde", "1.0.200"),
+            ("anyhow", "1.0.80"),
+            ("clap", "4.5.0"),
+            ("log", "0.4.22"),
+        ],
+        Duration::from_millis(5),
+    );
+    let fixture = three_member_workspace();
+    let root = Manifest::from_path(&fixture.path().join("Cargo.toml")).unwrap();
+    let workspace = Workspace::load(root).unwrap().unwrap();
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    );
+    let report = block_on(checker.check_workspace(&workspace)).unwrap();
+
+    // Each crate is looked up once, however many members declare it
+    assert_eq!(registry.requests(), 4);
+
+    let members: Vec<&str> = report.members.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(members, vec!["app", "core", "web"]);
+    let app = &report.members[0].summary;
+    assert_eq!((app.total, app.patch, app.minor, app.major), (3, 1, 0, 1));
+
+    let serde = report.crates.iter().find(|c| c.name == "serde").unwrap();
+    assert_eq!(serde.latest_version, Some(Version::new(1, 0, 200)));
+    let requirements: Vec<(&str, Option<&str>)> = serde
+        .declared_by
+        .iter()
+        .map(|d| (d.member.as_str(), d.requirement.as_deref()))
+        .collect();
+    assert_eq!(
+        requirements,
+        vec![
+            ("app", Some("1.0")),
+            ("core", Some("1.0.150")),
+            ("web", Some("1.0"))
+        ]
+    );
+    assert!(serde.requirements_diverge());
+
+    let outdated: Vec<&str> = report.outdated().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(outdated, vec!["clap", "log", "serde"]);
+    let clap = report.crates.iter().find(|c| c.name == "clap").unwrap();
+    assert_eq!(clap.update_type(), UpdateType::Major);
+
+    let anyhow = report.crates.iter().find(|c| c.name == "anyhow").unwrap();
+    assert!(!anyhow.has_update());
+    assert!(!anyhow.requirements_diverge());
+
+    let core = report.scoped_to("core").unwrap();
+    let crates: Vec<&str> = core.crates.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(crates, vec!["log", "serde"]);
+}
+
+#[test]
+fn test_workspace_json_nests_members_and_crates() {
+    let registry = MockRegistry::start(&[("serde", "1.0.200")], Duration::from_millis(1));
+    let fixture = three_member_workspace();
+    let root = Manifest::from_path(&fixture.path().join("Cargo.toml")).unwrap();
+    let workspace = Workspace::load(root).unwrap().unwrap();
+
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    );
+    let report = block_on(checker.check_workspace(&workspace)).unwrap();
+    let json = serde_json::to_value(&report).unwrap();
+
+    assert_eq!(json["members"].as_array().unwrap().len(), 3);
+    assert_eq!(json["members"][1]["name"], "core");
+    assert_eq!(json["members"][1]["summary"]["total"], 2);
+    let serde = json["crates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["name"] == "serde")
+        .unwrap();
+    assert_eq!(serde["declared_by"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn test_each_crate_is_fetched_once_across_members() {
+    let pool = ["serde", "anyhow", "log", "regex", "tokio", "rand"];
+    let registry = MockRegistry::start(
+        &[
+            ("serde", "1.0.200"),
+            ("anyhow", "1.0.86"),
+            ("log", "0.4.22"),
+            ("regex", "1.10.5"),
+            ("tokio", "1.38.0"),
+            ("rand", "0.8.5"),
+        ],
+        Duration::from_millis(5),
+    );
+    // Ten members, each declaring three neighbouring crates of the pool
+    let members: Vec<(String, String)> = (0..10)
+        .rev()
+        .map(|i| {
+            let declarations: String = (0..3)
+                .map(|j| format!("{} = \"0.1\"\n", pool[(i + j) % pool.len()]))
+                .collect();
+            (format!("member-{:02}", i), declarations)
+        })
+        .collect();
+    let members: Vec<(&str, &str)> = members
+        .iter()
+        .map(|(name, declarations)| (name.as_str(), declarations.as_str()))
+        .collect();
+    let fixture = common::workspace(&members);
+    let root = Manifest::from_path(&fixture.path().join("Cargo.toml")).unwrap();
+    let workspace = Workspace::load(root).unwrap().unwrap();
+
+    let progress = Arc::new(CapturedProgress::default());
+    let checker = DependencyChecker::with_provider(
+        CratesIoClient::with_base_url(&registry.base_url).unwrap(),
+    )
+    .with_progress(progress.clone());
+    let report = block_on(checker.check_workspace(&workspace)).unwrap();
+
+    // The savings, then each member as it's done
+    let events = progress.events();
+    let members_started = events
+        .iter()
+        .position(|e| e == "start 10 Fetched 6 unique crates for 10 members")
+        .unwrap_or_else(|| panic!("{:?}", events));
+    let members_done = events[members_started..]
+        .iter()
+        .filter(|e| e.starts_with("message Checked member-"))
+        .count();
+    assert_eq!(members_done, 10, "{:?}", events);
+
+    for name in pool {
+        assert_eq!(registry.requests_for(name), 1, "{}", name);
+    }
+    assert_eq!(registry.requests(), pool.len());
+
+    let names: Vec<&str> = report.members.iter().map(|m| m.name.as_str()).collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names.len(), 10);
+    assert_eq!(names, sorted);
+    assert!(report
+        .members
+        .iter()
+        .flat_map(|m| &m.dependencies)
+        .all(|d| d.latest_version.is_some()));
+}
+
+#[test]
+fn test_lint_unifies_features_across_members() {
+    let fixture = common::workspace(&[
+        (
+            "api",
+            "serde = { version = \"1.0.150\", features = [\"derive\"] }\nlog = \"0.4\"\n",
+        ),
+        ("cli", "serde = \"1.0\"\nlog = \"0.4\"\n"),
+    ]);
+    let root = fixture.path().join("Cargo.toml");
+    let manifest_arg = Some(root.display().to_string());
+
+    // The members disagree on serde's features, so lint fails
+    assert!(!commands::lint_command(manifest_arg.clone(), false, false, true).unwrap());
+
+    assert!(commands::lint_command(manifest_arg.clone(), false, true, true).unwrap());
+    let root_toml = std::fs::read_to_string(&root).unwrap();
+    assert!(
+        root_toml.contains(
+            "[workspace.dependencies]\nserde = { version = \"1.0.150\", features = [\"derive\"] }"
+        ),
+        "{}",
+        root_toml
+    );
+    for member in ["api", "cli"] {
+        let path = fixture
+            .path()
+            .join("crates")
+            .join(member)
+            .join("Cargo.toml");
+        let toml = std::fs::read_to_string(path).unwrap();
+        assert!(toml.contains("serde = { workspace = true }"), "{}", toml);
+        // Consistent declarations are left alone
+        assert!(toml.contains("log = \"0.4\""), "{}", toml);
+    }
+
+    // Inherited declarations agree by construction
+    assert!(commands::lint_command(manifest_arg, false, false, true).unwrap());
+}
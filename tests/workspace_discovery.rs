@@ -0,0 +1,77 @@
+//! `--no-workspace-discovery` is process-wide, so this file keeps to one test
+
+mod common;
+
+use cargo_sane::core::lockfile::Lockfile;
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::core::workspace::Workspace;
+use cargo_sane::utils::cargo::{self, CargoOptions};
+use common::write_package;
+use std::fs;
+use std::path::Path;
+
+fn lock(dir: &Path, name: &str) {
+    fs::write(
+        dir.join("Cargo.lock"),
+        format!(
+            "version = 3\n\n[[package]]\nname = \"{}\"\nversion = \"0.1.0\"\n",
+            name
+        ),
+    )
+    .unwrap();
+}
+
+/// A workspace with one member and one excluded package below it
+fn workspace_with_excluded_member() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("Cargo.toml"),
+        "[workspace]\nmembers = [\"member\"]\nexclude = [\"excluded\"]\nresolver = \"2\"\n",
+    )
+    .unwrap();
+    write_package(&dir.path().join("member"), "member");
+    write_package(&dir.path().join("excluded"), "excluded");
+    lock(dir.path(), "member");
+    lock(&dir.path().join("member"), "member");
+    dir
+}
+
+#[test]
+fn test_workspace_context_and_standalone_treatment() {
+    let fixture = workspace_with_excluded_member();
+    let root = fixture.path().canonicalize().unwrap();
+    let member = Manifest::from_path(&root.join("member/Cargo.toml")).unwrap();
+    let excluded = Manifest::from_path(&root.join("excluded/Cargo.toml")).unwrap();
+    let options = CargoOptions::default();
+
+    // Cargo resolves the member in the workspace, the excluded package alone
+    assert_eq!(
+        cargo::enclosing_workspace(&member.path, &options).unwrap(),
+        Some(root.join("Cargo.toml"))
+    );
+    assert_eq!(
+        cargo::enclosing_workspace(&excluded.path, &options).unwrap(),
+        None
+    );
+    assert_eq!(
+        Lockfile::for_manifest(&member).unwrap().unwrap().path,
+        root.join("Cargo.lock")
+    );
+    assert!(Lockfile::for_manifest(&excluded).unwrap().is_none());
+
+    Workspace::set_standalone();
+
+    // The member's own lockfile now, and no cargo run that would disagree
+    assert_eq!(
+        Lockfile::for_manifest(&member).unwrap().unwrap().path,
+        root.join("member/Cargo.lock")
+    );
+    let error = cargo::metadata(&member.path, &options).unwrap_err();
+    assert!(
+        error.to_string().contains("--no-workspace-discovery"),
+        "{}",
+        error
+    );
+    let metadata = cargo::metadata(&excluded.path, &options).unwrap();
+    assert!(metadata.package_for_manifest(&excluded.path).is_some());
+}
@@ -0,0 +1,199 @@
+mod common;
+
+use cargo_sane::cli::commands;
+use cargo_sane::core::manifest::Manifest;
+use cargo_sane::core::workspace::Workspace;
+use common::copy_fixture;
+use semver::{Version, VersionReq};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// What a declaration builds with once inheritance is resolved
+#[derive(Debug, PartialEq, Eq)]
+struct Effective {
+    requirement: String,
+    features: BTreeSet<String>,
+    default_features: bool,
+    optional: bool,
+}
+
+/// Every member's declarations by `(member, section, name)`
+fn effective(root: &Path) -> BTreeMap<(String, String, String), Effective> {
+    let workspace = Workspace::load(Manifest::from_path(&root.join("Cargo.toml")).unwrap())
+        .unwrap()
+        .unwrap();
+    let inherited = &workspace
+        .root
+        .content
+        .workspace
+        .as_ref()
+        .unwrap()
+        .dependencies;
+    let mut found = BTreeMap::new();
+    for manifest in &workspace.members {
+        for (section, name, spec) in manifest.declarations() {
+            let base = if spec.inherits_workspace() {
+                &inherited[&name]
+            } else {
+                &spec
+            };
+            let mut features: BTreeSet<String> = base.features().iter().cloned().collect();
+            features.extend(spec.features().iter().cloned());
+            found.insert(
+                (
+                    Workspace::member_name(manifest),
+                    section.to_string(),
+                    name.clone(),
+                ),
+                Effective {
+                    requirement: base
+                        .version()
+                        .or(base.git().map(|(url, _)| url))
+                        .unwrap()
+                        .to_string(),
+                    features,
+                    default_features: base.default_features(),
+                    optional: spec.is_optional(),
+                },
+            );
+        }
+    }
+    found
+}
+
+/// The lowest version a plain requirement like `1.10` accepts
+fn lowest(requirement: &str) -> Version {
+    let mut parts: Vec<&str> = requirement.split('.').collect();
+    parts.resize(3, "0");
+    Version::parse(&parts.join(".")).unwrap()
+}
+
+fn suggest(dir: &Path, apply: bool) {
+    commands::fix_command(
+        Some(dir.join("Cargo.toml").to_string_lossy().to_string()),
+        false,
+        false,
+        false,
+        None,
+        None,
+        true,
+        apply,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_suggestions_leave_the_workspace_alone() {
+    let dir = copy_fixture("inherit-workspace");
+    let before = fs::read_to_string(dir.path().join("crates/api/Cargo.toml")).unwrap();
+    suggest(dir.path(), false);
+    let after = fs::read_to_string(dir.path().join("crates/api/Cargo.toml")).unwrap();
+    assert_eq!(before, after);
+    assert!(!dir.path().join("Cargo.toml.backup").exists());
+}
+
+#[test]
+fn test_applied_inheritance_keeps_every_member_building_the_same() {
+    let dir = copy_fixture("inherit-workspace");
+    let before = effective(dir.path());
+    suggest(dir.path(), true);
+    let after = effective(dir.path());
+
+    // Every manifest still parses, and declares what it did before
+    assert_eq!(
+        before.keys().collect::<Vec<_>>(),
+        after.keys().collect::<Vec<_>>()
+    );
+    for (key, was) in &before {
+        let now = &after[key];
+        assert_eq!(
+            (&was.features, was.default_features, was.optional),
+            (&now.features, now.default_features, now.optional),
+            "{:?}",
+            key
+        );
+        // A requirement only tightens to one the old one accepted
+        if was.requirement != now.requirement {
+            assert!(
+                VersionReq::parse(&was.requirement)
+                    .unwrap()
+                    .matches(&lowest(&now.requirement)),
+                "{:?}",
+                key
+            );
+        }
+    }
+
+    let root = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    assert!(
+        root.ends_with(
+            "[workspace.dependencies]\n\
+             log = { version = \"0.4.20\" }\n\
+             regex = { version = \"1.10\" }\n\
+             serde = { version = \"1.0.200\" }\n\
+             tokio = { version = \"1.38\", default-features = false, features = [\"rt\"] }\n"
+        ),
+        "{}",
+        root
+    );
+    assert!(root.contains("serde = { workspace = true, features = [\"derive\"] }\n"));
+    let api = fs::read_to_string(dir.path().join("crates/api/Cargo.toml")).unwrap();
+    assert!(
+        api.contains("[dependencies.regex]\nworkspace = true\noptional = true\n"),
+        "{}",
+        api
+    );
+    let cli = fs::read_to_string(dir.path().join("crates/cli/Cargo.toml")).unwrap();
+    assert!(cli.contains("tokio = { workspace = true, features = [\"macros\"] }\n"));
+    // Requirements with no version in common stay as they were
+    assert!(cli.contains("rand = \"0.7\"\n"));
+    assert!(dir.path().join("crates/cli/Cargo.toml.backup").exists());
+}
+
+#[test]
+fn test_members_resolve_what_they_inherit() {
+    let dir = copy_fixture("inherit-workspace");
+    suggest(dir.path(), true);
+    let expected = effective(dir.path());
+
+    let workspace = Workspace::load(Manifest::from_path(&dir.path().join("Cargo.toml")).unwrap())
+        .unwrap()
+        .unwrap();
+    let mut inherited = 0;
+    for manifest in &workspace.members {
+        for (section, name, spec) in manifest.declarations() {
+            let Some(resolved) = manifest.resolve_inherited(&name, &spec) else {
+                assert!(!spec.inherits_workspace(), "{}", name);
+                continue;
+            };
+            inherited += 1;
+            let key = (
+                Workspace::member_name(manifest),
+                section.to_string(),
+                name.clone(),
+            );
+            let want = &expected[&key];
+            assert_eq!(
+                resolved.version(),
+                Some(want.requirement.as_str()),
+                "{:?}",
+                key
+            );
+            assert_eq!(
+                resolved.features().iter().cloned().collect::<BTreeSet<_>>(),
+                want.features,
+                "{:?}",
+                key
+            );
+            assert_eq!(
+                resolved.default_features(),
+                want.default_features,
+                "{:?}",
+                key
+            );
+            assert_eq!(resolved.is_optional(), want.optional, "{:?}", key);
+        }
+    }
+    assert!(inherited > 0);
+}